@@ -2210,6 +2210,18 @@ pub async fn export_to_format<R: Runtime>(
                 continue;
             }
         };
+        // Leave out blocks and properties for other audiences
+        let content = match diaryx_core::redaction::redact(&content, Some(aud)) {
+            Ok(redacted) => redacted.content,
+            Err(e) => {
+                log::warn!(
+                    "[ExportFormat] Failed to redact {:?}: {}",
+                    included.source_path,
+                    e
+                );
+                continue;
+            }
+        };
 
         let relative_str = included.relative_path.to_string_lossy().to_string();
 
@@ -2850,6 +2862,21 @@ pub async fn start_websocket_sync<R: Runtime>(
             log::error!("[start_websocket_sync] Sync error: {}", message);
             let _ = app_handle.emit("sync-error", message);
         }
+        SyncEvent::PendingChanged { count } => {
+            log::debug!("[start_websocket_sync] Pending updates: {}", count);
+            let _ = app_handle.emit("sync-pending-changed", count);
+        }
+        SyncEvent::PeersChanged { peers } => {
+            log::debug!("[start_websocket_sync] Peers online: {}", peers.len());
+            let _ = app_handle.emit("sync-peers-changed", peers);
+        }
+        SyncEvent::ServerError(error) => {
+            log::warn!(
+                "[start_websocket_sync] Server refused update: {}",
+                error.message
+            );
+            let _ = app_handle.emit("sync-server-error", error);
+        }
         _ => {}
     }));
 
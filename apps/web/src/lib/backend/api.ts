@@ -18,6 +18,8 @@ import type {
   ExportPlan,
   ExportedFile,
   BinaryFileInfo,
+  BinaryExportFile,
+  ImportResult,
   TemplateInfo,
   StorageInfo,
   CreateEntryOptions,
@@ -423,6 +425,57 @@ export function createApi(backend: Backend) {
       return expectResponse(response, 'BinaryFilePaths').data;
     },
 
+    /**
+     * Export for another journaling app ('dayone', 'obsidian', 'logseq') or
+     * as a full JSON dump ('json'), returning the files to write.
+     */
+    async exportToFormat(
+      rootPath: string,
+      audience: string,
+      format: string,
+    ): Promise<BinaryExportFile[]> {
+      const response = await backend.execute({
+        type: 'ExportToFormat',
+        params: { root_path: rootPath, audience, format },
+      });
+      return expectResponse(response, 'BinaryFiles').data;
+    },
+
+    /** Export as an EPUB book (no pandoc needed), returning the archive's bytes. */
+    async exportToEpub(
+      rootPath: string,
+      audience?: string,
+      title?: string,
+    ): Promise<number[]> {
+      const response = await backend.execute({
+        type: 'ExportToEpub',
+        params: { root_path: rootPath, audience: audience ?? null, title: title ?? null },
+      });
+      return expectResponse(response, 'Bytes').data;
+    },
+
+    /**
+     * Import another journaling app's export ('dayone', 'journey', 'notion',
+     * 'text' or 'enex') into the workspace of a root index.
+     */
+    async importEntries(
+      workspacePath: string,
+      format: string,
+      files: BinaryExportFile[],
+      dailyEntryFolder?: string,
+    ): Promise<ImportResult> {
+      const response = await backend.execute({
+        type: 'ImportEntries',
+        params: {
+          workspace_path: workspacePath,
+          daily_entry_folder: dailyEntryFolder ?? null,
+          format,
+          files,
+        },
+      });
+      return expectResponse(response, 'ImportResult').data;
+    },
+
     // =========================================================================
     // Templates
     // =========================================================================
@@ -1,7 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BinaryExportFile } from "./BinaryExportFile";
 import type { CreateEntryOptions } from "./CreateEntryOptions";
 import type { JsonValue } from "../serde_json/JsonValue";
 import type { SearchOptions } from "./SearchOptions";
+import type { ShareScope } from "./ShareScope";
 import type { ValidationResult } from "./ValidationResult";
 
 /**
@@ -292,7 +294,47 @@ root_path: string,
 /**
  * Target audience.
  */
-audience: string, } } | { "type": "ListTemplates", "params": {
+audience: string, } } | { "type": "ExportToFormat", "params": {
+/**
+ * Root path.
+ */
+root_path: string,
+/**
+ * Target audience.
+ */
+audience: string,
+/**
+ * Format name.
+ */
+format: string, } } | { "type": "ExportToEpub", "params": {
+/**
+ * Root path.
+ */
+root_path: string,
+/**
+ * Target audience (every page if not set).
+ */
+audience: string | null,
+/**
+ * Book title (defaults to the root's title).
+ */
+title: string | null, } } | { "type": "ImportEntries", "params": {
+/**
+ * Workspace path (the workspace root index file).
+ */
+workspace_path: string,
+/**
+ * Optional subfolder for daily entries (e.g., "Daily" or "Journal/Daily").
+ */
+daily_entry_folder: string | null,
+/**
+ * Format name.
+ */
+format: string,
+/**
+ * Files of the export, by their path in it.
+ */
+files: Array<BinaryExportFile>, } } | { "type": "ListTemplates", "params": {
 /**
  * Optional workspace path.
  */
@@ -597,7 +639,11 @@ guest_join_code: string | null,
 /**
  * Whether the guest uses OPFS (requires path prefixing).
  */
-uses_opfs: boolean, } } | { "type": "ApplyRemoteWorkspaceUpdateWithEffects", "params": {
+uses_opfs: boolean,
+/**
+ * Paths shared with the guest, if the session is scoped.
+ */
+guest_scope: ShareScope | null, } } | { "type": "ApplyRemoteWorkspaceUpdateWithEffects", "params": {
 /**
  * Binary update data.
  */
@@ -1,4 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Redaction } from "./Redaction";
 
 /**
  * A file to be exported
@@ -19,4 +20,8 @@ dest_path: string,
 /**
  * Contents entries that will be filtered out (if any)
  */
-filtered_contents: Array<string>, };
+filtered_contents: Array<string>, 
+/**
+ * Parts of the file that will be left out for the audience
+ */
+redactions: Array<Redaction>, };
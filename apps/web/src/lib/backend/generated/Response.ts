@@ -13,6 +13,7 @@ import type { FileMetadata } from "./FileMetadata";
 import type { FixResult } from "./FixResult";
 import type { FixSummary } from "./FixSummary";
 import type { HistoryEntry } from "./HistoryEntry";
+import type { ImportResult } from "./ImportResult";
 import type { JsonValue } from "../serde_json/JsonValue";
 import type { LinkFormat } from "./LinkFormat";
 import type { SearchResults } from "./SearchResults";
@@ -25,7 +26,7 @@ import type { WorkspaceConfig } from "./WorkspaceConfig";
 /**
  * Response from a command execution.
  */
-export type Response = { "type": "Ok" } | { "type": "String", "data": string } | { "type": "Bool", "data": boolean } | { "type": "Entry", "data": EntryData } | { "type": "Tree", "data": TreeNode } | { "type": "Frontmatter", "data": { [key in string]?: JsonValue } } | { "type": "SearchResults", "data": SearchResults } | { "type": "ValidationResult", "data": ValidationResultWithMeta } | { "type": "FixResult", "data": FixResult } | { "type": "FixSummary", "data": FixSummary } | { "type": "ExportPlan", "data": ExportPlan } | { "type": "ExportedFiles", "data": Array<ExportedFile> } | { "type": "BinaryFiles", "data": Array<BinaryExportFile> } | { "type": "BinaryFilePaths", "data": Array<BinaryFileInfo> } | { "type": "Templates", "data": Array<TemplateInfo> } | { "type": "Strings", "data": Array<string> } | { "type": "Bytes", "data": Array<number> } | { "type": "StorageInfo", "data": StorageInfo } | { "type": "AncestorAttachments", "data": AncestorAttachmentsResult } | { "type": "LinkFormat", "data": LinkFormat } | { "type": "WorkspaceConfig", "data": WorkspaceConfig } | { "type": "ConvertLinksResult", "data": ConvertLinksResult } | { "type": "CreateChildResult", "data": CreateChildResult } | { "type": "Binary", "data": Array<number> } | { "type": "CrdtFile", "data": FileMetadata | null } | { "type": "CrdtFiles", "data": Array<[string, FileMetadata]> } | { "type": "CrdtHistory", "data": Array<CrdtHistoryEntry> } | { "type": "UpdateId", "data": bigint | null } | { "type": "VersionDiff", "data": Array<FileDiff> } | { "type": "HistoryEntries", "data": Array<HistoryEntry> } | { "type": "WorkspaceSyncResult", "data": {
+export type Response = { "type": "Ok" } | { "type": "String", "data": string } | { "type": "Bool", "data": boolean } | { "type": "Entry", "data": EntryData } | { "type": "Tree", "data": TreeNode } | { "type": "Frontmatter", "data": { [key in string]?: JsonValue } } | { "type": "SearchResults", "data": SearchResults } | { "type": "ValidationResult", "data": ValidationResultWithMeta } | { "type": "FixResult", "data": FixResult } | { "type": "FixSummary", "data": FixSummary } | { "type": "ExportPlan", "data": ExportPlan } | { "type": "ExportedFiles", "data": Array<ExportedFile> } | { "type": "BinaryFiles", "data": Array<BinaryExportFile> } | { "type": "BinaryFilePaths", "data": Array<BinaryFileInfo> } | { "type": "ImportResult", "data": ImportResult } | { "type": "Templates", "data": Array<TemplateInfo> } | { "type": "Strings", "data": Array<string> } | { "type": "Bytes", "data": Array<number> } | { "type": "StorageInfo", "data": StorageInfo } | { "type": "AncestorAttachments", "data": AncestorAttachmentsResult } | { "type": "LinkFormat", "data": LinkFormat } | { "type": "WorkspaceConfig", "data": WorkspaceConfig } | { "type": "ConvertLinksResult", "data": ConvertLinksResult } | { "type": "CreateChildResult", "data": CreateChildResult } | { "type": "Binary", "data": Array<number> } | { "type": "CrdtFile", "data": FileMetadata | null } | { "type": "CrdtFiles", "data": Array<[string, FileMetadata]> } | { "type": "CrdtHistory", "data": Array<CrdtHistoryEntry> } | { "type": "UpdateId", "data": bigint | null } | { "type": "VersionDiff", "data": Array<FileDiff> } | { "type": "HistoryEntries", "data": Array<HistoryEntry> } | { "type": "WorkspaceSyncResult", "data": {
 /**
  * Optional response bytes to send back.
  */
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SharePermission } from "./SharePermission";
+
+/**
+ * One shared path in a [`ShareScope`].
+ */
+export type ScopeEntry = {
+/**
+ * Workspace-relative path of the shared file or index.
+ */
+path: string,
+/**
+ * What the guest may do with it.
+ */
+permission: SharePermission,
+/**
+ * Also share everything under this index (its `contents`, recursively).
+ */
+include_contents: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Access level granted to a guest for a path.
+ */
+export type SharePermission = "read" | "write";
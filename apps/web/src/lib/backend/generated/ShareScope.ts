@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ScopeEntry } from "./ScopeEntry";
+
+/**
+ * The set of paths a share session exposes.
+ */
+export type ShareScope = {
+/**
+ * Shared files and subtrees.
+ */
+entries: Array<ScopeEntry>, };
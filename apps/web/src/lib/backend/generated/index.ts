@@ -33,8 +33,10 @@ export type { ExportPlan } from './ExportPlan';
 export type { ExportFile } from './ExportFile';
 export type { ExcludedFile } from './ExcludedFile';
 export type { ExclusionReason } from './ExclusionReason';
+export type { Redaction } from './Redaction';
 export type { ExportedFile } from './ExportedFile';
 export type { BinaryExportFile } from './BinaryExportFile';
+export type { ImportResult } from './ImportResult';
 export type { BinaryFileInfo } from './BinaryFileInfo';
 
 // Template types
@@ -58,6 +60,9 @@ export type { FileMetadata } from './FileMetadata';
 // Sync types
 export type { FileSystemEvent } from './FileSystemEvent';
 export type { GuestConfig } from './GuestConfig';
+export type { ShareScope } from './ShareScope';
+export type { ScopeEntry } from './ScopeEntry';
+export type { SharePermission } from './SharePermission';
 
 // Link format and workspace config types
 export type { LinkFormat } from './LinkFormat';
@@ -15,6 +15,7 @@
  */
 
 import type { Backend, FileSystemEvent } from '../backend/interface';
+import type { ShareScope } from '../backend/generated';
 
 /**
  * Configure the sync handler for guest mode.
@@ -25,11 +26,13 @@ import type { Backend, FileSystemEvent } from '../backend/interface';
  * @param backend - The backend instance
  * @param joinCode - The session join code, or null to disable guest mode
  * @param usesOpfs - Whether the guest uses OPFS (requires path prefixing)
+ * @param scope - Paths shared with the guest, or null for the whole workspace
  */
 export async function configureSyncHandler(
   backend: Backend,
   joinCode: string | null,
-  usesOpfs: boolean
+  usesOpfs: boolean,
+  scope: ShareScope | null = null
 ): Promise<void> {
   // Use type assertion since bindings may not be regenerated yet
   await backend.execute({
@@ -37,6 +40,7 @@ export async function configureSyncHandler(
     params: {
       guest_join_code: joinCode,
       uses_opfs: usesOpfs,
+      guest_scope: scope,
     },
   } as any);
 }
@@ -162,8 +162,9 @@ pub enum Commands {
         /// Destination directory for the export
         destination: PathBuf,
 
-        /// Output format (markdown, html, docx, epub, pdf, latex, odt, rst).
-        /// Formats other than markdown and html require pandoc to be installed.
+        /// Output format (markdown, html, docx, epub, pdf, latex, odt, rst),
+        /// or an app format: dayone, obsidian, logseq, or json (full dump).
+        /// docx, epub, pdf, latex, odt and rst require pandoc to be installed.
         #[arg(short = 'F', long, default_value = "markdown")]
         format: String,
 
@@ -175,6 +176,11 @@ pub enum Commands {
         #[arg(long)]
         keep_audience: bool,
 
+        /// Math rendering when converting: katex, mathml (needs pandoc) or none.
+        /// Mermaid and Typst diagrams are rendered when mmdc/typst are installed.
+        #[arg(long, default_value = "katex")]
+        math: String,
+
         /// Show detailed information about what's being exported/excluded
         #[arg(short, long)]
         verbose: bool,
@@ -184,6 +190,76 @@ pub enum Commands {
         dry_run: bool,
     },
 
+    /// Compile an index and everything under it into one markdown document,
+    /// in `contents` reading order (e.g. to convert with pandoc or publish)
+    Compile {
+        /// Markdown file to write
+        output: PathBuf,
+
+        /// Index to compile (defaults to the workspace root)
+        #[arg(short, long)]
+        index: Option<PathBuf>,
+
+        /// Only include entries visible to this audience ("*" for every
+        /// non-private entry)
+        #[arg(short, long)]
+        audience: Option<String>,
+
+        /// Document title (defaults to the index's title)
+        #[arg(short, long)]
+        title: Option<String>,
+
+        /// Start each entry with its title as a heading
+        #[arg(long)]
+        title_headings: bool,
+
+        /// Shift each entry's headings down by its depth in the tree
+        #[arg(long)]
+        shift_headings: bool,
+
+        /// Put the titles of entries with contents on pages of their own
+        #[arg(long)]
+        title_pages: bool,
+
+        /// Separator between sibling entries (e.g. "* * *"). Repeat for deeper
+        /// levels: the first is used between the index's children, the next
+        /// between their children, and so on.
+        #[arg(long = "separator", value_name = "TEXT")]
+        separators: Vec<String>,
+
+        /// Markdown file to place before the entries (repeatable)
+        #[arg(long = "front-matter", value_name = "FILE")]
+        front_matter: Vec<PathBuf>,
+
+        /// Markdown file to place after the entries (repeatable)
+        #[arg(long = "back-matter", value_name = "FILE")]
+        back_matter: Vec<PathBuf>,
+
+        /// Overwrite an existing output file
+        #[arg(short, long)]
+        force: bool,
+
+        /// Show which entries would be compiled without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Import another journaling app's export into the workspace.
+    /// Dated entries become daily entries; same-day entries are appended.
+    Import {
+        /// Export folder, or a single exported file (e.g. an .enex file)
+        source: PathBuf,
+
+        /// Export format: dayone, journey, notion, text (YYYY-MM-DD.txt files)
+        /// or enex (Evernote)
+        #[arg(short = 'F', long)]
+        format: String,
+
+        /// Show which entries would be imported without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+
     /// Manipulate file content (body text after frontmatter)
     #[command(alias = "c")]
     Content {
@@ -232,7 +308,8 @@ pub enum Commands {
     /// Publish workspace as HTML for sharing
     #[command(alias = "pub")]
     Publish {
-        /// Destination path (directory for multi-file, file for single-file)
+        /// Destination path (directory for multi-file, file for single-file
+        /// and epub)
         destination: PathBuf,
 
         /// Target audience to publish for (filters files by audience property)
@@ -240,7 +317,8 @@ pub enum Commands {
         audience: Option<String>,
 
         /// Output format (html, docx, epub, pdf, latex, odt, rst).
-        /// Non-HTML formats require pandoc to be installed.
+        /// epub writes one book to the destination file; other non-HTML
+        /// formats require pandoc to be installed.
         #[arg(short = 'F', long, default_value = "html")]
         format: String,
 
@@ -252,7 +330,32 @@ pub enum Commands {
         #[arg(short, long)]
         title: Option<String>,
 
-        /// Overwrite existing destination
+        /// Theme: a built-in theme (default, minimal) or a theme folder in the
+        /// workspace. Defaults to the workspace's _theme/ folder if present.
+        #[arg(long)]
+        theme: Option<String>,
+
+        /// Public URL of the site, for absolute links in feeds and the sitemap
+        /// (both are skipped without it)
+        #[arg(long)]
+        base_url: Option<String>,
+
+        /// Show embedded images wider than this many pixels as a scaled-down
+        /// copy linking to the original
+        #[arg(long, value_name = "PX")]
+        image_max_width: Option<u32>,
+
+        /// Math rendering: katex, mathml or none. Mermaid and Typst diagrams
+        /// are rendered when mmdc/typst are installed.
+        #[arg(long, default_value = "katex")]
+        math: String,
+
+        /// Keep running and publish again whenever the workspace changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Overwrite existing destination, re-rendering every page
+        /// (a destination published to before is updated without it)
         #[arg(short, long)]
         force: bool,
 
@@ -905,14 +1008,23 @@ pub enum AttachmentCommands {
 
 #[derive(Subcommand)]
 pub enum SyncCommands {
-    /// Authenticate via magic link flow
+    /// Authenticate via magic link flow, or with --device by approving from a signed-in device
     Login {
         /// Email address to authenticate with
-        email: String,
+        #[arg(required_unless_present = "device")]
+        email: Option<String>,
 
         /// Sync server URL (default: https://sync.diaryx.org)
         #[arg(short, long)]
         server: Option<String>,
+
+        /// Log in without email: show a code to approve from a signed-in device
+        #[arg(long, conflicts_with = "email")]
+        device: bool,
+
+        /// Device name for this login session (with --device)
+        #[arg(long, requires = "device")]
+        device_name: Option<String>,
     },
 
     /// Complete login by verifying magic link token
@@ -925,6 +1037,16 @@ pub enum SyncCommands {
         device_name: Option<String>,
     },
 
+    /// Approve (or deny) a `sync login --device` request from this signed-in device
+    Approve {
+        /// Code shown by the device that is logging in
+        code: String,
+
+        /// Deny the request instead of approving it
+        #[arg(long)]
+        deny: bool,
+    },
+
     /// Clear stored credentials
     Logout,
 
@@ -966,4 +1088,145 @@ pub enum SyncCommands {
         #[arg(long)]
         show: bool,
     },
+
+    /// Write local CRDT changes to an update bundle (for syncing via a shared folder)
+    ExportUpdates {
+        /// Directory (or .dxsync file) to write the bundle to
+        path: PathBuf,
+
+        /// Export full state instead of only what the other devices using this
+        /// path are missing
+        #[arg(long)]
+        full: bool,
+
+        /// Name recorded in the bundle (default: hostname)
+        #[arg(long)]
+        device_name: Option<String>,
+    },
+
+    /// Merge update bundles from a directory or .dxsync file
+    ImportUpdates {
+        /// Directory of bundles, or a single .dxsync file
+        path: PathBuf,
+
+        /// Name this device exports bundles under (default: hostname); its
+        /// own bundles don't count as the other devices' state
+        #[arg(long)]
+        device_name: Option<String>,
+    },
+
+    /// Sync directly with another device on the local network (no server)
+    Peer {
+        #[command(subcommand)]
+        command: PeerCommands,
+    },
+
+    /// Manage who can access the synced workspace
+    Members {
+        #[command(subcommand)]
+        command: MembersCommands,
+    },
+
+    /// Manage personal access tokens for scripts and automation
+    Token {
+        #[command(subcommand)]
+        command: TokenCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TokenCommands {
+    /// Create a token (shown once)
+    Create {
+        /// Name to identify the token
+        name: String,
+
+        /// Limit the token to one workspace ID (default: all workspaces)
+        #[arg(long)]
+        workspace: Option<String>,
+
+        /// What the token may do: read, write, or snapshot (download/upload snapshots only)
+        #[arg(short, long, default_value = "read", value_parser = ["read", "write", "snapshot"])]
+        permission: String,
+
+        /// Expire the token after this many days (default: never)
+        #[arg(long)]
+        expires_days: Option<i64>,
+    },
+
+    /// List tokens
+    List,
+
+    /// Revoke a token
+    Revoke {
+        /// Token ID (from `sync token list`)
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MembersCommands {
+    /// List members and pending invitations
+    List,
+
+    /// Invite someone to the workspace by email (owner only)
+    Invite {
+        /// Email address to send the invitation to
+        email: String,
+
+        /// Role to grant: editor or viewer
+        #[arg(short, long, default_value = "editor", value_parser = ["editor", "viewer"])]
+        role: String,
+    },
+
+    /// Remove a member or revoke a pending invitation
+    Remove {
+        /// User ID or email address
+        member: String,
+    },
+
+    /// Accept an invitation
+    Accept {
+        /// Invitation token from the invite email (the part after ?token=)
+        token: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PeerCommands {
+    /// Wait for a peer to connect, showing a one-time pairing code
+    Serve {
+        /// TCP port to listen on
+        #[arg(short, long, default_value_t = diaryx_core::crdt::DEFAULT_PEER_PORT)]
+        port: u16,
+
+        /// Name shown to the other device (default: hostname)
+        #[arg(long)]
+        device_name: Option<String>,
+
+        /// Don't advertise this device via mDNS
+        #[arg(long)]
+        no_advertise: bool,
+    },
+
+    /// Connect to a peer and sync once
+    Connect {
+        /// Pairing code shown by the serving device
+        code: String,
+
+        /// Peer address (host:port). Discovered via mDNS if omitted.
+        #[arg(short, long)]
+        addr: Option<String>,
+
+        /// Name shown to the other device (default: hostname)
+        #[arg(long)]
+        device_name: Option<String>,
+    },
+
+    /// List peers advertising on the local network
+    Discover {
+        /// Seconds to listen for announcements
+        #[arg(short, long, default_value = "3")]
+        timeout: u64,
+    },
 }
@@ -1,19 +1,26 @@
 //! CLI handler for export command
 
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 
-use diaryx_core::export::{ExportOptions, ExportPlan, Exporter};
+use diaryx_core::export::{CompileOptions, ExportOptions, ExportPlan, Exporter};
+use diaryx_core::export_formats::ExportFormat;
 use diaryx_core::fs::{RealFileSystem, SyncToAsyncFs};
 use diaryx_core::pandoc;
+use diaryx_core::path_utils::is_contained;
+use diaryx_core::render::{DIAGRAMS_DIR, MathStyle, RenderPipeline};
 use diaryx_core::workspace::Workspace;
 use std::path::Path;
 
+use super::publish::render_pipeline;
+
 /// Helper to run async operations in sync context
 fn block_on<F: std::future::Future>(f: F) -> F::Output {
     futures_lite::future::block_on(f)
 }
 
 /// Handle the export command
+#[allow(clippy::too_many_arguments)]
 pub fn handle_export(
     workspace_root: PathBuf,
     audience: &str,
@@ -21,19 +28,31 @@ pub fn handle_export(
     format: &str,
     force: bool,
     keep_audience: bool,
+    math: &str,
     verbose: bool,
     dry_run: bool,
 ) {
     // Validate format
-    if !pandoc::is_supported_format(format) {
+    let app_format = ExportFormat::from_name(format);
+    if !pandoc::is_supported_format(format) && app_format.is_none() {
+        let app_formats: Vec<&str> = ExportFormat::ALL.iter().map(|f| f.name()).collect();
         eprintln!(
-            "✗ Unsupported format: '{}'. Supported: {}",
+            "✗ Unsupported format: '{}'. Supported: {}, {}",
             format,
-            pandoc::SUPPORTED_FORMATS.join(", ")
+            pandoc::SUPPORTED_FORMATS.join(", "),
+            app_formats.join(", ")
         );
         return;
     }
 
+    let renderers = match render_pipeline(math) {
+        Ok(renderers) => renderers,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            return;
+        }
+    };
+
     // Check pandoc availability for formats that need it
     if pandoc::requires_pandoc(format) && !pandoc::is_pandoc_available() {
         pandoc::print_install_instructions();
@@ -91,6 +110,11 @@ pub fn handle_export(
         return;
     }
 
+    if let Some(app_format) = app_format {
+        export_app_format(&exporter, &plan, app_format, destination, force);
+        return;
+    }
+
     // Execute the export (writes markdown files to destination)
     let options = ExportOptions {
         force,
@@ -117,12 +141,32 @@ pub fn handle_export(
         let mut converted = 0;
         let mut failed = 0;
 
+        // HTML gets math in the chosen style (other formats typeset it natively)
+        let math_args: &[&str] = match (format, renderers.math()) {
+            ("html", Some(MathStyle::Katex)) => &["--katex"],
+            ("html", Some(MathStyle::MathMl)) => &["--mathml"],
+            _ => &[],
+        };
+        let mut diagram_dirs = BTreeSet::new();
+
         // Walk destination and convert each .md file
         for entry in walkdir(destination) {
             let md_path = entry;
             let out_path = md_path.with_extension(ext);
 
-            match pandoc::convert_file(&md_path, &out_path, "markdown", format, true) {
+            match render_diagrams(&renderers, &md_path) {
+                Ok(Some(dir)) => {
+                    diagram_dirs.insert(dir);
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!(
+                    "  ⚠ Failed to render diagrams in {}: {}",
+                    md_path.display(),
+                    e
+                ),
+            }
+
+            match pandoc::convert_file(&md_path, &out_path, "markdown", format, true, math_args) {
                 Ok(()) => {
                     // Remove the original .md file
                     let _ = std::fs::remove_file(&md_path);
@@ -138,6 +182,13 @@ pub fn handle_export(
             }
         }
 
+        // Diagrams are embedded in every format but HTML
+        if format != "html" {
+            for dir in diagram_dirs {
+                let _ = std::fs::remove_dir_all(dir);
+            }
+        }
+
         if failed == 0 {
             println!("✓ Converted {} files to {}", converted, format);
         } else {
@@ -148,6 +199,27 @@ pub fn handle_export(
     println!("  Exported to: {}", destination.display());
 }
 
+/// Replace diagram code blocks in an exported file with rendered images for
+/// pandoc. Returns the folder the images were written to, if any.
+fn render_diagrams(renderers: &RenderPipeline, md_path: &Path) -> std::io::Result<Option<PathBuf>> {
+    let content = std::fs::read_to_string(md_path)?;
+    let (rendered, diagrams) = renderers.render_markdown(&content);
+    if diagrams.is_empty() {
+        return Ok(None);
+    }
+
+    let dir = md_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(DIAGRAMS_DIR);
+    std::fs::create_dir_all(&dir)?;
+    for diagram in &diagrams {
+        std::fs::write(dir.join(&diagram.file_name), &diagram.svg)?;
+    }
+    std::fs::write(md_path, rendered)?;
+    Ok(Some(dir))
+}
+
 /// Print detailed information about the export plan
 fn print_verbose_plan(plan: &ExportPlan) {
     println!("Included files:");
@@ -159,6 +231,9 @@ fn print_verbose_plan(plan: &ExportPlan) {
                 file.filtered_contents.join(", ")
             );
         }
+        for redaction in &file.redactions {
+            println!("    (redacted: {})", redaction);
+        }
     }
     println!();
 
@@ -192,6 +267,128 @@ fn walkdir(dir: &Path) -> Vec<PathBuf> {
     results
 }
 
+/// Write the files of an export for another app (Day One, Obsidian, ...)
+fn export_app_format(
+    exporter: &Exporter<SyncToAsyncFs<RealFileSystem>>,
+    plan: &ExportPlan,
+    format: ExportFormat,
+    destination: &Path,
+    force: bool,
+) {
+    if destination.exists() && !force {
+        eprintln!(
+            "✗ Destination '{}' already exists (use --force to overwrite)",
+            destination.display()
+        );
+        return;
+    }
+
+    let files = match block_on(exporter.export_to_format(plan, format)) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("✗ Export failed: {}", e);
+            return;
+        }
+    };
+    for file in &files {
+        if !is_contained(&file.path) {
+            eprintln!("⚠ Skipping {}: outside the destination", file.path);
+            continue;
+        }
+        let path = destination.join(&file.path);
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, &file.data));
+        if let Err(e) = written {
+            eprintln!("✗ Failed to write {}: {}", path.display(), e);
+            return;
+        }
+    }
+    println!(
+        "✓ Exported {} files for {} to {}",
+        files.len(),
+        format.name(),
+        destination.display()
+    );
+}
+
+/// Handle the compile command
+pub fn handle_compile(
+    workspace_root: PathBuf,
+    index: Option<PathBuf>,
+    output: &Path,
+    options: &CompileOptions,
+    force: bool,
+    dry_run: bool,
+) {
+    if output.exists() && !force && !dry_run {
+        eprintln!(
+            "✗ Output file '{}' already exists (use --force to overwrite)",
+            output.display()
+        );
+        return;
+    }
+    let index = match index {
+        Some(index) => std::path::absolute(&index).unwrap_or(index),
+        None => workspace_root.clone(),
+    };
+
+    let exporter = Exporter::new(SyncToAsyncFs::new(RealFileSystem));
+    let compiled = match block_on(exporter.compile(&workspace_root, &index, options)) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            eprintln!("✗ Compile failed: {}", e);
+            return;
+        }
+    };
+
+    if dry_run {
+        println!("Compile Plan");
+        println!("============");
+        for entry in &compiled.entries {
+            println!("  {}", entry.display());
+        }
+        for excluded in &compiled.excluded {
+            println!("  ✗ {} ({})", excluded.path.display(), excluded.reason);
+        }
+        println!();
+        println!("(dry run - no changes made)");
+        return;
+    }
+
+    if let Err(e) = std::fs::write(output, &compiled.markdown) {
+        eprintln!("✗ Failed to write {}: {}", output.display(), e);
+        return;
+    }
+    println!(
+        "✓ Compiled {} entr{} into {}",
+        compiled.entries.len(),
+        if compiled.entries.len() == 1 {
+            "y"
+        } else {
+            "ies"
+        },
+        output.display()
+    );
+    if !compiled.excluded.is_empty() {
+        println!("  ({} excluded for the audience)", compiled.excluded.len());
+    }
+
+    // Relative links and images point into the index's folder
+    let index_dir = index.parent().unwrap_or(Path::new("."));
+    let output_dir = std::path::absolute(output)
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf));
+    if output_dir.as_deref() != Some(index_dir) {
+        println!(
+            "  Relative links point into {} (e.g. pandoc --resource-path {})",
+            index_dir.display(),
+            index_dir.display()
+        );
+    }
+}
+
 /// Resolve the workspace root for export
 pub fn resolve_workspace_for_export(
     workspace_override: Option<PathBuf>,
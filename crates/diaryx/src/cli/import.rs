@@ -0,0 +1,93 @@
+//! CLI handler for import command
+
+use std::path::{Path, PathBuf};
+
+use diaryx_core::config::Config;
+use diaryx_core::fs::{RealFileSystem, SyncToAsyncFs};
+use diaryx_core::import::{ImportFiles, ImportFormat, Importer, read_import_files};
+
+/// Helper to run async operations in sync context
+fn block_on<F: std::future::Future>(f: F) -> F::Output {
+    futures_lite::future::block_on(f)
+}
+
+/// Handle the import command
+pub fn handle_import(workspace_root: PathBuf, source: &Path, format: &str, dry_run: bool) {
+    let Some(format) = ImportFormat::from_name(format) else {
+        let formats: Vec<&str> = ImportFormat::ALL.iter().map(|f| f.name()).collect();
+        eprintln!(
+            "✗ Unsupported import format: '{}'. Supported: {}",
+            format,
+            formats.join(", ")
+        );
+        return;
+    };
+
+    let files = match read_source(source) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("✗ Cannot read '{}': {}", source.display(), e);
+            return;
+        }
+    };
+
+    // Daily entries go where `diaryx today` puts them, in this workspace
+    let mut config = Config::load().unwrap_or_default();
+    if let Some(dir) = workspace_root.parent() {
+        config.default_workspace = dir.to_path_buf();
+    }
+
+    if dry_run {
+        let entries = match format.importer().parse(&files) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("✗ Import failed: {}", e);
+                return;
+            }
+        };
+        println!("Import Plan");
+        println!("===========");
+        for entry in &entries {
+            let date = entry
+                .date
+                .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "undated".to_string());
+            let title = entry.title.as_deref().unwrap_or("");
+            println!("  {}  {}  ({})", date, title, entry.source);
+        }
+        println!();
+        println!("{} entries", entries.len());
+        println!("(dry run - no changes made)");
+        return;
+    }
+
+    let importer = Importer::new(SyncToAsyncFs::new(RealFileSystem));
+    match block_on(importer.import_files(&workspace_root, &config, format, &files)) {
+        Ok(result) => {
+            for path in &result.created {
+                println!("  + {}", path.display());
+            }
+            for path in &result.appended {
+                println!("  ~ {}", path.display());
+            }
+            println!("✓ {}", result);
+        }
+        Err(e) => eprintln!("✗ Import failed: {}", e),
+    }
+}
+
+/// Files of an export folder, or a single exported file
+fn read_source(source: &Path) -> Result<ImportFiles, String> {
+    if source.is_dir() {
+        let fs = SyncToAsyncFs::new(RealFileSystem);
+        return block_on(read_import_files(&fs, source)).map_err(|e| e.to_string());
+    }
+    let data = std::fs::read(source).map_err(|e| e.to_string())?;
+    let name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| "not a file".to_string())?;
+    let mut files = ImportFiles::new();
+    files.insert(name, data);
+    Ok(files)
+}
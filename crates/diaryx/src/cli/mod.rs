@@ -18,6 +18,9 @@ mod entry;
 /// `diaryx_core` export with audience filtering
 mod export;
 
+/// Import from other journaling apps
+mod import;
+
 /// normalize command changes filenames to slug
 mod normalize;
 
@@ -53,6 +56,7 @@ use std::path::PathBuf;
 
 use diaryx_core::config::Config;
 use diaryx_core::entry::{DiaryxApp, DiaryxAppSync};
+use diaryx_core::export::CompileOptions;
 use diaryx_core::fs::{RealFileSystem, SyncToAsyncFs};
 use diaryx_core::workspace::Workspace;
 
@@ -148,6 +152,7 @@ pub fn run_cli() {
             format,
             force,
             keep_audience,
+            math,
             verbose,
             dry_run,
         } => {
@@ -165,6 +170,7 @@ pub fn run_cli() {
                 &format,
                 force,
                 keep_audience,
+                &math,
                 verbose,
                 dry_run,
             );
@@ -200,12 +206,68 @@ pub fn run_cli() {
             true
         }
 
+        Commands::Compile {
+            output,
+            index,
+            audience,
+            title,
+            title_headings,
+            shift_headings,
+            title_pages,
+            separators,
+            front_matter,
+            back_matter,
+            force,
+            dry_run,
+        } => {
+            let workspace_root = match export::resolve_workspace_for_export(cli.workspace) {
+                Ok(root) => root,
+                Err(e) => {
+                    eprintln!("✗ {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let options = CompileOptions {
+                audience,
+                title,
+                title_headings,
+                shift_headings,
+                title_pages,
+                separators,
+                front_matter,
+                back_matter,
+            };
+            export::handle_compile(workspace_root, index, &output, &options, force, dry_run);
+            true
+        }
+
+        Commands::Import {
+            source,
+            format,
+            dry_run,
+        } => {
+            let workspace_root = match export::resolve_workspace_for_export(cli.workspace) {
+                Ok(root) => root,
+                Err(e) => {
+                    eprintln!("✗ {}", e);
+                    std::process::exit(1);
+                }
+            };
+            import::handle_import(workspace_root, &source, &format, dry_run);
+            true
+        }
+
         Commands::Publish {
             destination,
             audience,
             format,
             single_file,
             title,
+            theme,
+            base_url,
+            image_max_width,
+            math,
+            watch,
             force,
             dry_run,
         } => {
@@ -216,6 +278,11 @@ pub fn run_cli() {
                 &format,
                 single_file,
                 title,
+                theme,
+                base_url,
+                image_max_width,
+                &math,
+                watch,
                 force,
                 dry_run,
             );
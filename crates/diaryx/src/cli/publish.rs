@@ -1,10 +1,14 @@
 //! CLI handler for publish command
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use diaryx_core::fs::{RealFileSystem, SyncToAsyncFs};
 use diaryx_core::pandoc;
+use diaryx_core::publish::manifest::MANIFEST_FILE;
 use diaryx_core::publish::{PublishOptions, Publisher};
+use diaryx_core::render::{MathStyle, RenderPipeline};
 use diaryx_core::workspace::Workspace;
 
 /// Helper to run async operations in sync context
@@ -13,6 +17,7 @@ fn block_on<F: std::future::Future>(f: F) -> F::Output {
 }
 
 /// Handle the publish command
+#[allow(clippy::too_many_arguments)]
 pub fn handle_publish(
     destination: PathBuf,
     workspace_override: Option<PathBuf>,
@@ -20,6 +25,11 @@ pub fn handle_publish(
     format: &str,
     single_file: bool,
     title: Option<String>,
+    theme: Option<String>,
+    base_url: Option<String>,
+    image_max_width: Option<u32>,
+    math: &str,
+    watch: bool,
     force: bool,
     dry_run: bool,
 ) {
@@ -34,8 +44,22 @@ pub fn handle_publish(
         return;
     }
 
-    // Check pandoc availability for non-HTML formats
-    if pandoc::requires_pandoc(format) && !pandoc::is_pandoc_available() {
+    if watch && format != "html" {
+        eprintln!("✗ --watch only supports the html format");
+        return;
+    }
+
+    let renderers = match render_pipeline(math) {
+        Ok(renderers) => renderers,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            return;
+        }
+    };
+
+    // Check pandoc availability for non-HTML formats (EPUB is written natively)
+    let native_epub = format == "epub";
+    if !native_epub && pandoc::requires_pandoc(format) && !pandoc::is_pandoc_available() {
         pandoc::print_install_instructions();
         return;
     }
@@ -48,9 +72,10 @@ pub fn handle_publish(
         }
     };
 
-    // Check destination
-    if destination.exists() && !force {
-        if single_file {
+    // Check destination (a site published before is updated incrementally)
+    let previously_published = !single_file && destination.join(MANIFEST_FILE).exists();
+    if destination.exists() && !force && !previously_published {
+        if single_file || native_epub {
             eprintln!(
                 "✗ Destination file '{}' already exists (use --force to overwrite)",
                 destination.display()
@@ -65,11 +90,14 @@ pub fn handle_publish(
     }
 
     // Build options
-    let options = PublishOptions {
+    let mut options = PublishOptions {
         single_file,
         title,
         audience: audience.clone(),
         force,
+        theme: theme.clone(),
+        base_url: base_url.clone(),
+        image_max_width,
     };
 
     // Show plan
@@ -80,17 +108,33 @@ pub fn handle_publish(
     if let Some(ref aud) = audience {
         println!("Audience: {}", aud);
     }
-    if format != "html" {
+    if let Some(ref theme) = theme {
+        println!("Theme: {}", theme);
+    }
+    if let Some(ref base_url) = base_url {
+        println!("Base URL: {}", base_url);
+    }
+    if let Some(width) = image_max_width {
+        println!("Image max width: {}px", width);
+    }
+    if native_epub {
+        println!("Format: epub");
+    } else if format != "html" {
         println!("Format: {} (via pandoc)", format);
     }
     println!(
         "Output mode: {}",
-        if single_file {
+        if single_file || native_epub {
             "single file"
+        } else if previously_published && !force {
+            "multiple files (only changed pages)"
         } else {
             "multiple files"
         }
     );
+    if base_url.is_none() && !single_file && format == "html" {
+        println!("⚠ No --base-url: skipping sitemap.xml and the RSS/Atom feeds");
+    }
     println!();
 
     if dry_run {
@@ -100,16 +144,110 @@ pub fn handle_publish(
 
     // Execute publish
     let fs = SyncToAsyncFs::new(RealFileSystem);
-    let publisher = Publisher::new(fs);
+    let publisher = Publisher::new(fs).with_renderers(renderers);
+
+    if native_epub {
+        publish_epub(&publisher, &workspace_root, &destination, &options);
+        return;
+    }
+
+    let published = publish_once(
+        &publisher,
+        &workspace_root,
+        &destination,
+        &options,
+        format,
+        audience.is_some(),
+    );
+    if !watch {
+        return;
+    }
+    if !published {
+        eprintln!("  (watching for changes anyway)");
+    }
+
+    // Later runs only re-render what changed
+    options.force = false;
+    let watch_dir = workspace_root
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    println!(
+        "Watching {} for changes (Ctrl+C to stop)...",
+        watch_dir.display()
+    );
+    let mut snapshot = workspace_snapshot(&watch_dir, &destination);
+    loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        let current = workspace_snapshot(&watch_dir, &destination);
+        if current == snapshot {
+            continue;
+        }
+
+        // Wait for the workspace to settle (editors often write in steps)
+        let mut settled = current;
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let next = workspace_snapshot(&watch_dir, &destination);
+            if next == settled {
+                break;
+            }
+            settled = next;
+        }
+        snapshot = settled;
+
+        println!();
+        println!(
+            "Change detected at {}, publishing...",
+            chrono::Local::now().format("%H:%M:%S")
+        );
+        publish_once(
+            &publisher,
+            &workspace_root,
+            &destination,
+            &options,
+            format,
+            audience.is_some(),
+        );
+    }
+}
+
+/// Build the math and diagram pipeline for a `--math` value, with the
+/// diagram tools found on PATH
+pub fn render_pipeline(math: &str) -> Result<RenderPipeline, String> {
+    let style = match math {
+        "none" => None,
+        name => Some(MathStyle::from_name(name).ok_or_else(|| {
+            format!(
+                "Unknown math style '{}'. Supported: katex, mathml, none",
+                name
+            )
+        })?),
+    };
+    Ok(RenderPipeline::new().with_math(style).with_local_tools())
+}
+
+/// How often `--watch` checks the workspace for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
-    match block_on(publisher.publish(&workspace_root, &destination, &options)) {
+/// Publish once and report the result. Returns whether it succeeded.
+fn publish_once(
+    publisher: &Publisher<SyncToAsyncFs<RealFileSystem>>,
+    workspace_root: &Path,
+    destination: &Path,
+    options: &PublishOptions,
+    format: &str,
+    has_audience: bool,
+) -> bool {
+    let single_file = options.single_file;
+    match block_on(publisher.publish(workspace_root, destination, options)) {
         Ok(result) => {
             if result.files_processed == 0 {
                 println!("⚠ No files to publish");
-                if audience.is_some() {
+                if has_audience {
                     println!("  (no files match the specified audience)");
                 }
-                return;
+                return true;
             }
 
             println!(
@@ -118,6 +256,20 @@ pub fn handle_publish(
                 if result.files_processed == 1 { "" } else { "s" },
                 destination.display()
             );
+            if !single_file && result.pages_rendered < result.files_processed {
+                println!(
+                    "  {} rendered, {} unchanged",
+                    result.pages_rendered,
+                    result.files_processed - result.pages_rendered
+                );
+            }
+            if result.files_removed > 0 {
+                println!(
+                    "  Removed {} file{} of deleted pages",
+                    result.files_removed,
+                    if result.files_removed == 1 { "" } else { "s" }
+                );
+            }
 
             // Post-process with pandoc if a non-HTML format was requested
             if pandoc::requires_pandoc(format) {
@@ -127,14 +279,14 @@ pub fn handle_publish(
                 let mut failed = 0;
 
                 let html_files = if single_file {
-                    vec![destination.clone()]
+                    vec![destination.to_path_buf()]
                 } else {
-                    walkdir_html(&destination)
+                    walkdir_html(destination)
                 };
 
                 for html_path in &html_files {
                     let out_path = html_path.with_extension(ext);
-                    match pandoc::convert_file(html_path, &out_path, "html", format, true) {
+                    match pandoc::convert_file(html_path, &out_path, "html", format, true, &[]) {
                         Ok(()) => {
                             let _ = std::fs::remove_file(html_path);
                             converted += 1;
@@ -157,11 +309,73 @@ pub fn handle_publish(
                 let index_path = destination.join("index.html");
                 println!("  Open {} in a browser to view", index_path.display());
             }
+            true
+        }
+        Err(e) => {
+            eprintln!("✗ Publish failed: {}", e);
+            false
         }
+    }
+}
+
+/// Write the workspace as one EPUB book to `destination`
+fn publish_epub(
+    publisher: &Publisher<SyncToAsyncFs<RealFileSystem>>,
+    workspace_root: &Path,
+    destination: &Path,
+    options: &PublishOptions,
+) {
+    let bytes = match block_on(publisher.epub(workspace_root, options)) {
+        Ok(bytes) => bytes,
         Err(e) => {
             eprintln!("✗ Publish failed: {}", e);
+            return;
+        }
+    };
+    if let Some(parent) = destination.parent()
+        && !parent.as_os_str().is_empty()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        eprintln!("✗ Failed to create {}: {}", parent.display(), e);
+        return;
+    }
+    match std::fs::write(destination, bytes) {
+        Ok(()) => println!("✓ Wrote EPUB to {}", destination.display()),
+        Err(e) => eprintln!("✗ Failed to write {}: {}", destination.display(), e),
+    }
+}
+
+/// Modification time and size of every file in the workspace folder, skipping
+/// hidden entries and the publish destination
+fn workspace_snapshot(dir: &Path, destination: &Path) -> BTreeMap<PathBuf, (SystemTime, u64)> {
+    fn visit(dir: &Path, destination: &Path, snapshot: &mut BTreeMap<PathBuf, (SystemTime, u64)>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') || path == destination {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                visit(&path, destination, snapshot);
+            } else {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                snapshot.insert(path, (modified, metadata.len()));
+            }
         }
     }
+
+    let destination = destination
+        .canonicalize()
+        .unwrap_or_else(|_| destination.to_path_buf());
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut snapshot = BTreeMap::new();
+    visit(&dir, &destination, &mut snapshot);
+    snapshot
 }
 
 /// Collect all `.html` files under a directory recursively.
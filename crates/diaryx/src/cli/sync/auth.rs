@@ -1,6 +1,6 @@
 //! Authentication command handlers for sync.
 //!
-//! Handles login, verify, and logout commands.
+//! Handles login (magic link or device code), verify, approve, and logout commands.
 
 use diaryx_core::config::Config;
 
@@ -88,68 +88,7 @@ pub fn handle_verify(config: &Config, token: &str, device_name: Option<&str>) {
             if resp.status().is_success() {
                 // Parse response to get session token
                 match resp.json::<serde_json::Value>() {
-                    Ok(json) => {
-                        // Server returns "token" not "session_token"
-                        let session_token = json
-                            .get("token")
-                            .or_else(|| json.get("session_token"))
-                            .and_then(|v| v.as_str());
-
-                        if let Some(session_token) = session_token {
-                            // Get email from response - may be nested under "user"
-                            let email = json
-                                .get("user")
-                                .and_then(|u| u.get("email"))
-                                .and_then(|v| v.as_str())
-                                .or_else(|| json.get("email").and_then(|v| v.as_str()))
-                                .map(String::from)
-                                .or_else(|| config.sync_email.clone());
-
-                            // Get user_id from response - may be nested under "user"
-                            // This can be used as a workspace_id fallback
-                            let user_id = json
-                                .get("user")
-                                .and_then(|u| u.get("id"))
-                                .and_then(|v| v.as_str())
-                                .map(String::from);
-
-                            // Get workspace_id from response if present
-                            let workspace_id = json
-                                .get("workspace_id")
-                                .and_then(|v| v.as_str())
-                                .map(String::from)
-                                .or(user_id);
-
-                            // Save credentials to config
-                            let mut new_config = config.clone();
-                            new_config.sync_session_token = Some(session_token.to_string());
-                            if let Some(e) = email.clone() {
-                                new_config.sync_email = Some(e);
-                            }
-                            if let Some(wid) = workspace_id.clone() {
-                                new_config.sync_workspace_id = Some(wid);
-                            }
-
-                            if let Err(e) = new_config.save() {
-                                eprintln!("Warning: Could not save config: {}", e);
-                            }
-
-                            println!();
-                            println!("Successfully logged in!");
-                            if let Some(e) = email {
-                                println!("  Email: {}", e);
-                            }
-                            if let Some(wid) = workspace_id {
-                                println!("  Workspace ID: {}", wid);
-                            }
-                            println!();
-                            println!("You can now start syncing with:");
-                            println!("  diaryx sync start");
-                        } else {
-                            eprintln!("Verification succeeded but no session token in response");
-                            eprintln!("Response: {:?}", json);
-                        }
-                    }
+                    Ok(json) => complete_login(config, &json),
                     Err(e) => {
                         eprintln!("Failed to parse verification response: {}", e);
                     }
@@ -174,6 +113,235 @@ pub fn handle_verify(config: &Config, token: &str, device_name: Option<&str>) {
     }
 }
 
+/// Handle `login --device` - log in by approving a code from a signed-in device.
+///
+/// Works without email, so it's the way to sign in on headless servers.
+pub fn handle_device_login(config: &Config, server: Option<&str>, device_name: Option<&str>) {
+    let server_url = server
+        .or(config.sync_server_url.as_deref())
+        .unwrap_or(DEFAULT_SYNC_SERVER);
+
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .post(format!("{}/auth/device/code", server_url))
+        .json(&serde_json::json!({ "device_name": device_name.unwrap_or("CLI") }))
+        .send();
+
+    let json = match response {
+        Ok(resp) if resp.status().is_success() => match resp.json::<serde_json::Value>() {
+            Ok(json) => json,
+            Err(e) => {
+                eprintln!("Failed to parse device code response: {}", e);
+                return;
+            }
+        },
+        Ok(resp) => {
+            let status = resp.status();
+            let body = resp.text().unwrap_or_default();
+            eprintln!("Device login request failed: {} - {}", status, body);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to sync server: {}", e);
+            return;
+        }
+    };
+
+    let (Some(device_code), Some(user_code)) = (
+        json.get("device_code").and_then(|v| v.as_str()),
+        json.get("user_code").and_then(|v| v.as_str()),
+    ) else {
+        eprintln!("Unexpected device code response: {:?}", json);
+        return;
+    };
+    let interval = json.get("interval").and_then(|v| v.as_u64()).unwrap_or(5);
+    let expires_in = json
+        .get("expires_in")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(600);
+
+    println!("To log in, run this on a device that is already signed in:");
+    println!();
+    println!("  diaryx sync approve {}", user_code);
+    println!();
+    println!(
+        "Waiting for approval (code expires in {} minutes)...",
+        expires_in / 60
+    );
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(expires_in);
+    while std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_secs(interval));
+
+        let resp = match client
+            .post(format!("{}/auth/device/token", server_url))
+            .json(&serde_json::json!({ "device_code": device_code }))
+            .send()
+        {
+            Ok(resp) => resp,
+            // Transient network errors: keep polling until the code expires
+            Err(_) => continue,
+        };
+
+        let success = resp.status().is_success();
+        let Ok(json) = resp.json::<serde_json::Value>() else {
+            continue;
+        };
+
+        if success {
+            let mut new_config = config.clone();
+            new_config.sync_server_url = Some(server_url.to_string());
+            complete_login(&new_config, &json);
+            return;
+        }
+
+        match device_poll_error(&json) {
+            Some("authorization_pending") => {}
+            Some("access_denied") => {
+                eprintln!("Login request was denied.");
+                return;
+            }
+            Some("expired_token") => break,
+            other => {
+                eprintln!("Device login failed: {}", other.unwrap_or("unknown error"));
+                return;
+            }
+        }
+    }
+
+    eprintln!("Code expired. Run 'diaryx sync login --device' to try again.");
+}
+
+/// Extract the OAuth-style error code from a device token poll response.
+fn device_poll_error(json: &serde_json::Value) -> Option<&str> {
+    json.get("error").and_then(|v| v.as_str())
+}
+
+/// Handle the approve command - approve or deny a device login from this device.
+pub fn handle_approve(config: &Config, code: &str, approve: bool) {
+    let Some(token) = config.sync_session_token.as_deref() else {
+        eprintln!("Not logged in. Run 'diaryx sync login <email>' first.");
+        return;
+    };
+    let server_url = config
+        .sync_server_url
+        .as_deref()
+        .unwrap_or(DEFAULT_SYNC_SERVER);
+
+    let client = reqwest::blocking::Client::new();
+
+    // Show which device is asking before approving it
+    let lookup = client
+        .get(format!(
+            "{}/auth/device?user_code={}",
+            server_url,
+            urlencoding::encode(code)
+        ))
+        .header("Authorization", format!("Bearer {}", token))
+        .send();
+    if let Ok(resp) = lookup
+        && resp.status().is_success()
+        && let Ok(json) = resp.json::<serde_json::Value>()
+    {
+        let device = json
+            .get("device_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unnamed device");
+        println!("Login request from: {}", device);
+    }
+
+    let response = client
+        .post(format!("{}/auth/device/approve", server_url))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&serde_json::json!({ "user_code": code, "approve": approve }))
+        .send();
+
+    match response {
+        Ok(resp) if resp.status().is_success() => {
+            if approve {
+                println!("Approved. The other device is now logged in.");
+            } else {
+                println!("Denied.");
+            }
+        }
+        Ok(resp) if resp.status().as_u16() == 404 => {
+            eprintln!("Invalid or expired code: {}", code);
+        }
+        Ok(resp) => {
+            let status = resp.status();
+            let body = resp.text().unwrap_or_default();
+            eprintln!("Approval failed: {} - {}", status, body);
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to sync server: {}", e);
+        }
+    }
+}
+
+/// Save the session from a successful login response and print a summary.
+fn complete_login(config: &Config, json: &serde_json::Value) {
+    // Server returns "token" not "session_token"
+    let session_token = json
+        .get("token")
+        .or_else(|| json.get("session_token"))
+        .and_then(|v| v.as_str());
+
+    if let Some(session_token) = session_token {
+        // Get email from response - may be nested under "user"
+        let email = json
+            .get("user")
+            .and_then(|u| u.get("email"))
+            .and_then(|v| v.as_str())
+            .or_else(|| json.get("email").and_then(|v| v.as_str()))
+            .map(String::from)
+            .or_else(|| config.sync_email.clone());
+
+        // Get user_id from response - may be nested under "user"
+        // This can be used as a workspace_id fallback
+        let user_id = json
+            .get("user")
+            .and_then(|u| u.get("id"))
+            .and_then(|v| v.as_str())
+            .map(String::from);
+
+        // Get workspace_id from response if present
+        let workspace_id = json
+            .get("workspace_id")
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .or(user_id);
+
+        // Save credentials to config
+        let mut new_config = config.clone();
+        new_config.sync_session_token = Some(session_token.to_string());
+        if let Some(e) = email.clone() {
+            new_config.sync_email = Some(e);
+        }
+        if let Some(wid) = workspace_id.clone() {
+            new_config.sync_workspace_id = Some(wid);
+        }
+
+        if let Err(e) = new_config.save() {
+            eprintln!("Warning: Could not save config: {}", e);
+        }
+
+        println!();
+        println!("Successfully logged in!");
+        if let Some(e) = email {
+            println!("  Email: {}", e);
+        }
+        if let Some(wid) = workspace_id {
+            println!("  Workspace ID: {}", wid);
+        }
+        println!();
+        println!("You can now start syncing with:");
+        println!("  diaryx sync start");
+    } else {
+        eprintln!("Verification succeeded but no session token in response");
+        eprintln!("Response: {:?}", json);
+    }
+}
+
 /// Handle the logout command - clear stored credentials.
 pub fn handle_logout(config: &Config) {
     let server_url = config.sync_server_url.as_deref();
@@ -271,6 +439,16 @@ mod tests {
         assert!(url.contains("My%20Device%20Name"));
     }
 
+    #[test]
+    fn test_device_poll_error_parsing() {
+        let json: serde_json::Value =
+            serde_json::from_str(r#"{"error": "authorization_pending"}"#).unwrap();
+        assert_eq!(device_poll_error(&json), Some("authorization_pending"));
+
+        let json: serde_json::Value = serde_json::from_str(r#"{"token": "abc"}"#).unwrap();
+        assert_eq!(device_poll_error(&json), None);
+    }
+
     #[test]
     fn test_logout_url_construction() {
         let server = "https://sync.diaryx.org";
@@ -0,0 +1,290 @@
+//! Update bundle command handlers.
+//!
+//! Handles `sync export-updates` and `sync import-updates`, which carry CRDT
+//! changes between devices as files (a shared Syncthing folder, a USB drive,
+//! an email attachment) instead of over a live connection.
+//!
+//! Importing records what the other devices using a folder have, from the
+//! state vectors in their bundles, so exports to that folder only carry what
+//! they're missing. Until they've written a bundle, exports are full.
+
+use std::path::{Path, PathBuf};
+
+use diaryx_core::config::Config;
+use diaryx_core::crdt::{
+    BundleHeader, SyncMessage, UPDATE_BUNDLE_EXTENSION, UpdateBundle, export_update_bundle,
+    import_update_bundle, received_state,
+};
+
+use super::{create_local_sync_manager, default_device_name, local_workspace_id};
+use crate::cli::block_on;
+
+/// Handle the export-updates command - write the changes the other devices
+/// using `target` haven't reported having.
+pub fn handle_export(
+    config: &Config,
+    workspace_root: &Path,
+    target: &Path,
+    full: bool,
+    device_name: Option<String>,
+) {
+    let device_name = device_name.unwrap_or_else(default_device_name);
+    let workspace_id = local_workspace_id(config);
+    let state_path = recorded_state_path(workspace_root, target);
+
+    println!("Exporting updates...");
+    println!("  Local path: {}", workspace_root.display());
+
+    let sync_manager = match create_local_sync_manager(workspace_root) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let recorded = if full {
+        None
+    } else {
+        std::fs::read(&state_path)
+            .ok()
+            .and_then(|data| UpdateBundle::decode(&data).ok())
+            .map(|state| state.state_vectors())
+    };
+
+    let header = BundleHeader::new(&device_name, &workspace_id);
+    let bundle = match block_on(export_update_bundle(
+        &sync_manager,
+        header,
+        recorded.as_ref(),
+    )) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Failed to export updates: {}", e);
+            return;
+        }
+    };
+
+    if !bundle.has_updates() {
+        println!("The other devices using this path already have every change.");
+        println!("  Use --full to export everything.");
+        return;
+    }
+
+    let bundle_path = bundle_file_path(target, &bundle.header);
+    if let Some(parent) = bundle_path.parent().filter(|p| !p.as_os_str().is_empty())
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        eprintln!("Failed to create {}: {}", parent.display(), e);
+        return;
+    }
+    if let Err(e) = std::fs::write(&bundle_path, bundle.encode()) {
+        eprintln!("Failed to write {}: {}", bundle_path.display(), e);
+        return;
+    }
+
+    println!("Wrote {}", bundle_path.display());
+    println!("  Docs updated: {}", bundle.update_count());
+}
+
+/// Handle the import-updates command - merge every bundle found at `source`
+/// and record what the devices that wrote them have.
+pub fn handle_import(
+    config: &Config,
+    workspace_root: &Path,
+    source: &Path,
+    device_name: Option<String>,
+) {
+    let device_name = device_name.unwrap_or_else(default_device_name);
+    let workspace_id = local_workspace_id(config);
+
+    let bundle_paths = match find_bundles(source) {
+        Ok(paths) if paths.is_empty() => {
+            println!("No update bundles found in {}", source.display());
+            return;
+        }
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", source.display(), e);
+            return;
+        }
+    };
+
+    println!("Importing updates...");
+    println!("  Local path: {}", workspace_root.display());
+
+    let sync_manager = match create_local_sync_manager(workspace_root) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let mut files_changed = 0;
+    let mut bodies_changed = 0;
+    let mut failed = 0;
+    let mut from_others = Vec::new();
+
+    for path in &bundle_paths {
+        let bundle = match std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| UpdateBundle::decode(&data).map_err(|e| e.to_string()))
+        {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("  Skipping {}: {}", path.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        match block_on(import_update_bundle(
+            &sync_manager,
+            &bundle,
+            &workspace_id,
+            true,
+        )) {
+            Ok(result) => {
+                files_changed += result.changed_files.len();
+                bodies_changed += result.body_changed.len();
+                // This device's own bundles say nothing about the others
+                if bundle.header.device_name != device_name {
+                    from_others.push(bundle);
+                }
+            }
+            Err(e) => {
+                eprintln!("  Skipping {}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if !from_others.is_empty() {
+        let state_path = recorded_state_path(workspace_root, source);
+        let mut state = UpdateBundle::new(BundleHeader::new(&device_name, &workspace_id));
+        for (doc_id, sv) in received_state(&from_others) {
+            state.push(doc_id, SyncMessage::SyncStep1(sv).encode());
+        }
+        if let Err(e) = state_path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&state_path, state.encode()))
+        {
+            eprintln!(
+                "  Warning: could not record the other devices' state: {}",
+                e
+            );
+        }
+    }
+
+    println!("Imported {} bundle(s)", bundle_paths.len() - failed);
+    println!("  Files updated: {}", files_changed);
+    println!("  Bodies updated: {}", bodies_changed);
+}
+
+/// Whether `path` names a single bundle file rather than a directory.
+fn is_bundle_file(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some(UPDATE_BUNDLE_EXTENSION)
+}
+
+/// Where to write a bundle: `target` itself, or a new file inside it.
+fn bundle_file_path(target: &Path, header: &BundleHeader) -> PathBuf {
+    if is_bundle_file(target) {
+        return target.to_path_buf();
+    }
+
+    let device: String = header
+        .device_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    target.join(format!(
+        "{}-{}.{}",
+        device, header.created_at, UPDATE_BUNDLE_EXTENSION
+    ))
+}
+
+/// List bundle files at `source`, oldest name first.
+fn find_bundles(source: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if source.is_file() {
+        return Ok(vec![source.to_path_buf()]);
+    }
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(source)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && is_bundle_file(p))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Local file recording what the devices using the bundle folder `target`
+/// (or the folder of a `.dxsync` file) have.
+fn recorded_state_path(workspace_root: &Path, target: &Path) -> PathBuf {
+    let folder = match target.parent() {
+        Some(parent) if is_bundle_file(target) => parent,
+        _ => target,
+    };
+    let absolute = std::path::absolute(folder).unwrap_or_else(|_| folder.to_path_buf());
+    let key: String = absolute
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    workspace_root
+        .join(".diaryx")
+        .join("bundle-state")
+        .join(format!("{}.{}", key, UPDATE_BUNDLE_EXTENSION))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_file_path_in_directory() {
+        let header = BundleHeader {
+            device_name: "my laptop".to_string(),
+            workspace_id: "default".to_string(),
+            created_at: 42,
+        };
+        assert_eq!(
+            bundle_file_path(Path::new("/shared"), &header),
+            PathBuf::from("/shared/my-laptop-42.dxsync")
+        );
+        assert_eq!(
+            bundle_file_path(Path::new("/tmp/out.dxsync"), &header),
+            PathBuf::from("/tmp/out.dxsync")
+        );
+    }
+
+    #[test]
+    fn test_find_bundles_skips_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b-2.dxsync"), b"").unwrap();
+        std::fs::write(dir.path().join("a-1.dxsync"), b"").unwrap();
+        std::fs::write(dir.path().join("notes.md"), b"").unwrap();
+
+        let found = find_bundles(dir.path()).unwrap();
+        let names: Vec<_> = found
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a-1.dxsync", "b-2.dxsync"]);
+    }
+
+    #[test]
+    fn test_recorded_state_path_is_per_target() {
+        let root = Path::new("/ws");
+        let a = recorded_state_path(root, Path::new("/shared/a"));
+        let b = recorded_state_path(root, Path::new("/shared/b"));
+        assert_ne!(a, b);
+        assert!(a.starts_with("/ws/.diaryx/bundle-state"));
+        // A bundle file shares the state of its folder
+        assert_eq!(
+            recorded_state_path(root, Path::new("/shared/a/laptop-1.dxsync")),
+            a
+        );
+    }
+}
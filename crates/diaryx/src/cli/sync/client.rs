@@ -3,18 +3,19 @@
 //! Handles start, push, and pull commands using WebSocket connections.
 
 use std::path::Path;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 
 use base64::Engine;
 use diaryx_core::config::Config;
 use diaryx_core::crdt::{
-    BodyDocManager, DocIdKind, RustSyncManager, SyncHandler, SyncMessage, WorkspaceCrdt,
-    format_body_doc_id, format_workspace_doc_id, frame_message_v2, parse_doc_id,
-    unframe_message_v2,
+    BodyDocManager, CrdtStorage, DocIdKind, ERROR_CHECK_INTERVAL_MS, PendingUpdate,
+    RustSyncManager, SyncHandler, SyncMessage, WorkspaceCrdt, decode_sync_error_control,
+    encode_error_check_control, format_body_doc_id, format_workspace_doc_id, frame_message_v2,
+    parse_doc_id, pending_updates, unframe_message_v2,
 };
 use diaryx_core::fs::{RealFileSystem, SyncToAsyncFs};
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{Sink, SinkExt, StreamExt};
 use serde::Deserialize;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
@@ -23,10 +24,122 @@ use super::progress;
 
 const DEFAULT_SYNC_SERVER: &str = "https://sync.diaryx.org";
 
+/// Tag sent as the `client` query parameter so the server's `sync_error`
+/// messages can be matched to this process.
+fn client_tag() -> &'static str {
+    static TAG: OnceLock<String> = OnceLock::new();
+    TAG.get_or_init(|| {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        format!("cli-{}-{}", std::process::id(), nanos)
+    })
+}
+
+/// Print a warning if `text` is a `sync_error` addressed to this client.
+///
+/// Returns true if `text` was a `sync_error` message.
+fn report_sync_error(text: &str) -> bool {
+    let Some(error) = decode_sync_error_control(text) else {
+        return false;
+    };
+    if error.is_for(client_tag()) {
+        let retry = error
+            .retry_after_ms
+            .map(|ms| format!(" (retry in {} ms)", ms))
+            .unwrap_or_default();
+        eprintln!(
+            "\r\x1b[K  Server refused update: {}{}",
+            error.message, retry
+        );
+    }
+    true
+}
+
+/// Send a sync message, keeping it in the outbox if the send fails.
+///
+/// Only messages that carry changes are queued; handshakes are redone on the
+/// next connection anyway.
+async fn send_or_queue<S>(
+    ws: &mut S,
+    storage: &dyn CrdtStorage,
+    doc_id: &str,
+    message: &[u8],
+) -> Result<(), S::Error>
+where
+    S: Sink<Message> + Unpin,
+{
+    let framed = frame_message_v2(doc_id, message);
+    let Err(e) = ws.send(Message::Binary(framed)).await else {
+        return Ok(());
+    };
+
+    let has_changes = SyncMessage::decode_all(message).is_ok_and(|messages| {
+        messages.iter().any(|msg| {
+            matches!(msg, SyncMessage::SyncStep2(update) | SyncMessage::Update(update) if update.len() > 2)
+        })
+    });
+    if has_changes {
+        let queued = match parse_doc_id(doc_id) {
+            Some(DocIdKind::Workspace(_)) => storage.enqueue_pending("workspace", false, message),
+            Some(DocIdKind::Body { file_path, .. }) => {
+                storage.enqueue_pending(&file_path, true, message)
+            }
+            None => return Err(e),
+        };
+        if let Err(queue_err) = queued {
+            log::warn!("Failed to queue message for {}: {}", doc_id, queue_err);
+        }
+    }
+    Err(e)
+}
+
+/// Send the updates queued in the outbox by earlier connections.
+///
+/// Sent entries are removed; the rest stay queued for the next connection.
+/// Returns the number of documents sent.
+async fn replay_outbox<S>(ws: &mut S, storage: &dyn CrdtStorage, workspace_id: &str) -> usize
+where
+    S: Sink<Message> + Unpin,
+{
+    let pending = match pending_updates(storage) {
+        Ok(pending) => pending,
+        Err(e) => {
+            log::warn!("Failed to read the outbox: {}", e);
+            return 0;
+        }
+    };
+
+    let mut sent = 0;
+    for PendingUpdate {
+        doc_name,
+        is_body,
+        message,
+        ids,
+    } in pending
+    {
+        let doc_id = if is_body {
+            format_body_doc_id(workspace_id, &doc_name)
+        } else {
+            format_workspace_doc_id(workspace_id)
+        };
+        let framed = frame_message_v2(&doc_id, &message);
+        if ws.send(Message::Binary(framed)).await.is_err() {
+            break;
+        }
+        if let Err(e) = storage.remove_pending(&ids) {
+            log::warn!("Failed to clear sent outbox entries: {}", e);
+        }
+        sent += 1;
+    }
+    sent
+}
+
 /// Scan the workspace and import existing files into the CRDT.
 ///
 /// This is needed for first-time sync when local files exist but the CRDT is empty.
-fn import_existing_files(
+pub(super) fn import_existing_files(
     workspace_root: &Path,
     workspace_crdt: &WorkspaceCrdt,
     body_manager: &BodyDocManager,
@@ -237,6 +350,7 @@ pub fn handle_start(config: &Config, workspace_root: &Path) {
         }
     };
 
+    let storage = ctx.storage;
     let workspace_crdt = ctx.workspace_crdt;
     let body_manager = ctx.body_manager;
 
@@ -268,7 +382,12 @@ pub fn handle_start(config: &Config, workspace_root: &Path) {
         .replace("https://", "wss://")
         .replace("http://", "ws://");
 
-    let sync_url = format!("{}/sync2?token={}", ws_server, session_token);
+    let sync_url = format!(
+        "{}/sync2?token={}&client={}",
+        ws_server,
+        session_token,
+        client_tag()
+    );
 
     // Set up shutdown flag
     let running = Arc::new(AtomicBool::new(true));
@@ -304,6 +423,7 @@ pub fn handle_start(config: &Config, workspace_root: &Path) {
             workspace_id,
             sync_manager,
             workspace_crdt,
+            storage,
             running,
         )
         .await;
@@ -338,6 +458,7 @@ pub fn handle_push(config: &Config, workspace_root: &Path) {
         }
     };
 
+    let storage = ctx.storage;
     let workspace_crdt = ctx.workspace_crdt;
     let body_manager = ctx.body_manager;
 
@@ -369,7 +490,12 @@ pub fn handle_push(config: &Config, workspace_root: &Path) {
         .replace("https://", "wss://")
         .replace("http://", "ws://");
 
-    let sync_url = format!("{}/sync2?token={}", ws_server, session_token);
+    let sync_url = format!(
+        "{}/sync2?token={}&client={}",
+        ws_server,
+        session_token,
+        client_tag()
+    );
 
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
@@ -380,6 +506,7 @@ pub fn handle_push(config: &Config, workspace_root: &Path) {
             &sync_manager,
             &workspace_crdt,
             &body_manager,
+            storage.as_ref(),
             false,
         )
         .await
@@ -419,6 +546,7 @@ pub fn handle_pull(config: &Config, workspace_root: &Path) {
         }
     };
 
+    let storage = ctx.storage;
     let workspace_crdt = ctx.workspace_crdt;
     let body_manager = ctx.body_manager;
     let fs = SyncToAsyncFs::new(RealFileSystem);
@@ -435,7 +563,12 @@ pub fn handle_pull(config: &Config, workspace_root: &Path) {
         .replace("https://", "wss://")
         .replace("http://", "ws://");
 
-    let sync_url = format!("{}/sync2?token={}", ws_server, session_token);
+    let sync_url = format!(
+        "{}/sync2?token={}&client={}",
+        ws_server,
+        session_token,
+        client_tag()
+    );
 
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
@@ -446,6 +579,7 @@ pub fn handle_pull(config: &Config, workspace_root: &Path) {
             &sync_manager,
             &workspace_crdt,
             &body_manager,
+            storage.as_ref(),
             true,
         )
         .await
@@ -491,6 +625,7 @@ async fn run_sync_loop_v2(
     workspace_id: &str,
     sync_manager: Arc<RustSyncManager<SyncToAsyncFs<RealFileSystem>>>,
     workspace_crdt: Arc<WorkspaceCrdt>,
+    storage: Arc<dyn CrdtStorage>,
     running: Arc<AtomicBool>,
 ) {
     println!("Connecting to sync server (v2 protocol)...");
@@ -612,6 +747,11 @@ async fn run_sync_loop_v2(
         println!("\r\x1b[K  Sent state for {} files", sent);
     }
 
+    let replayed = replay_outbox(&mut ws, storage.as_ref(), workspace_id).await;
+    if replayed > 0 {
+        println!("  Sent {} queued offline changes", replayed);
+    }
+
     progress::show_progress(50);
     println!();
     println!("Sync is running. Press Ctrl+C to stop.");
@@ -629,8 +769,8 @@ async fn run_sync_loop_v2(
                     if let Ok(result) = sync_manager.handle_workspace_message(&payload, true).await
                     {
                         if let Some(response) = result.response {
-                            let framed = frame_message_v2(&doc_id, &response);
-                            let _ = ws.send(Message::Binary(framed.into())).await;
+                            let _ =
+                                send_or_queue(&mut ws, storage.as_ref(), &doc_id, &response).await;
                         }
                         if !result.changed_files.is_empty() {
                             for file in &result.changed_files {
@@ -645,8 +785,8 @@ async fn run_sync_loop_v2(
                         .await
                     {
                         if let Some(response) = result.response {
-                            let framed = frame_message_v2(&doc_id, &response);
-                            let _ = ws.send(Message::Binary(framed.into())).await;
+                            let _ =
+                                send_or_queue(&mut ws, storage.as_ref(), &doc_id, &response).await;
                         }
                     }
                 }
@@ -655,6 +795,18 @@ async fn run_sync_loop_v2(
         }
     }
 
+    // The server holds errors for refused updates until we send a control
+    // message, so poll for them regularly
+    let error_check = encode_error_check_control(&ws_doc_id);
+    let error_check_period = tokio::time::Duration::from_millis(ERROR_CHECK_INTERVAL_MS);
+    let mut error_check_timer = tokio::time::interval_at(
+        tokio::time::Instant::now() + error_check_period,
+        error_check_period,
+    );
+    let ping_period = tokio::time::Duration::from_secs(30);
+    let mut ping_timer =
+        tokio::time::interval_at(tokio::time::Instant::now() + ping_period, ping_period);
+
     // Message loop
     while running.load(Ordering::SeqCst) {
         tokio::select! {
@@ -669,8 +821,7 @@ async fn run_sync_loop_v2(
                                     match sync_manager.handle_workspace_message(&payload, true).await {
                                         Ok(result) => {
                                             if let Some(response) = result.response {
-                                                let framed = frame_message_v2(&doc_id, &response);
-                                                if let Err(e) = ws.send(Message::Binary(framed.into())).await {
+                                                if let Err(e) = send_or_queue(&mut ws, storage.as_ref(), &doc_id, &response).await {
                                                     eprintln!("Failed to send workspace response: {}", e);
                                                 }
                                             }
@@ -690,8 +841,7 @@ async fn run_sync_loop_v2(
                                     match sync_manager.handle_body_message(&file_path, &payload, true).await {
                                         Ok(result) => {
                                             if let Some(response) = result.response {
-                                                let framed = frame_message_v2(&doc_id, &response);
-                                                if let Err(e) = ws.send(Message::Binary(framed.into())).await {
+                                                if let Err(e) = send_or_queue(&mut ws, storage.as_ref(), &doc_id, &response).await {
                                                     eprintln!("Failed to send body response: {}", e);
                                                 }
                                             }
@@ -711,6 +861,9 @@ async fn run_sync_loop_v2(
                         }
                     }
                     Some(Ok(Message::Text(text))) => {
+                        if report_sync_error(&text) {
+                            continue;
+                        }
                         // Handle JSON control messages
                         if let Ok(ctrl_msg) = serde_json::from_str::<ControlMessage>(&text) {
                             match ctrl_msg {
@@ -762,7 +915,13 @@ async fn run_sync_loop_v2(
                     _ => {}
                 }
             }
-            _ = tokio::time::sleep(tokio::time::Duration::from_secs(30)) => {
+            _ = error_check_timer.tick() => {
+                if let Err(e) = ws.send(Message::Text(error_check.clone().into())).await {
+                    eprintln!("Failed to check for sync errors: {}", e);
+                    break;
+                }
+            }
+            _ = ping_timer.tick() => {
                 // Send ping to keep connection alive
                 if let Err(e) = ws.send(Message::Ping(vec![].into())).await {
                     eprintln!("Failed to send ping: {}", e);
@@ -783,6 +942,7 @@ async fn do_one_shot_sync_v2(
     sync_manager: &RustSyncManager<SyncToAsyncFs<RealFileSystem>>,
     workspace_crdt: &WorkspaceCrdt,
     body_manager: &BodyDocManager,
+    storage: &dyn CrdtStorage,
     pull: bool,
 ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
     use std::collections::HashSet;
@@ -860,7 +1020,7 @@ async fn do_one_shot_sync_v2(
         ws.send(Message::Binary(framed.into())).await?;
     }
 
-    let mut push_count = 0;
+    let mut push_count = replay_outbox(&mut ws, storage, workspace_id).await;
     let mut pull_count = 0;
     let mut ws_sent_step2 = false;
     let mut ws_received_step2 = false;
@@ -891,7 +1051,11 @@ async fn do_one_shot_sync_v2(
             };
             match msg {
                 Some(Ok(Message::Binary(data))) => data.to_vec(),
-                Some(Ok(Message::Text(_))) => continue, // Ignore text in main loop
+                Some(Ok(Message::Text(text))) => {
+                    // Only sync errors matter once the handshake is done
+                    report_sync_error(&text);
+                    continue;
+                }
                 Some(Ok(Message::Close(_))) | None => break,
                 Some(Err(e)) => return Err(e.into()),
                 _ => continue,
@@ -909,11 +1073,10 @@ async fn do_one_shot_sync_v2(
                                 SyncMessage::SyncStep1(remote_sv) => {
                                     let diff = workspace_crdt.encode_diff(&remote_sv)?;
                                     if diff.len() > 2 {
-                                        push_count = 1;
+                                        push_count += 1;
                                     }
                                     let step2 = SyncMessage::SyncStep2(diff).encode();
-                                    let framed = frame_message_v2(&doc_id, &step2);
-                                    ws.send(Message::Binary(framed.into())).await?;
+                                    send_or_queue(&mut ws, storage, &doc_id, &step2).await?;
                                     ws_sent_step2 = true;
                                 }
                                 SyncMessage::SyncStep2(update) | SyncMessage::Update(update) => {
@@ -940,8 +1103,7 @@ async fn do_one_shot_sync_v2(
                                         push_count += 1;
                                     }
                                     let step2 = SyncMessage::SyncStep2(diff).encode();
-                                    let framed = frame_message_v2(&doc_id, &step2);
-                                    ws.send(Message::Binary(framed.into())).await?;
+                                    send_or_queue(&mut ws, storage, &doc_id, &step2).await?;
                                     body_files_sent_step2.insert(file_path.clone());
                                 }
                                 SyncMessage::SyncStep2(update) | SyncMessage::Update(update) => {
@@ -972,6 +1134,24 @@ async fn do_one_shot_sync_v2(
         }
     }
 
+    // Collect any error held for a refused push. Nothing is sent back when
+    // there is none, so only wait briefly.
+    if push_count > 0 {
+        let check = encode_error_check_control(&ws_doc_id);
+        ws.send(Message::Text(check.into())).await?;
+        let check_deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(1);
+        loop {
+            tokio::select! {
+                msg = ws.next() => match msg {
+                    Some(Ok(Message::Text(text))) if report_sync_error(&text) => break,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                },
+                _ = tokio::time::sleep_until(check_deadline) => break,
+            }
+        }
+    }
+
     ws.close(None).await?;
     Ok(if pull { pull_count } else { push_count })
 }
@@ -1457,6 +1637,67 @@ Content here."#;
         assert_eq!(doc2.get_body(), "Hello, World!");
     }
 
+    // =========================================================================
+    // Offline Outbox Tests
+    // =========================================================================
+
+    /// A connection whose sends always fail.
+    fn closed() -> std::pin::Pin<Box<dyn Sink<Message, Error = std::io::Error>>> {
+        Box::pin(futures_util::sink::unfold((), |(), _: Message| async {
+            Err(std::io::Error::other("connection closed"))
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_failed_sends_are_queued_and_replayed() {
+        let storage = MemoryStorage::new();
+        let body_manager = BodyDocManager::new(Arc::new(MemoryStorage::new()));
+        body_manager
+            .get_or_create("a.md")
+            .set_body("offline edit")
+            .unwrap();
+        let diff = body_manager.get_diff("a.md", &[0]).unwrap();
+
+        let step2 = SyncMessage::SyncStep2(diff).encode();
+        assert!(
+            send_or_queue(&mut closed(), &storage, "body:ws1/a.md", &step2)
+                .await
+                .is_err()
+        );
+        // Handshakes carry no changes and aren't queued
+        let step1 = SyncMessage::SyncStep1(vec![0]).encode();
+        assert!(
+            send_or_queue(&mut closed(), &storage, "workspace:ws1", &step1)
+                .await
+                .is_err()
+        );
+        assert_eq!(storage.pending_count().unwrap(), 1);
+
+        // Nothing is lost while still offline
+        assert_eq!(replay_outbox(&mut closed(), &storage, "ws1").await, 0);
+        assert_eq!(storage.pending_count().unwrap(), 1);
+
+        let mut sent: Vec<Message> = Vec::new();
+        assert_eq!(replay_outbox(&mut sent, &storage, "ws1").await, 1);
+        assert_eq!(storage.pending_count().unwrap(), 0);
+
+        let Message::Binary(data) = &sent[0] else {
+            panic!("expected a binary message");
+        };
+        let (doc_id, payload) = unframe_message_v2(data).unwrap();
+        assert_eq!(doc_id, "body:ws1/a.md");
+        let Some(SyncMessage::Update(update)) = SyncMessage::decode_all(&payload).unwrap().pop()
+        else {
+            panic!("expected an update");
+        };
+        let remote = BodyDocManager::new(Arc::new(MemoryStorage::new()));
+        remote
+            .get_or_create("a.md")
+            .apply_update(&update, diaryx_core::crdt::UpdateOrigin::Sync)
+            .unwrap();
+        assert_eq!(remote.get_or_create("a.md").get_body(), "offline edit");
+    }
+
     // =========================================================================
     // WebSocket URL Construction Tests
     // =========================================================================
@@ -0,0 +1,207 @@
+//! Workspace membership command handlers for sync.
+//!
+//! Handles listing members, inviting by email, removing members, and
+//! accepting invitations.
+
+use diaryx_core::config::Config;
+
+use crate::cli::args::MembersCommands;
+
+const DEFAULT_SYNC_SERVER: &str = "https://sync.diaryx.org";
+
+/// Handle `diaryx sync members` subcommands.
+pub fn handle_members_command(command: MembersCommands, config: &Config) {
+    let Some(token) = config.sync_session_token.as_deref() else {
+        eprintln!("Not logged in. Run 'diaryx sync login <email>' first.");
+        return;
+    };
+    let server_url = config
+        .sync_server_url
+        .as_deref()
+        .unwrap_or(DEFAULT_SYNC_SERVER);
+    let workspace_id = config.sync_workspace_id.as_deref().unwrap_or("default");
+
+    match command {
+        MembersCommands::List => handle_list(server_url, token, workspace_id),
+        MembersCommands::Invite { email, role } => {
+            handle_invite(server_url, token, workspace_id, &email, &role)
+        }
+        MembersCommands::Remove { member } => {
+            handle_remove(server_url, token, workspace_id, &member)
+        }
+        MembersCommands::Accept { token: invite } => handle_accept(server_url, token, &invite),
+    }
+}
+
+/// Build the members endpoint URL for a workspace.
+fn members_url(server_url: &str, workspace_id: &str) -> String {
+    format!(
+        "{}/api/workspaces/{}/members",
+        server_url,
+        urlencoding::encode(workspace_id)
+    )
+}
+
+/// Extract the `error` message from a failed response, falling back to the body.
+pub(super) fn error_message(resp: reqwest::blocking::Response) -> String {
+    let status = resp.status();
+    let body = resp.text().unwrap_or_default();
+    let message = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|json| json.get("error").and_then(|v| v.as_str()).map(String::from))
+        .unwrap_or(body);
+    format!("{} - {}", status, message)
+}
+
+fn handle_list(server_url: &str, token: &str, workspace_id: &str) {
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .get(members_url(server_url, workspace_id))
+        .header("Authorization", format!("Bearer {}", token))
+        .send();
+
+    let json = match response {
+        Ok(resp) if resp.status().is_success() => match resp.json::<serde_json::Value>() {
+            Ok(json) => json,
+            Err(e) => {
+                eprintln!("Failed to parse members response: {}", e);
+                return;
+            }
+        },
+        Ok(resp) => {
+            eprintln!("Failed to list members: {}", error_message(resp));
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to sync server: {}", e);
+            return;
+        }
+    };
+
+    println!("Members of workspace {}:", workspace_id);
+    for member in json["members"].as_array().into_iter().flatten() {
+        println!(
+            "  {:<32} {}",
+            member["email"].as_str().unwrap_or("?"),
+            member["role"].as_str().unwrap_or("?")
+        );
+    }
+
+    let invites = json["invites"].as_array().cloned().unwrap_or_default();
+    if !invites.is_empty() {
+        println!();
+        println!("Pending invitations:");
+        for invite in invites {
+            println!(
+                "  {:<32} {} (expires {})",
+                invite["email"].as_str().unwrap_or("?"),
+                invite["role"].as_str().unwrap_or("?"),
+                invite["expires_at"].as_str().unwrap_or("?")
+            );
+        }
+    }
+}
+
+fn handle_invite(server_url: &str, token: &str, workspace_id: &str, email: &str, role: &str) {
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .post(members_url(server_url, workspace_id))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&serde_json::json!({ "email": email, "role": role }))
+        .send();
+
+    match response {
+        Ok(resp) if resp.status().is_success() => {
+            println!("Invited {} as {}.", email, role);
+            if let Ok(json) = resp.json::<serde_json::Value>()
+                && let Some(link) = json.get("dev_link").and_then(|v| v.as_str())
+            {
+                println!();
+                println!("Email is not configured on the server. Invitation link:");
+                println!("  {}", link);
+            }
+        }
+        Ok(resp) => eprintln!("Failed to invite {}: {}", email, error_message(resp)),
+        Err(e) => eprintln!("Failed to connect to sync server: {}", e),
+    }
+}
+
+fn handle_remove(server_url: &str, token: &str, workspace_id: &str, member: &str) {
+    let url = format!(
+        "{}/{}",
+        members_url(server_url, workspace_id),
+        urlencoding::encode(member)
+    );
+
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .delete(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send();
+
+    match response {
+        Ok(resp) if resp.status().is_success() => println!("Removed {}.", member),
+        Ok(resp) => eprintln!("Failed to remove {}: {}", member, error_message(resp)),
+        Err(e) => eprintln!("Failed to connect to sync server: {}", e),
+    }
+}
+
+fn handle_accept(server_url: &str, token: &str, invite: &str) {
+    let url = format!(
+        "{}/api/invites/{}/accept",
+        server_url,
+        urlencoding::encode(invite)
+    );
+
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send();
+
+    match response {
+        Ok(resp) if resp.status().is_success() => match resp.json::<serde_json::Value>() {
+            Ok(json) => {
+                let workspace_id = json["workspace_id"].as_str().unwrap_or("?");
+                println!(
+                    "Joined workspace {} as {}.",
+                    workspace_id,
+                    json["role"].as_str().unwrap_or("?")
+                );
+                println!();
+                println!("To sync it, run:");
+                println!("  diaryx sync config --workspace-id {}", workspace_id);
+            }
+            Err(e) => eprintln!("Failed to parse invite response: {}", e),
+        },
+        Ok(resp) => eprintln!("Failed to accept invitation: {}", error_message(resp)),
+        Err(e) => eprintln!("Failed to connect to sync server: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_members_url_construction() {
+        let url = members_url("https://sync.diaryx.org", "ws-123");
+        assert_eq!(url, "https://sync.diaryx.org/api/workspaces/ws-123/members");
+    }
+
+    #[test]
+    fn test_members_url_encodes_workspace_id() {
+        let url = members_url("https://sync.diaryx.org", "my workspace");
+        assert!(url.ends_with("/api/workspaces/my%20workspace/members"));
+    }
+
+    #[test]
+    fn test_remove_url_encodes_email() {
+        let url = format!(
+            "{}/{}",
+            members_url("https://sync.diaryx.org", "ws-1"),
+            urlencoding::encode("friend+diary@example.com")
+        );
+        assert!(url.ends_with("/members/friend%2Bdiary%40example.com"));
+    }
+}
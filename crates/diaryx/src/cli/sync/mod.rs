@@ -4,15 +4,22 @@
 //! workspace metadata and file content with a remote sync server.
 
 mod auth;
+mod bundle;
 mod client;
+mod members;
+mod peer;
 mod progress;
 mod status;
+mod token;
 
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use diaryx_core::config::Config;
-use diaryx_core::crdt::{BodyDocManager, CrdtStorage, SqliteStorage, WorkspaceCrdt};
+use diaryx_core::crdt::{
+    BodyDocManager, CrdtStorage, RustSyncManager, SqliteStorage, SyncHandler, WorkspaceCrdt,
+};
+use diaryx_core::fs::{RealFileSystem, SyncToAsyncFs};
 
 use crate::cli::args::SyncCommands;
 
@@ -23,7 +30,6 @@ use crate::cli::args::SyncCommands;
 /// like `diaryx open` to persist changes to the CRDT.
 pub struct CrdtContext {
     /// Underlying storage backend
-    #[allow(dead_code)]
     pub storage: Arc<dyn CrdtStorage>,
     /// Workspace-level CRDT (file metadata index)
     pub workspace_crdt: Arc<WorkspaceCrdt>,
@@ -76,6 +82,53 @@ impl CrdtContext {
     }
 }
 
+/// Sync manager that reads and writes the real workspace on disk.
+type LocalSyncManager = RustSyncManager<SyncToAsyncFs<RealFileSystem>>;
+
+/// Build a sync manager backed by the workspace's local CRDT database.
+///
+/// Imports existing files first if the CRDT is still empty, so serverless
+/// sync (peers, update bundles) has something to send.
+fn create_local_sync_manager(workspace_root: &Path) -> Result<Arc<LocalSyncManager>, String> {
+    let ctx = CrdtContext::load_or_create(workspace_root)?;
+
+    let existing_files = ctx.workspace_crdt.list_files();
+    if existing_files.is_empty() {
+        println!("  Scanning local files...");
+        let imported =
+            client::import_existing_files(workspace_root, &ctx.workspace_crdt, &ctx.body_manager);
+        if imported > 0 {
+            println!("  Ready to sync {} files", imported);
+        }
+    } else {
+        println!("  CRDT has {} files tracked", existing_files.len());
+    }
+
+    let sync_handler = Arc::new(SyncHandler::new(SyncToAsyncFs::new(RealFileSystem)));
+    sync_handler.set_workspace_root(workspace_root.to_path_buf());
+
+    Ok(Arc::new(RustSyncManager::new(
+        ctx.workspace_crdt,
+        ctx.body_manager,
+        sync_handler,
+    )))
+}
+
+/// Default name this device uses when talking to peers.
+fn default_device_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "diaryx-cli".to_string())
+}
+
+/// Workspace ID used for doc IDs; both sides of a sync must agree on it.
+fn local_workspace_id(config: &Config) -> String {
+    config
+        .sync_workspace_id
+        .clone()
+        .unwrap_or_else(|| "default".to_string())
+}
+
 /// Handle sync subcommands.
 pub fn handle_sync_command(command: SyncCommands, workspace_override: Option<PathBuf>) {
     // Load config
@@ -91,12 +144,21 @@ pub fn handle_sync_command(command: SyncCommands, workspace_override: Option<Pat
     let workspace_root = workspace_override.unwrap_or_else(|| config.default_workspace.clone());
 
     match command {
-        SyncCommands::Login { email, server } => {
-            auth::handle_login(&config, &email, server.as_deref());
-        }
+        SyncCommands::Login {
+            email,
+            server,
+            device,
+            device_name,
+        } => match email {
+            Some(email) if !device => auth::handle_login(&config, &email, server.as_deref()),
+            _ => auth::handle_device_login(&config, server.as_deref(), device_name.as_deref()),
+        },
         SyncCommands::Verify { token, device_name } => {
             auth::handle_verify(&config, &token, device_name.as_deref());
         }
+        SyncCommands::Approve { code, deny } => {
+            auth::handle_approve(&config, &code, !deny);
+        }
         SyncCommands::Logout => {
             auth::handle_logout(&config);
         }
@@ -123,5 +185,37 @@ pub fn handle_sync_command(command: SyncCommands, workspace_override: Option<Pat
         } => {
             status::handle_config(&config, server, workspace_id, show);
         }
+        SyncCommands::ExportUpdates {
+            path,
+            full,
+            device_name,
+        } => {
+            bundle::handle_export(&config, &workspace_root, &path, full, device_name);
+        }
+        SyncCommands::ImportUpdates { path, device_name } => {
+            bundle::handle_import(&config, &workspace_root, &path, device_name);
+        }
+        SyncCommands::Peer { command } => {
+            peer::handle_peer_command(command, &config, &workspace_root);
+        }
+        SyncCommands::Members { command } => {
+            members::handle_members_command(command, &config);
+        }
+        SyncCommands::Token { command } => {
+            token::handle_token_command(command, &config);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_workspace_id_defaults() {
+        let mut config = Config::default();
+        assert_eq!(local_workspace_id(&config), "default");
+        config.sync_workspace_id = Some("ws-1".to_string());
+        assert_eq!(local_workspace_id(&config), "ws-1");
     }
 }
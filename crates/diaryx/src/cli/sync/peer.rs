@@ -0,0 +1,249 @@
+//! Peer-to-peer sync command handlers.
+//!
+//! Handles `sync peer serve`, `sync peer connect` and `sync peer discover`,
+//! which sync two workspaces directly over the LAN without the sync server.
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use diaryx_core::config::Config;
+use diaryx_core::crdt::{
+    PairingCode, PeerSyncSession, PeerSyncSummary, accept_peer, advertise_peer, connect_peer,
+    discover_peers,
+};
+use diaryx_core::fs::{RealFileSystem, SyncToAsyncFs};
+use tokio::net::TcpListener;
+
+use super::{create_local_sync_manager, default_device_name, local_workspace_id};
+use crate::cli::args::PeerCommands;
+
+type CliPeerSession = PeerSyncSession<SyncToAsyncFs<RealFileSystem>>;
+
+/// Handle `sync peer` subcommands.
+pub fn handle_peer_command(command: PeerCommands, config: &Config, workspace_root: &Path) {
+    match command {
+        PeerCommands::Serve {
+            port,
+            device_name,
+            no_advertise,
+        } => handle_serve(config, workspace_root, port, device_name, !no_advertise),
+        PeerCommands::Connect {
+            code,
+            addr,
+            device_name,
+        } => handle_connect(config, workspace_root, &code, addr.as_deref(), device_name),
+        PeerCommands::Discover { timeout } => handle_discover(timeout),
+    }
+}
+
+/// Build a peer session backed by the workspace's local CRDT database.
+fn create_session(config: &Config, workspace_root: &Path) -> Result<CliPeerSession, String> {
+    let sync_manager = create_local_sync_manager(workspace_root)?;
+    Ok(PeerSyncSession::new(
+        local_workspace_id(config),
+        sync_manager,
+    ))
+}
+
+fn print_summary(summary: &PeerSyncSummary) {
+    println!("Synced with {}", summary.peer_device);
+    println!("  Files updated: {}", summary.files_changed);
+    println!("  Bodies updated: {}", summary.bodies_changed);
+}
+
+/// Handle the serve command - wait for one peer and sync with it.
+fn handle_serve(
+    config: &Config,
+    workspace_root: &Path,
+    port: u16,
+    device_name: Option<String>,
+    advertise: bool,
+) {
+    let device_name = device_name.unwrap_or_else(default_device_name);
+
+    println!("Starting peer sync...");
+    println!("  Local path: {}", workspace_root.display());
+
+    let session = match create_session(config, workspace_root) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let pairing = PairingCode::generate();
+
+    let _advertisement = if advertise {
+        match advertise_peer(&device_name, session.workspace_id(), port) {
+            Ok(ad) => Some(ad),
+            Err(e) => {
+                eprintln!("  Warning: could not advertise via mDNS: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+
+    runtime.block_on(async {
+        let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("Failed to listen on port {}: {}", port, e);
+                return;
+            }
+        };
+
+        println!();
+        println!("Waiting for a peer on port {} as \"{}\"", port, device_name);
+        println!("Pairing code: {}", pairing.code().unwrap_or_default());
+        println!("On the other device, run:");
+        println!("  diaryx sync peer connect <code>");
+        println!();
+
+        // Keep accepting until the one-time code is used up
+        while pairing.is_active() {
+            let (stream, addr) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+
+            println!("Connection from {}", addr);
+            match accept_peer(stream, &pairing, &device_name, &session).await {
+                Ok(summary) => {
+                    print_summary(&summary);
+                    return;
+                }
+                Err(e) => eprintln!("  {}", e),
+            }
+        }
+
+        eprintln!("Pairing code expired. Run `diaryx sync peer serve` again.");
+    });
+}
+
+/// Handle the connect command - sync once with a serving peer.
+fn handle_connect(
+    config: &Config,
+    workspace_root: &Path,
+    code: &str,
+    addr: Option<&str>,
+    device_name: Option<String>,
+) {
+    let device_name = device_name.unwrap_or_else(default_device_name);
+
+    let addr: SocketAddr = match addr {
+        Some(addr) => match resolve_addr(addr) {
+            Some(a) => a,
+            None => {
+                eprintln!("Could not resolve peer address: {}", addr);
+                return;
+            }
+        },
+        None => {
+            println!("Looking for peers on the local network...");
+            let workspace_id = local_workspace_id(config);
+            let peers = match discover_peers(Duration::from_secs(3)) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("Discovery failed: {}", e);
+                    return;
+                }
+            };
+            let matching: Vec<_> = peers
+                .into_iter()
+                .filter(|p| p.workspace_id.as_deref().unwrap_or(&workspace_id) == workspace_id)
+                .collect();
+            match matching.as_slice() {
+                [] => {
+                    eprintln!("No peers found. Pass --addr <host:port> to connect directly.");
+                    return;
+                }
+                [peer] => {
+                    println!("  Found {} at {}", peer.device_name, peer.addr);
+                    peer.addr
+                }
+                many => {
+                    eprintln!("Found several peers; pass --addr to pick one:");
+                    for peer in many {
+                        eprintln!("  {} - {}", peer.device_name, peer.addr);
+                    }
+                    return;
+                }
+            }
+        }
+    };
+
+    println!("Connecting to {}...", addr);
+
+    let session = match create_session(config, workspace_root) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    runtime.block_on(async {
+        match connect_peer(addr, code, &device_name, &session).await {
+            Ok(summary) => print_summary(&summary),
+            Err(e) => eprintln!("Peer sync failed: {}", e),
+        }
+    });
+}
+
+/// Handle the discover command - list advertising peers.
+fn handle_discover(timeout: u64) {
+    println!("Listening for peers for {}s...", timeout);
+    match discover_peers(Duration::from_secs(timeout)) {
+        Ok(peers) if peers.is_empty() => println!("No peers found."),
+        Ok(peers) => {
+            for peer in peers {
+                println!(
+                    "  {} - {} (workspace: {})",
+                    peer.device_name,
+                    peer.addr,
+                    peer.workspace_id.as_deref().unwrap_or("unknown")
+                );
+            }
+        }
+        Err(e) => eprintln!("Discovery failed: {}", e),
+    }
+}
+
+/// Resolve `host:port` (or a bare host, using the default port).
+fn resolve_addr(addr: &str) -> Option<SocketAddr> {
+    use std::net::ToSocketAddrs;
+
+    let with_port = if addr.contains(':') {
+        addr.to_string()
+    } else {
+        format!("{}:{}", addr, diaryx_core::crdt::DEFAULT_PEER_PORT)
+    };
+    with_port.to_socket_addrs().ok()?.next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_addr_with_port() {
+        let addr = resolve_addr("127.0.0.1:9000").unwrap();
+        assert_eq!(addr.port(), 9000);
+    }
+
+    #[test]
+    fn test_resolve_addr_default_port() {
+        let addr = resolve_addr("127.0.0.1").unwrap();
+        assert_eq!(addr.port(), diaryx_core::crdt::DEFAULT_PEER_PORT);
+    }
+}
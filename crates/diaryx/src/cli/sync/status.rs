@@ -5,7 +5,7 @@
 use std::path::Path;
 
 use diaryx_core::config::Config;
-use diaryx_core::crdt::SqliteStorage;
+use diaryx_core::crdt::{CrdtStorage, SqliteStorage};
 
 /// Handle the status command - show sync status.
 pub fn handle_status(config: &Config, workspace_root: &Path) {
@@ -51,6 +51,9 @@ pub fn handle_status(config: &Config, workspace_root: &Path) {
             if let Ok(files) = storage.query_active_files() {
                 println!("  Files tracked: {}", files.len());
             }
+            if let Ok(pending) = storage.pending_count() {
+                println!("  Pending updates: {}", pending);
+            }
         }
     } else {
         println!("CRDT database: (not initialized)");
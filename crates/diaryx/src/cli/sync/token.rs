@@ -0,0 +1,169 @@
+//! Personal access token command handlers for sync.
+//!
+//! Handles creating, listing, and revoking tokens for scripts and automation.
+
+use diaryx_core::config::Config;
+
+use super::members::error_message;
+use crate::cli::args::TokenCommands;
+
+const DEFAULT_SYNC_SERVER: &str = "https://sync.diaryx.org";
+
+/// Handle `diaryx sync token` subcommands.
+pub fn handle_token_command(command: TokenCommands, config: &Config) {
+    let Some(session) = config.sync_session_token.as_deref() else {
+        eprintln!("Not logged in. Run 'diaryx sync login <email>' first.");
+        return;
+    };
+    let server_url = config
+        .sync_server_url
+        .as_deref()
+        .unwrap_or(DEFAULT_SYNC_SERVER);
+
+    match command {
+        TokenCommands::Create {
+            name,
+            workspace,
+            permission,
+            expires_days,
+        } => handle_create(
+            server_url,
+            session,
+            &create_request_body(&name, workspace.as_deref(), &permission, expires_days),
+        ),
+        TokenCommands::List => handle_list(server_url, session),
+        TokenCommands::Revoke { id } => handle_revoke(server_url, session, &id),
+    }
+}
+
+/// Build the JSON body for `POST /auth/tokens`.
+fn create_request_body(
+    name: &str,
+    workspace: Option<&str>,
+    permission: &str,
+    expires_days: Option<i64>,
+) -> serde_json::Value {
+    let mut body = serde_json::json!({ "name": name, "permission": permission });
+    if let Some(workspace) = workspace {
+        body["workspace_id"] = workspace.into();
+    }
+    if let Some(days) = expires_days {
+        body["expires_in_days"] = days.into();
+    }
+    body
+}
+
+fn handle_create(server_url: &str, session: &str, body: &serde_json::Value) {
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .post(format!("{}/auth/tokens", server_url))
+        .header("Authorization", format!("Bearer {}", session))
+        .json(body)
+        .send();
+
+    match response {
+        Ok(resp) if resp.status().is_success() => match resp.json::<serde_json::Value>() {
+            Ok(json) => {
+                println!(
+                    "Created token '{}' ({})",
+                    json["name"].as_str().unwrap_or("?"),
+                    json["id"].as_str().unwrap_or("?")
+                );
+                println!();
+                println!("  {}", json["token"].as_str().unwrap_or("?"));
+                println!();
+                println!("Copy it now; it won't be shown again.");
+                println!("Use it as a bearer token: Authorization: Bearer <token>");
+            }
+            Err(e) => eprintln!("Failed to parse token response: {}", e),
+        },
+        Ok(resp) => eprintln!("Failed to create token: {}", error_message(resp)),
+        Err(e) => eprintln!("Failed to connect to sync server: {}", e),
+    }
+}
+
+fn handle_list(server_url: &str, session: &str) {
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .get(format!("{}/auth/tokens", server_url))
+        .header("Authorization", format!("Bearer {}", session))
+        .send();
+
+    let tokens = match response {
+        Ok(resp) if resp.status().is_success() => match resp.json::<Vec<serde_json::Value>>() {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprintln!("Failed to parse tokens response: {}", e);
+                return;
+            }
+        },
+        Ok(resp) => {
+            eprintln!("Failed to list tokens: {}", error_message(resp));
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to sync server: {}", e);
+            return;
+        }
+    };
+
+    if tokens.is_empty() {
+        println!("No tokens.");
+        return;
+    }
+
+    for token in tokens {
+        println!(
+            "{}  {}  {}  workspace: {}",
+            token["id"].as_str().unwrap_or("?"),
+            token["name"].as_str().unwrap_or("?"),
+            token["permission"].as_str().unwrap_or("?"),
+            token["workspace_id"].as_str().unwrap_or("all")
+        );
+        println!(
+            "    last used: {}  expires: {}",
+            token["last_used_at"].as_str().unwrap_or("never"),
+            token["expires_at"].as_str().unwrap_or("never")
+        );
+    }
+}
+
+fn handle_revoke(server_url: &str, session: &str, id: &str) {
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .delete(format!(
+            "{}/auth/tokens/{}",
+            server_url,
+            urlencoding::encode(id)
+        ))
+        .header("Authorization", format!("Bearer {}", session))
+        .send();
+
+    match response {
+        Ok(resp) if resp.status().is_success() => println!("Revoked token {}.", id),
+        Ok(resp) => eprintln!("Failed to revoke token: {}", error_message(resp)),
+        Err(e) => eprintln!("Failed to connect to sync server: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_request_body_defaults() {
+        let body = create_request_body("ci", None, "read", None);
+        assert_eq!(
+            body,
+            serde_json::json!({ "name": "ci", "permission": "read" })
+        );
+    }
+
+    #[test]
+    fn test_create_request_body_scoped() {
+        let body = create_request_body("backup", Some("ws-1"), "snapshot", Some(90));
+        assert_eq!(body["workspace_id"], "ws-1");
+        assert_eq!(body["permission"], "snapshot");
+        assert_eq!(body["expires_in_days"], 90);
+    }
+}
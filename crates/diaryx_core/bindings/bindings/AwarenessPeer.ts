@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PeerPresence } from "./PeerPresence";
+
+/**
+ * A remote peer that is currently online.
+ */
+export type AwarenessPeer = { 
+/**
+ * Y.js client ID of the peer.
+ */
+clientId: number, 
+/**
+ * The peer's latest presence state.
+ */
+presence: PeerPresence, };
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A cursor or selection inside a file body.
+ *
+ * Offsets are character positions in the body text. `anchor == head`
+ * describes a collapsed cursor.
+ */
+export type CursorRange = { 
+/**
+ * Where the selection started.
+ */
+anchor: number, 
+/**
+ * Where the selection ends (the caret position).
+ */
+head: number, };
@@ -1,4 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ShareScope } from "./ShareScope";
 
 /**
  * Configuration for guest mode sync.
@@ -14,4 +15,9 @@ join_code: string,
  * If true, prefix paths with guest/{join_code}/ for OPFS storage.
  * If false (in-memory storage), paths are used as-is.
  */
-uses_opfs: boolean, };
+uses_opfs: boolean,
+/**
+ * Paths shared with this guest, if the session is scoped.
+ * `None` means the whole workspace is shared.
+ */
+scope: ShareScope | null, };
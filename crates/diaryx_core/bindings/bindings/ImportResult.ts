@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What an import wrote
+ */
+export type ImportResult = { 
+/**
+ * Entries created, in import order
+ */
+created: Array<string>, 
+/**
+ * Existing entries imported entries were appended to
+ */
+appended: Array<string>, 
+/**
+ * Number of attachments written
+ */
+attachments: number, };
@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CursorRange } from "./CursorRange";
+
+/**
+ * Presence state a client shares with its collaborators.
+ */
+export type PeerPresence = { 
+/**
+ * Display name.
+ */
+name: string | null, 
+/**
+ * Color used to draw this peer's cursor (CSS color string).
+ */
+color: string | null, 
+/**
+ * Workspace-relative path of the file the peer has open.
+ */
+file: string | null, 
+/**
+ * Cursor or selection within `file`.
+ */
+cursor: CursorRange | null, };
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Something left out of a file for an audience
+ */
+export type Redaction = { "Block": { 
+/**
+ * First line of the block
+ */
+start_line: number, 
+/**
+ * Last line of the block
+ */
+end_line: number, 
+/**
+ * Audiences the block is for
+ */
+audience: Array<string>, } } | { "Property": { 
+/**
+ * Property name
+ */
+name: string, 
+/**
+ * Audiences the property is for
+ */
+audience: Array<string>, } };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why the server refused a message.
+ */
+export type SyncErrorCode = "rate_limited" | "message_too_large";
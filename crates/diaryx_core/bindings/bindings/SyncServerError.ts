@@ -0,0 +1,27 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncErrorCode } from "./SyncErrorCode";
+
+/**
+ * A `sync_error` control message.
+ */
+export type SyncServerError = { 
+/**
+ * Why the message was refused.
+ */
+code: SyncErrorCode, 
+/**
+ * Human-readable explanation.
+ */
+message: string, 
+/**
+ * Document the refused message was for.
+ */
+doc_id: string | null, 
+/**
+ * Tag of the connection that sent the refused message.
+ */
+client: string | null, 
+/**
+ * How long to wait before sending again.
+ */
+retry_after_ms: number | null, };
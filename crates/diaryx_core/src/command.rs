@@ -24,6 +24,7 @@ use serde_json::Value as JsonValue;
 use ts_rs::TS;
 
 use crate::export::ExportPlan;
+use crate::import::ImportResult;
 use crate::link_parser::LinkFormat;
 use crate::search::SearchResults;
 use crate::validate::{FixResult, ValidationResult, ValidationResultWithMeta};
@@ -354,6 +355,42 @@ pub enum Command {
         audience: String,
     },
 
+    /// Export for another journaling app (`dayone`, `obsidian`, `logseq`)
+    /// or as a full JSON dump (`json`). Returns the files to write.
+    ExportToFormat {
+        /// Root path.
+        root_path: String,
+        /// Target audience.
+        audience: String,
+        /// Format name.
+        format: String,
+    },
+
+    /// Export as an EPUB book, without pandoc. Returns the archive's bytes.
+    #[cfg(feature = "epub")]
+    ExportToEpub {
+        /// Root path.
+        root_path: String,
+        /// Target audience (every page if not set).
+        audience: Option<String>,
+        /// Book title (defaults to the root's title).
+        title: Option<String>,
+    },
+
+    /// Import another journaling app's export (`dayone`, `journey`,
+    /// `notion`, `text` or `enex`) into the workspace.
+    ImportEntries {
+        /// Workspace path (the workspace root index file).
+        workspace_path: String,
+        /// Optional subfolder for daily entries (e.g., "Daily" or "Journal/Daily").
+        #[serde(default)]
+        daily_entry_folder: Option<String>,
+        /// Format name.
+        format: String,
+        /// Files of the export, by their path in it.
+        files: Vec<BinaryExportFile>,
+    },
+
     // === Templates ===
     /// List available templates.
     ListTemplates {
@@ -755,6 +792,9 @@ pub enum Command {
         /// Whether the guest uses OPFS (requires path prefixing).
         #[serde(default)]
         uses_opfs: bool,
+        /// Paths shared with the guest, if the session is scoped.
+        #[serde(default)]
+        guest_scope: Option<crate::crdt::ShareScope>,
     },
 
     /// Apply a remote workspace update with disk write side effects.
@@ -1070,6 +1110,9 @@ pub enum Response {
     /// Binary file paths response (no data - for efficient listing).
     BinaryFilePaths(Vec<BinaryFileInfo>),
 
+    /// Import result response.
+    ImportResult(ImportResult),
+
     /// Templates list response.
     Templates(Vec<TemplateInfo>),
 
@@ -1602,6 +1602,8 @@ impl<FS: AsyncFileSystem + Clone> Diaryx<FS> {
                 for included in &plan.included {
                     match self.fs().read_to_string(&included.source_path).await {
                         Ok(content) => {
+                            let content =
+                                crate::redaction::redact(&content, Some(&audience))?.content;
                             files.push(crate::command::ExportedFile {
                                 path: included.relative_path.to_string_lossy().to_string(),
                                 content,
@@ -1630,6 +1632,7 @@ impl<FS: AsyncFileSystem + Clone> Diaryx<FS> {
                 let mut files = Vec::new();
                 for included in &plan.included {
                     if let Ok(content) = self.fs().read_to_string(&included.source_path).await {
+                        let content = crate::redaction::redact(&content, Some(&audience))?.content;
                         let html_path = included
                             .relative_path
                             .to_string_lossy()
@@ -1775,6 +1778,76 @@ impl<FS: AsyncFileSystem + Clone> Diaryx<FS> {
                 Ok(Response::BinaryFilePaths(attachments))
             }
 
+            Command::ExportToFormat {
+                root_path,
+                audience,
+                format,
+            } => {
+                let format =
+                    crate::export_formats::ExportFormat::from_name(&format).ok_or_else(|| {
+                        DiaryxError::Unsupported(format!("Unknown export format: {}", format))
+                    })?;
+                let plan = self
+                    .export()
+                    .plan_export(Path::new(&root_path), &audience, Path::new("/tmp/export"))
+                    .await?;
+                let files = self.export().export_to_format(&plan, format).await?;
+                Ok(Response::BinaryFiles(files))
+            }
+
+            #[cfg(feature = "epub")]
+            Command::ExportToEpub {
+                root_path,
+                audience,
+                title,
+            } => {
+                let publisher = crate::publish::Publisher::new(self.fs().clone());
+                let options = crate::publish::PublishOptions {
+                    audience,
+                    title,
+                    ..Default::default()
+                };
+                let bytes = publisher.epub(Path::new(&root_path), &options).await?;
+                Ok(Response::Bytes(bytes))
+            }
+
+            Command::ImportEntries {
+                workspace_path,
+                daily_entry_folder,
+                format,
+                files,
+            } => {
+                use crate::config::Config;
+                use crate::import::{ImportFormat, Importer};
+
+                let format = ImportFormat::from_name(&format).ok_or_else(|| {
+                    DiaryxError::Unsupported(format!("Unknown import format: {}", format))
+                })?;
+                // workspace_path is the root index file (e.g., "workspace/README.md")
+                let root_index = PathBuf::from(&workspace_path);
+                let workspace_dir = root_index
+                    .parent()
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_else(|| root_index.clone());
+                let config = Config::with_options(
+                    workspace_dir,
+                    daily_entry_folder,
+                    None, // editor
+                    None, // default_template
+                    None, // daily_template
+                );
+                let files = files
+                    .into_iter()
+                    .map(|file| (file.path, file.data))
+                    .collect();
+
+                let importer = Importer::new(self.fs().clone());
+                let result = importer
+                    .import_files(&root_index, &config, format, &files)
+                    .await?;
+                Ok(Response::ImportResult(result))
+            }
+
             // === Template Operations ===
             Command::ListTemplates { workspace_path } => {
                 let templates_dir = PathBuf::from(workspace_path.as_deref().unwrap_or("workspace"))
@@ -2845,6 +2918,7 @@ impl<FS: AsyncFileSystem + Clone> Diaryx<FS> {
             Command::ConfigureSyncHandler {
                 guest_join_code,
                 uses_opfs,
+                guest_scope,
             } => {
                 let sync_handler = self.sync_handler().ok_or_else(|| {
                     DiaryxError::Unsupported(
@@ -2855,6 +2929,7 @@ impl<FS: AsyncFileSystem + Clone> Diaryx<FS> {
                 let config = guest_join_code.map(|join_code| crate::crdt::GuestConfig {
                     join_code,
                     uses_opfs,
+                    scope: guest_scope,
                 });
                 sync_handler.configure_guest(config);
                 Ok(Response::Ok)
@@ -0,0 +1,424 @@
+//! Presence tracking on top of the Y.js awareness protocol.
+//!
+//! Awareness carries ephemeral per-client state that is never persisted:
+//! who is online, which file they have open, and where their cursor is.
+//! The wire encoding lives in [`AwarenessUpdate`](super::AwarenessUpdate);
+//! this module keeps the set of known peers and applies updates using the
+//! same clock rules as `y-protocols/awareness`.
+//!
+//! # Relaying through the sync server
+//!
+//! The v2 sync server relays awareness as a JSON control message on the
+//! text channel, alongside `focus`/`unfocus`:
+//!
+//! ```json
+//! {"type": "awareness", "doc_id": "workspace:abc", "update": "<base64>"}
+//! ```
+//!
+//! `update` is the raw y-protocols awareness update, so browser clients can
+//! feed it straight into `applyAwarenessUpdate`. Use
+//! [`encode_awareness_control`] and [`decode_awareness_control`] to build
+//! and parse these messages.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut awareness = Awareness::new();
+//! let update = awareness.set_local_state(Some(PeerPresence {
+//!     name: Some("Ada".into()),
+//!     file: Some("notes/today.md".into()),
+//!     ..Default::default()
+//! }));
+//! transport.send_text(&encode_awareness_control(&doc_id, &update)).await?;
+//!
+//! // On an incoming text message
+//! if let Some((_, update)) = decode_awareness_control(&text) {
+//!     if awareness.apply_update(&update, now_ms) {
+//!         render_peers(awareness.peers());
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::sync::{AwarenessEntry, AwarenessUpdate};
+
+/// Time after which a peer that stopped renewing its state is considered offline.
+pub const AWARENESS_TIMEOUT_MS: i64 = 30_000;
+
+/// How often a client should renew its local state to stay visible.
+pub const AWARENESS_RENEW_INTERVAL_MS: i64 = 15_000;
+
+/// A cursor or selection inside a file body.
+///
+/// Offsets are character positions in the body text. `anchor == head`
+/// describes a collapsed cursor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "bindings/")]
+pub struct CursorRange {
+    /// Where the selection started.
+    pub anchor: u32,
+    /// Where the selection ends (the caret position).
+    pub head: u32,
+}
+
+/// Presence state a client shares with its collaborators.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "bindings/")]
+pub struct PeerPresence {
+    /// Display name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Color used to draw this peer's cursor (CSS color string).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Workspace-relative path of the file the peer has open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Cursor or selection within `file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<CursorRange>,
+}
+
+/// A remote peer that is currently online.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "bindings/")]
+pub struct AwarenessPeer {
+    /// Y.js client ID of the peer.
+    #[ts(type = "number")]
+    pub client_id: u64,
+    /// The peer's latest presence state.
+    pub presence: PeerPresence,
+}
+
+/// What we know about one remote client.
+#[derive(Debug, Clone)]
+struct PeerEntry {
+    clock: u64,
+    /// Raw JSON state as received, kept so it can be relayed unchanged.
+    state: Option<String>,
+    presence: Option<PeerPresence>,
+    updated_at: i64,
+}
+
+/// Local presence plus the states of all known remote peers.
+#[derive(Debug, Clone)]
+pub struct Awareness {
+    client_id: u64,
+    clock: u64,
+    local: Option<PeerPresence>,
+    peers: HashMap<u64, PeerEntry>,
+}
+
+impl Awareness {
+    /// Create an awareness instance with a random client ID.
+    pub fn new() -> Self {
+        // Y.js client IDs are random 32-bit integers
+        Self::with_client_id(uuid::Uuid::new_v4().as_u128() as u32 as u64)
+    }
+
+    /// Create an awareness instance with a fixed client ID.
+    pub fn with_client_id(client_id: u64) -> Self {
+        Self {
+            client_id,
+            clock: 0,
+            local: None,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// This client's awareness ID.
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    /// This client's current presence, if set.
+    pub fn local_state(&self) -> Option<&PeerPresence> {
+        self.local.as_ref()
+    }
+
+    /// Replace the local presence and return the update to broadcast.
+    ///
+    /// Passing `None` tells peers this client went offline.
+    pub fn set_local_state(&mut self, presence: Option<PeerPresence>) -> AwarenessUpdate {
+        self.clock += 1;
+        self.local = presence;
+        AwarenessUpdate {
+            entries: vec![self.local_entry()],
+        }
+    }
+
+    /// Re-announce the local presence so peers don't time it out.
+    ///
+    /// Returns `None` if no local presence is set.
+    pub fn renew_local_state(&mut self) -> Option<AwarenessUpdate> {
+        self.local.as_ref()?;
+        self.clock += 1;
+        Some(AwarenessUpdate {
+            entries: vec![self.local_entry()],
+        })
+    }
+
+    /// Apply a remote awareness update received at `now_ms`.
+    ///
+    /// Entries for our own client ID are ignored. Returns `true` if the set
+    /// of visible peers or any of their states changed.
+    pub fn apply_update(&mut self, update: &AwarenessUpdate, now_ms: i64) -> bool {
+        let mut changed = false;
+
+        for entry in &update.entries {
+            if entry.client_id == self.client_id {
+                continue;
+            }
+
+            let known = self.peers.get(&entry.client_id);
+            let current_clock = known.map_or(0, |p| p.clock);
+            let is_visible = known.is_some_and(|p| p.presence.is_some());
+
+            // Same rule as y-protocols: newer clocks win, and a removal at the
+            // same clock is accepted so a peer can sign off cleanly.
+            let accept = current_clock < entry.clock
+                || (current_clock == entry.clock && entry.state.is_none() && is_visible);
+            if !accept {
+                continue;
+            }
+
+            let presence = entry
+                .state
+                .as_deref()
+                .map(|json| serde_json::from_str(json).unwrap_or_default());
+            if known.map(|p| &p.presence) != Some(&presence) {
+                changed = true;
+            }
+
+            self.peers.insert(
+                entry.client_id,
+                PeerEntry {
+                    clock: entry.clock,
+                    state: entry.state.clone(),
+                    presence,
+                    updated_at: now_ms,
+                },
+            );
+        }
+
+        changed
+    }
+
+    /// Hide peers that have not renewed their state within the timeout.
+    ///
+    /// Returns `true` if any peer was removed.
+    pub fn remove_outdated(&mut self, now_ms: i64) -> bool {
+        let mut changed = false;
+        for peer in self.peers.values_mut() {
+            if peer.presence.is_some() && now_ms - peer.updated_at >= AWARENESS_TIMEOUT_MS {
+                peer.presence = None;
+                peer.state = None;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Forget every remote peer (e.g. after disconnecting).
+    pub fn clear_peers(&mut self) {
+        self.peers.clear();
+    }
+
+    /// Remote peers that are currently online, ordered by client ID.
+    pub fn peers(&self) -> Vec<AwarenessPeer> {
+        let mut peers: Vec<AwarenessPeer> = self
+            .peers
+            .iter()
+            .filter_map(|(client_id, entry)| {
+                entry.presence.clone().map(|presence| AwarenessPeer {
+                    client_id: *client_id,
+                    presence,
+                })
+            })
+            .collect();
+        peers.sort_by_key(|p| p.client_id);
+        peers
+    }
+
+    /// An update describing every visible state, local included.
+    ///
+    /// Send this to a peer that just joined so it sees everyone at once.
+    pub fn full_update(&self) -> AwarenessUpdate {
+        let mut entries = Vec::new();
+        if self.local.is_some() {
+            entries.push(self.local_entry());
+        }
+        let mut remote: Vec<AwarenessEntry> = self
+            .peers
+            .iter()
+            .filter(|(_, entry)| entry.state.is_some())
+            .map(|(client_id, entry)| AwarenessEntry {
+                client_id: *client_id,
+                clock: entry.clock,
+                state: entry.state.clone(),
+            })
+            .collect();
+        remote.sort_by_key(|e| e.client_id);
+        entries.extend(remote);
+        AwarenessUpdate { entries }
+    }
+
+    fn local_entry(&self) -> AwarenessEntry {
+        AwarenessEntry {
+            client_id: self.client_id,
+            clock: self.clock,
+            state: self
+                .local
+                .as_ref()
+                .and_then(|presence| serde_json::to_string(presence).ok()),
+        }
+    }
+}
+
+impl Default for Awareness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build the JSON control message that relays `update` for `doc_id`.
+pub fn encode_awareness_control(doc_id: &str, update: &AwarenessUpdate) -> String {
+    serde_json::json!({
+        "type": "awareness",
+        "doc_id": doc_id,
+        "update": BASE64.encode(update.encode_update()),
+    })
+    .to_string()
+}
+
+/// Parse an awareness control message into `(doc_id, update)`.
+///
+/// Returns `None` for any other control message or a malformed update.
+pub fn decode_awareness_control(text: &str) -> Option<(String, AwarenessUpdate)> {
+    let json: serde_json::Value = serde_json::from_str(text).ok()?;
+    if json.get("type")?.as_str()? != "awareness" {
+        return None;
+    }
+    let doc_id = json.get("doc_id")?.as_str()?.to_string();
+    let bytes = BASE64.decode(json.get("update")?.as_str()?).ok()?;
+    let update = AwarenessUpdate::decode_update(&bytes).ok()?;
+    Some((doc_id, update))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presence(name: &str, file: &str) -> PeerPresence {
+        PeerPresence {
+            name: Some(name.to_string()),
+            file: Some(file.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_remote_update_adds_peer() {
+        let mut alice = Awareness::with_client_id(1);
+        let mut bob = Awareness::with_client_id(2);
+
+        let update = alice.set_local_state(Some(PeerPresence {
+            cursor: Some(CursorRange { anchor: 3, head: 8 }),
+            ..presence("Alice", "a.md")
+        }));
+        assert!(bob.apply_update(&update, 0));
+
+        let peers = bob.peers();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].client_id, 1);
+        assert_eq!(peers[0].presence.file.as_deref(), Some("a.md"));
+        assert_eq!(
+            peers[0].presence.cursor,
+            Some(CursorRange { anchor: 3, head: 8 })
+        );
+
+        // Replaying the same update is a no-op
+        assert!(!bob.apply_update(&update, 0));
+    }
+
+    #[test]
+    fn test_stale_clock_is_ignored() {
+        let mut alice = Awareness::with_client_id(1);
+        let mut bob = Awareness::with_client_id(2);
+
+        let old = alice.set_local_state(Some(presence("Alice", "a.md")));
+        let new = alice.set_local_state(Some(presence("Alice", "b.md")));
+
+        bob.apply_update(&new, 0);
+        assert!(!bob.apply_update(&old, 0));
+        assert_eq!(bob.peers()[0].presence.file.as_deref(), Some("b.md"));
+    }
+
+    #[test]
+    fn test_clearing_state_removes_peer() {
+        let mut alice = Awareness::with_client_id(1);
+        let mut bob = Awareness::with_client_id(2);
+
+        bob.apply_update(&alice.set_local_state(Some(presence("Alice", "a.md"))), 0);
+        assert!(bob.apply_update(&alice.set_local_state(None), 0));
+        assert!(bob.peers().is_empty());
+    }
+
+    #[test]
+    fn test_own_entries_are_ignored() {
+        let mut alice = Awareness::with_client_id(1);
+        let update = alice.set_local_state(Some(presence("Alice", "a.md")));
+        assert!(!alice.apply_update(&update, 0));
+        assert!(alice.peers().is_empty());
+    }
+
+    #[test]
+    fn test_peers_time_out_unless_renewed() {
+        let mut alice = Awareness::with_client_id(1);
+        let mut bob = Awareness::with_client_id(2);
+
+        bob.apply_update(&alice.set_local_state(Some(presence("Alice", "a.md"))), 0);
+        assert!(!bob.remove_outdated(AWARENESS_RENEW_INTERVAL_MS));
+
+        let renewed = alice.renew_local_state().unwrap();
+        bob.apply_update(&renewed, AWARENESS_RENEW_INTERVAL_MS);
+        assert!(!bob.remove_outdated(AWARENESS_TIMEOUT_MS));
+        assert_eq!(bob.peers().len(), 1);
+
+        assert!(bob.remove_outdated(AWARENESS_RENEW_INTERVAL_MS + AWARENESS_TIMEOUT_MS));
+        assert!(bob.peers().is_empty());
+    }
+
+    #[test]
+    fn test_full_update_relays_known_states() {
+        let mut alice = Awareness::with_client_id(1);
+        let mut relay = Awareness::with_client_id(0);
+        let mut carol = Awareness::with_client_id(3);
+
+        relay.apply_update(&alice.set_local_state(Some(presence("Alice", "a.md"))), 0);
+        carol.apply_update(&relay.full_update(), 0);
+
+        assert_eq!(carol.peers().len(), 1);
+        assert_eq!(carol.peers()[0].presence.name.as_deref(), Some("Alice"));
+    }
+
+    #[test]
+    fn test_control_message_roundtrip() {
+        let mut alice = Awareness::with_client_id(42);
+        let update = alice.set_local_state(Some(presence("Alice", "a.md")));
+
+        let text = encode_awareness_control("workspace:ws1", &update);
+        let (doc_id, decoded) = decode_awareness_control(&text).unwrap();
+        assert_eq!(doc_id, "workspace:ws1");
+        assert_eq!(decoded, update);
+
+        assert!(decode_awareness_control(r#"{"type":"focus","files":[]}"#).is_none());
+        assert!(decode_awareness_control("not json").is_none());
+    }
+}
@@ -0,0 +1,770 @@
+//! LAN transport for peer-to-peer sync (native only).
+//!
+//! This module connects two `PeerSyncSession`s over a plain WebSocket on the
+//! local network, with optional mDNS discovery. It requires the `lan-sync`
+//! feature.
+//!
+//! - `advertise_peer` / `discover_peers`: mDNS service `_diaryx-sync._tcp`
+//! - `accept_peer`: serve one incoming connection (listening side)
+//! - `connect_peer`: dial a listening peer with its pairing code
+//!
+//! While pairing, text frames carry `PeerControl` JSON. Before any CRDT data
+//! is sent, the peers pair:
+//!
+//! 1. The connecting peer sends `Hello` with its SPAKE2 message, keyed by
+//!    the pairing code
+//! 2. The listening peer answers with `Challenge`, its SPAKE2 message
+//! 3. The connecting peer proves it holds the shared key with `Confirm`, an
+//!    HMAC of a fixed label under the key
+//! 4. The listening peer checks it and sends `Welcome` with its own proof,
+//!    which the connecting peer checks in turn
+//!
+//! The code itself never crosses the network, and a device that doesn't
+//! know it gets a single guess per connection on either side. The
+//! connecting peer proves first, so every guess against the listening side
+//! counts towards `MAX_PAIRING_ATTEMPTS`.
+//!
+//! After pairing, every frame is a binary message authenticated with a
+//! session key derived from the SPAKE2 key (one per direction):
+//!
+//! ```text
+//! kind (1 byte) | body | HMAC-SHA256(session key, counter | kind | body)
+//! ```
+//!
+//! `kind` is `FRAME_SYNC` for `frame_message_v2` sync messages and
+//! `FRAME_CONTROL` for `PeerControl` JSON. The counter is the frame's
+//! position in its direction, so frames that are altered, injected,
+//! replayed or reordered are rejected and end the session. Frames are not
+//! encrypted: a device on the same network can read the synced content,
+//! but cannot change it.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Device A
+//! let pairing = PairingCode::generate();
+//! let _ad = advertise_peer("laptop", "default", DEFAULT_PEER_PORT)?;
+//! let listener = TcpListener::bind(("0.0.0.0", DEFAULT_PEER_PORT)).await?;
+//! let (stream, _) = listener.accept().await?;
+//! accept_peer(stream, &pairing, "laptop", &session).await?;
+//!
+//! // Device B
+//! connect_peer(addr, "123456", "desktop", &session).await?;
+//! ```
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use sha2::Sha256;
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::peer_sync::{PairingCode, PeerControl, PeerSyncSession};
+use crate::error::{DiaryxError, Result};
+use crate::fs::AsyncFileSystem;
+
+/// mDNS service type advertised by listening peers.
+pub const PEER_SERVICE_TYPE: &str = "_diaryx-sync._tcp.local.";
+
+/// Default TCP port for peer sync.
+pub const DEFAULT_PEER_PORT: u16 = 47470;
+
+/// How long to wait for the WebSocket upgrade and pairing handshake.
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Labels of the key confirmations, so neither side's proof can be replayed
+/// as the other's.
+const LISTENER_PROOF: &[u8] = b"diaryx-peer-sync listener";
+const CONNECTOR_PROOF: &[u8] = b"diaryx-peer-sync connector";
+
+/// Labels of the session keys authenticating each side's frames.
+const LISTENER_FRAMES: &[u8] = b"diaryx-peer-sync listener frames";
+const CONNECTOR_FRAMES: &[u8] = b"diaryx-peer-sync connector frames";
+
+/// Kinds of authenticated frames.
+const FRAME_SYNC: u8 = 0;
+const FRAME_CONTROL: u8 = 1;
+
+/// Length of a frame's HMAC-SHA256 tag.
+const FRAME_TAG_LEN: usize = 32;
+
+/// How long a session may be idle before it is considered finished.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A peer found via mDNS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredPeer {
+    /// Device name announced by the peer.
+    pub device_name: String,
+    /// Workspace ID announced by the peer, if any.
+    pub workspace_id: Option<String>,
+    /// Address to connect to.
+    pub addr: SocketAddr,
+}
+
+/// Summary of a finished peer session.
+#[derive(Debug, Clone, Default)]
+pub struct PeerSyncSummary {
+    /// Device name of the remote peer.
+    pub peer_device: String,
+    /// Number of workspace files whose metadata changed locally.
+    pub files_changed: usize,
+    /// Number of bodies whose content changed locally.
+    pub bodies_changed: usize,
+}
+
+/// An active mDNS advertisement. Dropping it stops advertising.
+pub struct PeerAdvertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Drop for PeerAdvertisement {
+    fn drop(&mut self) {
+        let _ = self.daemon.unregister(&self.fullname);
+        let _ = self.daemon.shutdown();
+    }
+}
+
+impl std::fmt::Debug for PeerAdvertisement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeerAdvertisement")
+            .field("fullname", &self.fullname)
+            .finish()
+    }
+}
+
+fn mdns_error(e: mdns_sd::Error) -> DiaryxError {
+    DiaryxError::Crdt(format!("mDNS error: {}", e))
+}
+
+fn ws_error(e: tokio_tungstenite::tungstenite::Error) -> DiaryxError {
+    DiaryxError::Crdt(format!("Peer connection error: {}", e))
+}
+
+/// Advertise this device as a sync peer via mDNS.
+pub fn advertise_peer(
+    device_name: &str,
+    workspace_id: &str,
+    port: u16,
+) -> Result<PeerAdvertisement> {
+    let daemon = ServiceDaemon::new().map_err(mdns_error)?;
+
+    // mDNS instance and host names must be DNS-label safe
+    let label: String = device_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let host_name = format!("{}.local.", label);
+    let properties = [("device", device_name), ("workspace", workspace_id)];
+
+    let info = ServiceInfo::new(
+        PEER_SERVICE_TYPE,
+        &label,
+        &host_name,
+        "",
+        port,
+        &properties[..],
+    )
+    .map_err(mdns_error)?
+    .enable_addr_auto();
+
+    let fullname = info.get_fullname().to_string();
+    daemon.register(info).map_err(mdns_error)?;
+    log::info!("[LanPeer] Advertising {} on port {}", fullname, port);
+
+    Ok(PeerAdvertisement { daemon, fullname })
+}
+
+/// Browse for sync peers via mDNS for up to `timeout`.
+///
+/// This call blocks; run it on a blocking thread from async code.
+pub fn discover_peers(timeout: Duration) -> Result<Vec<DiscoveredPeer>> {
+    let daemon = ServiceDaemon::new().map_err(mdns_error)?;
+    let receiver = daemon.browse(PEER_SERVICE_TYPE).map_err(mdns_error)?;
+    let deadline = std::time::Instant::now() + timeout;
+
+    let mut peers: Vec<DiscoveredPeer> = Vec::new();
+    while let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) {
+        let Ok(event) = receiver.recv_timeout(remaining) else {
+            break;
+        };
+        if let ServiceEvent::ServiceResolved(info) = event {
+            let device_name = info
+                .get_property_val_str("device")
+                .unwrap_or_else(|| info.get_hostname())
+                .to_string();
+            let workspace_id = info.get_property_val_str("workspace").map(String::from);
+
+            // Prefer IPv4, which works on more home networks
+            let mut addrs: Vec<_> = info.get_addresses().iter().copied().collect();
+            addrs.sort_by_key(|ip| !ip.is_ipv4());
+            if let Some(ip) = addrs.first() {
+                let peer = DiscoveredPeer {
+                    device_name,
+                    workspace_id,
+                    addr: SocketAddr::new(*ip, info.get_port()),
+                };
+                if !peers.contains(&peer) {
+                    peers.push(peer);
+                }
+            }
+        }
+    }
+
+    let _ = daemon.stop_browse(PEER_SERVICE_TYPE);
+    let _ = daemon.shutdown();
+    Ok(peers)
+}
+
+/// Serve one incoming peer connection.
+///
+/// Pairs with the peer using the pairing code, then runs the sync exchange
+/// until both sides are done.
+pub async fn accept_peer<FS: AsyncFileSystem>(
+    stream: TcpStream,
+    pairing: &PairingCode,
+    device_name: &str,
+    session: &PeerSyncSession<FS>,
+) -> Result<PeerSyncSummary> {
+    // The WebSocket upgrade counts towards the timeout too, so a connection
+    // that never sends it can't hold up the listener
+    let (ws, peer_device, frames) = tokio::time::timeout(PAIRING_TIMEOUT, async {
+        let mut ws = tokio_tungstenite::accept_async(stream)
+            .await
+            .map_err(ws_error)?;
+        let (peer_device, frames) =
+            pair_listening(&mut ws, pairing, device_name, session.workspace_id()).await?;
+        Ok::<_, DiaryxError>((ws, peer_device, frames))
+    })
+    .await
+    .map_err(|_| DiaryxError::Crdt("Timed out waiting for pairing".to_string()))??;
+    log::info!("[LanPeer] Paired with {}", peer_device);
+
+    run_exchange(ws, frames, session, peer_device).await
+}
+
+/// Connect to a listening peer and run the sync exchange.
+pub async fn connect_peer<FS: AsyncFileSystem>(
+    addr: SocketAddr,
+    code: &str,
+    device_name: &str,
+    session: &PeerSyncSession<FS>,
+) -> Result<PeerSyncSummary> {
+    let (ws, peer_device, frames) = tokio::time::timeout(PAIRING_TIMEOUT, async {
+        let stream = TcpStream::connect(addr).await?;
+        let url = format!("ws://{}/peer", addr);
+        let (mut ws, _) = tokio_tungstenite::client_async(url.as_str(), stream)
+            .await
+            .map_err(ws_error)?;
+        let (peer_device, frames) =
+            pair_connecting(&mut ws, code, device_name, session.workspace_id()).await?;
+        Ok::<_, DiaryxError>((ws, peer_device, frames))
+    })
+    .await
+    .map_err(|_| DiaryxError::Crdt("Timed out waiting for pairing".to_string()))??;
+
+    run_exchange(ws, frames, session, peer_device).await
+}
+
+/// Listening side of the pairing handshake. Returns the peer's device name
+/// and the session's frame keys.
+async fn pair_listening<S>(
+    ws: &mut WebSocketStream<S>,
+    pairing: &PairingCode,
+    device_name: &str,
+    workspace_id: &str,
+) -> Result<(String, FrameAuth)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let PeerControl::Hello {
+        device_name: peer_device,
+        workspace_id: peer_workspace,
+        pake,
+    } = next_control(ws).await?
+    else {
+        return Err(DiaryxError::Crdt("Expected hello from peer".to_string()));
+    };
+
+    if peer_workspace != workspace_id {
+        let reason = format!(
+            "workspace mismatch (expected {}, got {})",
+            workspace_id, peer_workspace
+        );
+        return Err(reject(ws, &peer_device, reason).await);
+    }
+    let Some(code) = pairing.code() else {
+        return Err(reject(ws, &peer_device, "invalid or expired pairing code").await);
+    };
+
+    let (state, message) = start_pake(&code, workspace_id);
+    let key = match finish_pake(state, &pake) {
+        Ok(key) => key,
+        Err(e) => return Err(reject(ws, &peer_device, e.to_string()).await),
+    };
+    let challenge = PeerControl::Challenge { pake: message };
+    ws.send(Message::Text(challenge.to_json().into()))
+        .await
+        .map_err(ws_error)?;
+
+    let confirm = next_control(ws).await?;
+    let confirmed = pairing.redeem_with(|current| {
+        current == code
+            && matches!(&confirm, PeerControl::Confirm { proof }
+                if check_key_proof(&key, CONNECTOR_PROOF, proof))
+    });
+    if !confirmed {
+        return Err(reject(ws, &peer_device, "invalid or expired pairing code").await);
+    }
+
+    let welcome = PeerControl::Welcome {
+        device_name: device_name.to_string(),
+        proof: key_proof(&key, LISTENER_PROOF),
+    };
+    ws.send(Message::Text(welcome.to_json().into()))
+        .await
+        .map_err(ws_error)?;
+    Ok((
+        peer_device,
+        FrameAuth::new(&key, LISTENER_FRAMES, CONNECTOR_FRAMES),
+    ))
+}
+
+/// Connecting side of the pairing handshake. Returns the peer's device name
+/// and the session's frame keys.
+async fn pair_connecting<S>(
+    ws: &mut WebSocketStream<S>,
+    code: &str,
+    device_name: &str,
+    workspace_id: &str,
+) -> Result<(String, FrameAuth)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (state, message) = start_pake(code.trim(), workspace_id);
+    let hello = PeerControl::Hello {
+        device_name: device_name.to_string(),
+        workspace_id: workspace_id.to_string(),
+        pake: message,
+    };
+    ws.send(Message::Text(hello.to_json().into()))
+        .await
+        .map_err(ws_error)?;
+
+    let key = match next_control(ws).await? {
+        PeerControl::Challenge { pake } => finish_pake(state, &pake)?,
+        other => return Err(pairing_reply_error(other)),
+    };
+
+    let confirm = PeerControl::Confirm {
+        proof: key_proof(&key, CONNECTOR_PROOF),
+    };
+    ws.send(Message::Text(confirm.to_json().into()))
+        .await
+        .map_err(ws_error)?;
+
+    // A device answering in the listener's place doesn't know the code, so
+    // it can't prove the key
+    match next_control(ws).await? {
+        PeerControl::Welcome { device_name, proof }
+            if check_key_proof(&key, LISTENER_PROOF, &proof) =>
+        {
+            Ok((
+                device_name,
+                FrameAuth::new(&key, CONNECTOR_FRAMES, LISTENER_FRAMES),
+            ))
+        }
+        PeerControl::Welcome { .. } => {
+            let _ = ws.close(None).await;
+            Err(DiaryxError::Crdt(
+                "Peer failed to prove the pairing code".to_string(),
+            ))
+        }
+        other => Err(pairing_reply_error(other)),
+    }
+}
+
+/// Refuse a pairing: tell the peer why, close, and return the error.
+async fn reject<S>(
+    ws: &mut WebSocketStream<S>,
+    peer_device: &str,
+    reason: impl Into<String>,
+) -> DiaryxError
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let reason = reason.into();
+    let msg = PeerControl::Rejected {
+        reason: reason.clone(),
+    };
+    let _ = ws.send(Message::Text(msg.to_json().into())).await;
+    let _ = ws.close(None).await;
+    DiaryxError::Crdt(format!("Rejected peer {}: {}", peer_device, reason))
+}
+
+/// Error for an unexpected reply from the listening peer while pairing.
+fn pairing_reply_error(reply: PeerControl) -> DiaryxError {
+    match reply {
+        PeerControl::Rejected { reason } => {
+            DiaryxError::Crdt(format!("Peer rejected pairing: {}", reason))
+        }
+        other => DiaryxError::Crdt(format!("Unexpected pairing reply: {:?}", other)),
+    }
+}
+
+/// Start a SPAKE2 exchange keyed by the pairing code and bound to the
+/// workspace, returning the state and the message for the peer (base64).
+fn start_pake(code: &str, workspace_id: &str) -> (Spake2<Ed25519Group>, String) {
+    let identity = format!("diaryx-peer-sync:{}", workspace_id);
+    let (state, message) = Spake2::<Ed25519Group>::start_symmetric(
+        &Password::new(code.as_bytes()),
+        &Identity::new(identity.as_bytes()),
+    );
+    (state, BASE64.encode(message))
+}
+
+/// Finish a SPAKE2 exchange with the peer's message, returning the shared
+/// key. Both sides only get the same key if they used the same code.
+fn finish_pake(state: Spake2<Ed25519Group>, message: &str) -> Result<Vec<u8>> {
+    let message = BASE64
+        .decode(message)
+        .map_err(|_| DiaryxError::Crdt("invalid pairing message".to_string()))?;
+    state
+        .finish(&message)
+        .map_err(|_| DiaryxError::Crdt("invalid pairing message".to_string()))
+}
+
+/// Proof that we hold the shared key: an HMAC of `label` under it (base64).
+fn key_proof(key: &[u8], label: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(label);
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+/// Check the peer's proof of the shared key, in constant time.
+fn check_key_proof(key: &[u8], label: &[u8], proof: &str) -> bool {
+    let Ok(proof) = BASE64.decode(proof) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(label);
+    mac.verify_slice(&proof).is_ok()
+}
+
+/// Keys and counters authenticating the frames of a paired session.
+struct FrameAuth {
+    send_key: Vec<u8>,
+    receive_key: Vec<u8>,
+    sent: u64,
+    received: u64,
+}
+
+impl FrameAuth {
+    /// Derive the session keys from the shared SPAKE2 key. `send_label` and
+    /// `receive_label` are swapped on the other side.
+    fn new(key: &[u8], send_label: &[u8], receive_label: &[u8]) -> Self {
+        Self {
+            send_key: frame_key(key, send_label),
+            receive_key: frame_key(key, receive_label),
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Authenticate the next outgoing frame.
+    fn seal(&mut self, kind: u8, body: &[u8]) -> Vec<u8> {
+        let tag = frame_tag(&self.send_key, self.sent, kind, body).finalize();
+        self.sent += 1;
+
+        let mut frame = Vec::with_capacity(1 + body.len() + FRAME_TAG_LEN);
+        frame.push(kind);
+        frame.extend_from_slice(body);
+        frame.extend_from_slice(&tag.into_bytes());
+        frame
+    }
+
+    /// Check the next incoming frame, returning its kind and body.
+    fn open<'a>(&mut self, frame: &'a [u8]) -> Result<(u8, &'a [u8])> {
+        let invalid = || DiaryxError::Crdt("Peer sent an unauthenticated frame".to_string());
+        if frame.len() < 1 + FRAME_TAG_LEN {
+            return Err(invalid());
+        }
+        let (kind, rest) = (frame[0], &frame[1..]);
+        let (body, tag) = rest.split_at(rest.len() - FRAME_TAG_LEN);
+        frame_tag(&self.receive_key, self.received, kind, body)
+            .verify_slice(tag)
+            .map_err(|_| invalid())?;
+        self.received += 1;
+        Ok((kind, body))
+    }
+}
+
+/// Session key for one direction: an HMAC of `label` under the shared key.
+fn frame_key(key: &[u8], label: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(label);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// HMAC over a frame's position, kind and body.
+fn frame_tag(key: &[u8], counter: u64, kind: u8, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    mac.update(&[kind]);
+    mac.update(body);
+    mac
+}
+
+/// Wait for the next control message, skipping pings.
+async fn next_control<S>(ws: &mut WebSocketStream<S>) -> Result<PeerControl>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    while let Some(msg) = ws.next().await {
+        match msg.map_err(ws_error)? {
+            Message::Text(text) => {
+                return PeerControl::from_json(&text).ok_or_else(|| {
+                    DiaryxError::Crdt(format!("Invalid control message: {}", text))
+                });
+            }
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+    Err(DiaryxError::Crdt("Peer closed the connection".to_string()))
+}
+
+/// Send an authenticated frame.
+async fn send_frame<S>(
+    ws: &mut WebSocketStream<S>,
+    frames: &mut FrameAuth,
+    kind: u8,
+    body: &[u8],
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    ws.send(Message::Binary(frames.seal(kind, body).into()))
+        .await
+        .map_err(ws_error)
+}
+
+/// Exchange sync frames until both peers have sent `Done`.
+async fn run_exchange<S, FS>(
+    mut ws: WebSocketStream<S>,
+    mut frames: FrameAuth,
+    session: &PeerSyncSession<FS>,
+    peer_device: String,
+) -> Result<PeerSyncSummary>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    FS: AsyncFileSystem,
+{
+    let mut summary = PeerSyncSummary {
+        peer_device,
+        ..Default::default()
+    };
+
+    for frame in session.initial_messages().await {
+        send_frame(&mut ws, &mut frames, FRAME_SYNC, &frame).await?;
+    }
+
+    let mut sent_done = false;
+    let mut peer_done = false;
+
+    while !(sent_done && peer_done) {
+        if !sent_done && session.is_complete() {
+            let done = PeerControl::Done.to_json();
+            send_frame(&mut ws, &mut frames, FRAME_CONTROL, done.as_bytes()).await?;
+            sent_done = true;
+            continue;
+        }
+
+        let msg = match tokio::time::timeout(IDLE_TIMEOUT, ws.next()).await {
+            Ok(Some(msg)) => msg.map_err(ws_error)?,
+            Ok(None) => break,
+            Err(_) => {
+                log::warn!(
+                    "[LanPeer] Session idle, {} handshakes unanswered",
+                    session.pending_count()
+                );
+                break;
+            }
+        };
+
+        let data = match msg {
+            Message::Binary(data) => data,
+            Message::Text(_) => {
+                let _ = ws.close(None).await;
+                return Err(DiaryxError::Crdt(
+                    "Peer sent an unauthenticated frame".to_string(),
+                ));
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let (kind, body) = match frames.open(&data) {
+            Ok(opened) => opened,
+            Err(e) => {
+                let _ = ws.close(None).await;
+                return Err(e);
+            }
+        };
+
+        match kind {
+            FRAME_SYNC => {
+                let result = session.handle_frame(body).await?;
+                summary.files_changed += result.changed_files.len();
+                summary.bodies_changed += result.body_changed.len();
+                for response in result.responses {
+                    send_frame(&mut ws, &mut frames, FRAME_SYNC, &response).await?;
+                }
+            }
+            FRAME_CONTROL => match std::str::from_utf8(body)
+                .ok()
+                .and_then(PeerControl::from_json)
+            {
+                Some(PeerControl::Done) => peer_done = true,
+                Some(other) => log::debug!("[LanPeer] Ignoring control message: {:?}", other),
+                None => log::debug!("[LanPeer] Ignoring unknown control frame"),
+            },
+            other => log::debug!("[LanPeer] Ignoring frame of kind {}", other),
+        }
+    }
+
+    let _ = ws.close(None).await;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::{
+        BodyDocManager, CrdtStorage, MAX_PAIRING_ATTEMPTS, MemoryStorage, RustSyncManager,
+        SyncHandler, WorkspaceCrdt,
+    };
+    use crate::fs::SyncToAsyncFs;
+    use crate::test_utils::MockFileSystem;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    fn create_session() -> PeerSyncSession<SyncToAsyncFs<MockFileSystem>> {
+        let storage: Arc<dyn CrdtStorage> = Arc::new(MemoryStorage::new());
+        let manager = Arc::new(RustSyncManager::new(
+            Arc::new(WorkspaceCrdt::new(Arc::clone(&storage))),
+            Arc::new(BodyDocManager::new(storage)),
+            Arc::new(SyncHandler::new(SyncToAsyncFs::new(MockFileSystem::new()))),
+        ));
+        PeerSyncSession::new("ws", manager).with_write_to_disk(false)
+    }
+
+    /// Pair a listener showing `pairing` with a peer that entered `entered`
+    async fn pair(
+        pairing: &PairingCode,
+        entered: &str,
+    ) -> (Result<PeerSyncSummary>, Result<PeerSyncSummary>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (listening, connecting) = (create_session(), create_session());
+        tokio::join!(
+            async {
+                let (stream, _) = listener.accept().await.unwrap();
+                accept_peer(stream, pairing, "laptop", &listening).await
+            },
+            connect_peer(addr, entered, "desktop", &connecting)
+        )
+    }
+
+    #[tokio::test]
+    async fn test_peers_pair_with_code() {
+        let pairing = PairingCode::from_code("123456");
+        let (accepted, connected) = pair(&pairing, " 123456 ").await;
+        assert_eq!(accepted.unwrap().peer_device, "desktop");
+        assert_eq!(connected.unwrap().peer_device, "laptop");
+        assert!(!pairing.is_active());
+    }
+
+    #[tokio::test]
+    async fn test_wrong_code_rejected_and_counted() {
+        let pairing = PairingCode::from_code("123456");
+        for _ in 0..MAX_PAIRING_ATTEMPTS {
+            let (accepted, connected) = pair(&pairing, "654321").await;
+            assert!(accepted.is_err());
+            assert!(connected.is_err());
+        }
+        assert!(!pairing.is_active());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_silent_connection_times_out() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let pairing = PairingCode::from_code("123456");
+        let session = create_session();
+
+        // Open the connection but never send the WebSocket upgrade
+        let _silent = TcpStream::connect(addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let accepted = accept_peer(stream, &pairing, "laptop", &session).await;
+        assert!(accepted.is_err());
+        assert!(pairing.is_active());
+    }
+
+    #[test]
+    fn test_key_proofs() {
+        let (a, message_a) = start_pake("123456", "ws");
+        let (b, message_b) = start_pake("123456", "ws");
+        let (c, _) = start_pake("000000", "ws");
+        let key_a = finish_pake(a, &message_b).unwrap();
+        let key_b = finish_pake(b, &message_a).unwrap();
+        let key_c = finish_pake(c, &message_a).unwrap();
+
+        let proof = key_proof(&key_a, CONNECTOR_PROOF);
+        assert!(check_key_proof(&key_b, CONNECTOR_PROOF, &proof));
+        assert!(!check_key_proof(&key_b, LISTENER_PROOF, &proof));
+        assert!(!check_key_proof(&key_c, CONNECTOR_PROOF, &proof));
+        assert!(!proof.contains("123456"));
+    }
+
+    #[test]
+    fn test_tampered_frames_rejected() {
+        let key = b"shared key";
+        let mut listener = FrameAuth::new(key, LISTENER_FRAMES, CONNECTOR_FRAMES);
+        let mut connector = FrameAuth::new(key, CONNECTOR_FRAMES, LISTENER_FRAMES);
+
+        let first = connector.seal(FRAME_SYNC, b"update");
+        let second = connector.seal(FRAME_CONTROL, b"{\"type\":\"done\"}");
+
+        // Altered body
+        let mut tampered = first.clone();
+        tampered[1] ^= 1;
+        assert!(listener.open(&tampered).is_err());
+        // Altered kind
+        let mut tampered = first.clone();
+        tampered[0] = FRAME_CONTROL;
+        assert!(listener.open(&tampered).is_err());
+        // Out of order
+        assert!(listener.open(&second).is_err());
+        // Reflected back from the listener's direction, or under another key
+        let reflected =
+            FrameAuth::new(key, LISTENER_FRAMES, CONNECTOR_FRAMES).seal(FRAME_SYNC, b"update");
+        assert!(listener.open(&reflected).is_err());
+        let mut other = FrameAuth::new(b"other key", CONNECTOR_FRAMES, LISTENER_FRAMES);
+        assert!(listener.open(&other.seal(FRAME_SYNC, b"update")).is_err());
+        assert!(listener.open(b"short").is_err());
+
+        assert_eq!(listener.open(&first).unwrap(), (FRAME_SYNC, &b"update"[..]));
+        // Replayed
+        assert!(listener.open(&first).is_err());
+        assert_eq!(listener.open(&second).unwrap().0, FRAME_CONTROL);
+    }
+}
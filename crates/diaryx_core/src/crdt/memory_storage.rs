@@ -9,7 +9,7 @@ use std::sync::{Arc, RwLock};
 use yrs::{Doc, ReadTxn, Transact, Update, updates::decoder::Decode};
 
 use super::storage::{CrdtStorage, StorageResult};
-use super::types::{CrdtUpdate, UpdateOrigin};
+use super::types::{CrdtUpdate, PendingMessage, UpdateOrigin};
 
 /// Threshold for triggering auto-compaction (number of updates)
 const AUTO_COMPACT_THRESHOLD: usize = 1000;
@@ -35,6 +35,9 @@ pub struct MemoryStorage {
 
     /// Counter for generating update IDs
     next_id: Arc<RwLock<i64>>,
+
+    /// Outgoing messages waiting to be sent (oldest first)
+    pending: Arc<RwLock<Vec<PendingMessage>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -250,6 +253,33 @@ impl CrdtStorage for MemoryStorage {
         }
         Ok(())
     }
+
+    fn enqueue_pending(&self, doc_name: &str, is_body: bool, message: &[u8]) -> StorageResult<i64> {
+        let id = self.next_update_id();
+        let mut pending = self.pending.write().unwrap();
+        pending.push(PendingMessage {
+            id,
+            doc_name: doc_name.to_string(),
+            is_body,
+            message: message.to_vec(),
+            queued_at: chrono::Utc::now().timestamp_millis(),
+        });
+        Ok(id)
+    }
+
+    fn list_pending(&self) -> StorageResult<Vec<PendingMessage>> {
+        Ok(self.pending.read().unwrap().clone())
+    }
+
+    fn remove_pending(&self, ids: &[i64]) -> StorageResult<()> {
+        let mut pending = self.pending.write().unwrap();
+        pending.retain(|msg| !ids.contains(&msg.id));
+        Ok(())
+    }
+
+    fn pending_count(&self) -> StorageResult<usize> {
+        Ok(self.pending.read().unwrap().len())
+    }
 }
 
 #[cfg(test)]
@@ -533,4 +563,22 @@ mod tests {
         let result = storage.clear_updates("nonexistent");
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_pending_queue() {
+        let storage = MemoryStorage::new();
+        let first = storage.enqueue_pending("workspace", false, b"ws").unwrap();
+        let second = storage.enqueue_pending("a.md", true, b"body").unwrap();
+        assert_eq!(storage.pending_count().unwrap(), 2);
+
+        let pending = storage.list_pending().unwrap();
+        assert_eq!(pending[0].id, first);
+        assert_eq!(pending[1].doc_name, "a.md");
+        assert!(pending[1].is_body);
+
+        storage.remove_pending(&[first]).unwrap();
+        let pending = storage.list_pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, second);
+    }
 }
@@ -1,42 +1,74 @@
 #![doc = include_str!(concat!(env!("OUT_DIR"), "/crdt_README.md"))]
 
+mod awareness;
 mod body_doc;
 mod body_doc_manager;
 mod history;
+#[cfg(all(not(target_arch = "wasm32"), feature = "lan-sync"))]
+mod lan_peer;
 mod memory_storage;
+mod outbox;
+mod peer_sync;
+mod share_scope;
 #[cfg(all(not(target_arch = "wasm32"), feature = "crdt-sqlite"))]
 mod sqlite_storage;
 mod storage;
 mod sync;
 mod sync_client;
+mod sync_error;
 mod sync_handler;
 mod sync_manager;
 #[cfg(all(not(target_arch = "wasm32"), feature = "native-sync"))]
 mod tokio_transport;
 mod transport;
 mod types;
+mod update_bundle;
 mod workspace_doc;
 
+pub use awareness::{
+    AWARENESS_RENEW_INTERVAL_MS, AWARENESS_TIMEOUT_MS, Awareness, AwarenessPeer, CursorRange,
+    PeerPresence, decode_awareness_control, encode_awareness_control,
+};
 pub use body_doc::BodyDoc;
 pub use body_doc_manager::BodyDocManager;
 pub use history::{ChangeType, FileDiff, HistoryEntry, HistoryManager};
+#[cfg(all(not(target_arch = "wasm32"), feature = "lan-sync"))]
+pub use lan_peer::{
+    DEFAULT_PEER_PORT, DiscoveredPeer, PEER_SERVICE_TYPE, PeerAdvertisement, PeerSyncSummary,
+    accept_peer, advertise_peer, connect_peer, discover_peers,
+};
 pub use memory_storage::MemoryStorage;
+pub use outbox::{PendingUpdate, pending_updates};
+pub use peer_sync::{
+    MAX_PAIRING_ATTEMPTS, PairingCode, PeerControl, PeerFrameResult, PeerSyncSession,
+};
+pub use share_scope::{ResolvedScope, ScopeEntry, SharePermission, ShareScope};
 #[cfg(all(not(target_arch = "wasm32"), feature = "crdt-sqlite"))]
 pub use sqlite_storage::SqliteStorage;
 pub use storage::{CrdtStorage, StorageResult};
 pub use sync::{
-    BodySyncProtocol, DocIdKind, SyncMessage, SyncProtocol, format_body_doc_id,
-    format_workspace_doc_id, frame_body_message, frame_message_v2, parse_doc_id,
-    unframe_body_message, unframe_message_v2,
+    AwarenessEntry, AwarenessUpdate, BodySyncProtocol, DocIdKind, SyncMessage, SyncProtocol,
+    format_body_doc_id, format_workspace_doc_id, frame_body_message, frame_message_v2,
+    parse_doc_id, unframe_body_message, unframe_message_v2,
 };
 pub use sync_client::{
     OutgoingSender, OutgoingSyncMessage, SyncClient, SyncClientConfig, SyncEvent, SyncEventBridge,
     SyncEventCallback, create_sync_event_bridge,
 };
+pub use sync_error::{
+    ERROR_CHECK_INTERVAL_MS, SyncErrorCode, SyncServerError, decode_sync_error_control,
+    encode_error_check_control, encode_sync_error_control,
+};
 pub use sync_handler::{GuestConfig, SyncHandler};
 pub use sync_manager::{BodySyncResult, RustSyncManager, SyncMessageResult};
 #[cfg(all(not(target_arch = "wasm32"), feature = "native-sync"))]
 pub use tokio_transport::TokioTransport;
-pub use transport::{ConnectionStatus, MessageCallback, StatusCallback, SyncConfig, SyncTransport};
-pub use types::{BinaryRef, CrdtUpdate, FileMetadata, UpdateOrigin};
+pub use transport::{
+    ConnectionStatus, MessageCallback, StatusCallback, SyncConfig, SyncTransport, TextCallback,
+};
+pub use types::{BinaryRef, CrdtUpdate, FileMetadata, PendingMessage, UpdateOrigin};
+pub use update_bundle::{
+    BundleHeader, BundleImportResult, UPDATE_BUNDLE_EXTENSION, UpdateBundle, export_update_bundle,
+    import_update_bundle, received_state,
+};
 pub use workspace_doc::WorkspaceCrdt;
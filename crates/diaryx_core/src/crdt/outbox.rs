@@ -0,0 +1,176 @@
+//! Replaying the offline outbox.
+//!
+//! Sync messages that could not be sent are kept in the storage backend's
+//! outbox (`CrdtStorage::enqueue_pending`) so they survive a restart. When
+//! the connection is back, [`pending_updates`] merges each document's queued
+//! updates into one `Update` message; the caller sends them and removes the
+//! entries with `CrdtStorage::remove_pending` once they're out.
+
+use super::storage::{CrdtStorage, StorageResult};
+use super::sync::SyncMessage;
+use super::types::PendingMessage;
+
+/// A document's queued updates, merged into one message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingUpdate {
+    /// Document name ("workspace" for metadata, file path for body).
+    pub doc_name: String,
+    /// Whether this is a body doc (true) or workspace (false).
+    pub is_body: bool,
+    /// Encoded `Update` message with every queued change.
+    pub message: Vec<u8>,
+    /// Outbox entries to remove once `message` was sent.
+    pub ids: Vec<i64>,
+}
+
+/// Merge the outbox into one update per document, in first-queued order.
+///
+/// Entries with nothing to replay (e.g. only stale handshakes) are removed
+/// right away.
+pub fn pending_updates(storage: &dyn CrdtStorage) -> StorageResult<Vec<PendingUpdate>> {
+    let mut updates = Vec::new();
+    for (doc_name, is_body, entries) in group_pending(storage.list_pending()?) {
+        let ids: Vec<i64> = entries.iter().map(|e| e.id).collect();
+        match merge_pending_updates(&entries) {
+            Some(message) => updates.push(PendingUpdate {
+                doc_name,
+                is_body,
+                message,
+                ids,
+            }),
+            None => storage.remove_pending(&ids)?,
+        }
+    }
+    Ok(updates)
+}
+
+/// Group outbox entries by document, keeping first-queued order.
+fn group_pending(pending: Vec<PendingMessage>) -> Vec<(String, bool, Vec<PendingMessage>)> {
+    let mut groups: Vec<(String, bool, Vec<PendingMessage>)> = Vec::new();
+    for entry in pending {
+        match groups
+            .iter_mut()
+            .find(|(name, is_body, _)| *name == entry.doc_name && *is_body == entry.is_body)
+        {
+            Some((_, _, entries)) => entries.push(entry),
+            None => groups.push((entry.doc_name.clone(), entry.is_body, vec![entry])),
+        }
+    }
+    groups
+}
+
+/// Merge the updates in a document's outbox entries into one Update message.
+///
+/// Handshake messages are dropped: they are stale after a reconnect and
+/// the fresh handshake replaces them.
+fn merge_pending_updates(entries: &[PendingMessage]) -> Option<Vec<u8>> {
+    let updates: Vec<Vec<u8>> = entries
+        .iter()
+        .filter_map(|entry| SyncMessage::decode_all(&entry.message).ok())
+        .flatten()
+        .filter_map(|msg| match msg {
+            SyncMessage::Update(update) | SyncMessage::SyncStep2(update) => Some(update),
+            SyncMessage::SyncStep1(_) => None,
+        })
+        .collect();
+
+    match updates.len() {
+        0 => None,
+        1 => Some(SyncMessage::Update(updates.into_iter().next()?).encode()),
+        _ => {
+            let refs: Vec<&[u8]> = updates.iter().map(Vec::as_slice).collect();
+            match yrs::merge_updates_v1(refs) {
+                Ok(merged) => Some(SyncMessage::Update(merged).encode()),
+                Err(e) => {
+                    log::warn!("[Outbox] Failed to merge queued updates: {}", e);
+                    // Fall back to sending each update in sequence
+                    Some(
+                        updates
+                            .into_iter()
+                            .flat_map(|u| SyncMessage::Update(u).encode())
+                            .collect(),
+                    )
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::MemoryStorage;
+    use crate::test_utils::body_update;
+
+    #[test]
+    fn test_merge_pending_updates() {
+        let entries: Vec<PendingMessage> = ["a", "b"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| PendingMessage {
+                id: i as i64,
+                doc_name: "a.md".to_string(),
+                is_body: true,
+                message: SyncMessage::Update(body_update(text)).encode(),
+                queued_at: 0,
+            })
+            .collect();
+
+        let merged = merge_pending_updates(&entries).unwrap();
+        let messages = SyncMessage::decode_all(&merged).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0], SyncMessage::Update(_)));
+
+        // Handshakes alone are not replayed
+        let stale = PendingMessage {
+            message: SyncMessage::SyncStep1(vec![0]).encode(),
+            ..entries[0].clone()
+        };
+        assert!(merge_pending_updates(&[stale]).is_none());
+    }
+
+    #[test]
+    fn test_group_pending_keeps_order() {
+        let entry = |id: i64, doc_name: &str, is_body: bool| PendingMessage {
+            id,
+            doc_name: doc_name.to_string(),
+            is_body,
+            message: Vec::new(),
+            queued_at: 0,
+        };
+        let groups = group_pending(vec![
+            entry(1, "b.md", true),
+            entry(2, "workspace", false),
+            entry(3, "b.md", true),
+        ]);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "b.md");
+        assert_eq!(groups[0].2.len(), 2);
+        assert_eq!(groups[1].0, "workspace");
+    }
+
+    #[test]
+    fn test_pending_updates_drops_stale_entries() {
+        let storage = MemoryStorage::new();
+        storage
+            .enqueue_pending(
+                "a.md",
+                true,
+                &SyncMessage::Update(body_update("a")).encode(),
+            )
+            .unwrap();
+        storage
+            .enqueue_pending(
+                "workspace",
+                false,
+                &SyncMessage::SyncStep1(vec![0]).encode(),
+            )
+            .unwrap();
+
+        let pending = pending_updates(&storage).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].doc_name, "a.md");
+        assert_eq!(storage.pending_count().unwrap(), 1);
+    }
+}
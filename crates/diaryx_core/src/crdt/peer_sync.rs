@@ -0,0 +1,582 @@
+//! Peer-to-peer sync sessions for syncing two workspaces without a server.
+//!
+//! This module provides `PeerSyncSession`, which runs the Y-sync handshake
+//! directly between two Diaryx instances (for example two CLI or desktop
+//! installs on the same LAN). It reuses the v2 wire format
+//! (`frame_message_v2`) and delegates all CRDT work to `RustSyncManager`,
+//! so a peer looks exactly like the sync server from the manager's
+//! point of view.
+//!
+//! # Handshake
+//!
+//! Unlike the client/server flow, both peers initiate:
+//!
+//! 1. Each side sends `SyncStep1` for the workspace and every known body doc
+//! 2. Each side answers every `SyncStep1` with exactly one `SyncStep2`
+//! 3. When a workspace update reveals new files, their bodies are requested
+//! 4. A peer is done once every `SyncStep1` it sent has been answered
+//!
+//! Answering with `SyncStep2` only (never `SyncStep2 + SyncStep1`) keeps
+//! the two sides from ping-ponging state vectors forever.
+//!
+//! # Pairing
+//!
+//! Before any CRDT data is exchanged, both peers prove they know the one-time
+//! `PairingCode` shown by the listening peer, without sending it: they run a
+//! SPAKE2 key exchange keyed by the code and then confirm the shared key in
+//! both directions. Control messages (`PeerControl`) are JSON. The shared
+//! key then authenticates every later frame (see `lan_peer`).
+//!
+//! Paths of body documents and workspace files come from the peer, so they
+//! must stay inside the workspace before they are read from or written to
+//! disk (see `is_safe_sync_path`).
+//!
+//! # Example
+//!
+//! ```ignore
+//! let session = PeerSyncSession::new("default", sync_manager);
+//!
+//! for frame in session.initial_messages().await {
+//!     transport.send(&frame).await?;
+//! }
+//!
+//! while let Some(frame) = transport.recv().await {
+//!     let result = session.handle_frame(&frame).await?;
+//!     for response in result.responses {
+//!         transport.send(&response).await?;
+//!     }
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::sync::{
+    DocIdKind, SyncMessage, format_body_doc_id, format_workspace_doc_id, frame_message_v2,
+    parse_doc_id, unframe_message_v2,
+};
+use super::sync_handler::is_safe_sync_path;
+use super::sync_manager::RustSyncManager;
+use crate::error::{DiaryxError, Result};
+use crate::fs::AsyncFileSystem;
+
+/// Number of failed attempts after which a pairing code is invalidated.
+pub const MAX_PAIRING_ATTEMPTS: u32 = 5;
+
+// ============================================================================
+// Pairing
+// ============================================================================
+
+/// One-time code that a peer must present before syncing.
+///
+/// The code is consumed on first successful use. It is also invalidated after
+/// `MAX_PAIRING_ATTEMPTS` wrong guesses, so a six-digit code cannot be brute
+/// forced by another device on the network.
+#[derive(Debug)]
+pub struct PairingCode {
+    state: Mutex<PairingState>,
+}
+
+#[derive(Debug)]
+struct PairingState {
+    code: Option<String>,
+    failed_attempts: u32,
+}
+
+impl PairingCode {
+    /// Generate a new random six-digit pairing code.
+    pub fn generate() -> Self {
+        let n = uuid::Uuid::new_v4().as_u128() % 1_000_000;
+        Self::from_code(format!("{:06}", n))
+    }
+
+    /// Create a pairing code from a known value.
+    pub fn from_code(code: impl Into<String>) -> Self {
+        Self {
+            state: Mutex::new(PairingState {
+                code: Some(code.into()),
+                failed_attempts: 0,
+            }),
+        }
+    }
+
+    /// Get the code to display, or `None` once it has been used up.
+    pub fn code(&self) -> Option<String> {
+        self.state.lock().unwrap().code.clone()
+    }
+
+    /// Check whether the code can still be redeemed.
+    pub fn is_active(&self) -> bool {
+        self.state.lock().unwrap().code.is_some()
+    }
+
+    /// Try to redeem the code with a proof of it rather than the code itself.
+    ///
+    /// `proof_matches` is given the current code; returns true (and consumes
+    /// the code) if it accepts. A rejected proof counts as a failed attempt.
+    pub fn redeem_with(&self, proof_matches: impl FnOnce(&str) -> bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(expected) = state.code.as_deref() else {
+            return false;
+        };
+
+        if proof_matches(expected) {
+            state.code = None;
+            return true;
+        }
+
+        state.failed_attempts += 1;
+        if state.failed_attempts >= MAX_PAIRING_ATTEMPTS {
+            log::warn!("[PeerSync] Too many failed pairing attempts, invalidating code");
+            state.code = None;
+        }
+        false
+    }
+}
+
+/// Control message exchanged between peers (JSON).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PeerControl {
+    /// Sent by the connecting peer to request pairing.
+    Hello {
+        /// Human-readable name of the connecting device.
+        device_name: String,
+        /// Workspace ID the connecting peer wants to sync.
+        workspace_id: String,
+        /// SPAKE2 message keyed by the pairing code (base64).
+        pake: String,
+    },
+    /// The listening peer's half of the key exchange.
+    Challenge {
+        /// SPAKE2 message keyed by the pairing code (base64).
+        pake: String,
+    },
+    /// The connecting peer's proof of the shared key.
+    Confirm {
+        /// Key confirmation from the connecting peer (base64).
+        proof: String,
+    },
+    /// Pairing accepted; binary sync frames may follow.
+    Welcome {
+        /// Human-readable name of the listening device.
+        device_name: String,
+        /// Key confirmation from the listening peer (base64).
+        proof: String,
+    },
+    /// Pairing refused; the connection will be closed.
+    Rejected {
+        /// Why the pairing was refused.
+        reason: String,
+    },
+    /// The sender has received answers to all of its SyncStep1 messages.
+    Done,
+}
+
+impl PeerControl {
+    /// Serialize to the JSON text sent over the wire.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Parse a control message, returning `None` for unknown payloads.
+    pub fn from_json(text: &str) -> Option<Self> {
+        serde_json::from_str(text).ok()
+    }
+}
+
+// ============================================================================
+// Session
+// ============================================================================
+
+/// Result of handling one frame from a peer.
+#[derive(Debug, Default)]
+pub struct PeerFrameResult {
+    /// Framed messages to send back to the peer.
+    pub responses: Vec<Vec<u8>>,
+    /// Workspace files whose metadata changed.
+    pub changed_files: Vec<String>,
+    /// Files whose body content changed.
+    pub body_changed: Vec<String>,
+}
+
+/// A Y-sync session with a single peer.
+///
+/// Tracks which handshakes are still waiting for an answer so callers can
+/// tell when the initial exchange is finished.
+pub struct PeerSyncSession<FS: AsyncFileSystem> {
+    workspace_id: String,
+    sync_manager: Arc<RustSyncManager<FS>>,
+    write_to_disk: bool,
+    /// Doc IDs we sent SyncStep1 for and have not seen SyncStep2 for yet.
+    awaiting: Mutex<HashSet<String>>,
+    /// Body paths we already requested from the peer.
+    requested_bodies: Mutex<HashSet<String>>,
+}
+
+impl<FS: AsyncFileSystem> PeerSyncSession<FS> {
+    /// Create a new session for the given workspace.
+    pub fn new(workspace_id: impl Into<String>, sync_manager: Arc<RustSyncManager<FS>>) -> Self {
+        Self {
+            workspace_id: workspace_id.into(),
+            sync_manager,
+            write_to_disk: true,
+            awaiting: Mutex::new(HashSet::new()),
+            requested_bodies: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Set whether synced changes are written to disk (default: true).
+    pub fn with_write_to_disk(mut self, write: bool) -> Self {
+        self.write_to_disk = write;
+        self
+    }
+
+    /// Get the workspace ID used for doc IDs.
+    pub fn workspace_id(&self) -> &str {
+        &self.workspace_id
+    }
+
+    /// Build the SyncStep1 frames that open the session.
+    ///
+    /// Loads body content from disk first so the peer receives real content
+    /// rather than an empty document.
+    pub async fn initial_messages(&self) -> Vec<Vec<u8>> {
+        let ws_doc_id = format_workspace_doc_id(&self.workspace_id);
+        self.awaiting.lock().unwrap().insert(ws_doc_id.clone());
+
+        let mut frames = vec![frame_message_v2(
+            &ws_doc_id,
+            &self.sync_manager.create_workspace_sync_step1(),
+        )];
+
+        for path in self.sync_manager.get_all_file_paths() {
+            if let Some(frame) = self.request_body(&path).await {
+                frames.push(frame);
+            }
+        }
+
+        frames
+    }
+
+    /// Handle one framed message from the peer.
+    pub async fn handle_frame(&self, data: &[u8]) -> Result<PeerFrameResult> {
+        let (doc_id, payload) = unframe_message_v2(data)
+            .ok_or_else(|| DiaryxError::Crdt("Invalid peer sync frame".to_string()))?;
+
+        match parse_doc_id(&doc_id) {
+            Some(DocIdKind::Workspace(_)) => self.handle_workspace_payload(&doc_id, &payload).await,
+            Some(DocIdKind::Body { file_path, .. }) => {
+                if !is_safe_sync_path(&file_path) {
+                    return Err(DiaryxError::Crdt(format!(
+                        "Peer sent a body outside the workspace: {}",
+                        file_path
+                    )));
+                }
+                self.handle_body_payload(&doc_id, &file_path, &payload)
+                    .await
+            }
+            None => Err(DiaryxError::Crdt(format!("Unknown doc ID: {}", doc_id))),
+        }
+    }
+
+    /// Whether every SyncStep1 we sent has been answered.
+    pub fn is_complete(&self) -> bool {
+        self.awaiting.lock().unwrap().is_empty()
+    }
+
+    /// Number of handshakes still waiting for an answer.
+    pub fn pending_count(&self) -> usize {
+        self.awaiting.lock().unwrap().len()
+    }
+
+    async fn handle_workspace_payload(
+        &self,
+        doc_id: &str,
+        payload: &[u8],
+    ) -> Result<PeerFrameResult> {
+        let mut result = PeerFrameResult::default();
+
+        for msg in SyncMessage::decode_all(payload)? {
+            match msg {
+                SyncMessage::SyncStep1(remote_sv) => {
+                    let step2 = self.sync_manager.create_workspace_sync_step2(&remote_sv)?;
+                    result.responses.push(frame_message_v2(doc_id, &step2));
+                }
+                SyncMessage::SyncStep2(_) | SyncMessage::Update(_) => {
+                    let is_step2 = matches!(msg, SyncMessage::SyncStep2(_));
+                    let synced = self
+                        .sync_manager
+                        .handle_workspace_message(&msg.encode(), self.write_to_disk)
+                        .await?;
+                    result.changed_files.extend(synced.changed_files);
+
+                    // Request bodies for files the peer just told us about
+                    for path in self.sync_manager.get_all_file_paths() {
+                        if let Some(frame) = self.request_body(&path).await {
+                            result.responses.push(frame);
+                        }
+                    }
+
+                    if is_step2 {
+                        self.awaiting.lock().unwrap().remove(doc_id);
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn handle_body_payload(
+        &self,
+        doc_id: &str,
+        file_path: &str,
+        payload: &[u8],
+    ) -> Result<PeerFrameResult> {
+        let mut result = PeerFrameResult::default();
+
+        for msg in SyncMessage::decode_all(payload)? {
+            match msg {
+                SyncMessage::SyncStep1(remote_sv) => {
+                    let step2 = self
+                        .sync_manager
+                        .create_body_sync_step2(file_path, &remote_sv)?;
+                    result.responses.push(frame_message_v2(doc_id, &step2));
+                }
+                SyncMessage::SyncStep2(_) | SyncMessage::Update(_) => {
+                    let is_step2 = matches!(msg, SyncMessage::SyncStep2(_));
+                    let synced = self
+                        .sync_manager
+                        .handle_body_message(file_path, &msg.encode(), self.write_to_disk)
+                        .await?;
+                    if synced.content.is_some() {
+                        result.body_changed.push(file_path.to_string());
+                    }
+                    if is_step2 {
+                        self.awaiting.lock().unwrap().remove(doc_id);
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Build a body SyncStep1 frame, or `None` if already requested or the
+    /// path (which may come from the peer's workspace) leaves the workspace.
+    async fn request_body(&self, path: &str) -> Option<Vec<u8>> {
+        if !is_safe_sync_path(path) {
+            log::warn!("[PeerSync] Skipping body outside the workspace: {}", path);
+            return None;
+        }
+        if !self
+            .requested_bodies
+            .lock()
+            .unwrap()
+            .insert(path.to_string())
+        {
+            return None;
+        }
+
+        if let Err(e) = self.sync_manager.ensure_body_content_loaded(path).await {
+            log::warn!(
+                "[PeerSync] Failed to load body content for {}: {:?}",
+                path,
+                e
+            );
+        }
+
+        let doc_id = format_body_doc_id(&self.workspace_id, path);
+        self.awaiting.lock().unwrap().insert(doc_id.clone());
+        Some(frame_message_v2(
+            &doc_id,
+            &self.sync_manager.create_body_sync_step1(path),
+        ))
+    }
+}
+
+impl<FS: AsyncFileSystem> std::fmt::Debug for PeerSyncSession<FS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeerSyncSession")
+            .field("workspace_id", &self.workspace_id)
+            .field("pending", &self.pending_count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::{
+        BodyDocManager, CrdtStorage, FileMetadata, MemoryStorage, SyncHandler, WorkspaceCrdt,
+    };
+    use crate::fs::SyncToAsyncFs;
+    use crate::test_utils::MockFileSystem;
+    use futures_lite::future::block_on;
+
+    type TestSession = PeerSyncSession<SyncToAsyncFs<MockFileSystem>>;
+
+    fn create_peer() -> (TestSession, Arc<WorkspaceCrdt>, Arc<BodyDocManager>) {
+        create_peer_on(MockFileSystem::new(), false)
+    }
+
+    fn create_peer_on(
+        fs: MockFileSystem,
+        write_to_disk: bool,
+    ) -> (TestSession, Arc<WorkspaceCrdt>, Arc<BodyDocManager>) {
+        let storage: Arc<dyn CrdtStorage> = Arc::new(MemoryStorage::new());
+        let workspace_crdt = Arc::new(WorkspaceCrdt::new(Arc::clone(&storage)));
+        let body_manager = Arc::new(BodyDocManager::new(Arc::clone(&storage)));
+        let sync_handler = Arc::new(SyncHandler::new(SyncToAsyncFs::new(fs)));
+        let manager = Arc::new(RustSyncManager::new(
+            Arc::clone(&workspace_crdt),
+            Arc::clone(&body_manager),
+            sync_handler,
+        ));
+        let session = PeerSyncSession::new("ws", manager).with_write_to_disk(write_to_disk);
+        (session, workspace_crdt, body_manager)
+    }
+
+    /// Deliver frames back and forth until both sides go quiet.
+    fn exchange(a: &TestSession, b: &TestSession) {
+        let mut to_b = block_on(a.initial_messages());
+        let mut to_a = block_on(b.initial_messages());
+
+        for _ in 0..20 {
+            if to_a.is_empty() && to_b.is_empty() {
+                break;
+            }
+            let mut next_to_a = Vec::new();
+            for frame in to_b.drain(..) {
+                next_to_a.extend(block_on(b.handle_frame(&frame)).unwrap().responses);
+            }
+            let mut next_to_b = Vec::new();
+            for frame in to_a.drain(..) {
+                next_to_b.extend(block_on(a.handle_frame(&frame)).unwrap().responses);
+            }
+            to_a = next_to_a;
+            to_b = next_to_b;
+        }
+    }
+
+    #[test]
+    fn test_pairing_code_is_one_time() {
+        let code = PairingCode::from_code("123456");
+        assert!(code.is_active());
+        assert!(!code.redeem_with(|_| false));
+        assert!(code.is_active());
+        assert!(code.redeem_with(|expected| expected == "123456"));
+        assert!(!code.is_active());
+        assert!(!code.redeem_with(|_| true));
+    }
+
+    #[test]
+    fn test_pairing_code_invalidated_after_failed_attempts() {
+        let code = PairingCode::from_code("123456");
+        for _ in 0..MAX_PAIRING_ATTEMPTS {
+            assert!(!code.redeem_with(|_| false));
+        }
+        assert!(!code.redeem_with(|_| true));
+        assert!(code.code().is_none());
+    }
+
+    #[test]
+    fn test_generated_pairing_code_format() {
+        let code = PairingCode::generate().code().unwrap();
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn test_peer_control_json_roundtrip() {
+        let hello = PeerControl::Hello {
+            device_name: "laptop".to_string(),
+            workspace_id: "ws".to_string(),
+            pake: "AAEC".to_string(),
+        };
+        let json = hello.to_json();
+        assert!(json.contains(r#""type":"hello""#));
+        assert_eq!(PeerControl::from_json(&json), Some(hello));
+        assert_eq!(
+            PeerControl::from_json(r#"{"type":"done"}"#),
+            Some(PeerControl::Done)
+        );
+        assert_eq!(PeerControl::from_json("garbage"), None);
+    }
+
+    #[test]
+    fn test_peers_exchange_workspace_and_bodies() {
+        let (a, ws_a, bodies_a) = create_peer();
+        let (b, ws_b, bodies_b) = create_peer();
+
+        ws_a.set_file("a.md", FileMetadata::new(Some("From A".to_string())))
+            .unwrap();
+        bodies_a.get_or_create("a.md").set_body("Body A").unwrap();
+        ws_b.set_file("b.md", FileMetadata::new(Some("From B".to_string())))
+            .unwrap();
+        bodies_b.get_or_create("b.md").set_body("Body B").unwrap();
+
+        exchange(&a, &b);
+
+        assert!(a.is_complete(), "A still waiting on {}", a.pending_count());
+        assert!(b.is_complete(), "B still waiting on {}", b.pending_count());
+        assert!(ws_a.get_file("b.md").is_some());
+        assert!(ws_b.get_file("a.md").is_some());
+        assert_eq!(bodies_a.get_or_create("b.md").get_body(), "Body B");
+        assert_eq!(bodies_b.get_or_create("a.md").get_body(), "Body A");
+    }
+
+    #[test]
+    fn test_peers_with_empty_workspaces_complete() {
+        let (a, _, _) = create_peer();
+        let (b, _, _) = create_peer();
+
+        exchange(&a, &b);
+
+        assert!(a.is_complete());
+        assert!(b.is_complete());
+    }
+
+    #[test]
+    fn test_body_paths_outside_workspace_rejected() {
+        let (a, ws_a, _) = create_peer();
+        for path in ["../outside.md", "/etc/passwd", "notes\\..\\..\\x.md"] {
+            let step1 = SyncMessage::SyncStep1(Vec::new()).encode();
+            let framed = frame_message_v2(&format_body_doc_id("ws", path), &step1);
+            assert!(block_on(a.handle_frame(&framed)).is_err(), "{}", path);
+
+            // Nor is a body requested for such a path in the peer's workspace
+            ws_a.set_file(path, FileMetadata::new(None)).unwrap();
+        }
+        let frames = block_on(a.initial_messages());
+        assert_eq!(frames.len(), 1);
+        assert!(is_safe_sync_path("notes/a.md"));
+    }
+
+    #[test]
+    fn test_workspace_files_outside_workspace_not_written() {
+        let fs = MockFileSystem::new();
+        let (a, _, _) = create_peer_on(fs.clone(), true);
+        let (b, ws_b, _) = create_peer();
+        for path in ["notes/a.md", "../outside.md", "notes\\..\\..\\x.md"] {
+            ws_b.set_file(path, FileMetadata::new(None)).unwrap();
+        }
+
+        exchange(&a, &b);
+
+        assert!(fs.get_content("notes/a.md").is_some());
+        assert!(fs.get_content("../outside.md").is_none());
+        assert!(fs.get_content("notes\\..\\..\\x.md").is_none());
+    }
+
+    #[test]
+    fn test_invalid_frame_is_error() {
+        let (a, _, _) = create_peer();
+        assert!(block_on(a.handle_frame(&[])).is_err());
+        let framed = frame_message_v2("nonsense", &[0, 0, 1, 0]);
+        assert!(block_on(a.handle_frame(&framed)).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+//! Path scoping for share sessions.
+//!
+//! A share session normally exposes the whole workspace. A [`ShareScope`]
+//! narrows it to specific files or subtrees, each with its own permission:
+//!
+//! ```json
+//! {"entries": [
+//!   {"path": "projects/index.md", "permission": "write", "include_contents": true},
+//!   {"path": "README.md", "permission": "read"}
+//! ]}
+//! ```
+//!
+//! With `include_contents`, the entry covers the index and every file whose
+//! `part_of` chain leads back to it, so files added to the subtree later are
+//! shared automatically. When entries overlap, `write` wins.
+//!
+//! Scopes are stored as paths and resolved against the current workspace
+//! CRDT with [`ShareScope::resolve`]. The sync server uses the result to
+//! reject updates outside the scope and to build the filtered workspace
+//! state sent to guests ([`ResolvedScope::filter_workspace`]).
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::memory_storage::MemoryStorage;
+use super::storage::StorageResult;
+use super::types::{FileMetadata, UpdateOrigin};
+use super::workspace_doc::WorkspaceCrdt;
+use crate::error::DiaryxError;
+
+/// Access level granted to a guest for a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export, export_to = "bindings/")]
+pub enum SharePermission {
+    /// Guest can see the file and its body.
+    Read,
+    /// Guest can also edit the file's body and metadata.
+    Write,
+}
+
+/// One shared path in a [`ShareScope`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "bindings/")]
+pub struct ScopeEntry {
+    /// Workspace-relative path of the shared file or index.
+    pub path: String,
+    /// What the guest may do with it.
+    pub permission: SharePermission,
+    /// Also share everything under this index (its `contents`, recursively).
+    #[serde(default)]
+    pub include_contents: bool,
+}
+
+/// The set of paths a share session exposes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "bindings/")]
+pub struct ShareScope {
+    /// Shared files and subtrees.
+    pub entries: Vec<ScopeEntry>,
+}
+
+impl ShareScope {
+    /// Whether the scope shares nothing (callers treat this as "no scoping").
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Resolve the scope against the current workspace tree.
+    pub fn resolve(&self, workspace: &WorkspaceCrdt) -> ResolvedScope {
+        let files: HashMap<String, FileMetadata> =
+            workspace.list_active_files().into_iter().collect();
+        let paths: HashMap<String, String> = files
+            .keys()
+            .filter_map(|key| file_path(workspace, key).map(|path| (key.clone(), path)))
+            .collect();
+
+        let mut resolved = ResolvedScope::default();
+        for (key, path) in &paths {
+            let ancestors = ancestor_paths(key, &files, &paths);
+
+            let permission = self
+                .entries
+                .iter()
+                .filter(|entry| {
+                    let scoped = normalize_path(&entry.path);
+                    scoped == *path || (entry.include_contents && ancestors.contains(&scoped))
+                })
+                .map(|entry| entry.permission)
+                .max();
+
+            if let Some(permission) = permission {
+                resolved.by_path.insert(path.clone(), permission);
+                resolved.by_key.insert(key.clone(), permission);
+            }
+        }
+        resolved
+    }
+
+    /// Check that applying `update` to `workspace` only touches writable files.
+    ///
+    /// The update is applied to a scratch copy, so `workspace` is unchanged.
+    /// Moving a file counts as writing both its old and its new location.
+    pub fn check_workspace_update(
+        &self,
+        workspace: &WorkspaceCrdt,
+        update: &[u8],
+    ) -> StorageResult<()> {
+        let before_scope = self.resolve(workspace);
+        let before: HashMap<String, FileMetadata> = workspace.list_files().into_iter().collect();
+
+        let scratch = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+        scratch.apply_update(&workspace.encode_state_as_update(), UpdateOrigin::Sync)?;
+        scratch.apply_update(update, UpdateOrigin::Remote)?;
+        let after_scope = self.resolve(&scratch);
+
+        for (key, new_meta) in scratch.list_files() {
+            let old_meta = before.get(&key);
+            if old_meta == Some(&new_meta) {
+                continue;
+            }
+
+            let was_active = old_meta.is_some_and(|m| !m.deleted);
+            if was_active && !before_scope.can_write_key(&key) {
+                return Err(denied(workspace, &key));
+            }
+            if !new_meta.deleted && !after_scope.can_write_key(&key) {
+                return Err(denied(&scratch, &key));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`ShareScope`] resolved to concrete files.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedScope {
+    by_path: HashMap<String, SharePermission>,
+    by_key: HashMap<String, SharePermission>,
+}
+
+impl ResolvedScope {
+    /// Permission for a workspace-relative path, or `None` if not shared.
+    pub fn permission(&self, path: &str) -> Option<SharePermission> {
+        self.by_path.get(&normalize_path(path)).copied()
+    }
+
+    /// Whether the guest may see `path`.
+    pub fn can_read(&self, path: &str) -> bool {
+        self.permission(path).is_some()
+    }
+
+    /// Whether the guest may edit `path`.
+    pub fn can_write(&self, path: &str) -> bool {
+        self.permission(path) == Some(SharePermission::Write)
+    }
+
+    /// Shared paths, sorted.
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.by_path.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+
+    fn can_write_key(&self, key: &str) -> bool {
+        self.by_key.get(key) == Some(&SharePermission::Write)
+    }
+
+    /// Build a workspace state containing only the shared files.
+    ///
+    /// Ancestors of shared files are kept as bare entries (filename and
+    /// hierarchy only) so paths resolve the same way as in the full
+    /// workspace. Titles, descriptions and attachments of unshared files
+    /// are never included.
+    pub fn filter_workspace(&self, workspace: &WorkspaceCrdt) -> StorageResult<Vec<u8>> {
+        let files: HashMap<String, FileMetadata> = workspace.list_files().into_iter().collect();
+
+        let mut structural = HashSet::new();
+        for key in self.by_key.keys() {
+            let mut visited = HashSet::new();
+            let mut current = files.get(key).and_then(|m| m.part_of.clone());
+            while let Some(parent) = current {
+                if !visited.insert(parent.clone()) {
+                    break;
+                }
+                if !self.by_key.contains_key(&parent) {
+                    structural.insert(parent.clone());
+                }
+                current = files.get(&parent).and_then(|m| m.part_of.clone());
+            }
+        }
+
+        let visible = |key: &String| self.by_key.contains_key(key) || structural.contains(key);
+        let filtered = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+        for (key, meta) in &files {
+            let contents = meta
+                .contents
+                .as_ref()
+                .map(|c| c.iter().filter(|k| visible(k)).cloned().collect());
+
+            let meta = if self.by_key.contains_key(key) {
+                FileMetadata {
+                    contents,
+                    ..meta.clone()
+                }
+            } else if structural.contains(key) {
+                FileMetadata {
+                    filename: meta.filename.clone(),
+                    part_of: meta.part_of.clone(),
+                    contents,
+                    ..Default::default()
+                }
+            } else {
+                continue;
+            };
+            filtered.set_file(key, meta)?;
+        }
+
+        Ok(filtered.encode_state_as_update())
+    }
+}
+
+/// Workspace-relative path for a file key (doc ID, or legacy path key).
+fn file_path(workspace: &WorkspaceCrdt, key: &str) -> Option<String> {
+    match workspace.get_path(key) {
+        Some(path) => Some(normalize_path(&path.to_string_lossy())),
+        None if key.contains('/') || key.ends_with(".md") => Some(normalize_path(key)),
+        None => None,
+    }
+}
+
+/// Paths of every ancestor of `key`, nearest first.
+fn ancestor_paths(
+    key: &str,
+    files: &HashMap<String, FileMetadata>,
+    paths: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut ancestors = Vec::new();
+    let mut visited = HashSet::new();
+    let mut current = files.get(key).and_then(|m| m.part_of.clone());
+    while let Some(parent) = current {
+        if !visited.insert(parent.clone()) {
+            break;
+        }
+        if let Some(path) = paths.get(&parent) {
+            ancestors.push(path.clone());
+        }
+        current = files.get(&parent).and_then(|m| m.part_of.clone());
+    }
+    ancestors
+}
+
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches("./")
+        .trim_start_matches('/')
+        .to_string()
+}
+
+fn denied(workspace: &WorkspaceCrdt, key: &str) -> DiaryxError {
+    let path = file_path(workspace, key).unwrap_or_else(|| key.to_string());
+    DiaryxError::Crdt(format!("{} is outside the writable share scope", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Workspace with `README.md` containing `projects.md`, which contains `a.md`,
+    /// plus a second root `private.md`.
+    fn workspace() -> (WorkspaceCrdt, HashMap<&'static str, String>) {
+        let ws = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+        let mut ids = HashMap::new();
+
+        let file = |filename: &str, part_of: Option<&String>| FileMetadata {
+            filename: filename.to_string(),
+            title: Some(filename.trim_end_matches(".md").to_string()),
+            part_of: part_of.cloned(),
+            ..Default::default()
+        };
+
+        ids.insert("root", ws.create_file(file("README.md", None)).unwrap());
+        ids.insert(
+            "projects",
+            ws.create_file(file("projects.md", ids.get("root")))
+                .unwrap(),
+        );
+        ids.insert(
+            "a",
+            ws.create_file(file("a.md", ids.get("projects"))).unwrap(),
+        );
+        ids.insert("private", ws.create_file(file("private.md", None)).unwrap());
+
+        let mut root = ws.get_file(&ids["root"]).unwrap();
+        root.contents = Some(vec![ids["projects"].clone()]);
+        ws.set_file(&ids["root"], root).unwrap();
+        let mut projects = ws.get_file(&ids["projects"]).unwrap();
+        projects.contents = Some(vec![ids["a"].clone()]);
+        ws.set_file(&ids["projects"], projects).unwrap();
+
+        (ws, ids)
+    }
+
+    fn path_of(ws: &WorkspaceCrdt, id: &str) -> String {
+        file_path(ws, id).unwrap()
+    }
+
+    fn subtree(path: &str, permission: SharePermission) -> ShareScope {
+        ShareScope {
+            entries: vec![ScopeEntry {
+                path: path.to_string(),
+                permission,
+                include_contents: true,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_resolve_subtree() {
+        let (ws, ids) = workspace();
+        let projects = path_of(&ws, &ids["projects"]);
+        let resolved = subtree(&projects, SharePermission::Write).resolve(&ws);
+
+        assert!(resolved.can_write(&projects));
+        assert!(resolved.can_write(&path_of(&ws, &ids["a"])));
+        assert!(!resolved.can_read(&path_of(&ws, &ids["root"])));
+        assert!(!resolved.can_read("private.md"));
+        assert_eq!(resolved.paths().len(), 2);
+    }
+
+    #[test]
+    fn test_single_file_and_overlap() {
+        let (ws, ids) = workspace();
+        let projects = path_of(&ws, &ids["projects"]);
+        let a = path_of(&ws, &ids["a"]);
+
+        let mut scope = subtree(&projects, SharePermission::Read);
+        scope.entries.push(ScopeEntry {
+            path: a.clone(),
+            permission: SharePermission::Write,
+            include_contents: false,
+        });
+        let resolved = scope.resolve(&ws);
+
+        assert_eq!(resolved.permission(&projects), Some(SharePermission::Read));
+        assert_eq!(resolved.permission(&a), Some(SharePermission::Write));
+    }
+
+    #[test]
+    fn test_filter_workspace_hides_unshared_files() {
+        let (ws, ids) = workspace();
+        let projects = path_of(&ws, &ids["projects"]);
+        let resolved = subtree(&projects, SharePermission::Read).resolve(&ws);
+
+        let filtered = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+        filtered
+            .apply_update(&resolved.filter_workspace(&ws).unwrap(), UpdateOrigin::Sync)
+            .unwrap();
+
+        // Shared files keep their metadata and paths
+        assert_eq!(
+            filtered.get_file(&ids["a"]).unwrap().title.as_deref(),
+            Some("a")
+        );
+        assert_eq!(path_of(&filtered, &ids["a"]), path_of(&ws, &ids["a"]));
+
+        // The unshared parent is only a bare path component
+        let root = filtered.get_file(&ids["root"]).unwrap();
+        assert_eq!(root.filename, "README.md");
+        assert!(root.title.is_none());
+
+        // Unrelated files are absent
+        assert!(filtered.get_file(&ids["private"]).is_none());
+    }
+
+    #[test]
+    fn test_check_workspace_update() {
+        let (ws, ids) = workspace();
+        let projects = path_of(&ws, &ids["projects"]);
+        let scope = subtree(&projects, SharePermission::Write);
+
+        let edit = |key: &str, title: &str| {
+            let copy = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+            copy.apply_update(&ws.encode_state_as_update(), UpdateOrigin::Sync)
+                .unwrap();
+            let sv = copy.encode_state_vector();
+            let mut meta = copy.get_file(key).unwrap();
+            meta.title = Some(title.to_string());
+            copy.set_file(key, meta).unwrap();
+            copy.encode_diff(&sv).unwrap()
+        };
+
+        assert!(
+            scope
+                .check_workspace_update(&ws, &edit(&ids["a"], "renamed"))
+                .is_ok()
+        );
+        assert!(
+            scope
+                .check_workspace_update(&ws, &edit(&ids["private"], "leak"))
+                .is_err()
+        );
+
+        let read_only = subtree(&projects, SharePermission::Read);
+        assert!(
+            read_only
+                .check_workspace_update(&ws, &edit(&ids["a"], "renamed"))
+                .is_err()
+        );
+    }
+}
@@ -11,12 +11,89 @@ use rusqlite::{Connection, params};
 use yrs::{Doc, ReadTxn, Transact, Update, updates::decoder::Decode, updates::encoder::Encode};
 
 use super::storage::{CrdtStorage, StorageResult};
-use super::types::{CrdtUpdate, UpdateOrigin};
+use super::types::{CrdtUpdate, PendingMessage, UpdateOrigin};
 use crate::error::DiaryxError;
 
 /// Row type for file index queries: (path, title, part_of)
 type FileIndexRow = (String, Option<String>, Option<String>);
 
+/// Ordered schema migrations. Applying `MIGRATIONS[i]` takes a store from
+/// version `i` to `i + 1`.
+///
+/// Never edit a migration once it has shipped; append a new one instead.
+/// Stores created before versioning report version 0 but may already contain
+/// these tables, hence `IF NOT EXISTS`.
+const MIGRATIONS: &[&str] = &[
+    // 1: documents, update history and file index
+    r#"
+    -- Document snapshots (compacted state)
+    CREATE TABLE IF NOT EXISTS documents (
+        name TEXT PRIMARY KEY,
+        state BLOB NOT NULL,
+        state_vector BLOB NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+
+    -- Incremental updates (for history)
+    -- Note: No foreign key constraint since updates may arrive before document snapshot
+    CREATE TABLE IF NOT EXISTS updates (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        doc_name TEXT NOT NULL,
+        data BLOB NOT NULL,
+        origin TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        device_id TEXT,
+        device_name TEXT
+    );
+
+    -- Index for efficient sync queries
+    CREATE INDEX IF NOT EXISTS idx_updates_doc_id ON updates(doc_name, id);
+
+    -- Metadata for workspace files (queryable without loading CRDT)
+    CREATE TABLE IF NOT EXISTS file_index (
+        path TEXT PRIMARY KEY,
+        title TEXT,
+        part_of TEXT,
+        deleted INTEGER NOT NULL DEFAULT 0,
+        modified_at INTEGER NOT NULL
+    );
+
+    -- Index for querying non-deleted files
+    CREATE INDEX IF NOT EXISTS idx_file_index_deleted ON file_index(deleted);
+    "#,
+    // 2: durable outbox
+    r#"
+    -- Outgoing sync messages queued while offline
+    CREATE TABLE IF NOT EXISTS pending_messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        doc_name TEXT NOT NULL,
+        is_body INTEGER NOT NULL,
+        message BLOB NOT NULL,
+        queued_at INTEGER NOT NULL
+    );
+    "#,
+];
+
+/// Apply every migration newer than the store's `user_version`.
+fn migrate(conn: &Connection, migrations: &[&str]) -> StorageResult<()> {
+    let current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let latest = migrations.len() as u32;
+    if current > latest {
+        return Err(DiaryxError::Unsupported(format!(
+            "CRDT database schema version {} is newer than this version of Diaryx supports ({})",
+            current, latest
+        )));
+    }
+
+    for (index, sql) in migrations.iter().enumerate().skip(current as usize) {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", index as u32 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
 /// SQLite-backed CRDT storage.
 ///
 /// This implementation persists CRDT state and updates to a SQLite database,
@@ -60,48 +137,14 @@ impl SqliteStorage {
         Ok(storage)
     }
 
-    /// Initialize the database schema.
+    /// Bring the database schema up to date by applying pending [`MIGRATIONS`].
+    ///
+    /// The version lives in `PRAGMA user_version`; each pending migration
+    /// runs in its own transaction. A store written by a newer Diaryx is
+    /// refused rather than read with a schema this build doesn't know.
     fn init_schema(&self) -> StorageResult<()> {
         let conn = self.conn.lock().unwrap();
-        conn.execute_batch(
-            r#"
-            -- Document snapshots (compacted state)
-            CREATE TABLE IF NOT EXISTS documents (
-                name TEXT PRIMARY KEY,
-                state BLOB NOT NULL,
-                state_vector BLOB NOT NULL,
-                updated_at INTEGER NOT NULL
-            );
-
-            -- Incremental updates (for history)
-            -- Note: No foreign key constraint since updates may arrive before document snapshot
-            CREATE TABLE IF NOT EXISTS updates (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                doc_name TEXT NOT NULL,
-                data BLOB NOT NULL,
-                origin TEXT NOT NULL,
-                timestamp INTEGER NOT NULL,
-                device_id TEXT,
-                device_name TEXT
-            );
-
-            -- Index for efficient sync queries
-            CREATE INDEX IF NOT EXISTS idx_updates_doc_id ON updates(doc_name, id);
-
-            -- Metadata for workspace files (queryable without loading CRDT)
-            CREATE TABLE IF NOT EXISTS file_index (
-                path TEXT PRIMARY KEY,
-                title TEXT,
-                part_of TEXT,
-                deleted INTEGER NOT NULL DEFAULT 0,
-                modified_at INTEGER NOT NULL
-            );
-
-            -- Index for querying non-deleted files
-            CREATE INDEX IF NOT EXISTS idx_file_index_deleted ON file_index(deleted);
-            "#,
-        )?;
-        Ok(())
+        migrate(&conn, MIGRATIONS)
     }
 
     /// Reconstruct CRDT state by applying updates up to a given ID.
@@ -212,6 +255,48 @@ impl SqliteStorage {
         conn.execute("DELETE FROM file_index", [])?;
         Ok(())
     }
+
+    /// Names of every document with a snapshot or at least one update.
+    ///
+    /// Unlike [`CrdtStorage::list_docs`], this includes documents that only
+    /// exist in the update log.
+    pub fn list_all_docs(&self) -> StorageResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name FROM documents UNION SELECT doc_name FROM updates ORDER BY 1")?;
+        let names = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(names)
+    }
+
+    /// Append updates from another store, keeping their timestamps, origins
+    /// and device attribution. Update IDs are assigned by this store.
+    ///
+    /// Used when restoring a workspace archive so history survives the move.
+    /// All updates are written in one transaction.
+    pub fn import_updates(&self, updates: &[CrdtUpdate]) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO updates (doc_name, data, origin, timestamp, device_id, device_name) VALUES (?, ?, ?, ?, ?, ?)",
+            )?;
+            for update in updates {
+                stmt.execute(params![
+                    update.doc_name,
+                    update.data,
+                    update.origin.to_string(),
+                    update.timestamp,
+                    update.device_id,
+                    update.device_name,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
 }
 
 impl std::fmt::Debug for SqliteStorage {
@@ -510,6 +595,58 @@ impl CrdtStorage for SqliteStorage {
         conn.execute("DELETE FROM updates WHERE doc_name = ?", params![name])?;
         Ok(())
     }
+
+    fn enqueue_pending(&self, doc_name: &str, is_body: bool, message: &[u8]) -> StorageResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Utc::now().timestamp_millis();
+        conn.execute(
+            "INSERT INTO pending_messages (doc_name, is_body, message, queued_at)
+             VALUES (?, ?, ?, ?)",
+            params![doc_name, is_body as i32, message, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn list_pending(&self) -> StorageResult<Vec<PendingMessage>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, doc_name, is_body, message, queued_at FROM pending_messages ORDER BY id ASC",
+        )?;
+        let pending = stmt
+            .query_map([], |row| {
+                Ok(PendingMessage {
+                    id: row.get(0)?,
+                    doc_name: row.get(1)?,
+                    is_body: row.get::<_, i32>(2)? != 0,
+                    message: row.get(3)?,
+                    queued_at: row.get(4)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(pending)
+    }
+
+    fn remove_pending(&self, ids: &[i64]) -> StorageResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("DELETE FROM pending_messages WHERE id = ?")?;
+            for id in ids {
+                stmt.execute(params![id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn pending_count(&self) -> StorageResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM pending_messages", [], |row| {
+            row.get(0)
+        })?;
+        Ok(count as usize)
+    }
 }
 
 #[cfg(test)]
@@ -812,6 +949,34 @@ mod tests {
         assert!(storage.load_doc("test").unwrap().is_some());
     }
 
+    #[test]
+    fn test_sqlite_import_updates_keeps_attribution() {
+        let source = SqliteStorage::in_memory().unwrap();
+        source
+            .append_update_with_device(
+                "doc",
+                b"one",
+                UpdateOrigin::Remote,
+                Some("dev-1"),
+                Some("Laptop"),
+            )
+            .unwrap();
+        let mut updates = source.get_all_updates("doc").unwrap();
+        updates[0].timestamp = 1234;
+
+        let target = SqliteStorage::in_memory().unwrap();
+        target.import_updates(&updates).unwrap();
+
+        assert!(target.list_docs().unwrap().is_empty());
+        assert_eq!(target.list_all_docs().unwrap(), vec!["doc"]);
+        let imported = target.get_all_updates("doc").unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].data, b"one");
+        assert_eq!(imported[0].timestamp, 1234);
+        assert_eq!(imported[0].origin, UpdateOrigin::Remote);
+        assert_eq!(imported[0].device_name.as_deref(), Some("Laptop"));
+    }
+
     #[test]
     fn test_sqlite_clear_updates_nonexistent() {
         let storage = SqliteStorage::in_memory().unwrap();
@@ -820,4 +985,106 @@ mod tests {
         let result = storage.clear_updates("nonexistent");
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_sqlite_pending_queue_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("crdt.db");
+
+        {
+            let storage = SqliteStorage::open(&db_path).unwrap();
+            storage.enqueue_pending("workspace", false, b"ws").unwrap();
+            storage.enqueue_pending("a.md", true, b"body").unwrap();
+        }
+
+        let storage = SqliteStorage::open(&db_path).unwrap();
+        let pending = storage.list_pending().unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].doc_name, "workspace");
+        assert!(!pending[0].is_body);
+        assert_eq!(pending[1].message, b"body");
+
+        storage.remove_pending(&[pending[0].id]).unwrap();
+        assert_eq!(storage.pending_count().unwrap(), 1);
+    }
+
+    fn user_version(path: &Path) -> u32 {
+        Connection::open(path)
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sqlite_upgrade_from_each_version() {
+        for version in 0..MIGRATIONS.len() {
+            let dir = tempfile::tempdir().unwrap();
+            let db_path = dir.path().join("crdt.db");
+            {
+                let conn = Connection::open(&db_path).unwrap();
+                migrate(&conn, &MIGRATIONS[..version]).unwrap();
+                if version >= 1 {
+                    conn.execute(
+                        "INSERT INTO updates (doc_name, data, origin, timestamp) VALUES ('workspace', x'00', 'local', 0)",
+                        [],
+                    )
+                    .unwrap();
+                }
+            }
+            assert_eq!(user_version(&db_path), version as u32);
+
+            let storage = SqliteStorage::open(&db_path).unwrap();
+            assert_eq!(
+                storage.get_all_updates("workspace").unwrap().len(),
+                if version >= 1 { 1 } else { 0 }
+            );
+            storage.enqueue_pending("workspace", false, b"ws").unwrap();
+            drop(storage);
+
+            assert_eq!(user_version(&db_path), MIGRATIONS.len() as u32);
+        }
+    }
+
+    #[test]
+    fn test_sqlite_upgrade_unversioned_store() {
+        // Stores written before migrations existed: tables present, user_version 0
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("crdt.db");
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.execute(
+                "INSERT INTO file_index (path, title, modified_at) VALUES ('a.md', 'A', 0)",
+                [],
+            )
+            .unwrap();
+        }
+        assert_eq!(user_version(&db_path), 0);
+
+        let storage = SqliteStorage::open(&db_path).unwrap();
+        let files = storage.query_active_files().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "a.md");
+        assert_eq!(storage.pending_count().unwrap(), 0);
+        drop(storage);
+
+        assert_eq!(user_version(&db_path), MIGRATIONS.len() as u32);
+        // Reopening an up-to-date store is a no-op
+        SqliteStorage::open(&db_path).unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_rejects_newer_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("crdt.db");
+        Connection::open(&db_path)
+            .unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() as u32 + 1)
+            .unwrap();
+
+        assert!(matches!(
+            SqliteStorage::open(&db_path),
+            Err(DiaryxError::Unsupported(_))
+        ));
+    }
 }
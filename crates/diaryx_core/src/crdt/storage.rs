@@ -3,7 +3,7 @@
 //! This module defines the [`CrdtStorage`] trait which abstracts over different
 //! storage backends (SQLite, in-memory) for persisting CRDT documents and updates.
 
-use super::types::{CrdtUpdate, UpdateOrigin};
+use super::types::{CrdtUpdate, PendingMessage, UpdateOrigin};
 use crate::error::DiaryxError;
 
 /// Result type for storage operations.
@@ -122,6 +122,35 @@ pub trait CrdtStorage: Send + Sync {
     /// This is used when replacing the entire CRDT state during initial sync.
     /// After replacement, the old update history is no longer valid and must be cleared.
     fn clear_updates(&self, name: &str) -> StorageResult<()>;
+
+    /// Queue an outgoing sync message in the durable outbox.
+    ///
+    /// Used by the sync client to keep local changes made while offline
+    /// across restarts. Returns the ID of the new queue entry.
+    ///
+    /// Backends without an outbox return `DiaryxError::Unsupported`.
+    fn enqueue_pending(&self, doc_name: &str, is_body: bool, message: &[u8]) -> StorageResult<i64> {
+        let _ = (doc_name, is_body, message);
+        Err(DiaryxError::Unsupported(
+            "Pending message queue not supported by this storage backend".to_string(),
+        ))
+    }
+
+    /// Get all queued outgoing messages, oldest first.
+    fn list_pending(&self) -> StorageResult<Vec<PendingMessage>> {
+        Ok(Vec::new())
+    }
+
+    /// Remove queued messages once they have been delivered.
+    fn remove_pending(&self, ids: &[i64]) -> StorageResult<()> {
+        let _ = ids;
+        Ok(())
+    }
+
+    /// Number of queued outgoing messages.
+    fn pending_count(&self) -> StorageResult<usize> {
+        Ok(self.list_pending()?.len())
+    }
 }
 
 #[cfg(test)]
@@ -276,7 +276,6 @@ mod msg_type {
     /// Sync message (SyncStep1, SyncStep2, Update)
     pub const SYNC: u8 = 0;
     /// Awareness message
-    #[allow(dead_code)]
     pub const AWARENESS: u8 = 1;
     /// Auth message (reserved for future use)
     #[allow(dead_code)]
@@ -482,6 +481,113 @@ impl SyncMessage {
     }
 }
 
+// ===========================================================================
+// Awareness protocol (y-protocols compatible)
+// ===========================================================================
+
+/// One client's entry in an awareness update.
+///
+/// `state` is the client's JSON presence state, or `None` when the client
+/// has cleared its state (went offline or closed the document).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AwarenessEntry {
+    /// Y.js client ID of the peer that owns this state.
+    pub client_id: u64,
+    /// Monotonic clock, incremented every time the owner changes its state.
+    pub clock: u64,
+    /// JSON-encoded state, or `None` if removed.
+    pub state: Option<String>,
+}
+
+/// Awareness update carrying presence states for one or more clients.
+///
+/// Wire format (same as `y-protocols/awareness`):
+/// `varUint(count)` followed by `count` entries of
+/// `varUint(clientID) + varUint(clock) + varString(JSON state)`,
+/// where a removed state is encoded as the JSON string `null`.
+///
+/// As a full message it is prefixed with `varUint(1)` and the update is
+/// written as a var byte array.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AwarenessUpdate {
+    /// Entries contained in this update.
+    pub entries: Vec<AwarenessEntry>,
+}
+
+impl AwarenessUpdate {
+    /// Encode the raw awareness update (without the message type prefix).
+    pub fn encode_update(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_var_uint(&mut buf, self.entries.len() as u64);
+        for entry in &self.entries {
+            write_var_uint(&mut buf, entry.client_id);
+            write_var_uint(&mut buf, entry.clock);
+            write_var_byte_array(
+                &mut buf,
+                entry.state.as_deref().unwrap_or("null").as_bytes(),
+            );
+        }
+        buf
+    }
+
+    /// Decode a raw awareness update (without the message type prefix).
+    pub fn decode_update(data: &[u8]) -> StorageResult<Self> {
+        let incomplete = || DiaryxError::Crdt("Incomplete awareness update".to_string());
+
+        let (count, mut offset) = read_var_uint(data).ok_or_else(incomplete)?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let (client_id, n) = read_var_uint(&data[offset..]).ok_or_else(incomplete)?;
+            offset += n;
+            let (clock, n) = read_var_uint(&data[offset..]).ok_or_else(incomplete)?;
+            offset += n;
+            let (state, n) = read_var_byte_array(&data[offset..]).ok_or_else(incomplete)?;
+            offset += n;
+
+            let state = String::from_utf8(state)
+                .map_err(|e| DiaryxError::Crdt(format!("Invalid awareness state: {}", e)))?;
+            entries.push(AwarenessEntry {
+                client_id,
+                clock,
+                state: (state != "null").then_some(state),
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Encode as a full awareness message: `varUint(1) + varByteArray(update)`.
+    pub fn encode(&self) -> Vec<u8> {
+        let update = self.encode_update();
+        let mut buf = Vec::with_capacity(update.len() + 6);
+        write_var_uint(&mut buf, msg_type::AWARENESS as u64);
+        write_var_byte_array(&mut buf, &update);
+        buf
+    }
+
+    /// Decode a full awareness message.
+    ///
+    /// Returns `None` for empty, incomplete, or non-awareness messages, so
+    /// callers can try this before `SyncMessage::decode` on the same bytes.
+    pub fn decode(data: &[u8]) -> StorageResult<Option<Self>> {
+        let Some((msg_type_val, msg_type_bytes)) = read_var_uint(data) else {
+            return Ok(None);
+        };
+        if msg_type_val != msg_type::AWARENESS as u64 {
+            return Ok(None);
+        }
+        let Some((update, _)) = read_var_byte_array(&data[msg_type_bytes..]) else {
+            return Ok(None);
+        };
+        Self::decode_update(&update).map(Some)
+    }
+
+    /// Whether `data` starts with the awareness message type.
+    pub fn is_awareness_message(data: &[u8]) -> bool {
+        matches!(read_var_uint(data), Some((t, _)) if t == msg_type::AWARENESS as u64)
+    }
+}
+
 /// Sync protocol handler for a workspace CRDT.
 ///
 /// This struct manages the Y-sync protocol state and message handling
@@ -1145,4 +1251,60 @@ mod tests {
             _ => panic!("Expected SyncStep1 after roundtrip"),
         }
     }
+
+    #[test]
+    fn test_awareness_update_wire_format() {
+        let update = AwarenessUpdate {
+            entries: vec![AwarenessEntry {
+                client_id: 1,
+                clock: 2,
+                state: Some(r#"{"a":1}"#.to_string()),
+            }],
+        };
+
+        let raw = update.encode_update();
+        assert_eq!(raw, [&[1u8, 1, 2, 7][..], br#"{"a":1}"#].concat());
+
+        let message = update.encode();
+        assert_eq!(message[0], 1); // msgType = awareness
+        assert_eq!(message[1] as usize, raw.len());
+        assert_eq!(&message[2..], &raw[..]);
+    }
+
+    #[test]
+    fn test_awareness_update_roundtrip_with_removal() {
+        let update = AwarenessUpdate {
+            entries: vec![
+                AwarenessEntry {
+                    client_id: 300,
+                    clock: 5,
+                    state: Some(r#"{"user":{"name":"Ada"}}"#.to_string()),
+                },
+                AwarenessEntry {
+                    client_id: 7,
+                    clock: 9,
+                    state: None,
+                },
+            ],
+        };
+
+        let decoded = AwarenessUpdate::decode(&update.encode()).unwrap().unwrap();
+        assert_eq!(decoded, update);
+        assert_eq!(
+            AwarenessUpdate::decode_update(&update.encode_update()).unwrap(),
+            update
+        );
+    }
+
+    #[test]
+    fn test_awareness_and_sync_messages_are_distinguished() {
+        let awareness = AwarenessUpdate::default().encode();
+        let sync = SyncMessage::SyncStep1(vec![0]).encode();
+
+        assert!(AwarenessUpdate::is_awareness_message(&awareness));
+        assert!(!AwarenessUpdate::is_awareness_message(&sync));
+        assert!(SyncMessage::decode(&awareness).unwrap().is_none());
+        assert!(AwarenessUpdate::decode(&sync).unwrap().is_none());
+        assert!(AwarenessUpdate::decode_update(&[2, 1]).is_err());
+    }
 }
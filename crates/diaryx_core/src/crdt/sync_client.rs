@@ -29,6 +29,8 @@
 //! │  - Exponential backoff reconnection                         │
 //! │  - Progress tracking and status reporting                   │
 //! │  - Message routing to RustSyncManager                       │
+//! │  - Durable offline outbox in CrdtStorage                    │
+//! │  - Presence (awareness) relay over the body connection      │
 //! │                                                             │
 //! └─────────────────────────────────────────────────────────────┘
 //! ```
@@ -47,6 +49,8 @@
 //!     auth_token: Some("token".to_string()),
 //!     workspace_root: PathBuf::from("/path/to/workspace"),
 //!     write_to_disk: true,
+//!     max_reconnect_attempts: 10,
+//!     client_tag: uuid::Uuid::new_v4().to_string(),
 //! };
 //!
 //! let client = SyncClient::new(
@@ -65,9 +69,18 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 
+use super::awareness::{
+    Awareness, AwarenessPeer, PeerPresence, decode_awareness_control, encode_awareness_control,
+};
+use super::outbox::{PendingUpdate, pending_updates};
+use super::storage::CrdtStorage;
+use super::sync::AwarenessUpdate;
+use super::sync_error::{SyncServerError, decode_sync_error_control, encode_error_check_control};
 use super::sync_manager::RustSyncManager;
-use super::transport::{ConnectionStatus, MessageCallback, SyncConfig, SyncTransport};
-use super::{frame_body_message, unframe_body_message};
+use super::transport::{
+    ConnectionStatus, MessageCallback, SyncConfig, SyncTransport, TextCallback,
+};
+use super::{format_workspace_doc_id, frame_body_message, unframe_body_message};
 use crate::error::Result;
 use crate::fs::{AsyncFileSystem, FileSystemEvent};
 
@@ -209,6 +222,10 @@ pub struct SyncClientConfig {
 
     /// Maximum reconnection attempts before giving up.
     pub max_reconnect_attempts: u32,
+
+    /// Random tag sent with both connections so this client can pick out
+    /// `sync_error` messages addressed to it.
+    pub client_tag: String,
 }
 
 impl SyncClientConfig {
@@ -221,6 +238,7 @@ impl SyncClientConfig {
             workspace_root,
             write_to_disk: true,
             max_reconnect_attempts: 10,
+            client_tag: uuid::Uuid::new_v4().to_string(),
         }
     }
 
@@ -244,7 +262,8 @@ impl SyncClientConfig {
 
     /// Build the SyncConfig for metadata connection.
     fn metadata_config(&self) -> SyncConfig {
-        let mut config = SyncConfig::metadata(self.server_url.clone(), self.workspace_id.clone());
+        let mut config = SyncConfig::metadata(self.server_url.clone(), self.workspace_id.clone())
+            .with_client_tag(self.client_tag.clone());
         if let Some(ref token) = self.auth_token {
             config = config.with_auth(token.clone());
         }
@@ -253,7 +272,8 @@ impl SyncClientConfig {
 
     /// Build the SyncConfig for body connection.
     fn body_config(&self) -> SyncConfig {
-        let mut config = SyncConfig::body(self.server_url.clone(), self.workspace_id.clone());
+        let mut config = SyncConfig::body(self.server_url.clone(), self.workspace_id.clone())
+            .with_client_tag(self.client_tag.clone());
         if let Some(ref token) = self.auth_token {
             config = config.with_auth(token.clone());
         }
@@ -309,6 +329,42 @@ pub enum SyncEvent {
         /// Paths of files that any client is focused on.
         files: Vec<String>,
     },
+    /// Number of outgoing messages waiting in the offline outbox changed.
+    PendingChanged {
+        /// Messages queued for sending on reconnect.
+        count: usize,
+    },
+    /// Collaborators' presence changed (joined, left, moved cursor, switched file).
+    PeersChanged {
+        /// Remote peers that are currently online.
+        peers: Vec<AwarenessPeer>,
+    },
+    /// The server refused a message from this client (rate limit or size limit).
+    ServerError(SyncServerError),
+}
+
+/// Emit `SyncEvent::ServerError` if `text` is a `sync_error` for this client.
+///
+/// Returns true if `text` was a `sync_error` message (for any client).
+fn handle_server_error(
+    text: &str,
+    client_tag: &str,
+    event_callback: &Option<SyncEventCallback>,
+) -> bool {
+    let Some(error) = decode_sync_error_control(text) else {
+        return false;
+    };
+    if error.is_for(client_tag) {
+        log::warn!(
+            "[SyncClient] Server refused message ({:?}): {}",
+            error.code,
+            error.message
+        );
+        if let Some(cb) = event_callback {
+            cb(SyncEvent::ServerError(error));
+        }
+    }
+    true
 }
 
 /// Unified sync client for dual-connection sync.
@@ -328,6 +384,28 @@ pub enum SyncEvent {
 ///
 /// This design allows the `RustSyncManager` event callback to queue messages
 /// without holding a direct reference to the SyncClient.
+///
+/// ## Offline Outbox
+///
+/// Messages that cannot be sent (not connected, or the send fails) are
+/// persisted with `CrdtStorage::enqueue_pending`, so edits made offline
+/// survive a restart. After reconnecting, `replay_pending()` sends one
+/// merged update per document, preceded by a body `SyncStep1` so the server
+/// also returns anything this client is missing.
+///
+/// ## Presence
+///
+/// `set_presence()` shares this client's name, open file and cursor with
+/// collaborators via the awareness protocol. Call `renew_presence()` every
+/// `AWARENESS_RENEW_INTERVAL_MS` to stay visible; remote changes arrive as
+/// `SyncEvent::PeersChanged`.
+///
+/// ## Server Errors
+///
+/// The server holds back errors for refused messages until the connection
+/// sends a control message. Call `check_errors()` every
+/// `ERROR_CHECK_INTERVAL_MS` while connected; held errors arrive as
+/// `SyncEvent::ServerError`.
 #[deprecated(
     note = "Use direct WebSocket with v2 protocol instead. See CLI sync/client.rs for reference."
 )]
@@ -350,6 +428,12 @@ pub struct SyncClient<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'sta
     // Outgoing message channel for local CRDT changes
     outgoing_tx: OutgoingSender,
     outgoing_rx: Mutex<Option<OutgoingReceiver>>,
+
+    // Durable outbox for messages that could not be sent
+    storage: Arc<dyn CrdtStorage>,
+
+    // Local presence and known remote peers
+    awareness: Arc<Mutex<Awareness>>,
 }
 
 impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T, FS> {
@@ -369,6 +453,7 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
     ) -> Self {
         // Create channel for outgoing messages
         let (outgoing_tx, outgoing_rx) = std::sync::mpsc::channel();
+        let storage = sync_manager.storage();
 
         Self {
             config,
@@ -383,6 +468,8 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
             event_callback: RwLock::new(None),
             outgoing_tx,
             outgoing_rx: Mutex::new(Some(outgoing_rx)),
+            storage,
+            awareness: Arc::new(Mutex::new(Awareness::new())),
         }
     }
 
@@ -400,7 +487,9 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
     /// Sends queued messages via the appropriate transport (metadata or body).
     /// Call this periodically or after local CRDT changes.
     ///
-    /// Returns the number of messages processed.
+    /// Messages that cannot be sent are moved to the offline outbox.
+    ///
+    /// Returns the number of messages sent.
     ///
     /// # Cancellation Safety
     ///
@@ -430,46 +519,156 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
 
         // Process the buffered messages outside the lock
         let mut count = 0;
+        let mut queued = false;
         for msg in pending_messages {
-            if self.send_outgoing_message(&msg).await.is_ok() {
-                count += 1;
+            match self.send_outgoing_message(&msg).await {
+                Ok(true) => count += 1,
+                Ok(false) => queued |= self.enqueue_pending(&msg),
+                Err(e) => {
+                    log::warn!(
+                        "[SyncClient] Failed to send message for {}: {:?}",
+                        msg.doc_name,
+                        e
+                    );
+                    queued |= self.enqueue_pending(&msg);
+                }
             }
         }
 
+        if queued {
+            self.emit_pending_count();
+        }
+
         count
     }
 
     /// Send a single outgoing message via the appropriate transport.
-    async fn send_outgoing_message(&self, msg: &OutgoingSyncMessage) -> Result<()> {
+    ///
+    /// Returns `Ok(false)` if the connection it needs is down.
+    async fn send_outgoing_message(&self, msg: &OutgoingSyncMessage) -> Result<bool> {
         if msg.is_body {
             // Body message - frame and send via body transport
-            if self.body_connected.load(Ordering::SeqCst) {
-                let framed = frame_body_message(&msg.doc_name, &msg.message);
-                self.body_transport.send(&framed).await?;
+            if !self.body_connected.load(Ordering::SeqCst) {
                 log::debug!(
-                    "[SyncClient] Sent body message for {}, {} bytes",
-                    msg.doc_name,
-                    msg.message.len()
-                );
-            } else {
-                log::warn!(
-                    "[SyncClient] Cannot send body message for {} - not connected",
+                    "[SyncClient] Body not connected, queueing message for {}",
                     msg.doc_name
                 );
+                return Ok(false);
             }
+            let framed = frame_body_message(&msg.doc_name, &msg.message);
+            self.body_transport.send(&framed).await?;
+            log::debug!(
+                "[SyncClient] Sent body message for {}, {} bytes",
+                msg.doc_name,
+                msg.message.len()
+            );
         } else {
             // Workspace metadata message - send via metadata transport
-            if self.metadata_connected.load(Ordering::SeqCst) {
-                self.metadata_transport.send(&msg.message).await?;
-                log::debug!(
-                    "[SyncClient] Sent workspace message, {} bytes",
-                    msg.message.len()
+            if !self.metadata_connected.load(Ordering::SeqCst) {
+                log::debug!("[SyncClient] Metadata not connected, queueing workspace message");
+                return Ok(false);
+            }
+            self.metadata_transport.send(&msg.message).await?;
+            log::debug!(
+                "[SyncClient] Sent workspace message, {} bytes",
+                msg.message.len()
+            );
+        }
+        Ok(true)
+    }
+
+    // ========================================================================
+    // Offline Outbox
+    // ========================================================================
+
+    /// Persist a message in the offline outbox. Returns true if it was stored.
+    fn enqueue_pending(&self, msg: &OutgoingSyncMessage) -> bool {
+        match self
+            .storage
+            .enqueue_pending(&msg.doc_name, msg.is_body, &msg.message)
+        {
+            Ok(_) => true,
+            Err(e) => {
+                log::warn!(
+                    "[SyncClient] Dropping message for {} - outbox unavailable: {}",
+                    msg.doc_name,
+                    e
                 );
+                false
+            }
+        }
+    }
+
+    /// Number of messages waiting in the offline outbox.
+    pub fn pending_count(&self) -> usize {
+        self.storage.pending_count().unwrap_or(0)
+    }
+
+    /// Emit the current outbox depth.
+    fn emit_pending_count(&self) {
+        self.emit_event(SyncEvent::PendingChanged {
+            count: self.pending_count(),
+        });
+    }
+
+    /// Replay the offline outbox.
+    ///
+    /// Queued updates are merged into a single update per document. Body docs
+    /// are preceded by a `SyncStep1`, so the server answers with whatever this
+    /// client is missing; the workspace handshake already does this on
+    /// connect. Entries are removed only after their update was sent.
+    ///
+    /// Returns the number of documents replayed.
+    pub async fn replay_pending(&self) -> Result<usize> {
+        let pending = pending_updates(self.storage.as_ref())?;
+        if pending.is_empty() {
+            self.emit_pending_count();
+            return Ok(0);
+        }
+
+        log::info!(
+            "[SyncClient] Replaying {} documents from offline outbox",
+            pending.len()
+        );
+
+        let mut replayed = 0;
+        for PendingUpdate {
+            doc_name,
+            is_body,
+            message: update,
+            ids,
+        } in pending
+        {
+            let msg = if is_body {
+                let step1 = self.sync_manager.create_body_sync_step1(&doc_name);
+                let handshake = OutgoingSyncMessage::body(doc_name.clone(), step1);
+                if !matches!(self.send_outgoing_message(&handshake).await, Ok(true)) {
+                    break;
+                }
+                OutgoingSyncMessage::body(doc_name, update)
             } else {
-                log::warn!("[SyncClient] Cannot send workspace message - not connected");
+                OutgoingSyncMessage::workspace(update)
+            };
+
+            match self.send_outgoing_message(&msg).await {
+                Ok(true) => {
+                    self.storage.remove_pending(&ids)?;
+                    replayed += 1;
+                }
+                Ok(false) => break,
+                Err(e) => {
+                    log::warn!(
+                        "[SyncClient] Failed to replay outbox for {}: {:?}",
+                        msg.doc_name,
+                        e
+                    );
+                    break;
+                }
             }
         }
-        Ok(())
+
+        self.emit_pending_count();
+        Ok(replayed)
     }
 
     /// Set the event callback.
@@ -581,6 +780,14 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
         });
 
         self.metadata_transport.set_on_message(callback);
+
+        let client_tag = self.config.client_tag.clone();
+        let event_callback = self.event_callback.read().unwrap().clone();
+        self.metadata_transport
+            .set_on_text(Arc::new(move |text: &str| {
+                handle_server_error(text, &client_tag, &event_callback);
+            }));
+
         self.metadata_transport.connect(&config).await?;
         self.metadata_connected.store(true, Ordering::SeqCst);
 
@@ -650,6 +857,31 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
         });
 
         self.body_transport.set_on_message(callback);
+
+        // Awareness updates from collaborators arrive as JSON text messages
+        let awareness = Arc::clone(&self.awareness);
+        let event_callback = self.event_callback.read().unwrap().clone();
+        let client_tag = self.config.client_tag.clone();
+        let text_callback: TextCallback = Arc::new(move |text: &str| {
+            if handle_server_error(text, &client_tag, &event_callback) {
+                return;
+            }
+            let Some((_, update)) = decode_awareness_control(text) else {
+                return;
+            };
+            let peers = {
+                let mut awareness = awareness.lock().unwrap();
+                if !awareness.apply_update(&update, chrono::Utc::now().timestamp_millis()) {
+                    return;
+                }
+                awareness.peers()
+            };
+            if let Some(ref cb) = event_callback {
+                cb(SyncEvent::PeersChanged { peers });
+            }
+        });
+        self.body_transport.set_on_text(text_callback);
+
         self.body_transport.connect(&config).await?;
         self.body_connected.store(true, Ordering::SeqCst);
 
@@ -658,6 +890,17 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
         // Update status to connected
         self.set_status(ConnectionStatus::Connected);
 
+        // Send anything that was queued while offline
+        if let Err(e) = self.replay_pending().await {
+            log::warn!("[SyncClient] Failed to replay offline outbox: {:?}", e);
+        }
+
+        // Re-announce presence set before (re)connecting
+        let update = self.awareness.lock().unwrap().renew_local_state();
+        if let Some(update) = update {
+            self.send_awareness(&update).await?;
+        }
+
         // NOTE: We no longer auto-subscribe to all body docs here.
         // Instead, clients use focus_files() to indicate which files they're working on,
         // and the server broadcasts focus_list_changed to all clients.
@@ -717,6 +960,24 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
 
         log::info!("[SyncClient] Stopping sync");
 
+        // Tell collaborators we left instead of letting them time us out
+        let goodbye = {
+            let mut awareness = self.awareness.lock().unwrap();
+            awareness.clear_peers();
+            awareness
+                .local_state()
+                .is_some()
+                .then(|| awareness.set_local_state(None))
+        };
+        if let Some(update) = goodbye {
+            let _ = self.send_awareness(&update).await;
+        }
+
+        // Move unsent messages to the durable outbox before disconnecting
+        self.metadata_connected.store(false, Ordering::SeqCst);
+        self.body_connected.store(false, Ordering::SeqCst);
+        self.process_outgoing().await;
+
         // Disconnect both transports
         let _ = self.metadata_transport.disconnect().await;
         let _ = self.body_transport.disconnect().await;
@@ -751,6 +1012,73 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> SyncClient<T
         self.reconnect_attempts.store(0, Ordering::SeqCst);
     }
 
+    // ========================================================================
+    // Presence (Awareness) APIs
+    // ========================================================================
+
+    /// Share this client's presence with collaborators.
+    ///
+    /// Pass `None` to appear offline. The presence is remembered and
+    /// re-announced after reconnecting.
+    pub async fn set_presence(&self, presence: Option<PeerPresence>) -> Result<()> {
+        let update = self.awareness.lock().unwrap().set_local_state(presence);
+        self.send_awareness(&update).await
+    }
+
+    /// Renew the local presence and drop peers that stopped renewing theirs.
+    ///
+    /// Call this every `AWARENESS_RENEW_INTERVAL_MS` while connected.
+    pub async fn renew_presence(&self) -> Result<()> {
+        let (update, peers) = {
+            let mut awareness = self.awareness.lock().unwrap();
+            let removed = awareness.remove_outdated(chrono::Utc::now().timestamp_millis());
+            (
+                awareness.renew_local_state(),
+                removed.then(|| awareness.peers()),
+            )
+        };
+
+        if let Some(peers) = peers {
+            self.emit_event(SyncEvent::PeersChanged { peers });
+        }
+        match update {
+            Some(update) => self.send_awareness(&update).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Ask the server for errors it is holding for either connection.
+    ///
+    /// Call this every `ERROR_CHECK_INTERVAL_MS` while connected. Errors
+    /// arrive as `SyncEvent::ServerError`.
+    pub async fn check_errors(&self) -> Result<()> {
+        let text = encode_error_check_control(&format_workspace_doc_id(&self.config.workspace_id));
+        if self.metadata_connected.load(Ordering::SeqCst) {
+            self.metadata_transport.send_text(&text).await?;
+        }
+        if self.body_connected.load(Ordering::SeqCst) {
+            self.body_transport.send_text(&text).await?;
+        }
+        Ok(())
+    }
+
+    /// Collaborators that are currently online.
+    pub fn peers(&self) -> Vec<AwarenessPeer> {
+        self.awareness.lock().unwrap().peers()
+    }
+
+    /// Send an awareness update over the body connection.
+    async fn send_awareness(&self, update: &AwarenessUpdate) -> Result<()> {
+        if !self.body_connected.load(Ordering::SeqCst) {
+            // Presence is ephemeral; it is re-announced after reconnecting
+            return Ok(());
+        }
+
+        let doc_id = format_workspace_doc_id(&self.config.workspace_id);
+        let text = encode_awareness_control(&doc_id, update);
+        self.body_transport.send_text(&text).await
+    }
+
     // ========================================================================
     // Focus-Based Sync APIs
     // ========================================================================
@@ -972,6 +1300,9 @@ impl<T: SyncTransport, FS: AsyncFileSystem + Send + Sync + 'static> std::fmt::De
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crdt::{BodyDocManager, MemoryStorage, SyncHandler, SyncMessage, WorkspaceCrdt};
+    use crate::fs::SyncToAsyncFs;
+    use crate::test_utils::MockFileSystem;
 
     #[test]
     fn test_sync_client_config() {
@@ -1099,4 +1430,212 @@ mod tests {
         bridge(&event);
         assert!(rx.try_recv().is_err());
     }
+
+    /// Transport that records sent binary and text messages.
+    #[derive(Default)]
+    struct RecordingTransport {
+        sent: Mutex<Vec<Vec<u8>>>,
+        texts: Mutex<Vec<String>>,
+        on_text: Mutex<Option<TextCallback>>,
+    }
+
+    impl SyncTransport for RecordingTransport {
+        async fn connect(&self, _config: &SyncConfig) -> Result<()> {
+            Ok(())
+        }
+
+        async fn send(&self, message: &[u8]) -> Result<()> {
+            self.sent.lock().unwrap().push(message.to_vec());
+            Ok(())
+        }
+
+        async fn send_text(&self, message: &str) -> Result<()> {
+            self.texts.lock().unwrap().push(message.to_string());
+            Ok(())
+        }
+
+        fn set_on_message(&self, _callback: MessageCallback) {}
+
+        fn set_on_text(&self, callback: TextCallback) {
+            *self.on_text.lock().unwrap() = Some(callback);
+        }
+
+        fn set_on_status(&self, _callback: super::super::transport::StatusCallback) {}
+
+        async fn disconnect(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        fn status(&self) -> ConnectionStatus {
+            ConnectionStatus::Connected
+        }
+    }
+
+    /// Client for workspace `ws123` over recording transports, with
+    /// in-memory storage.
+    #[allow(deprecated)]
+    fn test_client() -> SyncClient<RecordingTransport, SyncToAsyncFs<MockFileSystem>> {
+        let storage: Arc<dyn CrdtStorage> = Arc::new(MemoryStorage::new());
+        let sync_manager = Arc::new(RustSyncManager::new(
+            Arc::new(WorkspaceCrdt::new(Arc::clone(&storage))),
+            Arc::new(BodyDocManager::new(Arc::clone(&storage))),
+            Arc::new(SyncHandler::new(SyncToAsyncFs::new(MockFileSystem::new()))),
+        ));
+        let config = SyncClientConfig::new(
+            "wss://sync.example.com".to_string(),
+            "ws123".to_string(),
+            PathBuf::from("/workspace"),
+        );
+        SyncClient::new(
+            config,
+            RecordingTransport::default(),
+            RecordingTransport::default(),
+            sync_manager,
+        )
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_offline_messages_are_persisted_and_replayed() {
+        use crate::test_utils::body_update;
+        use futures_lite::future::block_on;
+
+        let client = test_client();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = Arc::clone(&events);
+        client.set_event_callback(Arc::new(move |event| {
+            if let SyncEvent::PendingChanged { count } = event {
+                events_clone.lock().unwrap().push(count);
+            }
+        }));
+
+        // Offline: both messages go to the durable outbox
+        let sender = client.outgoing_sender();
+        for text in ["one", "two"] {
+            sender
+                .send(OutgoingSyncMessage::body(
+                    "a.md".to_string(),
+                    SyncMessage::Update(body_update(text)).encode(),
+                ))
+                .unwrap();
+        }
+        assert_eq!(block_on(client.process_outgoing()), 0);
+        assert_eq!(client.pending_count(), 2);
+        assert_eq!(client.storage.pending_count().unwrap(), 2);
+
+        // Reconnect: outbox is replayed as SyncStep1 + one merged update
+        block_on(client.start()).unwrap();
+        assert_eq!(client.pending_count(), 0);
+        assert_eq!(client.body_transport.sent.lock().unwrap().len(), 2);
+        assert_eq!(events.lock().unwrap().as_slice(), &[2, 0]);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_presence_is_relayed_over_text() {
+        use futures_lite::future::block_on;
+
+        let client = test_client();
+
+        let peer_events = Arc::new(Mutex::new(Vec::new()));
+        let peer_events_clone = Arc::clone(&peer_events);
+        client.set_event_callback(Arc::new(move |event| {
+            if let SyncEvent::PeersChanged { peers } = event {
+                peer_events_clone.lock().unwrap().push(peers);
+            }
+        }));
+        block_on(client.start()).unwrap();
+
+        // Local presence goes out as an awareness control message
+        let presence = PeerPresence {
+            name: Some("Ada".to_string()),
+            file: Some("a.md".to_string()),
+            ..Default::default()
+        };
+        block_on(client.set_presence(Some(presence))).unwrap();
+        let sent = client.body_transport.texts.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        let (doc_id, update) = decode_awareness_control(&sent[0]).unwrap();
+        assert_eq!(doc_id, "workspace:ws123");
+        assert!(update.entries[0].state.as_deref().unwrap().contains("Ada"));
+
+        // A collaborator's presence arrives as text and becomes a peer
+        let mut remote = Awareness::with_client_id(7);
+        let remote_update = remote.set_local_state(Some(PeerPresence {
+            name: Some("Grace".to_string()),
+            ..Default::default()
+        }));
+        let on_text = client
+            .body_transport
+            .on_text
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap();
+        on_text(&encode_awareness_control("workspace:ws123", &remote_update));
+        assert_eq!(client.peers().len(), 1);
+        assert_eq!(client.peers()[0].client_id, 7);
+        assert_eq!(peer_events.lock().unwrap().len(), 1);
+
+        // Stopping announces that we left
+        block_on(client.stop());
+        let sent = client.body_transport.texts.lock().unwrap().clone();
+        let (_, goodbye) = decode_awareness_control(sent.last().unwrap()).unwrap();
+        assert!(goodbye.entries[0].state.is_none());
+        assert!(client.peers().is_empty());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_server_errors_for_this_client_are_emitted() {
+        use crate::crdt::{SyncErrorCode, encode_error_check_control, encode_sync_error_control};
+        use futures_lite::future::block_on;
+
+        let client = test_client();
+        let client_tag = client.config.client_tag.clone();
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = Arc::clone(&errors);
+        client.set_event_callback(Arc::new(move |event| {
+            if let SyncEvent::ServerError(error) = event {
+                errors_clone.lock().unwrap().push(error);
+            }
+        }));
+        block_on(client.start()).unwrap();
+
+        // Held errors are requested over both connections
+        block_on(client.check_errors()).unwrap();
+        let check = encode_error_check_control("workspace:ws123");
+        for transport in [&client.metadata_transport, &client.body_transport] {
+            assert_eq!(transport.texts.lock().unwrap().last(), Some(&check));
+        }
+
+        let error = |client: &str| {
+            encode_sync_error_control(&SyncServerError {
+                code: SyncErrorCode::RateLimited,
+                message: "Too many updates".to_string(),
+                doc_id: Some("workspace:ws123".to_string()),
+                client: Some(client.to_string()),
+                retry_after_ms: Some(500),
+            })
+        };
+        let on_text =
+            |transport: &RecordingTransport| transport.on_text.lock().unwrap().clone().unwrap();
+
+        // Errors for other peers of the document are ignored
+        on_text(&client.metadata_transport)(&error("someone-else"));
+        assert!(errors.lock().unwrap().is_empty());
+
+        on_text(&client.metadata_transport)(&error(&client_tag));
+        on_text(&client.body_transport)(&error(&client_tag));
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].retry_after_ms, Some(500));
+        assert!(client.peers().is_empty());
+    }
 }
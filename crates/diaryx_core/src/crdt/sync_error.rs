@@ -0,0 +1,141 @@
+//! Error control messages sent by the sync server.
+//!
+//! When the v2 sync server refuses a message — because the client is sending
+//! updates too quickly or a message exceeds the size limit — it answers on the
+//! text channel with:
+//!
+//! ```json
+//! {"type": "sync_error", "code": "rate_limited", "message": "...",
+//!  "doc_id": "workspace:abc", "client": "<tag>", "retry_after_ms": 1000}
+//! ```
+//!
+//! Errors only go to the connection whose message was refused. The server
+//! can only send text to one connection in reply to a control message from
+//! it, so an error for a refused update arrives with the reply to that
+//! connection's next control message. Clients therefore send a
+//! `check_errors` message (see [`encode_error_check_control`]) on every
+//! connection each [`ERROR_CHECK_INTERVAL_MS`]; the server answers it with
+//! nothing but the held error, if any. Each [`SyncClient`](super::SyncClient) still connects with a random
+//! `client` tag and ignores errors addressed to another tag. Updates refused
+//! by the server are not lost: they are exchanged again during the next sync
+//! handshake.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// How often clients poll each connection for held errors, in milliseconds.
+pub const ERROR_CHECK_INTERVAL_MS: u64 = 5_000;
+
+/// Why the server refused a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export, export_to = "bindings/")]
+pub enum SyncErrorCode {
+    /// The client exceeded the server's update rate; retry after `retry_after_ms`.
+    RateLimited,
+    /// The message was larger than the server accepts.
+    MessageTooLarge,
+}
+
+/// A `sync_error` control message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "bindings/")]
+pub struct SyncServerError {
+    /// Why the message was refused.
+    pub code: SyncErrorCode,
+    /// Human-readable explanation.
+    pub message: String,
+    /// Document the refused message was for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc_id: Option<String>,
+    /// Tag of the connection that sent the refused message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// How long to wait before sending again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(type = "number | null")]
+    pub retry_after_ms: Option<u64>,
+}
+
+impl SyncServerError {
+    /// Whether this error concerns the connection tagged `client_tag`.
+    ///
+    /// Untagged errors apply to every client.
+    pub fn is_for(&self, client_tag: &str) -> bool {
+        self.client.as_deref().is_none_or(|tag| tag == client_tag)
+    }
+}
+
+/// Build a `sync_error` control message.
+pub fn encode_sync_error_control(error: &SyncServerError) -> String {
+    let mut json = serde_json::to_value(error).unwrap_or_default();
+    json["type"] = "sync_error".into();
+    json.to_string()
+}
+
+/// Build a `check_errors` control message for `doc_id`.
+///
+/// The server replies with the error held for the sending connection, if any.
+pub fn encode_error_check_control(doc_id: &str) -> String {
+    serde_json::json!({ "type": "check_errors", "doc_id": doc_id }).to_string()
+}
+
+/// Parse a `sync_error` control message.
+///
+/// Returns `None` for any other control message.
+pub fn decode_sync_error_control(text: &str) -> Option<SyncServerError> {
+    let json: serde_json::Value = serde_json::from_str(text).ok()?;
+    if json.get("type")?.as_str()? != "sync_error" {
+        return None;
+    }
+    serde_json::from_value(json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let error = SyncServerError {
+            code: SyncErrorCode::RateLimited,
+            message: "Too many updates".to_string(),
+            doc_id: Some("workspace:abc".to_string()),
+            client: Some("tag-1".to_string()),
+            retry_after_ms: Some(250),
+        };
+        let text = encode_sync_error_control(&error);
+        assert!(text.contains("\"type\":\"sync_error\""));
+        assert!(text.contains("\"code\":\"rate_limited\""));
+        assert_eq!(decode_sync_error_control(&text), Some(error));
+    }
+
+    #[test]
+    fn test_ignores_other_control_messages() {
+        assert!(decode_sync_error_control(r#"{"type": "files_ready"}"#).is_none());
+        assert!(decode_sync_error_control("not json").is_none());
+    }
+
+    #[test]
+    fn test_error_check_is_not_an_error() {
+        let text = encode_error_check_control("workspace:abc");
+        assert!(text.contains("\"type\":\"check_errors\""));
+        assert!(text.contains("\"doc_id\":\"workspace:abc\""));
+        assert!(decode_sync_error_control(&text).is_none());
+    }
+
+    #[test]
+    fn test_is_for() {
+        let mut error = SyncServerError {
+            code: SyncErrorCode::MessageTooLarge,
+            message: String::new(),
+            doc_id: None,
+            client: Some("a".to_string()),
+            retry_after_ms: None,
+        };
+        assert!(error.is_for("a"));
+        assert!(!error.is_for("b"));
+        error.client = None;
+        assert!(error.is_for("b"));
+    }
+}
@@ -25,10 +25,22 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 use super::body_doc_manager::BodyDocManager;
+use super::share_scope::ShareScope;
 use super::types::FileMetadata;
-use crate::error::Result;
+use crate::error::{DiaryxError, Result};
 use crate::fs::{AsyncFileSystem, FileSystemEvent};
 use crate::metadata_writer;
+use crate::utils::path::is_contained;
+
+/// Whether a canonical path from a remote stays inside the workspace:
+/// relative, without `..`, and without backslashes that Windows would treat
+/// as separators.
+///
+/// Paths in remote updates come from other devices, so they are checked
+/// before anything is read from or written to disk.
+pub fn is_safe_sync_path(canonical_path: &str) -> bool {
+    !canonical_path.contains('\\') && is_contained(canonical_path)
+}
 
 /// Configuration for guest mode sync.
 ///
@@ -42,6 +54,11 @@ pub struct GuestConfig {
     /// If true, prefix paths with guest/{join_code}/ for OPFS storage.
     /// If false (in-memory storage), paths are used as-is.
     pub uses_opfs: bool,
+
+    /// Paths shared with this guest, if the session is scoped.
+    /// `None` means the whole workspace is shared.
+    #[serde(default)]
+    pub scope: Option<ShareScope>,
 }
 
 /// Handler for sync side effects.
@@ -111,6 +128,15 @@ impl<FS: AsyncFileSystem> SyncHandler<FS> {
         self.guest_config.read().unwrap().is_some()
     }
 
+    /// Get the share scope of the current guest session, if any.
+    pub fn guest_scope(&self) -> Option<ShareScope> {
+        self.guest_config
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|gc| gc.scope.clone())
+    }
+
     /// Get the storage path for a canonical path.
     ///
     /// Converts a canonical path (relative to workspace root) to an absolute
@@ -171,6 +197,19 @@ impl<FS: AsyncFileSystem> SyncHandler<FS> {
         stripped
     }
 
+    /// Check a path from a remote update, logging it if it leaves the
+    /// workspace.
+    fn check_remote_path(&self, canonical_path: &str) -> bool {
+        let safe = is_safe_sync_path(canonical_path);
+        if !safe {
+            log::warn!(
+                "SyncHandler: Ignoring path outside the workspace: {}",
+                canonical_path
+            );
+        }
+        safe
+    }
+
     /// Emit a filesystem event to the registered callback.
     fn emit_event(&self, event: FileSystemEvent) {
         if let Some(ref cb) = self.event_callback {
@@ -203,6 +242,16 @@ impl<FS: AsyncFileSystem> SyncHandler<FS> {
     ) -> Result<usize> {
         let mut synced_count = 0;
 
+        // Drop anything that would leave the workspace
+        let files: Vec<(String, FileMetadata)> = files
+            .into_iter()
+            .filter(|(path, _)| self.check_remote_path(path))
+            .collect();
+        let renames: Vec<(String, String)> = renames
+            .into_iter()
+            .filter(|(old, new)| self.check_remote_path(old) && self.check_remote_path(new))
+            .collect();
+
         // Track which renames actually succeeded (old file existed and was moved)
         let mut successful_old_paths: std::collections::HashSet<String> =
             std::collections::HashSet::new();
@@ -553,6 +602,10 @@ impl<FS: AsyncFileSystem> SyncHandler<FS> {
             return Ok(());
         }
 
+        if !self.check_remote_path(canonical_path) {
+            return Err(outside_workspace(canonical_path));
+        }
+
         let storage_path = self.get_storage_path(canonical_path);
         log::info!(
             "[SyncHandler] handle_remote_body_update START: canonical_path='{}', storage_path='{:?}', body_len={}, body_preview='{}'",
@@ -779,12 +832,18 @@ impl<FS: AsyncFileSystem> SyncHandler<FS> {
     /// This is used to populate body CRDTs with disk content before sync.
     /// The path is converted to storage path using guest config if set.
     pub async fn read_body_content(&self, canonical_path: &str) -> Result<String> {
+        if !self.check_remote_path(canonical_path) {
+            return Err(outside_workspace(canonical_path));
+        }
         let storage_path = self.get_storage_path(canonical_path);
         self.read_disk_body(&storage_path).await
     }
 
     /// Check if a file exists at the given canonical path.
     pub async fn file_exists(&self, canonical_path: &str) -> bool {
+        if !is_safe_sync_path(canonical_path) {
+            return false;
+        }
         let storage_path = self.get_storage_path(canonical_path);
         self.fs.exists(&storage_path).await
     }
@@ -800,6 +859,14 @@ impl<FS: AsyncFileSystem> SyncHandler<FS> {
     }
 }
 
+/// Error for a remote path that would leave the workspace.
+fn outside_workspace(canonical_path: &str) -> DiaryxError {
+    DiaryxError::InvalidPath {
+        path: PathBuf::from(canonical_path),
+        message: "outside the workspace".to_string(),
+    }
+}
+
 impl<FS: AsyncFileSystem> std::fmt::Debug for SyncHandler<FS> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let gc = self.guest_config.read().unwrap();
@@ -832,6 +899,16 @@ mod tests {
         assert_eq!(path, PathBuf::from("notes/hello.md"));
     }
 
+    #[test]
+    fn test_is_safe_sync_path() {
+        assert!(is_safe_sync_path("notes/hello.md"));
+        assert!(!is_safe_sync_path("../outside.md"));
+        assert!(!is_safe_sync_path("notes/../../outside.md"));
+        assert!(!is_safe_sync_path("/etc/passwd"));
+        assert!(!is_safe_sync_path("notes\\..\\..\\outside.md"));
+        assert!(!is_safe_sync_path(""));
+    }
+
     #[test]
     fn test_get_storage_path_with_workspace_root() {
         let handler = create_test_handler();
@@ -858,6 +935,7 @@ mod tests {
         handler.configure_guest(Some(GuestConfig {
             join_code: "ABC123".to_string(),
             uses_opfs: true,
+            scope: None,
         }));
 
         let path = handler.get_storage_path("notes/hello.md");
@@ -871,6 +949,7 @@ mod tests {
         handler.configure_guest(Some(GuestConfig {
             join_code: "ABC123".to_string(),
             uses_opfs: false, // In-memory, no prefix
+            scope: None,
         }));
 
         let path = handler.get_storage_path("notes/hello.md");
@@ -884,6 +963,7 @@ mod tests {
         handler.configure_guest(Some(GuestConfig {
             join_code: "ABC123".to_string(),
             uses_opfs: true,
+            scope: None,
         }));
 
         let canonical = handler.get_canonical_path("guest/ABC123/notes/hello.md");
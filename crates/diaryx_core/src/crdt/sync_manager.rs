@@ -28,6 +28,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 
 use super::body_doc_manager::BodyDocManager;
+use super::storage::CrdtStorage;
 use super::sync::SyncMessage;
 use super::sync_handler::SyncHandler;
 use super::types::{FileMetadata, UpdateOrigin};
@@ -478,6 +479,16 @@ impl<FS: AsyncFileSystem> RustSyncManager<FS> {
         SyncMessage::SyncStep1(sv).encode()
     }
 
+    /// Create a SyncStep2 message answering a remote workspace SyncStep1.
+    ///
+    /// Unlike `handle_workspace_message`, this does not append our own
+    /// SyncStep1, so it can be used by peers that each initiate their own
+    /// handshake without ping-ponging state vectors.
+    pub fn create_workspace_sync_step2(&self, remote_state_vector: &[u8]) -> Result<Vec<u8>> {
+        let diff = self.workspace_crdt.encode_diff(remote_state_vector)?;
+        Ok(SyncMessage::SyncStep2(diff).encode())
+    }
+
     /// Create an update message for local workspace changes.
     ///
     /// If `since_state_vector` is provided, returns only updates since that state.
@@ -706,6 +717,20 @@ impl<FS: AsyncFileSystem> RustSyncManager<FS> {
         SyncMessage::SyncStep1(sv).encode()
     }
 
+    /// Create a SyncStep2 message answering a remote body SyncStep1.
+    ///
+    /// Always returns a message, even when the diff is empty, so the remote
+    /// side can tell that its handshake for `doc_name` has completed.
+    pub fn create_body_sync_step2(
+        &self,
+        doc_name: &str,
+        remote_state_vector: &[u8],
+    ) -> Result<Vec<u8>> {
+        let body_doc = self.body_manager.get_or_create(doc_name);
+        let diff = body_doc.encode_diff(remote_state_vector)?;
+        Ok(SyncMessage::SyncStep2(diff).encode())
+    }
+
     /// Ensure body content is populated from disk before sync.
     ///
     /// This method reads the file content from disk and sets it into the body CRDT.
@@ -893,6 +918,11 @@ impl<FS: AsyncFileSystem> RustSyncManager<FS> {
     // File Discovery
     // =========================================================================
 
+    /// Get the storage backend shared by the workspace and body CRDTs.
+    pub fn storage(&self) -> Arc<dyn CrdtStorage> {
+        Arc::clone(self.workspace_crdt.storage())
+    }
+
     /// Get all active file paths in the workspace CRDT.
     ///
     /// Used by SyncClient to initiate body sync for all files after the body
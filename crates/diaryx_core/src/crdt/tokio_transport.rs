@@ -25,7 +25,7 @@ use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message;
 
 use super::transport::{
-    ConnectionStatus, MessageCallback, StatusCallback, SyncConfig, SyncTransport,
+    ConnectionStatus, MessageCallback, StatusCallback, SyncConfig, SyncTransport, TextCallback,
 };
 use crate::error::{DiaryxError, Result};
 
@@ -66,6 +66,9 @@ pub struct TokioTransport {
     /// Message callback.
     on_message: RwLock<Option<MessageCallback>>,
 
+    /// Text message callback.
+    on_text: RwLock<Option<TextCallback>>,
+
     /// Status callback.
     on_status: RwLock<Option<StatusCallback>>,
 
@@ -81,6 +84,7 @@ impl TokioTransport {
             status: RwLock::new(ConnectionStatus::Disconnected),
             connected: AtomicBool::new(false),
             on_message: RwLock::new(None),
+            on_text: RwLock::new(None),
             on_status: RwLock::new(None),
             task_handle: RwLock::new(None),
         }
@@ -148,6 +152,7 @@ impl SyncTransport for TokioTransport {
             .unwrap()
             .clone()
             .unwrap_or_else(|| Arc::new(default_message_callback));
+        let on_text = self.on_text.read().unwrap().clone();
 
         let connected_flag = Arc::new(AtomicBool::new(true));
         let connected_flag_clone = Arc::clone(&connected_flag);
@@ -171,6 +176,9 @@ impl SyncTransport for TokioTransport {
                             Some(Ok(Message::Text(text))) => {
                                 // Handle text messages (JSON control messages)
                                 log::debug!("[TokioTransport] Received text: {}", text);
+                                if let Some(ref on_text) = on_text {
+                                    on_text(&text);
+                                }
                             }
                             Some(Ok(Message::Ping(data))) => {
                                 if let Err(e) = write.send(Message::Pong(data)).await {
@@ -266,6 +274,11 @@ impl SyncTransport for TokioTransport {
         *cb = Some(callback);
     }
 
+    fn set_on_text(&self, callback: TextCallback) {
+        let mut cb = self.on_text.write().unwrap();
+        *cb = Some(callback);
+    }
+
     fn set_on_status(&self, callback: StatusCallback) {
         let mut cb = self.on_status.write().unwrap();
         *cb = Some(callback);
@@ -68,6 +68,9 @@ pub struct SyncConfig {
 
     /// Whether to write synced changes to disk.
     pub write_to_disk: bool,
+
+    /// Tag identifying this client in the server's `sync_error` messages.
+    pub client_tag: Option<String>,
 }
 
 impl SyncConfig {
@@ -79,6 +82,7 @@ impl SyncConfig {
             auth_token: None,
             multiplexed: false,
             write_to_disk: true,
+            client_tag: None,
         }
     }
 
@@ -90,6 +94,7 @@ impl SyncConfig {
             auth_token: None,
             multiplexed: true,
             write_to_disk: true,
+            client_tag: None,
         }
     }
 
@@ -105,6 +110,12 @@ impl SyncConfig {
         self
     }
 
+    /// Set the client tag sent as the `client` query parameter.
+    pub fn with_client_tag(mut self, tag: String) -> Self {
+        self.client_tag = Some(tag);
+        self
+    }
+
     /// Build the WebSocket URL with query parameters.
     pub fn build_url(&self) -> String {
         let mut url = self.server_url.clone();
@@ -128,6 +139,11 @@ impl SyncConfig {
             url.push_str(token);
         }
 
+        if let Some(ref tag) = self.client_tag {
+            url.push_str("&client=");
+            url.push_str(tag);
+        }
+
         url
     }
 }
@@ -169,6 +185,9 @@ pub enum ConnectionStatus {
 /// response to send back to the server.
 pub type MessageCallback = Arc<dyn Fn(&[u8]) -> Option<Vec<u8>> + Send + Sync>;
 
+/// Callback type for incoming text messages (JSON control messages).
+pub type TextCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
 /// Callback type for connection status changes.
 pub type StatusCallback = Arc<dyn Fn(ConnectionStatus) + Send + Sync>;
 
@@ -231,6 +250,13 @@ pub trait SyncTransport: Send + Sync {
     /// replaces the previous one.
     fn set_on_message(&self, callback: MessageCallback);
 
+    /// Set the callback for incoming text messages.
+    ///
+    /// Text frames carry JSON control messages such as awareness updates.
+    /// Transports that cannot receive text frames may keep the default,
+    /// which drops them.
+    fn set_on_text(&self, _callback: TextCallback) {}
+
     /// Set the callback for status changes.
     ///
     /// The callback is invoked whenever the connection status changes.
@@ -323,6 +349,18 @@ mod tests {
         assert!(url.contains("token=mytoken"));
     }
 
+    #[test]
+    fn test_build_url_with_client_tag() {
+        let config = SyncConfig::body(
+            "wss://sync.example.com/sync".to_string(),
+            "workspace123".to_string(),
+        )
+        .with_client_tag("abc".to_string());
+
+        let url = config.build_url();
+        assert!(url.ends_with("&client=abc"));
+    }
+
     #[test]
     fn test_build_url_existing_query_params() {
         let config = SyncConfig::metadata(
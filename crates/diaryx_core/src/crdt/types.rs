@@ -274,6 +274,28 @@ pub struct CrdtUpdate {
     pub device_name: Option<String>,
 }
 
+/// An outgoing sync message waiting in the durable outbox.
+///
+/// Messages are queued while the sync client is disconnected and replayed
+/// once it reconnects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingMessage {
+    /// Unique identifier for this queue entry
+    pub id: i64,
+
+    /// Document name ("workspace" for metadata, file path for body)
+    pub doc_name: String,
+
+    /// Whether this is a body doc (true) or workspace (false)
+    pub is_body: bool,
+
+    /// Encoded sync message bytes
+    pub message: Vec<u8>,
+
+    /// Unix timestamp when this message was queued (milliseconds)
+    pub queued_at: i64,
+}
+
 /// Origin of a CRDT update, used to distinguish local vs remote changes.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UpdateOrigin {
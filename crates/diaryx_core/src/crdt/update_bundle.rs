@@ -0,0 +1,582 @@
+//! File-based CRDT sync via update bundles.
+//!
+//! An update bundle is a single file holding Y.js updates for the workspace
+//! doc and any changed body docs, so CRDT state can travel over a shared
+//! folder (Syncthing, a USB drive, an email attachment) instead of a live
+//! connection. Importing a bundle merges it like any other remote update,
+//! so bundles can be applied in any order, more than once, without
+//! conflicts.
+//!
+//! # Format
+//!
+//! ```text
+//! "DXUB" | version: u8 | header_len: u32 BE | header JSON
+//!        | (frame_len: u32 BE | frame_message_v2(doc_id, SyncMessage))*
+//! ```
+//!
+//! Each doc contributes an `Update` message (changes the remote is missing)
+//! and a `SyncStep1` message (the exporter's state vector after export).
+//! The state vectors in bundles a remote wrote tell what it already has
+//! ([`received_state`]); recorded as a bundle with only state vectors, they
+//! let the next export to that remote carry only new changes.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let header = BundleHeader::new("laptop", "default");
+//! let bundle = export_update_bundle(&sync_manager, header, Some(&recorded)).await?;
+//! std::fs::write("laptop.dxsync", bundle.encode())?;
+//!
+//! // On the other device
+//! let bundle = UpdateBundle::decode(&std::fs::read("laptop.dxsync")?)?;
+//! let result = import_update_bundle(&sync_manager, &bundle, "default", true).await?;
+//! ```
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use yrs::StateVector;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+
+use super::sync::{
+    DocIdKind, SyncMessage, format_body_doc_id, format_workspace_doc_id, frame_message_v2,
+    parse_doc_id, unframe_message_v2,
+};
+use super::sync_handler::is_safe_sync_path;
+use super::sync_manager::RustSyncManager;
+use crate::error::{DiaryxError, Result};
+use crate::fs::AsyncFileSystem;
+
+/// File extension used for update bundles.
+pub const UPDATE_BUNDLE_EXTENSION: &str = "dxsync";
+
+const BUNDLE_MAGIC: &[u8; 4] = b"DXUB";
+const BUNDLE_VERSION: u8 = 1;
+
+/// Y.js v1 updates of this size or smaller carry no changes.
+const EMPTY_UPDATE_LEN: usize = 2;
+
+/// Metadata stored at the start of a bundle.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleHeader {
+    /// Name of the device that wrote the bundle.
+    pub device_name: String,
+    /// Workspace ID used for the bundle's doc IDs.
+    pub workspace_id: String,
+    /// Creation time (Unix milliseconds).
+    pub created_at: i64,
+}
+
+impl BundleHeader {
+    /// Create a header stamped with the current time.
+    pub fn new(device_name: impl Into<String>, workspace_id: impl Into<String>) -> Self {
+        Self {
+            device_name: device_name.into(),
+            workspace_id: workspace_id.into(),
+            created_at: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+/// A set of framed sync messages that can be written to a file.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateBundle {
+    /// Bundle metadata.
+    pub header: BundleHeader,
+    /// `(doc_id, encoded SyncMessage)` pairs, workspace first.
+    messages: Vec<(String, Vec<u8>)>,
+}
+
+impl UpdateBundle {
+    /// Create an empty bundle.
+    pub fn new(header: BundleHeader) -> Self {
+        Self {
+            header,
+            messages: Vec::new(),
+        }
+    }
+
+    /// Append an encoded sync message for `doc_id`.
+    pub fn push(&mut self, doc_id: impl Into<String>, message: Vec<u8>) {
+        self.messages.push((doc_id.into(), message));
+    }
+
+    /// Get the `(doc_id, message)` pairs in the bundle.
+    pub fn messages(&self) -> &[(String, Vec<u8>)] {
+        &self.messages
+    }
+
+    /// Number of docs with updates in this bundle.
+    pub fn update_count(&self) -> usize {
+        self.messages
+            .iter()
+            .filter(|(_, msg)| matches!(SyncMessage::decode(msg), Ok(Some(SyncMessage::Update(_)))))
+            .count()
+    }
+
+    /// Whether the bundle carries any updates.
+    pub fn has_updates(&self) -> bool {
+        self.update_count() > 0
+    }
+
+    /// Collect the state vectors in the bundle, keyed by doc ID.
+    pub fn state_vectors(&self) -> HashMap<String, Vec<u8>> {
+        self.messages
+            .iter()
+            .filter_map(|(doc_id, msg)| match SyncMessage::decode(msg) {
+                Ok(Some(SyncMessage::SyncStep1(sv))) => Some((doc_id.clone(), sv)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Encode the bundle to bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let header = serde_json::to_vec(&self.header).unwrap_or_default();
+
+        let mut out = Vec::new();
+        out.extend_from_slice(BUNDLE_MAGIC);
+        out.push(BUNDLE_VERSION);
+        out.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        out.extend_from_slice(&header);
+
+        for (doc_id, message) in &self.messages {
+            let frame = frame_message_v2(doc_id, message);
+            out.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            out.extend_from_slice(&frame);
+        }
+
+        out
+    }
+
+    /// Decode a bundle from bytes.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let invalid =
+            |reason: &str| DiaryxError::Crdt(format!("Invalid update bundle: {}", reason));
+
+        if data.len() < BUNDLE_MAGIC.len() + 1 || &data[..BUNDLE_MAGIC.len()] != BUNDLE_MAGIC {
+            return Err(invalid("missing header"));
+        }
+        let version = data[BUNDLE_MAGIC.len()];
+        if version != BUNDLE_VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        let mut rest = &data[BUNDLE_MAGIC.len() + 1..];
+        let header_bytes = read_chunk(&mut rest).ok_or_else(|| invalid("truncated header"))?;
+        let header: BundleHeader =
+            serde_json::from_slice(header_bytes).map_err(|e| invalid(&e.to_string()))?;
+
+        let mut bundle = Self::new(header);
+        while !rest.is_empty() {
+            let frame = read_chunk(&mut rest).ok_or_else(|| invalid("truncated frame"))?;
+            let (doc_id, message) =
+                unframe_message_v2(frame).ok_or_else(|| invalid("malformed frame"))?;
+            bundle.push(doc_id, message);
+        }
+
+        Ok(bundle)
+    }
+}
+
+/// Read a `u32 BE` length-prefixed chunk, advancing `data`.
+fn read_chunk<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let chunk = data.get(4..4 + len)?;
+    *data = &data[4 + len..];
+    Some(chunk)
+}
+
+/// Result of importing an update bundle.
+#[derive(Debug, Default)]
+pub struct BundleImportResult {
+    /// Workspace files whose metadata changed.
+    pub changed_files: Vec<String>,
+    /// Files whose body content changed.
+    pub body_changed: Vec<String>,
+}
+
+/// Export the changes a remote is missing as an update bundle.
+///
+/// `remote_state` maps doc IDs to the state vectors the remote is known to
+/// have (see [`received_state`]). Docs without a recorded state
+/// vector are exported in full; pass `None` to export everything. Bodies
+/// with nothing new are skipped, but every doc's current state vector is
+/// included so the result can be recorded for the next export.
+pub async fn export_update_bundle<FS: AsyncFileSystem>(
+    sync_manager: &RustSyncManager<FS>,
+    header: BundleHeader,
+    remote_state: Option<&HashMap<String, Vec<u8>>>,
+) -> Result<UpdateBundle> {
+    let empty_sv = StateVector::default().encode_v1();
+    let remote_sv = |doc_id: &str| {
+        remote_state
+            .and_then(|state| state.get(doc_id))
+            .map(Vec::as_slice)
+            .unwrap_or(&empty_sv)
+            .to_vec()
+    };
+
+    let workspace_id = header.workspace_id.clone();
+    let mut bundle = UpdateBundle::new(header);
+
+    let ws_doc_id = format_workspace_doc_id(&workspace_id);
+    let step2 = sync_manager.create_workspace_sync_step2(&remote_sv(&ws_doc_id))?;
+    if let Some(update) = non_empty_update(&step2) {
+        bundle.push(ws_doc_id.clone(), update);
+    }
+    bundle.push(ws_doc_id, sync_manager.create_workspace_sync_step1());
+
+    for path in sync_manager.get_all_file_paths() {
+        if let Err(e) = sync_manager.ensure_body_content_loaded(&path).await {
+            log::warn!(
+                "[UpdateBundle] Failed to load body content for {}: {:?}",
+                path,
+                e
+            );
+        }
+
+        let doc_id = format_body_doc_id(&workspace_id, &path);
+        let step2 = sync_manager.create_body_sync_step2(&path, &remote_sv(&doc_id))?;
+        if let Some(update) = non_empty_update(&step2) {
+            bundle.push(doc_id.clone(), update);
+        }
+        bundle.push(doc_id, sync_manager.create_body_sync_step1(&path));
+    }
+
+    Ok(bundle)
+}
+
+/// What every device that wrote one of `bundles` is known to have, for
+/// [`export_update_bundle`].
+///
+/// Each device counts with its newest bundle. Per doc, each client's clock is
+/// the lowest across devices, and docs some device never reported are left
+/// out so they're exported in full.
+pub fn received_state(bundles: &[UpdateBundle]) -> HashMap<String, Vec<u8>> {
+    let mut newest: HashMap<&str, &UpdateBundle> = HashMap::new();
+    for bundle in bundles {
+        let entry = newest
+            .entry(bundle.header.device_name.as_str())
+            .or_insert(bundle);
+        if bundle.header.created_at > entry.header.created_at {
+            *entry = bundle;
+        }
+    }
+
+    let mut devices = newest.values().map(|bundle| bundle.state_vectors());
+    let Some(first) = devices.next() else {
+        return HashMap::new();
+    };
+    let mut common: HashMap<String, StateVector> = first
+        .into_iter()
+        .filter_map(|(doc_id, sv)| Some((doc_id, StateVector::decode_v1(&sv).ok()?)))
+        .collect();
+    for state in devices {
+        common.retain(|doc_id, sv| {
+            let Some(other) = state
+                .get(doc_id)
+                .and_then(|other| StateVector::decode_v1(other).ok())
+            else {
+                return false;
+            };
+            *sv = StateVector::new(
+                sv.iter()
+                    .map(|(client, clock)| (*client, (*clock).min(other.get(client))))
+                    .filter(|(_, clock)| *clock > 0)
+                    .collect(),
+            );
+            true
+        });
+    }
+
+    common
+        .into_iter()
+        .map(|(doc_id, sv)| (doc_id, sv.encode_v1()))
+        .collect()
+}
+
+/// Turn a SyncStep2 into an Update message, or `None` if it carries no changes.
+fn non_empty_update(step2: &[u8]) -> Option<Vec<u8>> {
+    match SyncMessage::decode(step2) {
+        Ok(Some(SyncMessage::SyncStep2(diff))) if diff.len() > EMPTY_UPDATE_LEN => {
+            Some(SyncMessage::Update(diff).encode())
+        }
+        _ => None,
+    }
+}
+
+/// Merge an update bundle into the local CRDT.
+///
+/// The workspace update is applied before any body so new files exist by the
+/// time their content arrives. Fails without applying anything if the bundle
+/// belongs to a different workspace or has a body outside the workspace.
+/// Bundles come from untrusted files, so workspace files outside the
+/// workspace are never written either (see `SyncHandler`).
+pub async fn import_update_bundle<FS: AsyncFileSystem>(
+    sync_manager: &RustSyncManager<FS>,
+    bundle: &UpdateBundle,
+    workspace_id: &str,
+    write_to_disk: bool,
+) -> Result<BundleImportResult> {
+    if bundle.header.workspace_id != workspace_id {
+        return Err(DiaryxError::Crdt(format!(
+            "Bundle is for workspace {} (expected {})",
+            bundle.header.workspace_id, workspace_id
+        )));
+    }
+
+    let mut workspace_updates = Vec::new();
+    let mut body_updates = Vec::new();
+    for (doc_id, message) in bundle.messages() {
+        if !matches!(SyncMessage::decode(message)?, Some(SyncMessage::Update(_))) {
+            continue;
+        }
+        match parse_doc_id(doc_id) {
+            Some(DocIdKind::Workspace(_)) => workspace_updates.push(message),
+            Some(DocIdKind::Body { file_path, .. }) if is_safe_sync_path(&file_path) => {
+                body_updates.push((file_path, message))
+            }
+            Some(DocIdKind::Body { file_path, .. }) => {
+                return Err(DiaryxError::Crdt(format!(
+                    "Bundle has a body outside the workspace: {}",
+                    file_path
+                )));
+            }
+            None => return Err(DiaryxError::Crdt(format!("Unknown doc ID: {}", doc_id))),
+        }
+    }
+
+    let mut result = BundleImportResult::default();
+
+    for message in workspace_updates {
+        let synced = sync_manager
+            .handle_workspace_message(message, write_to_disk)
+            .await?;
+        result.changed_files.extend(synced.changed_files);
+    }
+
+    for (file_path, message) in body_updates {
+        let synced = sync_manager
+            .handle_body_message(&file_path, message, write_to_disk)
+            .await?;
+        if synced.content.is_some() {
+            result.body_changed.push(file_path);
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::{
+        BodyDocManager, CrdtStorage, FileMetadata, MemoryStorage, SyncHandler, WorkspaceCrdt,
+    };
+    use crate::fs::SyncToAsyncFs;
+    use crate::test_utils::MockFileSystem;
+    use futures_lite::future::block_on;
+    use std::sync::Arc;
+
+    type TestManager = RustSyncManager<SyncToAsyncFs<MockFileSystem>>;
+
+    fn create_device() -> (TestManager, Arc<WorkspaceCrdt>, Arc<BodyDocManager>) {
+        create_device_on(MockFileSystem::new())
+    }
+
+    fn create_device_on(
+        fs: MockFileSystem,
+    ) -> (TestManager, Arc<WorkspaceCrdt>, Arc<BodyDocManager>) {
+        let storage: Arc<dyn CrdtStorage> = Arc::new(MemoryStorage::new());
+        let workspace_crdt = Arc::new(WorkspaceCrdt::new(Arc::clone(&storage)));
+        let body_manager = Arc::new(BodyDocManager::new(Arc::clone(&storage)));
+        let sync_handler = Arc::new(SyncHandler::new(SyncToAsyncFs::new(fs)));
+        let manager = RustSyncManager::new(
+            Arc::clone(&workspace_crdt),
+            Arc::clone(&body_manager),
+            sync_handler,
+        );
+        (manager, workspace_crdt, body_manager)
+    }
+
+    fn add_file(ws: &WorkspaceCrdt, bodies: &BodyDocManager, path: &str, body: &str) {
+        ws.set_file(path, FileMetadata::new(Some(path.to_string())))
+            .unwrap();
+        bodies.get_or_create(path).set_body(body).unwrap();
+    }
+
+    #[test]
+    fn test_bundle_encode_decode_roundtrip() {
+        let mut bundle = UpdateBundle::new(BundleHeader::new("laptop", "ws"));
+        bundle.push("workspace:ws", SyncMessage::Update(vec![1, 2, 3]).encode());
+        bundle.push("body:ws/a.md", SyncMessage::SyncStep1(vec![0]).encode());
+
+        let decoded = UpdateBundle::decode(&bundle.encode()).unwrap();
+        assert_eq!(decoded.header, bundle.header);
+        assert_eq!(decoded.messages(), bundle.messages());
+        assert_eq!(decoded.update_count(), 1);
+        assert_eq!(decoded.state_vectors().len(), 1);
+    }
+
+    #[test]
+    fn test_decode_rejects_garbage() {
+        assert!(UpdateBundle::decode(b"").is_err());
+        assert!(UpdateBundle::decode(b"not a bundle").is_err());
+
+        let mut truncated = UpdateBundle::new(BundleHeader::new("laptop", "ws"));
+        truncated.push("workspace:ws", vec![1, 2, 3]);
+        let bytes = truncated.encode();
+        assert!(UpdateBundle::decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_export_import_between_devices() {
+        let (a, a_ws, a_bodies) = create_device();
+        let (b, b_ws, b_bodies) = create_device();
+        add_file(&a_ws, &a_bodies, "a.md", "from a");
+
+        let bundle =
+            block_on(export_update_bundle(&a, BundleHeader::new("a", "ws"), None)).unwrap();
+        assert!(bundle.has_updates());
+
+        let bundle = UpdateBundle::decode(&bundle.encode()).unwrap();
+        block_on(import_update_bundle(&b, &bundle, "ws", false)).unwrap();
+
+        assert!(b_ws.get_file("a.md").is_some());
+        assert_eq!(b_bodies.get_or_create("a.md").get_body(), "from a");
+    }
+
+    #[test]
+    fn test_export_against_recorded_state_is_incremental() {
+        let (a, a_ws, a_bodies) = create_device();
+        add_file(&a_ws, &a_bodies, "a.md", "first");
+
+        let first = block_on(export_update_bundle(&a, BundleHeader::new("a", "ws"), None)).unwrap();
+        let recorded = first.state_vectors();
+
+        // Nothing changed since the recorded export
+        let empty = block_on(export_update_bundle(
+            &a,
+            BundleHeader::new("a", "ws"),
+            Some(&recorded),
+        ))
+        .unwrap();
+        assert!(!empty.has_updates());
+
+        add_file(&a_ws, &a_bodies, "b.md", "second");
+        let next = block_on(export_update_bundle(
+            &a,
+            BundleHeader::new("a", "ws"),
+            Some(&recorded),
+        ))
+        .unwrap();
+        // Workspace update plus the new body only
+        assert_eq!(next.update_count(), 2);
+    }
+
+    #[test]
+    fn test_received_state_is_what_every_device_has() {
+        let (a, a_ws, a_bodies) = create_device();
+        let (b, b_ws, b_bodies) = create_device();
+        let (c, _, _) = create_device();
+        add_file(&a_ws, &a_bodies, "a.md", "from a");
+        let from_a =
+            block_on(export_update_bundle(&a, BundleHeader::new("a", "ws"), None)).unwrap();
+        block_on(import_update_bundle(&b, &from_a, "ws", false)).unwrap();
+        add_file(&b_ws, &b_bodies, "b.md", "from b");
+        let mut old_b = BundleHeader::new("b", "ws");
+        old_b.created_at -= 1000;
+        let stale_b = block_on(export_update_bundle(&c, old_b, None)).unwrap();
+        let from_b =
+            block_on(export_update_bundle(&b, BundleHeader::new("b", "ws"), None)).unwrap();
+
+        // B's newest bundle has everything A wrote, so A only sends new changes
+        let state = received_state(&[stale_b.clone(), from_b.clone()]);
+        let next = block_on(export_update_bundle(
+            &a,
+            BundleHeader::new("a", "ws"),
+            Some(&state),
+        ))
+        .unwrap();
+        assert!(!next.has_updates());
+
+        // A device that has nothing yet keeps the export full
+        let state = received_state(&[from_b, from_a.clone()]);
+        assert_eq!(
+            state.get("body:ws/a.md"),
+            from_a.state_vectors().get("body:ws/a.md")
+        );
+        assert!(!state.contains_key("body:ws/b.md"));
+        assert!(received_state(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_import_is_idempotent_and_merges_concurrent_edits() {
+        let (a, a_ws, a_bodies) = create_device();
+        let (b, b_ws, b_bodies) = create_device();
+        add_file(&a_ws, &a_bodies, "a.md", "from a");
+        add_file(&b_ws, &b_bodies, "b.md", "from b");
+
+        let from_a =
+            block_on(export_update_bundle(&a, BundleHeader::new("a", "ws"), None)).unwrap();
+        let from_b =
+            block_on(export_update_bundle(&b, BundleHeader::new("b", "ws"), None)).unwrap();
+
+        block_on(import_update_bundle(&b, &from_a, "ws", false)).unwrap();
+        let again = block_on(import_update_bundle(&b, &from_a, "ws", false)).unwrap();
+        assert!(again.body_changed.is_empty());
+        block_on(import_update_bundle(&a, &from_b, "ws", false)).unwrap();
+
+        for ws in [&a_ws, &b_ws] {
+            assert!(ws.get_file("a.md").is_some());
+            assert!(ws.get_file("b.md").is_some());
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_other_workspace() {
+        let (a, a_ws, a_bodies) = create_device();
+        let (b, _, _) = create_device();
+        add_file(&a_ws, &a_bodies, "a.md", "from a");
+
+        let bundle =
+            block_on(export_update_bundle(&a, BundleHeader::new("a", "ws"), None)).unwrap();
+        assert!(block_on(import_update_bundle(&b, &bundle, "other", false)).is_err());
+    }
+
+    #[test]
+    fn test_import_never_writes_outside_workspace() {
+        let fs = MockFileSystem::new();
+        let (b, _, b_bodies) = create_device_on(fs.clone());
+
+        // Workspace entries outside the workspace are merged but not written
+        let (a, a_ws, a_bodies) = create_device();
+        add_file(&a_ws, &a_bodies, "notes/a.md", "from a");
+        for path in ["../outside.md", "notes\\..\\..\\x.md"] {
+            a_ws.set_file(path, FileMetadata::new(None)).unwrap();
+        }
+        let bundle =
+            block_on(export_update_bundle(&a, BundleHeader::new("a", "ws"), None)).unwrap();
+        block_on(import_update_bundle(&b, &bundle, "ws", true)).unwrap();
+        assert!(fs.get_content("notes/a.md").is_some());
+        assert!(fs.get_content("../outside.md").is_none());
+        assert!(fs.get_content("notes\\..\\..\\x.md").is_none());
+
+        // A body outside the workspace fails the whole import
+        for path in ["../../x.md", "/etc/x.md", "notes\\..\\..\\x.md"] {
+            let (_, _, c_bodies) = create_device();
+            let body = c_bodies.get_or_create(path);
+            body.set_body("hostile").unwrap();
+            let mut hostile = UpdateBundle::new(BundleHeader::new("c", "ws"));
+            hostile.push(
+                format_body_doc_id("ws", path),
+                SyncMessage::Update(body.encode_state_as_update()).encode(),
+            );
+            assert!(block_on(import_update_bundle(&b, &hostile, "ws", true)).is_err());
+            assert!(fs.get_content(path).is_none());
+            assert!(b_bodies.get(path).is_none());
+        }
+    }
+}
@@ -745,6 +745,15 @@ impl<'a, FS: AsyncFileSystem + Clone> ExportOps<'a, FS> {
     ) -> crate::error::Result<crate::export::ExportStats> {
         self.inner().execute_export(plan, options).await
     }
+
+    /// Export a plan's entries for another app, returning the files to write.
+    pub async fn export_to_format(
+        &self,
+        plan: &crate::export::ExportPlan,
+        format: crate::export_formats::ExportFormat,
+    ) -> crate::error::Result<Vec<crate::command::BinaryExportFile>> {
+        self.inner().export_to_format(plan, format).await
+    }
 }
 
 // ============================================================================
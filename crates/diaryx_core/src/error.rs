@@ -10,6 +10,7 @@
 //! - **Configuration errors**: `DiaryxError::ConfigParse`, `DiaryxError::ConfigNotInitialized`
 //! - **Editor errors**: `DiaryxError::NoEditorFound`, `DiaryxError::EditorLaunchFailed`
 //! - **Workspace errors**: `DiaryxError::WorkspaceNotFound`, `DiaryxError::WorkspaceAlreadyExists`
+//! - **Publishing errors**: `DiaryxError::ThemeNotFound`, `DiaryxError::ThemeTemplate`
 //!
 //! # IPC Serialization
 //!
@@ -163,6 +164,28 @@ pub enum DiaryxError {
     #[error("Template already exists: '{0}'")]
     TemplateAlreadyExists(PathBuf),
 
+    /// Error for a publishing theme that is neither built in nor a folder.
+    #[error("Theme not found: '{0}'")]
+    ThemeNotFound(String),
+
+    /// Error for a publishing theme template that fails to compile or render.
+    #[error("Error in theme template '{template}': {message}")]
+    ThemeTemplate {
+        /// Template name (e.g. `page.html`)
+        template: String,
+        /// Description of the problem, with the offending template lines
+        message: String,
+    },
+
+    /// Error for another app's export that can't be read.
+    #[error("Cannot import {source_name}: {message}")]
+    Import {
+        /// Export file or folder being imported
+        source_name: String,
+        /// Description of the problem
+        message: String,
+    },
+
     /// Error for invalid path structure (e.g., missing parent directory or filename).
     #[error("Invalid path '{path}': {message}")]
     InvalidPath {
@@ -219,6 +242,9 @@ impl From<&DiaryxError> for SerializableError {
             DiaryxError::WorkspaceAlreadyExists(_) => "WorkspaceAlreadyExists",
             DiaryxError::TemplateNotFound(_) => "TemplateNotFound",
             DiaryxError::TemplateAlreadyExists(_) => "TemplateAlreadyExists",
+            DiaryxError::ThemeNotFound(_) => "ThemeNotFound",
+            DiaryxError::ThemeTemplate { .. } => "ThemeTemplate",
+            DiaryxError::Import { .. } => "Import",
             DiaryxError::InvalidPath { .. } => "InvalidPath",
             DiaryxError::Unsupported(_) => "Unsupported",
             #[cfg(feature = "crdt")]
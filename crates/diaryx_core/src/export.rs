@@ -12,8 +12,12 @@ use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use crate::command::BinaryExportFile;
 use crate::error::{DiaryxError, Result};
+use crate::export_formats::ExportFormat;
 use crate::fs::AsyncFileSystem;
+use crate::path_utils::normalize_path;
+use crate::redaction::{self, Redaction};
 use crate::workspace::{IndexFrontmatter, Workspace};
 
 /// Result of planning an export operation
@@ -44,6 +48,8 @@ pub struct ExportFile {
     pub dest_path: PathBuf,
     /// Contents entries that will be filtered out (if any)
     pub filtered_contents: Vec<String>,
+    /// Parts of the file that will be left out for the audience
+    pub redactions: Vec<Redaction>,
 }
 
 /// A file that was excluded from export
@@ -111,6 +117,52 @@ pub struct ExportOptions {
     pub keep_audience: bool,
 }
 
+/// Options for compiling a subtree into one document
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompileOptions {
+    /// Only include entries visible to this audience (`"*"` for every
+    /// non-private entry, every entry if not set)
+    pub audience: Option<String>,
+    /// Document title (defaults to the compiled index's title)
+    pub title: Option<String>,
+    /// Start each entry with its title as a heading at its depth
+    pub title_headings: bool,
+    /// Shift each entry's own headings down by its depth
+    pub shift_headings: bool,
+    /// Put the title of entries that have contents on a page of its own
+    pub title_pages: bool,
+    /// Separator between sibling entries, by depth: the first is used between
+    /// the index's children, the next between their children, and so on
+    /// (deeper levels reuse the last)
+    pub separators: Vec<String>,
+    /// Markdown files placed before the compiled entries
+    pub front_matter: Vec<PathBuf>,
+    /// Markdown files placed after the compiled entries
+    pub back_matter: Vec<PathBuf>,
+}
+
+/// A subtree compiled into one markdown document
+#[derive(Debug, Clone, Serialize)]
+pub struct CompiledDocument {
+    /// Markdown with `title` (and `author`) frontmatter
+    pub markdown: String,
+    /// Compiled entries, in reading order
+    pub entries: Vec<PathBuf>,
+    /// Entries left out for the audience
+    pub excluded: Vec<ExcludedFile>,
+}
+
+/// State of a compile while walking the tree
+struct Compilation<'a> {
+    options: &'a CompileOptions,
+    root_dir: &'a Path,
+    out_dir: &'a Path,
+    sections: Vec<String>,
+    entries: Vec<PathBuf>,
+    excluded: Vec<ExcludedFile>,
+    visited: HashSet<PathBuf>,
+}
+
 /// Export operations (async-first)
 pub struct Exporter<FS: AsyncFileSystem> {
     workspace: Workspace<FS>,
@@ -178,11 +230,9 @@ impl<FS: AsyncFileSystem> Exporter<FS> {
         visited: &mut HashSet<PathBuf>,
     ) -> Result<bool> {
         // Avoid cycles
-        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
-        if visited.contains(&canonical) {
+        if !visited.insert(visit_key(path, root_dir)) {
             return Ok(false);
         }
-        visited.insert(canonical);
 
         // Parse the file - handle files without frontmatter gracefully
         let parse_result = self.workspace.parse_index(path).await;
@@ -269,12 +319,21 @@ impl<FS: AsyncFileSystem> Exporter<FS> {
             }
         }
 
+        // Blocks and properties for other audiences
+        let redactions = match self.workspace.fs_ref().read_to_string(path).await {
+            Ok(content) => redaction::redact(&content, Some(audience))
+                .map(|redacted| redacted.redactions)
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+
         // Add this file to included list
         included.push(ExportFile {
             source_path: path.to_path_buf(),
             relative_path,
             dest_path,
             filtered_contents,
+            redactions,
         });
 
         Ok(true)
@@ -349,6 +408,281 @@ impl<FS: AsyncFileSystem> Exporter<FS> {
         ExclusionReason::NoAudienceDefined
     }
 
+    /// Export the entries of a plan to another journaling app's format,
+    /// returning the files to write (relative to the destination)
+    pub async fn export_to_format(
+        &self,
+        plan: &ExportPlan,
+        format: ExportFormat,
+    ) -> Result<Vec<BinaryExportFile>> {
+        crate::export_formats::export_to_format(&self.workspace, plan, format).await
+    }
+
+    /// Compile an index and everything under it into one markdown document.
+    ///
+    /// Walks `contents` depth-first from `index`, which can be any index in
+    /// the workspace whose root is `workspace_root`. Relative links and images
+    /// are rewritten to work from the index's folder, where the document is
+    /// meant to be written.
+    pub async fn compile(
+        &self,
+        workspace_root: &Path,
+        index: &Path,
+        options: &CompileOptions,
+    ) -> Result<CompiledDocument> {
+        let root_dir = workspace_root.parent().unwrap_or(workspace_root);
+        let out_dir = index.parent().unwrap_or(Path::new(""));
+
+        // A subtree inherits the audience of the index's ancestors
+        let inherited = match options.audience {
+            Some(_) => self.ancestor_audience(index, root_dir).await,
+            None => None,
+        };
+
+        let mut state = Compilation {
+            options,
+            root_dir,
+            out_dir,
+            sections: Vec::new(),
+            entries: Vec::new(),
+            excluded: Vec::new(),
+            visited: HashSet::new(),
+        };
+        let audience = options.audience.as_deref();
+        let front = self
+            .read_matter(&options.front_matter, out_dir, audience)
+            .await?;
+        self.compile_entry(index, 0, inherited.as_ref(), None, &mut state)
+            .await?;
+        let back = self
+            .read_matter(&options.back_matter, out_dir, audience)
+            .await?;
+
+        let root = self
+            .workspace
+            .fs_ref()
+            .read_to_string(index)
+            .await
+            .ok()
+            .and_then(|content| redaction::redact(&content, options.audience.as_deref()).ok())
+            .and_then(|redacted| crate::frontmatter::parse_or_empty(&redacted.content).ok());
+        let title = options
+            .title
+            .clone()
+            .or_else(|| {
+                root.as_ref()
+                    .and_then(|file| crate::frontmatter::get_string(&file.frontmatter, "title"))
+                    .map(str::to_string)
+            })
+            .unwrap_or_else(|| crate::link_parser::path_to_title(&index.to_string_lossy()));
+        let mut frontmatter = indexmap::IndexMap::new();
+        frontmatter.insert("title".to_string(), serde_yaml::Value::String(title));
+        if let Some(author) = root
+            .as_ref()
+            .and_then(|file| crate::frontmatter::get_property(&file.frontmatter, "author"))
+        {
+            frontmatter.insert("author".to_string(), author.clone());
+        }
+
+        let body = front
+            .into_iter()
+            .chain(state.sections)
+            .chain(back)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let markdown = crate::frontmatter::serialize(&frontmatter, &format!("\n{}\n", body))?;
+
+        Ok(CompiledDocument {
+            markdown,
+            entries: state.entries,
+            excluded: state.excluded,
+        })
+    }
+
+    /// Add an entry and its contents to a compile. Returns whether the entry
+    /// was included.
+    async fn compile_entry(
+        &self,
+        path: &Path,
+        depth: usize,
+        inherited_audience: Option<&Vec<String>>,
+        separator: Option<&str>,
+        state: &mut Compilation<'_>,
+    ) -> Result<bool> {
+        if !state.visited.insert(visit_key(path, state.root_dir)) {
+            return Ok(false);
+        }
+
+        let (frontmatter, index) = match self.workspace.parse_index(path).await {
+            Ok(index) => (index.frontmatter.clone(), Some(index)),
+            Err(DiaryxError::NoFrontmatter(_)) => (IndexFrontmatter::default(), None),
+            Err(DiaryxError::YamlParse { path, message }) => {
+                log::warn!(
+                    "[Export] Compiling file with YAML parse error: {} - {}",
+                    path.display(),
+                    message
+                );
+                (IndexFrontmatter::default(), None)
+            }
+            Err(DiaryxError::Yaml(e)) => {
+                log::warn!("[Export] Compiling file with YAML error: {}", e);
+                (IndexFrontmatter::default(), None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let options = state.options;
+        let child_audience = match options.audience {
+            Some(ref audience) => {
+                let (visible, effective) =
+                    self.check_visibility(&frontmatter, audience, inherited_audience);
+                if !visible {
+                    let reason =
+                        self.get_exclusion_reason(&frontmatter, audience, inherited_audience);
+                    state.excluded.push(ExcludedFile {
+                        path: path.to_path_buf(),
+                        reason,
+                    });
+                    return Ok(false);
+                }
+                effective.or_else(|| inherited_audience.cloned())
+            }
+            None => None,
+        };
+
+        let body = match index {
+            Some(ref index) => index.body.clone(),
+            None => {
+                let content = self
+                    .workspace
+                    .fs_ref()
+                    .read_to_string(path)
+                    .await
+                    .map_err(|e| DiaryxError::FileRead {
+                        path: path.to_path_buf(),
+                        source: e,
+                    })?;
+                crate::frontmatter::extract_body(&content).to_string()
+            }
+        };
+
+        let mut section = Vec::new();
+        if let Some(separator) = separator.filter(|s| !s.trim().is_empty()) {
+            section.push(separator.trim().to_string());
+        }
+        // The compiled index's title is the document's title
+        if depth > 0 {
+            let title = frontmatter
+                .title
+                .clone()
+                .unwrap_or_else(|| crate::link_parser::path_to_title(&path.to_string_lossy()));
+            let heading = format!("{} {}", "#".repeat(depth.min(6)), title);
+            if options.title_pages && frontmatter.is_index() {
+                section.push(format!(
+                    "<div class=\"title-page\" style=\"break-before: page; break-after: page\">\n\n{}\n\n</div>",
+                    heading
+                ));
+            } else if options.title_headings {
+                section.push(heading);
+            }
+        }
+        let (body, _) = redaction::redact_body(&body, options.audience.as_deref(), 1);
+        let entry_dir = path.parent().unwrap_or(Path::new(""));
+        let mut body = rebase_links(&body, entry_dir, state.out_dir);
+        if options.shift_headings {
+            body = shift_headings(&body, depth);
+        }
+        if !body.trim().is_empty() {
+            section.push(body.trim().to_string());
+        }
+        if !section.is_empty() {
+            state.sections.push(section.join("\n\n"));
+        }
+        state.entries.push(path.to_path_buf());
+
+        if let Some(ref index) = index {
+            let separator = options
+                .separators
+                .get(depth)
+                .or(options.separators.last())
+                .cloned();
+            let mut first = true;
+            for child in frontmatter.contents_list() {
+                let child_path = index.resolve_path(child);
+                let child_path = if child_path.is_absolute() {
+                    child_path
+                } else {
+                    state.root_dir.join(&child_path)
+                };
+                if !self.workspace.fs_ref().exists(&child_path).await {
+                    continue;
+                }
+                let included = Box::pin(self.compile_entry(
+                    &child_path,
+                    depth + 1,
+                    child_audience.as_ref(),
+                    if first { None } else { separator.as_deref() },
+                    state,
+                ))
+                .await?;
+                first &= !included;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Audience of the nearest ancestor of an index (following `part_of`)
+    /// that declares one
+    async fn ancestor_audience(&self, index: &Path, root_dir: &Path) -> Option<Vec<String>> {
+        let mut seen = HashSet::new();
+        let mut file = self.workspace.parse_index(index).await.ok()?;
+        loop {
+            let parent = file.resolve_path(file.frontmatter.part_of.as_deref()?);
+            let parent = if parent.is_absolute() {
+                parent
+            } else {
+                root_dir.join(parent)
+            };
+            if !seen.insert(parent.clone()) {
+                return None;
+            }
+            file = self.workspace.parse_index(&parent).await.ok()?;
+            if let Some(ref audience) = file.frontmatter.audience {
+                return Some(audience.clone());
+            }
+        }
+    }
+
+    /// Bodies of front or back matter files for the audience, with links
+    /// rebased to `out_dir`
+    async fn read_matter(
+        &self,
+        paths: &[PathBuf],
+        out_dir: &Path,
+        audience: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let mut bodies = Vec::new();
+        for path in paths {
+            let content = self
+                .workspace
+                .fs_ref()
+                .read_to_string(path)
+                .await
+                .map_err(|e| DiaryxError::FileRead {
+                    path: path.clone(),
+                    source: e,
+                })?;
+            let body = crate::frontmatter::extract_body(&content);
+            let (body, _) = redaction::redact_body(body, audience, 1);
+            let body = rebase_links(&body, path.parent().unwrap_or(Path::new("")), out_dir);
+            if !body.trim().is_empty() {
+                bodies.push(body.trim().to_string());
+            }
+        }
+        Ok(bodies)
+    }
+
     /// Execute an export plan
     /// Only available on native platforms (not WASM) since it writes to the filesystem
     #[cfg(not(target_arch = "wasm32"))]
@@ -389,6 +723,9 @@ impl<FS: AsyncFileSystem> Exporter<FS> {
                     source: e,
                 })?;
 
+            // Leave out blocks and properties for other audiences
+            let content = redaction::redact(&content, Some(&plan.audience))?.content;
+
             // Process content if needed (filter contents array)
             let processed_content = if !export_file.filtered_contents.is_empty() {
                 self.filter_contents_in_file(&content, &export_file.filtered_contents, options)?
@@ -520,6 +857,112 @@ impl std::fmt::Display for ExportStats {
     }
 }
 
+/// Key for the set of visited entries: the path relative to the workspace
+/// root, with `.` and `..` resolved lexically (the filesystem may not be a
+/// real one, so paths can't be canonicalized)
+fn visit_key(path: &Path, root_dir: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    pathdiff::diff_paths(&path, normalize_path(root_dir)).unwrap_or(path)
+}
+
+/// Whether a line opens or closes a fenced code block
+fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+/// Shift the ATX headings of a markdown body down by `by` levels (at most to
+/// level 6), leaving code blocks alone
+fn shift_headings(body: &str, by: usize) -> String {
+    if by == 0 {
+        return body.to_string();
+    }
+    let mut in_code = false;
+    let mut lines = Vec::new();
+    for line in body.lines() {
+        if is_fence(line) {
+            in_code = !in_code;
+        }
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let rest = &line[indent..];
+        let level = rest.len() - rest.trim_start_matches('#').len();
+        let is_heading = !in_code
+            && indent <= 3
+            && (1..=6).contains(&level)
+            && (rest.len() == level || rest[level..].starts_with([' ', '\t']));
+        if is_heading {
+            lines.push(format!(
+                "{}{}{}",
+                &line[..indent],
+                "#".repeat((level + by).min(6)),
+                &rest[level..]
+            ));
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+/// Rewrite the relative link and image targets of a markdown body written in
+/// `from_dir` so they work from `to_dir`
+fn rebase_links(body: &str, from_dir: &Path, to_dir: &Path) -> String {
+    let prefix = match pathdiff::diff_paths(from_dir, to_dir) {
+        Some(prefix) if !prefix.as_os_str().is_empty() => prefix
+            .to_string_lossy()
+            .replace('\\', "/")
+            .replace(' ', "%20"),
+        _ => return body.to_string(),
+    };
+
+    let mut in_code = false;
+    let mut lines = Vec::new();
+    for line in body.lines() {
+        if is_fence(line) {
+            in_code = !in_code;
+        }
+        if in_code || !line.contains("](") {
+            lines.push(line.to_string());
+            continue;
+        }
+        let mut out = String::with_capacity(line.len() + prefix.len());
+        let mut rest = line;
+        while let Some(pos) = rest.find("](") {
+            let (before, after) = rest.split_at(pos + 2);
+            out.push_str(before);
+            let (open, target) = match after.strip_prefix('<') {
+                Some(target) => ("<", target),
+                None => ("", after),
+            };
+            let end = target
+                .find(|c: char| c == ')' || c == '>' || c.is_whitespace())
+                .unwrap_or(target.len());
+            let url = &target[..end];
+            out.push_str(open);
+            if is_relative_url(url) {
+                out.push_str(&prefix);
+                out.push('/');
+            }
+            out.push_str(url);
+            rest = &target[end..];
+        }
+        out.push_str(rest);
+        lines.push(out);
+    }
+    lines.join("\n")
+}
+
+/// Whether a link target is a path relative to the linking file
+fn is_relative_url(url: &str) -> bool {
+    let has_scheme = url.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    !url.is_empty() && !url.starts_with(['#', '/']) && !has_scheme
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -649,4 +1092,158 @@ mod tests {
         // Root should track that private.md was filtered
         assert!(root.filtered_contents.contains(&"private.md".to_string()));
     }
+
+    #[test]
+    fn test_redactions_reported_and_removed() {
+        let fs = make_test_fs();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: Root\nlocation: Home\nproperty_audience:\n  location: private\naudience:\n  - family\n---\n\nShared\n\n:::audience private\nSecret\n:::\n",
+        )
+        .unwrap();
+
+        let exporter = Exporter::new(SyncToAsyncFs::new(fs.clone()));
+        let plan = block_on_test(exporter.plan_export(
+            Path::new("/workspace/README.md"),
+            "family",
+            Path::new("/export"),
+        ))
+        .unwrap();
+        assert_eq!(
+            plan.included[0].redactions,
+            vec![
+                Redaction::Block {
+                    start_line: 12,
+                    end_line: 14,
+                    audience: vec!["private".to_string()],
+                },
+                Redaction::Property {
+                    name: "location".to_string(),
+                    audience: vec!["private".to_string()],
+                },
+            ]
+        );
+
+        block_on_test(exporter.execute_export(&plan, &ExportOptions::default())).unwrap();
+        assert_eq!(
+            fs.read_to_string(Path::new("/export/README.md")).unwrap(),
+            "---\ntitle: Root\n---\n\nShared\n\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_subtree() {
+        let fs = make_test_fs();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: Root\ncontents:\n  - book/index.md\naudience:\n  - family\n---\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/book/index.md"),
+            "---\ntitle: My Book\nauthor: Ann\npart_of: ../README.md\ncontents:\n  - part-one/index.md\n  - secret.md\n  - epilogue.md\n---\n\nA novel.\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/book/part-one/index.md"),
+            "---\ntitle: Part One\npart_of: ../index.md\ncontents:\n  - one.md\n  - two.md\n---\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/book/part-one/one.md"),
+            "---\ntitle: Chapter One\npart_of: index.md\n---\n\n# Morning\n\n![Map](map.png)\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/book/part-one/two.md"),
+            "---\ntitle: Chapter Two\npart_of: index.md\n---\n\nLater.\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/book/secret.md"),
+            "---\ntitle: Secret\npart_of: index.md\naudience:\n  - private\n---\n\nHidden\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/book/epilogue.md"),
+            "---\ntitle: Epilogue\npart_of: index.md\n---\n\nThe end.\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/book/preface.md"),
+            "Dedicated to you.\n",
+        )
+        .unwrap();
+
+        let exporter = Exporter::new(SyncToAsyncFs::new(fs));
+        let compiled = block_on_test(exporter.compile(
+            Path::new("/workspace/README.md"),
+            Path::new("/workspace/book/index.md"),
+            &CompileOptions {
+                audience: Some("family".to_string()),
+                title_headings: true,
+                shift_headings: true,
+                title_pages: true,
+                separators: vec!["---".to_string(), "* * *".to_string()],
+                front_matter: vec![PathBuf::from("/workspace/book/preface.md")],
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(compiled.entries.len(), 5);
+        assert_eq!(compiled.excluded.len(), 1);
+        assert_eq!(
+            compiled.markdown,
+            "---\ntitle: My Book\nauthor: Ann\n---\n\nDedicated to you.\n\nA novel.\n\n\
+             <div class=\"title-page\" style=\"break-before: page; break-after: page\">\n\n# Part One\n\n</div>\n\n\
+             ## Chapter One\n\n### Morning\n\n![Map](part-one/map.png)\n\n\
+             * * *\n\n## Chapter Two\n\nLater.\n\n\
+             ---\n\n# Epilogue\n\nThe end.\n"
+        );
+    }
+
+    #[test]
+    fn test_entries_visited_once() {
+        let fs = make_test_fs();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: Root\ncontents:\n  - one.md\n  - ./one.md\naudience:\n  - family\n---\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/one.md"),
+            "---\ntitle: One\npart_of: README.md\ncontents:\n  - README.md\n---\n\nOnce.\n",
+        )
+        .unwrap();
+
+        // The cycle back to the root must be caught even when the root's
+        // path isn't normalized
+        let root = Path::new("/workspace/sub/../README.md");
+        let exporter = Exporter::new(SyncToAsyncFs::new(fs));
+        let compiled =
+            block_on_test(exporter.compile(root, root, &CompileOptions::default())).unwrap();
+        assert_eq!(compiled.entries.len(), 2);
+        assert_eq!(compiled.markdown.matches("Once.").count(), 1);
+
+        let plan =
+            block_on_test(exporter.plan_export(root, "family", Path::new("/export"))).unwrap();
+        assert_eq!(plan.included.len(), 2);
+    }
+
+    #[test]
+    fn test_shift_headings_and_rebase_links() {
+        assert_eq!(
+            shift_headings("# A\n```\n# code\n```\n##### B\n#tag", 2),
+            "### A\n```\n# code\n```\n###### B\n#tag"
+        );
+        assert_eq!(
+            rebase_links(
+                "[a](a.md#x) [w](https://x.org) ![i](<my img.png>) [s](#top)",
+                Path::new("/w/my notes"),
+                Path::new("/w"),
+            ),
+            "[a](my%20notes/a.md#x) [w](https://x.org) ![i](<my%20notes/my img.png>) [s](#top)"
+        );
+    }
 }
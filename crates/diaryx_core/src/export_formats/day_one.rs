@@ -0,0 +1,167 @@
+//! Day One JSON archive.
+//!
+//! `Journal.json` lists the dated entries, each with its title as a heading
+//! above the body. Embedded images become entry photos, stored in `photos/`
+//! under their MD5 hash like Day One's own exports, and links between
+//! exported entries become `dayone://` links. Zipping the files gives an
+//! archive Day One can import.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use md5::{Digest, Md5};
+use serde_json::{Value, json};
+
+use super::{ExportEntry, map_links, slash_path};
+use crate::command::BinaryExportFile;
+use crate::error::Result;
+use crate::publish::site;
+
+/// Journal file of the archive
+pub const JOURNAL_FILE: &str = "Journal.json";
+/// Folder of the archive's photos
+pub const PHOTOS_DIR: &str = "photos";
+
+pub(super) fn export(
+    entries: &[ExportEntry],
+    attachments: &BTreeMap<PathBuf, Vec<u8>>,
+) -> Result<Vec<BinaryExportFile>> {
+    let dated: Vec<&ExportEntry> = entries.iter().filter(|e| e.date.is_some()).collect();
+    let uuids: HashMap<&Path, String> = dated
+        .iter()
+        .map(|entry| {
+            (
+                entry.path.as_path(),
+                uuid(slash_path(&entry.path).as_bytes()),
+            )
+        })
+        .collect();
+
+    let mut files = Vec::new();
+    let mut written = HashSet::new();
+    let mut journal = Vec::new();
+    for entry in dated {
+        let entry_id = &uuids[entry.path.as_path()];
+        let mut photos = Vec::new();
+        let body = map_links(&entry.body, |link| {
+            let (path, _) = entry.resolve_url(link.url)?;
+            if let Some(target) = uuids.get(path.as_path()) {
+                return Some(format!("[{}](dayone://view?entryId={})", link.text, target));
+            }
+            let bytes = attachments.get(&path)?;
+            let Some(kind) = photo_type(&path).filter(|_| link.image) else {
+                // Other files can't be attached, keep the text
+                return Some(link.text.to_string());
+            };
+            let md5 = hex(&Md5::digest(bytes));
+            let identifier = uuid(format!("{}:{}", entry_id, slash_path(&path)).as_bytes());
+            let file_name = format!("{}/{}.{}", PHOTOS_DIR, md5, kind);
+            if written.insert(file_name.clone()) {
+                files.push(BinaryExportFile {
+                    path: file_name,
+                    data: bytes.clone(),
+                });
+            }
+            let order = photos.len();
+            photos.push(json!({
+                "identifier": identifier,
+                "md5": md5,
+                "type": kind,
+                "orderInEntry": order,
+            }));
+            Some(format!("![](dayone-moment://{})", identifier))
+        });
+
+        let date = entry
+            .date
+            .map(|date| {
+                date.with_timezone(&Utc)
+                    .format("%Y-%m-%dT%H:%M:%SZ")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let mut record = json!({
+            "uuid": entry_id,
+            "creationDate": date,
+            "modifiedDate": date,
+            "timeZone": "UTC",
+            "text": format!("# {}\n\n{}", entry.title, body.trim()),
+        });
+        let tags = site::tags_of(&entry.frontmatter);
+        if !tags.is_empty() {
+            record["tags"] = json!(tags);
+        }
+        if !photos.is_empty() {
+            record["photos"] = Value::Array(photos);
+        }
+        journal.push(record);
+    }
+
+    let journal = json!({
+        "metadata": { "version": "1.0" },
+        "entries": journal,
+    });
+    files.insert(
+        0,
+        BinaryExportFile {
+            path: JOURNAL_FILE.to_string(),
+            data: serde_json::to_vec_pretty(&journal).map_err(std::io::Error::from)?,
+        },
+    );
+    Ok(files)
+}
+
+/// Day One identifier (32 uppercase hex digits) derived from some bytes
+fn uuid(bytes: &[u8]) -> String {
+    hex(&Md5::digest(bytes)).to_ascii_uppercase()
+}
+
+/// Lowercase hex digits of some bytes
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Day One photo type of an image file
+fn photo_type(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Some("jpeg"),
+        "png" => Some("png"),
+        "gif" => Some("gif"),
+        "heic" => Some("heic"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ExportFormat;
+    use super::super::tests::export;
+    use super::*;
+
+    #[test]
+    fn test_day_one_archive() {
+        let files = export(ExportFormat::DayOne);
+        let journal: Value = serde_json::from_slice(&files[JOURNAL_FILE]).unwrap();
+        let entries = journal["entries"].as_array().unwrap();
+        // Only the dated entry
+        assert_eq!(entries.len(), 1);
+
+        let entry = &entries[0];
+        assert_eq!(entry["creationDate"], "2024-01-02T09:30:00Z");
+        assert_eq!(entry["tags"], json!(["travel", "family time"]));
+        assert_eq!(entry["uuid"].as_str().unwrap().len(), 32);
+
+        let photo = &entry["photos"][0];
+        let md5 = photo["md5"].as_str().unwrap();
+        assert_eq!(photo["type"], "png");
+        assert_eq!(files[&format!("photos/{}.png", md5)], b"png");
+        assert_eq!(
+            entry["text"],
+            format!(
+                "# January 2\n\nPacked. ![](dayone-moment://{})",
+                photo["identifier"].as_str().unwrap()
+            )
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Full-fidelity JSON dump.
+//!
+//! `diaryx.json` holds every exported entry, root first, with its frontmatter
+//! (as JSON), body and place in the hierarchy, so other tools can rebuild the
+//! workspace without parsing markdown files.
+
+use serde_json::json;
+
+use super::{ExportEntry, slash_path};
+use crate::command::BinaryExportFile;
+use crate::error::Result;
+
+/// File of the dump
+pub const JSON_FILE: &str = "diaryx.json";
+
+/// Version of the dump's layout
+pub const JSON_VERSION: u32 = 1;
+
+pub(super) fn export(entries: &[ExportEntry], audience: &str) -> Result<Vec<BinaryExportFile>> {
+    let records: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            json!({
+                "path": slash_path(&entry.path),
+                "title": entry.title,
+                "date": entry.date.map(|date| date.to_rfc3339()),
+                "part_of": entry.part_of.as_deref().map(slash_path),
+                "contents": entry.contents.iter().map(|p| slash_path(p)).collect::<Vec<_>>(),
+                "attachments": entry.attachments.iter().map(|p| slash_path(p)).collect::<Vec<_>>(),
+                "frontmatter": entry.frontmatter,
+                "body": entry.body,
+            })
+        })
+        .collect();
+
+    let dump = json!({
+        "version": JSON_VERSION,
+        "audience": audience,
+        "root": entries.first().map(|entry| slash_path(&entry.path)),
+        "entries": records,
+    });
+    Ok(vec![BinaryExportFile {
+        path: JSON_FILE.to_string(),
+        data: serde_json::to_vec_pretty(&dump).map_err(std::io::Error::from)?,
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::super::ExportFormat;
+    use super::super::tests::export;
+    use super::*;
+
+    #[test]
+    fn test_json_dump() {
+        let files = export(ExportFormat::Json);
+        assert_eq!(files.len(), 1);
+        let dump: Value = serde_json::from_slice(&files[JSON_FILE]).unwrap();
+        assert_eq!(dump["audience"], "family");
+        assert_eq!(dump["root"], "README.md");
+
+        let entries = dump["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0]["contents"],
+            json!(["2024/01/2024-01-02.md", "notes/trip.md"])
+        );
+
+        let day = &entries[1];
+        assert_eq!(day["date"], "2024-01-02T09:30:00+00:00");
+        assert_eq!(day["part_of"], "README.md");
+        assert_eq!(day["frontmatter"]["tags"], json!(["travel", "family time"]));
+        assert_eq!(day["attachments"], json!(["2024/01/map.png"]));
+        assert!(!day["body"].as_str().unwrap().contains("Hidden"));
+    }
+}
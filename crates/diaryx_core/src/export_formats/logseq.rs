@@ -0,0 +1,269 @@
+//! Logseq graph.
+//!
+//! Daily entries become journal pages (`journals/2024_01_02.md`, named like
+//! "Jan 2nd, 2024") and the other entries pages named after their title
+//! (`pages/Trip.md`). Page properties come first as `key:: value` lines,
+//! bodies are split into one outline block per paragraph, and children are
+//! listed under a "Contents" block. Attachments go to `assets/`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, NaiveDate};
+use serde_yaml::Value;
+
+use super::{ExportEntry, is_image, map_links, slash_path};
+use crate::command::BinaryExportFile;
+use crate::error::Result;
+use crate::publish::site;
+
+/// Properties that aren't copied as page properties
+const SKIPPED_PROPERTIES: &[&str] = &[
+    "title",
+    "tags",
+    "part_of",
+    "contents",
+    "attachments",
+    "audience",
+];
+
+pub(super) fn export(
+    entries: &[ExportEntry],
+    attachments: &BTreeMap<PathBuf, Vec<u8>>,
+) -> Result<Vec<BinaryExportFile>> {
+    // Page name and file of each entry
+    let mut taken = HashSet::new();
+    let mut pages: HashMap<&Path, (String, String)> = HashMap::new();
+    for entry in entries {
+        let page = match entry.day {
+            Some(day) => (
+                journal_name(day),
+                format!("journals/{}.md", day.format("%Y_%m_%d")),
+            ),
+            None => {
+                let mut name = entry.title.clone();
+                let mut n = 1;
+                while !taken.insert(name.to_lowercase()) {
+                    n += 1;
+                    name = format!("{} ({})", entry.title, n);
+                }
+                let file = format!("pages/{}.md", file_name(&name));
+                (name, file)
+            }
+        };
+        pages.insert(entry.path.as_path(), page);
+    }
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let (name, file) = &pages[entry.path.as_path()];
+        let mut out = String::new();
+
+        if entry.day.is_none() {
+            out.push_str(&format!("title:: {}\n", name));
+        }
+        let tags = site::tags_of(&entry.frontmatter);
+        if !tags.is_empty() {
+            out.push_str(&format!("tags:: {}\n", tags.join(", ")));
+        }
+        if let Some((parent, _)) = entry.part_of.as_deref().and_then(|p| pages.get(p)) {
+            out.push_str(&format!("part-of:: [[{}]]\n", parent));
+        }
+        for (key, value) in &entry.frontmatter {
+            if SKIPPED_PROPERTIES.contains(&key.as_str()) {
+                continue;
+            }
+            if let Some(value) = property_value(value) {
+                out.push_str(&format!("{}:: {}\n", key.replace('_', "-"), value));
+            }
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        let body = map_links(&entry.body, |link| {
+            let (path, _) = entry.resolve_url(link.url)?;
+            if let Some((target, _)) = pages.get(path.as_path()) {
+                return Some(if link.text == target {
+                    format!("[[{}]]", target)
+                } else {
+                    format!("[{}]([[{}]])", link.text, target)
+                });
+            }
+            if attachments.contains_key(&path) {
+                let bang = if link.image && is_image(&path) {
+                    "!"
+                } else {
+                    ""
+                };
+                return Some(format!(
+                    "{}[{}](../assets/{})",
+                    bang,
+                    link.text,
+                    asset_name(&path)
+                ));
+            }
+            // Entries left out of the export
+            (path.extension().is_some_and(|ext| ext == "md")).then(|| link.text.to_string())
+        });
+        for block in blocks(&body) {
+            out.push_str("- ");
+            out.push_str(&block.join("\n  "));
+            out.push('\n');
+        }
+
+        let children: Vec<&str> = entry
+            .contents
+            .iter()
+            .filter_map(|child| pages.get(child.as_path()))
+            .map(|(child, _)| child.as_str())
+            .collect();
+        if !children.is_empty() {
+            out.push_str("- Contents\n");
+            for child in children {
+                out.push_str(&format!("\t- [[{}]]\n", child));
+            }
+        }
+        if out.trim().is_empty() {
+            out = "- \n".to_string();
+        }
+
+        files.push(BinaryExportFile {
+            path: file.clone(),
+            data: out.into_bytes(),
+        });
+    }
+
+    for (path, bytes) in attachments {
+        files.push(BinaryExportFile {
+            path: format!("assets/{}", asset_name(path)),
+            data: bytes.clone(),
+        });
+    }
+    Ok(files)
+}
+
+/// Journal page name of a day, in Logseq's default format ("Jan 2nd, 2024")
+fn journal_name(day: NaiveDate) -> String {
+    let suffix = match (day.day() % 10, day.day() % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!(
+        "{} {}{}, {}",
+        day.format("%b"),
+        day.day(),
+        suffix,
+        day.year()
+    )
+}
+
+/// File name of a page, with namespaces (`/`) written like Logseq does
+fn file_name(name: &str) -> String {
+    name.replace('/', "___")
+        .chars()
+        .map(|c| match c {
+            '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// File name of an attachment in `assets/`
+fn asset_name(path: &Path) -> String {
+    slash_path(path).replace('/', "_")
+}
+
+/// A frontmatter value written as a page property, if it's simple enough
+fn property_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.contains('\n') => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Sequence(items) => {
+            let items: Option<Vec<String>> = items
+                .iter()
+                .map(|item| match item {
+                    Value::Sequence(_) | Value::Mapping(_) => None,
+                    item => property_value(item),
+                })
+                .collect();
+            items
+                .filter(|items| !items.is_empty())
+                .map(|items| items.join(", "))
+        }
+        _ => None,
+    }
+}
+
+/// Lines of the outline blocks of a body: its paragraphs, keeping fenced
+/// code in one block
+fn blocks(body: &str) -> Vec<Vec<&str>> {
+    let mut blocks = Vec::new();
+    let mut current = Vec::new();
+    let mut in_fence = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if !in_fence && line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        current.push(line);
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ExportFormat;
+    use super::super::tests::export;
+    use super::*;
+
+    #[test]
+    fn test_logseq_graph() {
+        let files = export(ExportFormat::Logseq);
+        let read = |path: &str| String::from_utf8(files[path].clone()).unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec![
+                "assets/2024_01_map.png",
+                "assets/notes_plan.pdf",
+                "journals/2024_01_02.md",
+                "pages/Journal.md",
+                "pages/Trip.md",
+            ]
+        );
+
+        assert_eq!(
+            read("pages/Journal.md"),
+            "title:: Journal\n\n- See [the trip]([[Trip]]).\n- Contents\n\t- [[Jan 2nd, 2024]]\n\t- [[Trip]]\n"
+        );
+        assert_eq!(
+            read("journals/2024_01_02.md"),
+            "tags:: travel, family time\npart-of:: [[Journal]]\ncreated:: 2024-01-02T09:30:00Z\n\n- Packed. ![Map](../assets/2024_01_map.png)\n"
+        );
+        assert_eq!(
+            read("pages/Trip.md"),
+            "title:: Trip\npart-of:: [[Journal]]\n\n- ## Day 1\n- Back to [January 2]([[Jan 2nd, 2024]]) and secret.\n- ```\n  [not](a-link.md)\n  ```\n"
+        );
+    }
+
+    #[test]
+    fn test_journal_name() {
+        let name = |d| journal_name(NaiveDate::from_ymd_opt(2024, 3, d).unwrap());
+        assert_eq!(name(1), "Mar 1st, 2024");
+        assert_eq!(name(12), "Mar 12th, 2024");
+        assert_eq!(name(23), "Mar 23rd, 2024");
+    }
+}
@@ -0,0 +1,524 @@
+//! Exports to other journaling apps' formats.
+//!
+//! Each format turns the entries of an [`ExportPlan`] into the files of an
+//! import for that app, so audience filtering and redaction still apply:
+//!
+//! - [`ExportFormat::DayOne`]: `Journal.json` and `photos/` of a Day One JSON
+//!   archive (zip them to import), from the dated entries
+//! - [`ExportFormat::Obsidian`]: a vault with the workspace's layout, using
+//!   wikilinks and tag lists
+//! - [`ExportFormat::Logseq`]: the `journals/`, `pages/` and `assets/` folders
+//!   of a Logseq graph, with bodies split into outline blocks
+//! - [`ExportFormat::Json`]: `diaryx.json`, every entry's frontmatter, body
+//!   and place in the hierarchy
+//!
+//! Entries are dated like on published sites: by their daily-entry path, or
+//! their `created` property.
+
+mod day_one;
+mod json;
+mod logseq;
+mod obsidian;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use indexmap::IndexMap;
+use serde_yaml::Value;
+
+use crate::command::BinaryExportFile;
+use crate::error::{DiaryxError, Result};
+use crate::export::ExportPlan;
+use crate::frontmatter;
+use crate::fs::AsyncFileSystem;
+use crate::publish::attachments::split_local_url;
+use crate::publish::site;
+use crate::redaction;
+use crate::utils::date::path_to_date;
+use crate::utils::path::{is_contained, normalize_path};
+use crate::workspace::Workspace;
+
+/// An app format entries can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Day One JSON archive
+    DayOne,
+    /// Obsidian vault
+    Obsidian,
+    /// Logseq graph
+    Logseq,
+    /// Full-fidelity JSON dump
+    Json,
+}
+
+impl ExportFormat {
+    /// Every format
+    pub const ALL: &[ExportFormat] = &[
+        ExportFormat::DayOne,
+        ExportFormat::Obsidian,
+        ExportFormat::Logseq,
+        ExportFormat::Json,
+    ];
+
+    /// Format from its name (`dayone`, `obsidian`, `logseq`, `json`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Name of the format
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::DayOne => "dayone",
+            ExportFormat::Obsidian => "obsidian",
+            ExportFormat::Logseq => "logseq",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// An entry of an export plan, with the parts for other audiences left out
+#[derive(Debug, Clone)]
+pub struct ExportEntry {
+    /// Path relative to the workspace folder
+    pub path: PathBuf,
+    /// Title (the `title` property or the file name)
+    pub title: String,
+    /// Frontmatter properties
+    pub frontmatter: IndexMap<String, Value>,
+    /// Markdown body
+    pub body: String,
+    /// Date, from the daily-entry path or the `created` property
+    pub date: Option<DateTime<FixedOffset>>,
+    /// Day of a daily entry, from its path
+    pub day: Option<NaiveDate>,
+    /// Parent entry (relative path), if it's exported
+    pub part_of: Option<PathBuf>,
+    /// Exported child entries (relative paths)
+    pub contents: Vec<PathBuf>,
+    /// Files the entry declares in `attachments` or links to from its body
+    /// (relative paths)
+    pub attachments: Vec<PathBuf>,
+}
+
+impl ExportEntry {
+    /// Workspace-relative path and `#fragment` of a local link in the body
+    pub fn resolve_url(&self, url: &str) -> Option<(PathBuf, Option<String>)> {
+        let (path, fragment) = split_local_url(url)?;
+        let path = match path.strip_prefix('/') {
+            Some(root_relative) => PathBuf::from(root_relative),
+            None => self.path.parent().unwrap_or(Path::new("")).join(path),
+        };
+        Some((normalize_path(&path), fragment.map(str::to_string)))
+    }
+}
+
+/// Export the entries of a plan to an app format, returning the files to
+/// write (relative to the destination)
+pub(crate) async fn export_to_format<FS: AsyncFileSystem>(
+    workspace: &Workspace<FS>,
+    plan: &ExportPlan,
+    format: ExportFormat,
+) -> Result<Vec<BinaryExportFile>> {
+    let entries = load_entries(workspace, plan).await?;
+    let mut attachments = BTreeMap::new();
+    for path in entries.iter().flat_map(|entry| &entry.attachments) {
+        if attachments.contains_key(path) {
+            continue;
+        }
+        if let Ok(bytes) = workspace
+            .fs_ref()
+            .read_binary(&plan.source_root.join(path))
+            .await
+        {
+            attachments.insert(path.clone(), bytes);
+        }
+    }
+
+    match format {
+        ExportFormat::DayOne => day_one::export(&entries, &attachments),
+        ExportFormat::Obsidian => obsidian::export(&entries, &attachments),
+        ExportFormat::Logseq => logseq::export(&entries, &attachments),
+        ExportFormat::Json => json::export(&entries, &plan.audience),
+    }
+}
+
+/// Read the entries of a plan, root first and then in `contents` order
+pub async fn load_entries<FS: AsyncFileSystem>(
+    workspace: &Workspace<FS>,
+    plan: &ExportPlan,
+) -> Result<Vec<ExportEntry>> {
+    let root_dir = &plan.source_root;
+    let relative = |path: &Path| -> PathBuf {
+        let path = normalize_path(&root_dir.join(path));
+        path.strip_prefix(root_dir)
+            .map(Path::to_path_buf)
+            .unwrap_or(path)
+    };
+
+    let mut entries = Vec::new();
+    for file in &plan.included {
+        let content = workspace
+            .fs_ref()
+            .read_to_string(&file.source_path)
+            .await
+            .map_err(|e| DiaryxError::FileRead {
+                path: file.source_path.clone(),
+                source: e,
+            })?;
+        let redacted = redaction::redact(&content, Some(&plan.audience))?;
+        let parsed = frontmatter::parse_or_empty(&redacted.content)?;
+        let path = relative(&file.relative_path);
+
+        // Hierarchy and declared attachments, resolved like the exporter does
+        let (mut contents, mut attachments, mut part_of) = (Vec::new(), Vec::new(), None);
+        if let Ok(index) = workspace.parse_index(&file.source_path).await {
+            contents = index
+                .frontmatter
+                .contents_list()
+                .iter()
+                .map(|child| relative(&index.resolve_path(child)))
+                .collect();
+            attachments = frontmatter::get_string_array(&parsed.frontmatter, "attachments")
+                .iter()
+                .map(|attachment| relative(&index.resolve_path(attachment)))
+                .filter(|attachment| is_contained(attachment))
+                .collect();
+            part_of = index
+                .frontmatter
+                .part_of
+                .as_deref()
+                .map(|parent| relative(&index.resolve_path(parent)));
+        }
+
+        let title = frontmatter::get_string(&parsed.frontmatter, "title")
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                file.source_path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("Untitled")
+                    .to_string()
+            });
+        let mut entry = ExportEntry {
+            date: site::date_of(&parsed.frontmatter, &file.source_path),
+            day: path_to_date(&file.source_path),
+            path,
+            title,
+            frontmatter: parsed.frontmatter,
+            body: parsed.body,
+            part_of,
+            contents,
+            attachments,
+        };
+
+        // Local files the body links to or embeds; files outside the
+        // workspace are never exported
+        let mut linked = Vec::new();
+        map_links(&entry.body, |link| {
+            if let Some((path, _)) = entry.resolve_url(link.url)
+                && is_contained(&path)
+                && path.extension().is_none_or(|ext| ext != "md")
+            {
+                linked.push(path);
+            }
+            None
+        });
+        for path in linked {
+            if !entry.attachments.contains(&path)
+                && workspace.fs_ref().exists(&root_dir.join(&path)).await
+            {
+                entry.attachments.push(path);
+            }
+        }
+        entries.push(entry);
+    }
+
+    // Keep only the links between exported entries
+    let exported: HashMap<PathBuf, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| (entry.path.clone(), idx))
+        .collect();
+    for entry in &mut entries {
+        entry.contents.retain(|child| exported.contains_key(child));
+        entry.part_of = entry.part_of.take().filter(|p| exported.contains_key(p));
+    }
+
+    // The plan lists children before their parent, so the root comes last
+    let mut order = Vec::with_capacity(entries.len());
+    let mut visited = vec![false; entries.len()];
+    let mut stack: Vec<usize> = (0..entries.len()).collect();
+    while let Some(idx) = stack.pop() {
+        if std::mem::replace(&mut visited[idx], true) {
+            continue;
+        }
+        order.push(idx);
+        for child in entries[idx].contents.iter().rev() {
+            stack.push(exported[child]);
+        }
+    }
+    let mut slots: Vec<Option<ExportEntry>> = entries.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .filter_map(|idx| slots[idx].take())
+        .collect())
+}
+
+/// An inline link or image in a markdown body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Link<'a> {
+    /// Whether it's an image (`![alt](src)`)
+    pub image: bool,
+    /// Link text or image description
+    pub text: &'a str,
+    /// Destination
+    pub url: &'a str,
+}
+
+/// Replace the inline links and images of a markdown body, outside code,
+/// with what `f` returns for them (`None` keeps a link as it is)
+pub(crate) fn map_links(body: &str, mut f: impl FnMut(&Link) -> Option<String>) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut in_fence = false;
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if in_fence || !line.contains("](") {
+            out.push_str(line);
+            continue;
+        }
+
+        let mut in_code = false;
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+            if rest.starts_with('`') {
+                in_code = !in_code;
+            } else if !in_code {
+                let image = rest.starts_with("![");
+                let start = if image { i + 1 } else { i };
+                if (image || rest.starts_with('['))
+                    && let Some((end, link)) = parse_link(line, start, image)
+                {
+                    match f(&link) {
+                        Some(replacement) => out.push_str(&replacement),
+                        None => out.push_str(&line[i..end]),
+                    }
+                    i = end;
+                    continue;
+                }
+            }
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            out.push_str(&rest[..len]);
+            i += len;
+        }
+    }
+    out
+}
+
+/// Parse the link whose text starts with the `[` at `start`, returning where
+/// it ends
+fn parse_link(line: &str, start: usize, image: bool) -> Option<(usize, Link<'_>)> {
+    let mut depth = 0;
+    let mut close = None;
+    for (offset, c) in line[start..].char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(start + offset);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let close = close?;
+    let dest = line[close + 1..].strip_prefix('(')?;
+    let dest_start = close + 2;
+
+    let (url, after) = match dest.strip_prefix('<') {
+        Some(angled) => {
+            let end = angled.find('>')?;
+            (&angled[..end], dest_start + 1 + end + 1)
+        }
+        None => {
+            let end = dest.find(|c: char| c == ')' || c.is_whitespace())?;
+            (&dest[..end], dest_start + end)
+        }
+    };
+    // Skip an optional title
+    let end = after + line[after..].find(')')? + 1;
+    Some((
+        end,
+        Link {
+            image,
+            text: &line[start + 1..close],
+            url,
+        },
+    ))
+}
+
+/// Path of a file with `/` separators
+pub(crate) fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Whether a file is an image, by its extension
+pub(crate) fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_ascii_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "gif" | "webp" | "heic" | "svg"
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::Exporter;
+    use crate::fs::{FileSystem, InMemoryFileSystem, SyncToAsyncFs, block_on_test};
+
+    /// A small workspace shared by the format tests
+    pub(super) fn make_workspace() -> InMemoryFileSystem {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: Journal\naudience: [family]\ncontents:\n  - 2024/01/2024-01-02.md\n  - notes/trip.md\n  - secret.md\n---\n\nSee [the trip](notes/trip.md#day-1).\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/2024/01/2024-01-02.md"),
+            "---\ntitle: January 2\ncreated: 2024-01-02T09:30:00Z\ntags: [travel, family time]\npart_of: ../../README.md\n---\n\nPacked. ![Map](map.png)\n\n:::audience private\nHidden\n:::\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/notes/trip.md"),
+            "---\ntitle: Trip\npart_of: ../README.md\nattachments:\n  - plan.pdf\n---\n\n## Day 1\n\nBack to [January 2](../2024/01/2024-01-02.md) and [secret](../secret.md).\n\n```\n[not](a-link.md)\n```\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/secret.md"),
+            "---\ntitle: Secret\naudience: [private]\npart_of: README.md\n---\n",
+        )
+        .unwrap();
+        fs.write_binary(Path::new("/workspace/2024/01/map.png"), b"png")
+            .unwrap();
+        fs.write_binary(Path::new("/workspace/notes/plan.pdf"), b"%PDF")
+            .unwrap();
+        fs
+    }
+
+    /// Export the shared workspace for `family`
+    pub(super) fn export(format: ExportFormat) -> BTreeMap<String, Vec<u8>> {
+        let exporter = Exporter::new(SyncToAsyncFs::new(make_workspace()));
+        let plan = block_on_test(exporter.plan_export(
+            Path::new("/workspace/README.md"),
+            "family",
+            Path::new("/export"),
+        ))
+        .unwrap();
+        block_on_test(exporter.export_to_format(&plan, format))
+            .unwrap()
+            .into_iter()
+            .map(|file| (file.path, file.data))
+            .collect()
+    }
+
+    #[test]
+    fn test_load_entries() {
+        let workspace = Workspace::new(SyncToAsyncFs::new(make_workspace()));
+        let plan = block_on_test(
+            Exporter::new(SyncToAsyncFs::new(make_workspace())).plan_export(
+                Path::new("/workspace/README.md"),
+                "family",
+                Path::new("/export"),
+            ),
+        )
+        .unwrap();
+        let entries = block_on_test(load_entries(&workspace, &plan)).unwrap();
+
+        let paths: Vec<_> = entries.iter().map(|e| slash_path(&e.path)).collect();
+        assert_eq!(
+            paths,
+            vec!["README.md", "2024/01/2024-01-02.md", "notes/trip.md"]
+        );
+        assert_eq!(entries[0].contents.len(), 2);
+        assert_eq!(entries[1].part_of, Some(PathBuf::from("README.md")));
+        assert_eq!(entries[1].day, NaiveDate::from_ymd_opt(2024, 1, 2));
+        assert_eq!(
+            entries[1].date.unwrap().to_rfc3339(),
+            "2024-01-02T09:30:00+00:00"
+        );
+        assert!(!entries[1].body.contains("Hidden"));
+        assert_eq!(
+            entries[1].attachments,
+            vec![PathBuf::from("2024/01/map.png")]
+        );
+        assert_eq!(
+            entries[2].attachments,
+            vec![PathBuf::from("notes/plan.pdf")]
+        );
+    }
+
+    #[test]
+    fn test_attachments_outside_workspace_skipped() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: Root\naudience: [family]\nattachments:\n  - ../outside/key.pem\n---\n\n[Secret](../outside/secret.txt) ![Photo](photo.png)\n",
+        )
+        .unwrap();
+        fs.write_binary(Path::new("/workspace/photo.png"), b"png")
+            .unwrap();
+        fs.write_file(Path::new("/outside/secret.txt"), "secret")
+            .unwrap();
+        fs.write_file(Path::new("/outside/key.pem"), "key").unwrap();
+
+        let exporter = Exporter::new(SyncToAsyncFs::new(fs));
+        let plan = block_on_test(exporter.plan_export(
+            Path::new("/workspace/README.md"),
+            "family",
+            Path::new("/export"),
+        ))
+        .unwrap();
+        for format in ExportFormat::ALL {
+            let files = block_on_test(exporter.export_to_format(&plan, *format)).unwrap();
+            assert!(files.iter().all(|file| is_contained(&file.path)));
+            assert!(
+                files
+                    .iter()
+                    .all(|file| file.data != b"secret" && file.data != b"key")
+            );
+        }
+    }
+
+    #[test]
+    fn test_map_links() {
+        let body = "A [link](a.md \"Title\") and ![img](<my img.png>) `[code](x.md)`\n```\n[fenced](y.md)\n```\n[[wiki]] [plain] ünï [b](b.md)";
+        let mut seen = Vec::new();
+        let out = map_links(body, |link| {
+            seen.push((link.image, link.text.to_string(), link.url.to_string()));
+            (link.url == "b.md").then(|| "B".to_string())
+        });
+        assert_eq!(
+            seen,
+            vec![
+                (false, "link".to_string(), "a.md".to_string()),
+                (true, "img".to_string(), "my img.png".to_string()),
+                (false, "b".to_string(), "b.md".to_string()),
+            ]
+        );
+        assert_eq!(out, body.replace("[b](b.md)", "B"));
+    }
+}
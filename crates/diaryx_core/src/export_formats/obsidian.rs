@@ -0,0 +1,154 @@
+//! Obsidian vault.
+//!
+//! Entries and attachments keep their paths, so the vault has the
+//! workspace's layout. Links become wikilinks (`[[notes/trip#Day 1|trip]]`,
+//! `![[map.png]]`), `part_of`, `contents` and `attachments` become wikilink
+//! properties, and tags are turned into Obsidian tag names.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde_yaml::Value;
+
+use super::{ExportEntry, is_image, map_links, slash_path};
+use crate::command::BinaryExportFile;
+use crate::error::Result;
+use crate::frontmatter;
+
+pub(super) fn export(
+    entries: &[ExportEntry],
+    attachments: &BTreeMap<PathBuf, Vec<u8>>,
+) -> Result<Vec<BinaryExportFile>> {
+    let exported: HashSet<&Path> = entries.iter().map(|e| e.path.as_path()).collect();
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let body = map_links(&entry.body, |link| {
+            let (path, fragment) = entry.resolve_url(link.url)?;
+            if exported.contains(path.as_path()) {
+                let mut target = note_name(&path);
+                if let Some(fragment) = fragment {
+                    target = format!("{}#{}", target, heading_of(&fragment));
+                }
+                return Some(format!("[[{}|{}]]", target, link.text));
+            }
+            if attachments.contains_key(&path) {
+                let target = slash_path(&path);
+                return Some(if link.image && is_image(&path) {
+                    format!("![[{}]]", target)
+                } else {
+                    format!("[[{}|{}]]", target, link.text)
+                });
+            }
+            // Entries left out of the export
+            (path.extension().is_some_and(|ext| ext == "md")).then(|| link.text.to_string())
+        });
+
+        let mut properties = entry.frontmatter.clone();
+        frontmatter::remove_property(&mut properties, "audience");
+        if let Some(parent) = &entry.part_of {
+            properties.insert("part_of".to_string(), wikilink(&note_name(parent)));
+        }
+        if properties.contains_key("contents") {
+            let contents = entry.contents.iter().map(|c| wikilink(&note_name(c)));
+            properties.insert("contents".to_string(), Value::Sequence(contents.collect()));
+        }
+        if properties.contains_key("attachments") {
+            let links = entry
+                .attachments
+                .iter()
+                .filter(|a| attachments.contains_key(*a))
+                .map(|a| wikilink(&slash_path(a)));
+            properties.insert("attachments".to_string(), Value::Sequence(links.collect()));
+        }
+        if let Some(tags) = properties.get_mut("tags") {
+            normalize_tags(tags);
+        }
+
+        let content = if properties.is_empty() {
+            body
+        } else {
+            frontmatter::serialize(&properties, &body)?
+        };
+        files.push(BinaryExportFile {
+            path: slash_path(&entry.path),
+            data: content.into_bytes(),
+        });
+    }
+
+    for (path, bytes) in attachments {
+        files.push(BinaryExportFile {
+            path: slash_path(path),
+            data: bytes.clone(),
+        });
+    }
+    Ok(files)
+}
+
+/// Name of a note in wikilinks (its path without `.md`)
+fn note_name(path: &Path) -> String {
+    slash_path(&path.with_extension(""))
+}
+
+/// A wikilink property value
+fn wikilink(target: &str) -> Value {
+    Value::String(format!("[[{}]]", target))
+}
+
+/// Heading a `#heading-slug` fragment most likely points to
+fn heading_of(fragment: &str) -> String {
+    fragment.replace('-', " ")
+}
+
+/// Turn tags into Obsidian tag names (no `#`, no spaces)
+fn normalize_tags(tags: &mut Value) {
+    let normalize = |tag: &str| tag.trim_start_matches('#').trim().replace(' ', "-");
+    match tags {
+        Value::String(tag) => *tag = normalize(tag),
+        Value::Sequence(items) => {
+            for item in items {
+                if let Value::String(tag) = item {
+                    *tag = normalize(tag);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ExportFormat;
+    use super::super::tests::export;
+
+    #[test]
+    fn test_obsidian_vault() {
+        let files = export(ExportFormat::Obsidian);
+        let read = |path: &str| String::from_utf8(files[path].clone()).unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec![
+                "2024/01/2024-01-02.md",
+                "2024/01/map.png",
+                "README.md",
+                "notes/plan.pdf",
+                "notes/trip.md",
+            ]
+        );
+
+        let root = read("README.md");
+        assert!(!root.contains("audience"));
+        assert!(root.contains("- '[[2024/01/2024-01-02]]'\n- '[[notes/trip]]'\n"));
+        assert!(root.contains("See [[notes/trip#day 1|the trip]]."));
+
+        let day = read("2024/01/2024-01-02.md");
+        assert!(day.contains("part_of: '[[README]]'"));
+        assert!(day.contains("- family-time"));
+        assert!(day.contains("Packed. ![[2024/01/map.png]]"));
+
+        let trip = read("notes/trip.md");
+        assert!(trip.contains("- '[[notes/plan.pdf]]'"));
+        assert!(trip.contains("Back to [[2024/01/2024-01-02|January 2]] and secret."));
+        assert!(trip.contains("[not](a-link.md)"));
+    }
+}
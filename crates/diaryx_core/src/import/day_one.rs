@@ -0,0 +1,168 @@
+//! Day One JSON export.
+//!
+//! Each journal is a `<Journal>.json` file with an `entries` list; photos
+//! are in `photos/`, named by their MD5 hash. Photo embeds
+//! (`![](dayone-moment://<id>)`) become attachment links, and a leading
+//! `# heading` becomes the entry's title.
+
+use serde_json::Value as Json;
+use serde_yaml::Value;
+
+use super::{
+    EntryImporter, ImportFiles, ImportedAttachment, ImportedEntry, attachment_link, file_name,
+};
+use crate::error::{DiaryxError, Result};
+
+/// Reads a Day One JSON export
+pub struct DayOneImporter;
+
+impl EntryImporter for DayOneImporter {
+    fn name(&self) -> &str {
+        "dayone"
+    }
+
+    fn parse(&self, files: &ImportFiles) -> Result<Vec<ImportedEntry>> {
+        let journals: Vec<(&String, Json)> = files
+            .iter()
+            .filter(|(path, _)| path.ends_with(".json"))
+            .filter_map(|(path, data)| Some((path, serde_json::from_slice::<Json>(data).ok()?)))
+            .filter(|(_, json)| json["entries"].is_array())
+            .collect();
+        if journals.is_empty() {
+            return Err(DiaryxError::Import {
+                source_name: "Day One export".to_string(),
+                message: "no journal (.json file with entries) found".to_string(),
+            });
+        }
+
+        let mut entries = Vec::new();
+        for (path, journal) in &journals {
+            let journal_name = file_name(path).trim_end_matches(".json");
+            for record in journal["entries"].as_array().into_iter().flatten() {
+                let mut entry = parse_entry(record, files);
+                entry.source = format!("{}#{}", path, record["uuid"].as_str().unwrap_or(""));
+                if journals.len() > 1 {
+                    entry
+                        .properties
+                        .insert("journal".to_string(), Value::String(journal_name.into()));
+                }
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Read one entry of a journal
+fn parse_entry(record: &Json, files: &ImportFiles) -> ImportedEntry {
+    let mut body = record["text"].as_str().unwrap_or("").to_string();
+    let mut title = None;
+    if let Some(first) = body.lines().next()
+        && let Some(heading) = first.strip_prefix("# ")
+    {
+        title = Some(heading.trim().to_string());
+        body = body[first.len()..].trim_start().to_string();
+    }
+
+    let mut attachments = Vec::new();
+    for photo in record["photos"].as_array().into_iter().flatten() {
+        let (Some(id), Some(md5)) = (photo["identifier"].as_str(), photo["md5"].as_str()) else {
+            continue;
+        };
+        let kind = photo["type"].as_str().unwrap_or("jpeg");
+        let suffix = format!("/{}.{}", md5, kind);
+        let Some((path, data)) = files
+            .iter()
+            .find(|(path, _)| path.ends_with(&suffix) || **path == suffix[1..])
+        else {
+            continue;
+        };
+        let name = file_name(path).to_string();
+        body = body.replace(&format!("dayone-moment://{}", id), &attachment_link(&name));
+        attachments.push(ImportedAttachment {
+            name,
+            data: data.clone(),
+        });
+    }
+
+    let mut properties = indexmap::IndexMap::new();
+    if record["starred"].as_bool() == Some(true) {
+        properties.insert("starred".to_string(), Value::Bool(true));
+    }
+    let location = &record["location"];
+    let place: Vec<&str> = ["placeName", "localityName", "country"]
+        .iter()
+        .filter_map(|key| location[key].as_str())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if !place.is_empty() {
+        properties.insert("location".to_string(), Value::String(place.join(", ")));
+    }
+    let weather = &record["weather"];
+    if let Some(conditions) = weather["conditionsDescription"].as_str() {
+        let weather = match weather["temperatureCelsius"].as_f64() {
+            Some(celsius) => format!("{}, {:.0}°C", conditions, celsius),
+            None => conditions.to_string(),
+        };
+        properties.insert("weather".to_string(), Value::String(weather));
+    }
+
+    ImportedEntry {
+        source: String::new(),
+        title,
+        date: record["creationDate"]
+            .as_str()
+            .and_then(|date| chrono::DateTime::parse_from_rfc3339(date).ok()),
+        body,
+        tags: record["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|tag| tag.as_str().map(str::to_string))
+            .collect(),
+        properties,
+        attachments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_day_one() {
+        let journal = r##"{
+            "metadata": {"version": "1.0"},
+            "entries": [{
+                "uuid": "A1",
+                "creationDate": "2024-01-02T09:30:00Z",
+                "text": "# Trip\n\nPacked. ![](dayone-moment://P1)",
+                "tags": ["travel"],
+                "starred": true,
+                "location": {"placeName": "Home", "localityName": "Oslo"},
+                "weather": {"conditionsDescription": "Snow", "temperatureCelsius": -3.2},
+                "photos": [{"identifier": "P1", "md5": "abc", "type": "jpeg"}]
+            }]
+        }"##;
+        let mut files = ImportFiles::new();
+        files.insert("Journal.json".to_string(), journal.as_bytes().to_vec());
+        files.insert("photos/abc.jpeg".to_string(), b"jpg".to_vec());
+
+        let entries = DayOneImporter.parse(&files).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.title.as_deref(), Some("Trip"));
+        assert_eq!(entry.body, "Packed. ![](_attachments/abc.jpeg)");
+        assert_eq!(
+            entry.date.unwrap().to_rfc3339(),
+            "2024-01-02T09:30:00+00:00"
+        );
+        assert_eq!(entry.tags, vec!["travel"]);
+        assert_eq!(entry.properties["location"], "Home, Oslo");
+        assert_eq!(entry.properties["weather"], "Snow, -3°C");
+        assert_eq!(entry.properties["starred"], true);
+        assert_eq!(entry.attachments[0].data, b"jpg");
+
+        assert!(DayOneImporter.parse(&ImportFiles::new()).is_err());
+    }
+}
@@ -0,0 +1,504 @@
+//! Evernote `.enex` export.
+//!
+//! Each `<note>` has a title, ENML (XHTML) content, `created` timestamp,
+//! tags and base64-encoded `<resource>`s. The content is converted to
+//! markdown, with `<en-media>` elements becoming links to the resources,
+//! which are matched by their MD5 hash.
+
+use std::collections::HashMap;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use chrono::NaiveDateTime;
+use md5::{Digest, Md5};
+use serde_yaml::Value;
+
+use super::{
+    EntryImporter, ImportFiles, ImportedAttachment, ImportedEntry, attachment_link, attachment_name,
+};
+use crate::error::{DiaryxError, Result};
+
+/// Reads Evernote `.enex` exports
+pub struct EvernoteImporter;
+
+impl EntryImporter for EvernoteImporter {
+    fn name(&self) -> &str {
+        "enex"
+    }
+
+    fn parse(&self, files: &ImportFiles) -> Result<Vec<ImportedEntry>> {
+        let mut entries = Vec::new();
+        let mut found = false;
+        for (path, data) in files.iter().filter(|(path, _)| path.ends_with(".enex")) {
+            found = true;
+            let xml = String::from_utf8_lossy(data);
+            for (i, note) in elements(&xml, "note").into_iter().enumerate() {
+                let mut entry = parse_note(note);
+                entry.source = format!("{}#{}", path, i + 1);
+                entries.push(entry);
+            }
+        }
+        if !found {
+            return Err(DiaryxError::Import {
+                source_name: "Evernote export".to_string(),
+                message: "no .enex file found".to_string(),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Read one `<note>`
+fn parse_note(note: &str) -> ImportedEntry {
+    let mut attachments = Vec::new();
+    let mut media = HashMap::new();
+    for resource in elements(note, "resource") {
+        let Some(data) = element(resource, "data") else {
+            continue;
+        };
+        let encoded: String = text(data).split_whitespace().collect();
+        let Ok(data) = STANDARD.decode(encoded) else {
+            continue;
+        };
+        let hash: String = Md5::digest(&data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let name = element(resource, "file-name")
+            .map(text)
+            .and_then(|name| attachment_name(&name).map(str::to_string))
+            .unwrap_or_else(|| {
+                let mime = element(resource, "mime").map(text).unwrap_or_default();
+                let ext = mime.rsplit('/').next().unwrap_or("bin");
+                format!("{}.{}", hash, ext)
+            });
+        let name = unique_name(&attachments, name);
+        media.insert(hash, name.clone());
+        attachments.push(ImportedAttachment { name, data });
+    }
+
+    let content = element(note, "content").map(cdata).unwrap_or_default();
+    let enml = element(content, "en-note").unwrap_or(content);
+
+    let mut properties = indexmap::IndexMap::new();
+    if let Some(attributes) = element(note, "note-attributes") {
+        for (tag, key) in [("source-url", "source"), ("author", "author")] {
+            if let Some(value) = element(attributes, tag).map(text).filter(|v| !v.is_empty()) {
+                properties.insert(key.to_string(), Value::String(value));
+            }
+        }
+    }
+
+    ImportedEntry {
+        source: String::new(),
+        title: element(note, "title").map(text).filter(|t| !t.is_empty()),
+        date: element(note, "created")
+            .and_then(|created| {
+                NaiveDateTime::parse_from_str(&text(created), "%Y%m%dT%H%M%SZ").ok()
+            })
+            .map(|created| created.and_utc().fixed_offset()),
+        body: html_to_markdown(enml, &media),
+        tags: elements(note, "tag").into_iter().map(text).collect(),
+        properties,
+        attachments,
+    }
+}
+
+/// `name`, numbered if another attachment has it
+fn unique_name(attachments: &[ImportedAttachment], name: String) -> String {
+    let mut candidate = name.clone();
+    let mut n = 1;
+    while attachments.iter().any(|a| a.name == candidate) {
+        n += 1;
+        candidate = super::numbered(&name, n);
+    }
+    candidate
+}
+
+/// Inner XML of every `<tag>` element (not nested in one another)
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // `<note>` but not `<note-attributes>`
+        if !after.starts_with(['>', ' ', '/', '\n', '\t', '\r']) {
+            rest = after;
+            continue;
+        }
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        if after[..tag_end].ends_with('/') {
+            found.push("");
+            rest = &after[tag_end + 1..];
+            continue;
+        }
+        let inner = &after[tag_end + 1..];
+        let Some(end) = inner.find(&close) else {
+            break;
+        };
+        found.push(&inner[..end]);
+        rest = &inner[end + close.len()..];
+    }
+    found
+}
+
+/// Inner XML of the first `<tag>` element
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    elements(xml, tag).into_iter().next()
+}
+
+/// Contents of a `<![CDATA[...]]>` section, or the text itself
+fn cdata(xml: &str) -> &str {
+    let xml = xml.trim();
+    xml.strip_prefix("<![CDATA[")
+        .and_then(|inner| inner.strip_suffix("]]>"))
+        .unwrap_or(xml)
+}
+
+/// Text of an element, with entities decoded
+fn text(xml: &str) -> String {
+    decode_entities(cdata(xml)).trim().to_string()
+}
+
+/// Decode XML and common HTML entities
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest
+            .char_indices()
+            .take(12)
+            .find_map(|(i, c)| (c == ';').then_some(i))
+        else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|n| n.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Convert (X)HTML to markdown. `media` maps the MD5 hashes of
+/// `<en-media>` elements to attachment names.
+pub(super) fn html_to_markdown(html: &str, media: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut lists: Vec<Option<usize>> = Vec::new();
+    let mut links: Vec<String> = Vec::new();
+    let mut pre = false;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            push_text(&mut out, rest, pre);
+            break;
+        };
+        push_text(&mut out, &rest[..lt], pre);
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(gt) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let tag = tag.trim_start_matches('/').trim_end_matches('/');
+        let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let name = name.to_ascii_lowercase();
+        match (name.as_str(), closing) {
+            ("br", _) => out.push_str(if pre { "\n" } else { "  \n" }),
+            ("p" | "div" | "table" | "blockquote", _) | ("tr", true) => block_break(&mut out),
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                block_break(&mut out);
+                let level = name[1..].parse().unwrap_or(1);
+                out.push_str(&"#".repeat(level));
+                out.push(' ');
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => block_break(&mut out),
+            ("hr", _) => {
+                block_break(&mut out);
+                out.push_str("---");
+                block_break(&mut out);
+            }
+            ("ul", false) => {
+                line_break(&mut out);
+                lists.push(None);
+            }
+            ("ol", false) => {
+                line_break(&mut out);
+                lists.push(Some(0));
+            }
+            ("ul" | "ol", true) => {
+                lists.pop();
+                if lists.is_empty() {
+                    block_break(&mut out);
+                }
+            }
+            ("li", false) => {
+                line_break(&mut out);
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        out.push_str(&format!("{}. ", n));
+                    }
+                    _ => out.push_str("- "),
+                }
+            }
+            ("li", true) => line_break(&mut out),
+            ("td" | "th", true) => out.push(' '),
+            ("b" | "strong", _) => out.push_str("**"),
+            ("i" | "em", _) => out.push('*'),
+            ("s" | "strike" | "del", _) => out.push_str("~~"),
+            ("code", _) if !pre => out.push('`'),
+            ("pre", false) => {
+                block_break(&mut out);
+                out.push_str("```\n");
+                pre = true;
+            }
+            ("pre", true) => {
+                line_break(&mut out);
+                out.push_str("```");
+                block_break(&mut out);
+                pre = false;
+            }
+            ("a", false) => {
+                links.push(attribute(attrs, "href").unwrap_or_default());
+                out.push('[');
+            }
+            ("a", true) => {
+                let href = links.pop().unwrap_or_default();
+                out.push_str(&format!("]({})", href));
+            }
+            ("img", _) => {
+                if let Some(src) = attribute(attrs, "src") {
+                    let alt = attribute(attrs, "alt").unwrap_or_default();
+                    out.push_str(&format!("![{}]({})", alt, src));
+                }
+            }
+            ("en-media", false) => {
+                let hash = attribute(attrs, "hash").unwrap_or_default();
+                if let Some(name) = media.get(&hash.to_ascii_lowercase()) {
+                    let kind = attribute(attrs, "type").unwrap_or_default();
+                    let bang = if kind.starts_with("image/") { "!" } else { "" };
+                    let text = if bang.is_empty() { name.as_str() } else { "" };
+                    out.push_str(&format!("{}[{}]({})", bang, text, attachment_link(name)));
+                }
+            }
+            ("en-todo", false) => {
+                let checked = attribute(attrs, "checked").as_deref() == Some("true");
+                out.push_str(if checked { "[x] " } else { "[ ] " });
+            }
+            _ => {}
+        }
+    }
+
+    // Tidy up blank lines and trailing spaces left by the tags
+    let mut tidy = String::with_capacity(out.len());
+    let mut blank = 0;
+    for line in out.lines() {
+        let line = if line.ends_with("  ") && !line.trim().is_empty() {
+            line
+        } else {
+            line.trim_end()
+        };
+        if line.trim().is_empty() {
+            blank += 1;
+            continue;
+        }
+        if !tidy.is_empty() {
+            tidy.push_str(if blank > 0 { "\n\n" } else { "\n" });
+        }
+        blank = 0;
+        tidy.push_str(line);
+    }
+    tidy
+}
+
+/// Add text between tags, collapsing whitespace outside `<pre>`
+fn push_text(out: &mut String, text: &str, pre: bool) {
+    let text = decode_entities(text);
+    if pre {
+        out.push_str(&text);
+        return;
+    }
+    let starts_line = out.is_empty() || out.ends_with('\n') || out.ends_with(' ');
+    let mut words = text.split_whitespace().peekable();
+    if words.peek().is_none() {
+        if !text.is_empty() && !starts_line {
+            out.push(' ');
+        }
+        return;
+    }
+    if text.starts_with(char::is_whitespace) && !starts_line {
+        out.push(' ');
+    }
+    out.push_str(&words.collect::<Vec<_>>().join(" "));
+    if text.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+}
+
+/// End the current line
+fn line_break(out: &mut String) {
+    let trimmed = out.trim_end_matches(' ').len();
+    out.truncate(trimmed);
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// End the current paragraph
+fn block_break(out: &mut String) {
+    line_break(out);
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+}
+
+/// Value of an attribute in a tag's attribute list
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().last();
+        let after = rest[pos + name.len()..].trim_start();
+        rest = &rest[pos + name.len()..];
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value.chars().next()?;
+        let value = if quote == '"' || quote == '\'' {
+            let inner = &value[1..];
+            &inner[..inner.find(quote)?]
+        } else {
+            value.split_whitespace().next()?
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_enex() {
+        let data = STANDARD.encode(b"png");
+        let hash: String = Md5::digest(b"png")
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let enex = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<en-export>
+<note><title>Trip &amp; plans</title>
+<content><![CDATA[<?xml version="1.0" encoding="UTF-8"?><!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd"><en-note><div>Packed <b>light</b>.</div><div><en-todo checked="true"/>Tickets</div><ul><li>Map</li><li><a href="https://example.com">Hotel</a></li></ul><en-media hash="{hash}" type="image/png"/></en-note>]]></content>
+<created>20240102T093000Z</created>
+<tag>travel</tag><tag>family</tag>
+<note-attributes><author>Sam</author></note-attributes>
+<resource><data encoding="base64">
+{data}
+</data><mime>image/png</mime><resource-attributes><file-name>map.png</file-name></resource-attributes></resource>
+</note>
+</en-export>"#
+        );
+        let mut files = ImportFiles::new();
+        files.insert("My Notes.enex".to_string(), enex.into_bytes());
+
+        let entries = EvernoteImporter.parse(&files).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.title.as_deref(), Some("Trip & plans"));
+        assert_eq!(
+            entry.date.unwrap().to_rfc3339(),
+            "2024-01-02T09:30:00+00:00"
+        );
+        assert_eq!(entry.tags, vec!["travel", "family"]);
+        assert_eq!(entry.properties["author"], "Sam");
+        assert_eq!(
+            entry.body,
+            "Packed **light**.\n\n[x] Tickets\n\n- Map\n- [Hotel](https://example.com)\n\n![](_attachments/map.png)"
+        );
+        assert_eq!(entry.attachments[0].name, "map.png");
+        assert_eq!(entry.attachments[0].data, b"png");
+    }
+
+    #[test]
+    fn test_attachment_names_stay_in_attachments_folder() {
+        let data = STANDARD.encode(b"png");
+        let resources: String = ["../../evil.png", "..\\evil.png", "C:evil.png", "..", "dir/ok.png"]
+            .iter()
+            .map(|name| {
+                format!(
+                    "<resource><data>{data}</data><mime>image/png</mime><resource-attributes><file-name>{name}</file-name></resource-attributes></resource>"
+                )
+            })
+            .collect();
+        let enex = format!(
+            "<en-export><note><title>Evil</title><content><![CDATA[<en-note>Hi</en-note>]]></content>{resources}</note></en-export>"
+        );
+        let mut files = ImportFiles::new();
+        files.insert("evil.enex".to_string(), enex.into_bytes());
+
+        let entries = EvernoteImporter.parse(&files).unwrap();
+        let names: Vec<&str> = entries[0]
+            .attachments
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(names.len(), 5);
+        assert_eq!(names[4], "ok.png");
+        for name in names {
+            assert_eq!(attachment_name(name), Some(name));
+            assert!(!name.contains(".."));
+        }
+    }
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = "<h2>Title</h2><p>One&nbsp;two<br/>three</p><ol><li>a</li><li>b</li></ol><pre>let x = 1;\n</pre>";
+        assert_eq!(
+            html_to_markdown(html, &HashMap::new()),
+            "## Title\n\nOne two  \nthree\n\n1. a\n2. b\n\n```\nlet x = 1;\n```"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Journey JSON export.
+//!
+//! Each entry is a `.json` file with its `text` (markdown, or HTML when
+//! `type` is `html`), its date as `date_journal` (milliseconds since the
+//! epoch) and the file names of its `photos`, which sit next to it. Photos
+//! are appended to the body as image links.
+
+use chrono::{DateTime, FixedOffset};
+use serde_json::Value as Json;
+use serde_yaml::Value;
+
+use super::evernote::html_to_markdown;
+use super::{
+    EntryImporter, ImportFiles, ImportedAttachment, ImportedEntry, attachment_link, attachment_name,
+};
+use crate::error::{DiaryxError, Result};
+
+/// Reads a Journey JSON export
+pub struct JourneyImporter;
+
+impl EntryImporter for JourneyImporter {
+    fn name(&self) -> &str {
+        "journey"
+    }
+
+    fn parse(&self, files: &ImportFiles) -> Result<Vec<ImportedEntry>> {
+        let mut entries = Vec::new();
+        for (path, data) in files.iter().filter(|(path, _)| path.ends_with(".json")) {
+            let Ok(record) = serde_json::from_slice::<Json>(data) else {
+                continue;
+            };
+            if record["date_journal"].is_number() && record.get("text").is_some() {
+                let mut entry = parse_entry(&record, path, files);
+                entry.source = path.clone();
+                entries.push(entry);
+            }
+        }
+        if entries.is_empty() {
+            return Err(DiaryxError::Import {
+                source_name: "Journey export".to_string(),
+                message: "no entries (.json files with date_journal) found".to_string(),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Read one entry file
+fn parse_entry(record: &Json, path: &str, files: &ImportFiles) -> ImportedEntry {
+    let text = record["text"].as_str().unwrap_or("");
+    let mut body = if record["type"].as_str() == Some("html") {
+        html_to_markdown(text, &Default::default())
+    } else {
+        text.trim().to_string()
+    };
+
+    let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let mut attachments = Vec::new();
+    for photo in record["photos"].as_array().into_iter().flatten() {
+        let Some(name) = photo.as_str() else {
+            continue;
+        };
+        let photo_path = if dir.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", dir, name)
+        };
+        if let (Some(data), Some(name)) = (files.get(&photo_path), attachment_name(name)) {
+            let name = name.to_string();
+            body.push_str(&format!("\n\n![]({})", attachment_link(&name)));
+            attachments.push(ImportedAttachment {
+                name,
+                data: data.clone(),
+            });
+        }
+    }
+
+    let offset = record["timezone"]
+        .as_str()
+        .and_then(|tz| tz.parse::<FixedOffset>().ok())
+        .unwrap_or(FixedOffset::east_opt(0).unwrap());
+    let date = record["date_journal"]
+        .as_i64()
+        .and_then(DateTime::from_timestamp_millis)
+        .map(|date| date.with_timezone(&offset));
+
+    let mut properties = indexmap::IndexMap::new();
+    if let Some(address) = record["address"].as_str().filter(|a| !a.is_empty()) {
+        properties.insert("location".to_string(), Value::String(address.to_string()));
+    }
+    if record["favourite"].as_bool() == Some(true) {
+        properties.insert("starred".to_string(), Value::Bool(true));
+    }
+
+    ImportedEntry {
+        source: String::new(),
+        title: None,
+        date,
+        body: body.trim().to_string(),
+        tags: record["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|tag| tag.as_str().map(str::to_string))
+            .collect(),
+        properties,
+        attachments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_journey() {
+        let mut files = ImportFiles::new();
+        files.insert(
+            "export/1704187800000-abc.json".to_string(),
+            br#"{"text": "<p>Hello <b>there</b></p>", "type": "html", "date_journal": 1704187800000,
+                "timezone": "+01:00", "tags": ["home"], "photos": ["p1.jpg"], "address": "Oslo"}"#
+                .to_vec(),
+        );
+        files.insert("export/p1.jpg".to_string(), b"jpg".to_vec());
+        files.insert("export/other.json".to_string(), b"{}".to_vec());
+
+        let entries = JourneyImporter.parse(&files).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.body, "Hello **there**\n\n![](_attachments/p1.jpg)");
+        assert_eq!(
+            entry.date.unwrap().to_rfc3339(),
+            "2024-01-02T10:30:00+01:00"
+        );
+        assert_eq!(entry.tags, vec!["home"]);
+        assert_eq!(entry.properties["location"], "Oslo");
+        assert_eq!(entry.attachments[0].name, "p1.jpg");
+    }
+}
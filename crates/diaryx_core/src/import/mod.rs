@@ -0,0 +1,709 @@
+//! Imports from other journaling apps.
+//!
+//! An [`EntryImporter`] reads the files of another app's export and turns
+//! them into [`ImportedEntry`]s; an [`Importer`] then writes those into a
+//! workspace:
+//!
+//! - dated entries go to their daily entry (`YYYY/MM/YYYY-MM-DD.md` under
+//!   [`Config::daily_entry_dir`]), creating the year and month indexes like
+//!   daily entries do. Entries for a day that already has one are appended
+//!   to it under a heading, so nothing is overwritten; text that is already
+//!   there is skipped, so importing an export twice is harmless.
+//! - undated entries go to an `imported/` folder with its own index.
+//! - attachments are written to the entry's `_attachments/` folder and
+//!   listed in its `attachments` property.
+//!
+//! `part_of` and `contents` are wired as each file is written, so the
+//! workspace validates afterwards. Built-in importers are listed in
+//! [`ImportFormat`]; other apps can be supported by implementing
+//! [`EntryImporter`].
+
+mod day_one;
+mod evernote;
+mod journey;
+mod notion;
+mod plain_text;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use ts_rs::TS;
+
+use crate::config::Config;
+use crate::date::date_to_path;
+use crate::entry::slugify;
+use crate::error::{DiaryxError, Result};
+use crate::frontmatter;
+use crate::fs::AsyncFileSystem;
+use crate::path_utils::{relative_path_from_dir_to_target, relative_path_from_file_to_target};
+use crate::workspace::Workspace;
+
+pub use day_one::DayOneImporter;
+pub use evernote::EvernoteImporter;
+pub use journey::JourneyImporter;
+pub use notion::NotionImporter;
+pub use plain_text::PlainTextImporter;
+
+/// Files of an export to import, by their `/`-separated path in the export
+pub type ImportFiles = BTreeMap<String, Vec<u8>>;
+
+/// Folder (and index name) for imported entries without a date
+pub const UNDATED_FOLDER: &str = "imported";
+
+/// Folder next to an entry holding its attachments
+pub const ATTACHMENTS_FOLDER: &str = "_attachments";
+
+/// Reads another app's export
+pub trait EntryImporter: Send + Sync {
+    /// Name of the importer
+    fn name(&self) -> &str;
+
+    /// Read the entries of an export
+    fn parse(&self, files: &ImportFiles) -> Result<Vec<ImportedEntry>>;
+}
+
+/// An app entries can be imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Day One JSON export (`Journal.json` and `photos/`)
+    DayOne,
+    /// Journey JSON export (one `.json` file per entry, with its photos)
+    Journey,
+    /// Notion markdown and CSV export
+    Notion,
+    /// Folder of `YYYY-MM-DD.txt` files
+    PlainText,
+    /// Evernote `.enex` export
+    Evernote,
+}
+
+impl ImportFormat {
+    /// Every format
+    pub const ALL: &[ImportFormat] = &[
+        ImportFormat::DayOne,
+        ImportFormat::Journey,
+        ImportFormat::Notion,
+        ImportFormat::PlainText,
+        ImportFormat::Evernote,
+    ];
+
+    /// Format from its name (`dayone`, `journey`, `notion`, `text`, `enex`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Name of the format
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportFormat::DayOne => "dayone",
+            ImportFormat::Journey => "journey",
+            ImportFormat::Notion => "notion",
+            ImportFormat::PlainText => "text",
+            ImportFormat::Evernote => "enex",
+        }
+    }
+
+    /// Importer reading this format
+    pub fn importer(self) -> Box<dyn EntryImporter> {
+        match self {
+            ImportFormat::DayOne => Box::new(DayOneImporter),
+            ImportFormat::Journey => Box::new(JourneyImporter),
+            ImportFormat::Notion => Box::new(NotionImporter),
+            ImportFormat::PlainText => Box::new(PlainTextImporter),
+            ImportFormat::Evernote => Box::new(EvernoteImporter),
+        }
+    }
+}
+
+/// An entry read from another app
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportedEntry {
+    /// Where the entry was in the export (for reports)
+    pub source: String,
+    /// Title, if the app has one
+    pub title: Option<String>,
+    /// When the entry was written
+    pub date: Option<DateTime<FixedOffset>>,
+    /// Markdown body, linking to attachments as `_attachments/<name>`
+    pub body: String,
+    /// Tags
+    pub tags: Vec<String>,
+    /// Other frontmatter properties
+    pub properties: IndexMap<String, Value>,
+    /// Files of the entry
+    pub attachments: Vec<ImportedAttachment>,
+}
+
+/// A file of an imported entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedAttachment {
+    /// File name
+    pub name: String,
+    /// File contents
+    pub data: Vec<u8>,
+}
+
+/// What an import wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "bindings/")]
+pub struct ImportResult {
+    /// Entries created, in import order
+    pub created: Vec<PathBuf>,
+    /// Existing entries imported entries were appended to
+    pub appended: Vec<PathBuf>,
+    /// Number of attachments written
+    pub attachments: usize,
+}
+
+impl std::fmt::Display for ImportResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Created {} entries, appended to {} entries, wrote {} attachments",
+            self.created.len(),
+            self.appended.len(),
+            self.attachments
+        )
+    }
+}
+
+/// Writes imported entries into a workspace
+pub struct Importer<FS: AsyncFileSystem> {
+    workspace: Workspace<FS>,
+}
+
+impl<FS: AsyncFileSystem> Importer<FS> {
+    /// Create an importer writing to a filesystem
+    pub fn new(fs: FS) -> Self {
+        Self {
+            workspace: Workspace::new(fs),
+        }
+    }
+
+    /// Read an export with a format's importer and write its entries into
+    /// the workspace of `root_index`
+    pub async fn import_files(
+        &self,
+        root_index: &Path,
+        config: &Config,
+        format: ImportFormat,
+        files: &ImportFiles,
+    ) -> Result<ImportResult> {
+        let entries = format.importer().parse(files)?;
+        self.import(root_index, config, entries).await
+    }
+
+    /// Write entries into the workspace of `root_index`, oldest first and
+    /// undated ones last
+    pub async fn import(
+        &self,
+        root_index: &Path,
+        config: &Config,
+        mut entries: Vec<ImportedEntry>,
+    ) -> Result<ImportResult> {
+        entries.sort_by_key(|entry| (entry.date.is_none(), entry.date));
+        let mut result = ImportResult::default();
+        for entry in entries {
+            let (path, parent) = match entry.date {
+                Some(date) => {
+                    let day = date.date_naive();
+                    let parent = self.daily_parent(root_index, config, day).await?;
+                    (date_to_path(&config.daily_entry_dir(), &day), parent)
+                }
+                None => {
+                    let parent = self.undated_parent(root_index, config).await?;
+                    let dir = parent.parent().unwrap_or(Path::new("")).to_path_buf();
+                    let title = entry.title.as_deref().unwrap_or("untitled");
+                    let existing = dir.join(format!("{}.md", slugify(title)));
+                    if self.has_text(&existing, &entry.body).await {
+                        continue;
+                    }
+                    (self.unused_path(&dir, &slugify(title)).await, parent)
+                }
+            };
+
+            let mut entry = entry;
+            let attachments = self.write_attachments(&path, &mut entry).await?;
+            if self.workspace.fs_ref().exists(&path).await {
+                if !self.append_entry(&path, &entry, &attachments).await? {
+                    continue;
+                }
+                if !result.created.contains(&path) && !result.appended.contains(&path) {
+                    result.appended.push(path);
+                }
+            } else {
+                self.create_entry(&path, &parent, &entry, attachments)
+                    .await?;
+                result.created.push(path);
+            }
+            result.attachments += entry.attachments.len();
+        }
+        Ok(result)
+    }
+
+    /// Month index of a day, creating the daily indexes up to the root
+    async fn daily_parent(
+        &self,
+        root_index: &Path,
+        config: &Config,
+        day: NaiveDate,
+    ) -> Result<PathBuf> {
+        let daily_dir = config.daily_entry_dir();
+        let mut parent = root_index.to_path_buf();
+        if config.daily_entry_folder.is_some() {
+            parent = self
+                .ensure_index(&daily_dir, "daily_index.md", "Daily Entries", &parent)
+                .await?;
+        }
+
+        let year = day.format("%Y").to_string();
+        let year_dir = daily_dir.join(&year);
+        parent = self
+            .ensure_index(&year_dir, &format!("{}.md", year), &year, &parent)
+            .await?;
+
+        let month = day.format("%B").to_string();
+        self.ensure_index(
+            &year_dir.join(day.format("%m").to_string()),
+            &format!("{}_{}.md", year, month.to_lowercase()),
+            &format!("{} {}", month, year),
+            &parent,
+        )
+        .await
+    }
+
+    /// Index of the undated entries, creating it under the root
+    async fn undated_parent(&self, root_index: &Path, config: &Config) -> Result<PathBuf> {
+        let dir = root_index
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| config.default_workspace.clone())
+            .join(UNDATED_FOLDER);
+        self.ensure_index(
+            &dir,
+            &format!("{}.md", UNDATED_FOLDER),
+            "Imported",
+            root_index,
+        )
+        .await
+    }
+
+    /// The index in `dir`, or a new `file_name` index listed in `parent`
+    async fn ensure_index(
+        &self,
+        dir: &Path,
+        file_name: &str,
+        title: &str,
+        parent: &Path,
+    ) -> Result<PathBuf> {
+        let fs = self.workspace.fs_ref();
+        if fs.is_dir(dir).await
+            && let Ok(Some(existing)) = self.workspace.find_any_index_in_dir(dir).await
+        {
+            return Ok(existing);
+        }
+
+        let path = dir.join(file_name);
+        let mut properties = IndexMap::new();
+        properties.insert("title".to_string(), Value::String(title.to_string()));
+        properties.insert(
+            "part_of".to_string(),
+            Value::String(relative_path_from_file_to_target(&path, parent)),
+        );
+        properties.insert("contents".to_string(), Value::Sequence(Vec::new()));
+        let content = frontmatter::serialize(&properties, &format!("\n# {}\n", title))?;
+        self.write(&path, &content).await?;
+        self.add_to_contents(parent, &path).await?;
+        Ok(path)
+    }
+
+    /// Write an entry's attachments next to `path`, renaming the ones whose
+    /// names are taken by other files. Returns their paths relative to the
+    /// entry.
+    async fn write_attachments(
+        &self,
+        path: &Path,
+        entry: &mut ImportedEntry,
+    ) -> Result<Vec<String>> {
+        let fs = self.workspace.fs_ref();
+        let dir = path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(ATTACHMENTS_FOLDER);
+        let mut written = Vec::new();
+        for attachment in &entry.attachments {
+            let mut name = attachment.name.clone();
+            let mut n = 1;
+            loop {
+                let target = dir.join(&name);
+                match fs.read_binary(&target).await {
+                    Ok(existing) if existing != attachment.data => {
+                        n += 1;
+                        name = numbered(&attachment.name, n);
+                    }
+                    Ok(_) => break,
+                    Err(_) => {
+                        fs.create_dir_all(&dir).await?;
+                        fs.write_binary(&target, &attachment.data)
+                            .await
+                            .map_err(|e| DiaryxError::FileWrite {
+                                path: target.clone(),
+                                source: e,
+                            })?;
+                        break;
+                    }
+                }
+            }
+            if name != attachment.name {
+                entry.body = entry.body.replace(
+                    &format!("{}/{}", ATTACHMENTS_FOLDER, attachment.name),
+                    &format!("{}/{}", ATTACHMENTS_FOLDER, name),
+                );
+            }
+            written.push(format!("{}/{}", ATTACHMENTS_FOLDER, name));
+        }
+        Ok(written)
+    }
+
+    /// Create an entry's file and list it in `parent`
+    async fn create_entry(
+        &self,
+        path: &Path,
+        parent: &Path,
+        entry: &ImportedEntry,
+        attachments: Vec<String>,
+    ) -> Result<()> {
+        let title = entry
+            .title
+            .clone()
+            .or_else(|| entry.date.map(|d| d.format("%B %d, %Y").to_string()))
+            .unwrap_or_else(|| "Untitled".to_string());
+
+        let mut properties = IndexMap::new();
+        properties.insert("title".to_string(), Value::String(title));
+        properties.insert(
+            "part_of".to_string(),
+            Value::String(relative_path_from_file_to_target(path, parent)),
+        );
+        if let Some(date) = entry.date {
+            properties.insert("created".to_string(), Value::String(date.to_rfc3339()));
+        }
+        if !entry.tags.is_empty() {
+            properties.insert("tags".to_string(), strings(&entry.tags));
+        }
+        for (key, value) in &entry.properties {
+            properties
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        if !attachments.is_empty() {
+            properties.insert("attachments".to_string(), strings(&attachments));
+        }
+
+        let body = format!("\n{}\n", entry.body.trim());
+        let content = frontmatter::serialize(&properties, &body)?;
+        self.write(path, &content).await?;
+        self.add_to_contents(parent, path).await
+    }
+
+    /// Append an entry to an existing one, under a heading. Returns `false`
+    /// if its text is already there (e.g. when an export is imported twice).
+    async fn append_entry(
+        &self,
+        path: &Path,
+        entry: &ImportedEntry,
+        attachments: &[String],
+    ) -> Result<bool> {
+        let content = self
+            .workspace
+            .fs_ref()
+            .read_to_string(path)
+            .await
+            .map_err(|e| DiaryxError::FileRead {
+                path: path.to_path_buf(),
+                source: e,
+            })?;
+        let parsed = frontmatter::parse_or_empty(&content)?;
+        if body_has_text(&parsed.body, &entry.body) {
+            return Ok(false);
+        }
+        let mut properties = parsed.frontmatter;
+        merge_list(&mut properties, "tags", &entry.tags);
+        merge_list(&mut properties, "attachments", attachments);
+
+        let heading = entry
+            .title
+            .clone()
+            .or_else(|| entry.date.map(|d| d.format("%H:%M").to_string()))
+            .unwrap_or_else(|| "Imported".to_string());
+        let body = format!(
+            "{}\n\n## {}\n\n{}\n",
+            parsed.body.trim_end(),
+            heading,
+            entry.body.trim()
+        );
+        let content = if properties.is_empty() {
+            body
+        } else {
+            frontmatter::serialize(&properties, &body)?
+        };
+        self.write(path, &content).await?;
+        Ok(true)
+    }
+
+    /// Whether the entry at `path` exists and has `text` in its body
+    async fn has_text(&self, path: &Path, text: &str) -> bool {
+        match self.workspace.fs_ref().read_to_string(path).await {
+            Ok(content) => frontmatter::parse_or_empty(&content)
+                .is_ok_and(|parsed| body_has_text(&parsed.body, text)),
+            Err(_) => false,
+        }
+    }
+
+    /// List `child` in the `contents` of `index`
+    async fn add_to_contents(&self, index: &Path, child: &Path) -> Result<()> {
+        let index_dir = index.parent().unwrap_or(Path::new(""));
+        let relative = relative_path_from_dir_to_target(index_dir, child);
+        self.workspace
+            .add_to_index_contents(index, &relative)
+            .await
+            .map(|_| ())
+    }
+
+    /// `dir/<stem>.md`, numbered if it exists
+    async fn unused_path(&self, dir: &Path, stem: &str) -> PathBuf {
+        let stem = if stem.is_empty() { "untitled" } else { stem };
+        let file_name = format!("{}.md", stem);
+        let mut path = dir.join(&file_name);
+        let mut n = 1;
+        while self.workspace.fs_ref().exists(&path).await {
+            n += 1;
+            path = dir.join(numbered(&file_name, n));
+        }
+        path
+    }
+
+    async fn write(&self, path: &Path, content: &str) -> Result<()> {
+        let fs = self.workspace.fs_ref();
+        if let Some(dir) = path.parent() {
+            fs.create_dir_all(dir).await?;
+        }
+        fs.write_file(path, content)
+            .await
+            .map_err(|e| DiaryxError::FileWrite {
+                path: path.to_path_buf(),
+                source: e,
+            })
+    }
+}
+
+/// `name` with `-n` before its extension
+fn numbered(name: &str, n: usize) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}-{}.{}", stem, n, ext),
+        _ => format!("{}-{}", name, n),
+    }
+}
+
+/// Whether a body already has an imported entry's (non-empty) text
+fn body_has_text(body: &str, text: &str) -> bool {
+    !text.trim().is_empty() && body.contains(text.trim())
+}
+
+/// A YAML list of strings
+fn strings(items: &[String]) -> Value {
+    Value::Sequence(items.iter().cloned().map(Value::String).collect())
+}
+
+/// Add the missing `items` to a list property
+fn merge_list(properties: &mut IndexMap<String, Value>, key: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    let mut list = frontmatter::get_string_array(properties, key);
+    for item in items {
+        if !list.contains(item) {
+            list.push(item.clone());
+        }
+    }
+    properties.insert(key.to_string(), strings(&list));
+}
+
+/// Name of an attachment referenced from a body
+pub(crate) fn attachment_link(name: &str) -> String {
+    format!("{}/{}", ATTACHMENTS_FOLDER, name.replace(' ', "%20"))
+}
+
+/// File name of a path in an export
+pub(crate) fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Attachment name for a file name or path in an export: its final
+/// component, or `None` if it could still leave the attachments folder
+/// (empty, `.`, `..`, or with a backslash or a drive letter colon)
+pub(crate) fn attachment_name(name: &str) -> Option<&str> {
+    let name = file_name(name);
+    let unsafe_name = name.is_empty() || name == "." || name == ".." || name.contains(['\\', ':']);
+    (!unsafe_name).then_some(name)
+}
+
+/// Lowercase `snake_case` property name of a column or field
+pub(crate) fn property_key(name: &str) -> String {
+    slugify(name).replace('-', "_")
+}
+
+/// Files of an export in a folder (and below) of a real or in-memory
+/// filesystem, by their path relative to it
+pub async fn read_import_files<FS: AsyncFileSystem>(fs: &FS, dir: &Path) -> Result<ImportFiles> {
+    let mut files = ImportFiles::new();
+    let paths = fs
+        .list_all_files_recursive(dir)
+        .await
+        .map_err(|e| DiaryxError::FileRead {
+            path: dir.to_path_buf(),
+            source: e,
+        })?;
+    for path in paths {
+        if fs.is_dir(&path).await {
+            continue;
+        }
+        let Ok(relative) = path.strip_prefix(dir) else {
+            continue;
+        };
+        let data = fs
+            .read_binary(&path)
+            .await
+            .map_err(|e| DiaryxError::FileRead {
+                path: path.clone(),
+                source: e,
+            })?;
+        files.insert(relative.to_string_lossy().replace('\\', "/"), data);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{FileSystem, InMemoryFileSystem, SyncToAsyncFs, block_on_test};
+    use crate::validate::Validator;
+
+    fn make_workspace() -> SyncToAsyncFs<InMemoryFileSystem> {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/ws/README.md"),
+            "---\ntitle: Journal\ncontents: []\n---\n",
+        )
+        .unwrap();
+        SyncToAsyncFs::new(fs)
+    }
+
+    fn entry(date: Option<&str>, title: Option<&str>, body: &str) -> ImportedEntry {
+        ImportedEntry {
+            source: "test".to_string(),
+            title: title.map(str::to_string),
+            date: date.map(|d| DateTime::parse_from_rfc3339(d).unwrap()),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_import_entries() {
+        let fs = make_workspace();
+        let config = Config::with_options(
+            PathBuf::from("/ws"),
+            Some("Daily".to_string()),
+            None,
+            None,
+            None,
+        );
+        let mut photo = entry(Some("2024-01-02T09:30:00+00:00"), None, "Packed.");
+        photo.body.push_str(" ![](_attachments/map.png)");
+        photo.tags = vec!["travel".to_string()];
+        photo.attachments = vec![ImportedAttachment {
+            name: "map.png".to_string(),
+            data: b"png".to_vec(),
+        }];
+        let entries = vec![
+            entry(Some("2024-01-02T18:00:00+00:00"), Some("Evening"), "Home."),
+            photo,
+            entry(None, Some("Recipes"), "Soup."),
+        ];
+
+        let importer = Importer::new(fs.clone());
+        let result =
+            block_on_test(importer.import(Path::new("/ws/README.md"), &config, entries)).unwrap();
+        assert_eq!(
+            result.created,
+            vec![
+                PathBuf::from("/ws/Daily/2024/01/2024-01-02.md"),
+                PathBuf::from("/ws/imported/recipes.md"),
+            ]
+        );
+        assert_eq!(result.appended, Vec::<PathBuf>::new());
+        assert_eq!(result.attachments, 1);
+
+        let day =
+            block_on_test(fs.read_to_string(Path::new("/ws/Daily/2024/01/2024-01-02.md"))).unwrap();
+        assert_eq!(
+            day,
+            "---\ntitle: January 02, 2024\npart_of: 2024_january.md\ncreated: 2024-01-02T09:30:00+00:00\ntags:\n- travel\nattachments:\n- _attachments/map.png\n---\n\nPacked. ![](_attachments/map.png)\n\n## Evening\n\nHome.\n"
+        );
+        assert_eq!(
+            block_on_test(fs.read_binary(Path::new("/ws/Daily/2024/01/_attachments/map.png")))
+                .unwrap(),
+            b"png"
+        );
+
+        // Everything is linked from the root
+        let result = block_on_test(
+            Validator::new(fs.clone()).validate_workspace(Path::new("/ws/README.md"), None),
+        )
+        .unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+
+        // Importing again appends instead of overwriting
+        let result = block_on_test(importer.import(
+            Path::new("/ws/README.md"),
+            &config,
+            vec![entry(Some("2024-01-02T20:00:00+00:00"), None, "Late.")],
+        ))
+        .unwrap();
+        assert_eq!(
+            result.appended,
+            vec![PathBuf::from("/ws/Daily/2024/01/2024-01-02.md")]
+        );
+
+        // ...but not twice
+        let result = block_on_test(importer.import(
+            Path::new("/ws/README.md"),
+            &config,
+            vec![
+                entry(Some("2024-01-02T20:00:00+00:00"), None, "Late."),
+                entry(None, Some("Recipes"), "Soup."),
+            ],
+        ))
+        .unwrap();
+        assert_eq!(result, ImportResult::default());
+    }
+
+    #[test]
+    fn test_import_format_names() {
+        for format in ImportFormat::ALL {
+            assert_eq!(ImportFormat::from_name(format.name()), Some(*format));
+            assert_eq!(format.importer().name(), format.name());
+        }
+        assert_eq!(numbered("a.tar.gz", 2), "a.tar-2.gz");
+        assert_eq!(numbered("notes", 3), "notes-3");
+    }
+}
@@ -0,0 +1,345 @@
+//! Notion markdown and CSV export.
+//!
+//! Pages are `<Title> <32-hex id>.md` files; a database is a
+//! `<Name> <id>.csv` file listing its rows next to a `<Name> <id>/` folder
+//! with one page per row. Properties are written at the top of each page
+//! (`Key: Value` lines after the title), and are also read from the row of
+//! the page's database, which gives the date (`Date`, `Created` or
+//! `Created time`) and `Tags`. Images linked from a page become attachments.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime};
+use indexmap::IndexMap;
+use serde_yaml::Value;
+
+use super::{
+    EntryImporter, ImportFiles, ImportedAttachment, ImportedEntry, attachment_link, file_name,
+    property_key,
+};
+use crate::error::{DiaryxError, Result};
+use crate::publish::attachments::split_local_url;
+
+/// Reads a Notion markdown and CSV export
+pub struct NotionImporter;
+
+/// Columns holding an entry's date, in order of preference
+const DATE_COLUMNS: &[&str] = &["Date", "Created", "Created time"];
+
+/// Extensions of linked files imported as attachments
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "heic"];
+
+impl EntryImporter for NotionImporter {
+    fn name(&self) -> &str {
+        "notion"
+    }
+
+    fn parse(&self, files: &ImportFiles) -> Result<Vec<ImportedEntry>> {
+        // Rows of each database, by the folder holding its pages
+        let mut databases: HashMap<String, Vec<IndexMap<String, String>>> = HashMap::new();
+        for (path, data) in files.iter().filter(|(path, _)| path.ends_with(".csv")) {
+            let text = String::from_utf8_lossy(data);
+            let text = text.trim_start_matches('\u{feff}');
+            databases.insert(path.trim_end_matches(".csv").to_string(), parse_csv(text));
+        }
+
+        let mut entries = Vec::new();
+        for (path, data) in files.iter().filter(|(path, _)| path.ends_with(".md")) {
+            let text = String::from_utf8_lossy(data);
+            let folder = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+            let mut entry = parse_page(&text, path, files);
+            let title = entry.title.clone().unwrap_or_default();
+            if let Some(row) = databases.get(folder).and_then(|rows| {
+                rows.iter()
+                    .find(|row| row.values().next().is_some_and(|v| v.trim() == title))
+            }) {
+                apply_row(&mut entry, row);
+            }
+            entry.source = path.clone();
+            entries.push(entry);
+        }
+        if entries.is_empty() {
+            return Err(DiaryxError::Import {
+                source_name: "Notion export".to_string(),
+                message: "no pages (.md files) found".to_string(),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Read a page: title, property lines and body
+fn parse_page(text: &str, path: &str, files: &ImportFiles) -> ImportedEntry {
+    let mut lines = text.trim_start_matches('\u{feff}').lines().peekable();
+    let title = match lines.peek().and_then(|line| line.strip_prefix("# ")) {
+        Some(heading) => {
+            let heading = heading.trim().to_string();
+            lines.next();
+            heading
+        }
+        None => strip_id(file_name(path).trim_end_matches(".md")),
+    };
+
+    // Property block: `Key: Value` lines up to the first blank line after them
+    let mut entry = ImportedEntry {
+        title: Some(title),
+        ..Default::default()
+    };
+    while lines.peek().is_some_and(|line| line.trim().is_empty()) {
+        lines.next();
+    }
+    let mut property_lines = Vec::new();
+    while let Some(line) = lines.peek() {
+        match line.split_once(": ") {
+            Some((key, value)) if is_property_key(key) => {
+                property_lines.push((key.trim().to_string(), value.trim().to_string()));
+                lines.next();
+            }
+            _ => break,
+        }
+    }
+    let starts_body = lines.peek().is_none_or(|line| line.trim().is_empty());
+    if starts_body {
+        let row: IndexMap<String, String> = property_lines.into_iter().collect();
+        apply_row(&mut entry, &row);
+        entry.body = lines.collect::<Vec<_>>().join("\n");
+    } else {
+        // Not a property block after all
+        let mut body: Vec<String> = property_lines
+            .into_iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect();
+        body.extend(lines.map(str::to_string));
+        entry.body = body.join("\n");
+    }
+
+    let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    entry.body = rewrite_links(&entry.body, dir, files, &mut entry.attachments);
+    entry.body = entry.body.trim().to_string();
+    entry
+}
+
+/// Whether text before `: ` looks like a property name
+fn is_property_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 40
+        && !key.starts_with(['#', '-', '*', '>', '!', '[', '|'])
+        && !key.contains(['[', ']', '(', ')'])
+}
+
+/// Page title from a file stem, without Notion's trailing id
+fn strip_id(stem: &str) -> String {
+    match stem.rsplit_once(' ') {
+        Some((title, id)) if id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()) => {
+            title.to_string()
+        }
+        _ => stem.to_string(),
+    }
+}
+
+/// Take the date, tags and other properties of a database row
+fn apply_row(entry: &mut ImportedEntry, row: &IndexMap<String, String>) {
+    let title = entry.title.clone().unwrap_or_default();
+    for (column, value) in row {
+        let value = value.trim();
+        if value.is_empty() || value == title {
+            continue;
+        }
+        if DATE_COLUMNS.iter().any(|c| c.eq_ignore_ascii_case(column)) {
+            continue;
+        }
+        if column.eq_ignore_ascii_case("tags") {
+            for tag in value.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+                if !entry.tags.iter().any(|t| t == tag) {
+                    entry.tags.push(tag.to_string());
+                }
+            }
+            continue;
+        }
+        entry
+            .properties
+            .entry(property_key(column))
+            .or_insert_with(|| Value::String(value.to_string()));
+    }
+    if entry.date.is_none() {
+        entry.date = DATE_COLUMNS.iter().find_map(|column| {
+            row.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(column))
+                .and_then(|(_, value)| parse_date(value))
+        });
+    }
+}
+
+/// Parse the dates Notion writes (`January 2, 2024 9:30 AM`, ISO dates)
+fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    // Ranges (`January 2, 2024 → January 4, 2024`) start on their first date
+    let value = value.split('→').next()?.trim();
+    for format in ["%B %d, %Y %I:%M %p", "%Y-%m-%d %H:%M", "%Y/%m/%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date.and_utc().fixed_offset());
+        }
+    }
+    for format in ["%B %d, %Y", "%Y-%m-%d", "%Y/%m/%d"] {
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            return Some(date.and_hms_opt(0, 0, 0)?.and_utc().fixed_offset());
+        }
+    }
+    None
+}
+
+/// Turn links to images in the export into attachment links, and links to
+/// other pages into their text
+fn rewrite_links(
+    body: &str,
+    dir: &str,
+    files: &ImportFiles,
+    attachments: &mut Vec<ImportedAttachment>,
+) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("](") {
+        let Some(end) = rest[start + 2..].find(')') else {
+            break;
+        };
+        let url = &rest[start + 2..start + 2 + end];
+        let link_end = start + 3 + end;
+        let (Some(text_start), Some((local, _))) = (rest[..start].rfind('['), split_local_url(url))
+        else {
+            out.push_str(&rest[..link_end]);
+            rest = &rest[link_end..];
+            continue;
+        };
+        let target = if dir.is_empty() {
+            local.clone()
+        } else {
+            format!("{}/{}", dir, local)
+        };
+        let extension = local.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+
+        if let Some(data) = files
+            .get(&target)
+            .filter(|_| IMAGE_EXTENSIONS.contains(&extension.as_str()))
+        {
+            let name = file_name(&local).to_string();
+            if !attachments.iter().any(|a| a.name == name) {
+                attachments.push(ImportedAttachment {
+                    name: name.clone(),
+                    data: data.clone(),
+                });
+            }
+            out.push_str(&rest[..start + 2]);
+            out.push_str(&attachment_link(&name));
+            out.push(')');
+        } else if extension == "md" || extension == "csv" {
+            // Other pages aren't imported as links, so keep their text
+            out.push_str(&rest[..text_start]);
+            out.push_str(&rest[text_start + 1..start]);
+        } else {
+            out.push_str(&rest[..link_end]);
+        }
+        rest = &rest[link_end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Rows of a CSV file, keyed by the header's column names (in order)
+fn parse_csv(text: &str) -> Vec<IndexMap<String, String>> {
+    let mut records = csv_records(text).into_iter();
+    let Some(header) = records.next() else {
+        return Vec::new();
+    };
+    records
+        .filter(|record| record.iter().any(|field| !field.is_empty()))
+        .map(|record| {
+            header
+                .iter()
+                .cloned()
+                .zip(record)
+                .collect::<IndexMap<_, _>>()
+        })
+        .collect()
+}
+
+/// Records of an RFC 4180 CSV file
+fn csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notion() {
+        let mut files = ImportFiles::new();
+        files.insert(
+            "Journal 0123456789abcdef0123456789abcdef.csv".to_string(),
+            b"\xef\xbb\xbfName,Date,Tags,Mood\nFirst day,\"January 2, 2024 9:30 AM\",\"travel, family\",Good\n"
+                .to_vec(),
+        );
+        files.insert(
+            "Journal 0123456789abcdef0123456789abcdef/First day 11112222333344445555666677778888.md"
+                .to_string(),
+            b"# First day\n\nMood: Good\nDate: January 2, 2024 9:30 AM\n\nSaw [Notes](Notes%20aaaa.md).\n\n![map](First%20day/map%20one.png)\n"
+                .to_vec(),
+        );
+        files.insert(
+            "Journal 0123456789abcdef0123456789abcdef/First day/map one.png".to_string(),
+            b"png".to_vec(),
+        );
+        files.insert(
+            "Notes 22223333444455556666777788889999.md".to_string(),
+            b"Just text: no properties here\nMore.".to_vec(),
+        );
+
+        let entries = NotionImporter.parse(&files).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let day = &entries[0];
+        assert_eq!(day.title.as_deref(), Some("First day"));
+        assert_eq!(day.date.unwrap().to_rfc3339(), "2024-01-02T09:30:00+00:00");
+        assert_eq!(day.tags, vec!["travel", "family"]);
+        assert_eq!(day.properties["mood"], "Good");
+        assert_eq!(day.body, "Saw Notes.\n\n![map](_attachments/map%20one.png)");
+        assert_eq!(day.attachments[0].name, "map one.png");
+
+        let notes = &entries[1];
+        assert_eq!(notes.title.as_deref(), Some("Notes"));
+        assert_eq!(notes.date, None);
+        assert_eq!(notes.body, "Just text: no properties here\nMore.");
+    }
+
+    #[test]
+    fn test_csv_records() {
+        assert_eq!(
+            csv_records("a,\"b, \"\"c\"\"\"\r\n1,\"two\nlines\"\n"),
+            vec![vec!["a", "b, \"c\""], vec!["1", "two\nlines"]]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Folder of plain-text journal files.
+//!
+//! Each `YYYY-MM-DD.txt` file (anywhere in the folder) is the entry for
+//! that day; its text becomes the entry's body as-is. Other files are
+//! ignored.
+
+use chrono::NaiveDate;
+
+use super::{EntryImporter, ImportFiles, ImportedEntry, file_name};
+use crate::error::{DiaryxError, Result};
+
+/// Reads a folder of `YYYY-MM-DD.txt` files
+pub struct PlainTextImporter;
+
+impl EntryImporter for PlainTextImporter {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn parse(&self, files: &ImportFiles) -> Result<Vec<ImportedEntry>> {
+        let mut entries = Vec::new();
+        for (path, data) in files {
+            let Some(date) = file_name(path)
+                .strip_suffix(".txt")
+                .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+            else {
+                continue;
+            };
+            entries.push(ImportedEntry {
+                source: path.clone(),
+                date: Some(date.and_utc().fixed_offset()),
+                body: String::from_utf8_lossy(data)
+                    .trim_start_matches('\u{feff}')
+                    .replace("\r\n", "\n")
+                    .trim()
+                    .to_string(),
+                ..Default::default()
+            });
+        }
+        if entries.is_empty() {
+            return Err(DiaryxError::Import {
+                source_name: "text folder".to_string(),
+                message: "no YYYY-MM-DD.txt files found".to_string(),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_text() {
+        let mut files = ImportFiles::new();
+        files.insert(
+            "2024/2024-01-02.txt".to_string(),
+            b"Cold.\r\nSnow.\r\n".to_vec(),
+        );
+        files.insert("notes.txt".to_string(), b"Not an entry".to_vec());
+
+        let entries = PlainTextImporter.parse(&files).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].date.unwrap().to_rfc3339(),
+            "2024-01-02T00:00:00+00:00"
+        );
+        assert_eq!(entries[0].body, "Cold.\nSnow.");
+        assert_eq!(entries[0].title, None);
+
+        files.remove("2024/2024-01-02.txt");
+        assert!(PlainTextImporter.parse(&files).is_err());
+    }
+}
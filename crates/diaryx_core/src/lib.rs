@@ -26,12 +26,24 @@ pub mod error;
 /// Export (for backup or filtering by audience property)
 pub mod export;
 
+/// Exports to other journaling apps (Day One, Obsidian, Logseq, JSON)
+pub mod export_formats;
+
+/// Imports from other journaling apps (Day One, Journey, Notion, plain text, Evernote)
+pub mod import;
+
+/// Redaction of audience-scoped blocks and properties in exports
+pub mod redaction;
+
 /// Filesystem abstraction
 pub mod fs;
 
 /// Publish (exports as HTML)
 pub mod publish;
 
+/// Math and diagram rendering for publish and export
+pub mod render;
+
 /// Search (query frontmatter or search content)
 pub mod search;
 
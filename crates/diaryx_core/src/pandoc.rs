@@ -34,12 +34,16 @@ pub fn pandoc_version() -> Option<String> {
 }
 
 /// Convert a file on disk to a target format using pandoc.
+///
+/// Images are looked up relative to the input file. `extra_args` are passed
+/// to pandoc as-is (e.g. `--katex`).
 pub fn convert_file(
     input_path: &Path,
     output_path: &Path,
     from: &str,
     to: &str,
     standalone: bool,
+    extra_args: &[&str],
 ) -> Result<(), String> {
     let pandoc_to = pandoc_format_name(to);
 
@@ -52,10 +56,16 @@ pub fn convert_file(
         .arg("-o")
         .arg(output_path);
 
+    if let Some(dir) = input_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        cmd.arg("--resource-path").arg(dir);
+    }
+
     if standalone {
         cmd.arg("--standalone");
     }
 
+    cmd.args(extra_args);
+
     if to == "pdf" {
         cmd.arg("--pdf-engine=typst");
     }
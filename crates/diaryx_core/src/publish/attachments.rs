@@ -0,0 +1,198 @@
+//! Attachments and body links of published pages.
+//!
+//! Files a page declares in its `attachments` property, and local files its
+//! body links to or embeds, are copied to `attachments/` in the output,
+//! keeping their path in the workspace (`attachments/2024/03/photo.jpg`).
+//! Body links to other entries point at their published page (or its section
+//! in single-file mode); links to entries that aren't published are reduced to
+//! their text.
+//!
+//! With [`PublishOptions::image_max_width`](super::PublishOptions::image_max_width)
+//! and the `publish-images` feature, embedded images wider than that are shown
+//! as a scaled-down copy in `thumbnails/` linking to the original.
+
+use std::path::Path;
+
+/// Output folder of copied attachments
+pub const ATTACHMENTS_DIR: &str = "attachments";
+/// Output folder of scaled-down images
+pub const THUMBNAILS_DIR: &str = "thumbnails";
+
+/// Output path of an attachment, relative to the destination
+pub(crate) fn attachment_output(workspace_dir: &Path, path: &Path) -> String {
+    format!(
+        "{}/{}",
+        ATTACHMENTS_DIR,
+        super::source_key(workspace_dir, path)
+    )
+}
+
+/// Output path of an attachment's thumbnail, relative to the destination
+pub(crate) fn thumbnail_output(workspace_dir: &Path, path: &Path) -> String {
+    format!(
+        "{}/{}",
+        THUMBNAILS_DIR,
+        super::source_key(workspace_dir, path)
+    )
+}
+
+/// Split a body link into the local path it points at and its `#fragment`.
+///
+/// Returns `None` for external links (`https:`, `mailto:`, ...) and links
+/// within the page (`#section`).
+pub(crate) fn split_local_url(url: &str) -> Option<(String, Option<&str>)> {
+    if url.is_empty() || url.starts_with('#') || url.starts_with("//") || has_scheme(url) {
+        return None;
+    }
+    let (path, fragment) = match url.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (url, None),
+    };
+    let path = path.split('?').next().unwrap_or(path);
+    if path.is_empty() {
+        return None;
+    }
+    Some((percent_decode(path), fragment))
+}
+
+/// Whether a URL starts with a scheme like `https:` or `mailto:`
+fn has_scheme(url: &str) -> bool {
+    match url.split_once(':') {
+        Some((scheme, _)) => {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// Decode `%XX` escapes in a link path (invalid escapes are kept as-is)
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).unwrap_or_else(|_| s.to_string())
+}
+
+/// Percent-encode an output path for use in an `href` or `src`
+pub(crate) fn encode_href(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'/') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+/// Whether a file is an image that can be scaled down
+pub(crate) fn is_resizable_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_ascii_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "gif" | "webp"
+            )
+        })
+}
+
+/// Width in pixels of an image, read from its header
+#[cfg(feature = "publish-images")]
+pub(crate) fn image_width(bytes: &[u8]) -> Option<u32> {
+    image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+        .map(|(width, _)| width)
+}
+
+#[cfg(not(feature = "publish-images"))]
+pub(crate) fn image_width(_bytes: &[u8]) -> Option<u32> {
+    None
+}
+
+/// Scale an image down to `max_width`, keeping its format and aspect ratio
+#[cfg(feature = "publish-images")]
+pub(crate) fn thumbnail(bytes: &[u8], path: &Path, max_width: u32) -> Option<Vec<u8>> {
+    let format = image::ImageFormat::from_path(path).ok()?;
+    let image = image::load_from_memory_with_format(bytes, format).ok()?;
+    if image.width() <= max_width {
+        return None;
+    }
+    let height =
+        (u64::from(image.height()) * u64::from(max_width) / u64::from(image.width())).max(1) as u32;
+    let thumbnail = image.resize(max_width, height, image::imageops::FilterType::Triangle);
+    let mut out = std::io::Cursor::new(Vec::new());
+    thumbnail.write_to(&mut out, format).ok()?;
+    Some(out.into_inner())
+}
+
+#[cfg(not(feature = "publish-images"))]
+pub(crate) fn thumbnail(_bytes: &[u8], _path: &Path, _max_width: u32) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_local_url() {
+        assert_eq!(
+            split_local_url("../notes/My%20Day.md#intro"),
+            Some(("../notes/My Day.md".to_string(), Some("intro")))
+        );
+        assert_eq!(
+            split_local_url("img.png?v=2"),
+            Some(("img.png".to_string(), None))
+        );
+        assert_eq!(split_local_url("https://example.com/a.md"), None);
+        assert_eq!(split_local_url("mailto:me@example.com"), None);
+        assert_eq!(split_local_url("#section"), None);
+        assert_eq!(split_local_url("//cdn.example.com/x.js"), None);
+    }
+
+    #[test]
+    fn test_encode_href() {
+        assert_eq!(
+            encode_href("attachments/My Photos/a&b.jpg"),
+            "attachments/My%20Photos/a%26b.jpg"
+        );
+        assert_eq!(percent_decode("a%2"), "a%2");
+    }
+
+    #[cfg(feature = "publish-images")]
+    #[test]
+    fn test_thumbnail_scales_wide_images() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(400, 200)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+        assert_eq!(image_width(&png), Some(400));
+
+        let small = thumbnail(&png, Path::new("a.png"), 100).unwrap();
+        let small = image::load_from_memory(&small).unwrap();
+        assert_eq!((small.width(), small.height()), (100, 50));
+        assert!(thumbnail(&png, Path::new("a.png"), 800).is_none());
+    }
+}
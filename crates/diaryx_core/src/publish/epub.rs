@@ -0,0 +1,415 @@
+//! EPUB output of published pages, without pandoc.
+//!
+//! [`Publisher::epub`](super::Publisher::epub) packages a workspace as an
+//! EPUB 3 book: one chapter per page in `contents` reading order (depth-first
+//! from the root index), a navigation document nesting the chapters like the
+//! `contents` hierarchy, and the pages' attachments and diagrams embedded at
+//! the paths their links already point to.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+
+use zip::CompressionMethod;
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+use super::attachments::encode_href;
+use super::{PublishedPage, html_escape};
+use crate::error::Result;
+
+/// Folder of the book's contents inside the archive
+const CONTENT_DIR: &str = "OEBPS";
+/// Navigation document (the generated table of contents)
+const NAV_FILE: &str = "nav.xhtml";
+/// Stylesheet of the chapters
+const STYLESHEET_FILE: &str = "style.css";
+
+const STYLESHEET: &str = "body { font-family: serif; line-height: 1.5; }
+h1, h2, h3, h4, h5, h6 { font-family: sans-serif; line-height: 1.2; }
+img, svg { max-width: 100%; }
+pre { white-space: pre-wrap; font-size: 0.85em; }
+blockquote { margin-left: 1em; padding-left: 1em; border-left: 2px solid #ccc; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; }
+nav ol { list-style: none; }
+";
+
+/// Book-level metadata
+pub(crate) struct EpubMetadata {
+    pub title: String,
+    pub author: Option<String>,
+    /// BCP 47 language tag
+    pub language: String,
+    /// Unique identifier of the book (stable across exports of a workspace)
+    pub identifier: String,
+    /// Last modification, as `YYYY-MM-DDThh:mm:ssZ`
+    pub modified: String,
+}
+
+/// Pages in reading order with their depth in the `contents` hierarchy: the
+/// root first, then each page followed by its children. Pages not reachable
+/// from the root come last.
+pub(crate) fn reading_order(pages: &[PublishedPage]) -> Vec<(usize, usize)> {
+    let by_filename: HashMap<&str, usize> = pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| (page.dest_filename.as_str(), i))
+        .collect();
+
+    fn visit(
+        idx: usize,
+        depth: usize,
+        pages: &[PublishedPage],
+        by_filename: &HashMap<&str, usize>,
+        seen: &mut HashSet<usize>,
+        order: &mut Vec<(usize, usize)>,
+    ) {
+        if !seen.insert(idx) {
+            return;
+        }
+        order.push((idx, depth));
+        for link in &pages[idx].contents_links {
+            if let Some(&child) = by_filename.get(link.href.as_str()) {
+                visit(child, depth + 1, pages, by_filename, seen, order);
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    let root = pages.iter().position(|page| page.is_root).unwrap_or(0);
+    if !pages.is_empty() {
+        visit(root, 0, pages, &by_filename, &mut seen, &mut order);
+    }
+    for idx in 0..pages.len() {
+        visit(idx, 0, pages, &by_filename, &mut seen, &mut order);
+    }
+    order
+}
+
+/// Write an EPUB archive of `pages` (in `order`, see [`reading_order`]) and
+/// the `resources` they link to (path relative to the pages -> contents)
+pub(crate) fn write_epub(
+    metadata: &EpubMetadata,
+    pages: &[PublishedPage],
+    order: &[(usize, usize)],
+    resources: &[(String, Vec<u8>)],
+) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must come first, uncompressed
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )
+    .map_err(std::io::Error::from)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)
+        .map_err(std::io::Error::from)?;
+    zip.write_all(container_xml().as_bytes())?;
+
+    let mut add = |path: &str, contents: &[u8]| -> Result<()> {
+        zip.start_file(format!("{}/{}", CONTENT_DIR, path), deflated)
+            .map_err(std::io::Error::from)?;
+        zip.write_all(contents)?;
+        Ok(())
+    };
+    add(
+        "content.opf",
+        content_opf(metadata, pages, order, resources).as_bytes(),
+    )?;
+    add(NAV_FILE, nav_xhtml(metadata, pages, order).as_bytes())?;
+    add(STYLESHEET_FILE, STYLESHEET.as_bytes())?;
+    for &(idx, _) in order {
+        let page = &pages[idx];
+        add(
+            &page.dest_filename,
+            chapter_xhtml(metadata, page).as_bytes(),
+        )?;
+    }
+    for (path, contents) in resources {
+        add(path, contents)?;
+    }
+
+    let cursor = zip.finish().map_err(std::io::Error::from)?;
+    Ok(cursor.into_inner())
+}
+
+fn container_xml() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="{}/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#,
+        CONTENT_DIR
+    )
+}
+
+/// Package document: metadata, every file of the book, and the reading order
+fn content_opf(
+    metadata: &EpubMetadata,
+    pages: &[PublishedPage],
+    order: &[(usize, usize)],
+    resources: &[(String, Vec<u8>)],
+) -> String {
+    let mut manifest = format!(
+        "    <item id=\"nav\" href=\"{}\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n    <item id=\"style\" href=\"{}\" media-type=\"text/css\"/>\n",
+        NAV_FILE, STYLESHEET_FILE
+    );
+    let mut spine = String::from("    <itemref idref=\"nav\"/>\n");
+    for (n, &(idx, _)) in order.iter().enumerate() {
+        let page = &pages[idx];
+        let properties = if page.html_body.contains("<svg") {
+            " properties=\"svg\""
+        } else {
+            ""
+        };
+        manifest.push_str(&format!(
+            "    <item id=\"page{}\" href=\"{}\" media-type=\"application/xhtml+xml\"{}/>\n",
+            n,
+            html_escape(&encode_href(&page.dest_filename)),
+            properties
+        ));
+        spine.push_str(&format!("    <itemref idref=\"page{}\"/>\n", n));
+    }
+    for (n, (path, _)) in resources.iter().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"res{}\" href=\"{}\" media-type=\"{}\"/>\n",
+            n,
+            html_escape(&encode_href(path)),
+            media_type(path)
+        ));
+    }
+
+    let creator = metadata
+        .author
+        .as_ref()
+        .map(|author| format!("    <dc:creator>{}</dc:creator>\n", html_escape(author)))
+        .unwrap_or_default();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{lang}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{id}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{lang}</dc:language>
+{creator}    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+        lang = html_escape(&metadata.language),
+        id = html_escape(&metadata.identifier),
+        title = html_escape(&metadata.title),
+        modified = metadata.modified,
+    )
+}
+
+/// Table of contents, nesting pages like the `contents` hierarchy
+fn nav_xhtml(metadata: &EpubMetadata, pages: &[PublishedPage], order: &[(usize, usize)]) -> String {
+    let mut list = String::new();
+    let mut current = 0; // depth of the previous item
+    for (n, &(idx, depth)) in order.iter().enumerate() {
+        let page = &pages[idx];
+        if n > 0 {
+            // Children can't skip levels
+            let depth = depth.min(current + 1);
+            if depth > current {
+                list.push_str("\n<ol>\n");
+            } else {
+                list.push_str("</li>\n");
+                for _ in depth..current {
+                    list.push_str("</ol>\n</li>\n");
+                }
+            }
+            current = depth;
+        }
+        list.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            html_escape(&encode_href(&page.dest_filename)),
+            html_escape(&page.title)
+        ));
+    }
+    if !order.is_empty() {
+        list.push_str("</li>\n");
+        for _ in 0..current {
+            list.push_str("</ol>\n</li>\n");
+        }
+    }
+
+    xhtml_document(
+        &metadata.language,
+        &metadata.title,
+        &format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>",
+            html_escape(&metadata.title),
+            list
+        ),
+    )
+}
+
+/// One page as a chapter
+fn chapter_xhtml(metadata: &EpubMetadata, page: &PublishedPage) -> String {
+    xhtml_document(
+        &metadata.language,
+        &page.title,
+        &format!(
+            "<section epub:type=\"chapter\">\n<h1>{}</h1>\n{}</section>",
+            html_escape(&page.title),
+            page.html_body
+        ),
+    )
+}
+
+fn xhtml_document(language: &str, title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{lang}" xml:lang="{lang}">
+<head>
+<meta charset="UTF-8"/>
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="{css}"/>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        lang = html_escape(language),
+        title = html_escape(title),
+        css = STYLESHEET_FILE,
+    )
+}
+
+/// Media type of a file embedded in the book
+fn media_type(path: &str) -> &'static str {
+    let ext = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "css" => "text/css",
+        "mp3" => "audio/mpeg",
+        "m4a" | "mp4" => "audio/mp4",
+        "ogg" => "audio/ogg",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "pdf" => "application/pdf",
+        "txt" | "md" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publish::NavLink;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    fn page(filename: &str, title: &str, children: &[&str]) -> PublishedPage {
+        PublishedPage {
+            source_path: PathBuf::from(filename),
+            dest_filename: filename.to_string(),
+            title: title.to_string(),
+            html_body: format!("<p>{}</p>\n", title),
+            markdown_body: String::new(),
+            contents_links: children
+                .iter()
+                .map(|child| NavLink {
+                    href: child.to_string(),
+                    title: child.to_string(),
+                })
+                .collect(),
+            parent_link: None,
+            is_root: filename == "index.html",
+            frontmatter: Default::default(),
+            attachments: Vec::new(),
+            linked_files: Vec::new(),
+            diagrams: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_reading_order_follows_contents() {
+        let pages = vec![
+            page("b.html", "B", &["b1.html"]),
+            page("orphan.html", "Orphan", &[]),
+            page("b1.html", "B1", &[]),
+            page("index.html", "Book", &["a.html", "b.html"]),
+            page("a.html", "A", &[]),
+        ];
+        let order: Vec<(&str, usize)> = reading_order(&pages)
+            .into_iter()
+            .map(|(i, depth)| (pages[i].title.as_str(), depth))
+            .collect();
+        assert_eq!(
+            order,
+            vec![("Book", 0), ("A", 1), ("B", 1), ("B1", 2), ("Orphan", 0)]
+        );
+
+        let metadata = EpubMetadata {
+            title: "Book".to_string(),
+            author: None,
+            language: "en".to_string(),
+            identifier: "urn:test".to_string(),
+            modified: "2024-01-01T00:00:00Z".to_string(),
+        };
+        let nav = nav_xhtml(&metadata, &pages, &reading_order(&pages));
+        assert!(nav.contains(
+            "<li><a href=\"index.html\">Book</a>\n<ol>\n<li><a href=\"a.html\">A</a></li>\n<li><a href=\"b.html\">B</a>\n<ol>\n<li><a href=\"b1.html\">B1</a></li>\n</ol>\n</li>\n</ol>\n</li>\n<li><a href=\"orphan.html\">Orphan</a></li>\n</ol>"
+        ));
+    }
+
+    #[test]
+    fn test_write_epub_layout() {
+        let pages = vec![page("index.html", "Book & Co", &[])];
+        let metadata = EpubMetadata {
+            title: "Book & Co".to_string(),
+            author: Some("Ann".to_string()),
+            language: "en".to_string(),
+            identifier: "urn:test".to_string(),
+            modified: "2024-01-01T00:00:00Z".to_string(),
+        };
+        let resources = vec![("attachments/a b.png".to_string(), b"png".to_vec())];
+        let bytes = write_epub(&metadata, &pages, &reading_order(&pages), &resources).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        assert_eq!(
+            archive.by_index(0).unwrap().compression(),
+            CompressionMethod::Stored
+        );
+        let mut opf = String::new();
+        archive
+            .by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut opf)
+            .unwrap();
+        assert!(opf.contains("<dc:title>Book &amp; Co</dc:title>"));
+        assert!(opf.contains("<dc:creator>Ann</dc:creator>"));
+        assert!(
+            opf.contains(
+                r#"<item id="res0" href="attachments/a%20b.png" media-type="image/png"/>"#
+            )
+        );
+        assert!(opf.contains(r#"<itemref idref="page0"/>"#));
+        assert!(archive.by_name("OEBPS/index.html").is_ok());
+        assert!(archive.by_name("OEBPS/attachments/a b.png").is_ok());
+    }
+}
@@ -0,0 +1,159 @@
+//! Publish manifest for incremental publishing.
+//!
+//! A multi-file publish writes `.diaryx-publish.json` to the destination,
+//! recording for each published source its content hash, its output file, the
+//! pages its navigation links to (with the titles used) and the files its body
+//! links to. The next publish
+//! to the same destination only re-renders a page when its source, one of
+//! those links, or something every page depends on (the theme, the options,
+//! the site-wide links) changed, and removes the output of deleted sources.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::fs::AsyncFileSystem;
+
+/// Manifest file name, in the publish destination
+pub const MANIFEST_FILE: &str = ".diaryx-publish.json";
+
+/// State of a previous multi-file publish
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PublishManifest {
+    /// Version of the manifest format
+    pub version: u32,
+    /// Hash of what every page depends on: theme, options and site-wide links
+    pub fingerprint: String,
+    /// Published pages by source path (relative to the workspace folder)
+    pub pages: BTreeMap<String, ManifestPage>,
+    /// Other files written to the destination (stylesheet, assets, listings,
+    /// feeds and sitemap)
+    pub generated: BTreeSet<String>,
+}
+
+/// A published page in the [`PublishManifest`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ManifestPage {
+    /// Hash of the source file
+    pub source_hash: String,
+    /// Output file, relative to the destination
+    pub output: String,
+    /// Pages linked from the navigation (source path, or href for pages that
+    /// weren't published) -> title shown in the link, and files linked from
+    /// the body (source path) -> the output the link points at
+    pub links: BTreeMap<String, String>,
+}
+
+impl PublishManifest {
+    /// Current manifest format version
+    pub const CURRENT_VERSION: u32 = 1;
+
+    /// Create an empty manifest
+    pub fn new(fingerprint: impl Into<String>) -> Self {
+        Self {
+            version: Self::CURRENT_VERSION,
+            fingerprint: fingerprint.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Read the manifest of a destination.
+    ///
+    /// Returns `None` if there is none, or it can't be read or is from another
+    /// format version (everything is then re-rendered).
+    pub async fn load<FS: AsyncFileSystem>(fs: &FS, destination: &Path) -> Option<Self> {
+        let json = fs
+            .read_to_string(&destination.join(MANIFEST_FILE))
+            .await
+            .ok()?;
+        serde_json::from_str::<Self>(&json)
+            .ok()
+            .filter(|manifest| manifest.version == Self::CURRENT_VERSION)
+    }
+
+    /// Write the manifest to a destination
+    pub async fn save<FS: AsyncFileSystem>(&self, fs: &FS, destination: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::from)?;
+        fs.write_file(&destination.join(MANIFEST_FILE), &json)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether a page must be rendered again to match the previous publish
+    /// (its output file existing is checked separately)
+    pub fn needs_render(&self, source: &str, page: &ManifestPage) -> bool {
+        self.pages.get(source) != Some(page)
+    }
+
+    /// Files written by this publish that `current` no longer writes
+    pub fn stale_outputs(&self, current: &PublishManifest) -> Vec<String> {
+        let written = current.outputs();
+        self.outputs()
+            .into_iter()
+            .filter(|output| !written.contains(output))
+            .collect()
+    }
+
+    /// Every file written to the destination
+    fn outputs(&self) -> BTreeSet<String> {
+        self.pages
+            .values()
+            .map(|page| page.output.clone())
+            .chain(self.generated.iter().cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{InMemoryFileSystem, SyncToAsyncFs, block_on_test};
+
+    fn page(hash: &str, output: &str) -> ManifestPage {
+        ManifestPage {
+            source_hash: hash.to_string(),
+            output: output.to_string(),
+            links: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_manifest_roundtrip_and_version() {
+        let fs = SyncToAsyncFs::new(InMemoryFileSystem::new());
+        let dest = Path::new("/site");
+        assert!(block_on_test(PublishManifest::load(&fs, dest)).is_none());
+
+        let mut manifest = PublishManifest::new("abc");
+        manifest.pages.insert("a.md".into(), page("1", "a.html"));
+        block_on_test(manifest.save(&fs, dest)).unwrap();
+        assert_eq!(
+            block_on_test(PublishManifest::load(&fs, dest)),
+            Some(manifest.clone())
+        );
+
+        manifest.version = 0;
+        block_on_test(manifest.save(&fs, dest)).unwrap();
+        assert!(block_on_test(PublishManifest::load(&fs, dest)).is_none());
+    }
+
+    #[test]
+    fn test_needs_render_and_stale_outputs() {
+        let mut previous = PublishManifest::new("abc");
+        previous.pages.insert("a.md".into(), page("1", "a.html"));
+        previous.pages.insert("b.md".into(), page("2", "b.html"));
+        previous.generated.insert("tags.html".into());
+
+        let mut current = PublishManifest::new("abc");
+        current.pages.insert("a.md".into(), page("1", "a.html"));
+
+        assert!(!previous.needs_render("a.md", &page("1", "a.html")));
+        assert!(previous.needs_render("a.md", &page("3", "a.html")));
+        assert!(previous.needs_render("c.md", &page("1", "c.html")));
+        assert_eq!(
+            previous.stale_outputs(&current),
+            vec!["b.html".to_string(), "tags.html".to_string()]
+        );
+    }
+}
@@ -7,11 +7,25 @@
 //! This module uses `AsyncFileSystem` for all filesystem operations.
 //! For synchronous contexts (CLI, tests), wrap a sync filesystem with
 //! `SyncToAsyncFs` and use `futures_lite::future::block_on()`.
-
+//!
+//! Pages are rendered with a [`Theme`]; see the [`theme`] module for how to
+//! write one. Multi-file sites also get feeds, a sitemap, date archives and
+//! tag pages (see the [`site`] module), and are published incrementally: only
+//! pages that changed since the last publish are rendered again (see the
+//! [`manifest`] module).
+
+pub mod attachments;
+#[cfg(feature = "epub")]
+mod epub;
+pub mod manifest;
+pub mod site;
+pub mod theme;
 mod types;
 
 // Re-export types for backwards compatibility
-pub use types::{NavLink, PublishOptions, PublishResult, PublishedPage};
+pub use manifest::{ManifestPage, PublishManifest};
+pub use theme::Theme;
+pub use types::{NavLink, PublishOptions, PublishResult, PublishedAttachment, PublishedPage};
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -22,17 +36,32 @@ use crate::export::{ExportPlan, Exporter};
 use crate::frontmatter;
 use crate::fs::AsyncFileSystem;
 use crate::link_parser;
+use crate::redaction;
+use crate::render::{Diagram, RenderPipeline};
+use crate::utils::path::is_contained;
 use crate::workspace::Workspace;
+use serde::Serialize;
+use site::Listing;
 
 /// Publisher for converting workspace to HTML (async-first)
 pub struct Publisher<FS: AsyncFileSystem> {
     fs: FS,
+    renderers: RenderPipeline,
 }
 
 impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
     /// Create a new publisher
     pub fn new(fs: FS) -> Self {
-        Self { fs }
+        Self {
+            fs,
+            renderers: RenderPipeline::default(),
+        }
+    }
+
+    /// Render math and diagrams with this pipeline (by default, math only)
+    pub fn with_renderers(mut self, renderers: RenderPipeline) -> Self {
+        self.renderers = renderers;
+        self
     }
 
     /// Publish a workspace to HTML
@@ -44,18 +73,23 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
         destination: &Path,
         options: &PublishOptions,
     ) -> Result<PublishResult> {
+        let workspace_dir = workspace_root.parent().unwrap_or(Path::new("."));
+        let theme = Theme::resolve(&self.fs, workspace_dir, options.theme.as_deref()).await?;
+
         // Collect files to publish
         let pages = if let Some(ref audience) = options.audience {
-            self.collect_with_audience(workspace_root, destination, audience)
+            self.collect_with_audience(workspace_root, destination, audience, options)
                 .await?
         } else {
-            self.collect_all(workspace_root).await?
+            self.collect_all(workspace_root, options).await?
         };
 
         if pages.is_empty() {
             return Ok(PublishResult {
                 pages: vec![],
                 files_processed: 0,
+                pages_rendered: 0,
+                files_removed: 0,
             });
         }
 
@@ -63,19 +97,134 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
 
         // Generate output
         if options.single_file {
-            self.write_single_file(&pages, destination, options).await?;
-        } else {
-            self.write_multi_file(&pages, destination, options).await?;
+            self.write_single_file(&pages, destination, options, &theme)
+                .await?;
+            return Ok(PublishResult {
+                pages,
+                files_processed,
+                pages_rendered: files_processed,
+                files_removed: 0,
+            });
+        }
+
+        let previous = PublishManifest::load(&self.fs, destination).await;
+        let (manifest, pages_rendered) = self
+            .write_multi_file(
+                &pages,
+                destination,
+                options,
+                &theme,
+                workspace_dir,
+                previous.as_ref(),
+            )
+            .await?;
+
+        // Remove the output of deleted sources and listings no longer generated.
+        // The manifest is read back from the destination, so paths that would
+        // leave it are never deleted.
+        let mut files_removed = 0;
+        for stale in previous
+            .map(|p| p.stale_outputs(&manifest))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|stale| is_contained(stale))
+        {
+            let path = destination.join(&stale);
+            if self.fs.exists(&path).await {
+                self.fs.delete_file(&path).await?;
+                files_removed += 1;
+            }
         }
+        manifest.save(&self.fs, destination).await?;
 
         Ok(PublishResult {
             pages,
             files_processed,
+            pages_rendered,
+            files_removed,
         })
     }
 
+    /// Package a workspace as an EPUB book, returning the archive's bytes.
+    ///
+    /// Pages are chapters in `contents` reading order, with the attachments
+    /// and diagrams they embed. Uses `title`, `audience` and
+    /// `image_max_width` from the options; the book's author and language
+    /// come from the root's `author` and `lang` properties.
+    #[cfg(feature = "epub")]
+    pub async fn epub(&self, workspace_root: &Path, options: &PublishOptions) -> Result<Vec<u8>> {
+        // Links between chapters work like between the pages of a site
+        let options = PublishOptions {
+            single_file: false,
+            ..options.clone()
+        };
+        let workspace_dir = workspace_root.parent().unwrap_or(Path::new("."));
+        let pages = if let Some(ref audience) = options.audience {
+            self.collect_with_audience(workspace_root, workspace_dir, audience, &options)
+                .await?
+        } else {
+            self.collect_all(workspace_root, &options).await?
+        };
+
+        let root = pages.iter().find(|page| page.is_root).or(pages.first());
+        let root_property = |key: &str| {
+            root.and_then(|page| frontmatter::get_string(&page.frontmatter, key))
+                .map(str::to_string)
+        };
+        let title = options
+            .title
+            .clone()
+            .or_else(|| root.map(|page| page.title.clone()))
+            .unwrap_or_else(|| "Journal".to_string());
+        let metadata = epub::EpubMetadata {
+            identifier: format!(
+                "urn:diaryx:{}",
+                crate::cloud::compute_content_hash(workspace_root.to_string_lossy().as_bytes())
+            ),
+            author: root_property("author"),
+            language: root_property("lang").unwrap_or_else(|| "en".to_string()),
+            modified: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            title,
+        };
+
+        // Attachments (or their thumbnails) and diagrams, at the paths the
+        // pages link to
+        let mut resources: Vec<(String, Vec<u8>)> = Vec::new();
+        for attachment in pages.iter().flat_map(|page| &page.attachments) {
+            if !is_contained(&attachment.output)
+                || resources.iter().any(|(path, _)| *path == attachment.output)
+            {
+                continue;
+            }
+            let Ok(bytes) = self.fs.read_binary(&attachment.source_path).await else {
+                continue;
+            };
+            if let (Some(thumbnail), Some(max_width)) =
+                (&attachment.thumbnail, options.image_max_width)
+            {
+                let small = attachments::thumbnail(&bytes, &attachment.source_path, max_width)
+                    .unwrap_or_else(|| bytes.clone());
+                resources.push((thumbnail.clone(), small));
+            }
+            resources.push((attachment.output.clone(), bytes));
+        }
+        for diagram in pages.iter().flat_map(|page| &page.diagrams) {
+            let path = diagram.path();
+            if !resources.iter().any(|(p, _)| *p == path) {
+                resources.push((path, diagram.svg.clone().into_bytes()));
+            }
+        }
+
+        let order = epub::reading_order(&pages);
+        epub::write_epub(&metadata, &pages, &order, &resources)
+    }
+
     /// Collect all workspace files without audience filtering
-    async fn collect_all(&self, workspace_root: &Path) -> Result<Vec<PublishedPage>> {
+    async fn collect_all(
+        &self,
+        workspace_root: &Path,
+        options: &PublishOptions,
+    ) -> Result<Vec<PublishedPage>> {
         let workspace = Workspace::new(self.fs.clone());
         let mut files = workspace.collect_workspace_files(workspace_root).await?;
 
@@ -105,11 +254,19 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
             };
             path_to_filename.insert(file_path.to_path_buf(), filename);
         }
+        let targets = self.link_targets(&path_to_filename, options).await;
 
         // Second pass: process files
         for (idx, file_path) in files.iter().enumerate() {
             if let Some(page) = self
-                .process_file(file_path, idx == 0, &path_to_filename, workspace_root)
+                .process_file(
+                    file_path,
+                    idx == 0,
+                    &path_to_filename,
+                    workspace_root,
+                    &targets,
+                    None,
+                )
                 .await?
             {
                 pages.push(page);
@@ -125,6 +282,7 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
         workspace_root: &Path,
         destination: &Path,
         audience: &str,
+        options: &PublishOptions,
     ) -> Result<Vec<PublishedPage>> {
         let exporter = Exporter::new(self.fs.clone());
         let plan = exporter
@@ -134,24 +292,34 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
         let mut pages = Vec::new();
         let mut path_to_filename: HashMap<PathBuf, String> = HashMap::new();
 
+        // The plan lists children before their parent, so find the root
+        let root_idx = plan
+            .included
+            .iter()
+            .position(|f| f.source_path == workspace_root)
+            .unwrap_or(0);
+
         // First pass: assign filenames
         for (idx, export_file) in plan.included.iter().enumerate() {
-            let filename = if idx == 0 {
+            let filename = if idx == root_idx {
                 "index.html".to_string()
             } else {
                 self.path_to_html_filename(&export_file.source_path)
             };
             path_to_filename.insert(export_file.source_path.clone(), filename);
         }
+        let targets = self.link_targets(&path_to_filename, options).await;
 
         // Second pass: process files
         for (idx, export_file) in plan.included.iter().enumerate() {
             if let Some(page) = self
                 .process_file(
                     &export_file.source_path,
-                    idx == 0,
+                    idx == root_idx,
                     &path_to_filename,
                     workspace_root,
+                    &targets,
+                    Some(audience),
                 )
                 .await?
             {
@@ -182,16 +350,48 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
         page
     }
 
-    /// Process a single file into a PublishedPage
+    /// Where body links to each published file point: its page, or its
+    /// section in single-file mode
+    async fn link_targets(
+        &self,
+        path_to_filename: &HashMap<PathBuf, String>,
+        options: &PublishOptions,
+    ) -> LinkTargets {
+        let mut pages = HashMap::new();
+        for (path, filename) in path_to_filename {
+            let href = if options.single_file {
+                let title = self.get_title_from_file(path).await.unwrap_or_else(|| {
+                    path.file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("Untitled")
+                        .to_string()
+                });
+                format!("#{}", self.title_to_anchor(&title))
+            } else {
+                attachments::encode_href(filename)
+            };
+            pages.insert(path.clone(), href);
+        }
+        LinkTargets {
+            pages,
+            image_max_width: options.image_max_width,
+        }
+    }
+
+    /// Process a single file into a PublishedPage, leaving out the parts that
+    /// aren't for `audience`
     async fn process_file(
         &self,
         path: &Path,
         is_root: bool,
         path_to_filename: &HashMap<PathBuf, String>,
-        _workspace_root: &Path,
+        workspace_root: &Path,
+        targets: &LinkTargets,
+        audience: Option<&str>,
     ) -> Result<Option<PublishedPage>> {
+        let workspace_dir = workspace_root.parent().unwrap_or(Path::new(""));
         let content = match self.fs.read_to_string(path).await {
-            Ok(c) => c,
+            Ok(c) => redaction::redact(&c, audience)?.content,
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
             Err(e) => {
                 return Err(DiaryxError::FileRead {
@@ -218,16 +418,40 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
 
         // Build contents links
         let contents_links = self
-            .build_contents_links(&parsed.frontmatter, path, path_to_filename)
+            .build_contents_links(&parsed.frontmatter, path, workspace_dir, path_to_filename)
             .await;
 
         // Build parent link
         let parent_link = self
-            .build_parent_link(&parsed.frontmatter, path, path_to_filename)
+            .build_parent_link(&parsed.frontmatter, path, workspace_dir, path_to_filename)
             .await;
 
-        // Convert markdown to HTML
-        let html_body = self.markdown_to_html(&parsed.body);
+        // Declared attachments
+        let mut page_attachments = Vec::new();
+        for attachment in frontmatter::get_string_array(&parsed.frontmatter, "attachments") {
+            let parsed_link = link_parser::parse_link(&attachment);
+            let (_, attachment_path) = resolve_link(&parsed_link, path, workspace_dir);
+            self.add_attachment(
+                &mut page_attachments,
+                workspace_dir,
+                &attachment_path,
+                targets,
+            )
+            .await;
+        }
+
+        // Convert markdown to HTML, pointing body links at published files
+        let mut diagrams = Vec::new();
+        let (html_body, linked_files) = self
+            .render_body(
+                &parsed.body,
+                path,
+                workspace_dir,
+                targets,
+                &mut page_attachments,
+                &mut diagrams,
+            )
+            .await;
 
         Ok(Some(PublishedPage {
             source_path: path.to_path_buf(),
@@ -238,27 +462,129 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
             contents_links,
             parent_link,
             is_root,
+            frontmatter: parsed.frontmatter,
+            attachments: page_attachments,
+            linked_files,
+            diagrams,
         }))
     }
 
+    /// Render a page body, rewriting links to workspace files and collecting
+    /// the files it links to and the diagrams it embeds
+    async fn render_body(
+        &self,
+        markdown: &str,
+        path: &Path,
+        workspace_dir: &Path,
+        targets: &LinkTargets,
+        page_attachments: &mut Vec<PublishedAttachment>,
+        diagrams: &mut Vec<Diagram>,
+    ) -> (String, Vec<PathBuf>) {
+        let mut rewrites = HashMap::new();
+        let mut linked_files = Vec::new();
+        for (url, is_image) in self.body_urls(markdown) {
+            if rewrites.contains_key(&(url.clone(), is_image)) {
+                continue;
+            }
+            let Some((local, fragment)) = attachments::split_local_url(&url) else {
+                continue;
+            };
+            let (canonical, target) =
+                resolve_link(&link_parser::parse_link(&local), path, workspace_dir);
+            // Links leaving the workspace are never published
+            if !is_contained(&canonical) {
+                continue;
+            }
+
+            let rewrite = if let Some(href) = targets.pages.get(&target) {
+                Some(match fragment {
+                    Some(fragment) if !href.starts_with('#') => {
+                        BodyLink::Href(format!("{}#{}", href, fragment))
+                    }
+                    _ => BodyLink::Href(href.clone()),
+                })
+            } else if target.extension().is_some_and(|ext| ext == "md") {
+                // An entry that isn't published (e.g. hidden from the audience)
+                Some(BodyLink::Unlink)
+            } else if let Some(attachment) = self
+                .add_attachment(page_attachments, workspace_dir, &target, targets)
+                .await
+            {
+                Some(BodyLink::File {
+                    href: attachments::encode_href(&attachment.output),
+                    thumbnail: attachment
+                        .thumbnail
+                        .as_deref()
+                        .map(attachments::encode_href),
+                })
+            } else {
+                None
+            };
+            // Missing files are recorded too, so the page is rendered again
+            // once they exist
+            if !linked_files.contains(&target) {
+                linked_files.push(target);
+            }
+            if let Some(rewrite) = rewrite {
+                rewrites.insert((url, is_image), rewrite);
+            }
+        }
+
+        (
+            self.markdown_to_html(markdown, &rewrites, diagrams),
+            linked_files,
+        )
+    }
+
+    /// Add a workspace file to a page's attachments, if it exists
+    async fn add_attachment(
+        &self,
+        page_attachments: &mut Vec<PublishedAttachment>,
+        workspace_dir: &Path,
+        path: &Path,
+        targets: &LinkTargets,
+    ) -> Option<PublishedAttachment> {
+        if let Some(existing) = page_attachments.iter().find(|a| a.source_path == path) {
+            return Some(existing.clone());
+        }
+        let inside = path.strip_prefix(workspace_dir).is_ok_and(is_contained);
+        if !inside || self.fs.is_dir(path).await || !self.fs.exists(path).await {
+            return None;
+        }
+
+        let thumbnail = match targets.image_max_width {
+            Some(max_width) if attachments::is_resizable_image(path) => {
+                let bytes = self.fs.read_binary(path).await.unwrap_or_default();
+                attachments::image_width(&bytes)
+                    .filter(|&width| width > max_width)
+                    .map(|_| attachments::thumbnail_output(workspace_dir, path))
+            }
+            _ => None,
+        };
+        let attachment = PublishedAttachment {
+            source_path: path.to_path_buf(),
+            output: attachments::attachment_output(workspace_dir, path),
+            thumbnail,
+        };
+        page_attachments.push(attachment.clone());
+        Some(attachment)
+    }
+
     /// Build navigation links from contents property
     async fn build_contents_links(
         &self,
         fm: &indexmap::IndexMap<String, serde_yaml::Value>,
         current_path: &Path,
+        workspace_dir: &Path,
         path_to_filename: &HashMap<PathBuf, String>,
     ) -> Vec<NavLink> {
         let contents = frontmatter::get_string_array(fm, "contents");
-        let current_dir = current_path.parent();
 
         let mut links = Vec::new();
         for child_ref in contents {
             // Parse markdown link to extract the actual path
             let parsed = link_parser::parse_link(&child_ref);
-            let canonical = link_parser::to_canonical(&parsed, current_path);
-            let child_path = current_dir
-                .map(|d| d.join(&canonical))
-                .unwrap_or_else(|| PathBuf::from(&canonical));
+            let (canonical, child_path) = resolve_link(&parsed, current_path, workspace_dir);
 
             // Try to find the HTML filename for this path
             let href = path_to_filename
@@ -283,17 +609,14 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
         &self,
         fm: &indexmap::IndexMap<String, serde_yaml::Value>,
         current_path: &Path,
+        workspace_dir: &Path,
         path_to_filename: &HashMap<PathBuf, String>,
     ) -> Option<NavLink> {
         let part_of = frontmatter::get_string(fm, "part_of")?;
-        let current_dir = current_path.parent();
 
         // Parse markdown link to extract the actual path
         let parsed = link_parser::parse_link(part_of);
-        let canonical = link_parser::to_canonical(&parsed, current_path);
-        let parent_path = current_dir
-            .map(|d| d.join(&canonical))
-            .unwrap_or_else(|| PathBuf::from(&canonical));
+        let (canonical, parent_path) = resolve_link(&parsed, current_path, workspace_dir);
 
         let href = path_to_filename
             .get(&parent_path)
@@ -345,35 +668,190 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
             .join(" ")
     }
 
-    /// Convert markdown to HTML using comrak
+    /// Comrak options used to render page bodies
     #[cfg(feature = "markdown")]
-    fn markdown_to_html(&self, markdown: &str) -> String {
-        use comrak::{Options, markdown_to_html};
-
-        let mut options = Options::default();
+    fn comrak_options(&self) -> comrak::Options<'static> {
+        let mut options = comrak::Options::default();
         options.extension.strikethrough = true;
         options.extension.table = true;
         options.extension.autolink = true;
         options.extension.tasklist = true;
+        options.extension.math_dollars = self.renderers.math().is_some();
         options.render.r#unsafe = true; // Allow raw HTML
+        options
+    }
+
+    /// Destinations of the links (`false`) and images (`true`) in a body
+    #[cfg(feature = "markdown")]
+    fn body_urls(&self, markdown: &str) -> Vec<(String, bool)> {
+        use comrak::nodes::NodeValue;
+
+        let arena = comrak::Arena::new();
+        let root = comrak::parse_document(&arena, markdown, &self.comrak_options());
+        root.descendants()
+            .filter_map(|node| match &node.data().value {
+                NodeValue::Link(link) => Some((link.url.clone(), false)),
+                NodeValue::Image(link) => Some((link.url.clone(), true)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[cfg(not(feature = "markdown"))]
+    fn body_urls(&self, _markdown: &str) -> Vec<(String, bool)> {
+        Vec::new()
+    }
+
+    /// Convert markdown to HTML using comrak, applying link `rewrites` and
+    /// rendering math and diagrams (collected in `diagrams`)
+    #[cfg(feature = "markdown")]
+    fn markdown_to_html(
+        &self,
+        markdown: &str,
+        rewrites: &HashMap<(String, bool), BodyLink>,
+        diagrams: &mut Vec<Diagram>,
+    ) -> String {
+        use comrak::nodes::{NodeLink, NodeValue};
+
+        let options = self.comrak_options();
+        let arena = comrak::Arena::new();
+        let root = comrak::parse_document(&arena, markdown, &options);
+
+        let nodes: Vec<_> = root.descendants().collect();
+        for node in nodes {
+            let rendered = match &node.data().value {
+                NodeValue::Math(math) => Some(NodeValue::HtmlInline(
+                    self.renderers.render_math(&math.literal, math.display_math),
+                )),
+                NodeValue::CodeBlock(block) if block.fenced => {
+                    self.render_code_block(&block.info, &block.literal, diagrams)
+                }
+                _ => None,
+            };
+            if let Some(rendered) = rendered {
+                node.data_mut().value = rendered;
+                continue;
+            }
+
+            let (url, is_image, title) = match &node.data().value {
+                NodeValue::Link(link) => (link.url.clone(), false, link.title.clone()),
+                NodeValue::Image(link) => (link.url.clone(), true, link.title.clone()),
+                _ => continue,
+            };
+            match rewrites.get(&(url, is_image)) {
+                Some(BodyLink::Href(href))
+                | Some(BodyLink::File {
+                    href,
+                    thumbnail: None,
+                }) => {
+                    Self::set_url(node, href);
+                }
+                Some(BodyLink::File {
+                    href,
+                    thumbnail: Some(thumbnail),
+                }) => {
+                    let in_link = node
+                        .parent()
+                        .is_some_and(|parent| matches!(parent.data().value, NodeValue::Link(_)));
+                    if is_image && !in_link {
+                        // Show the thumbnail, linking to the full-size image
+                        Self::set_url(node, thumbnail);
+                        let link = arena.alloc(
+                            NodeValue::Link(Box::new(NodeLink {
+                                url: href.clone(),
+                                title,
+                            }))
+                            .into(),
+                        );
+                        node.insert_before(link);
+                        link.append(node);
+                    } else if is_image {
+                        Self::set_url(node, thumbnail);
+                    } else {
+                        Self::set_url(node, href);
+                    }
+                }
+                Some(BodyLink::Unlink) => {
+                    // Keep the link text, drop the link
+                    let children: Vec<_> = node.children().collect();
+                    for child in children {
+                        node.insert_before(child);
+                    }
+                    node.detach();
+                }
+                None => {}
+            }
+        }
+
+        let mut html = String::new();
+        comrak::format_html(root, &options, &mut html).expect("writing to a String cannot fail");
+        html
+    }
+
+    /// Render a ```math or diagram code block to HTML, or `None` to keep it
+    /// as code
+    #[cfg(feature = "markdown")]
+    fn render_code_block(
+        &self,
+        info: &str,
+        source: &str,
+        diagrams: &mut Vec<Diagram>,
+    ) -> Option<comrak::nodes::NodeValue> {
+        use comrak::nodes::{NodeHtmlBlock, NodeValue};
+
+        let language = info.split_whitespace().next()?;
+        let literal = if language == "math" && self.renderers.math().is_some() {
+            format!(
+                "<p>{}</p>\n",
+                self.renderers.render_math(source.trim_end(), true)
+            )
+        } else {
+            let diagram = self.renderers.render_diagram(language, source)?;
+            let html = diagram.html();
+            if !diagrams.iter().any(|d| d.file_name == diagram.file_name) {
+                diagrams.push(diagram);
+            }
+            html
+        };
+        Some(NodeValue::HtmlBlock(NodeHtmlBlock {
+            block_type: 6,
+            literal,
+        }))
+    }
+
+    /// Point a link or image node at a new URL
+    #[cfg(feature = "markdown")]
+    fn set_url<'a>(node: comrak::Node<'a>, url: &str) {
+        use comrak::nodes::NodeValue;
 
-        markdown_to_html(markdown, &options)
+        if let NodeValue::Link(link) | NodeValue::Image(link) = &mut node.data_mut().value {
+            link.url = url.to_string();
+        }
     }
 
     #[cfg(not(feature = "markdown"))]
-    fn markdown_to_html(&self, markdown: &str) -> String {
+    fn markdown_to_html(
+        &self,
+        markdown: &str,
+        _rewrites: &HashMap<(String, bool), BodyLink>,
+        _diagrams: &mut Vec<Diagram>,
+    ) -> String {
         // Basic fallback without comrak
         format!("<pre>{}</pre>", markdown)
     }
 
-    /// Write multiple HTML files
+    /// Write multiple HTML files, re-rendering only pages that changed since
+    /// the `previous` publish (all of them with `force`)
     #[cfg(not(target_arch = "wasm32"))]
     async fn write_multi_file(
         &self,
         pages: &[PublishedPage],
         destination: &Path,
         options: &PublishOptions,
-    ) -> Result<()> {
+        theme: &Theme,
+        workspace_dir: &Path,
+        previous: Option<&PublishManifest>,
+    ) -> Result<(PublishManifest, usize)> {
         // Create destination directory
         self.fs.create_dir_all(destination).await?;
 
@@ -383,17 +861,323 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
                 .map(|p| p.title.clone())
                 .unwrap_or_else(|| "Journal".to_string())
         });
+        let base_url = options.base_url.as_deref();
+
+        let dates: Vec<_> = pages.iter().map(site::entry_date).collect();
+        let has_dates = dates.iter().any(Option::is_some);
+        // Feeds and the sitemap need absolute links
+        let has_feeds = has_dates && base_url.is_some();
+        let archives = site::archives(&dates);
+        let tags = site::tag_pages(pages, &dates);
+        // A page of the workspace wins over a generated listing of the same name
+        let listings: Vec<Listing> = archives
+            .iter()
+            .chain(&tags)
+            .filter(|listing| {
+                !pages
+                    .iter()
+                    .any(|page| page.dest_filename == listing.dest_filename)
+            })
+            .cloned()
+            .collect();
+        let written = |file: &'static str| {
+            listings
+                .iter()
+                .any(|listing| listing.dest_filename == file)
+                .then_some(file)
+        };
+
+        let site = SiteContext {
+            title: &site_title,
+            theme: &theme.name,
+            stylesheet: Some(theme::STYLESHEET),
+            css: None,
+            base_url,
+            rss: has_feeds.then_some(site::RSS_FILE),
+            atom: has_feeds.then_some(site::ATOM_FILE),
+            archive: written(site::ARCHIVE_FILE),
+            tags: written(site::TAGS_FILE),
+            katex: needs_katex(pages),
+        };
+        let all_pages: Vec<PageContext> = pages
+            .iter()
+            .zip(&dates)
+            .map(|(page, date)| self.page_context(page, date, false))
+            .collect();
+
+        let mut manifest =
+            PublishManifest::new(self.fingerprint(options, theme, &site, &all_pages));
+        // Everything is re-rendered when forced or when the theme, options or
+        // site links changed
+        let previous = previous.filter(|p| !options.force && p.fingerprint == manifest.fingerprint);
+        let sources: HashMap<&str, String> = pages
+            .iter()
+            .map(|page| {
+                (
+                    page.dest_filename.as_str(),
+                    source_key(workspace_dir, &page.source_path),
+                )
+            })
+            .collect();
+
+        let env = theme.environment()?;
+        let mut rendered = 0;
+        for page in &all_pages {
+            let source = source_key(workspace_dir, &page.page.source_path);
+            let entry = ManifestPage {
+                source_hash: crate::cloud::compute_content_hash(
+                    &self
+                        .fs
+                        .read_binary(&page.page.source_path)
+                        .await
+                        .unwrap_or_default(),
+                ),
+                output: page.page.dest_filename.clone(),
+                links: page
+                    .page
+                    .parent_link
+                    .iter()
+                    .chain(&page.page.contents_links)
+                    .map(|link| {
+                        let target = sources
+                            .get(link.href.as_str())
+                            .cloned()
+                            .unwrap_or_else(|| link.href.clone());
+                        (target, link.title.clone())
+                    })
+                    .chain(page.page.linked_files.iter().map(|file| {
+                        (
+                            source_key(workspace_dir, file),
+                            Self::linked_output(pages, &page.page.attachments, file),
+                        )
+                    }))
+                    .collect(),
+            };
+            let dest_path = destination.join(&page.page.dest_filename);
+            let up_to_date = previous.is_some_and(|p| !p.needs_render(&source, &entry))
+                && self.fs.exists(&dest_path).await;
+            manifest.pages.insert(source, entry);
+            if up_to_date {
+                continue;
+            }
+
+            let template = if page.page.is_root {
+                theme::INDEX_TEMPLATE
+            } else {
+                theme::PAGE_TEMPLATE
+            };
+            let html = theme::render(
+                &env,
+                template,
+                TemplateContext {
+                    site: &site,
+                    page: Some(page),
+                    listing: None,
+                    pages: &all_pages,
+                },
+            )?;
+            self.write_if_changed(&dest_path, &html).await?;
+            rendered += 1;
+        }
+
+        // Archive and tag pages, feeds and sitemap are cheap to generate, so
+        // they're only compared with what's on disk
+        let mut generated: Vec<(String, String)> = Vec::new();
+        for listing in &listings {
+            let html = theme::render(
+                &env,
+                theme::LIST_TEMPLATE,
+                TemplateContext {
+                    site: &site,
+                    page: None,
+                    listing: Some(&ListingContext {
+                        title: &listing.title,
+                        href: &listing.dest_filename,
+                        links: &listing.links,
+                        pages: listing.pages.iter().map(|&i| &all_pages[i]).collect(),
+                    }),
+                    pages: &all_pages,
+                },
+            )?;
+            generated.push((listing.dest_filename.clone(), html));
+        }
+        match base_url {
+            Some(base_url) => {
+                if has_dates {
+                    let rss = site::rss(&site_title, base_url, pages, &dates);
+                    generated.push((site::RSS_FILE.to_string(), rss));
+                    let atom = site::atom(&site_title, base_url, pages, &dates);
+                    generated.push((site::ATOM_FILE.to_string(), atom));
+                }
+                let sitemap = site::sitemap(base_url, pages, &dates, &listings);
+                generated.push((site::SITEMAP_FILE.to_string(), sitemap));
+            }
+            None => log::warn!("[Publish] No base URL set, skipping the sitemap and feeds"),
+        }
+        generated.push((
+            theme::STYLESHEET.to_string(),
+            theme.stylesheet().to_string(),
+        ));
+        for (relative, contents) in generated {
+            self.write_if_changed(&destination.join(&relative), &contents)
+                .await?;
+            manifest.generated.insert(relative);
+        }
+
+        let copied = self.copy_attachments(pages, destination, options).await?;
+        manifest.generated.extend(copied);
+        let diagrams = self.write_diagrams(pages, destination).await?;
+        manifest.generated.extend(diagrams);
+
+        // Copy theme assets
+        for (relative, contents) in theme.assets() {
+            let asset_path = destination.join(theme::ASSETS_DIR).join(relative);
+            if self.fs.read_binary(&asset_path).await.ok().as_deref() != Some(contents) {
+                if let Some(parent) = asset_path.parent() {
+                    self.fs.create_dir_all(parent).await?;
+                }
+                self.fs.write_binary(&asset_path, contents).await?;
+            }
+            manifest
+                .generated
+                .insert(source_key(destination, &asset_path));
+        }
+
+        Ok((manifest, rendered))
+    }
+
+    /// What a body link to `file` points at, recorded in the manifest so the
+    /// page is rendered again when that changes
+    fn linked_output(
+        pages: &[PublishedPage],
+        page_attachments: &[PublishedAttachment],
+        file: &Path,
+    ) -> String {
+        if let Some(page) = pages.iter().find(|page| page.source_path == file) {
+            return page.dest_filename.clone();
+        }
+        page_attachments
+            .iter()
+            .find(|attachment| attachment.source_path == file)
+            .map(|attachment| {
+                attachment
+                    .thumbnail
+                    .clone()
+                    .unwrap_or_else(|| attachment.output.clone())
+            })
+            .unwrap_or_default()
+    }
+
+    /// Copy the pages' attachments (and thumbnails of large images) into the
+    /// output folder, returning the files written relative to it.
+    ///
+    /// Files already matching their source are left alone, and thumbnails are
+    /// only made again when their image changed.
+    async fn copy_attachments(
+        &self,
+        pages: &[PublishedPage],
+        output_dir: &Path,
+        options: &PublishOptions,
+    ) -> Result<Vec<String>> {
+        let mut written = Vec::new();
+        for attachment in pages.iter().flat_map(|page| &page.attachments) {
+            if !is_contained(&attachment.output) || written.contains(&attachment.output) {
+                continue;
+            }
+            let bytes = self.fs.read_binary(&attachment.source_path).await?;
+            let output = output_dir.join(&attachment.output);
+            let changed = self.fs.read_binary(&output).await.ok() != Some(bytes.clone());
+            if changed {
+                if let Some(parent) = output.parent() {
+                    self.fs.create_dir_all(parent).await?;
+                }
+                self.fs.write_binary(&output, &bytes).await?;
+            }
+            written.push(attachment.output.clone());
+
+            if let (Some(thumbnail), Some(max_width)) =
+                (&attachment.thumbnail, options.image_max_width)
+            {
+                let thumbnail_path = output_dir.join(thumbnail);
+                if (changed || !self.fs.exists(&thumbnail_path).await)
+                    && let Some(small) =
+                        attachments::thumbnail(&bytes, &attachment.source_path, max_width)
+                {
+                    if let Some(parent) = thumbnail_path.parent() {
+                        self.fs.create_dir_all(parent).await?;
+                    }
+                    self.fs.write_binary(&thumbnail_path, &small).await?;
+                }
+                written.push(thumbnail.clone());
+            }
+        }
+        Ok(written)
+    }
 
-        for page in pages {
-            let html = self.render_page(page, &site_title, false);
-            let dest_path = destination.join(&page.dest_filename);
-            self.fs.write_file(&dest_path, &html).await?;
+    /// Write the pages' rendered diagrams into the output folder, returning
+    /// the files written relative to it
+    async fn write_diagrams(
+        &self,
+        pages: &[PublishedPage],
+        output_dir: &Path,
+    ) -> Result<Vec<String>> {
+        let mut written = Vec::new();
+        for diagram in pages.iter().flat_map(|page| &page.diagrams) {
+            let relative = diagram.path();
+            if written.contains(&relative) {
+                continue;
+            }
+            let path = output_dir.join(&relative);
+            if let Some(parent) = path.parent() {
+                self.fs.create_dir_all(parent).await?;
+            }
+            self.write_if_changed(&path, &diagram.svg).await?;
+            written.push(relative);
         }
+        Ok(written)
+    }
 
-        // Write CSS file
-        let css_path = destination.join("style.css");
-        self.fs.write_file(&css_path, Self::get_css()).await?;
+    /// Hash of everything all pages depend on, so a change re-renders them all
+    fn fingerprint(
+        &self,
+        options: &PublishOptions,
+        theme: &Theme,
+        site: &SiteContext,
+        pages: &[PageContext],
+    ) -> String {
+        #[derive(Serialize)]
+        struct Fingerprint<'a> {
+            version: &'a str,
+            theme: String,
+            renderers: String,
+            audience: Option<&'a str>,
+            site: &'a SiteContext<'a>,
+            /// Hash of the page list, only when page templates may list every page
+            pages: Option<String>,
+        }
+        let fingerprint = Fingerprint {
+            version: env!("CARGO_PKG_VERSION"),
+            theme: theme.fingerprint(),
+            renderers: self.renderers.fingerprint(),
+            audience: options.audience.as_deref(),
+            site,
+            pages: theme.lists_pages().then(|| {
+                let list: Vec<_> = pages
+                    .iter()
+                    .map(|p| (&p.href, &p.page.title, &p.date, &p.tags))
+                    .collect();
+                hash_json(&list)
+            }),
+        };
+        hash_json(&fingerprint)
+    }
 
+    /// Write a file unless it already has these contents, so unchanged files
+    /// keep their modification time
+    async fn write_if_changed(&self, path: &Path, contents: &str) -> Result<()> {
+        if self.fs.read_to_string(path).await.ok().as_deref() != Some(contents) {
+            self.fs.write_file(path, contents).await?;
+        }
         Ok(())
     }
 
@@ -404,6 +1188,7 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
         pages: &[PublishedPage],
         destination: &Path,
         options: &PublishOptions,
+        theme: &Theme,
     ) -> Result<()> {
         let site_title = options.title.clone().unwrap_or_else(|| {
             pages
@@ -412,430 +1197,810 @@ impl<FS: AsyncFileSystem + Clone> Publisher<FS> {
                 .unwrap_or_else(|| "Journal".to_string())
         });
 
-        let html = self.render_single_file(pages, &site_title);
+        // Navigation links point at sections of the same file
+        let anchored: Vec<PublishedPage> =
+            pages.iter().map(|page| self.anchor_links(page)).collect();
+        let dates: Vec<_> = anchored.iter().map(site::entry_date).collect();
+        let all_pages: Vec<PageContext> = anchored
+            .iter()
+            .zip(&dates)
+            .map(|(page, date)| self.page_context(page, date, true))
+            .collect();
+
+        let env = theme.environment()?;
+        let html = theme::render(
+            &env,
+            theme::SINGLE_TEMPLATE,
+            TemplateContext {
+                site: &SiteContext {
+                    title: &site_title,
+                    theme: &theme.name,
+                    stylesheet: None,
+                    css: Some(theme.stylesheet()),
+                    base_url: options.base_url.as_deref(),
+                    rss: None,
+                    atom: None,
+                    archive: None,
+                    tags: None,
+                    katex: needs_katex(pages),
+                },
+                page: None,
+                listing: None,
+                pages: &all_pages,
+            },
+        )?;
 
         // Ensure parent directory exists
         if let Some(parent) = destination.parent() {
             self.fs.create_dir_all(parent).await?;
         }
 
-        self.fs.write_file(destination, &html).await?;
+        self.write_if_changed(destination, &html).await?;
+
+        // Attachments and diagrams go next to the HTML file
+        let output_dir = destination.parent().unwrap_or(Path::new(""));
+        self.copy_attachments(pages, output_dir, options).await?;
+        self.write_diagrams(pages, output_dir).await?;
 
         Ok(())
     }
 
-    /// Render a single page to HTML
-    fn render_page(&self, page: &PublishedPage, site_title: &str, single_file: bool) -> String {
-        let nav_html = self.render_navigation(page, single_file);
-        let css_link = if single_file {
-            format!("<style>{}</style>", Self::get_css())
+    /// Template variables for a page
+    fn page_context<'a>(
+        &self,
+        page: &'a PublishedPage,
+        date: &Option<chrono::DateTime<chrono::FixedOffset>>,
+        single_file: bool,
+    ) -> PageContext<'a> {
+        let anchor = self.title_to_anchor(&page.title);
+        let href = if single_file {
+            format!("#{}", anchor)
         } else {
-            r#"<link rel="stylesheet" href="style.css">"#.to_string()
+            page.dest_filename.clone()
         };
-
-        format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{page_title} - {site_title}</title>
-    {css_link}
-</head>
-<body>
-    <header>
-        <h1 class="site-title"><a href="index.html">{site_title}</a></h1>
-    </header>
-    <main>
-        <article>
-            <h1 class="page-title">{page_title}</h1>
-            {nav_html}
-            <div class="content">
-                {content}
-            </div>
-        </article>
-    </main>
-    <footer>
-        <p>Generated by <a href="https://github.com/diaryx-org/diaryx-core">diaryx</a></p>
-    </footer>
-</body>
-</html>"#,
-            page_title = html_escape(&page.title),
-            site_title = html_escape(site_title),
-            css_link = css_link,
-            nav_html = nav_html,
-            content = page.html_body,
-        )
+        // Single-file output has no tag pages to link to
+        let tags = site::page_tags(page)
+            .into_iter()
+            .map(|tag| NavLink {
+                href: if single_file {
+                    String::new()
+                } else {
+                    site::tag_filename(&tag)
+                },
+                title: tag,
+            })
+            .collect();
+        PageContext {
+            page,
+            href,
+            anchor,
+            date: date.map(|d| d.format("%Y-%m-%d").to_string()),
+            tags,
+        }
     }
 
-    /// Render navigation links
-    fn render_navigation(&self, page: &PublishedPage, single_file: bool) -> String {
-        let mut nav_parts = Vec::new();
-
-        // Parent link (breadcrumb style)
-        if let Some(ref parent) = page.parent_link {
-            let href = if single_file {
-                format!("#{}", self.title_to_anchor(&parent.title))
-            } else {
-                parent.href.clone()
-            };
-            nav_parts.push(format!(
-                r#"<div class="parent-link">↑ <a href="{}">{}</a></div>"#,
-                html_escape(&href),
-                html_escape(&parent.title)
-            ));
-        }
-
-        // Contents links
-        if !page.contents_links.is_empty() {
-            let mut contents_html = String::from(r#"<nav class="contents"><h3>Contents</h3><ul>"#);
-            for link in &page.contents_links {
-                let href = if single_file {
-                    format!("#{}", self.title_to_anchor(&link.title))
-                } else {
-                    link.href.clone()
-                };
-                contents_html.push_str(&format!(
-                    r#"<li><a href="{}">{}</a></li>"#,
-                    html_escape(&href),
-                    html_escape(&link.title)
-                ));
-            }
-            contents_html.push_str("</ul></nav>");
-            nav_parts.push(contents_html);
-        }
-
-        nav_parts.join("\n")
-    }
-
-    /// Render all pages into a single HTML file
-    fn render_single_file(&self, pages: &[PublishedPage], site_title: &str) -> String {
-        let mut sections = Vec::new();
-
-        for page in pages {
-            let anchor = self.title_to_anchor(&page.title);
-            let nav_html = self.render_navigation(page, true);
-
-            sections.push(format!(
-                r#"<section id="{anchor}">
-    <h2 class="page-title">{title}</h2>
-    {nav_html}
-    <div class="content">
-        {content}
-    </div>
-</section>"#,
-                anchor = html_escape(&anchor),
-                title = html_escape(&page.title),
-                nav_html = nav_html,
-                content = page.html_body,
-            ));
-        }
-
-        // Build table of contents
-        let mut toc = String::from(r#"<nav class="toc"><h2>Table of Contents</h2><ul>"#);
-        for page in pages {
-            let anchor = self.title_to_anchor(&page.title);
-            toc.push_str(&format!(
-                r##"<li><a href="#{}">{}</a></li>"##,
-                html_escape(&anchor),
-                html_escape(&page.title)
-            ));
-        }
-        toc.push_str("</ul></nav>");
-
-        format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{site_title}</title>
-    <style>{css}</style>
-</head>
-<body>
-    <header>
-        <h1 class="site-title">{site_title}</h1>
-    </header>
-    <main>
-        {toc}
-        {sections}
-    </main>
-    <footer>
-        <p>Generated by <a href="https://github.com/diaryx-org/diaryx-core">diaryx</a></p>
-    </footer>
-</body>
-</html>"#,
-            site_title = html_escape(site_title),
-            css = Self::get_css(),
-            toc = toc,
-            sections = sections.join("\n<hr>\n"),
-        )
+    /// Copy of a page with navigation links pointing at single-file anchors
+    fn anchor_links(&self, page: &PublishedPage) -> PublishedPage {
+        let anchor = |link: &NavLink| NavLink {
+            href: format!("#{}", self.title_to_anchor(&link.title)),
+            title: link.title.clone(),
+        };
+        PublishedPage {
+            parent_link: page.parent_link.as_ref().map(anchor),
+            contents_links: page.contents_links.iter().map(anchor).collect(),
+            ..page.clone()
+        }
     }
 
     /// Convert a title to an anchor ID
     fn title_to_anchor(&self, title: &str) -> String {
         slugify(title)
     }
-
-    /// Get the CSS stylesheet
-    fn get_css() -> &'static str {
-        r#"
-:root {
-    --bg: #fafafa;
-    --text: #333;
-    --text-muted: #666;
-    --accent: #2563eb;
-    --accent-hover: #1d4ed8;
-    --border: #e5e7eb;
-    --code-bg: #f3f4f6;
-}
-
-@media (prefers-color-scheme: dark) {
-    :root {
-        --bg: #1a1a1a;
-        --text: #e5e5e5;
-        --text-muted: #a3a3a3;
-        --accent: #60a5fa;
-        --accent-hover: #93c5fd;
-        --border: #404040;
-        --code-bg: #262626;
-    }
-}
-
-* {
-    box-sizing: border-box;
-}
-
-html {
-    font-size: 16px;
-}
-
-body {
-    font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, Oxygen, Ubuntu, sans-serif;
-    line-height: 1.6;
-    color: var(--text);
-    background: var(--bg);
-    max-width: 48rem;
-    margin: 0 auto;
-    padding: 2rem 1rem;
-}
-
-header {
-    margin-bottom: 2rem;
-    padding-bottom: 1rem;
-    border-bottom: 1px solid var(--border);
 }
 
-.site-title {
-    font-size: 1.5rem;
-    margin: 0;
+/// Where body links to published files point
+struct LinkTargets {
+    /// Published source -> href of its page (or section in single-file mode)
+    pages: HashMap<PathBuf, String>,
+    /// Scale down embedded images wider than this
+    image_max_width: Option<u32>,
 }
 
-.site-title a {
-    color: var(--text);
-    text-decoration: none;
+/// How a body link or image is rewritten
+#[cfg_attr(not(feature = "markdown"), allow(dead_code))]
+enum BodyLink {
+    /// Point it at a published page
+    Href(String),
+    /// Point it at a copied attachment, showing images as their thumbnail
+    File {
+        href: String,
+        thumbnail: Option<String>,
+    },
+    /// Keep only the link text (the linked entry isn't published)
+    Unlink,
 }
 
-.site-title a:hover {
-    color: var(--accent);
+/// Variables available to every theme template
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+    site: &'a SiteContext<'a>,
+    /// The page being rendered (`None` in single-file mode and listings)
+    page: Option<&'a PageContext<'a>>,
+    /// The archive or tag page being rendered
+    listing: Option<&'a ListingContext<'a>>,
+    pages: &'a [PageContext<'a>],
 }
 
-.page-title {
-    font-size: 2rem;
-    margin-top: 0;
-    margin-bottom: 1rem;
+/// Site-wide template variables
+#[derive(Serialize)]
+struct SiteContext<'a> {
+    title: &'a str,
+    theme: &'a str,
+    /// Stylesheet href (multi-file)
+    stylesheet: Option<&'a str>,
+    /// Stylesheet contents to inline (single-file)
+    css: Option<&'a str>,
+    base_url: Option<&'a str>,
+    /// Feed hrefs, when there are dated entries
+    rss: Option<&'a str>,
+    atom: Option<&'a str>,
+    /// Archive and tag index hrefs, when written
+    archive: Option<&'a str>,
+    tags: Option<&'a str>,
+    /// Whether pages contain math for KaTeX to render
+    katex: bool,
 }
 
-.parent-link {
-    margin-bottom: 1rem;
-    font-size: 0.9rem;
+/// A page as templates see it
+#[derive(Serialize)]
+struct PageContext<'a> {
+    #[serde(flatten)]
+    page: &'a PublishedPage,
+    /// Link to the page from anywhere in the output
+    href: String,
+    /// Section ID in single-file output
+    anchor: String,
+    /// Entry date (`YYYY-MM-DD`), see [`site::entry_date`]
+    date: Option<String>,
+    /// Tags, linking to their tag pages (no href in single-file output)
+    tags: Vec<NavLink>,
 }
 
-.parent-link a {
-    color: var(--accent);
+/// An archive or tag page as templates see it
+#[derive(Serialize)]
+struct ListingContext<'a> {
+    title: &'a str,
+    href: &'a str,
+    /// Narrower listings (an archive year's months, the tags of the tag index)
+    links: &'a [NavLink],
+    /// Listed pages, newest first
+    pages: Vec<&'a PageContext<'a>>,
 }
 
-nav.contents {
-    background: var(--code-bg);
-    padding: 1rem;
-    border-radius: 0.5rem;
-    margin-bottom: 1.5rem;
+/// Resolve a link in a workspace file to its workspace-relative canonical
+/// path and its path on the filesystem
+fn resolve_link(
+    parsed: &link_parser::ParsedLink,
+    current_path: &Path,
+    workspace_dir: &Path,
+) -> (String, PathBuf) {
+    let relative = current_path
+        .strip_prefix(workspace_dir)
+        .unwrap_or(current_path);
+    let canonical = link_parser::to_canonical(parsed, relative);
+    let path = workspace_dir.join(&canonical);
+    (canonical, path)
 }
 
-nav.contents h3 {
-    margin-top: 0;
-    margin-bottom: 0.5rem;
-    font-size: 1rem;
+/// Whether any page has math in the KaTeX-ready form
+fn needs_katex(pages: &[PublishedPage]) -> bool {
+    pages
+        .iter()
+        .any(|page| page.html_body.contains(r#"<span class="math "#))
 }
 
-nav.contents ul {
-    margin: 0;
-    padding-left: 1.5rem;
+/// Hash of a value's JSON serialization
+fn hash_json(value: &impl Serialize) -> String {
+    let json = serde_json::to_string(value).unwrap_or_default();
+    crate::cloud::compute_content_hash(json.as_bytes())
 }
 
-nav.contents li {
-    margin: 0.25rem 0;
+/// Manifest key of a source: its path relative to the workspace folder
+fn source_key(workspace_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(workspace_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
 }
 
-nav.toc {
-    background: var(--code-bg);
-    padding: 1.5rem;
-    border-radius: 0.5rem;
-    margin-bottom: 2rem;
-}
-
-nav.toc h2 {
-    margin-top: 0;
-}
-
-nav.toc ul {
-    margin: 0;
-    padding-left: 1.5rem;
-}
-
-nav.toc li {
-    margin: 0.5rem 0;
+/// Escape HTML special characters
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
 }
 
-a {
-    color: var(--accent);
-    text-decoration: none;
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{FileSystem, InMemoryFileSystem, SyncToAsyncFs, block_on_test};
 
-a:hover {
-    color: var(--accent-hover);
-    text-decoration: underline;
-}
+    fn make_workspace() -> InMemoryFileSystem {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\ncontents:\n  - day-one.md\n---\n\nWelcome\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/day-one.md"),
+            "---\ntitle: Day <One>\nauthor: Ann\npart_of: README.md\n---\n\nHello\n",
+        )
+        .unwrap();
+        fs
+    }
 
-.content {
-    margin-top: 1.5rem;
-}
+    fn publish(fs: InMemoryFileSystem, options: PublishOptions) -> InMemoryFileSystem {
+        let publisher = Publisher::new(SyncToAsyncFs::new(fs.clone()));
+        block_on_test(publisher.publish(
+            Path::new("/workspace/README.md"),
+            Path::new("/site"),
+            &options,
+        ))
+        .unwrap();
+        fs
+    }
 
-.content h1, .content h2, .content h3, .content h4, .content h5, .content h6 {
-    margin-top: 2rem;
-    margin-bottom: 0.5rem;
-}
+    #[test]
+    fn test_html_escape() {
+        assert_eq!(html_escape("<script>"), "&lt;script&gt;");
+        assert_eq!(html_escape("a & b"), "a &amp; b");
+        assert_eq!(html_escape(r#"say "hi""#), "say &quot;hi&quot;");
+    }
 
-.content p {
-    margin: 1rem 0;
-}
+    #[test]
+    fn test_publish_with_default_theme() {
+        let fs = publish(make_workspace(), PublishOptions::default());
+
+        let index = fs.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(index.contains("<title>My Journal - My Journal</title>"));
+        assert!(index.contains(r#"<link rel="stylesheet" href="style.css">"#));
+        assert!(
+            index.contains(
+                r#"<nav class="contents"><h3>Contents</h3><ul><li><a href="day-one.html">"#
+            )
+        );
+
+        let page = fs.read_to_string(Path::new("/site/day-one.html")).unwrap();
+        assert!(page.contains(r#"<h1 class="page-title">Day &lt;One&gt;</h1>"#));
+        // Links resolve to the linked pages and use their titles
+        assert!(page.contains(r#"<div class="parent-link">↑ <a href="index.html">My Journal</a>"#));
+        assert!(index.contains(r#"<a href="day-one.html">Day &lt;One&gt;</a>"#));
+        assert!(fs.exists(Path::new("/site/style.css")));
+    }
 
-.content ul, .content ol {
-    margin: 1rem 0;
-    padding-left: 2rem;
-}
+    #[test]
+    fn test_publish_single_file_links_to_sections() {
+        let fs = make_workspace();
+        let publisher = Publisher::new(SyncToAsyncFs::new(fs.clone()));
+        let options = PublishOptions {
+            single_file: true,
+            ..Default::default()
+        };
+        block_on_test(publisher.publish(
+            Path::new("/workspace/README.md"),
+            Path::new("/out/site.html"),
+            &options,
+        ))
+        .unwrap();
+
+        let html = fs.read_to_string(Path::new("/out/site.html")).unwrap();
+        assert!(html.contains("<style>"));
+        assert!(html.contains(r#"<section id="day-one">"#));
+        assert!(html.contains(r##"<ul><li><a href="#my-journal">My Journal</a></li>"##));
+        assert!(html.contains(r##"<div class="parent-link">↑ <a href="#"##));
+    }
 
-.content li {
-    margin: 0.25rem 0;
-}
+    #[test]
+    fn test_publish_redacts_blocks_for_audience() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\naudience: [family]\n---\n\nShared\n\n<!-- audience: private -->\nSecret\n<!-- /audience -->\n",
+        )
+        .unwrap();
+        let fs = publish(
+            fs,
+            PublishOptions {
+                audience: Some("family".to_string()),
+                ..Default::default()
+            },
+        );
+        let index = fs.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(index.contains("Shared"));
+        assert!(!index.contains("Secret"));
+
+        // Without an audience every block is shown, without its markers
+        let fs = publish(
+            fs,
+            PublishOptions {
+                force: true,
+                ..Default::default()
+            },
+        );
+        let index = fs.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(index.contains("Secret"));
+        assert!(!index.contains("audience:"));
+    }
 
-.content pre {
-    background: var(--code-bg);
-    padding: 1rem;
-    border-radius: 0.5rem;
-    overflow-x: auto;
-}
+    #[test]
+    fn test_publish_attachments_and_body_links() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\naudience: [public]\ncontents:\n  - day-one.md\n  - secret.md\n---\n\nStart at [day one](day-one.md#morning) or [the secret](secret.md).\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/day-one.md"),
+            "---\ntitle: Day One\npart_of: README.md\nattachments:\n  - _attachments/notes.pdf\n---\n\n![Photo](_attachments/my%20photo.png) and [home](https://example.com)\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/secret.md"),
+            "---\ntitle: Secret\naudience: [private]\npart_of: README.md\nattachments:\n  - _attachments/hidden.txt\n---\n",
+        )
+        .unwrap();
+        fs.write_binary(Path::new("/workspace/_attachments/notes.pdf"), b"%PDF")
+            .unwrap();
+        fs.write_binary(Path::new("/workspace/_attachments/my photo.png"), b"png")
+            .unwrap();
+        fs.write_binary(Path::new("/workspace/_attachments/hidden.txt"), b"no")
+            .unwrap();
+        let fs = publish(
+            fs,
+            PublishOptions {
+                audience: Some("public".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let index = fs.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(index.contains(r#"<a href="day-one.html#morning">day one</a>"#));
+        // Entries hidden from the audience keep only the link text
+        assert!(index.contains("or the secret."));
+        assert!(!index.contains("secret.md"));
+
+        let page = fs.read_to_string(Path::new("/site/day-one.html")).unwrap();
+        assert!(
+            page.contains(r#"<img src="attachments/_attachments/my%20photo.png" alt="Photo" />"#)
+        );
+        assert!(page.contains(r#"<a href="https://example.com">home</a>"#));
+        assert_eq!(
+            fs.read_binary(Path::new("/site/attachments/_attachments/notes.pdf"))
+                .unwrap(),
+            b"%PDF"
+        );
+        assert!(fs.exists(Path::new("/site/attachments/_attachments/my photo.png")));
+        assert!(!fs.exists(Path::new("/site/attachments/_attachments/hidden.txt")));
+
+        // Single-file output links to sections and copies attachments beside it
+        let publisher = Publisher::new(SyncToAsyncFs::new(fs.clone()));
+        block_on_test(publisher.publish(
+            Path::new("/workspace/README.md"),
+            Path::new("/out/site.html"),
+            &PublishOptions {
+                single_file: true,
+                audience: Some("public".to_string()),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+        let html = fs.read_to_string(Path::new("/out/site.html")).unwrap();
+        assert!(html.contains(r##"<a href="#day-one">day one</a>"##));
+        assert!(fs.exists(Path::new("/out/attachments/_attachments/notes.pdf")));
+    }
 
-.content code {
-    background: var(--code-bg);
-    padding: 0.2rem 0.4rem;
-    border-radius: 0.25rem;
-    font-size: 0.9em;
-}
+    #[test]
+    fn test_publish_ignores_files_outside_workspace() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\nattachments:\n  - ../outside/key.pem\n---\n\nSee [this](../outside/secret.txt) and [that](/../outside/secret.txt).\n",
+        )
+        .unwrap();
+        fs.write_binary(Path::new("/outside/secret.txt"), b"secret")
+            .unwrap();
+        fs.write_binary(Path::new("/outside/key.pem"), b"key")
+            .unwrap();
+        let fs = publish(fs, PublishOptions::default());
+
+        let index = fs.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(index.contains(r#"<a href="../outside/secret.txt">this</a>"#));
+        assert!(!index.contains("attachments/"));
+        assert!(!fs.exists(Path::new("/site/outside/secret.txt")));
+        assert!(!fs.exists(Path::new("/site/outside/key.pem")));
+        assert!(!fs.exists(Path::new("/site/attachments/../outside/secret.txt")));
+
+        assert!(!is_contained("attachments/../../etc/passwd"));
+        assert!(!is_contained("/etc/passwd"));
+        assert!(!is_contained(""));
+        assert!(is_contained("attachments/2024/photo.png"));
+    }
 
-.content pre code {
-    background: none;
-    padding: 0;
-}
+    /// Renders ```dot blocks to a fixed SVG
+    struct DotRenderer;
 
-.content blockquote {
-    border-left: 4px solid var(--border);
-    margin: 1rem 0;
-    padding-left: 1rem;
-    color: var(--text-muted);
-}
+    impl crate::render::CodeRenderer for DotRenderer {
+        fn name(&self) -> &str {
+            "dot"
+        }
 
-.content table {
-    width: 100%;
-    border-collapse: collapse;
-    margin: 1rem 0;
-}
+        fn handles(&self, language: &str) -> bool {
+            language == "dot"
+        }
 
-.content th, .content td {
-    border: 1px solid var(--border);
-    padding: 0.5rem;
-    text-align: left;
-}
+        fn render(&self, _language: &str, source: &str) -> Option<String> {
+            Some(format!("<svg>{}</svg>", source.trim()))
+        }
+    }
 
-.content th {
-    background: var(--code-bg);
-}
+    #[test]
+    fn test_publish_renders_math_and_diagrams() {
+        let fs = make_workspace();
+        fs.write_file(
+            Path::new("/workspace/day-one.md"),
+            "---\ntitle: Day One\npart_of: README.md\n---\n\nEnergy $E = mc^2$ costs $5 and $10.\n\n```math\na < b\n```\n\n```dot\na -> b\n```\n\n```rust\nfn main() {}\n```\n",
+        )
+        .unwrap();
+        let publisher = Publisher::new(SyncToAsyncFs::new(fs.clone()))
+            .with_renderers(RenderPipeline::new().with_renderer(Box::new(DotRenderer)));
+        block_on_test(publisher.publish(
+            Path::new("/workspace/README.md"),
+            Path::new("/site"),
+            &PublishOptions::default(),
+        ))
+        .unwrap();
+
+        let page = fs.read_to_string(Path::new("/site/day-one.html")).unwrap();
+        assert!(
+            page.contains(r#"<span class="math inline">\(E = mc^2\)</span> costs $5 and $10."#)
+        );
+        assert!(page.contains(r#"<p><span class="math display">\[a &lt; b\]</span></p>"#));
+        assert!(page.contains(r#"<code class="language-rust">"#));
+        assert!(page.contains("katex.min.js"));
+        // The CDN scripts are pinned and integrity-checked
+        assert_eq!(page.matches(r#" integrity="sha384-"#).count(), 3);
+
+        let pages = block_on_test(publisher.collect_all(
+            Path::new("/workspace/README.md"),
+            &PublishOptions::default(),
+        ))
+        .unwrap();
+        let diagram = &pages[1].diagrams[0];
+        assert!(page.contains(&diagram.html()));
+        assert_eq!(
+            fs.read_to_string(&Path::new("/site").join(diagram.path()))
+                .unwrap(),
+            "<svg>a -> b</svg>"
+        );
+        // KaTeX is only loaded when some page has math
+        let index = fs.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(index.contains("katex.min.js"));
+        let plain = publish(make_workspace(), PublishOptions::default());
+        let index = plain.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(!index.contains("katex"));
+    }
 
-.content img {
-    max-width: 100%;
-    height: auto;
-}
+    #[test]
+    fn test_publish_feeds_archives_and_tags() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\ncontents:\n  - 2024/03/2024-03-02.md\n  - about.md\n---\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/2024/03/2024-03-02.md"),
+            "---\ntitle: Spring\ntags: [garden]\npart_of: ../../README.md\n---\n\nBlooms\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/about.md"),
+            "---\ntitle: About\ntags: [Garden, me]\npart_of: README.md\n---\n",
+        )
+        .unwrap();
+        let fs = publish(
+            fs,
+            PublishOptions {
+                base_url: Some("https://example.com/".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let rss = fs.read_to_string(Path::new("/site/rss.xml")).unwrap();
+        assert!(rss.contains("<link>https://example.com/2024-03-02.html</link>"));
+        assert!(!rss.contains("about.html"));
+        assert!(fs.exists(Path::new("/site/atom.xml")));
+        let sitemap = fs.read_to_string(Path::new("/site/sitemap.xml")).unwrap();
+        assert!(sitemap.contains("<loc>https://example.com/about.html</loc>"));
+        assert!(sitemap.contains("<loc>https://example.com/archive-2024-03.html</loc>"));
+
+        let month = fs
+            .read_to_string(Path::new("/site/archive-2024-03.html"))
+            .unwrap();
+        assert!(month.contains("<title>March 2024 - My Journal</title>"));
+        assert!(month.contains(
+            r#"<li><time datetime="2024-03-02">2024-03-02</time> <a href="2024-03-02.html">Spring</a></li>"#
+        ));
+        let garden = fs
+            .read_to_string(Path::new("/site/tag-garden.html"))
+            .unwrap();
+        assert!(
+            garden
+                .contains(r#"<a href="2024-03-02.html">Spring</a></li><li><a href="about.html">"#)
+        );
+
+        let page = fs
+            .read_to_string(Path::new("/site/2024-03-02.html"))
+            .unwrap();
+        assert!(page.contains(r#"<a class="tag" href="tag-garden.html">#garden</a>"#));
+        assert!(page.contains(r#"<a href="archive.html">Archive</a>"#));
+        assert!(page.contains(r#"type="application/atom+xml""#));
+    }
 
-hr {
-    border: none;
-    border-top: 1px solid var(--border);
-    margin: 3rem 0;
-}
+    #[test]
+    fn test_publish_without_dates_skips_feeds() {
+        let fs = publish(
+            make_workspace(),
+            PublishOptions {
+                base_url: Some("https://example.com".to_string()),
+                ..Default::default()
+            },
+        );
+        assert!(!fs.exists(Path::new("/site/rss.xml")));
+        assert!(!fs.exists(Path::new("/site/archive.html")));
+        assert!(!fs.exists(Path::new("/site/tags.html")));
+        let sitemap = fs.read_to_string(Path::new("/site/sitemap.xml")).unwrap();
+        assert!(sitemap.contains("<url><loc>https://example.com/day-one.html</loc></url>"));
+    }
 
-section {
-    margin-bottom: 2rem;
-}
+    #[test]
+    fn test_publish_without_base_url_skips_sitemap_and_feeds() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\ncontents:\n  - 2024/03/2024-03-02.md\n---\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/2024/03/2024-03-02.md"),
+            "---\ntitle: Spring\npart_of: ../../README.md\n---\n\nBlooms\n",
+        )
+        .unwrap();
+        let fs = publish(fs, PublishOptions::default());
+        assert!(!fs.exists(Path::new("/site/sitemap.xml")));
+        assert!(!fs.exists(Path::new("/site/rss.xml")));
+        assert!(!fs.exists(Path::new("/site/atom.xml")));
+        assert!(fs.exists(Path::new("/site/archive.html")));
+        let page = fs
+            .read_to_string(Path::new("/site/2024-03-02.html"))
+            .unwrap();
+        assert!(!page.contains("application/atom+xml"));
+    }
 
-footer {
-    margin-top: 3rem;
-    padding-top: 1rem;
-    border-top: 1px solid var(--border);
-    color: var(--text-muted);
-    font-size: 0.9rem;
-}
+    #[test]
+    fn test_incremental_publish() {
+        let fs = make_workspace();
+        fs.write_file(
+            Path::new("/workspace/notes.md"),
+            "---\ntitle: Notes\npart_of: README.md\n---\n\nSome notes\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\ncontents:\n  - day-one.md\n  - notes.md\n---\n\nWelcome\n",
+        )
+        .unwrap();
+        let publisher = Publisher::new(SyncToAsyncFs::new(fs.clone()));
+        let run = |options: &PublishOptions| {
+            block_on_test(publisher.publish(
+                Path::new("/workspace/README.md"),
+                Path::new("/site"),
+                options,
+            ))
+            .unwrap()
+        };
+        let options = PublishOptions::default();
 
-footer a {
-    color: var(--text-muted);
-}
+        assert_eq!(run(&options).pages_rendered, 3);
+        assert!(fs.exists(Path::new("/site/.diaryx-publish.json")));
+        assert_eq!(run(&options).pages_rendered, 0);
 
-footer a:hover {
-    color: var(--accent);
-}
+        // A body edit only re-renders that page
+        fs.write_file(
+            Path::new("/workspace/notes.md"),
+            "---\ntitle: Notes\npart_of: README.md\n---\n\nMore notes\n",
+        )
+        .unwrap();
+        assert_eq!(run(&options).pages_rendered, 1);
+        assert!(
+            fs.read_to_string(Path::new("/site/notes.html"))
+                .unwrap()
+                .contains("More notes")
+        );
+
+        // A new title also re-renders the pages linking to it
+        fs.write_file(
+            Path::new("/workspace/notes.md"),
+            "---\ntitle: Field Notes\npart_of: README.md\n---\n\nMore notes\n",
+        )
+        .unwrap();
+        assert_eq!(run(&options).pages_rendered, 2);
+        assert!(
+            fs.read_to_string(Path::new("/site/index.html"))
+                .unwrap()
+                .contains("Field Notes")
+        );
+
+        // A deleted output is written again
+        fs.delete_file(Path::new("/site/day-one.html")).unwrap();
+        assert_eq!(run(&options).pages_rendered, 1);
+
+        // Deleting a source removes its page
+        fs.delete_file(Path::new("/workspace/notes.md")).unwrap();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\ncontents:\n  - day-one.md\n---\n\nWelcome\n",
+        )
+        .unwrap();
+        let result = run(&options);
+        assert_eq!((result.pages_rendered, result.files_removed), (1, 1));
+        assert!(!fs.exists(Path::new("/site/notes.html")));
+
+        // Options every page depends on, or force, re-render everything
+        let titled = PublishOptions {
+            title: Some("Site".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(run(&titled).pages_rendered, 2);
+        let forced = PublishOptions {
+            force: true,
+            ..titled
+        };
+        assert_eq!(run(&forced).pages_rendered, 2);
 
-@media (max-width: 600px) {
-    html {
-        font-size: 14px;
+        // Forced publishes still remove the output of deleted sources
+        fs.delete_file(Path::new("/workspace/day-one.md")).unwrap();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\n---\n\nWelcome\n",
+        )
+        .unwrap();
+        let result = run(&forced);
+        assert_eq!(result.pages_rendered, 1);
+        assert!(result.files_removed >= 1);
+        assert!(!fs.exists(Path::new("/site/day-one.html")));
     }
 
-    body {
-        padding: 1rem;
+    #[test]
+    fn test_stale_outputs_stay_inside_destination() {
+        let fs = make_workspace();
+        fs.write_file(Path::new("/workspace/keep.txt"), "keep")
+            .unwrap();
+        let publisher = Publisher::new(SyncToAsyncFs::new(fs.clone()));
+        let run = || {
+            block_on_test(publisher.publish(
+                Path::new("/workspace/README.md"),
+                Path::new("/site"),
+                &PublishOptions::default(),
+            ))
+            .unwrap()
+        };
+        run();
+
+        // A tampered manifest can't delete files outside the destination
+        let manifest_path = Path::new("/site/.diaryx-publish.json");
+        let mut manifest: serde_json::Value =
+            serde_json::from_str(&fs.read_to_string(manifest_path).unwrap()).unwrap();
+        manifest["generated"]
+            .as_array_mut()
+            .unwrap()
+            .extend(["../workspace/keep.txt".into(), "/workspace/keep.txt".into()]);
+        fs.write_file(manifest_path, &manifest.to_string()).unwrap();
+
+        assert_eq!(run().files_removed, 0);
+        assert!(fs.exists(Path::new("/workspace/keep.txt")));
     }
 
-    .page-title {
-        font-size: 1.5rem;
-    }
-}
-"#
+    #[test]
+    fn test_publish_with_workspace_theme() {
+        let fs = make_workspace();
+        fs.write_file(
+            Path::new("/workspace/_theme/page.html"),
+            "{{ page.title }} by {{ page.frontmatter.author }} ({{ pages | length }} pages)",
+        )
+        .unwrap();
+        fs.write_file(Path::new("/workspace/_theme/assets/site.js"), "// js")
+            .unwrap();
+        let fs = publish(fs, PublishOptions::default());
+
+        assert_eq!(
+            fs.read_to_string(Path::new("/site/day-one.html")).unwrap(),
+            "Day &lt;One&gt; by Ann (2 pages)"
+        );
+        // The root page still uses the default index template
+        let index = fs.read_to_string(Path::new("/site/index.html")).unwrap();
+        assert!(index.starts_with("My Journal by"));
+        assert!(fs.exists(Path::new("/site/assets/site.js")));
+
+        // An explicit built-in theme wins over the workspace folder
+        let fs = publish(
+            fs,
+            PublishOptions {
+                theme: Some("minimal".to_string()),
+                ..Default::default()
+            },
+        );
+        let page = fs.read_to_string(Path::new("/site/day-one.html")).unwrap();
+        assert!(page.starts_with("<!DOCTYPE html>"));
     }
-}
-
-/// Escape HTML special characters
-fn html_escape(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&#39;")
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
+    #[cfg(feature = "epub")]
     #[test]
-    fn test_html_escape() {
-        assert_eq!(html_escape("<script>"), "&lt;script&gt;");
-        assert_eq!(html_escape("a & b"), "a &amp; b");
-        assert_eq!(html_escape(r#"say "hi""#), "say &quot;hi&quot;");
+    fn test_epub_embeds_pages_and_attachments() {
+        use std::io::Read;
+
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/README.md"),
+            "---\ntitle: My Journal\nauthor: Ann\naudience: [public]\ncontents:\n  - day-one.md\n  - secret.md\n---\n\nWelcome\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/day-one.md"),
+            "---\ntitle: Day One\npart_of: README.md\n---\n\n![Photo](photo.png)\n",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/secret.md"),
+            "---\ntitle: Secret\naudience: [private]\npart_of: README.md\n---\n",
+        )
+        .unwrap();
+        fs.write_binary(Path::new("/workspace/photo.png"), b"png")
+            .unwrap();
+
+        let publisher = Publisher::new(SyncToAsyncFs::new(fs));
+        let bytes = block_on_test(publisher.epub(
+            Path::new("/workspace/README.md"),
+            &PublishOptions {
+                audience: Some("public".to_string()),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut opf = String::new();
+        archive
+            .by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut opf)
+            .unwrap();
+        assert!(opf.contains("<dc:title>My Journal</dc:title>"));
+        assert!(opf.contains("<dc:creator>Ann</dc:creator>"));
+        assert!(archive.by_name("OEBPS/index.html").is_ok());
+        assert!(archive.by_name("OEBPS/day-one.html").is_ok());
+        assert!(archive.by_name("OEBPS/secret.html").is_err());
+
+        let mut photo = Vec::new();
+        archive
+            .by_name("OEBPS/attachments/photo.png")
+            .unwrap()
+            .read_to_end(&mut photo)
+            .unwrap();
+        assert_eq!(photo, b"png");
     }
 }
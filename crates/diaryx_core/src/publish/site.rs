@@ -0,0 +1,549 @@
+//! Static-site extras for multi-file publishing.
+//!
+//! Besides the pages themselves, a multi-file site gets:
+//!
+//! - `sitemap.xml` listing every page
+//! - `rss.xml` and `atom.xml` feeds of the most recent dated entries
+//! - `archive.html` with year (`archive-2024.html`) and month
+//!   (`archive-2024-01.html`) pages
+//! - `tags.html` with a page per tag (`tag-travel.html`)
+//!
+//! Entries are dated by their daily-entry path (`YYYY/MM/YYYY-MM-DD.md`) or,
+//! failing that, their `created` property; tags come from the `tags` property.
+//! Feeds and archives are only written when there are dated entries, and tag
+//! pages when there are tags. Feeds and the sitemap need absolute links, so
+//! they're only written with a
+//! [`PublishOptions::base_url`](super::PublishOptions::base_url); links in the
+//! feed content are made absolute too.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+use indexmap::IndexMap;
+use serde_yaml::Value;
+
+use super::{NavLink, PublishedPage, html_escape};
+use crate::entry::slugify;
+use crate::frontmatter;
+use crate::utils::date::path_to_date;
+
+/// Sitemap file name
+pub const SITEMAP_FILE: &str = "sitemap.xml";
+/// RSS 2.0 feed file name
+pub const RSS_FILE: &str = "rss.xml";
+/// Atom feed file name
+pub const ATOM_FILE: &str = "atom.xml";
+/// Archive index page
+pub const ARCHIVE_FILE: &str = "archive.html";
+/// Tag index page
+pub const TAGS_FILE: &str = "tags.html";
+
+/// Most recent entries included in the feeds
+const FEED_LIMIT: usize = 50;
+
+/// A generated page listing other pages (archives and tags)
+#[derive(Debug, Clone)]
+pub(crate) struct Listing {
+    pub dest_filename: String,
+    pub title: String,
+    /// Links to narrower listings (years -> months, tag index -> tags)
+    pub links: Vec<NavLink>,
+    /// Indexes of the listed pages, newest first
+    pub pages: Vec<usize>,
+}
+
+/// Date of an entry.
+///
+/// Daily entries are dated by their path, using a `created` timestamp from
+/// the same day for the time. Other pages use `created`.
+pub(crate) fn entry_date(page: &PublishedPage) -> Option<DateTime<FixedOffset>> {
+    date_of(&page.frontmatter, &page.source_path)
+}
+
+/// Date of the entry at `path` with this frontmatter (see [`entry_date`])
+pub(crate) fn date_of(
+    frontmatter: &IndexMap<String, Value>,
+    path: &Path,
+) -> Option<DateTime<FixedOffset>> {
+    let created = frontmatter::get_string(frontmatter, "created").and_then(parse_date);
+    match path_to_date(path) {
+        Some(date) => Some(
+            created
+                .filter(|c| c.date_naive() == date)
+                .unwrap_or_else(|| midnight(date)),
+        ),
+        None => created,
+    }
+}
+
+fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value).ok().or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .map(midnight)
+    })
+}
+
+fn midnight(date: NaiveDate) -> DateTime<FixedOffset> {
+    date.and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+        .fixed_offset()
+}
+
+/// Tags of a page, from its `tags` property
+pub(crate) fn page_tags(page: &PublishedPage) -> Vec<String> {
+    tags_of(&page.frontmatter)
+}
+
+/// Tags in a frontmatter's `tags` property
+pub(crate) fn tags_of(frontmatter: &IndexMap<String, Value>) -> Vec<String> {
+    frontmatter::get_string_array(frontmatter, "tags")
+        .into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !slugify(tag).is_empty())
+        .collect()
+}
+
+/// Output file of a tag's page
+pub(crate) fn tag_filename(tag: &str) -> String {
+    format!("tag-{}.html", slugify(tag))
+}
+
+/// `href` (relative to the site root) joined to `base_url`
+pub(crate) fn absolute_url(base_url: &str, href: &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), href)
+}
+
+/// `href` as found on the page at `page_url`, made absolute
+fn resolve_url(page_url: &str, href: &str) -> String {
+    let has_scheme = href.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if href.is_empty() || has_scheme || href.starts_with("//") {
+        href.to_string()
+    } else if href.starts_with('#') {
+        format!("{}{}", page_url, href)
+    } else if href.starts_with('/') {
+        // Root-relative: keep the scheme and host only
+        let host_start = page_url.find("://").map_or(0, |i| i + 3);
+        let origin_end = page_url[host_start..]
+            .find('/')
+            .map_or(page_url.len(), |i| host_start + i);
+        format!("{}{}", &page_url[..origin_end], href)
+    } else {
+        let dir_end = page_url.rfind('/').map_or(0, |i| i + 1);
+        format!("{}{}", &page_url[..dir_end], href)
+    }
+}
+
+/// A page's HTML with its `href` and `src` links made absolute, since feed
+/// readers show it away from the site
+fn absolute_links(html: &str, page_url: &str) -> String {
+    let mut html = html.to_string();
+    for attr in [" href=\"", " src=\""] {
+        let mut resolved = String::with_capacity(html.len());
+        let mut rest = html.as_str();
+        while let Some(start) = rest.find(attr) {
+            let value_start = start + attr.len();
+            let Some(len) = rest[value_start..].find('"') else {
+                break;
+            };
+            resolved.push_str(&rest[..value_start]);
+            resolved.push_str(&resolve_url(
+                page_url,
+                &rest[value_start..value_start + len],
+            ));
+            rest = &rest[value_start + len..];
+        }
+        resolved.push_str(rest);
+        html = resolved;
+    }
+    html
+}
+
+/// Indexes of dated pages, newest first
+fn newest_first(dates: &[Option<DateTime<FixedOffset>>]) -> Vec<usize> {
+    let mut dated: Vec<usize> = (0..dates.len()).filter(|&i| dates[i].is_some()).collect();
+    dated.sort_by(|&a, &b| dates[b].cmp(&dates[a]));
+    dated
+}
+
+/// Archive index, year and month pages
+pub(crate) fn archives(dates: &[Option<DateTime<FixedOffset>>]) -> Vec<Listing> {
+    let mut years: BTreeMap<i32, BTreeMap<u32, Vec<usize>>> = BTreeMap::new();
+    for index in newest_first(dates) {
+        let Some(date) = dates[index] else { continue };
+        years
+            .entry(date.year())
+            .or_default()
+            .entry(date.month())
+            .or_default()
+            .push(index);
+    }
+    if years.is_empty() {
+        return Vec::new();
+    }
+
+    let mut listings = vec![Listing {
+        dest_filename: ARCHIVE_FILE.to_string(),
+        title: "Archive".to_string(),
+        links: Vec::new(),
+        pages: Vec::new(),
+    }];
+    for (year, months) in years.into_iter().rev() {
+        let year_file = format!("archive-{}.html", year);
+        let mut year_listing = Listing {
+            dest_filename: year_file.clone(),
+            title: year.to_string(),
+            links: Vec::new(),
+            pages: Vec::new(),
+        };
+        let mut month_listings = Vec::new();
+        for (month, pages) in months.into_iter().rev() {
+            let title = NaiveDate::from_ymd_opt(year, month, 1)
+                .map(|d| d.format("%B %Y").to_string())
+                .unwrap_or_else(|| format!("{}-{:02}", year, month));
+            let month_file = format!("archive-{}-{:02}.html", year, month);
+            year_listing.links.push(NavLink {
+                href: month_file.clone(),
+                title: format!("{} ({})", title, pages.len()),
+            });
+            year_listing.pages.extend(&pages);
+            month_listings.push(Listing {
+                dest_filename: month_file,
+                title,
+                links: Vec::new(),
+                pages,
+            });
+        }
+        listings[0].links.push(NavLink {
+            href: year_file,
+            title: format!("{} ({})", year, year_listing.pages.len()),
+        });
+        listings.push(year_listing);
+        listings.extend(month_listings);
+    }
+    listings
+}
+
+/// Tag index and one page per tag (tags differing only in case or
+/// punctuation share a page)
+pub(crate) fn tag_pages(
+    pages: &[PublishedPage],
+    dates: &[Option<DateTime<FixedOffset>>],
+) -> Vec<Listing> {
+    // Dated entries newest first, then undated pages in publish order
+    let mut order = newest_first(dates);
+    order.extend((0..pages.len()).filter(|&i| dates[i].is_none()));
+
+    let mut tags: BTreeMap<String, Listing> = BTreeMap::new();
+    for index in order {
+        for tag in page_tags(&pages[index]) {
+            let listing = tags.entry(slugify(&tag)).or_insert_with(|| Listing {
+                dest_filename: tag_filename(&tag),
+                title: format!("#{}", tag),
+                links: Vec::new(),
+                pages: Vec::new(),
+            });
+            if !listing.pages.contains(&index) {
+                listing.pages.push(index);
+            }
+        }
+    }
+    if tags.is_empty() {
+        return Vec::new();
+    }
+
+    let index = Listing {
+        dest_filename: TAGS_FILE.to_string(),
+        title: "Tags".to_string(),
+        links: tags
+            .values()
+            .map(|tag| NavLink {
+                href: tag.dest_filename.clone(),
+                title: format!("{} ({})", tag.title, tag.pages.len()),
+            })
+            .collect(),
+        pages: Vec::new(),
+    };
+    std::iter::once(index).chain(tags.into_values()).collect()
+}
+
+/// RSS 2.0 feed of the most recent dated entries
+pub(crate) fn rss(
+    site_title: &str,
+    base_url: &str,
+    pages: &[PublishedPage],
+    dates: &[Option<DateTime<FixedOffset>>],
+) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n");
+    xml.push_str(&format!("  <title>{}</title>\n", html_escape(site_title)));
+    xml.push_str(&format!(
+        "  <link>{}</link>\n",
+        html_escape(&absolute_url(base_url, "index.html"))
+    ));
+    xml.push_str(&format!(
+        "  <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        html_escape(&absolute_url(base_url, RSS_FILE))
+    ));
+    xml.push_str(&format!(
+        "  <description>{}</description>\n",
+        html_escape(site_title)
+    ));
+    for index in newest_first(dates).into_iter().take(FEED_LIMIT) {
+        let (page, Some(date)) = (&pages[index], dates[index]) else {
+            continue;
+        };
+        let page_url = absolute_url(base_url, &page.dest_filename);
+        let url = html_escape(&page_url);
+        xml.push_str("  <item>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            html_escape(&page.title)
+        ));
+        xml.push_str(&format!("    <link>{}</link>\n", url));
+        xml.push_str(&format!("    <guid>{}</guid>\n", url));
+        xml.push_str(&format!("    <pubDate>{}</pubDate>\n", date.to_rfc2822()));
+        for tag in page_tags(page) {
+            xml.push_str(&format!("    <category>{}</category>\n", html_escape(&tag)));
+        }
+        xml.push_str(&format!(
+            "    <description>{}</description>\n",
+            html_escape(&absolute_links(&page.html_body, &page_url))
+        ));
+        xml.push_str("  </item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+/// Atom feed of the most recent dated entries
+pub(crate) fn atom(
+    site_title: &str,
+    base_url: &str,
+    pages: &[PublishedPage],
+    dates: &[Option<DateTime<FixedOffset>>],
+) -> String {
+    let entries: Vec<usize> = newest_first(dates).into_iter().take(FEED_LIMIT).collect();
+    let updated = entries
+        .first()
+        .and_then(|&i| dates[i])
+        .unwrap_or_else(|| Utc::now().fixed_offset());
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", html_escape(site_title)));
+    xml.push_str(&format!(
+        "  <id>{}</id>\n",
+        html_escape(&absolute_url(base_url, "index.html"))
+    ));
+    xml.push_str(&format!(
+        "  <link href=\"{}\"/>\n",
+        html_escape(&absolute_url(base_url, "index.html"))
+    ));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"self\"/>\n",
+        html_escape(&absolute_url(base_url, ATOM_FILE))
+    ));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated.to_rfc3339()));
+    for index in entries {
+        let (page, Some(date)) = (&pages[index], dates[index]) else {
+            continue;
+        };
+        let page_url = absolute_url(base_url, &page.dest_filename);
+        let url = html_escape(&page_url);
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            html_escape(&page.title)
+        ));
+        xml.push_str(&format!("    <id>{}</id>\n", url));
+        xml.push_str(&format!("    <link href=\"{}\"/>\n", url));
+        xml.push_str(&format!("    <updated>{}</updated>\n", date.to_rfc3339()));
+        if let Some(author) = frontmatter::get_string(&page.frontmatter, "author") {
+            xml.push_str(&format!(
+                "    <author><name>{}</name></author>\n",
+                html_escape(author)
+            ));
+        }
+        for tag in page_tags(page) {
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", html_escape(&tag)));
+        }
+        xml.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            html_escape(&absolute_links(&page.html_body, &page_url))
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Sitemap of the pages and generated listings
+pub(crate) fn sitemap(
+    base_url: &str,
+    pages: &[PublishedPage],
+    dates: &[Option<DateTime<FixedOffset>>],
+    listings: &[Listing],
+) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for (page, date) in pages.iter().zip(dates) {
+        xml.push_str(&format!(
+            "  <url><loc>{}</loc>",
+            html_escape(&absolute_url(base_url, &page.dest_filename))
+        ));
+        if let Some(date) = date {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", date.format("%Y-%m-%d")));
+        }
+        xml.push_str("</url>\n");
+    }
+    for listing in listings {
+        xml.push_str(&format!(
+            "  <url><loc>{}</loc></url>\n",
+            html_escape(&absolute_url(base_url, &listing.dest_filename))
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+    use std::path::{Path, PathBuf};
+
+    fn page(path: &str, frontmatter: &str) -> PublishedPage {
+        PublishedPage {
+            source_path: PathBuf::from(path),
+            dest_filename: format!(
+                "{}.html",
+                slugify(Path::new(path).file_stem().unwrap().to_str().unwrap())
+            ),
+            title: path.to_string(),
+            html_body: "<p>Hi & bye</p>".to_string(),
+            markdown_body: String::new(),
+            contents_links: Vec::new(),
+            parent_link: None,
+            is_root: false,
+            frontmatter: serde_yaml::from_str::<Option<IndexMap<String, serde_yaml::Value>>>(
+                frontmatter,
+            )
+            .unwrap()
+            .unwrap_or_default(),
+            attachments: Vec::new(),
+            linked_files: Vec::new(),
+            diagrams: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_entry_date_prefers_path_and_uses_created_time() {
+        let daily = page(
+            "/w/2024/01/2024-01-15.md",
+            "created: 2024-01-15T08:30:00+02:00",
+        );
+        assert_eq!(
+            entry_date(&daily).unwrap().to_rfc3339(),
+            "2024-01-15T08:30:00+02:00"
+        );
+        // Created on another day: the path date wins
+        let ahead = page("/w/2024/01/2024-01-16.md", "created: 2024-01-15T08:30:00Z");
+        assert_eq!(
+            entry_date(&ahead).unwrap().to_rfc3339(),
+            "2024-01-16T00:00:00+00:00"
+        );
+        let note = page("/w/note.md", "created: 2023-05-01");
+        assert_eq!(
+            entry_date(&note).unwrap().date_naive().to_string(),
+            "2023-05-01"
+        );
+        assert!(entry_date(&page("/w/about.md", "title: About")).is_none());
+    }
+
+    #[test]
+    fn test_archives_group_by_year_and_month() {
+        let dates = vec![
+            None,
+            parse_date("2024-01-15"),
+            parse_date("2024-02-01"),
+            parse_date("2023-12-31"),
+        ];
+        let listings = archives(&dates);
+        let files: Vec<&str> = listings.iter().map(|l| l.dest_filename.as_str()).collect();
+        assert_eq!(
+            files,
+            vec![
+                "archive.html",
+                "archive-2024.html",
+                "archive-2024-02.html",
+                "archive-2024-01.html",
+                "archive-2023.html",
+                "archive-2023-12.html",
+            ]
+        );
+        assert_eq!(listings[0].links[0].title, "2024 (2)");
+        assert_eq!(listings[1].pages, vec![2, 1]);
+        assert_eq!(listings[2].title, "February 2024");
+        assert!(archives(&[None]).is_empty());
+    }
+
+    #[test]
+    fn test_tag_pages_merge_equivalent_tags() {
+        let pages = vec![
+            page("/w/a.md", "tags: [Travel, food]"),
+            page("/w/b.md", "tags: [travel]"),
+        ];
+        let listings = tag_pages(&pages, &[None, None]);
+        assert_eq!(listings[0].dest_filename, TAGS_FILE);
+        assert_eq!(listings[0].links.len(), 2);
+        let travel = listings
+            .iter()
+            .find(|l| l.dest_filename == "tag-travel.html")
+            .unwrap();
+        assert_eq!(travel.pages, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_feeds_use_base_url() {
+        let pages = vec![page("/w/2024/01/2024-01-15.md", "tags: [life]")];
+        let dates = vec![entry_date(&pages[0])];
+        let rss = rss("Site", "https://example.com/blog/", &pages, &dates);
+        assert!(rss.contains("<link>https://example.com/blog/2024-01-15.html</link>"));
+        assert!(rss.contains("<pubDate>Mon, 15 Jan 2024 00:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<description>&lt;p&gt;Hi &amp; bye&lt;/p&gt;</description>"));
+
+        let atom = atom("Site", "https://example.com", &pages, &dates);
+        assert!(atom.contains("<link href=\"https://example.com/atom.xml\" rel=\"self\"/>"));
+        assert!(atom.contains("<category term=\"life\"/>"));
+
+        let map = sitemap("https://example.com", &pages, &dates, &[]);
+        assert!(map.contains(
+            "<url><loc>https://example.com/2024-01-15.html</loc><lastmod>2024-01-15</lastmod></url>"
+        ));
+    }
+
+    #[test]
+    fn test_feed_content_links_are_absolute() {
+        let page_url = "https://example.com/blog/2024/entry.html";
+        let html = concat!(
+            "<a href=\"other.html\">a</a> <a href=\"#notes\">b</a> ",
+            "<a href=\"/about.html\">c</a> <img src=\"../img/cat.png\"> ",
+            "<a href=\"https://elsewhere.org/\">d</a> <a href=\"mailto:me@example.com\">e</a>"
+        );
+        let resolved = absolute_links(html, page_url);
+        assert!(resolved.contains("href=\"https://example.com/blog/2024/other.html\""));
+        assert!(resolved.contains("href=\"https://example.com/blog/2024/entry.html#notes\""));
+        assert!(resolved.contains("href=\"https://example.com/about.html\""));
+        assert!(resolved.contains("src=\"https://example.com/blog/2024/../img/cat.png\""));
+        assert!(resolved.contains("href=\"https://elsewhere.org/\""));
+        assert!(resolved.contains("href=\"mailto:me@example.com\""));
+    }
+}
@@ -0,0 +1,402 @@
+//! Themes for HTML publishing.
+//!
+//! A theme is a set of [minijinja](https://docs.rs/minijinja) templates (Jinja
+//! syntax), a stylesheet and optional static assets:
+//!
+//! ```text
+//! _theme/
+//! ├── base.html          # layout the other templates extend
+//! ├── page.html          # each page of a multi-file site
+//! ├── index.html         # the root page of a multi-file site
+//! ├── single.html        # the whole site in single-file mode
+//! ├── list.html          # archive and tag pages of a multi-file site
+//! ├── partials/*.html    # snippets included with {% include "partials/nav.html" %}
+//! ├── style.css          # written to style.css (inlined in single-file mode)
+//! └── assets/**          # copied to assets/ in the output
+//! ```
+//!
+//! Every file is optional: anything a workspace theme leaves out comes from the
+//! built-in `default` theme. Templates see:
+//!
+//! - `site`: `title`, `stylesheet` (href, multi-file) or `css` (inline,
+//!   single-file), `theme` (the theme name), `base_url`, and the hrefs of the
+//!   `rss`/`atom` feeds and `archive`/`tags` indexes when they were written
+//! - `page`: the current [`PublishedPage`](super::PublishedPage) fields, its
+//!   `frontmatter` (any property, e.g. `page.frontmatter.author`), `href`,
+//!   `anchor`, `date` and `tags` (links to tag pages)
+//! - `listing` (in `list.html`): `title`, `href`, `links` to narrower listings
+//!   and the listed `pages`
+//! - `pages`: every page in publish order, with the same fields
+//!
+//! Output is HTML-escaped; use `{{ page.html_body | safe }}` for the rendered
+//! markdown.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use minijinja::{AutoEscape, Environment};
+use serde::Serialize;
+
+use crate::error::{DiaryxError, Result};
+use crate::fs::AsyncFileSystem;
+
+/// Workspace folder (next to the root index) holding a custom theme
+pub const THEME_DIR: &str = "_theme";
+
+/// Names of the built-in themes
+pub const BUILTIN_THEMES: &[&str] = &["default", "minimal"];
+
+/// Template for each non-root page of a multi-file site
+pub const PAGE_TEMPLATE: &str = "page.html";
+/// Template for the root page of a multi-file site
+pub const INDEX_TEMPLATE: &str = "index.html";
+/// Template for single-file output
+pub const SINGLE_TEMPLATE: &str = "single.html";
+/// Template for archive and tag pages of a multi-file site
+pub const LIST_TEMPLATE: &str = "list.html";
+
+/// Stylesheet file name, in a theme folder and in the output
+pub const STYLESHEET: &str = "style.css";
+/// Folder of included templates
+const PARTIALS_DIR: &str = "partials";
+/// Folder of files copied as-is to the output
+pub const ASSETS_DIR: &str = "assets";
+
+/// Templates of the built-in `default` theme, which other themes fall back to
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    ("base.html", include_str!("themes/default/base.html")),
+    (PAGE_TEMPLATE, include_str!("themes/default/page.html")),
+    (INDEX_TEMPLATE, include_str!("themes/default/index.html")),
+    (SINGLE_TEMPLATE, include_str!("themes/default/single.html")),
+    (LIST_TEMPLATE, include_str!("themes/default/list.html")),
+    (
+        "partials/head.html",
+        include_str!("themes/default/partials/head.html"),
+    ),
+    (
+        "partials/nav.html",
+        include_str!("themes/default/partials/nav.html"),
+    ),
+    (
+        "partials/footer.html",
+        include_str!("themes/default/partials/footer.html"),
+    ),
+];
+
+/// A publishing theme: templates, stylesheet and assets
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Built-in name, or the folder name of a workspace theme
+    pub name: String,
+    /// Template name (e.g. `page.html`, `partials/nav.html`) -> source
+    templates: BTreeMap<String, String>,
+    /// Stylesheet contents
+    stylesheet: String,
+    /// Asset path relative to `assets/` -> contents
+    assets: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            templates: DEFAULT_TEMPLATES
+                .iter()
+                .map(|(name, source)| (name.to_string(), source.to_string()))
+                .collect(),
+            stylesheet: include_str!("themes/default/style.css").to_string(),
+            assets: BTreeMap::new(),
+        }
+    }
+}
+
+impl Theme {
+    /// Get a built-in theme by name (see [`BUILTIN_THEMES`])
+    pub fn builtin(name: &str) -> Option<Self> {
+        let stylesheet = match name {
+            "default" => return Some(Self::default()),
+            "minimal" => include_str!("themes/minimal/style.css"),
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            stylesheet: stylesheet.to_string(),
+            ..Self::default()
+        })
+    }
+
+    /// Load a theme folder, falling back to the `default` theme for any
+    /// template or stylesheet it doesn't provide.
+    pub async fn load<FS: AsyncFileSystem>(fs: &FS, dir: &Path) -> Result<Self> {
+        if !fs.is_dir(dir).await {
+            return Err(DiaryxError::ThemeNotFound(dir.display().to_string()));
+        }
+
+        let mut theme = Self {
+            name: dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(THEME_DIR)
+                .to_string(),
+            ..Self::default()
+        };
+
+        for path in fs.list_files(dir).await? {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name == STYLESHEET {
+                theme.stylesheet = read_theme_file(fs, &path).await?;
+            } else if name.ends_with(".html") && !fs.is_dir(&path).await {
+                let source = read_theme_file(fs, &path).await?;
+                theme.templates.insert(name.to_string(), source);
+            }
+        }
+
+        let partials = dir.join(PARTIALS_DIR);
+        if fs.is_dir(&partials).await {
+            for path in fs.list_files(&partials).await? {
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if name.ends_with(".html") && !fs.is_dir(&path).await {
+                    let source = read_theme_file(fs, &path).await?;
+                    theme
+                        .templates
+                        .insert(format!("{}/{}", PARTIALS_DIR, name), source);
+                }
+            }
+        }
+
+        let assets = dir.join(ASSETS_DIR);
+        if fs.is_dir(&assets).await {
+            for path in fs.list_all_files_recursive(&assets).await? {
+                if fs.is_dir(&path).await {
+                    continue;
+                }
+                let Ok(relative) = path.strip_prefix(&assets) else {
+                    continue;
+                };
+                let contents = fs
+                    .read_binary(&path)
+                    .await
+                    .map_err(|e| DiaryxError::FileRead {
+                        path: path.clone(),
+                        source: e,
+                    })?;
+                theme.assets.insert(relative.to_path_buf(), contents);
+            }
+        }
+
+        // Catch syntax errors now rather than halfway through publishing
+        theme.environment()?;
+        Ok(theme)
+    }
+
+    /// Pick the theme to publish a workspace with.
+    ///
+    /// `requested` is a built-in name or a folder relative to `workspace_dir`.
+    /// Without one, the workspace's `_theme/` folder is used if it exists,
+    /// otherwise the `default` theme.
+    pub async fn resolve<FS: AsyncFileSystem>(
+        fs: &FS,
+        workspace_dir: &Path,
+        requested: Option<&str>,
+    ) -> Result<Self> {
+        match requested {
+            Some(name) => match Self::builtin(name) {
+                Some(theme) => Ok(theme),
+                None => {
+                    let dir = workspace_dir.join(name);
+                    if fs.is_dir(&dir).await {
+                        Self::load(fs, &dir).await
+                    } else {
+                        Err(DiaryxError::ThemeNotFound(name.to_string()))
+                    }
+                }
+            },
+            None => {
+                let dir = workspace_dir.join(THEME_DIR);
+                if fs.is_dir(&dir).await {
+                    Self::load(fs, &dir).await
+                } else {
+                    Ok(Self::default())
+                }
+            }
+        }
+    }
+
+    /// Stylesheet contents
+    pub fn stylesheet(&self) -> &str {
+        &self.stylesheet
+    }
+
+    /// Names of the theme's templates, partials included
+    pub fn template_names(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+
+    /// Static files to copy, as (path relative to `assets/`, contents)
+    pub fn assets(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.assets
+            .iter()
+            .map(|(path, contents)| (path.as_path(), contents.as_slice()))
+    }
+
+    /// Hash of the theme's templates and stylesheet
+    pub(crate) fn fingerprint(&self) -> String {
+        let mut contents = self.name.clone();
+        for (name, source) in &self.templates {
+            contents.push_str(&format!("\0{}\0{}", name, source));
+        }
+        contents.push('\0');
+        contents.push_str(&self.stylesheet);
+        crate::cloud::compute_content_hash(contents.as_bytes())
+    }
+
+    /// Whether a page template may use the list of all pages, so any page
+    /// being added, removed or renamed changes every page (a cheap textual
+    /// check that errs on the side of re-rendering)
+    pub(crate) fn lists_pages(&self) -> bool {
+        self.templates
+            .iter()
+            .filter(|(name, _)| *name != SINGLE_TEMPLATE && *name != LIST_TEMPLATE)
+            .any(|(_, source)| source.contains("pages"))
+    }
+
+    /// Template environment with every template of the theme loaded
+    pub(crate) fn environment(&self) -> Result<Environment<'_>> {
+        let mut env = Environment::new();
+        // Escape like the rest of the publisher; minijinja's default also
+        // escapes `/`, which clutters every href
+        env.set_formatter(|out, state, value| {
+            if matches!(state.auto_escape(), AutoEscape::Html)
+                && !value.is_safe()
+                && let Some(s) = value.as_str()
+            {
+                return out.write_str(&super::html_escape(s)).map_err(Into::into);
+            }
+            minijinja::escape_formatter(out, state, value)
+        });
+        for (name, source) in &self.templates {
+            env.add_template(name, source)
+                .map_err(|e| template_error(name, e))?;
+        }
+        Ok(env)
+    }
+}
+
+/// Render a template of an environment made by [`Theme::environment`]
+pub(crate) fn render(env: &Environment<'_>, name: &str, context: impl Serialize) -> Result<String> {
+    env.get_template(name)
+        .and_then(|template| template.render(context))
+        .map_err(|e| template_error(name, e))
+}
+
+fn template_error(name: &str, error: minijinja::Error) -> DiaryxError {
+    DiaryxError::ThemeTemplate {
+        template: name.to_string(),
+        message: format!("{:#}", error),
+    }
+}
+
+async fn read_theme_file<FS: AsyncFileSystem>(fs: &FS, path: &Path) -> Result<String> {
+    fs.read_to_string(path)
+        .await
+        .map_err(|e| DiaryxError::FileRead {
+            path: path.to_path_buf(),
+            source: e,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{FileSystem, InMemoryFileSystem, SyncToAsyncFs, block_on_test};
+
+    #[test]
+    fn test_builtin_themes_compile() {
+        for name in BUILTIN_THEMES {
+            let theme = Theme::builtin(name).unwrap();
+            assert!(theme.environment().is_ok(), "{} failed to compile", name);
+            assert!(!theme.stylesheet().is_empty());
+        }
+        assert!(Theme::builtin("nope").is_none());
+    }
+
+    #[test]
+    fn test_workspace_theme_overrides_default() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(
+            Path::new("/workspace/_theme/page.html"),
+            "<p>{{ page.title }} by {{ page.frontmatter.author }}</p>",
+        )
+        .unwrap();
+        fs.write_file(
+            Path::new("/workspace/_theme/partials/nav.html"),
+            "<nav></nav>",
+        )
+        .unwrap();
+        fs.write_file(Path::new("/workspace/_theme/style.css"), "body {}")
+            .unwrap();
+        fs.write_binary(Path::new("/workspace/_theme/assets/img/logo.png"), &[1, 2])
+            .unwrap();
+        let fs = SyncToAsyncFs::new(fs);
+
+        let theme = block_on_test(Theme::resolve(&fs, Path::new("/workspace"), None)).unwrap();
+        assert_eq!(theme.name, "_theme");
+        assert_eq!(theme.stylesheet(), "body {}");
+        let names: Vec<&str> = theme.template_names().collect();
+        assert!(names.contains(&"single.html"));
+        assert!(names.contains(&"partials/nav.html"));
+        let assets: Vec<_> = theme.assets().collect();
+        assert_eq!(assets, vec![(Path::new("img/logo.png"), &[1u8, 2][..])]);
+
+        let env = theme.environment().unwrap();
+        let html = render(
+            &env,
+            PAGE_TEMPLATE,
+            minijinja::context! { page => minijinja::context! {
+                title => "A <b>", frontmatter => minijinja::context! { author => "Ann" }
+            }},
+        )
+        .unwrap();
+        assert_eq!(html, "<p>A &lt;b&gt; by Ann</p>");
+    }
+
+    #[test]
+    fn test_resolve_named_themes() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(Path::new("/workspace/themes/dark/style.css"), "x")
+            .unwrap();
+        let fs = SyncToAsyncFs::new(fs);
+        let root = Path::new("/workspace");
+
+        let minimal = block_on_test(Theme::resolve(&fs, root, Some("minimal"))).unwrap();
+        assert_eq!(minimal.name, "minimal");
+        let dark = block_on_test(Theme::resolve(&fs, root, Some("themes/dark"))).unwrap();
+        assert_eq!(dark.name, "dark");
+        assert_eq!(dark.stylesheet(), "x");
+        assert!(matches!(
+            block_on_test(Theme::resolve(&fs, root, Some("missing"))),
+            Err(DiaryxError::ThemeNotFound(_))
+        ));
+        // No _theme folder: the default theme
+        assert_eq!(
+            block_on_test(Theme::resolve(&fs, root, None)).unwrap().name,
+            "default"
+        );
+    }
+
+    #[test]
+    fn test_template_errors_name_the_template() {
+        let fs = InMemoryFileSystem::new();
+        fs.write_file(Path::new("/t/page.html"), "{% if %}")
+            .unwrap();
+        let fs = SyncToAsyncFs::new(fs);
+        match block_on_test(Theme::load(&fs, Path::new("/t"))) {
+            Err(DiaryxError::ThemeTemplate { template, .. }) => assert_eq!(template, "page.html"),
+            other => panic!("unexpected: {:?}", other.map(|t| t.name)),
+        }
+    }
+}
@@ -4,8 +4,11 @@
 
 use std::path::PathBuf;
 
+use indexmap::IndexMap;
 use serde::Serialize;
 
+use crate::render::Diagram;
+
 /// Options for publishing
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct PublishOptions {
@@ -15,8 +18,18 @@ pub struct PublishOptions {
     pub title: Option<String>,
     /// Include audience filtering
     pub audience: Option<String>,
-    /// Overwrite existing destination
+    /// Overwrite existing destination, re-rendering every page instead of
+    /// only those changed since the last publish
     pub force: bool,
+    /// Built-in theme name or theme folder relative to the workspace
+    /// (defaults to the workspace's `_theme/` folder, then `default`)
+    pub theme: Option<String>,
+    /// Public URL of the site (e.g. `https://example.com/journal`), used for
+    /// absolute links in feeds and the sitemap (neither is written without it)
+    pub base_url: Option<String>,
+    /// Show embedded images wider than this many pixels as a scaled-down
+    /// copy linking to the original (needs the `publish-images` feature)
+    pub image_max_width: Option<u32>,
 }
 
 /// A navigation link
@@ -47,6 +60,27 @@ pub struct PublishedPage {
     pub parent_link: Option<NavLink>,
     /// Whether this is the root index
     pub is_root: bool,
+    /// All frontmatter properties, for themes
+    pub frontmatter: IndexMap<String, serde_yaml::Value>,
+    /// Files copied into the site for this page (declared attachments and
+    /// local files the body links to or embeds)
+    pub attachments: Vec<PublishedAttachment>,
+    /// Workspace files the body links to (pages and attachments)
+    pub linked_files: Vec<PathBuf>,
+    /// Diagrams rendered from the body, written to `diagrams/`
+    #[serde(skip)]
+    pub diagrams: Vec<Diagram>,
+}
+
+/// A file copied into the published site
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishedAttachment {
+    /// Original source path
+    pub source_path: PathBuf,
+    /// Output file, relative to the destination
+    pub output: String,
+    /// Scaled-down copy shown for embedded images, relative to the destination
+    pub thumbnail: Option<String>,
 }
 
 /// Result of publishing operation
@@ -56,4 +90,8 @@ pub struct PublishResult {
     pub pages: Vec<PublishedPage>,
     /// Total files processed
     pub files_processed: usize,
+    /// Pages rendered again (the others were unchanged since the last publish)
+    pub pages_rendered: usize,
+    /// Output files removed because their source was deleted
+    pub files_removed: usize,
 }
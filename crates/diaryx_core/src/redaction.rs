@@ -0,0 +1,377 @@
+//! Audience-scoped parts of a file.
+//!
+//! Besides whole files (the `audience` property), parts of a file can be
+//! limited to some audiences:
+//!
+//! - body blocks fenced with `:::audience family friends` ... `:::`, or
+//!   between `<!-- audience: family, friends -->` and `<!-- /audience -->`
+//!   (each marker on a line of its own; blocks can nest)
+//! - frontmatter properties listed in `property_audience`
+//!   (`property_audience: { location: [family] }`)
+//!
+//! Exports for other audiences leave them out, and the `property_audience`
+//! property itself. A block that is never closed runs to the end of the file.
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use ts_rs::TS;
+
+use crate::error::Result;
+use crate::frontmatter;
+
+/// Frontmatter property limiting other properties to some audiences
+pub const PROPERTY_AUDIENCE_KEY: &str = "property_audience";
+
+/// Something left out of a file for an audience
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "bindings/")]
+pub enum Redaction {
+    /// A body block (1-based lines of the file, markers included)
+    Block {
+        /// First line of the block
+        start_line: usize,
+        /// Last line of the block
+        end_line: usize,
+        /// Audiences the block is for
+        audience: Vec<String>,
+    },
+    /// A frontmatter property
+    Property {
+        /// Property name
+        name: String,
+        /// Audiences the property is for
+        audience: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for Redaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Redaction::Block {
+                start_line,
+                end_line,
+                audience,
+            } => write!(
+                f,
+                "lines {}-{} (for {})",
+                start_line,
+                end_line,
+                audience.join(", ")
+            ),
+            Redaction::Property { name, audience } => {
+                write!(f, "property '{}' (for {})", name, audience.join(", "))
+            }
+        }
+    }
+}
+
+/// A file with the parts for other audiences left out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redacted {
+    /// Remaining file content
+    pub content: String,
+    /// What was left out
+    pub redactions: Vec<Redaction>,
+}
+
+/// Leave the parts of a file that aren't for `audience` out.
+///
+/// `"*"` keeps everything that isn't for `private`. Without an audience
+/// everything is kept and only the block markers are removed.
+pub fn redact(content: &str, audience: Option<&str>) -> Result<Redacted> {
+    let body = frontmatter::extract_body(content);
+    let header = &content[..content.len() - body.len()];
+    let first_line = header.lines().count() + 1;
+    let (body, mut redactions) = redact_body(body, audience, first_line);
+
+    let mut header = header.to_string();
+    if !header.is_empty()
+        && let Some(audience) = audience
+    {
+        let mut parsed = frontmatter::parse(content)?.frontmatter;
+        if let Some(scoped) = frontmatter::remove_property(&mut parsed, PROPERTY_AUDIENCE_KEY) {
+            redactions.extend(redact_properties(&mut parsed, &scoped, audience));
+            header = frontmatter::serialize(&parsed, "")?;
+        }
+    }
+
+    Ok(Redacted {
+        content: header + &body,
+        redactions,
+    })
+}
+
+/// Whether a block or property for `scope` is shown to `audience`
+pub fn is_visible(scope: &[String], audience: &str) -> bool {
+    if audience == "*" {
+        return !scope.iter().any(|a| a.eq_ignore_ascii_case("private"));
+    }
+    scope.iter().any(|a| a.eq_ignore_ascii_case(audience))
+}
+
+/// Leave the body blocks that aren't for `audience` out of a body whose
+/// first line is line `first_line` of its file
+pub fn redact_body(
+    body: &str,
+    audience: Option<&str>,
+    first_line: usize,
+) -> (String, Vec<Redaction>) {
+    let mut stack: Vec<Open> = Vec::new();
+    let mut redactions = Vec::new();
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    let mut last_line = first_line;
+
+    // Close a block, recording it if it's the outermost hidden one
+    let close = |open: Open, line: usize, stack: &[Open], redactions: &mut Vec<Redaction>| {
+        if let Open::Audience {
+            scope,
+            visible: false,
+            start_line,
+            ..
+        } = open
+            && !is_hidden(stack)
+        {
+            redactions.push(Redaction::Block {
+                start_line,
+                end_line: line,
+                audience: scope,
+            });
+        }
+    };
+
+    for (i, line) in body.lines().enumerate() {
+        let line_no = first_line + i;
+        last_line = line_no;
+        let trimmed = line.trim();
+
+        if !in_code {
+            let opened = fence_scope(trimmed)
+                .map(|scope| (scope, false))
+                .or_else(|| comment_scope(trimmed).map(|scope| (scope, true)));
+            if let Some((scope, comment)) = opened {
+                let visible = audience.is_none_or(|audience| is_visible(&scope, audience));
+                stack.push(Open::Audience {
+                    scope,
+                    visible,
+                    start_line: line_no,
+                    comment,
+                });
+                continue;
+            }
+
+            if is_comment_close(trimmed)
+                && matches!(stack.last(), Some(Open::Audience { comment: true, .. }))
+            {
+                let open = stack.pop().unwrap();
+                close(open, line_no, &stack, &mut redactions);
+                continue;
+            }
+
+            if let Some(rest) = div_fence(trimmed) {
+                if !rest.is_empty() {
+                    stack.push(Open::Div);
+                } else if matches!(stack.last(), Some(Open::Audience { comment: false, .. })) {
+                    let open = stack.pop().unwrap();
+                    close(open, line_no, &stack, &mut redactions);
+                    continue;
+                } else if matches!(stack.last(), Some(Open::Div)) {
+                    // The closing fence is shown like the div's opening one
+                    let hidden = is_hidden(&stack);
+                    stack.pop();
+                    if !hidden {
+                        lines.push(line);
+                    }
+                    continue;
+                }
+            }
+        }
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+        }
+        if !is_hidden(&stack) {
+            lines.push(line);
+        }
+    }
+
+    // Blocks left open run to the end of the body
+    while let Some(open) = stack.pop() {
+        close(open, last_line, &stack, &mut redactions);
+    }
+
+    let mut out = lines.join("\n");
+    if body.ends_with('\n') && !out.is_empty() {
+        out.push('\n');
+    }
+    (out, redactions)
+}
+
+/// A body block opened and not closed yet
+enum Open {
+    Audience {
+        scope: Vec<String>,
+        visible: bool,
+        start_line: usize,
+        comment: bool,
+    },
+    /// Another fenced div (`::: note`), whose closing fence is kept
+    Div,
+}
+
+/// Whether the lines inside the open blocks are hidden
+fn is_hidden(stack: &[Open]) -> bool {
+    stack
+        .iter()
+        .any(|open| matches!(open, Open::Audience { visible: false, .. }))
+}
+
+/// Remove the properties of a frontmatter that aren't for `audience`, given
+/// the `property_audience` map
+fn redact_properties(
+    frontmatter: &mut IndexMap<String, Value>,
+    scoped: &Value,
+    audience: &str,
+) -> Vec<Redaction> {
+    let Some(scoped) = scoped.as_mapping() else {
+        return Vec::new();
+    };
+    let mut redactions = Vec::new();
+    for (name, scope) in scoped {
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        let scope: Vec<String> = match scope {
+            Value::String(s) => vec![s.clone()],
+            Value::Sequence(items) => items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect(),
+            _ => continue,
+        };
+        if !is_visible(&scope, audience)
+            && frontmatter::remove_property(frontmatter, name).is_some()
+        {
+            redactions.push(Redaction::Property {
+                name: name.to_string(),
+                audience: scope,
+            });
+        }
+    }
+    redactions
+}
+
+/// Audiences of a `:::audience a b` opening fence
+fn fence_scope(line: &str) -> Option<Vec<String>> {
+    let rest = div_fence(line)?.strip_prefix("audience")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(split_scope(rest))
+}
+
+/// Audiences of a `<!-- audience: a, b -->` marker
+fn comment_scope(line: &str) -> Option<Vec<String>> {
+    let inner = line.strip_prefix("<!--")?.strip_suffix("-->")?.trim();
+    Some(split_scope(inner.strip_prefix("audience:")?))
+}
+
+/// Whether a line is a `<!-- /audience -->` marker
+fn is_comment_close(line: &str) -> bool {
+    line.strip_prefix("<!--")
+        .and_then(|rest| rest.strip_suffix("-->"))
+        .is_some_and(|inner| inner.trim() == "/audience")
+}
+
+/// What follows the colons of a `:::` fenced div line
+fn div_fence(line: &str) -> Option<&str> {
+    let rest = line.trim_start_matches(':');
+    (line.len() - rest.len() >= 3).then_some(rest.trim())
+}
+
+/// Audience names separated by commas or spaces
+fn split_scope(s: &str) -> Vec<String> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|a| !a.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "---\ntitle: Day\nlocation: Home\nproperty_audience:\n  location: [family]\n---\n\nShared.\n\n:::audience private\nSecret.\n:::\n\n<!-- audience: family, friends -->\nFamily news.\n<!-- /audience -->\n\n::: note\nA note.\n:::\n";
+
+    #[test]
+    fn test_redact_for_audience() {
+        let redacted = redact(FILE, Some("work")).unwrap();
+        assert_eq!(
+            redacted.content,
+            "---\ntitle: Day\n---\n\nShared.\n\n\n\n::: note\nA note.\n:::\n"
+        );
+        assert_eq!(
+            redacted.redactions,
+            vec![
+                Redaction::Block {
+                    start_line: 10,
+                    end_line: 12,
+                    audience: vec!["private".to_string()],
+                },
+                Redaction::Block {
+                    start_line: 14,
+                    end_line: 16,
+                    audience: vec!["family".to_string(), "friends".to_string()],
+                },
+                Redaction::Property {
+                    name: "location".to_string(),
+                    audience: vec!["family".to_string()],
+                },
+            ]
+        );
+
+        let family = redact(FILE, Some("Family")).unwrap();
+        assert!(family.content.contains("location: Home"));
+        assert!(family.content.contains("Family news.\n"));
+        assert!(!family.content.contains("Secret."));
+        assert!(!family.content.contains("property_audience"));
+        assert_eq!(family.redactions.len(), 1);
+    }
+
+    #[test]
+    fn test_redact_without_audience_keeps_blocks() {
+        let redacted = redact(FILE, None).unwrap();
+        assert!(redacted.redactions.is_empty());
+        assert!(redacted.content.contains("Secret.\n\nFamily news.\n"));
+        assert!(redacted.content.contains("property_audience"));
+        assert!(!redacted.content.contains(":::audience"));
+    }
+
+    #[test]
+    fn test_redact_body_nesting_and_code() {
+        let body = "```\n:::audience private\n```\n:::audience family\nA\n:::audience private\nB\n:::\nC\n";
+        let (out, redactions) = redact_body(body, Some("family"), 1);
+        assert_eq!(out, "```\n:::audience private\n```\nA\nC\n");
+        assert_eq!(
+            redactions,
+            vec![Redaction::Block {
+                start_line: 6,
+                end_line: 8,
+                audience: vec!["private".to_string()],
+            }]
+        );
+
+        // An unclosed block runs to the end
+        let (out, redactions) = redact_body("A\n:::audience private\nB\n", Some("*"), 1);
+        assert_eq!(out, "A\n");
+        assert_eq!(
+            redactions,
+            vec![Redaction::Block {
+                start_line: 2,
+                end_line: 3,
+                audience: vec!["private".to_string()],
+            }]
+        );
+    }
+}
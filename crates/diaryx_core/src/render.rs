@@ -0,0 +1,751 @@
+//! Rendering of math and diagrams in markdown.
+//!
+//! A [`RenderPipeline`] turns `$...$` / `$$...$$` math (and ```` ```math ````
+//! blocks) into HTML, and fenced code blocks of diagram languages into SVG
+//! images through [`CodeRenderer`]s. Diagrams become files in
+//! [`DIAGRAMS_DIR`], named by a hash of their source, so pages link to them
+//! with `<img>` and converters like pandoc can pick them up.
+//!
+//! The publisher applies the pipeline while converting pages to HTML, and
+//! [`RenderPipeline::render_markdown`] applies it to markdown before export
+//! hands it to pandoc, so PDF and EPUB output get the same diagrams.
+//!
+//! On native platforms, [`RenderPipeline::with_local_tools`] adds renderers for
+//! ```` ```mermaid ```` (via `mmdc`) and ```` ```typst ```` (via `typst`)
+//! blocks when those binaries are on PATH.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// Folder diagrams are written to, next to the pages that embed them
+pub const DIAGRAMS_DIR: &str = "diagrams";
+
+/// How math is rendered to HTML
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MathStyle {
+    /// `\(...\)` and `\[...\]` in `math` spans, for KaTeX's auto-render (and
+    /// pandoc's HTML reader)
+    #[default]
+    Katex,
+    /// MathML, which browsers render without scripts
+    MathMl,
+}
+
+impl MathStyle {
+    /// Parse a style name (`katex` or `mathml`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "katex" => Some(Self::Katex),
+            "mathml" => Some(Self::MathMl),
+            _ => None,
+        }
+    }
+}
+
+/// Renders fenced code blocks of some languages to SVG
+pub trait CodeRenderer: Send + Sync {
+    /// Name of the renderer, recorded in publish fingerprints
+    fn name(&self) -> &str;
+
+    /// Whether this renderer handles code blocks of a language
+    fn handles(&self, language: &str) -> bool;
+
+    /// Render a block's source to SVG, or `None` to leave it as a code block
+    fn render(&self, language: &str, source: &str) -> Option<String>;
+}
+
+/// A rendered diagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagram {
+    /// File name in [`DIAGRAMS_DIR`] (e.g. `mermaid-1a2b3c.svg`)
+    pub file_name: String,
+    /// Language of the code block
+    pub language: String,
+    /// SVG contents
+    pub svg: String,
+}
+
+impl Diagram {
+    /// Path of the diagram relative to the page embedding it
+    pub fn path(&self) -> String {
+        format!("{}/{}", DIAGRAMS_DIR, self.file_name)
+    }
+
+    /// HTML embedding the diagram in a page
+    pub fn html(&self) -> String {
+        format!(
+            "<figure class=\"diagram diagram-{lang}\"><img src=\"{src}\" alt=\"{lang} diagram\"></figure>\n",
+            lang = escape_html(&self.language),
+            src = self.path(),
+        )
+    }
+}
+
+/// Math and diagram rendering applied to markdown bodies
+pub struct RenderPipeline {
+    math: Option<MathStyle>,
+    renderers: Vec<Box<dyn CodeRenderer>>,
+    /// Rendered output by input, so unchanged diagrams aren't rendered again
+    /// (e.g. across `publish --watch` runs)
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl Default for RenderPipeline {
+    fn default() -> Self {
+        Self {
+            math: Some(MathStyle::default()),
+            renderers: Vec::new(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl RenderPipeline {
+    /// Create a pipeline rendering math in the default style, without diagram
+    /// renderers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how math is rendered (`None` leaves `$` as plain text)
+    pub fn with_math(mut self, math: Option<MathStyle>) -> Self {
+        self.math = math;
+        self
+    }
+
+    /// Add a diagram renderer (earlier renderers win for the same language)
+    pub fn with_renderer(mut self, renderer: Box<dyn CodeRenderer>) -> Self {
+        self.renderers.push(renderer);
+        self
+    }
+
+    /// Add renderers for the diagram tools found on PATH
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_local_tools(mut self) -> Self {
+        for renderer in [CommandRenderer::mermaid(), CommandRenderer::typst()] {
+            if renderer.is_available() {
+                self.renderers.push(Box::new(renderer));
+            }
+        }
+        self
+    }
+
+    /// How math is rendered, if at all
+    pub fn math(&self) -> Option<MathStyle> {
+        self.math
+    }
+
+    /// Whether a code block language is rendered (diagrams, or `math`)
+    pub fn handles(&self, language: &str) -> bool {
+        (language == "math" && self.math.is_some())
+            || self.renderers.iter().any(|r| r.handles(language))
+    }
+
+    /// Summary of the configuration, so publishing again re-renders pages
+    /// when it changes
+    pub fn fingerprint(&self) -> String {
+        let math = match self.math {
+            Some(MathStyle::Katex) => "katex",
+            Some(MathStyle::MathMl) => "mathml",
+            None => "none",
+        };
+        let names: Vec<&str> = self.renderers.iter().map(|r| r.name()).collect();
+        format!("math={};renderers={}", math, names.join(","))
+    }
+
+    /// Render TeX math to HTML
+    pub fn render_math(&self, tex: &str, display: bool) -> String {
+        if self.math == Some(MathStyle::MathMl) {
+            return tex_to_mathml(tex, display);
+        }
+        if display {
+            format!(
+                r#"<span class="math display">\[{}\]</span>"#,
+                escape_html(tex)
+            )
+        } else {
+            format!(
+                r#"<span class="math inline">\({}\)</span>"#,
+                escape_html(tex)
+            )
+        }
+    }
+
+    /// Render a diagram code block, or `None` if no renderer handles its
+    /// language or rendering failed
+    pub fn render_diagram(&self, language: &str, source: &str) -> Option<Diagram> {
+        let renderer = self.renderers.iter().find(|r| r.handles(language))?;
+        let hash = crate::cloud::compute_content_hash(
+            format!("{}\n{}\n{}", renderer.name(), language, source).as_bytes(),
+        );
+        let svg = self.cached(&format!("diagram:{}", hash), || {
+            renderer.render(language, source)
+        })?;
+        Some(Diagram {
+            file_name: format!("{}-{}.svg", slug(language), hash),
+            language: language.to_string(),
+            svg,
+        })
+    }
+
+    /// Replace diagram code blocks in a markdown document with images of the
+    /// rendered diagrams, for converters that read markdown (like pandoc).
+    ///
+    /// Math is left alone, since those converters understand `$` math. The
+    /// returned diagrams are to be written to [`DIAGRAMS_DIR`] next to the
+    /// document.
+    #[cfg(feature = "markdown")]
+    pub fn render_markdown(&self, markdown: &str) -> (String, Vec<Diagram>) {
+        use comrak::nodes::NodeValue;
+
+        let mut options = comrak::Options::default();
+        options.extension.front_matter_delimiter = Some("---".to_string());
+        let arena = comrak::Arena::new();
+        let root = comrak::parse_document(&arena, markdown, &options);
+
+        // Lines (1-based, inclusive) of each rendered block
+        let mut replacements = Vec::new();
+        let mut diagrams: Vec<Diagram> = Vec::new();
+        for node in root.descendants() {
+            let data = node.data();
+            let NodeValue::CodeBlock(block) = &data.value else {
+                continue;
+            };
+            let language = block.info.split_whitespace().next().unwrap_or("");
+            if !block.fenced || language.is_empty() {
+                continue;
+            }
+            if let Some(diagram) = self.render_diagram(language, &block.literal) {
+                let image = format!("![{} diagram]({})", language, diagram.path());
+                replacements.push((data.sourcepos.start.line, data.sourcepos.end.line, image));
+                if !diagrams.iter().any(|d| d.file_name == diagram.file_name) {
+                    diagrams.push(diagram);
+                }
+            }
+        }
+        if replacements.is_empty() {
+            return (markdown.to_string(), diagrams);
+        }
+
+        let mut out = String::with_capacity(markdown.len());
+        let mut replacements = replacements.into_iter().peekable();
+        for (idx, line) in markdown.split_inclusive('\n').enumerate() {
+            let line_no = idx + 1;
+            match replacements.peek() {
+                Some((start, end, image)) if line_no >= *start && line_no <= *end => {
+                    if line_no == *start {
+                        // Keep the block's indentation (e.g. inside a list)
+                        let indent = &line[..line.len() - line.trim_start().len()];
+                        out.push_str(indent);
+                        out.push_str(image);
+                        out.push('\n');
+                    }
+                    if line_no == *end {
+                        replacements.next();
+                    }
+                }
+                _ => out.push_str(line),
+            }
+        }
+        (out, diagrams)
+    }
+
+    /// Look up or compute a rendered output
+    fn cached(&self, key: &str, render: impl FnOnce() -> Option<String>) -> Option<String> {
+        if let Some(hit) = self.cache.lock().ok().and_then(|c| c.get(key).cloned()) {
+            return hit;
+        }
+        let rendered = render();
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(key.to_string(), rendered.clone());
+        }
+        rendered
+    }
+}
+
+/// How long a diagram renderer may run before it's killed
+#[cfg(not(target_arch = "wasm32"))]
+const RENDER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Renders diagrams by running a local command-line tool
+#[cfg(not(target_arch = "wasm32"))]
+pub struct CommandRenderer {
+    name: &'static str,
+    language: &'static str,
+    program: &'static str,
+    /// Arguments before the input and output paths
+    args: &'static [&'static str],
+    /// Flag before the output path, if any
+    output_flag: Option<&'static str>,
+    /// Flag before the input path, if any
+    input_flag: Option<&'static str>,
+    /// Extension of the input file
+    extension: &'static str,
+    /// Text put before the block's source
+    prelude: &'static str,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CommandRenderer {
+    /// ```` ```mermaid ```` blocks, via mermaid-cli (`mmdc`)
+    pub fn mermaid() -> Self {
+        Self {
+            name: "mermaid",
+            language: "mermaid",
+            program: "mmdc",
+            args: &["--quiet"],
+            input_flag: Some("-i"),
+            output_flag: Some("-o"),
+            extension: "mmd",
+            prelude: "",
+        }
+    }
+
+    /// ```` ```typst ```` blocks, via the `typst` CLI, on a page fitted to the
+    /// content
+    pub fn typst() -> Self {
+        Self {
+            name: "typst",
+            language: "typst",
+            program: "typst",
+            args: &["compile"],
+            input_flag: None,
+            output_flag: None,
+            extension: "typ",
+            prelude: "#set page(width: auto, height: auto, margin: 8pt)\n",
+        }
+    }
+
+    /// Whether the tool is on PATH
+    pub fn is_available(&self) -> bool {
+        std::process::Command::new(self.program)
+            .arg("--version")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CodeRenderer for CommandRenderer {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn handles(&self, language: &str) -> bool {
+        language.eq_ignore_ascii_case(self.language)
+    }
+
+    fn render(&self, _language: &str, source: &str) -> Option<String> {
+        // A private directory, so other users can't swap the files for symlinks
+        let dir = tempfile::Builder::new()
+            .prefix("diaryx-render-")
+            .tempdir()
+            .ok()?;
+        let input = dir.path().join("diagram").with_extension(self.extension);
+        let output = dir.path().join("diagram.svg");
+        std::fs::write(&input, format!("{}{}", self.prelude, source)).ok()?;
+
+        let mut cmd = std::process::Command::new(self.program);
+        cmd.args(self.args);
+        if let Some(flag) = self.input_flag {
+            cmd.arg(flag);
+        }
+        cmd.arg(&input);
+        if let Some(flag) = self.output_flag {
+            cmd.arg(flag);
+        }
+        cmd.arg(&output)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        let mut child = cmd.spawn().ok()?;
+        match wait_with_timeout(&mut child, RENDER_TIMEOUT) {
+            Some(status) if status.success() => std::fs::read_to_string(&output).ok(),
+            Some(_) => None,
+            None => {
+                log::warn!(
+                    "[Render] {} took longer than {:?}, skipping block",
+                    self.program,
+                    RENDER_TIMEOUT
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Wait for a child process to exit, killing it after `timeout` (`None`)
+#[cfg(not(target_arch = "wasm32"))]
+fn wait_with_timeout(
+    child: &mut std::process::Child,
+    timeout: std::time::Duration,
+) -> Option<std::process::ExitStatus> {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if std::time::Instant::now() < deadline => {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+/// Convert TeX math to MathML.
+///
+/// Unsupported commands are shown in red in the output and logged. The TeX
+/// source isn't embedded as an annotation since it would be written
+/// unescaped.
+fn tex_to_mathml(tex: &str, display: bool) -> String {
+    use pulldown_latex::config::DisplayMode;
+    use pulldown_latex::{Parser, RenderConfig, Storage};
+
+    let storage = Storage::new();
+    let events: Vec<_> = Parser::new(tex, &storage).collect();
+    if let Some(Err(e)) = events.iter().find(|event| event.is_err()) {
+        log::warn!("[Render] Invalid math `{}`: {}", tex, e);
+    }
+    let config = RenderConfig {
+        display_mode: if display {
+            DisplayMode::Block
+        } else {
+            DisplayMode::Inline
+        },
+        ..Default::default()
+    };
+    let mut mathml = String::new();
+    if let Err(e) = pulldown_latex::push_mathml(&mut mathml, events.into_iter(), config) {
+        log::warn!("[Render] Failed to render math `{}`: {}", tex, e);
+    }
+    sanitize_mathml(&mathml)
+}
+
+/// MathML elements kept by [`sanitize_mathml`]
+const MATHML_ELEMENTS: &[&str] = &[
+    "math",
+    "semantics",
+    "annotation",
+    "mrow",
+    "mstyle",
+    "merror",
+    "mpadded",
+    "mphantom",
+    "menclose",
+    "mi",
+    "mn",
+    "mo",
+    "ms",
+    "mtext",
+    "mspace",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "msub",
+    "msup",
+    "msubsup",
+    "munder",
+    "mover",
+    "munderover",
+    "mmultiscripts",
+    "mprescripts",
+    "none",
+    "mtable",
+    "mtr",
+    "mtd",
+    "mlabeledtr",
+];
+
+/// MathML elements holding only text
+const MATHML_TOKENS: &[&str] = &["mi", "mn", "mo", "ms", "mtext"];
+
+/// A MathML tag found by [`mathml_tag`]
+enum MathMlTag<'a> {
+    Open { name: &'a str, empty: bool },
+    Close(&'a str),
+}
+
+/// Escape the text of pulldown-latex's MathML output.
+///
+/// Text is written unescaped (`<mo><</mo>`, `<mtext>a<b</mtext>`), so markup
+/// in the TeX source would reach the page. Only well-nested MathML tags with
+/// plain attributes are kept, and never inside a token element; every other
+/// `<`, `>` and `&` is escaped, which also keeps EPUB XHTML well-formed.
+fn sanitize_mathml(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut open: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while let Some(c) = raw[i..].chars().next() {
+        let rest = &raw[i..];
+        let in_token = open.last().is_some_and(|name| MATHML_TOKENS.contains(name));
+        match mathml_tag(rest) {
+            Some((MathMlTag::Open { name, empty }, len)) if !in_token => {
+                if !empty {
+                    open.push(name);
+                }
+                out.push_str(&rest[..len]);
+                i += len;
+                continue;
+            }
+            Some((MathMlTag::Close(name), len)) if open.last() == Some(&name) => {
+                open.pop();
+                out.push_str(&rest[..len]);
+                i += len;
+                continue;
+            }
+            _ => {}
+        }
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' if !starts_with_entity(rest) => out.push_str("&amp;"),
+            c => out.push(c),
+        }
+        i += c.len_utf8();
+    }
+    for name in open.iter().rev() {
+        out.push_str(&format!("</{}>", name));
+    }
+    out
+}
+
+/// The MathML tag at the start of `s`, and its length
+fn mathml_tag(s: &str) -> Option<(MathMlTag<'_>, usize)> {
+    let inner = s.strip_prefix('<')?;
+    let end = inner.find('>')?;
+    let inner = &inner[..end];
+    let len = end + 2;
+    if let Some(name) = inner.strip_prefix('/') {
+        return MATHML_ELEMENTS
+            .contains(&name)
+            .then_some((MathMlTag::Close(name), len));
+    }
+
+    let (inner, empty) = match inner.strip_suffix('/') {
+        Some(inner) => (inner.trim_end(), true),
+        None => (inner, false),
+    };
+    let (name, mut attributes) = inner.split_once(' ').unwrap_or((inner, ""));
+    if !MATHML_ELEMENTS.contains(&name) {
+        return None;
+    }
+    attributes = attributes.trim_start();
+    while !attributes.is_empty() {
+        let (attribute, value) = attributes.split_once("=\"")?;
+        let (value, after) = value.split_once('"')?;
+        let plain_name = !attribute.is_empty()
+            && attribute
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c == '-')
+            && !attribute.starts_with("on")
+            && !matches!(attribute, "href" | "src" | "style");
+        if !plain_name || value.contains(['<', '&']) {
+            return None;
+        }
+        attributes = after.trim_start();
+    }
+    Some((MathMlTag::Open { name, empty }, len))
+}
+
+/// Whether `s` starts with an XML character or entity reference
+fn starts_with_entity(s: &str) -> bool {
+    let Some((reference, _)) = s
+        .strip_prefix('&')
+        .and_then(|rest| rest.get(..rest.len().min(10)))
+        .and_then(|head| head.split_once(';'))
+    else {
+        return false;
+    };
+    match reference.strip_prefix('#') {
+        Some(hex) if hex.starts_with(['x', 'X']) => {
+            hex.len() > 1 && hex[1..].chars().all(|c| c.is_ascii_hexdigit())
+        }
+        Some(decimal) => !decimal.is_empty() && decimal.chars().all(|c| c.is_ascii_digit()),
+        None => matches!(reference, "amp" | "lt" | "gt" | "quot" | "apos"),
+    }
+}
+
+/// Lowercase alphanumeric form of a language name, for file names
+fn slug(language: &str) -> String {
+    language
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Escape HTML special characters
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Renders ```dot blocks to a fixed SVG, counting calls
+    struct FakeRenderer(Arc<AtomicUsize>);
+
+    impl CodeRenderer for FakeRenderer {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn handles(&self, language: &str) -> bool {
+            language == "dot"
+        }
+
+        fn render(&self, _language: &str, source: &str) -> Option<String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            (!source.contains("broken")).then(|| "<svg/>".to_string())
+        }
+    }
+
+    #[test]
+    fn test_render_math_katex() {
+        let pipeline = RenderPipeline::new();
+        assert_eq!(
+            pipeline.render_math("a < b", false),
+            r#"<span class="math inline">\(a &lt; b\)</span>"#
+        );
+        assert_eq!(
+            pipeline.render_math("x^2", true),
+            r#"<span class="math display">\[x^2\]</span>"#
+        );
+        assert_eq!(MathStyle::from_name("MathML"), Some(MathStyle::MathMl));
+        assert_eq!(MathStyle::from_name("tex"), None);
+    }
+
+    #[test]
+    fn test_render_math_mathml() {
+        let pipeline = RenderPipeline::new().with_math(Some(MathStyle::MathMl));
+        let inline = pipeline.render_math("a < b", false);
+        assert!(inline.starts_with("<math"));
+        assert!(inline.contains("<mo>&lt;</mo>"));
+        assert!(!inline.contains(r#"display="block""#));
+        assert!(
+            pipeline
+                .render_math(r"\frac{1}{2}", true)
+                .contains(r#"display="block""#)
+        );
+        assert!(
+            pipeline
+                .render_math(r"a \& b > c", false)
+                .contains("<mi>&amp;</mi><mi>b</mi><mo>&gt;</mo>")
+        );
+        // Markup in text is escaped, not written into the page
+        let text = pipeline.render_math(r"\text{a<b}", false);
+        assert!(text.contains("a&lt;b"));
+        let text = pipeline.render_math(r"\text{<img src=x onerror=alert(1)>}", false);
+        assert!(!text.contains("<img"));
+        assert!(text.contains("&lt;img src=x onerror=alert(1)&gt;"));
+        let text = pipeline.render_math(r#"\text{</mtext><mi onclick="alert(1)">x</mi>}"#, false);
+        assert!(!text.contains("<mi onclick"));
+        assert_eq!(
+            text.matches("<math").count(),
+            text.matches("</math>").count()
+        );
+        // Invalid TeX shows an error instead of being dropped
+        assert!(
+            pipeline
+                .render_math(r"\nosuchcommand", false)
+                .contains("<merror")
+        );
+    }
+
+    #[test]
+    fn test_sanitize_mathml() {
+        assert_eq!(
+            sanitize_mathml(
+                r#"<math display="block"><mo><</mo><mtext>a&b &amp; &#x3c;</mtext><mspace width="1em"/></math>"#
+            ),
+            r#"<math display="block"><mo>&lt;</mo><mtext>a&amp;b &amp; &#x3c;</mtext><mspace width="1em"/></math>"#
+        );
+        // Tags inside text, unknown tags and unsafe attributes are text
+        assert_eq!(
+            sanitize_mathml("<math><mtext><mi>x</mi></mtext><b>y</b></math>"),
+            "<math><mtext>&lt;mi&gt;x&lt;/mi&gt;</mtext>&lt;b&gt;y&lt;/b&gt;</math>"
+        );
+        assert_eq!(
+            sanitize_mathml(r#"<mrow onclick="x"></mrow><mrow style="x">"#),
+            r#"&lt;mrow onclick="x"&gt;&lt;/mrow&gt;&lt;mrow style="x"&gt;"#
+        );
+        // Unclosed elements are closed
+        assert_eq!(
+            sanitize_mathml("<math><mrow>"),
+            "<math><mrow></mrow></math>"
+        );
+    }
+
+    #[test]
+    fn test_render_diagram_caches_and_names_by_hash() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let pipeline = RenderPipeline::new().with_renderer(Box::new(FakeRenderer(calls.clone())));
+        assert!(pipeline.handles("dot"));
+        assert!(pipeline.handles("math"));
+        assert!(!pipeline.handles("rust"));
+        assert!(pipeline.render_diagram("rust", "fn main() {}").is_none());
+
+        let first = pipeline.render_diagram("dot", "a -> b").unwrap();
+        let again = pipeline.render_diagram("dot", "a -> b").unwrap();
+        assert_eq!(first, again);
+        assert!(first.file_name.starts_with("dot-") && first.file_name.ends_with(".svg"));
+        assert!(first.html().contains(r#"<img src="diagrams/dot-"#));
+        assert!(pipeline.render_diagram("dot", "broken").is_none());
+        assert!(pipeline.render_diagram("dot", "broken").is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(pipeline.fingerprint(), "math=katex;renderers=fake");
+    }
+
+    #[cfg(feature = "markdown")]
+    #[test]
+    #[cfg(unix)]
+    fn test_hung_renderer_is_killed() {
+        use std::time::{Duration, Instant};
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        assert!(wait_with_timeout(&mut child, Duration::from_millis(100)).is_none());
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let status = wait_with_timeout(&mut child, Duration::from_secs(10)).unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_render_markdown_replaces_diagram_blocks() {
+        let pipeline = RenderPipeline::new().with_renderer(Box::new(FakeRenderer(Arc::default())));
+        let markdown = "---\ntitle: T\n---\n\nIntro $x$\n\n```dot\na -> b\n```\n\n- item\n\n  ```dot\n  c\n  ```\n\n```rust\nfn main() {}\n```\n";
+        let (out, diagrams) = pipeline.render_markdown(markdown);
+        assert_eq!(diagrams.len(), 2);
+        assert_eq!(
+            out,
+            format!(
+                "---\ntitle: T\n---\n\nIntro $x$\n\n![dot diagram]({})\n\n- item\n\n  ![dot diagram]({})\n\n```rust\nfn main() {{}}\n```\n",
+                diagrams[0].path(),
+                diagrams[1].path()
+            )
+        );
+
+        let (unchanged, none) = RenderPipeline::new().render_markdown(markdown);
+        assert_eq!(unchanged, markdown);
+        assert!(none.is_empty());
+    }
+}
@@ -1,7 +1,7 @@
 //! Test utilities for diaryx_core
 //!
 //! This module provides shared testing infrastructure, including a mock filesystem
-//! that can be used across all test modules.
+//! and CRDT update builders that can be used across all test modules.
 
 use std::collections::HashMap;
 use std::io;
@@ -147,3 +147,14 @@ impl FileSystem for MockFileSystem {
         Ok(())
     }
 }
+
+/// A body document update inserting `text`, as a client would send it.
+#[cfg(feature = "crdt")]
+pub fn body_update(text: &str) -> Vec<u8> {
+    use yrs::{Text, Transact};
+    let doc = yrs::Doc::new();
+    let body = doc.get_or_insert_text("body");
+    let mut txn = doc.transact_mut();
+    body.insert(&mut txn, 0, text);
+    txn.encode_update_v1()
+}
@@ -46,6 +46,25 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     normalized.iter().collect()
 }
 
+/// Whether a relative path stays inside the folder it's joined to: no `..`,
+/// root or prefix components.
+///
+/// # Example
+/// ```
+/// use diaryx_core::path_utils::is_contained;
+///
+/// assert!(is_contained("attachments/photo.png"));
+/// assert!(!is_contained("attachments/../../etc/passwd"));
+/// assert!(!is_contained("/etc/passwd"));
+/// ```
+pub fn is_contained(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
 /// Compute a relative path from a base directory to a target file.
 ///
 /// # Example
@@ -0,0 +1,220 @@
+//! `diaryx_sync_server admin ...` subcommands.
+//!
+//! These run against the database and workspace files directly, so they work
+//! whether or not the server is running.
+
+use clap::Subcommand;
+
+use super::{AdminError, DEFAULT_AUDIT_LIMIT, DEFAULT_KEEP_UPDATES};
+use crate::audit::{self, AuditAction};
+use crate::db::AuthRepo;
+use crate::sync_v2::WorkspaceStore;
+
+/// Operator commands
+#[derive(Debug, Subcommand)]
+pub enum AdminCommand {
+    /// List users and how many workspaces they own
+    Users,
+
+    /// List workspaces with their owner, file count and size on disk
+    Workspaces,
+
+    /// Revoke all sessions and API tokens of a user
+    Logout {
+        /// User ID or email address
+        user: String,
+    },
+
+    /// Delete a workspace, its memberships, share sessions and data
+    DeleteWorkspace {
+        /// Workspace ID
+        workspace_id: String,
+
+        /// Confirm the deletion; this cannot be undone
+        #[arg(long, required = true)]
+        yes: bool,
+    },
+
+    /// Show recent security events from the audit log
+    Audit {
+        /// Only show events for this user ID or email address
+        #[arg(long)]
+        user: Option<String>,
+
+        /// Number of events to show
+        #[arg(long, default_value_t = DEFAULT_AUDIT_LIMIT)]
+        limit: usize,
+    },
+
+    /// Remove expired magic links, sessions, device codes, API tokens, share sessions and invites
+    Cleanup,
+
+    /// Compact CRDT update logs to reclaim space
+    Compact {
+        /// Only compact this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+
+        /// Number of recent updates to keep per document
+        #[arg(long, default_value_t = DEFAULT_KEEP_UPDATES)]
+        keep_updates: usize,
+    },
+}
+
+/// Run an admin subcommand, printing results to stdout
+pub fn run_admin_command(
+    command: AdminCommand,
+    repo: &AuthRepo,
+    store: &WorkspaceStore,
+) -> Result<(), AdminError> {
+    match command {
+        AdminCommand::Users => {
+            let users = super::list_users(repo)?;
+            println!(
+                "{:<38} {:<32} {:>10}  LAST LOGIN",
+                "ID", "EMAIL", "WORKSPACES"
+            );
+            for user in &users {
+                println!(
+                    "{:<38} {:<32} {:>10}  {}",
+                    user.id,
+                    user.email,
+                    user.workspaces,
+                    user.last_login_at.as_deref().unwrap_or("never")
+                );
+            }
+            println!("{} user(s)", users.len());
+        }
+        AdminCommand::Workspaces => {
+            let workspaces = super::list_workspaces(repo, store)?;
+            println!(
+                "{:<38} {:<16} {:<32} {:>6} {:>10}",
+                "ID", "NAME", "OWNER", "FILES", "SIZE"
+            );
+            for workspace in &workspaces {
+                println!(
+                    "{:<38} {:<16} {:<32} {:>6} {:>10}",
+                    workspace.id,
+                    workspace.name,
+                    workspace
+                        .owner_email
+                        .as_deref()
+                        .unwrap_or(&workspace.owner_id),
+                    workspace.file_count,
+                    format_bytes(workspace.size_bytes)
+                );
+            }
+            let total: u64 = workspaces.iter().map(|w| w.size_bytes).sum();
+            println!(
+                "{} workspace(s), {} total",
+                workspaces.len(),
+                format_bytes(total)
+            );
+        }
+        AdminCommand::Logout { user } => {
+            let report = super::force_logout(repo, &user)?;
+            audit::record(
+                repo,
+                AuditAction::AdminLogout,
+                None,
+                Some(&report.user_id),
+                serde_json::json!({
+                    "via": "cli",
+                    "sessions_revoked": report.sessions_revoked,
+                    "api_tokens_revoked": report.api_tokens_revoked,
+                }),
+            );
+            println!(
+                "Logged out {}: revoked {} session(s) and {} API token(s).",
+                report.email, report.sessions_revoked, report.api_tokens_revoked
+            );
+        }
+        AdminCommand::DeleteWorkspace { workspace_id, .. } => {
+            super::delete_workspace(repo, store, &workspace_id)?;
+            audit::record(
+                repo,
+                AuditAction::AdminWorkspaceDeleted,
+                None,
+                Some(&workspace_id),
+                serde_json::json!({ "via": "cli" }),
+            );
+            println!("Deleted workspace {}.", workspace_id);
+        }
+        AdminCommand::Audit { user, limit } => {
+            let events = super::audit_log(repo, user.as_deref(), limit)?;
+            println!(
+                "{:<25} {:<24} {:<38} {:<38} DETAILS",
+                "TIME", "ACTION", "USER", "TARGET"
+            );
+            // Oldest first so the newest event ends up next to the prompt
+            for event in events.iter().rev() {
+                println!(
+                    "{:<25} {:<24} {:<38} {:<38} {}",
+                    event.created_at.to_rfc3339(),
+                    event.action,
+                    event.user_id.as_deref().unwrap_or("-"),
+                    event.target.as_deref().unwrap_or("-"),
+                    event.details
+                );
+            }
+        }
+        AdminCommand::Cleanup => {
+            let report = super::cleanup(repo)?;
+            println!("Removed {} expired row(s):", report.total());
+            println!("  magic links:     {}", report.magic_tokens);
+            println!("  sessions:        {}", report.sessions);
+            println!("  device codes:    {}", report.device_codes);
+            println!("  passkey flows:   {}", report.passkey_challenges);
+            println!("  API tokens:      {}", report.api_tokens);
+            println!("  share sessions:  {}", report.share_sessions);
+            println!("  invitations:     {}", report.workspace_invites);
+        }
+        AdminCommand::Compact {
+            workspace,
+            keep_updates,
+        } => {
+            let report = super::compact(repo, store, workspace.as_deref(), keep_updates)?;
+            println!(
+                "Compacted {} document(s) in {} workspace(s): {} -> {}",
+                report.documents,
+                report.workspaces,
+                format_bytes(report.bytes_before),
+                format_bytes(report.bytes_after)
+            );
+            if report.bytes_after >= report.bytes_before && report.documents > 0 {
+                println!(
+                    "(SQLite reuses freed pages; run VACUUM on the database files to shrink them)"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Format a byte count for display
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(2048), "2.0 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 + 512 * 1024), "5.5 MB");
+    }
+}
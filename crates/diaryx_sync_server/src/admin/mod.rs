@@ -0,0 +1,334 @@
+//! Operator tasks shared by the `admin` CLI subcommands and `/api/admin` endpoints.
+
+mod cli;
+
+pub use cli::{AdminCommand, run_admin_command};
+
+use crate::db::{AuditEventInfo, AuthRepo, DbError, UserInfo};
+use crate::sync_v2::WorkspaceStore;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Updates kept per document when compacting (matches the client default)
+pub const DEFAULT_KEEP_UPDATES: usize = 100;
+
+/// Audit events shown when no limit is given
+pub const DEFAULT_AUDIT_LIMIT: usize = 50;
+
+/// User summary for operators
+#[derive(Debug, Clone, Serialize)]
+pub struct UserSummary {
+    pub id: String,
+    pub email: String,
+    pub created_at: String,
+    pub last_login_at: Option<String>,
+    /// Workspaces this user owns
+    pub workspaces: usize,
+}
+
+/// Workspace summary with storage usage
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkspaceUsage {
+    pub id: String,
+    pub name: String,
+    pub owner_id: String,
+    pub owner_email: Option<String>,
+    pub created_at: String,
+    pub file_count: usize,
+    /// On-disk size of the workspace database in bytes
+    pub size_bytes: u64,
+}
+
+/// Result of a forced logout
+#[derive(Debug, Clone, Serialize)]
+pub struct LogoutReport {
+    pub user_id: String,
+    pub email: String,
+    pub sessions_revoked: usize,
+    pub api_tokens_revoked: usize,
+}
+
+/// Rows removed by `cleanup`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CleanupReport {
+    pub magic_tokens: usize,
+    pub sessions: usize,
+    pub device_codes: usize,
+    pub passkey_challenges: usize,
+    pub api_tokens: usize,
+    pub share_sessions: usize,
+    pub workspace_invites: usize,
+}
+
+impl CleanupReport {
+    pub fn total(&self) -> usize {
+        self.magic_tokens
+            + self.sessions
+            + self.device_codes
+            + self.passkey_challenges
+            + self.api_tokens
+            + self.share_sessions
+            + self.workspace_invites
+    }
+}
+
+/// Result of compacting workspace storage
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompactReport {
+    pub workspaces: usize,
+    pub documents: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// Error types for admin operations
+#[derive(Debug)]
+pub enum AdminError {
+    /// No user or workspace matched
+    NotFound(String),
+    /// Workspace storage could not be read or changed
+    Storage(String),
+    /// Database error
+    DatabaseError(String),
+}
+
+impl std::fmt::Display for AdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminError::NotFound(what) => write!(f, "Not found: {}", what),
+            AdminError::Storage(e) => write!(f, "Storage error: {}", e),
+            AdminError::DatabaseError(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+impl From<DbError> for AdminError {
+    fn from(error: DbError) -> Self {
+        AdminError::DatabaseError(error.to_string())
+    }
+}
+
+/// List all users with how many workspaces they own
+pub fn list_users(repo: &AuthRepo) -> Result<Vec<UserSummary>, AdminError> {
+    let mut owned: HashMap<String, usize> = HashMap::new();
+    for workspace in repo.list_all_workspaces()? {
+        *owned.entry(workspace.user_id).or_default() += 1;
+    }
+
+    Ok(repo
+        .list_users()?
+        .into_iter()
+        .map(|user| UserSummary {
+            workspaces: owned.get(&user.id).copied().unwrap_or(0),
+            id: user.id,
+            email: user.email,
+            created_at: user.created_at.to_rfc3339(),
+            last_login_at: user.last_login_at.map(|t| t.to_rfc3339()),
+        })
+        .collect())
+}
+
+/// List all workspaces with their owner, file count and size on disk
+pub fn list_workspaces(
+    repo: &AuthRepo,
+    store: &WorkspaceStore,
+) -> Result<Vec<WorkspaceUsage>, AdminError> {
+    let emails: HashMap<String, String> = repo
+        .list_users()?
+        .into_iter()
+        .map(|user| (user.id, user.email))
+        .collect();
+
+    Ok(repo
+        .list_all_workspaces()?
+        .into_iter()
+        .map(|workspace| {
+            let size_bytes = store.storage_size(&workspace.id);
+            WorkspaceUsage {
+                // Don't create databases for workspaces that never synced
+                file_count: if size_bytes > 0 {
+                    store.get_file_count(&workspace.id)
+                } else {
+                    0
+                },
+                size_bytes,
+                owner_email: emails.get(&workspace.user_id).cloned(),
+                created_at: workspace.created_at.to_rfc3339(),
+                id: workspace.id,
+                name: workspace.name,
+                owner_id: workspace.user_id,
+            }
+        })
+        .collect())
+}
+
+/// Find a user by ID or email address
+pub fn find_user(repo: &AuthRepo, user: &str) -> Result<UserInfo, AdminError> {
+    let found = if user.contains('@') {
+        repo.get_user_by_email(&user.trim().to_lowercase())?
+    } else {
+        repo.get_user(user)?
+    };
+    found.ok_or_else(|| AdminError::NotFound(format!("user {}", user)))
+}
+
+/// Revoke every session and personal access token of a user.
+///
+/// Open sync connections stay up until they reconnect, at which point the
+/// revoked credentials are rejected.
+pub fn force_logout(repo: &AuthRepo, user: &str) -> Result<LogoutReport, AdminError> {
+    let user = find_user(repo, user)?;
+    let sessions_revoked = repo.delete_user_sessions(&user.id)?;
+    let api_tokens_revoked = repo.delete_user_api_tokens(&user.id)?;
+
+    Ok(LogoutReport {
+        user_id: user.id,
+        email: user.email,
+        sessions_revoked,
+        api_tokens_revoked,
+    })
+}
+
+/// Delete a workspace's records and its database files
+pub fn delete_workspace(
+    repo: &AuthRepo,
+    store: &WorkspaceStore,
+    workspace_id: &str,
+) -> Result<(), AdminError> {
+    if !repo.delete_workspace(workspace_id)? {
+        return Err(AdminError::NotFound(format!("workspace {}", workspace_id)));
+    }
+    store
+        .delete_files(workspace_id)
+        .map_err(|e| AdminError::Storage(e.to_string()))?;
+    Ok(())
+}
+
+/// Remove expired magic links, sessions, device codes, passkey challenges,
+/// API tokens, share sessions and invitations
+pub fn cleanup(repo: &AuthRepo) -> Result<CleanupReport, AdminError> {
+    Ok(CleanupReport {
+        magic_tokens: repo.cleanup_expired_magic_tokens()?,
+        sessions: repo.cleanup_expired_sessions()?,
+        device_codes: repo.cleanup_expired_device_codes()?,
+        passkey_challenges: repo.cleanup_expired_passkey_challenges()?,
+        api_tokens: repo.cleanup_expired_api_tokens()?,
+        share_sessions: repo.cleanup_expired_share_sessions()?,
+        workspace_invites: repo.cleanup_expired_workspace_invites()?,
+    })
+}
+
+/// Most recent audit events, optionally for one user (ID or email address)
+pub fn audit_log(
+    repo: &AuthRepo,
+    user: Option<&str>,
+    limit: usize,
+) -> Result<Vec<AuditEventInfo>, AdminError> {
+    let user_id = match user {
+        Some(user) => Some(find_user(repo, user)?.id),
+        None => None,
+    };
+    Ok(repo.get_audit_events(user_id.as_deref(), limit)?)
+}
+
+/// Compact CRDT update logs, for one workspace or all of them
+pub fn compact(
+    repo: &AuthRepo,
+    store: &WorkspaceStore,
+    workspace_id: Option<&str>,
+    keep_updates: usize,
+) -> Result<CompactReport, AdminError> {
+    let workspace_ids: Vec<String> = match workspace_id {
+        Some(id) => {
+            if repo.get_workspace(id)?.is_none() {
+                return Err(AdminError::NotFound(format!("workspace {}", id)));
+            }
+            vec![id.to_string()]
+        }
+        None => repo
+            .list_all_workspaces()?
+            .into_iter()
+            .map(|w| w.id)
+            .collect(),
+    };
+
+    let mut report = CompactReport::default();
+    for id in workspace_ids {
+        report.bytes_before += store.storage_size(&id);
+        let documents = store
+            .compact(&id, keep_updates)
+            .map_err(AdminError::Storage)?;
+        report.bytes_after += store.storage_size(&id);
+        if documents > 0 {
+            report.workspaces += 1;
+            report.documents += documents;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_database;
+    use crate::sync_v2::StorageCache;
+    use rusqlite::Connection;
+    use std::sync::Arc;
+
+    fn setup() -> (AuthRepo, WorkspaceStore, tempfile::TempDir) {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let store = WorkspaceStore::new(Arc::new(StorageCache::new(dir.path().to_path_buf())));
+        (AuthRepo::new(conn), store, dir)
+    }
+
+    #[test]
+    fn test_force_logout_by_email() {
+        let (repo, _store, _dir) = setup();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+        let device_id = repo.create_device(&user_id, None, None).unwrap();
+        let expires = chrono::Utc::now() + chrono::Duration::days(1);
+        let session = repo.create_session(&user_id, &device_id, expires).unwrap();
+
+        let report = force_logout(&repo, "Test@Example.com").unwrap();
+        assert_eq!(report.user_id, user_id);
+        assert_eq!(report.sessions_revoked, 1);
+        assert!(repo.validate_session(&session).unwrap().is_none());
+
+        assert!(matches!(
+            force_logout(&repo, "nobody@example.com"),
+            Err(AdminError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_workspace_removes_files() {
+        let (repo, store, dir) = setup();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+        let workspace_id = repo.get_or_create_workspace(&user_id, "default").unwrap();
+
+        // Touch the storage so a database file exists
+        assert_eq!(store.get_file_count(&workspace_id), 0);
+        let db_path = dir.path().join(format!("{}.db", workspace_id));
+        assert!(db_path.exists());
+
+        let workspaces = list_workspaces(&repo, &store).unwrap();
+        assert_eq!(workspaces.len(), 1);
+        assert!(workspaces[0].size_bytes > 0);
+        assert_eq!(
+            workspaces[0].owner_email.as_deref(),
+            Some("test@example.com")
+        );
+
+        delete_workspace(&repo, &store, &workspace_id).unwrap();
+        assert!(!db_path.exists());
+        assert!(list_workspaces(&repo, &store).unwrap().is_empty());
+        assert!(matches!(
+            delete_workspace(&repo, &store, &workspace_id),
+            Err(AdminError::NotFound(_))
+        ));
+    }
+}
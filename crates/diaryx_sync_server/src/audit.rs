@@ -0,0 +1,90 @@
+//! Security audit log.
+//!
+//! Handlers call [`record`] for logins, credential changes, share sessions and
+//! snapshot uploads. Events are appended to the `audit_events` table (which
+//! rejects updates and deletes) and mirrored to tracing under the `audit` target.
+
+use crate::db::AuthRepo;
+use tracing::{error, info};
+
+/// Kinds of audited events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    Logout,
+    AccountDeleted,
+    DeviceDeleted,
+    DeviceCodeApproved,
+    DeviceCodeDenied,
+    PasskeyRegistered,
+    PasskeyDeleted,
+    ApiTokenCreated,
+    ApiTokenRevoked,
+    ShareSessionCreated,
+    ShareSessionUpdated,
+    ShareSessionDeleted,
+    SnapshotUploaded,
+    ArchiveImported,
+    SnapshotCreated,
+    SnapshotDeleted,
+    WorkspaceRestored,
+    WebhookCreated,
+    WebhookDeleted,
+    AdminLogout,
+    AdminWorkspaceDeleted,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "auth.login",
+            AuditAction::LoginFailed => "auth.login_failed",
+            AuditAction::Logout => "auth.logout",
+            AuditAction::AccountDeleted => "account.delete",
+            AuditAction::DeviceDeleted => "device.delete",
+            AuditAction::DeviceCodeApproved => "device_code.approve",
+            AuditAction::DeviceCodeDenied => "device_code.deny",
+            AuditAction::PasskeyRegistered => "passkey.register",
+            AuditAction::PasskeyDeleted => "passkey.delete",
+            AuditAction::ApiTokenCreated => "api_token.create",
+            AuditAction::ApiTokenRevoked => "api_token.revoke",
+            AuditAction::ShareSessionCreated => "session.create",
+            AuditAction::ShareSessionUpdated => "session.update",
+            AuditAction::ShareSessionDeleted => "session.delete",
+            AuditAction::SnapshotUploaded => "snapshot.upload",
+            AuditAction::ArchiveImported => "archive.import",
+            AuditAction::SnapshotCreated => "snapshot.create",
+            AuditAction::SnapshotDeleted => "snapshot.delete",
+            AuditAction::WorkspaceRestored => "workspace.restore",
+            AuditAction::WebhookCreated => "webhook.create",
+            AuditAction::WebhookDeleted => "webhook.delete",
+            AuditAction::AdminLogout => "admin.logout",
+            AuditAction::AdminWorkspaceDeleted => "admin.delete_workspace",
+        }
+    }
+}
+
+/// Append an event to the audit log.
+///
+/// Failures are logged rather than returned so auditing never fails the request.
+pub fn record(
+    repo: &AuthRepo,
+    action: AuditAction,
+    user_id: Option<&str>,
+    target: Option<&str>,
+    details: serde_json::Value,
+) {
+    info!(
+        target: "audit",
+        action = action.as_str(),
+        user_id = user_id.unwrap_or("-"),
+        resource = target.unwrap_or("-"),
+        details = %details,
+        "audit event"
+    );
+
+    if let Err(e) = repo.append_audit_event(action.as_str(), user_id, target, &details) {
+        error!("Failed to write audit event {}: {}", action.as_str(), e);
+    }
+}
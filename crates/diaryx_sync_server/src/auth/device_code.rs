@@ -0,0 +1,258 @@
+use super::session::{VerifyResult, issue_session};
+use crate::config::Config;
+use crate::db::{AuthRepo, DeviceCodeInfo, DeviceCodeStatus};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+
+/// How long a device code stays valid
+const DEVICE_CODE_EXPIRY_MINUTES: i64 = 10;
+
+/// Minimum seconds between polls that clients should respect
+pub const DEVICE_CODE_POLL_INTERVAL_SECS: u64 = 5;
+
+/// Device authorization flow (modeled on OAuth 2.0 device authorization, RFC 8628).
+///
+/// A device without a browser or working email requests a code, shows the
+/// short user code, and polls until a signed-in device approves it.
+pub struct DeviceCodeService {
+    repo: Arc<AuthRepo>,
+    config: Arc<Config>,
+}
+
+/// A newly issued device authorization
+#[derive(Debug)]
+pub struct DeviceAuthorization {
+    /// Secret the new device polls with
+    pub device_code: String,
+    /// Short code the user enters on a signed-in device
+    pub user_code: String,
+    /// Seconds until the codes expire
+    pub expires_in: i64,
+    /// Seconds to wait between polls
+    pub interval: u64,
+}
+
+/// Error types for device code operations
+#[derive(Debug)]
+pub enum DeviceCodeError {
+    /// Not yet approved; keep polling
+    AuthorizationPending,
+    /// The user denied the request
+    AccessDenied,
+    /// Code not found or expired
+    ExpiredToken,
+    /// Database error
+    DatabaseError(String),
+}
+
+impl DeviceCodeError {
+    /// OAuth error code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            DeviceCodeError::AuthorizationPending => "authorization_pending",
+            DeviceCodeError::AccessDenied => "access_denied",
+            DeviceCodeError::ExpiredToken => "expired_token",
+            DeviceCodeError::DatabaseError(_) => "server_error",
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceCodeError::AuthorizationPending => write!(f, "Waiting for approval"),
+            DeviceCodeError::AccessDenied => write!(f, "Login request was denied"),
+            DeviceCodeError::ExpiredToken => write!(f, "Invalid or expired device code"),
+            DeviceCodeError::DatabaseError(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DeviceCodeError {}
+
+impl DeviceCodeService {
+    /// Create a new DeviceCodeService
+    pub fn new(repo: Arc<AuthRepo>, config: Arc<Config>) -> Self {
+        Self { repo, config }
+    }
+
+    /// Start a device login
+    pub fn start(&self, device_name: Option<&str>) -> Result<DeviceAuthorization, DeviceCodeError> {
+        let expires_at = Utc::now() + Duration::minutes(DEVICE_CODE_EXPIRY_MINUTES);
+        let (device_code, user_code) = self
+            .repo
+            .create_device_code(device_name, expires_at)
+            .map_err(|e| DeviceCodeError::DatabaseError(e.to_string()))?;
+
+        Ok(DeviceAuthorization {
+            device_code,
+            user_code,
+            expires_in: DEVICE_CODE_EXPIRY_MINUTES * 60,
+            interval: DEVICE_CODE_POLL_INTERVAL_SECS,
+        })
+    }
+
+    /// Look up a pending request by user code (so the approver can see which device it is)
+    pub fn lookup(&self, user_code: &str) -> Result<Option<DeviceCodeInfo>, DeviceCodeError> {
+        self.repo
+            .get_device_code_by_user_code(&normalize_user_code(user_code))
+            .map(|info| info.filter(|i| i.status == DeviceCodeStatus::Pending))
+            .map_err(|e| DeviceCodeError::DatabaseError(e.to_string()))
+    }
+
+    /// Approve or deny a pending request as the signed-in user
+    pub fn resolve(
+        &self,
+        user_code: &str,
+        user_id: &str,
+        approve: bool,
+    ) -> Result<(), DeviceCodeError> {
+        let resolved = self
+            .repo
+            .resolve_device_code(&normalize_user_code(user_code), user_id, approve)
+            .map_err(|e| DeviceCodeError::DatabaseError(e.to_string()))?;
+
+        if resolved {
+            Ok(())
+        } else {
+            Err(DeviceCodeError::ExpiredToken)
+        }
+    }
+
+    /// Poll for the outcome of a device login, creating a session once approved
+    pub fn poll(
+        &self,
+        device_code: &str,
+        user_agent: Option<&str>,
+    ) -> Result<VerifyResult, DeviceCodeError> {
+        let info = self
+            .repo
+            .get_device_code(device_code)
+            .map_err(|e| DeviceCodeError::DatabaseError(e.to_string()))?
+            .ok_or(DeviceCodeError::ExpiredToken)?;
+
+        if info.expires_at <= Utc::now() {
+            self.consume(device_code)?;
+            return Err(DeviceCodeError::ExpiredToken);
+        }
+
+        match (info.status, info.user_id) {
+            (DeviceCodeStatus::Pending, _) => Err(DeviceCodeError::AuthorizationPending),
+            (DeviceCodeStatus::Denied, _) => {
+                self.consume(device_code)?;
+                Err(DeviceCodeError::AccessDenied)
+            }
+            (DeviceCodeStatus::Approved, Some(user_id)) => {
+                // Only the poll that consumes the code gets a session
+                if !self.consume(device_code)? {
+                    return Err(DeviceCodeError::ExpiredToken);
+                }
+
+                let user = self
+                    .repo
+                    .get_user(&user_id)
+                    .map_err(|e| DeviceCodeError::DatabaseError(e.to_string()))?
+                    .ok_or(DeviceCodeError::ExpiredToken)?;
+
+                issue_session(
+                    &self.repo,
+                    &self.config,
+                    &user.id,
+                    &user.email,
+                    info.device_name.as_deref(),
+                    user_agent,
+                )
+                .map_err(|e| DeviceCodeError::DatabaseError(e.to_string()))
+            }
+            (DeviceCodeStatus::Approved, None) => Err(DeviceCodeError::ExpiredToken),
+        }
+    }
+
+    fn consume(&self, device_code: &str) -> Result<bool, DeviceCodeError> {
+        self.repo
+            .delete_device_code(device_code)
+            .map_err(|e| DeviceCodeError::DatabaseError(e.to_string()))
+    }
+}
+
+/// Normalize a user-entered code: uppercase, ignore spaces, and accept it with
+/// or without the dash.
+fn normalize_user_code(code: &str) -> String {
+    let compact: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if compact.len() == 8 {
+        format!("{}-{}", &compact[..4], &compact[4..])
+    } else {
+        compact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_database;
+    use rusqlite::Connection;
+
+    fn setup_test_service() -> (DeviceCodeService, Arc<AuthRepo>) {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        let repo = Arc::new(AuthRepo::new(conn));
+        let config = Arc::new(Config::from_env().unwrap());
+        (DeviceCodeService::new(repo.clone(), config), repo)
+    }
+
+    #[test]
+    fn test_device_code_flow() {
+        let (service, repo) = setup_test_service();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+
+        let auth = service.start(Some("Server")).unwrap();
+        assert_eq!(auth.user_code.len(), 9);
+
+        assert!(matches!(
+            service.poll(&auth.device_code, None),
+            Err(DeviceCodeError::AuthorizationPending)
+        ));
+
+        // Approver may type the code in lowercase without the dash
+        let typed = auth.user_code.replace('-', "").to_lowercase();
+        let pending = service.lookup(&typed).unwrap().unwrap();
+        assert_eq!(pending.device_name.as_deref(), Some("Server"));
+        service.resolve(&typed, &user_id, true).unwrap();
+
+        let result = service.poll(&auth.device_code, None).unwrap();
+        assert_eq!(result.user_id, user_id);
+        assert_eq!(result.email, "test@example.com");
+        assert!(
+            repo.validate_session(&result.session_token)
+                .unwrap()
+                .is_some()
+        );
+
+        // Code is single-use
+        assert!(matches!(
+            service.poll(&auth.device_code, None),
+            Err(DeviceCodeError::ExpiredToken)
+        ));
+    }
+
+    #[test]
+    fn test_device_code_denied() {
+        let (service, repo) = setup_test_service();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+
+        let auth = service.start(None).unwrap();
+        service.resolve(&auth.user_code, &user_id, false).unwrap();
+
+        assert!(matches!(
+            service.poll(&auth.device_code, None),
+            Err(DeviceCodeError::AccessDenied)
+        ));
+        // Already resolved codes can't be approved afterwards
+        assert!(service.resolve(&auth.user_code, &user_id, true).is_err());
+    }
+}
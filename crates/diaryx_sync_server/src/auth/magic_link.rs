@@ -1,3 +1,4 @@
+use super::session::{VerifyResult, issue_session};
 use crate::config::Config;
 use crate::db::AuthRepo;
 use chrono::{Duration, Utc};
@@ -9,15 +10,6 @@ pub struct MagicLinkService {
     config: Arc<Config>,
 }
 
-/// Result of magic link verification
-#[derive(Debug)]
-pub struct VerifyResult {
-    pub session_token: String,
-    pub user_id: String,
-    pub device_id: String,
-    pub email: String,
-}
-
 /// Error types for magic link operations
 #[derive(Debug)]
 pub enum MagicLinkError {
@@ -102,35 +94,15 @@ impl MagicLinkService {
             .get_or_create_user(&email)
             .map_err(|e| MagicLinkError::DatabaseError(e.to_string()))?;
 
-        // Update last login
-        self.repo
-            .update_last_login(&user_id)
-            .map_err(|e| MagicLinkError::DatabaseError(e.to_string()))?;
-
-        // Create device
-        let device_id = self
-            .repo
-            .create_device(&user_id, device_name, user_agent)
-            .map_err(|e| MagicLinkError::DatabaseError(e.to_string()))?;
-
-        // Create session
-        let expires_at = Utc::now() + Duration::days(self.config.session_expiry_days);
-        let session_token = self
-            .repo
-            .create_session(&user_id, &device_id, expires_at)
-            .map_err(|e| MagicLinkError::DatabaseError(e.to_string()))?;
-
-        // Create default workspace if needed
-        self.repo
-            .get_or_create_workspace(&user_id, "default")
-            .map_err(|e| MagicLinkError::DatabaseError(e.to_string()))?;
-
-        Ok(VerifyResult {
-            session_token,
-            user_id,
-            device_id,
-            email,
-        })
+        issue_session(
+            &self.repo,
+            &self.config,
+            &user_id,
+            &email,
+            device_name,
+            user_agent,
+        )
+        .map_err(|e| MagicLinkError::DatabaseError(e.to_string()))
     }
 
     /// Build the magic link URL for a token
@@ -1,4 +1,5 @@
-use crate::db::{AuthRepo, SessionInfo, UserInfo};
+use crate::db::{API_TOKEN_PREFIX, ApiTokenInfo, AuthRepo, SessionInfo, UserInfo};
+use crate::metrics::{AuthFailure, metrics};
 use axum::{
     extract::FromRequestParts,
     http::{StatusCode, request::Parts},
@@ -10,6 +11,8 @@ use std::sync::Arc;
 pub struct AuthUser {
     pub session: SessionInfo,
     pub user: UserInfo,
+    /// Set when authenticated with a personal access token instead of a login session
+    pub api_token: Option<ApiTokenInfo>,
 }
 
 /// Extension trait for extracting auth from requests
@@ -26,10 +29,18 @@ pub struct OptionalAuth(pub Option<AuthUser>);
 
 /// Extractor for required authentication
 ///
-/// Use this for protected endpoints - returns 401 if not authenticated
+/// Use this for protected endpoints - returns 401 if not authenticated.
+/// Only accepts login sessions; personal access tokens get 403.
 #[derive(Debug, Clone)]
 pub struct RequireAuth(pub AuthUser);
 
+/// Extractor for required authentication that also accepts personal access tokens
+///
+/// Use this for workspace data endpoints, and check the token's scope with
+/// `AuthUser::api_token`.
+#[derive(Debug, Clone)]
+pub struct RequireAnyAuth(pub AuthUser);
+
 impl AuthExtractor {
     pub fn new(repo: Arc<AuthRepo>) -> Self {
         Self { repo }
@@ -54,18 +65,11 @@ impl AuthExtractor {
             })
         });
 
-        let token = token?;
-
-        // Validate session
-        let session = self.repo.validate_session(&token).ok()??;
-
-        // Update device last seen
-        let _ = self.repo.update_device_last_seen(&session.device_id);
-
-        // Get user info
-        let user = self.repo.get_user(&session.user_id).ok()??;
-
-        Some(AuthUser { session, user })
+        let user = validate_token(&self.repo, &token?);
+        if user.is_none() {
+            metrics().record_auth_failure(AuthFailure::Token);
+        }
+        user
     }
 }
 
@@ -93,11 +97,31 @@ where
 {
     type Rejection = (StatusCode, &'static str);
 
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let RequireAnyAuth(user) = RequireAnyAuth::from_request_parts(parts, state).await?;
+
+        if user.api_token.is_some() {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "API tokens cannot be used for this endpoint",
+            ));
+        }
+
+        Ok(RequireAuth(user))
+    }
+}
+
+impl<S> FromRequestParts<S> for RequireAnyAuth
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let OptionalAuth(auth) = OptionalAuth::from_request_parts(parts, state).await?;
 
         match auth {
-            Some(user) => Ok(RequireAuth(user)),
+            Some(user) => Ok(RequireAnyAuth(user)),
             None => Err((StatusCode::UNAUTHORIZED, "Authentication required")),
         }
     }
@@ -112,10 +136,39 @@ pub fn extract_token_from_query(query: Option<&str>) -> Option<String> {
     })
 }
 
-/// Validate a token and return the auth user
+/// Validate a session token or personal access token and return the auth user
 pub fn validate_token(repo: &AuthRepo, token: &str) -> Option<AuthUser> {
+    if token.starts_with(API_TOKEN_PREFIX) {
+        return validate_api_token(repo, token);
+    }
+
     let session = repo.validate_session(token).ok()??;
     let _ = repo.update_device_last_seen(&session.device_id);
     let user = repo.get_user(&session.user_id).ok()??;
-    Some(AuthUser { session, user })
+    Some(AuthUser {
+        session,
+        user,
+        api_token: None,
+    })
+}
+
+/// Personal access tokens aren't tied to a device; they get a synthetic session
+/// whose device ID (`token:<id>`) attributes their changes in the CRDT history.
+fn validate_api_token(repo: &AuthRepo, token: &str) -> Option<AuthUser> {
+    let info = repo.validate_api_token(token).ok()??;
+    let user = repo.get_user(&info.user_id).ok()??;
+    let session = SessionInfo {
+        token: token.to_string(),
+        user_id: info.user_id.clone(),
+        device_id: format!("token:{}", info.id),
+        expires_at: info
+            .expires_at
+            .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC),
+        created_at: info.created_at,
+    };
+    Some(AuthUser {
+        session,
+        user,
+        api_token: Some(info),
+    })
 }
@@ -1,7 +1,16 @@
+mod device_code;
 mod magic_link;
 mod middleware;
+mod passkey;
+mod session;
 
+pub use device_code::{
+    DEVICE_CODE_POLL_INTERVAL_SECS, DeviceAuthorization, DeviceCodeError, DeviceCodeService,
+};
 pub use magic_link::{MagicLinkError, MagicLinkService};
 pub use middleware::{
-    AuthExtractor, AuthUser, OptionalAuth, RequireAuth, extract_token_from_query, validate_token,
+    AuthExtractor, AuthUser, OptionalAuth, RequireAnyAuth, RequireAuth, extract_token_from_query,
+    validate_token,
 };
+pub use passkey::{PasskeyError, PasskeyService};
+pub use session::VerifyResult;
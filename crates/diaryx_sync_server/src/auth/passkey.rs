@@ -0,0 +1,352 @@
+use super::session::{VerifyResult, issue_session};
+use crate::config::Config;
+use crate::db::{AuthRepo, PasskeyInfo, UserInfo};
+use base64::Engine;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, CredentialID, Passkey, PasskeyAuthentication, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse, Url, Uuid,
+    Webauthn, WebauthnBuilder,
+};
+
+/// How long a started registration or login stays valid, in minutes
+const CEREMONY_TIMEOUT_MINUTES: i64 = 5;
+
+/// WebAuthn passkey registration and login
+pub struct PasskeyService {
+    webauthn: Webauthn,
+    repo: Arc<AuthRepo>,
+    config: Arc<Config>,
+}
+
+/// A started ceremony, stored in `passkey_challenges` until it is finished
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Ceremony {
+    Registration {
+        user_id: String,
+        state: PasskeyRegistration,
+    },
+    Authentication {
+        user_id: String,
+        state: PasskeyAuthentication,
+    },
+}
+
+/// Error types for passkey operations
+#[derive(Debug)]
+pub enum PasskeyError {
+    /// Challenge not found, expired, or for a different user
+    InvalidChallenge,
+    /// The account has no passkeys registered
+    NoPasskeys,
+    /// The authenticator response failed verification
+    Verification(String),
+    /// Relying party configuration is invalid
+    Config(String),
+    /// Database error
+    DatabaseError(String),
+}
+
+impl std::fmt::Display for PasskeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasskeyError::InvalidChallenge => write!(f, "Invalid or expired passkey challenge"),
+            PasskeyError::NoPasskeys => write!(f, "No passkeys registered for this account"),
+            PasskeyError::Verification(e) => write!(f, "Passkey verification failed: {}", e),
+            PasskeyError::Config(e) => write!(f, "Invalid WebAuthn configuration: {}", e),
+            PasskeyError::DatabaseError(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PasskeyError {}
+
+impl PasskeyService {
+    /// Create a new PasskeyService for the configured relying party
+    pub fn new(repo: Arc<AuthRepo>, config: Arc<Config>) -> Result<Self, PasskeyError> {
+        let origin = Url::parse(&config.webauthn_rp_origin)
+            .map_err(|e| PasskeyError::Config(e.to_string()))?;
+        let webauthn = WebauthnBuilder::new(&config.webauthn_rp_id, &origin)
+            .and_then(|builder| builder.rp_name("Diaryx").build())
+            .map_err(|e| PasskeyError::Config(e.to_string()))?;
+
+        Ok(Self {
+            webauthn,
+            repo,
+            config,
+        })
+    }
+
+    /// Start registering a new passkey for a signed-in user
+    ///
+    /// Returns the challenge ID and the options to pass to `navigator.credentials.create()`.
+    pub fn start_registration(
+        &self,
+        user: &UserInfo,
+    ) -> Result<(String, CreationChallengeResponse), PasskeyError> {
+        let user_uuid =
+            Uuid::parse_str(&user.id).map_err(|e| PasskeyError::DatabaseError(e.to_string()))?;
+
+        // Don't let the same authenticator register twice
+        let existing: Vec<CredentialID> = self
+            .load_passkeys(&user.id)?
+            .into_iter()
+            .map(|(_, passkey)| passkey.cred_id().clone())
+            .collect();
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_registration(user_uuid, &user.email, &user.email, Some(existing))
+            .map_err(|e| PasskeyError::Verification(e.to_string()))?;
+
+        let challenge_id = self.insert_pending(Ceremony::Registration {
+            user_id: user.id.clone(),
+            state,
+        })?;
+        Ok((challenge_id, options))
+    }
+
+    /// Finish registering a passkey, storing the credential
+    pub fn finish_registration(
+        &self,
+        challenge_id: &str,
+        user_id: &str,
+        name: Option<&str>,
+        credential: &RegisterPublicKeyCredential,
+    ) -> Result<PasskeyInfo, PasskeyError> {
+        let state = match self.take_pending(challenge_id)? {
+            Ceremony::Registration {
+                user_id: expected,
+                state,
+            } if expected == user_id => state,
+            _ => return Err(PasskeyError::InvalidChallenge),
+        };
+
+        let passkey = self
+            .webauthn
+            .finish_passkey_registration(credential, &state)
+            .map_err(|e| PasskeyError::Verification(e.to_string()))?;
+
+        let id = encode_credential_id(passkey.cred_id());
+        let serialized = serde_json::to_string(&passkey)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?;
+        self.repo
+            .add_passkey(&id, user_id, name, &serialized)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?;
+
+        self.repo
+            .get_user_passkeys(user_id)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?
+            .into_iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| PasskeyError::DatabaseError("Passkey not saved".to_string()))
+    }
+
+    /// Start a passkey login for the account with this email
+    ///
+    /// Returns the challenge ID and the options to pass to `navigator.credentials.get()`.
+    pub fn start_login(
+        &self,
+        email: &str,
+    ) -> Result<(String, RequestChallengeResponse), PasskeyError> {
+        let user = self
+            .repo
+            .get_user_by_email(&email.trim().to_lowercase())
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?
+            .ok_or(PasskeyError::NoPasskeys)?;
+
+        let passkeys: Vec<Passkey> = self
+            .load_passkeys(&user.id)?
+            .into_iter()
+            .map(|(_, passkey)| passkey)
+            .collect();
+        if passkeys.is_empty() {
+            return Err(PasskeyError::NoPasskeys);
+        }
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|e| PasskeyError::Verification(e.to_string()))?;
+
+        let challenge_id = self.insert_pending(Ceremony::Authentication {
+            user_id: user.id,
+            state,
+        })?;
+        Ok((challenge_id, options))
+    }
+
+    /// Finish a passkey login and create a session
+    pub fn finish_login(
+        &self,
+        challenge_id: &str,
+        credential: &PublicKeyCredential,
+        device_name: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<VerifyResult, PasskeyError> {
+        let (user_id, state) = match self.take_pending(challenge_id)? {
+            Ceremony::Authentication { user_id, state } => (user_id, state),
+            Ceremony::Registration { .. } => return Err(PasskeyError::InvalidChallenge),
+        };
+
+        let result = self
+            .webauthn
+            .finish_passkey_authentication(credential, &state)
+            .map_err(|e| PasskeyError::Verification(e.to_string()))?;
+
+        // Persist the new signature counter so cloned authenticators are detectable
+        let used_id = encode_credential_id(result.cred_id());
+        if let Some((_, mut passkey)) = self
+            .load_passkeys(&user_id)?
+            .into_iter()
+            .find(|(id, _)| *id == used_id)
+        {
+            passkey.update_credential(&result);
+            let serialized = serde_json::to_string(&passkey)
+                .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?;
+            self.repo
+                .record_passkey_use(&used_id, &serialized)
+                .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?;
+        }
+
+        let user = self
+            .repo
+            .get_user(&user_id)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?
+            .ok_or(PasskeyError::InvalidChallenge)?;
+
+        issue_session(
+            &self.repo,
+            &self.config,
+            &user.id,
+            &user.email,
+            device_name,
+            user_agent,
+        )
+        .map_err(|e| PasskeyError::DatabaseError(e.to_string()))
+    }
+
+    /// Load a user's stored passkeys, skipping any that fail to deserialize
+    fn load_passkeys(&self, user_id: &str) -> Result<Vec<(String, Passkey)>, PasskeyError> {
+        let stored = self
+            .repo
+            .get_user_passkeys(user_id)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?;
+
+        Ok(stored
+            .into_iter()
+            .filter_map(|p| {
+                serde_json::from_str(&p.credential)
+                    .ok()
+                    .map(|passkey| (p.id, passkey))
+            })
+            .collect())
+    }
+
+    /// Store a started ceremony in the database, returning its challenge ID
+    fn insert_pending(&self, ceremony: Ceremony) -> Result<String, PasskeyError> {
+        let user_id = match &ceremony {
+            Ceremony::Registration { user_id, .. } | Ceremony::Authentication { user_id, .. } => {
+                user_id.clone()
+            }
+        };
+        let state = serde_json::to_string(&ceremony)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?;
+        let expires_at = Utc::now() + Duration::minutes(CEREMONY_TIMEOUT_MINUTES);
+        self.repo
+            .create_passkey_challenge(&user_id, &state, expires_at)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))
+    }
+
+    /// Remove a started ceremony from the database; it can only be finished once
+    fn take_pending(&self, challenge_id: &str) -> Result<Ceremony, PasskeyError> {
+        let state = self
+            .repo
+            .take_passkey_challenge(challenge_id)
+            .map_err(|e| PasskeyError::DatabaseError(e.to_string()))?
+            .ok_or(PasskeyError::InvalidChallenge)?;
+        serde_json::from_str(&state).map_err(|_| PasskeyError::InvalidChallenge)
+    }
+}
+
+fn encode_credential_id(id: &CredentialID) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(id.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_database;
+    use rusqlite::Connection;
+
+    fn setup_test_service() -> (PasskeyService, Arc<AuthRepo>) {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        let repo = Arc::new(AuthRepo::new(conn));
+        let config = Arc::new(Config::from_env().unwrap());
+        (PasskeyService::new(repo.clone(), config).unwrap(), repo)
+    }
+
+    #[test]
+    fn test_login_requires_registered_passkey() {
+        let (service, repo) = setup_test_service();
+        repo.get_or_create_user("test@example.com").unwrap();
+
+        assert!(matches!(
+            service.start_login("test@example.com"),
+            Err(PasskeyError::NoPasskeys)
+        ));
+        assert!(matches!(
+            service.start_login("unknown@example.com"),
+            Err(PasskeyError::NoPasskeys)
+        ));
+    }
+
+    #[test]
+    fn test_challenge_finishes_on_any_instance() {
+        let (first, repo) = setup_test_service();
+        let config = Arc::new(Config::from_env().unwrap());
+        let second = PasskeyService::new(repo.clone(), config).unwrap();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+        let user = repo.get_user(&user_id).unwrap().unwrap();
+
+        let (challenge_id, _options) = first.start_registration(&user).unwrap();
+        assert!(matches!(
+            second.take_pending(&challenge_id),
+            Ok(Ceremony::Registration { user_id: id, .. }) if id == user_id
+        ));
+
+        // A challenge can only be used once
+        assert!(matches!(
+            first.take_pending(&challenge_id),
+            Err(PasskeyError::InvalidChallenge)
+        ));
+    }
+
+    #[test]
+    fn test_expired_challenge_rejected() {
+        let (service, repo) = setup_test_service();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+        let challenge_id = repo
+            .create_passkey_challenge(&user_id, "{}", Utc::now() - Duration::minutes(1))
+            .unwrap();
+
+        assert!(matches!(
+            service.take_pending(&challenge_id),
+            Err(PasskeyError::InvalidChallenge)
+        ));
+        assert_eq!(repo.cleanup_expired_passkey_challenges().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_unknown_challenge_rejected() {
+        let (service, _repo) = setup_test_service();
+        assert!(matches!(
+            service.take_pending("missing"),
+            Err(PasskeyError::InvalidChallenge)
+        ));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::config::Config;
+use crate::db::{AuthRepo, DbError};
+use chrono::{Duration, Utc};
+
+/// Result of a successful login (magic link, device code, or passkey)
+#[derive(Debug)]
+pub struct VerifyResult {
+    pub session_token: String,
+    pub user_id: String,
+    pub device_id: String,
+    pub email: String,
+}
+
+/// Create a device and session for a user who has just proven who they are.
+///
+/// Also creates the user's default workspace if it doesn't exist yet.
+pub fn issue_session(
+    repo: &AuthRepo,
+    config: &Config,
+    user_id: &str,
+    email: &str,
+    device_name: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<VerifyResult, DbError> {
+    // Update last login
+    repo.update_last_login(user_id)?;
+
+    // Create device
+    let device_id = repo.create_device(user_id, device_name, user_agent)?;
+
+    // Create session
+    let expires_at = Utc::now() + Duration::days(config.session_expiry_days);
+    let session_token = repo.create_session(user_id, &device_id, expires_at)?;
+
+    // Create default workspace if needed
+    repo.get_or_create_workspace(user_id, "default")?;
+
+    Ok(VerifyResult {
+        session_token,
+        user_id: user_id.to_string(),
+        device_id,
+        email: email.to_string(),
+    })
+}
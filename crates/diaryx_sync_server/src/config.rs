@@ -1,5 +1,6 @@
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Server configuration loaded from environment variables
 #[derive(Debug, Clone)]
@@ -10,6 +11,11 @@ pub struct Config {
     pub port: u16,
     /// Database file path (default: ./diaryx_sync.db)
     pub database_path: PathBuf,
+    /// PostgreSQL URL for auth data and CRDT storage; when set, replaces the
+    /// SQLite database and per-workspace files (requires the `postgres` feature)
+    pub database_url: Option<String>,
+    /// Maximum pooled PostgreSQL connections (default: 16)
+    pub database_max_connections: u32,
     /// Base URL for magic link verification (e.g., https://app.diaryx.org)
     pub app_base_url: String,
     /// SMTP configuration for sending emails
@@ -20,6 +26,21 @@ pub struct Config {
     pub magic_link_expiry_minutes: i64,
     /// CORS allowed origins (comma-separated)
     pub cors_origins: Vec<String>,
+    /// WebAuthn relying party ID, i.e. the domain passkeys are bound to
+    /// (default: host of `app_base_url`)
+    pub webauthn_rp_id: String,
+    /// Origin passkey ceremonies must come from (default: `app_base_url`)
+    pub webauthn_rp_origin: String,
+    /// Emails of users allowed to call `/api/admin` endpoints (comma-separated)
+    pub admin_emails: Vec<String>,
+    /// Bearer token required to scrape `/metrics` (unset: `/metrics` is disabled)
+    pub metrics_token: Option<String>,
+    /// Server-side snapshots kept per workspace; older ones are pruned (default: 20)
+    pub snapshot_retention: usize,
+    /// Request and sync rate limits
+    pub rate_limits: RateLimitConfig,
+    /// Outbound webhook delivery
+    pub webhooks: WebhookConfig,
 }
 
 /// SMTP configuration for email sending
@@ -39,6 +60,131 @@ pub struct SmtpConfig {
     pub from_name: String,
 }
 
+/// Rate limits for HTTP routes and sync connections (0 disables a limit)
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// `/auth/*` requests per minute per IP (default: 30)
+    pub auth_per_minute: u32,
+    /// `/api/*` requests per minute per IP (default: 600)
+    pub api_per_minute: u32,
+    /// Authenticated requests per minute per user, across IPs (default: 1200)
+    pub user_per_minute: u32,
+    /// `/sync2` WebSocket upgrades per minute per IP (default: 60)
+    pub sync_connects_per_minute: u32,
+    /// Largest sync update or control message in bytes (default: 8 MiB)
+    pub sync_max_message_bytes: usize,
+    /// Sustained sync updates per second per connection (default: 100)
+    pub sync_updates_per_second: u32,
+    /// Sync updates a connection may send in a burst, e.g. during the initial
+    /// sync (default: 2000)
+    pub sync_update_burst: u32,
+    /// Take the client IP from `X-Forwarded-For` (default: false; enable only
+    /// behind a reverse proxy that sets it)
+    pub trust_proxy_headers: bool,
+    /// Reverse proxies in front of the server that append to
+    /// `X-Forwarded-For`; the client IP is the entry the outermost one added
+    /// (default: 1)
+    pub trusted_proxy_hops: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            auth_per_minute: 30,
+            api_per_minute: 600,
+            user_per_minute: 1200,
+            sync_connects_per_minute: 60,
+            sync_max_message_bytes: 8 * 1024 * 1024,
+            sync_updates_per_second: 100,
+            sync_update_burst: 2000,
+            trust_proxy_headers: false,
+            trusted_proxy_hops: 1,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            auth_per_minute: env_or("RATE_LIMIT_AUTH_PER_MINUTE", defaults.auth_per_minute),
+            api_per_minute: env_or("RATE_LIMIT_API_PER_MINUTE", defaults.api_per_minute),
+            user_per_minute: env_or("RATE_LIMIT_USER_PER_MINUTE", defaults.user_per_minute),
+            sync_connects_per_minute: env_or(
+                "RATE_LIMIT_SYNC_CONNECT_PER_MINUTE",
+                defaults.sync_connects_per_minute,
+            ),
+            sync_max_message_bytes: env_or(
+                "SYNC_MAX_MESSAGE_BYTES",
+                defaults.sync_max_message_bytes,
+            ),
+            sync_updates_per_second: env_or(
+                "SYNC_UPDATES_PER_SECOND",
+                defaults.sync_updates_per_second,
+            ),
+            sync_update_burst: env_or("SYNC_UPDATE_BURST", defaults.sync_update_burst),
+            trust_proxy_headers: env_or("TRUST_PROXY_HEADERS", defaults.trust_proxy_headers),
+            trusted_proxy_hops: env_or("TRUSTED_PROXY_HOPS", defaults.trusted_proxy_hops),
+        }
+    }
+}
+
+/// Debouncing and retries for outbound webhooks
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Quiet period after a workspace's last change before its webhooks are
+    /// called (default: 5s)
+    pub debounce: Duration,
+    /// Longest a change waits while edits keep coming in (default: 60s)
+    pub max_delay: Duration,
+    /// Delivery attempts before giving up (default: 5)
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubles after every failed attempt
+    /// (default: 10s)
+    pub retry_delay: Duration,
+    /// Timeout for each delivery request (default: 10s)
+    pub timeout: Duration,
+    /// Allow webhook URLs on loopback and private networks, e.g. a receiver
+    /// on the same host (default: false)
+    pub allow_private_urls: bool,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_secs(5),
+            max_delay: Duration::from_secs(60),
+            max_attempts: 5,
+            retry_delay: Duration::from_secs(10),
+            timeout: Duration::from_secs(10),
+            allow_private_urls: false,
+        }
+    }
+}
+
+impl WebhookConfig {
+    fn from_env() -> Self {
+        let defaults = Self::default();
+        let secs = |name, default: Duration| Duration::from_secs(env_or(name, default.as_secs()));
+        Self {
+            debounce: secs("WEBHOOK_DEBOUNCE_SECS", defaults.debounce),
+            max_delay: secs("WEBHOOK_MAX_DELAY_SECS", defaults.max_delay),
+            max_attempts: env_or("WEBHOOK_MAX_ATTEMPTS", defaults.max_attempts).max(1),
+            retry_delay: secs("WEBHOOK_RETRY_DELAY_SECS", defaults.retry_delay),
+            timeout: secs("WEBHOOK_TIMEOUT_SECS", defaults.timeout),
+            allow_private_urls: env_or("WEBHOOK_ALLOW_PRIVATE_URLS", defaults.allow_private_urls),
+        }
+    }
+}
+
+/// Parse an environment variable, falling back to `default` if unset or invalid
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
 impl Config {
     /// Load configuration from environment variables
     pub fn from_env() -> Result<Self, ConfigError> {
@@ -54,6 +200,17 @@ impl Config {
             env::var("DATABASE_PATH").unwrap_or_else(|_| "./diaryx_sync.db".to_string()),
         );
 
+        let database_url = env::var("DATABASE_URL")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        if let Some(url) = &database_url
+            && !(url.starts_with("postgres://") || url.starts_with("postgresql://"))
+        {
+            return Err(ConfigError::InvalidDatabaseUrl);
+        }
+        let database_max_connections = env_or("DATABASE_MAX_CONNECTIONS", 16);
+
         let app_base_url =
             env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:5174".to_string());
 
@@ -87,15 +244,42 @@ impl Config {
             .filter(|s| !s.is_empty())
             .collect();
 
+        let webauthn_rp_origin = env::var("WEBAUTHN_RP_ORIGIN")
+            .unwrap_or_else(|_| app_base_url.trim_end_matches('/').to_string());
+        let webauthn_rp_id =
+            env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| url_host(&webauthn_rp_origin));
+
+        let admin_emails = env::var("ADMIN_EMAILS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let metrics_token = env::var("METRICS_TOKEN")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+
+        let snapshot_retention = env_or("SNAPSHOT_RETENTION", 20);
+
         Ok(Config {
             host,
             port,
             database_path,
+            database_url,
+            database_max_connections,
             app_base_url,
             smtp,
             session_expiry_days,
             magic_link_expiry_minutes,
             cors_origins,
+            webauthn_rp_id,
+            webauthn_rp_origin,
+            admin_emails,
+            metrics_token,
+            snapshot_retention,
+            rate_limits: RateLimitConfig::from_env(),
+            webhooks: WebhookConfig::from_env(),
         })
     }
 
@@ -104,16 +288,30 @@ impl Config {
         !self.smtp.username.is_empty() && !self.smtp.password.is_empty()
     }
 
+    /// Check if a user may use the admin endpoints
+    pub fn is_admin(&self, email: &str) -> bool {
+        let email = email.to_lowercase();
+        self.admin_emails.contains(&email)
+    }
+
     /// Get the server address
     pub fn server_addr(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
 }
 
+/// Extract the host from a URL like `https://app.diaryx.org:8443/path`
+fn url_host(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = without_scheme.split('/').next().unwrap_or_default();
+    authority.split(':').next().unwrap_or_default().to_string()
+}
+
 #[derive(Debug)]
 pub enum ConfigError {
     InvalidPort,
     InvalidSmtpPort,
+    InvalidDatabaseUrl,
 }
 
 impl std::fmt::Display for ConfigError {
@@ -121,6 +319,9 @@ impl std::fmt::Display for ConfigError {
         match self {
             ConfigError::InvalidPort => write!(f, "Invalid PORT environment variable"),
             ConfigError::InvalidSmtpPort => write!(f, "Invalid SMTP_PORT environment variable"),
+            ConfigError::InvalidDatabaseUrl => {
+                write!(f, "DATABASE_URL must be a postgres:// or postgresql:// URL")
+            }
         }
     }
 }
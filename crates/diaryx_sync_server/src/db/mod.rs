@@ -1,5 +1,20 @@
+#[cfg(feature = "postgres")]
+mod postgres;
 mod repo;
 mod schema;
+mod store;
 
-pub use repo::{AuthRepo, DeviceInfo, SessionInfo, ShareSessionInfo, UserInfo, WorkspaceInfo};
-pub use schema::init_database;
+#[cfg(feature = "postgres")]
+pub(crate) use postgres::blocking;
+#[cfg(all(test, feature = "postgres"))]
+pub(crate) use postgres::test_pool;
+#[cfg(feature = "postgres")]
+pub use postgres::{MIGRATIONS, PostgresAuthStore, PostgresPool, connect_postgres, migrate};
+pub use repo::{
+    API_TOKEN_PREFIX, ApiTokenInfo, ApiTokenPermission, AuditEventInfo, DeviceCodeInfo,
+    DeviceCodeStatus, DeviceInfo, PasskeyInfo, SessionInfo, ShareSessionInfo, SqliteAuthStore,
+    UserInfo, WEBHOOK_SECRET_PREFIX, WebhookDeliveryInfo, WebhookDeliveryStatus, WebhookInfo,
+    WorkspaceInfo, WorkspaceInviteInfo, WorkspaceMemberInfo, WorkspaceRole,
+};
+pub use schema::{SCHEMA_VERSION, init_database, schema_version};
+pub use store::{AuthRepo, AuthStore, DbError};
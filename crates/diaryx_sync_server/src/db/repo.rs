@@ -1,7 +1,11 @@
 use chrono::{DateTime, Utc};
+use diaryx_core::crdt::{ScopeEntry, SharePermission, ShareScope};
 use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 
+use super::store::{AuthStore, DbError};
+
 /// User information
 #[derive(Debug, Clone)]
 pub struct UserInfo {
@@ -32,6 +36,127 @@ pub struct SessionInfo {
     pub created_at: DateTime<Utc>,
 }
 
+/// What a personal access token may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiTokenPermission {
+    /// Read workspaces, sync read-only, and download snapshots
+    Read,
+    /// Everything the user's workspace role allows
+    Write,
+    /// Only download and upload snapshots (e.g. for backups); no live sync
+    Snapshot,
+}
+
+impl ApiTokenPermission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenPermission::Read => "read",
+            ApiTokenPermission::Write => "write",
+            ApiTokenPermission::Snapshot => "snapshot",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "read" => Some(ApiTokenPermission::Read),
+            "write" => Some(ApiTokenPermission::Write),
+            "snapshot" => Some(ApiTokenPermission::Snapshot),
+            _ => None,
+        }
+    }
+
+    /// Whether the token may open sync connections and read workspace data
+    pub fn can_sync(&self) -> bool {
+        !matches!(self, ApiTokenPermission::Snapshot)
+    }
+
+    /// Whether the token may change documents over sync
+    pub fn can_write(&self) -> bool {
+        matches!(self, ApiTokenPermission::Write)
+    }
+
+    /// Whether the token may replace or merge workspace snapshots
+    pub fn can_upload_snapshot(&self) -> bool {
+        !matches!(self, ApiTokenPermission::Read)
+    }
+}
+
+/// Personal access token information
+#[derive(Debug, Clone)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// Workspace the token is limited to, or `None` for all of the user's workspaces
+    pub workspace_id: Option<String>,
+    pub permission: ApiTokenPermission,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiTokenInfo {
+    /// Whether the token's scope includes this workspace
+    pub fn covers_workspace(&self, workspace_id: &str) -> bool {
+        self.workspace_id
+            .as_deref()
+            .is_none_or(|id| id == workspace_id)
+    }
+}
+
+/// Status of a device authorization code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceCodeStatus {
+    /// Waiting for a signed-in device to approve or deny
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl DeviceCodeStatus {
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            DeviceCodeStatus::Pending => "pending",
+            DeviceCodeStatus::Approved => "approved",
+            DeviceCodeStatus::Denied => "denied",
+        }
+    }
+
+    pub(super) fn parse(s: &str) -> Self {
+        match s {
+            "approved" => DeviceCodeStatus::Approved,
+            "denied" => DeviceCodeStatus::Denied,
+            _ => DeviceCodeStatus::Pending,
+        }
+    }
+}
+
+/// Device authorization code information
+#[derive(Debug, Clone)]
+pub struct DeviceCodeInfo {
+    pub device_code: String,
+    pub user_code: String,
+    pub device_name: Option<String>,
+    /// User who approved or denied the code
+    pub user_id: Option<String>,
+    pub status: DeviceCodeStatus,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Stored passkey credential
+#[derive(Debug, Clone)]
+pub struct PasskeyInfo {
+    /// Credential ID (base64url)
+    pub id: String,
+    pub user_id: String,
+    pub name: Option<String>,
+    /// Serialized credential (JSON)
+    pub credential: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
 /// Workspace information
 #[derive(Debug, Clone)]
 pub struct WorkspaceInfo {
@@ -41,6 +166,62 @@ pub struct WorkspaceInfo {
     pub created_at: DateTime<Utc>,
 }
 
+/// Role of a user in a workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceRole {
+    /// Created the workspace; can manage members
+    Owner,
+    /// Can read and write
+    Editor,
+    /// Can only read
+    Viewer,
+}
+
+impl WorkspaceRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkspaceRole::Owner => "owner",
+            WorkspaceRole::Editor => "editor",
+            WorkspaceRole::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "owner" => Some(WorkspaceRole::Owner),
+            "editor" => Some(WorkspaceRole::Editor),
+            "viewer" => Some(WorkspaceRole::Viewer),
+            _ => None,
+        }
+    }
+
+    /// Whether this role may change workspace content
+    pub fn can_write(&self) -> bool {
+        !matches!(self, WorkspaceRole::Viewer)
+    }
+}
+
+/// Workspace member information (including the owner)
+#[derive(Debug, Clone)]
+pub struct WorkspaceMemberInfo {
+    pub user_id: String,
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Pending workspace invitation
+#[derive(Debug, Clone)]
+pub struct WorkspaceInviteInfo {
+    pub token: String,
+    pub workspace_id: String,
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub invited_by: String,
+    pub expires_at: DateTime<Utc>,
+}
+
 /// Share session information
 #[derive(Debug, Clone)]
 pub struct ShareSessionInfo {
@@ -48,64 +229,145 @@ pub struct ShareSessionInfo {
     pub workspace_id: String,
     pub owner_user_id: String,
     pub read_only: bool,
+    /// Paths shared by this session, or `None` for the whole workspace
+    pub scope: Option<ShareScope>,
     pub created_at: DateTime<Utc>,
     pub expires_at: Option<DateTime<Utc>>,
 }
 
-/// Authentication repository for database operations
+/// Entry in the security audit log
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEventInfo {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub action: String,
+    pub user_id: Option<String>,
+    pub target: Option<String>,
+    pub details: serde_json::Value,
+}
+
+/// Webhook registered on a workspace
+#[derive(Debug, Clone)]
+pub struct WebhookInfo {
+    pub id: String,
+    pub workspace_id: String,
+    pub url: String,
+    /// Key payloads are signed with (HMAC-SHA256)
+    pub secret: String,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// State of a webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDeliveryStatus {
+    /// Not delivered yet; more attempts will follow
+    Pending,
+    /// The receiver answered with a 2xx status
+    Delivered,
+    /// Every attempt failed
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(WebhookDeliveryStatus::Pending),
+            "delivered" => Some(WebhookDeliveryStatus::Delivered),
+            "failed" => Some(WebhookDeliveryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Entry in a webhook's delivery log
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDeliveryInfo {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if the receiver answered
+    pub response_status: Option<u16>,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Prefix that distinguishes personal access tokens from session tokens
+pub const API_TOKEN_PREFIX: &str = "dxt_";
+
+/// Prefix of generated webhook signing secrets
+pub const WEBHOOK_SECRET_PREFIX: &str = "whsec_";
+
+/// SQLite implementation of [`AuthStore`]
 #[derive(Clone)]
-pub struct AuthRepo {
+pub struct SqliteAuthStore {
     conn: Arc<Mutex<Connection>>,
 }
 
-impl AuthRepo {
-    /// Create a new AuthRepo with the given connection
+impl SqliteAuthStore {
+    /// Wrap a connection whose schema was set up with `init_database`
     pub fn new(conn: Connection) -> Self {
         Self {
             conn: Arc::new(Mutex::new(conn)),
         }
     }
+}
 
+impl AuthStore for SqliteAuthStore {
     // ===== User operations =====
 
-    /// Get a user by ID
-    pub fn get_user(&self, user_id: &str) -> Result<Option<UserInfo>, rusqlite::Error> {
+    fn get_user(&self, user_id: &str) -> Result<Option<UserInfo>, DbError> {
         let conn = self.conn.lock().unwrap();
-        conn.query_row(
-            "SELECT id, email, created_at, last_login_at FROM users WHERE id = ?",
-            [user_id],
-            |row| {
-                Ok(UserInfo {
-                    id: row.get(0)?,
-                    email: row.get(1)?,
-                    created_at: timestamp_to_datetime(row.get(2)?),
-                    last_login_at: row.get::<_, Option<i64>>(3)?.map(timestamp_to_datetime),
-                })
-            },
-        )
-        .optional()
+        Ok(conn
+            .query_row(
+                "SELECT id, email, created_at, last_login_at FROM users WHERE id = ?",
+                [user_id],
+                |row| {
+                    Ok(UserInfo {
+                        id: row.get(0)?,
+                        email: row.get(1)?,
+                        created_at: timestamp_to_datetime(row.get(2)?),
+                        last_login_at: row.get::<_, Option<i64>>(3)?.map(timestamp_to_datetime),
+                    })
+                },
+            )
+            .optional()?)
     }
 
-    /// Get a user by email
-    pub fn get_user_by_email(&self, email: &str) -> Result<Option<UserInfo>, rusqlite::Error> {
+    fn get_user_by_email(&self, email: &str) -> Result<Option<UserInfo>, DbError> {
         let conn = self.conn.lock().unwrap();
-        conn.query_row(
-            "SELECT id, email, created_at, last_login_at FROM users WHERE email = ?",
-            [email],
-            |row| {
-                Ok(UserInfo {
-                    id: row.get(0)?,
-                    email: row.get(1)?,
-                    created_at: timestamp_to_datetime(row.get(2)?),
-                    last_login_at: row.get::<_, Option<i64>>(3)?.map(timestamp_to_datetime),
-                })
-            },
-        )
-        .optional()
+        Ok(conn
+            .query_row(
+                "SELECT id, email, created_at, last_login_at FROM users WHERE email = ?",
+                [email],
+                |row| {
+                    Ok(UserInfo {
+                        id: row.get(0)?,
+                        email: row.get(1)?,
+                        created_at: timestamp_to_datetime(row.get(2)?),
+                        last_login_at: row.get::<_, Option<i64>>(3)?.map(timestamp_to_datetime),
+                    })
+                },
+            )
+            .optional()?)
     }
 
-    /// Create or get a user by email (returns user ID)
-    pub fn get_or_create_user(&self, email: &str) -> Result<String, rusqlite::Error> {
+    fn get_or_create_user(&self, email: &str) -> Result<String, DbError> {
         let conn = self.conn.lock().unwrap();
 
         // Try to get existing user
@@ -130,8 +392,7 @@ impl AuthRepo {
         Ok(user_id)
     }
 
-    /// Update user's last login time
-    pub fn update_last_login(&self, user_id: &str) -> Result<(), rusqlite::Error> {
+    fn update_last_login(&self, user_id: &str) -> Result<(), DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
         conn.execute(
@@ -141,9 +402,28 @@ impl AuthRepo {
         Ok(())
     }
 
-    /// Delete a user and all related data (devices, sessions, workspaces, share_sessions cascade)
-    /// Returns the list of workspace IDs that were deleted (for file cleanup)
-    pub fn delete_user(&self, user_id: &str) -> Result<Vec<String>, rusqlite::Error> {
+    fn list_users(&self) -> Result<Vec<UserInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, email, created_at, last_login_at FROM users ORDER BY created_at, email",
+        )?;
+
+        let users = stmt
+            .query_map([], |row| {
+                Ok(UserInfo {
+                    id: row.get(0)?,
+                    email: row.get(1)?,
+                    created_at: timestamp_to_datetime(row.get(2)?),
+                    last_login_at: row.get::<_, Option<i64>>(3)?.map(timestamp_to_datetime),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(users)
+    }
+
+    fn delete_user(&self, user_id: &str) -> Result<Vec<String>, DbError> {
         let conn = self.conn.lock().unwrap();
 
         // First, get the user's workspace IDs for file cleanup
@@ -161,13 +441,12 @@ impl AuthRepo {
 
     // ===== Device operations =====
 
-    /// Create a new device
-    pub fn create_device(
+    fn create_device(
         &self,
         user_id: &str,
         name: Option<&str>,
         user_agent: Option<&str>,
-    ) -> Result<String, rusqlite::Error> {
+    ) -> Result<String, DbError> {
         let conn = self.conn.lock().unwrap();
         let device_id = uuid::Uuid::new_v4().to_string();
         let now = Utc::now().timestamp();
@@ -180,8 +459,7 @@ impl AuthRepo {
         Ok(device_id)
     }
 
-    /// Get devices for a user
-    pub fn get_user_devices(&self, user_id: &str) -> Result<Vec<DeviceInfo>, rusqlite::Error> {
+    fn get_user_devices(&self, user_id: &str) -> Result<Vec<DeviceInfo>, DbError> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, user_id, name, user_agent, created_at, last_seen_at
@@ -205,8 +483,7 @@ impl AuthRepo {
         Ok(devices)
     }
 
-    /// Update device last seen time
-    pub fn update_device_last_seen(&self, device_id: &str) -> Result<(), rusqlite::Error> {
+    fn update_device_last_seen(&self, device_id: &str) -> Result<(), DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
         conn.execute(
@@ -216,8 +493,7 @@ impl AuthRepo {
         Ok(())
     }
 
-    /// Delete a device (and its sessions)
-    pub fn delete_device(&self, device_id: &str) -> Result<(), rusqlite::Error> {
+    fn delete_device(&self, device_id: &str) -> Result<(), DbError> {
         let conn = self.conn.lock().unwrap();
         conn.execute("DELETE FROM devices WHERE id = ?", [device_id])?;
         Ok(())
@@ -225,12 +501,11 @@ impl AuthRepo {
 
     // ===== Magic link operations =====
 
-    /// Create a magic link token
-    pub fn create_magic_token(
+    fn create_magic_token(
         &self,
         email: &str,
         expires_at: DateTime<Utc>,
-    ) -> Result<String, rusqlite::Error> {
+    ) -> Result<String, DbError> {
         let conn = self.conn.lock().unwrap();
 
         // Generate secure random token
@@ -245,8 +520,7 @@ impl AuthRepo {
         Ok(token)
     }
 
-    /// Verify and consume a magic token (returns email if valid)
-    pub fn verify_magic_token(&self, token: &str) -> Result<Option<String>, rusqlite::Error> {
+    fn verify_magic_token(&self, token: &str) -> Result<Option<String>, DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
 
@@ -267,37 +541,30 @@ impl AuthRepo {
         Ok(result)
     }
 
-    /// Clean up expired magic tokens
-    pub fn cleanup_expired_magic_tokens(&self) -> Result<usize, rusqlite::Error> {
+    fn cleanup_expired_magic_tokens(&self) -> Result<usize, DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
         let deleted = conn.execute("DELETE FROM magic_tokens WHERE expires_at < ?", [now])?;
         Ok(deleted)
     }
 
-    /// Count recent magic tokens for an email (for rate limiting)
-    pub fn count_recent_magic_tokens(
-        &self,
-        email: &str,
-        since: DateTime<Utc>,
-    ) -> Result<i64, rusqlite::Error> {
+    fn count_recent_magic_tokens(&self, email: &str, since: DateTime<Utc>) -> Result<i64, DbError> {
         let conn = self.conn.lock().unwrap();
-        conn.query_row(
+        Ok(conn.query_row(
             "SELECT COUNT(*) FROM magic_tokens WHERE email = ? AND created_at > ?",
             params![email, since.timestamp()],
             |row| row.get(0),
-        )
+        )?)
     }
 
     // ===== Session operations =====
 
-    /// Create a new auth session
-    pub fn create_session(
+    fn create_session(
         &self,
         user_id: &str,
         device_id: &str,
         expires_at: DateTime<Utc>,
-    ) -> Result<String, rusqlite::Error> {
+    ) -> Result<String, DbError> {
         let conn = self.conn.lock().unwrap();
         let token = generate_secure_token();
         let now = Utc::now().timestamp();
@@ -310,217 +577,772 @@ impl AuthRepo {
         Ok(token)
     }
 
-    /// Validate a session token (returns session info if valid)
-    pub fn validate_session(&self, token: &str) -> Result<Option<SessionInfo>, rusqlite::Error> {
+    fn validate_session(&self, token: &str) -> Result<Option<SessionInfo>, DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
 
-        conn.query_row(
-            "SELECT token, user_id, device_id, expires_at, created_at
+        Ok(conn
+            .query_row(
+                "SELECT token, user_id, device_id, expires_at, created_at
              FROM auth_sessions WHERE token = ? AND expires_at > ?",
-            params![token, now],
-            |row| {
-                Ok(SessionInfo {
-                    token: row.get(0)?,
-                    user_id: row.get(1)?,
-                    device_id: row.get(2)?,
-                    expires_at: timestamp_to_datetime(row.get(3)?),
-                    created_at: timestamp_to_datetime(row.get(4)?),
-                })
-            },
-        )
-        .optional()
+                params![token, now],
+                |row| {
+                    Ok(SessionInfo {
+                        token: row.get(0)?,
+                        user_id: row.get(1)?,
+                        device_id: row.get(2)?,
+                        expires_at: timestamp_to_datetime(row.get(3)?),
+                        created_at: timestamp_to_datetime(row.get(4)?),
+                    })
+                },
+            )
+            .optional()?)
     }
 
-    /// Delete a session
-    pub fn delete_session(&self, token: &str) -> Result<(), rusqlite::Error> {
+    fn delete_session(&self, token: &str) -> Result<(), DbError> {
         let conn = self.conn.lock().unwrap();
         conn.execute("DELETE FROM auth_sessions WHERE token = ?", [token])?;
         Ok(())
     }
 
-    /// Delete all sessions for a user
-    pub fn delete_user_sessions(&self, user_id: &str) -> Result<(), rusqlite::Error> {
+    fn delete_user_sessions(&self, user_id: &str) -> Result<usize, DbError> {
         let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM auth_sessions WHERE user_id = ?", [user_id])?;
-        Ok(())
+        let deleted = conn.execute("DELETE FROM auth_sessions WHERE user_id = ?", [user_id])?;
+        Ok(deleted)
     }
 
-    /// Clean up expired sessions
-    pub fn cleanup_expired_sessions(&self) -> Result<usize, rusqlite::Error> {
+    fn cleanup_expired_sessions(&self) -> Result<usize, DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
         let deleted = conn.execute("DELETE FROM auth_sessions WHERE expires_at < ?", [now])?;
         Ok(deleted)
     }
 
-    // ===== Workspace operations =====
+    // ===== API token operations =====
 
-    /// Get or create a workspace for a user
-    pub fn get_or_create_workspace(
+    fn create_api_token(
         &self,
         user_id: &str,
         name: &str,
-    ) -> Result<String, rusqlite::Error> {
+        workspace_id: Option<&str>,
+        permission: ApiTokenPermission,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(String, String), DbError> {
         let conn = self.conn.lock().unwrap();
-
-        // Try to get existing workspace
-        if let Some(workspace_id) = conn
-            .query_row(
-                "SELECT id FROM user_workspaces WHERE user_id = ? AND name = ?",
-                params![user_id, name],
-                |row| row.get::<_, String>(0),
-            )
-            .optional()?
-        {
-            return Ok(workspace_id);
-        }
-
-        // Create new workspace
-        let workspace_id = uuid::Uuid::new_v4().to_string();
+        let id = uuid::Uuid::new_v4().to_string();
+        let token = format!("{}{}", API_TOKEN_PREFIX, generate_secure_token());
         let now = Utc::now().timestamp();
 
         conn.execute(
-            "INSERT INTO user_workspaces (id, user_id, name, created_at) VALUES (?, ?, ?, ?)",
-            params![workspace_id, user_id, name, now],
+            "INSERT INTO api_tokens (id, token, user_id, name, workspace_id, permission, created_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                id,
+                token,
+                user_id,
+                name,
+                workspace_id,
+                permission.as_str(),
+                now,
+                expires_at.map(|t| t.timestamp())
+            ],
         )?;
 
-        Ok(workspace_id)
+        Ok((id, token))
     }
 
-    /// Get all workspaces for a user
-    pub fn get_user_workspaces(
-        &self,
-        user_id: &str,
-    ) -> Result<Vec<WorkspaceInfo>, rusqlite::Error> {
+    fn validate_api_token(&self, token: &str) -> Result<Option<ApiTokenInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+
+        let info = conn
+            .query_row(
+                "SELECT id, user_id, name, workspace_id, permission, created_at, last_used_at, expires_at
+                 FROM api_tokens WHERE token = ? AND (expires_at IS NULL OR expires_at > ?)",
+                params![token, now],
+                api_token_from_row,
+            )
+            .optional()?;
+
+        if let Some(info) = &info {
+            conn.execute(
+                "UPDATE api_tokens SET last_used_at = ? WHERE id = ?",
+                params![now, info.id],
+            )?;
+        }
+
+        Ok(info)
+    }
+
+    fn get_user_api_tokens(&self, user_id: &str) -> Result<Vec<ApiTokenInfo>, DbError> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, user_id, name, created_at FROM user_workspaces WHERE user_id = ?",
+            "SELECT id, user_id, name, workspace_id, permission, created_at, last_used_at, expires_at
+             FROM api_tokens WHERE user_id = ? ORDER BY created_at",
         )?;
 
-        let workspaces = stmt
-            .query_map([user_id], |row| {
-                Ok(WorkspaceInfo {
-                    id: row.get(0)?,
-                    user_id: row.get(1)?,
-                    name: row.get(2)?,
-                    created_at: timestamp_to_datetime(row.get(3)?),
-                })
-            })?
+        let tokens = stmt
+            .query_map([user_id], api_token_from_row)?
             .filter_map(|r| r.ok())
             .collect();
 
-        Ok(workspaces)
+        Ok(tokens)
     }
 
-    /// Get a workspace by ID
-    pub fn get_workspace(
-        &self,
-        workspace_id: &str,
-    ) -> Result<Option<WorkspaceInfo>, rusqlite::Error> {
+    fn delete_api_token(&self, user_id: &str, id: &str) -> Result<bool, DbError> {
         let conn = self.conn.lock().unwrap();
-        conn.query_row(
-            "SELECT id, user_id, name, created_at FROM user_workspaces WHERE id = ?",
-            [workspace_id],
-            |row| {
-                Ok(WorkspaceInfo {
-                    id: row.get(0)?,
-                    user_id: row.get(1)?,
-                    name: row.get(2)?,
-                    created_at: timestamp_to_datetime(row.get(3)?),
-                })
-            },
-        )
-        .optional()
+        let deleted = conn.execute(
+            "DELETE FROM api_tokens WHERE id = ? AND user_id = ?",
+            params![id, user_id],
+        )?;
+        Ok(deleted > 0)
     }
 
-    // ===== Share session operations =====
+    fn delete_user_api_tokens(&self, user_id: &str) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM api_tokens WHERE user_id = ?", [user_id])?;
+        Ok(deleted)
+    }
+
+    fn cleanup_expired_api_tokens(&self) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        let deleted = conn.execute(
+            "DELETE FROM api_tokens WHERE expires_at IS NOT NULL AND expires_at < ?",
+            [now],
+        )?;
+        Ok(deleted)
+    }
 
-    /// Create a new share session
-    pub fn create_share_session(
+    // ===== Device code operations =====
+
+    fn create_device_code(
         &self,
-        workspace_id: &str,
-        owner_user_id: &str,
-        read_only: bool,
-        expires_at: Option<DateTime<Utc>>,
-    ) -> Result<String, rusqlite::Error> {
+        device_name: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(String, String), DbError> {
         let conn = self.conn.lock().unwrap();
-        let code = generate_session_code();
+        let device_code = generate_secure_token();
+        let user_code = generate_user_code();
         let now = Utc::now().timestamp();
 
         conn.execute(
-            "INSERT INTO share_sessions (code, workspace_id, owner_user_id, read_only, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
-            params![code, workspace_id, owner_user_id, read_only as i32, now, expires_at.map(|e| e.timestamp())],
+            "INSERT INTO device_codes (device_code, user_code, device_name, expires_at, created_at)
+             VALUES (?, ?, ?, ?, ?)",
+            params![
+                device_code,
+                user_code,
+                device_name,
+                expires_at.timestamp(),
+                now
+            ],
         )?;
 
-        Ok(code)
+        Ok((device_code, user_code))
+    }
+
+    fn get_device_code(&self, device_code: &str) -> Result<Option<DeviceCodeInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT device_code, user_code, device_name, user_id, status, expires_at
+             FROM device_codes WHERE device_code = ?",
+                [device_code],
+                device_code_from_row,
+            )
+            .optional()?)
     }
 
-    /// Get a share session by code
-    pub fn get_share_session(
+    fn get_device_code_by_user_code(
         &self,
-        code: &str,
-    ) -> Result<Option<ShareSessionInfo>, rusqlite::Error> {
+        user_code: &str,
+    ) -> Result<Option<DeviceCodeInfo>, DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
-
-        conn.query_row(
-            "SELECT code, workspace_id, owner_user_id, read_only, created_at, expires_at
-             FROM share_sessions
-             WHERE code = ? AND (expires_at IS NULL OR expires_at > ?)",
-            params![code, now],
-            |row| {
-                Ok(ShareSessionInfo {
-                    code: row.get(0)?,
-                    workspace_id: row.get(1)?,
-                    owner_user_id: row.get(2)?,
-                    read_only: row.get::<_, i32>(3)? != 0,
-                    created_at: timestamp_to_datetime(row.get(4)?),
-                    expires_at: row.get::<_, Option<i64>>(5)?.map(timestamp_to_datetime),
-                })
-            },
-        )
-        .optional()
+        Ok(conn
+            .query_row(
+                "SELECT device_code, user_code, device_name, user_id, status, expires_at
+             FROM device_codes WHERE user_code = ? AND expires_at > ?",
+                params![user_code, now],
+                device_code_from_row,
+            )
+            .optional()?)
     }
 
-    /// Get all share sessions for a user
-    pub fn get_user_share_sessions(
+    fn resolve_device_code(
         &self,
+        user_code: &str,
         user_id: &str,
-    ) -> Result<Vec<ShareSessionInfo>, rusqlite::Error> {
+        approve: bool,
+    ) -> Result<bool, DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
+        let status = if approve {
+            DeviceCodeStatus::Approved
+        } else {
+            DeviceCodeStatus::Denied
+        };
+        let updated = conn.execute(
+            "UPDATE device_codes SET status = ?, user_id = ?
+             WHERE user_code = ? AND status = 'pending' AND expires_at > ?",
+            params![status.as_str(), user_id, user_code, now],
+        )?;
+        Ok(updated > 0)
+    }
 
-        let mut stmt = conn.prepare(
-            "SELECT code, workspace_id, owner_user_id, read_only, created_at, expires_at
-             FROM share_sessions
-             WHERE owner_user_id = ? AND (expires_at IS NULL OR expires_at > ?)
-             ORDER BY created_at DESC",
+    fn delete_device_code(&self, device_code: &str) -> Result<bool, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM device_codes WHERE device_code = ?",
+            [device_code],
         )?;
+        Ok(deleted > 0)
+    }
 
-        let sessions = stmt
-            .query_map(params![user_id, now], |row| {
+    fn cleanup_expired_device_codes(&self) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        let deleted = conn.execute("DELETE FROM device_codes WHERE expires_at < ?", [now])?;
+        Ok(deleted)
+    }
+
+    // ===== Passkey operations =====
+
+    fn create_passkey_challenge(
+        &self,
+        user_id: &str,
+        state: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO passkey_challenges (id, user_id, state, expires_at, created_at)
+             VALUES (?, ?, ?, ?, ?)",
+            params![id, user_id, state, expires_at.timestamp(), now],
+        )?;
+        Ok(id)
+    }
+
+    fn take_passkey_challenge(&self, id: &str) -> Result<Option<String>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        let challenge = conn
+            .query_row(
+                "SELECT state, expires_at FROM passkey_challenges WHERE id = ?",
+                [id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .optional()?;
+        conn.execute("DELETE FROM passkey_challenges WHERE id = ?", [id])?;
+        Ok(challenge
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(state, _)| state))
+    }
+
+    fn cleanup_expired_passkey_challenges(&self) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        let deleted = conn.execute("DELETE FROM passkey_challenges WHERE expires_at < ?", [now])?;
+        Ok(deleted)
+    }
+
+    fn add_passkey(
+        &self,
+        id: &str,
+        user_id: &str,
+        name: Option<&str>,
+        credential: &str,
+    ) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO passkeys (id, user_id, name, credential, created_at) VALUES (?, ?, ?, ?, ?)",
+            params![id, user_id, name, credential, now],
+        )?;
+        Ok(())
+    }
+
+    fn get_user_passkeys(&self, user_id: &str) -> Result<Vec<PasskeyInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, user_id, name, credential, created_at, last_used_at
+             FROM passkeys WHERE user_id = ? ORDER BY created_at",
+        )?;
+
+        let passkeys = stmt
+            .query_map([user_id], |row| {
+                Ok(PasskeyInfo {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    name: row.get(2)?,
+                    credential: row.get(3)?,
+                    created_at: timestamp_to_datetime(row.get(4)?),
+                    last_used_at: row.get::<_, Option<i64>>(5)?.map(timestamp_to_datetime),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(passkeys)
+    }
+
+    fn record_passkey_use(&self, id: &str, credential: &str) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        conn.execute(
+            "UPDATE passkeys SET credential = ?, last_used_at = ? WHERE id = ?",
+            params![credential, now, id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_passkey(&self, user_id: &str, id: &str) -> Result<bool, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM passkeys WHERE id = ? AND user_id = ?",
+            params![id, user_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    // ===== Workspace operations =====
+
+    fn get_or_create_workspace(&self, user_id: &str, name: &str) -> Result<String, DbError> {
+        let conn = self.conn.lock().unwrap();
+
+        // Try to get existing workspace
+        if let Some(workspace_id) = conn
+            .query_row(
+                "SELECT id FROM user_workspaces WHERE user_id = ? AND name = ?",
+                params![user_id, name],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+        {
+            return Ok(workspace_id);
+        }
+
+        // Create new workspace
+        let workspace_id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp();
+
+        conn.execute(
+            "INSERT INTO user_workspaces (id, user_id, name, created_at) VALUES (?, ?, ?, ?)",
+            params![workspace_id, user_id, name, now],
+        )?;
+
+        Ok(workspace_id)
+    }
+
+    fn get_user_workspaces(&self, user_id: &str) -> Result<Vec<WorkspaceInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, user_id, name, created_at FROM user_workspaces WHERE user_id = ?",
+        )?;
+
+        let workspaces = stmt
+            .query_map([user_id], |row| {
+                Ok(WorkspaceInfo {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    name: row.get(2)?,
+                    created_at: timestamp_to_datetime(row.get(3)?),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(workspaces)
+    }
+
+    fn get_workspace(&self, workspace_id: &str) -> Result<Option<WorkspaceInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn
+            .query_row(
+                "SELECT id, user_id, name, created_at FROM user_workspaces WHERE id = ?",
+                [workspace_id],
+                |row| {
+                    Ok(WorkspaceInfo {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        name: row.get(2)?,
+                        created_at: timestamp_to_datetime(row.get(3)?),
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    fn list_all_workspaces(&self) -> Result<Vec<WorkspaceInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, user_id, name, created_at FROM user_workspaces ORDER BY created_at, name",
+        )?;
+
+        let workspaces = stmt
+            .query_map([], |row| {
+                Ok(WorkspaceInfo {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    name: row.get(2)?,
+                    created_at: timestamp_to_datetime(row.get(3)?),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(workspaces)
+    }
+
+    fn delete_workspace(&self, workspace_id: &str) -> Result<bool, DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "DELETE FROM workspace_members WHERE workspace_id = ?",
+            [workspace_id],
+        )?;
+        tx.execute(
+            "DELETE FROM workspace_invites WHERE workspace_id = ?",
+            [workspace_id],
+        )?;
+        tx.execute(
+            "DELETE FROM api_tokens WHERE workspace_id = ?",
+            [workspace_id],
+        )?;
+        tx.execute(
+            "DELETE FROM share_session_scopes WHERE session_code IN
+             (SELECT code FROM share_sessions WHERE workspace_id = ?)",
+            [workspace_id],
+        )?;
+        tx.execute(
+            "DELETE FROM share_sessions WHERE workspace_id = ?",
+            [workspace_id],
+        )?;
+        tx.execute(
+            "DELETE FROM webhook_deliveries WHERE webhook_id IN
+             (SELECT id FROM webhooks WHERE workspace_id = ?)",
+            [workspace_id],
+        )?;
+        tx.execute(
+            "DELETE FROM webhooks WHERE workspace_id = ?",
+            [workspace_id],
+        )?;
+        let deleted = tx.execute("DELETE FROM user_workspaces WHERE id = ?", [workspace_id])?;
+
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    // ===== Workspace membership operations =====
+
+    fn get_workspace_role(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceRole>, DbError> {
+        let conn = self.conn.lock().unwrap();
+
+        let owner = conn
+            .query_row(
+                "SELECT user_id FROM user_workspaces WHERE id = ?",
+                [workspace_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        if owner.as_deref() == Some(user_id) {
+            return Ok(Some(WorkspaceRole::Owner));
+        }
+
+        let role = conn
+            .query_row(
+                "SELECT role FROM workspace_members WHERE workspace_id = ? AND user_id = ?",
+                params![workspace_id, user_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(role.as_deref().and_then(WorkspaceRole::parse))
+    }
+
+    fn get_workspace_members(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceMemberInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT u.id, u.email, 'owner', w.created_at
+             FROM user_workspaces w JOIN users u ON u.id = w.user_id
+             WHERE w.id = ?1
+             UNION ALL
+             SELECT u.id, u.email, m.role, m.created_at
+             FROM workspace_members m JOIN users u ON u.id = m.user_id
+             WHERE m.workspace_id = ?1",
+        )?;
+
+        let members = stmt
+            .query_map([workspace_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .filter_map(|r| r.ok())
+            .filter_map(|(user_id, email, role, created_at)| {
+                Some(WorkspaceMemberInfo {
+                    user_id,
+                    email,
+                    role: WorkspaceRole::parse(&role)?,
+                    created_at: timestamp_to_datetime(created_at),
+                })
+            })
+            .collect();
+
+        Ok(members)
+    }
+
+    fn add_workspace_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+        role: WorkspaceRole,
+    ) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(workspace_id, user_id) DO UPDATE SET role = excluded.role",
+            params![workspace_id, user_id, role.as_str(), now],
+        )?;
+        Ok(())
+    }
+
+    fn remove_workspace_member(&self, workspace_id: &str, user_id: &str) -> Result<bool, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM workspace_members WHERE workspace_id = ? AND user_id = ?",
+            params![workspace_id, user_id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn get_shared_workspaces(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<(WorkspaceInfo, WorkspaceRole)>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT w.id, w.user_id, w.name, w.created_at, m.role
+             FROM workspace_members m JOIN user_workspaces w ON w.id = m.workspace_id
+             WHERE m.user_id = ?",
+        )?;
+
+        let workspaces = stmt
+            .query_map([user_id], |row| {
+                Ok((
+                    WorkspaceInfo {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        name: row.get(2)?,
+                        created_at: timestamp_to_datetime(row.get(3)?),
+                    },
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .filter_map(|r| r.ok())
+            .filter_map(|(workspace, role)| Some((workspace, WorkspaceRole::parse(&role)?)))
+            .collect();
+
+        Ok(workspaces)
+    }
+
+    fn create_workspace_invite(
+        &self,
+        workspace_id: &str,
+        email: &str,
+        role: WorkspaceRole,
+        invited_by: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let token = generate_secure_token();
+        let now = Utc::now().timestamp();
+
+        conn.execute(
+            "INSERT INTO workspace_invites (token, workspace_id, email, role, invited_by, expires_at, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![token, workspace_id, email, role.as_str(), invited_by, expires_at.timestamp(), now],
+        )?;
+
+        Ok(token)
+    }
+
+    fn get_workspace_invites(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceInviteInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        let mut stmt = conn.prepare(
+            "SELECT token, workspace_id, email, role, invited_by, expires_at
+             FROM workspace_invites WHERE workspace_id = ? AND expires_at > ?
+             ORDER BY created_at",
+        )?;
+
+        let invites = stmt
+            .query_map(params![workspace_id, now], invite_from_row)?
+            .filter_map(|r| r.ok())
+            .flatten()
+            .collect();
+
+        Ok(invites)
+    }
+
+    fn accept_workspace_invite(
+        &self,
+        token: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceInviteInfo>, DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        let tx = conn.transaction()?;
+
+        let invite = tx
+            .query_row(
+                "SELECT token, workspace_id, email, role, invited_by, expires_at
+                 FROM workspace_invites WHERE token = ? AND expires_at > ?",
+                params![token, now],
+                invite_from_row,
+            )
+            .optional()?
+            .flatten();
+        let Some(invite) = invite else {
+            return Ok(None);
+        };
+
+        let email: Option<String> = tx
+            .query_row("SELECT email FROM users WHERE id = ?", [user_id], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        if !email.is_some_and(|e| e.eq_ignore_ascii_case(&invite.email)) {
+            return Ok(None);
+        }
+
+        tx.execute("DELETE FROM workspace_invites WHERE token = ?", [token])?;
+        tx.execute(
+            "INSERT INTO workspace_members (workspace_id, user_id, role, created_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(workspace_id, user_id) DO UPDATE SET role = excluded.role",
+            params![invite.workspace_id, user_id, invite.role.as_str(), now],
+        )?;
+        tx.commit()?;
+
+        Ok(Some(invite))
+    }
+
+    fn revoke_workspace_invites(&self, workspace_id: &str, email: &str) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute(
+            "DELETE FROM workspace_invites WHERE workspace_id = ? AND email = ? COLLATE NOCASE",
+            params![workspace_id, email],
+        )?)
+    }
+
+    fn cleanup_expired_workspace_invites(&self) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        Ok(conn.execute("DELETE FROM workspace_invites WHERE expires_at < ?", [now])?)
+    }
+
+    // ===== Share session operations =====
+
+    fn create_share_session(
+        &self,
+        workspace_id: &str,
+        owner_user_id: &str,
+        read_only: bool,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<String, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let code = generate_session_code();
+        let now = Utc::now().timestamp();
+
+        conn.execute(
+            "INSERT INTO share_sessions (code, workspace_id, owner_user_id, read_only, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+            params![code, workspace_id, owner_user_id, read_only as i32, now, expires_at.map(|e| e.timestamp())],
+        )?;
+
+        Ok(code)
+    }
+
+    fn get_share_session(&self, code: &str) -> Result<Option<ShareSessionInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+
+        let session = conn
+            .query_row(
+                "SELECT code, workspace_id, owner_user_id, read_only, created_at, expires_at
+                 FROM share_sessions
+                 WHERE code = ? AND (expires_at IS NULL OR expires_at > ?)",
+                params![code, now],
+                |row| {
+                    Ok(ShareSessionInfo {
+                        code: row.get(0)?,
+                        workspace_id: row.get(1)?,
+                        owner_user_id: row.get(2)?,
+                        read_only: row.get::<_, i32>(3)? != 0,
+                        scope: None,
+                        created_at: timestamp_to_datetime(row.get(4)?),
+                        expires_at: row.get::<_, Option<i64>>(5)?.map(timestamp_to_datetime),
+                    })
+                },
+            )
+            .optional()?;
+
+        session
+            .map(|mut session| {
+                session.scope = load_share_scope(&conn, &session.code)?;
+                Ok(session)
+            })
+            .transpose()
+    }
+
+    fn get_user_share_sessions(&self, user_id: &str) -> Result<Vec<ShareSessionInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+
+        let mut stmt = conn.prepare(
+            "SELECT code, workspace_id, owner_user_id, read_only, created_at, expires_at
+             FROM share_sessions
+             WHERE owner_user_id = ? AND (expires_at IS NULL OR expires_at > ?)
+             ORDER BY created_at DESC",
+        )?;
+
+        let mut sessions = stmt
+            .query_map(params![user_id, now], |row| {
                 Ok(ShareSessionInfo {
                     code: row.get(0)?,
                     workspace_id: row.get(1)?,
                     owner_user_id: row.get(2)?,
                     read_only: row.get::<_, i32>(3)? != 0,
+                    scope: None,
                     created_at: timestamp_to_datetime(row.get(4)?),
                     expires_at: row.get::<_, Option<i64>>(5)?.map(timestamp_to_datetime),
                 })
             })?
             .filter_map(|r| r.ok())
-            .collect();
+            .collect::<Vec<_>>();
+
+        for session in &mut sessions {
+            session.scope = load_share_scope(&conn, &session.code)?;
+        }
 
         Ok(sessions)
     }
 
-    /// Update read-only status for a share session
-    pub fn update_share_session_read_only(
-        &self,
-        code: &str,
-        read_only: bool,
-    ) -> Result<bool, rusqlite::Error> {
+    fn update_share_session_read_only(&self, code: &str, read_only: bool) -> Result<bool, DbError> {
         let conn = self.conn.lock().unwrap();
         let updated = conn.execute(
             "UPDATE share_sessions SET read_only = ? WHERE code = ?",
@@ -529,37 +1351,405 @@ impl AuthRepo {
         Ok(updated > 0)
     }
 
-    /// Delete a share session
-    pub fn delete_share_session(&self, code: &str) -> Result<bool, rusqlite::Error> {
+    fn set_share_session_scope(
+        &self,
+        code: &str,
+        scope: Option<&ShareScope>,
+    ) -> Result<(), DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "DELETE FROM share_session_scopes WHERE session_code = ?",
+            [code],
+        )?;
+        for entry in scope.map(|s| s.entries.as_slice()).unwrap_or_default() {
+            tx.execute(
+                "INSERT OR REPLACE INTO share_session_scopes (session_code, path, permission, include_contents) VALUES (?, ?, ?, ?)",
+                params![
+                    code,
+                    entry.path,
+                    permission_to_str(entry.permission),
+                    entry.include_contents as i32
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_share_session(&self, code: &str) -> Result<bool, DbError> {
         let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM share_session_scopes WHERE session_code = ?",
+            [code],
+        )?;
         let deleted = conn.execute("DELETE FROM share_sessions WHERE code = ?", [code])?;
         Ok(deleted > 0)
     }
 
-    /// Clean up expired share sessions
-    pub fn cleanup_expired_share_sessions(&self) -> Result<usize, rusqlite::Error> {
+    fn cleanup_expired_share_sessions(&self) -> Result<usize, DbError> {
         let conn = self.conn.lock().unwrap();
         let now = Utc::now().timestamp();
+        conn.execute(
+            "DELETE FROM share_session_scopes WHERE session_code IN
+             (SELECT code FROM share_sessions WHERE expires_at IS NOT NULL AND expires_at < ?)",
+            [now],
+        )?;
         let deleted = conn.execute(
             "DELETE FROM share_sessions WHERE expires_at IS NOT NULL AND expires_at < ?",
             [now],
         )?;
         Ok(deleted)
     }
+
+    // ===== Audit log operations =====
+
+    fn append_audit_event(
+        &self,
+        action: &str,
+        user_id: Option<&str>,
+        target: Option<&str>,
+        details: &serde_json::Value,
+    ) -> Result<i64, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO audit_events (created_at, action, user_id, target, details) VALUES (?, ?, ?, ?, ?)",
+            params![now, action, user_id, target, details.to_string()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn get_audit_events(
+        &self,
+        user_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<AuditEventInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, action, user_id, target, details FROM audit_events
+             WHERE ?1 IS NULL OR user_id = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )?;
+
+        let events = stmt
+            .query_map(params![user_id, limit as i64], |row| {
+                Ok(AuditEventInfo {
+                    id: row.get(0)?,
+                    created_at: timestamp_to_datetime(row.get(1)?),
+                    action: row.get(2)?,
+                    user_id: row.get(3)?,
+                    target: row.get(4)?,
+                    details: row
+                        .get::<_, Option<String>>(5)?
+                        .and_then(|d| serde_json::from_str(&d).ok())
+                        .unwrap_or(serde_json::Value::Null),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(events)
+    }
+
+    // ===== Webhook operations =====
+
+    fn create_webhook(
+        &self,
+        workspace_id: &str,
+        url: &str,
+        description: Option<&str>,
+        created_by: Option<&str>,
+    ) -> Result<WebhookInfo, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let webhook = WebhookInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            workspace_id: workspace_id.to_string(),
+            url: url.to_string(),
+            secret: format!("{}{}", WEBHOOK_SECRET_PREFIX, generate_secure_token()),
+            description: description.map(str::to_string),
+            created_by: created_by.map(str::to_string),
+            created_at: timestamp_to_datetime(Utc::now().timestamp()),
+        };
+
+        conn.execute(
+            "INSERT INTO webhooks (id, workspace_id, url, secret, description, created_by, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                webhook.id,
+                webhook.workspace_id,
+                webhook.url,
+                webhook.secret,
+                webhook.description,
+                webhook.created_by,
+                webhook.created_at.timestamp()
+            ],
+        )?;
+
+        Ok(webhook)
+    }
+
+    fn get_webhook(&self, id: &str) -> Result<Option<WebhookInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let webhook = conn
+            .query_row(
+                "SELECT id, workspace_id, url, secret, description, created_by, created_at
+                 FROM webhooks WHERE id = ?",
+                [id],
+                webhook_from_row,
+            )
+            .optional()?;
+        Ok(webhook)
+    }
+
+    fn get_workspace_webhooks(&self, workspace_id: &str) -> Result<Vec<WebhookInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, workspace_id, url, secret, description, created_by, created_at
+             FROM webhooks WHERE workspace_id = ? ORDER BY created_at, id",
+        )?;
+
+        let webhooks = stmt
+            .query_map([workspace_id], webhook_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(webhooks)
+    }
+
+    fn delete_webhook(&self, workspace_id: &str, id: &str) -> Result<bool, DbError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let deleted = tx.execute(
+            "DELETE FROM webhooks WHERE id = ? AND workspace_id = ?",
+            params![id, workspace_id],
+        )?;
+        if deleted > 0 {
+            tx.execute("DELETE FROM webhook_deliveries WHERE webhook_id = ?", [id])?;
+        }
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    fn create_webhook_delivery(
+        &self,
+        webhook_id: &str,
+        event: &str,
+        payload: &serde_json::Value,
+    ) -> Result<String, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp();
+
+        conn.execute(
+            "INSERT INTO webhook_deliveries (id, webhook_id, event, payload, status, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                id,
+                webhook_id,
+                event,
+                payload.to_string(),
+                WebhookDeliveryStatus::Pending.as_str(),
+                now,
+                now
+            ],
+        )?;
+
+        Ok(id)
+    }
+
+    fn update_webhook_delivery(
+        &self,
+        id: &str,
+        status: WebhookDeliveryStatus,
+        attempts: u32,
+        response_status: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<(), DbError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE webhook_deliveries
+             SET status = ?, attempts = ?, response_status = ?, error = ?, updated_at = ?
+             WHERE id = ?",
+            params![
+                status.as_str(),
+                attempts,
+                response_status,
+                error,
+                Utc::now().timestamp(),
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_webhook_deliveries(
+        &self,
+        webhook_id: &str,
+        limit: usize,
+    ) -> Result<Vec<WebhookDeliveryInfo>, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, webhook_id, event, payload, status, attempts, response_status, error, created_at, updated_at
+             FROM webhook_deliveries WHERE webhook_id = ?
+             ORDER BY created_at DESC, rowid DESC LIMIT ?",
+        )?;
+
+        let deliveries = stmt
+            .query_map(params![webhook_id, limit as i64], |row| {
+                let status: String = row.get(4)?;
+                Ok(WebhookDeliveryInfo {
+                    id: row.get(0)?,
+                    webhook_id: row.get(1)?,
+                    event: row.get(2)?,
+                    payload: serde_json::from_str(&row.get::<_, String>(3)?)
+                        .unwrap_or(serde_json::Value::Null),
+                    status: WebhookDeliveryStatus::parse(&status)
+                        .unwrap_or(WebhookDeliveryStatus::Failed),
+                    attempts: row.get(5)?,
+                    response_status: row.get(6)?,
+                    error: row.get(7)?,
+                    created_at: timestamp_to_datetime(row.get(8)?),
+                    updated_at: timestamp_to_datetime(row.get(9)?),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(deliveries)
+    }
+
+    fn trim_webhook_deliveries(&self, webhook_id: &str, keep: usize) -> Result<usize, DbError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM webhook_deliveries WHERE webhook_id = ?1 AND id NOT IN
+             (SELECT id FROM webhook_deliveries WHERE webhook_id = ?1
+              ORDER BY created_at DESC, rowid DESC LIMIT ?2)",
+            params![webhook_id, keep as i64],
+        )?;
+        Ok(deleted)
+    }
 }
 
 // ===== Helper functions =====
 
+/// Map a `workspace_invites` row (`None` if its role is unknown)
+fn invite_from_row(row: &rusqlite::Row) -> Result<Option<WorkspaceInviteInfo>, rusqlite::Error> {
+    let Some(role) = WorkspaceRole::parse(&row.get::<_, String>(3)?) else {
+        return Ok(None);
+    };
+    Ok(Some(WorkspaceInviteInfo {
+        token: row.get(0)?,
+        workspace_id: row.get(1)?,
+        email: row.get(2)?,
+        role,
+        invited_by: row.get(4)?,
+        expires_at: timestamp_to_datetime(row.get(5)?),
+    }))
+}
+
+/// Load the scope entries of a share session (`None` if it is unscoped)
+fn load_share_scope(conn: &Connection, code: &str) -> Result<Option<ShareScope>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT path, permission, include_contents FROM share_session_scopes
+         WHERE session_code = ? ORDER BY path",
+    )?;
+    let entries = stmt
+        .query_map([code], |row| {
+            Ok(ScopeEntry {
+                path: row.get(0)?,
+                permission: permission_from_str(&row.get::<_, String>(1)?),
+                include_contents: row.get::<_, i32>(2)? != 0,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((!entries.is_empty()).then_some(ShareScope { entries }))
+}
+
+pub(super) fn permission_to_str(permission: SharePermission) -> &'static str {
+    match permission {
+        SharePermission::Read => "read",
+        SharePermission::Write => "write",
+    }
+}
+
+pub(super) fn permission_from_str(s: &str) -> SharePermission {
+    match s {
+        "write" => SharePermission::Write,
+        _ => SharePermission::Read,
+    }
+}
+
 /// Generate a cryptographically secure random token
-fn generate_secure_token() -> String {
+pub(super) fn generate_secure_token() -> String {
     use rand::Rng;
     let mut rng = rand::thread_rng();
     let bytes: Vec<u8> = (0..32).map(|_| rng.r#gen()).collect();
     base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, bytes)
 }
 
+fn api_token_from_row(row: &rusqlite::Row<'_>) -> Result<ApiTokenInfo, rusqlite::Error> {
+    let permission: String = row.get(4)?;
+    Ok(ApiTokenInfo {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        workspace_id: row.get(3)?,
+        permission: ApiTokenPermission::parse(&permission).unwrap_or(ApiTokenPermission::Read),
+        created_at: timestamp_to_datetime(row.get(5)?),
+        last_used_at: row.get::<_, Option<i64>>(6)?.map(timestamp_to_datetime),
+        expires_at: row.get::<_, Option<i64>>(7)?.map(timestamp_to_datetime),
+    })
+}
+
+fn webhook_from_row(row: &rusqlite::Row<'_>) -> Result<WebhookInfo, rusqlite::Error> {
+    Ok(WebhookInfo {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        url: row.get(2)?,
+        secret: row.get(3)?,
+        description: row.get(4)?,
+        created_by: row.get(5)?,
+        created_at: timestamp_to_datetime(row.get(6)?),
+    })
+}
+
+fn device_code_from_row(row: &rusqlite::Row<'_>) -> Result<DeviceCodeInfo, rusqlite::Error> {
+    let status: String = row.get(4)?;
+    Ok(DeviceCodeInfo {
+        device_code: row.get(0)?,
+        user_code: row.get(1)?,
+        device_name: row.get(2)?,
+        user_id: row.get(3)?,
+        status: DeviceCodeStatus::parse(&status),
+        expires_at: timestamp_to_datetime(row.get(5)?),
+    })
+}
+
+/// Generate a device login user code in XXXX-XXXX format.
+///
+/// Uses consonants only, so codes are easy to read aloud and can't spell words.
+pub(super) fn generate_user_code() -> String {
+    use rand::Rng;
+    const CHARSET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+    let mut rng = rand::thread_rng();
+
+    let mut part = || -> String {
+        (0..4)
+            .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+            .collect()
+    };
+
+    let part1 = part();
+    let part2 = part();
+    format!("{}-{}", part1, part2)
+}
+
 /// Generate a session code in XXXXXXXX-XXXXXXXX format
-fn generate_session_code() -> String {
+pub(super) fn generate_session_code() -> String {
     use rand::Rng;
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
     let mut rng = rand::thread_rng();
@@ -582,14 +1772,14 @@ fn generate_session_code() -> String {
 }
 
 /// Convert Unix timestamp to DateTime<Utc>
-fn timestamp_to_datetime(timestamp: i64) -> DateTime<Utc> {
+pub(super) fn timestamp_to_datetime(timestamp: i64) -> DateTime<Utc> {
     DateTime::from_timestamp(timestamp, 0).unwrap_or_else(|| Utc::now())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::init_database;
+    use crate::db::{AuthRepo, init_database};
 
     fn setup_test_db() -> AuthRepo {
         let conn = Connection::open_in_memory().unwrap();
@@ -689,4 +1879,335 @@ mod tests {
         assert!(repo.validate_session(&session_token).unwrap().is_none());
         assert!(repo.get_user_workspaces(&user_id).unwrap().is_empty());
     }
+
+    #[test]
+    fn test_share_session_scope() {
+        let repo = setup_test_db();
+        let user_id = repo.get_or_create_user("owner@example.com").unwrap();
+        let code = repo
+            .create_share_session("ws-1", &user_id, false, None)
+            .unwrap();
+
+        // New sessions share the whole workspace
+        let session = repo.get_share_session(&code).unwrap().unwrap();
+        assert!(session.scope.is_none());
+
+        let scope = ShareScope {
+            entries: vec![
+                ScopeEntry {
+                    path: "projects/index.md".to_string(),
+                    permission: SharePermission::Write,
+                    include_contents: true,
+                },
+                ScopeEntry {
+                    path: "README.md".to_string(),
+                    permission: SharePermission::Read,
+                    include_contents: false,
+                },
+            ],
+        };
+        repo.set_share_session_scope(&code, Some(&scope)).unwrap();
+
+        let session = repo.get_share_session(&code).unwrap().unwrap();
+        let stored = session.scope.unwrap();
+        assert_eq!(stored.entries.len(), 2);
+        assert!(stored.entries.contains(&scope.entries[0]));
+        assert!(stored.entries.contains(&scope.entries[1]));
+
+        let listed = repo.get_user_share_sessions(&user_id).unwrap();
+        assert_eq!(listed[0].scope.as_ref(), Some(&stored));
+
+        // Clearing the scope shares everything again
+        repo.set_share_session_scope(&code, None).unwrap();
+        let session = repo.get_share_session(&code).unwrap().unwrap();
+        assert!(session.scope.is_none());
+    }
+
+    #[test]
+    fn test_workspace_membership() {
+        let repo = setup_test_db();
+        let owner = repo.get_or_create_user("owner@example.com").unwrap();
+        let partner = repo.get_or_create_user("partner@example.com").unwrap();
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+
+        assert_eq!(
+            repo.get_workspace_role(&workspace_id, &owner).unwrap(),
+            Some(WorkspaceRole::Owner)
+        );
+        assert_eq!(
+            repo.get_workspace_role(&workspace_id, &partner).unwrap(),
+            None
+        );
+
+        // Invites only work for the invited email
+        let expires = Utc::now() + chrono::Duration::days(7);
+        let token = repo
+            .create_workspace_invite(
+                &workspace_id,
+                "partner@example.com",
+                WorkspaceRole::Editor,
+                &owner,
+                expires,
+            )
+            .unwrap();
+        assert_eq!(repo.get_workspace_invites(&workspace_id).unwrap().len(), 1);
+        assert!(
+            repo.accept_workspace_invite(&token, &owner)
+                .unwrap()
+                .is_none()
+        );
+
+        let invite = repo
+            .accept_workspace_invite(&token, &partner)
+            .unwrap()
+            .unwrap();
+        assert_eq!(invite.workspace_id, workspace_id);
+        assert_eq!(
+            repo.get_workspace_role(&workspace_id, &partner).unwrap(),
+            Some(WorkspaceRole::Editor)
+        );
+
+        // Invites are single-use
+        assert!(
+            repo.accept_workspace_invite(&token, &partner)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            repo.get_workspace_invites(&workspace_id)
+                .unwrap()
+                .is_empty()
+        );
+
+        let members = repo.get_workspace_members(&workspace_id).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].role, WorkspaceRole::Owner);
+
+        let shared = repo.get_shared_workspaces(&partner).unwrap();
+        assert_eq!(shared[0].0.id, workspace_id);
+        assert_eq!(shared[0].1, WorkspaceRole::Editor);
+
+        repo.add_workspace_member(&workspace_id, &partner, WorkspaceRole::Viewer)
+            .unwrap();
+        assert_eq!(
+            repo.get_workspace_role(&workspace_id, &partner).unwrap(),
+            Some(WorkspaceRole::Viewer)
+        );
+
+        assert!(
+            repo.remove_workspace_member(&workspace_id, &partner)
+                .unwrap()
+        );
+        assert_eq!(
+            repo.get_workspace_role(&workspace_id, &partner).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_passkey_storage() {
+        let repo = setup_test_db();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+        let other = repo.get_or_create_user("other@example.com").unwrap();
+
+        repo.add_passkey("cred-1", &user_id, Some("Laptop"), "{}")
+            .unwrap();
+        let passkeys = repo.get_user_passkeys(&user_id).unwrap();
+        assert_eq!(passkeys.len(), 1);
+        assert_eq!(passkeys[0].name.as_deref(), Some("Laptop"));
+        assert!(passkeys[0].last_used_at.is_none());
+
+        repo.record_passkey_use("cred-1", r#"{"counter":1}"#)
+            .unwrap();
+        let passkeys = repo.get_user_passkeys(&user_id).unwrap();
+        assert_eq!(passkeys[0].credential, r#"{"counter":1}"#);
+        assert!(passkeys[0].last_used_at.is_some());
+
+        // Users can only delete their own passkeys
+        assert!(!repo.delete_passkey(&other, "cred-1").unwrap());
+        assert!(repo.delete_passkey(&user_id, "cred-1").unwrap());
+        assert!(repo.get_user_passkeys(&user_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_api_tokens() {
+        let repo = setup_test_db();
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+        let other = repo.get_or_create_user("other@example.com").unwrap();
+        let workspace_id = repo.get_or_create_workspace(&user_id, "default").unwrap();
+
+        let (id, token) = repo
+            .create_api_token(
+                &user_id,
+                "backup",
+                Some(&workspace_id),
+                ApiTokenPermission::Snapshot,
+                None,
+            )
+            .unwrap();
+        assert!(token.starts_with(API_TOKEN_PREFIX));
+
+        let info = repo.validate_api_token(&token).unwrap().unwrap();
+        assert_eq!(info.id, id);
+        assert_eq!(info.permission, ApiTokenPermission::Snapshot);
+        assert!(info.covers_workspace(&workspace_id));
+        assert!(!info.covers_workspace("other-workspace"));
+        assert!(
+            repo.get_user_api_tokens(&user_id).unwrap()[0]
+                .last_used_at
+                .is_some()
+        );
+
+        // Expired tokens are rejected
+        let (_, expired) = repo
+            .create_api_token(
+                &user_id,
+                "old",
+                None,
+                ApiTokenPermission::Read,
+                Some(Utc::now() - chrono::Duration::days(1)),
+            )
+            .unwrap();
+        assert!(repo.validate_api_token(&expired).unwrap().is_none());
+        assert_eq!(repo.cleanup_expired_api_tokens().unwrap(), 1);
+
+        // Only the owner can revoke
+        assert!(!repo.delete_api_token(&other, &id).unwrap());
+        assert!(repo.delete_api_token(&user_id, &id).unwrap());
+        assert!(repo.validate_api_token(&token).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_delete_workspace() {
+        let repo = setup_test_db();
+        let owner = repo.get_or_create_user("owner@example.com").unwrap();
+        let friend = repo.get_or_create_user("friend@example.com").unwrap();
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let other_id = repo.get_or_create_workspace(&owner, "other").unwrap();
+
+        repo.add_workspace_member(&workspace_id, &friend, WorkspaceRole::Viewer)
+            .unwrap();
+        let code = repo
+            .create_share_session(&workspace_id, &owner, false, None)
+            .unwrap();
+        let (_, token) = repo
+            .create_api_token(
+                &owner,
+                "ci",
+                Some(&workspace_id),
+                ApiTokenPermission::Read,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(repo.list_all_workspaces().unwrap().len(), 2);
+        assert!(repo.delete_workspace(&workspace_id).unwrap());
+        assert!(!repo.delete_workspace(&workspace_id).unwrap());
+
+        let remaining = repo.list_all_workspaces().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, other_id);
+        assert!(
+            repo.get_workspace_role(&workspace_id, &friend)
+                .unwrap()
+                .is_none()
+        );
+        assert!(repo.get_share_session(&code).unwrap().is_none());
+        assert!(repo.validate_api_token(&token).unwrap().is_none());
+        assert_eq!(repo.list_users().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_audit_events() {
+        let repo = setup_test_db();
+        repo.append_audit_event("auth.login", Some("u1"), None, &serde_json::json!({}))
+            .unwrap();
+        repo.append_audit_event(
+            "session.create",
+            Some("u2"),
+            Some("ABCD-EFGH"),
+            &serde_json::json!({ "read_only": true }),
+        )
+        .unwrap();
+
+        let all = repo.get_audit_events(None, 10).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].action, "session.create");
+        assert_eq!(all[0].target.as_deref(), Some("ABCD-EFGH"));
+        assert_eq!(all[0].details["read_only"], true);
+
+        let mine = repo.get_audit_events(Some("u1"), 10).unwrap();
+        assert_eq!(mine.len(), 1);
+        assert_eq!(mine[0].action, "auth.login");
+        assert_eq!(repo.get_audit_events(None, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_webhooks() {
+        let repo = setup_test_db();
+        let owner = repo.get_or_create_user("owner@example.com").unwrap();
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+
+        let webhook = repo
+            .create_webhook(
+                &workspace_id,
+                "https://example.com/hook",
+                Some("CI"),
+                Some(&owner),
+            )
+            .unwrap();
+        assert!(webhook.secret.starts_with(WEBHOOK_SECRET_PREFIX));
+        assert_eq!(
+            repo.get_webhook(&webhook.id).unwrap().unwrap().url,
+            "https://example.com/hook"
+        );
+        assert_eq!(repo.get_workspace_webhooks(&workspace_id).unwrap().len(), 1);
+
+        let mut ids = Vec::new();
+        for n in 0..3 {
+            let id = repo
+                .create_webhook_delivery(&webhook.id, "ping", &serde_json::json!({ "n": n }))
+                .unwrap();
+            ids.push(id);
+        }
+        repo.update_webhook_delivery(
+            &ids[2],
+            WebhookDeliveryStatus::Failed,
+            5,
+            Some(502),
+            Some("HTTP 502 Bad Gateway"),
+        )
+        .unwrap();
+
+        let log = repo.get_webhook_deliveries(&webhook.id, 10).unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[0].id, ids[2]);
+        assert_eq!(log[0].status, WebhookDeliveryStatus::Failed);
+        assert_eq!(log[0].attempts, 5);
+        assert_eq!(log[0].response_status, Some(502));
+        assert_eq!(log[1].status, WebhookDeliveryStatus::Pending);
+        assert_eq!(log[2].payload["n"], 0);
+
+        assert_eq!(repo.trim_webhook_deliveries(&webhook.id, 2).unwrap(), 1);
+        let log = repo.get_webhook_deliveries(&webhook.id, 10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[1].id, ids[1]);
+
+        // Only deletable through its own workspace
+        assert!(!repo.delete_webhook("other", &webhook.id).unwrap());
+        assert!(repo.delete_webhook(&workspace_id, &webhook.id).unwrap());
+        assert!(repo.get_webhook(&webhook.id).unwrap().is_none());
+        assert!(
+            repo.get_webhook_deliveries(&webhook.id, 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Deleting the workspace removes its webhooks
+        let webhook = repo
+            .create_webhook(&workspace_id, "https://example.com/hook", None, None)
+            .unwrap();
+        repo.delete_workspace(&workspace_id).unwrap();
+        assert!(repo.get_webhook(&webhook.id).unwrap().is_none());
+    }
 }
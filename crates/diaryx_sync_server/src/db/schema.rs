@@ -1,7 +1,24 @@
+//! Auth database schema and its migrations.
+//!
+//! The schema version is kept in SQLite's `PRAGMA user_version`. On startup
+//! [`init_database`] applies every migration newer than the stored version,
+//! each in its own transaction, so a crash mid-upgrade leaves the database at
+//! the last fully applied version.
+
 use rusqlite::Connection;
 
-/// SQL schema for auth-related tables
-const SCHEMA: &str = r#"
+use super::store::DbError;
+
+/// Ordered up-migrations. Applying `MIGRATIONS[i]` takes the database from
+/// version `i` to `i + 1`.
+///
+/// Never edit a migration once it has shipped; append a new one instead.
+/// Databases created before versioning report version 0 but may already
+/// contain any of these tables, which is why the statements here use
+/// `IF NOT EXISTS`.
+const MIGRATIONS: &[&str] = &[
+    // 1: users, devices, sign-in, workspaces and share sessions
+    r#"
 -- Users table
 CREATE TABLE IF NOT EXISTS users (
     id TEXT PRIMARY KEY,
@@ -69,11 +86,192 @@ CREATE TABLE IF NOT EXISTS share_sessions (
 
 CREATE INDEX IF NOT EXISTS idx_share_sessions_owner ON share_sessions(owner_user_id);
 CREATE INDEX IF NOT EXISTS idx_share_sessions_workspace ON share_sessions(workspace_id);
-"#;
+"#,
+    // 2: per-path share scopes
+    r#"
+-- Paths shared by a scoped share session (no rows = whole workspace)
+CREATE TABLE IF NOT EXISTS share_session_scopes (
+    session_code TEXT NOT NULL,
+    path TEXT NOT NULL,
+    permission TEXT NOT NULL,           -- 'read' or 'write'
+    include_contents INTEGER DEFAULT 0, -- 1 = path and everything under it
+    PRIMARY KEY (session_code, path),
+    FOREIGN KEY (session_code) REFERENCES share_sessions(code) ON DELETE CASCADE
+);
+"#,
+    // 3: workspace membership and invitations
+    r#"
+-- Workspace members (users other than the owner who can access a workspace)
+CREATE TABLE IF NOT EXISTS workspace_members (
+    workspace_id TEXT NOT NULL REFERENCES user_workspaces(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL,                 -- 'editor' or 'viewer'
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_workspace_members_user ON workspace_members(user_id);
+
+-- Pending workspace invitations (accepted via emailed token)
+CREATE TABLE IF NOT EXISTS workspace_invites (
+    token TEXT PRIMARY KEY,
+    workspace_id TEXT NOT NULL REFERENCES user_workspaces(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    role TEXT NOT NULL,
+    invited_by TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_workspace_invites_workspace ON workspace_invites(workspace_id);
+"#,
+    // 4: device-code login and passkeys
+    r#"
+-- Device authorization codes (login on a headless device, approved from a signed-in one)
+CREATE TABLE IF NOT EXISTS device_codes (
+    device_code TEXT PRIMARY KEY,       -- secret polled by the new device
+    user_code TEXT UNIQUE NOT NULL,     -- XXXX-XXXX, shown to the user
+    device_name TEXT,
+    user_id TEXT REFERENCES users(id) ON DELETE CASCADE, -- set once approved
+    status TEXT NOT NULL DEFAULT 'pending', -- 'pending', 'approved', or 'denied'
+    expires_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
 
-/// Initialize the database with the auth schema
-pub fn init_database(conn: &Connection) -> Result<(), rusqlite::Error> {
-    conn.execute_batch(SCHEMA)?;
+CREATE INDEX IF NOT EXISTS idx_device_codes_expires ON device_codes(expires_at);
+
+-- WebAuthn passkey credentials
+CREATE TABLE IF NOT EXISTS passkeys (
+    id TEXT PRIMARY KEY,                -- credential ID (base64url)
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT,
+    credential TEXT NOT NULL,           -- serialized passkey (JSON)
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_passkeys_user_id ON passkeys(user_id);
+"#,
+    // 5: personal access tokens
+    r#"
+-- Personal access tokens for automation (scoped, revocable, optionally non-expiring)
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id TEXT PRIMARY KEY,
+    token TEXT UNIQUE NOT NULL,         -- dxt_... bearer token
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    workspace_id TEXT REFERENCES user_workspaces(id) ON DELETE CASCADE, -- NULL = all workspaces
+    permission TEXT NOT NULL,           -- 'read', 'write', or 'snapshot'
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER,
+    expires_at INTEGER                  -- NULL = no expiry
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id ON api_tokens(user_id);
+"#,
+    // 6: audit log
+    r#"
+-- Security audit log (append-only; kept after users and workspaces are deleted)
+CREATE TABLE IF NOT EXISTS audit_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at INTEGER NOT NULL,
+    action TEXT NOT NULL,               -- e.g. 'auth.login', 'session.create'
+    user_id TEXT,                       -- acting user, if known
+    target TEXT,                        -- affected device, session code, workspace...
+    details TEXT                        -- JSON object
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_user ON audit_events(user_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_update BEFORE UPDATE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit log is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_delete BEFORE DELETE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit log is append-only');
+END;
+"#,
+    // 7: outbound webhooks and their delivery log
+    r#"
+-- Webhooks called when a workspace changes
+CREATE TABLE IF NOT EXISTS webhooks (
+    id TEXT PRIMARY KEY,
+    workspace_id TEXT NOT NULL REFERENCES user_workspaces(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,               -- HMAC-SHA256 signing key
+    description TEXT,
+    created_by TEXT,                    -- user who registered it
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhooks_workspace_id ON webhooks(workspace_id);
+
+-- Delivery log (trimmed to the most recent deliveries per webhook)
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id TEXT PRIMARY KEY,
+    webhook_id TEXT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event TEXT NOT NULL,                -- 'workspace.changed' or 'ping'
+    payload TEXT NOT NULL,              -- JSON body as sent
+    status TEXT NOT NULL,               -- 'pending', 'delivered' or 'failed'
+    attempts INTEGER NOT NULL DEFAULT 0,
+    response_status INTEGER,            -- HTTP status of the last attempt
+    error TEXT,                         -- error of the last failed attempt
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook ON webhook_deliveries(webhook_id, created_at);
+"#,
+    // 8: in-progress passkey ceremonies
+    r#"
+-- Passkey registrations and logins between their start and finish requests
+CREATE TABLE IF NOT EXISTS passkey_challenges (
+    id TEXT PRIMARY KEY,                -- challenge ID handed to the client
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    state TEXT NOT NULL,                -- serialized ceremony state (JSON)
+    expires_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_passkey_challenges_expires ON passkey_challenges(expires_at);
+"#,
+];
+
+/// Schema version this build creates and understands
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Initialize the database with the auth schema, upgrading it if it was
+/// created by an older server.
+///
+/// Fails with [`DbError::SchemaTooNew`] if the database was written by a newer
+/// server, rather than running against tables this build doesn't know.
+pub fn init_database(conn: &Connection) -> Result<(), DbError> {
+    migrate(conn, MIGRATIONS)
+}
+
+/// Read the schema version stored in the database
+pub fn schema_version(conn: &Connection) -> Result<u32, DbError> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+fn migrate(conn: &Connection, migrations: &[&str]) -> Result<(), DbError> {
+    let current = schema_version(conn)?;
+    let latest = migrations.len() as u32;
+    if current > latest {
+        return Err(DbError::SchemaTooNew {
+            found: current,
+            supported: latest,
+        });
+    }
+
+    for (index, sql) in migrations.iter().enumerate().skip(current as usize) {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", index as u32 + 1)?;
+        tx.commit()?;
+    }
     Ok(())
 }
 
@@ -99,7 +297,113 @@ mod tests {
         assert!(tables.contains(&"devices".to_string()));
         assert!(tables.contains(&"magic_tokens".to_string()));
         assert!(tables.contains(&"auth_sessions".to_string()));
+        assert!(tables.contains(&"api_tokens".to_string()));
+        assert!(tables.contains(&"device_codes".to_string()));
+        assert!(tables.contains(&"passkeys".to_string()));
         assert!(tables.contains(&"user_workspaces".to_string()));
+        assert!(tables.contains(&"workspace_members".to_string()));
+        assert!(tables.contains(&"workspace_invites".to_string()));
         assert!(tables.contains(&"share_sessions".to_string()));
+        assert!(tables.contains(&"share_session_scopes".to_string()));
+        assert!(tables.contains(&"audit_events".to_string()));
+        assert!(tables.contains(&"webhooks".to_string()));
+        assert!(tables.contains(&"webhook_deliveries".to_string()));
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_init_database_is_idempotent() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        init_database(&conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_upgrade_from_each_version() {
+        for version in 0..SCHEMA_VERSION as usize {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn, &MIGRATIONS[..version]).unwrap();
+            assert_eq!(schema_version(&conn).unwrap(), version as u32);
+            if version >= 1 {
+                conn.execute(
+                    "INSERT INTO users (id, email, created_at) VALUES ('u1', 'a@example.com', 0)",
+                    [],
+                )
+                .unwrap();
+            }
+
+            init_database(&conn).unwrap();
+
+            assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+            let users: i64 = conn
+                .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(users, if version >= 1 { 1 } else { 0 });
+            conn.execute(
+                "INSERT INTO audit_events (created_at, action) VALUES (0, 'auth.login')",
+                [],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_upgrade_unversioned_database() {
+        // Databases created before migrations existed: tables present, user_version 0
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.execute_batch(MIGRATIONS[1]).unwrap();
+        conn.execute(
+            "INSERT INTO users (id, email, created_at) VALUES ('u1', 'a@example.com', 0)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO share_sessions (code, workspace_id, owner_user_id, created_at)
+             VALUES ('ABCD-EFGH', 'w1', 'u1', 0)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+
+        init_database(&conn).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let code: String = conn
+            .query_row("SELECT code FROM share_sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(code, "ABCD-EFGH");
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        let err = init_database(&conn).unwrap_err();
+        assert!(matches!(
+            err,
+            DbError::SchemaTooNew { found, supported }
+                if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+        ));
+    }
+
+    #[test]
+    fn test_audit_events_append_only() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO audit_events (created_at, action) VALUES (0, 'auth.login')",
+            [],
+        )
+        .unwrap();
+        assert!(
+            conn.execute("UPDATE audit_events SET action = 'x'", [])
+                .is_err()
+        );
+        assert!(conn.execute("DELETE FROM audit_events", []).is_err());
     }
 }
@@ -0,0 +1,469 @@
+//! Storage-independent access to users, sessions, workspaces, webhooks and the
+//! audit log.
+//!
+//! [`AuthStore`] is implemented by [`SqliteAuthStore`](super::SqliteAuthStore)
+//! (the default, one database file) and, with the `postgres` feature, by
+//! `PostgresAuthStore`, which lets several server instances share one database.
+//! Handlers hold an [`AuthRepo`], which derefs to the configured store.
+
+use chrono::{DateTime, Utc};
+use diaryx_core::crdt::ShareScope;
+use rusqlite::Connection;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::repo::{
+    ApiTokenInfo, ApiTokenPermission, AuditEventInfo, DeviceCodeInfo, DeviceInfo, PasskeyInfo,
+    SessionInfo, ShareSessionInfo, SqliteAuthStore, UserInfo, WebhookDeliveryInfo,
+    WebhookDeliveryStatus, WebhookInfo, WorkspaceInfo, WorkspaceInviteInfo, WorkspaceMemberInfo,
+    WorkspaceRole,
+};
+
+/// Database error from any [`AuthStore`] backend
+#[derive(Debug)]
+pub enum DbError {
+    Sqlite(rusqlite::Error),
+    /// The database was migrated by a newer server than this one
+    SchemaTooNew {
+        found: u32,
+        supported: u32,
+    },
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::Error),
+    /// Couldn't get a connection from the pool
+    #[cfg(feature = "postgres")]
+    Pool(r2d2::Error),
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Sqlite(e) => write!(f, "{}", e),
+            DbError::SchemaTooNew { found, supported } => write!(
+                f,
+                "Database schema version {} is newer than this server supports ({})",
+                found, supported
+            ),
+            #[cfg(feature = "postgres")]
+            DbError::Postgres(e) => write!(f, "{}", e),
+            #[cfg(feature = "postgres")]
+            DbError::Pool(e) => write!(f, "Connection pool error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(error: rusqlite::Error) -> Self {
+        DbError::Sqlite(error)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<postgres::Error> for DbError {
+    fn from(error: postgres::Error) -> Self {
+        DbError::Postgres(error)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<r2d2::Error> for DbError {
+    fn from(error: r2d2::Error) -> Self {
+        DbError::Pool(error)
+    }
+}
+
+/// Persistence for authentication, workspaces, sharing and auditing.
+///
+/// Timestamps are stored as Unix seconds. Token and code generation happens
+/// in the store so every backend produces the same formats.
+pub trait AuthStore: Send + Sync {
+    // ===== User operations =====
+
+    /// Get a user by ID
+    fn get_user(&self, user_id: &str) -> Result<Option<UserInfo>, DbError>;
+
+    /// Get a user by email
+    fn get_user_by_email(&self, email: &str) -> Result<Option<UserInfo>, DbError>;
+
+    /// Create or get a user by email (returns user ID)
+    fn get_or_create_user(&self, email: &str) -> Result<String, DbError>;
+
+    /// Update user's last login time
+    fn update_last_login(&self, user_id: &str) -> Result<(), DbError>;
+
+    /// List all users, oldest first
+    fn list_users(&self) -> Result<Vec<UserInfo>, DbError>;
+
+    /// Delete a user and all related data (devices, sessions, workspaces, share_sessions cascade)
+    /// Returns the list of workspace IDs that were deleted (for file cleanup)
+    fn delete_user(&self, user_id: &str) -> Result<Vec<String>, DbError>;
+
+    // ===== Device operations =====
+
+    /// Create a new device
+    fn create_device(
+        &self,
+        user_id: &str,
+        name: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<String, DbError>;
+
+    /// Get devices for a user
+    fn get_user_devices(&self, user_id: &str) -> Result<Vec<DeviceInfo>, DbError>;
+
+    /// Update device last seen time
+    fn update_device_last_seen(&self, device_id: &str) -> Result<(), DbError>;
+
+    /// Delete a device (and its sessions)
+    fn delete_device(&self, device_id: &str) -> Result<(), DbError>;
+
+    // ===== Magic link operations =====
+
+    /// Create a magic link token
+    fn create_magic_token(&self, email: &str, expires_at: DateTime<Utc>)
+    -> Result<String, DbError>;
+
+    /// Verify and consume a magic token (returns email if valid)
+    fn verify_magic_token(&self, token: &str) -> Result<Option<String>, DbError>;
+
+    /// Clean up expired magic tokens
+    fn cleanup_expired_magic_tokens(&self) -> Result<usize, DbError>;
+
+    /// Count recent magic tokens for an email (for rate limiting)
+    fn count_recent_magic_tokens(&self, email: &str, since: DateTime<Utc>) -> Result<i64, DbError>;
+
+    // ===== Session operations =====
+
+    /// Create a new auth session
+    fn create_session(
+        &self,
+        user_id: &str,
+        device_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, DbError>;
+
+    /// Validate a session token (returns session info if valid)
+    fn validate_session(&self, token: &str) -> Result<Option<SessionInfo>, DbError>;
+
+    /// Delete a session
+    fn delete_session(&self, token: &str) -> Result<(), DbError>;
+
+    /// Delete all sessions for a user (returns how many were deleted)
+    fn delete_user_sessions(&self, user_id: &str) -> Result<usize, DbError>;
+
+    /// Clean up expired sessions
+    fn cleanup_expired_sessions(&self) -> Result<usize, DbError>;
+
+    // ===== API token operations =====
+
+    /// Create a personal access token (returns `(id, token)`)
+    fn create_api_token(
+        &self,
+        user_id: &str,
+        name: &str,
+        workspace_id: Option<&str>,
+        permission: ApiTokenPermission,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(String, String), DbError>;
+
+    /// Validate a personal access token and record its use
+    fn validate_api_token(&self, token: &str) -> Result<Option<ApiTokenInfo>, DbError>;
+
+    /// Get all personal access tokens for a user
+    fn get_user_api_tokens(&self, user_id: &str) -> Result<Vec<ApiTokenInfo>, DbError>;
+
+    /// Revoke one of a user's personal access tokens (returns true if it existed)
+    fn delete_api_token(&self, user_id: &str, id: &str) -> Result<bool, DbError>;
+
+    /// Revoke all of a user's personal access tokens (returns how many were deleted)
+    fn delete_user_api_tokens(&self, user_id: &str) -> Result<usize, DbError>;
+
+    /// Clean up expired personal access tokens
+    fn cleanup_expired_api_tokens(&self) -> Result<usize, DbError>;
+
+    // ===== Device code operations =====
+
+    /// Create a device authorization code (returns `(device_code, user_code)`)
+    fn create_device_code(
+        &self,
+        device_name: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(String, String), DbError>;
+
+    /// Get a device code by its secret device code (including expired ones)
+    fn get_device_code(&self, device_code: &str) -> Result<Option<DeviceCodeInfo>, DbError>;
+
+    /// Get an unexpired device code by the code shown to the user
+    fn get_device_code_by_user_code(
+        &self,
+        user_code: &str,
+    ) -> Result<Option<DeviceCodeInfo>, DbError>;
+
+    /// Approve or deny a pending device code on behalf of a user.
+    ///
+    /// Returns false if the code is unknown, expired, or already resolved.
+    fn resolve_device_code(
+        &self,
+        user_code: &str,
+        user_id: &str,
+        approve: bool,
+    ) -> Result<bool, DbError>;
+
+    /// Delete a device code (returns true if it existed)
+    fn delete_device_code(&self, device_code: &str) -> Result<bool, DbError>;
+
+    /// Clean up expired device codes
+    fn cleanup_expired_device_codes(&self) -> Result<usize, DbError>;
+
+    // ===== Passkey operations =====
+
+    /// Store the state of a started passkey registration or login
+    /// (returns the challenge ID)
+    fn create_passkey_challenge(
+        &self,
+        user_id: &str,
+        state: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, DbError>;
+
+    /// Remove a passkey challenge, returning its state if it hadn't expired.
+    ///
+    /// Each challenge can be taken at most once.
+    fn take_passkey_challenge(&self, id: &str) -> Result<Option<String>, DbError>;
+
+    /// Clean up expired passkey challenges
+    fn cleanup_expired_passkey_challenges(&self) -> Result<usize, DbError>;
+
+    /// Store a newly registered passkey
+    fn add_passkey(
+        &self,
+        id: &str,
+        user_id: &str,
+        name: Option<&str>,
+        credential: &str,
+    ) -> Result<(), DbError>;
+
+    /// Get all passkeys registered by a user
+    fn get_user_passkeys(&self, user_id: &str) -> Result<Vec<PasskeyInfo>, DbError>;
+
+    /// Record a successful passkey login, replacing the stored credential
+    /// (which carries the updated signature counter)
+    fn record_passkey_use(&self, id: &str, credential: &str) -> Result<(), DbError>;
+
+    /// Delete one of a user's passkeys (returns true if it existed)
+    fn delete_passkey(&self, user_id: &str, id: &str) -> Result<bool, DbError>;
+
+    // ===== Workspace operations =====
+
+    /// Get or create a workspace for a user
+    fn get_or_create_workspace(&self, user_id: &str, name: &str) -> Result<String, DbError>;
+
+    /// Get all workspaces for a user
+    fn get_user_workspaces(&self, user_id: &str) -> Result<Vec<WorkspaceInfo>, DbError>;
+
+    /// Get a workspace by ID
+    fn get_workspace(&self, workspace_id: &str) -> Result<Option<WorkspaceInfo>, DbError>;
+
+    /// List every workspace on the server, oldest first
+    fn list_all_workspaces(&self) -> Result<Vec<WorkspaceInfo>, DbError>;
+
+    /// Delete a workspace with its members, invitations, scoped API tokens and
+    /// share sessions. Returns false if the workspace did not exist.
+    fn delete_workspace(&self, workspace_id: &str) -> Result<bool, DbError>;
+
+    // ===== Workspace membership operations =====
+
+    /// Get a user's role in a workspace (`None` if they have no access)
+    fn get_workspace_role(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceRole>, DbError>;
+
+    /// Get all members of a workspace, owner first
+    fn get_workspace_members(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceMemberInfo>, DbError>;
+
+    /// Add a member to a workspace, or change their role
+    fn add_workspace_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+        role: WorkspaceRole,
+    ) -> Result<(), DbError>;
+
+    /// Remove a member from a workspace
+    fn remove_workspace_member(&self, workspace_id: &str, user_id: &str) -> Result<bool, DbError>;
+
+    /// Get workspaces shared with a user (excluding ones they own)
+    fn get_shared_workspaces(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<(WorkspaceInfo, WorkspaceRole)>, DbError>;
+
+    /// Create an invitation for an email address to join a workspace
+    fn create_workspace_invite(
+        &self,
+        workspace_id: &str,
+        email: &str,
+        role: WorkspaceRole,
+        invited_by: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, DbError>;
+
+    /// Get pending (unexpired) invitations for a workspace
+    fn get_workspace_invites(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceInviteInfo>, DbError>;
+
+    /// Accept an invitation on behalf of a user.
+    ///
+    /// The invite is consumed and the user added as a member. Returns `None`
+    /// if the token is unknown, expired, or addressed to a different email.
+    fn accept_workspace_invite(
+        &self,
+        token: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceInviteInfo>, DbError>;
+
+    /// Revoke pending invitations for an email address
+    fn revoke_workspace_invites(&self, workspace_id: &str, email: &str) -> Result<usize, DbError>;
+
+    /// Clean up expired workspace invitations
+    fn cleanup_expired_workspace_invites(&self) -> Result<usize, DbError>;
+
+    // ===== Share session operations =====
+
+    /// Create a new share session
+    fn create_share_session(
+        &self,
+        workspace_id: &str,
+        owner_user_id: &str,
+        read_only: bool,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<String, DbError>;
+
+    /// Get a share session by code
+    fn get_share_session(&self, code: &str) -> Result<Option<ShareSessionInfo>, DbError>;
+
+    /// Get all share sessions for a user
+    fn get_user_share_sessions(&self, user_id: &str) -> Result<Vec<ShareSessionInfo>, DbError>;
+
+    /// Update read-only status for a share session
+    fn update_share_session_read_only(&self, code: &str, read_only: bool) -> Result<bool, DbError>;
+
+    /// Replace the scope of a share session (`None` or empty shares everything)
+    fn set_share_session_scope(
+        &self,
+        code: &str,
+        scope: Option<&ShareScope>,
+    ) -> Result<(), DbError>;
+
+    /// Delete a share session
+    fn delete_share_session(&self, code: &str) -> Result<bool, DbError>;
+
+    /// Clean up expired share sessions
+    fn cleanup_expired_share_sessions(&self) -> Result<usize, DbError>;
+
+    // ===== Audit log operations =====
+
+    /// Append an event to the audit log
+    fn append_audit_event(
+        &self,
+        action: &str,
+        user_id: Option<&str>,
+        target: Option<&str>,
+        details: &serde_json::Value,
+    ) -> Result<i64, DbError>;
+
+    /// Get the most recent audit events, newest first, optionally for one user
+    fn get_audit_events(
+        &self,
+        user_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<AuditEventInfo>, DbError>;
+
+    // ===== Webhook operations =====
+
+    /// Register a webhook with a newly generated signing secret
+    fn create_webhook(
+        &self,
+        workspace_id: &str,
+        url: &str,
+        description: Option<&str>,
+        created_by: Option<&str>,
+    ) -> Result<WebhookInfo, DbError>;
+
+    /// Get a webhook by ID
+    fn get_webhook(&self, id: &str) -> Result<Option<WebhookInfo>, DbError>;
+
+    /// Get the webhooks registered on a workspace, oldest first
+    fn get_workspace_webhooks(&self, workspace_id: &str) -> Result<Vec<WebhookInfo>, DbError>;
+
+    /// Delete a webhook and its delivery log
+    fn delete_webhook(&self, workspace_id: &str, id: &str) -> Result<bool, DbError>;
+
+    /// Log a new pending delivery, returning its ID
+    fn create_webhook_delivery(
+        &self,
+        webhook_id: &str,
+        event: &str,
+        payload: &serde_json::Value,
+    ) -> Result<String, DbError>;
+
+    /// Record the outcome of a delivery attempt
+    fn update_webhook_delivery(
+        &self,
+        id: &str,
+        status: WebhookDeliveryStatus,
+        attempts: u32,
+        response_status: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<(), DbError>;
+
+    /// Get a webhook's most recent deliveries, newest first
+    fn get_webhook_deliveries(
+        &self,
+        webhook_id: &str,
+        limit: usize,
+    ) -> Result<Vec<WebhookDeliveryInfo>, DbError>;
+
+    /// Delete all but the `keep` most recent deliveries of a webhook
+    fn trim_webhook_deliveries(&self, webhook_id: &str, keep: usize) -> Result<usize, DbError>;
+}
+
+/// Authentication repository shared by handlers, hooks and admin commands.
+///
+/// Derefs to the configured [`AuthStore`], so store methods are called on it
+/// directly (`repo.get_user(...)`).
+#[derive(Clone)]
+pub struct AuthRepo {
+    store: Arc<dyn AuthStore>,
+}
+
+impl AuthRepo {
+    /// Create a SQLite-backed repository with the given connection
+    pub fn new(conn: Connection) -> Self {
+        Self::with_store(SqliteAuthStore::new(conn))
+    }
+
+    /// Create a repository backed by any store
+    pub fn with_store(store: impl AuthStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+        }
+    }
+}
+
+impl Deref for AuthRepo {
+    type Target = dyn AuthStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.store.as_ref()
+    }
+}
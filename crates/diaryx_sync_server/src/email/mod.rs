@@ -7,7 +7,7 @@ use lettre::{
 use std::sync::Arc;
 use tracing::{error, info};
 
-/// Email service for sending magic links
+/// Email service for sending magic links and workspace invitations
 pub struct EmailService {
     config: Arc<Config>,
     transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
@@ -94,6 +94,36 @@ impl EmailService {
         &self,
         to_email: &str,
         magic_link_url: &str,
+    ) -> Result<(), EmailError> {
+        let subject = "Sign in to Diaryx";
+        let body = self.build_magic_link_email_body(magic_link_url);
+        self.send_html(to_email, subject, body).await?;
+
+        info!("Magic link email sent to {}", to_email);
+        Ok(())
+    }
+
+    /// Send an invitation to join a workspace
+    pub async fn send_workspace_invite(
+        &self,
+        to_email: &str,
+        inviter_email: &str,
+        workspace_name: &str,
+        invite_url: &str,
+    ) -> Result<(), EmailError> {
+        let subject = format!("{} invited you to a Diaryx workspace", inviter_email);
+        let body = self.build_invite_email_body(inviter_email, workspace_name, invite_url);
+        self.send_html(to_email, &subject, body).await?;
+
+        info!("Workspace invite email sent to {}", to_email);
+        Ok(())
+    }
+
+    async fn send_html(
+        &self,
+        to_email: &str,
+        subject: &str,
+        body: String,
     ) -> Result<(), EmailError> {
         let transport = self.transport.as_ref().ok_or(EmailError::NotConfigured)?;
 
@@ -102,9 +132,6 @@ impl EmailService {
             self.config.smtp.from_name, self.config.smtp.from_email
         );
 
-        let subject = "Sign in to Diaryx";
-        let body = self.build_magic_link_email_body(magic_link_url);
-
         let email = Message::builder()
             .from(
                 from.parse()
@@ -123,7 +150,6 @@ impl EmailService {
             .await
             .map_err(|e| EmailError::SendError(e.to_string()))?;
 
-        info!("Magic link email sent to {}", to_email);
         Ok(())
     }
 
@@ -168,4 +194,63 @@ impl EmailService {
             self.config.magic_link_expiry_minutes, magic_link_url, magic_link_url, magic_link_url
         )
     }
+
+    fn build_invite_email_body(
+        &self,
+        inviter_email: &str,
+        workspace_name: &str,
+        invite_url: &str,
+    ) -> String {
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Join a Diaryx workspace</title>
+</head>
+<body style="font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; line-height: 1.6; color: #333; max-width: 600px; margin: 0 auto; padding: 20px;">
+    <div style="text-align: center; margin-bottom: 30px;">
+        <h1 style="color: #1a1a1a; margin-bottom: 10px;">Diaryx</h1>
+    </div>
+
+    <div style="background-color: #f9f9f9; border-radius: 8px; padding: 30px; margin-bottom: 20px;">
+        <h2 style="margin-top: 0; color: #1a1a1a;">You're invited</h2>
+        <p>{} invited you to the workspace <strong>{}</strong>. Sign in with this email address, then accept the invitation.</p>
+
+        <div style="text-align: center; margin: 30px 0;">
+            <a href="{}" style="display: inline-block; background-color: #0066cc; color: white; text-decoration: none; padding: 14px 28px; border-radius: 6px; font-weight: 500;">
+                Accept invitation
+            </a>
+        </div>
+
+        <p style="color: #666; font-size: 14px;">
+            If the button doesn't work, copy and paste this link into your browser:
+        </p>
+        <p style="word-break: break-all; color: #0066cc; font-size: 14px;">
+            <a href="{}" style="color: #0066cc;">{}</a>
+        </p>
+    </div>
+
+    <div style="text-align: center; color: #999; font-size: 12px;">
+        <p>If you weren't expecting this invitation, you can safely ignore it.</p>
+        <p>&copy; Diaryx</p>
+    </div>
+</body>
+</html>"#,
+            escape_html(inviter_email),
+            escape_html(workspace_name),
+            invite_url,
+            invite_url,
+            invite_url
+        )
+    }
+}
+
+/// Escape user-provided text for inclusion in an HTML email
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
@@ -0,0 +1,257 @@
+use crate::admin::{self, AdminError, DEFAULT_AUDIT_LIMIT, DEFAULT_KEEP_UPDATES};
+use crate::audit::{self, AuditAction};
+use crate::auth::{AuthUser, RequireAuth};
+use crate::config::Config;
+use crate::db::AuthRepo;
+use crate::sync_v2::SyncV2State;
+use axum::{
+    Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    routing::{delete, get, post},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+/// Shared state for admin handlers
+#[derive(Clone)]
+pub struct AdminState {
+    pub repo: Arc<AuthRepo>,
+    pub config: Arc<Config>,
+    pub sync_v2: Arc<SyncV2State>,
+}
+
+/// Request to compact workspace storage
+#[derive(Debug, Default, Deserialize)]
+pub struct CompactRequest {
+    /// Only compact this workspace (omit for all)
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+    #[serde(default)]
+    pub keep_updates: Option<usize>,
+}
+
+/// Query for the audit log
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    /// Only return events for this user ID or email address
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Create admin routes (nested under `/api/admin`)
+///
+/// Every route requires a login session whose email is listed in `ADMIN_EMAILS`.
+pub fn admin_routes(state: AdminState) -> Router {
+    Router::new()
+        .route("/stats", get(get_stats))
+        .route("/users", get(list_users))
+        .route("/users/{user}/logout", post(logout_user))
+        .route("/workspaces", get(list_workspaces))
+        .route("/workspaces/{workspace_id}", delete(delete_workspace))
+        .route("/audit", get(get_audit_log))
+        .route("/cleanup", post(run_cleanup))
+        .route("/compact", post(run_compact))
+        .with_state(state)
+}
+
+fn error_response(status: StatusCode, message: &str) -> axum::response::Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+fn admin_error_response(e: AdminError) -> axum::response::Response {
+    match e {
+        AdminError::NotFound(_) => error_response(StatusCode::NOT_FOUND, &e.to_string()),
+        _ => {
+            error!("Admin operation failed: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Admin operation failed")
+        }
+    }
+}
+
+/// Rejection response for users who aren't configured as admins
+fn reject_non_admin(state: &AdminState, auth: &AuthUser) -> Option<axum::response::Response> {
+    if state.config.is_admin(&auth.user.email) {
+        return None;
+    }
+    warn!("Non-admin {} tried to use admin endpoint", auth.user.email);
+    Some(error_response(
+        StatusCode::FORBIDDEN,
+        "Admin access required",
+    ))
+}
+
+/// GET /api/admin/stats - Live connection and room counts
+async fn get_stats(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    Json(state.sync_v2.connection_stats()).into_response()
+}
+
+/// GET /api/admin/users - List users
+async fn list_users(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    match admin::list_users(&state.repo) {
+        Ok(users) => Json(serde_json::json!({ "users": users })).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+/// GET /api/admin/workspaces - List workspaces with sizes and connected peers
+async fn list_workspaces(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    match admin::list_workspaces(&state.repo, &state.sync_v2.store) {
+        Ok(workspaces) => {
+            let workspaces: Vec<_> = workspaces
+                .into_iter()
+                .map(|w| {
+                    let peers = state.sync_v2.stats.workspace_peer_count(&w.id);
+                    let mut json = serde_json::to_value(w).unwrap_or_default();
+                    json["connected_peers"] = peers.into();
+                    json
+                })
+                .collect();
+            Json(serde_json::json!({ "workspaces": workspaces })).into_response()
+        }
+        Err(e) => admin_error_response(e),
+    }
+}
+
+/// POST /api/admin/users/{user}/logout - Revoke a user's sessions and API tokens
+///
+/// `user` is a user ID or email address.
+async fn logout_user(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+    Path(user): Path<String>,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    match admin::force_logout(&state.repo, &user) {
+        Ok(report) => {
+            info!("Admin {} logged out {}", auth.user.email, report.email);
+            audit::record(
+                &state.repo,
+                AuditAction::AdminLogout,
+                Some(&auth.user.id),
+                Some(&report.user_id),
+                serde_json::json!({
+                    "sessions_revoked": report.sessions_revoked,
+                    "api_tokens_revoked": report.api_tokens_revoked,
+                }),
+            );
+            Json(report).into_response()
+        }
+        Err(e) => admin_error_response(e),
+    }
+}
+
+/// DELETE /api/admin/workspaces/{workspace_id} - Delete a workspace and its data
+async fn delete_workspace(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+    Path(workspace_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    match admin::delete_workspace(&state.repo, &state.sync_v2.store, &workspace_id) {
+        Ok(()) => {
+            info!(
+                "Admin {} deleted workspace {}",
+                auth.user.email, workspace_id
+            );
+            audit::record(
+                &state.repo,
+                AuditAction::AdminWorkspaceDeleted,
+                Some(&auth.user.id),
+                Some(&workspace_id),
+                serde_json::json!({}),
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => admin_error_response(e),
+    }
+}
+
+/// GET /api/admin/audit?user=...&limit=... - Recent audit events, newest first
+async fn get_audit_log(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).min(1000);
+    match admin::audit_log(&state.repo, query.user.as_deref(), limit) {
+        Ok(events) => Json(serde_json::json!({ "events": events })).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+/// POST /api/admin/cleanup - Remove expired tokens, sessions and invites now
+async fn run_cleanup(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    match admin::cleanup(&state.repo) {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => admin_error_response(e),
+    }
+}
+
+/// POST /api/admin/compact - Compact CRDT update logs
+async fn run_compact(
+    State(state): State<AdminState>,
+    RequireAuth(auth): RequireAuth,
+    body: Option<Json<CompactRequest>>,
+) -> impl IntoResponse {
+    if let Some(resp) = reject_non_admin(&state, &auth) {
+        return resp;
+    }
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let keep_updates = req.keep_updates.unwrap_or(DEFAULT_KEEP_UPDATES);
+
+    // Compaction rewrites every document; keep it off the async workers
+    let result = tokio::task::spawn_blocking(move || {
+        admin::compact(
+            &state.repo,
+            &state.sync_v2.store,
+            req.workspace_id.as_deref(),
+            keep_updates,
+        )
+    })
+    .await;
+
+    match result {
+        Ok(Ok(report)) => Json(report).into_response(),
+        Ok(Err(e)) => admin_error_response(e),
+        Err(e) => {
+            error!("Compaction task failed: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Admin operation failed")
+        }
+    }
+}
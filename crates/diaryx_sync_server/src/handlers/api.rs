@@ -1,15 +1,19 @@
-use crate::auth::RequireAuth;
-use crate::db::AuthRepo;
-use crate::sync_v2::{SnapshotImportMode, SyncV2State};
+use crate::audit::{self, AuditAction};
+use crate::auth::{AuthUser, RequireAnyAuth, RequireAuth};
+use crate::db::{ApiTokenPermission, AuthRepo, WorkspaceRole};
+use crate::metrics::{SnapshotOp, metrics};
+use crate::sync_v2::{RestoreResult, SnapshotError, SnapshotImportMode, SyncV2State};
 use axum::body::Bytes;
 use axum::{
     Router,
     extract::{Query, State},
     http::{HeaderMap, StatusCode, header},
+    response::Response,
     response::{IntoResponse, Json},
-    routing::get,
+    routing::{get, post},
 };
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::error;
 
@@ -18,6 +22,8 @@ use tracing::error;
 pub struct ApiState {
     pub repo: Arc<AuthRepo>,
     pub sync_v2: Arc<SyncV2State>,
+    /// Server-side snapshots kept per workspace (0 keeps all)
+    pub snapshot_retention: usize,
 }
 
 /// Server status response
@@ -34,6 +40,8 @@ pub struct StatusResponse {
 pub struct WorkspaceResponse {
     pub id: String,
     pub name: String,
+    /// The requesting user's role in this workspace
+    pub role: WorkspaceRole,
 }
 
 /// User has data response
@@ -53,34 +61,76 @@ pub fn api_routes(state: ApiState) -> Router {
             "/workspaces/{workspace_id}/snapshot",
             get(get_workspace_snapshot).post(upload_workspace_snapshot),
         )
+        .route(
+            "/workspaces/{workspace_id}/archive",
+            get(get_workspace_archive).post(upload_workspace_archive),
+        )
+        .route(
+            "/workspaces/{workspace_id}/snapshots",
+            get(list_snapshots).post(create_snapshot),
+        )
+        .route(
+            "/workspaces/{workspace_id}/snapshots/{snapshot_id}",
+            get(download_snapshot).delete(delete_snapshot),
+        )
+        .route(
+            "/workspaces/{workspace_id}/snapshots/{snapshot_id}/restore",
+            post(restore_snapshot),
+        )
+        .route(
+            "/workspaces/{workspace_id}/restore",
+            post(restore_workspace),
+        )
         .route("/user/has-data", get(check_user_has_data))
         .with_state(state)
 }
 
 /// GET /api/status - Get server status (public endpoint)
-async fn get_status(State(_state): State<ApiState>) -> impl IntoResponse {
-    // Siphonophore doesn't expose global stats; return version info only
+async fn get_status(State(state): State<ApiState>) -> impl IntoResponse {
+    let stats = state.sync_v2.connection_stats();
     Json(StatusResponse {
         status: "ok".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        active_connections: 0,
-        active_rooms: 0,
+        active_connections: stats.active_connections,
+        active_rooms: stats.active_rooms,
     })
 }
 
-/// GET /api/workspaces - List user's workspaces
+/// Whether the request's personal access token (if any) covers this workspace
+/// and has the permission `check` asks for. Login sessions are always allowed.
+fn token_allows(
+    auth: &AuthUser,
+    workspace_id: &str,
+    check: impl Fn(&ApiTokenPermission) -> bool,
+) -> bool {
+    auth.api_token
+        .as_ref()
+        .is_none_or(|t| t.covers_workspace(workspace_id) && check(&t.permission))
+}
+
+/// GET /api/workspaces - List user's own and shared workspaces
 async fn list_workspaces(
     State(state): State<ApiState>,
-    RequireAuth(auth): RequireAuth,
+    RequireAnyAuth(auth): RequireAnyAuth,
 ) -> impl IntoResponse {
-    let workspaces = state
+    let owned = state
         .repo
         .get_user_workspaces(&auth.user.id)
         .unwrap_or_default()
         .into_iter()
-        .map(|w| WorkspaceResponse {
+        .map(|w| (w, WorkspaceRole::Owner));
+    let shared = state
+        .repo
+        .get_shared_workspaces(&auth.user.id)
+        .unwrap_or_default();
+
+    let workspaces = owned
+        .chain(shared)
+        .filter(|(w, _)| token_allows(&auth, &w.id, |_| true))
+        .map(|(w, role)| WorkspaceResponse {
             id: w.id,
             name: w.name,
+            role,
         })
         .collect::<Vec<_>>();
 
@@ -90,23 +140,30 @@ async fn list_workspaces(
 /// GET /api/workspaces/:workspace_id - Get workspace info
 async fn get_workspace(
     State(state): State<ApiState>,
-    RequireAuth(auth): RequireAuth,
+    RequireAnyAuth(auth): RequireAnyAuth,
     axum::extract::Path(workspace_id): axum::extract::Path<String>,
 ) -> impl IntoResponse {
+    if !token_allows(&auth, &workspace_id, |_| true) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
     let workspace = match state.repo.get_workspace(&workspace_id) {
         Ok(Some(w)) => w,
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
 
-    // Verify ownership
-    if workspace.user_id != auth.user.id {
-        return StatusCode::NOT_FOUND.into_response();
-    }
+    // Verify membership
+    let role = match state.repo.get_workspace_role(&workspace_id, &auth.user.id) {
+        Ok(Some(role)) => role,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
 
     Json(WorkspaceResponse {
         id: workspace.id,
         name: workspace.name,
+        role,
     })
     .into_response()
 }
@@ -114,27 +171,30 @@ async fn get_workspace(
 /// GET /api/workspaces/:workspace_id/snapshot - Download workspace snapshot zip
 async fn get_workspace_snapshot(
     State(state): State<ApiState>,
-    RequireAuth(auth): RequireAuth,
+    RequireAnyAuth(auth): RequireAnyAuth,
     axum::extract::Path(workspace_id): axum::extract::Path<String>,
 ) -> impl IntoResponse {
-    let workspace = match state.repo.get_workspace(&workspace_id) {
-        Ok(Some(w)) => w,
+    // Every token permission can download snapshots
+    if !token_allows(&auth, &workspace_id, |_| true) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    // Verify membership
+    match state.repo.get_workspace_role(&workspace_id, &auth.user.id) {
+        Ok(Some(_)) => {}
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    };
-
-    // Verify ownership
-    if workspace.user_id != auth.user.id {
-        return StatusCode::NOT_FOUND.into_response();
     }
 
     let snapshot = match state.sync_v2.store.export_snapshot_zip(&workspace_id) {
         Ok(bytes) => bytes,
         Err(err) => {
             error!("Snapshot export failed for {}: {:?}", workspace_id, err);
+            metrics().record_snapshot(SnapshotOp::Export, false);
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
     };
+    metrics().record_snapshot(SnapshotOp::Export, true);
 
     let mut headers = HeaderMap::new();
     headers.insert(header::CONTENT_TYPE, "application/zip".parse().unwrap());
@@ -159,19 +219,25 @@ struct SnapshotUploadQuery {
 /// POST /api/workspaces/:workspace_id/snapshot - Upload workspace snapshot zip
 async fn upload_workspace_snapshot(
     State(state): State<ApiState>,
-    RequireAuth(auth): RequireAuth,
+    RequireAnyAuth(auth): RequireAnyAuth,
     axum::extract::Path(workspace_id): axum::extract::Path<String>,
     Query(query): Query<SnapshotUploadQuery>,
     bytes: Bytes,
 ) -> impl IntoResponse {
-    let workspace = match state.repo.get_workspace(&workspace_id) {
-        Ok(Some(w)) => w,
+    if !token_allows(
+        &auth,
+        &workspace_id,
+        ApiTokenPermission::can_upload_snapshot,
+    ) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    // Viewers can download but not overwrite
+    match state.repo.get_workspace_role(&workspace_id, &auth.user.id) {
+        Ok(Some(role)) if role.can_write() => {}
+        Ok(Some(_)) => return StatusCode::FORBIDDEN.into_response(),
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    };
-
-    if workspace.user_id != auth.user.id {
-        return StatusCode::NOT_FOUND.into_response();
     }
 
     let mode = match query.mode.as_deref() {
@@ -187,9 +253,381 @@ async fn upload_workspace_snapshot(
         Ok(result) => result,
         Err(err) => {
             error!("Snapshot import failed for {}: {:?}", workspace_id, err);
+            metrics().record_snapshot(SnapshotOp::Import, false);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    metrics().record_snapshot(SnapshotOp::Import, true);
+
+    audit::record(
+        &state.repo,
+        AuditAction::SnapshotUploaded,
+        Some(&auth.user.id),
+        Some(&workspace_id),
+        serde_json::json!({
+            "mode": if mode == SnapshotImportMode::Merge { "merge" } else { "replace" },
+            "bytes": bytes.len(),
+            "files_imported": result.files_imported,
+            "api_token": auth.api_token.as_ref().map(|t| t.id.as_str()),
+        }),
+    );
+
+    Json(result).into_response()
+}
+
+/// The user's role in a workspace, or the status to respond with when they
+/// have none (404, so workspace IDs aren't revealed).
+fn member_role(
+    state: &ApiState,
+    workspace_id: &str,
+    user_id: &str,
+) -> Result<WorkspaceRole, StatusCode> {
+    match state.repo.get_workspace_role(workspace_id, user_id) {
+        Ok(Some(role)) => Ok(role),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Map archive, snapshot and restore errors to a response.
+fn snapshot_error_response(workspace_id: &str, err: SnapshotError) -> Response {
+    let status = match &err {
+        SnapshotError::NotFound => StatusCode::NOT_FOUND,
+        SnapshotError::NotEmpty | SnapshotError::HistoryUnavailable(_) => StatusCode::CONFLICT,
+        SnapshotError::Checksum(_)
+        | SnapshotError::Parse(_)
+        | SnapshotError::Json(_)
+        | SnapshotError::ZipFormat(_) => StatusCode::BAD_REQUEST,
+        SnapshotError::Zip(_) | SnapshotError::Storage(_) => {
+            error!("Snapshot operation failed for {}: {:?}", workspace_id, err);
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
     };
+    (
+        status,
+        Json(serde_json::json!({ "error": err.to_string() })),
+    )
+        .into_response()
+}
+
+fn zip_download(filename: String, bytes: Vec<u8>) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "application/zip".parse().unwrap());
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{}\"", filename)
+            .parse()
+            .unwrap(),
+    );
+    (headers, bytes).into_response()
+}
+
+/// GET /api/workspaces/:workspace_id/archive - Download a full-fidelity archive
+async fn get_workspace_archive(
+    State(state): State<ApiState>,
+    RequireAnyAuth(auth): RequireAnyAuth,
+    axum::extract::Path(workspace_id): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    if !token_allows(&auth, &workspace_id, |_| true) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Err(status) = member_role(&state, &workspace_id, &auth.user.id) {
+        return status.into_response();
+    }
+
+    match state.sync_v2.store.export_archive(&workspace_id, None) {
+        Ok(bytes) => {
+            metrics().record_snapshot(SnapshotOp::Export, true);
+            zip_download(format!("diaryx-archive-{}.zip", workspace_id), bytes)
+        }
+        Err(err) => {
+            metrics().record_snapshot(SnapshotOp::Export, false);
+            snapshot_error_response(&workspace_id, err)
+        }
+    }
+}
+
+/// POST /api/workspaces/:workspace_id/archive - Import an archive, history
+/// included, into an empty workspace
+async fn upload_workspace_archive(
+    State(state): State<ApiState>,
+    RequireAnyAuth(auth): RequireAnyAuth,
+    axum::extract::Path(workspace_id): axum::extract::Path<String>,
+    bytes: Bytes,
+) -> impl IntoResponse {
+    if !token_allows(
+        &auth,
+        &workspace_id,
+        ApiTokenPermission::can_upload_snapshot,
+    ) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match member_role(&state, &workspace_id, &auth.user.id) {
+        Ok(role) if role.can_write() => {}
+        Ok(_) => return StatusCode::FORBIDDEN.into_response(),
+        Err(status) => return status.into_response(),
+    }
+
+    let result = match state.sync_v2.store.import_archive(&workspace_id, &bytes) {
+        Ok(result) => result,
+        Err(err) => {
+            metrics().record_snapshot(SnapshotOp::Import, false);
+            return snapshot_error_response(&workspace_id, err);
+        }
+    };
+    metrics().record_snapshot(SnapshotOp::Import, true);
+
+    audit::record(
+        &state.repo,
+        AuditAction::ArchiveImported,
+        Some(&auth.user.id),
+        Some(&workspace_id),
+        serde_json::json!({
+            "bytes": bytes.len(),
+            "documents": result.documents,
+            "updates": result.updates,
+            "api_token": auth.api_token.as_ref().map(|t| t.id.as_str()),
+        }),
+    );
+
+    Json(result).into_response()
+}
+
+/// GET /api/workspaces/:workspace_id/snapshots - List server-side snapshots
+async fn list_snapshots(
+    State(state): State<ApiState>,
+    RequireAnyAuth(auth): RequireAnyAuth,
+    axum::extract::Path(workspace_id): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    if !token_allows(&auth, &workspace_id, |_| true) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Err(status) = member_role(&state, &workspace_id, &auth.user.id) {
+        return status.into_response();
+    }
+
+    match state.sync_v2.store.list_snapshots(&workspace_id) {
+        Ok(snapshots) => Json(snapshots).into_response(),
+        Err(err) => snapshot_error_response(&workspace_id, err),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CreateSnapshotRequest {
+    label: Option<String>,
+}
+
+/// POST /api/workspaces/:workspace_id/snapshots - Take a server-side snapshot
+async fn create_snapshot(
+    State(state): State<ApiState>,
+    RequireAnyAuth(auth): RequireAnyAuth,
+    axum::extract::Path(workspace_id): axum::extract::Path<String>,
+    body: Option<Json<CreateSnapshotRequest>>,
+) -> impl IntoResponse {
+    if !token_allows(
+        &auth,
+        &workspace_id,
+        ApiTokenPermission::can_upload_snapshot,
+    ) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match member_role(&state, &workspace_id, &auth.user.id) {
+        Ok(role) if role.can_write() => {}
+        Ok(_) => return StatusCode::FORBIDDEN.into_response(),
+        Err(status) => return status.into_response(),
+    }
+
+    let Json(body) = body.unwrap_or_default();
+    let store = &state.sync_v2.store;
+    let snapshot = match store.create_snapshot(&workspace_id, body.label.as_deref()) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            metrics().record_snapshot(SnapshotOp::Export, false);
+            return snapshot_error_response(&workspace_id, err);
+        }
+    };
+    metrics().record_snapshot(SnapshotOp::Export, true);
+
+    if let Err(err) = store.prune_snapshots(&workspace_id, state.snapshot_retention) {
+        error!("Snapshot pruning failed for {}: {:?}", workspace_id, err);
+    }
+
+    audit::record(
+        &state.repo,
+        AuditAction::SnapshotCreated,
+        Some(&auth.user.id),
+        Some(&workspace_id),
+        serde_json::json!({
+            "snapshot_id": snapshot.id,
+            "label": snapshot.label,
+            "api_token": auth.api_token.as_ref().map(|t| t.id.as_str()),
+        }),
+    );
+
+    (StatusCode::CREATED, Json(snapshot)).into_response()
+}
+
+/// GET /api/workspaces/:workspace_id/snapshots/:snapshot_id - Download a
+/// server-side snapshot's archive
+async fn download_snapshot(
+    State(state): State<ApiState>,
+    RequireAnyAuth(auth): RequireAnyAuth,
+    axum::extract::Path((workspace_id, snapshot_id)): axum::extract::Path<(String, String)>,
+) -> impl IntoResponse {
+    if !token_allows(&auth, &workspace_id, |_| true) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Err(status) = member_role(&state, &workspace_id, &auth.user.id) {
+        return status.into_response();
+    }
+
+    match state
+        .sync_v2
+        .store
+        .snapshot_archive(&workspace_id, &snapshot_id)
+    {
+        Ok(bytes) => zip_download(format!("diaryx-archive-{}.zip", snapshot_id), bytes),
+        Err(err) => snapshot_error_response(&workspace_id, err),
+    }
+}
+
+/// DELETE /api/workspaces/:workspace_id/snapshots/:snapshot_id - Delete a
+/// server-side snapshot (owner only)
+async fn delete_snapshot(
+    State(state): State<ApiState>,
+    RequireAuth(auth): RequireAuth,
+    axum::extract::Path((workspace_id, snapshot_id)): axum::extract::Path<(String, String)>,
+) -> impl IntoResponse {
+    match member_role(&state, &workspace_id, &auth.user.id) {
+        Ok(WorkspaceRole::Owner) => {}
+        Ok(_) => return StatusCode::FORBIDDEN.into_response(),
+        Err(status) => return status.into_response(),
+    }
+
+    if let Err(err) = state
+        .sync_v2
+        .store
+        .delete_snapshot(&workspace_id, &snapshot_id)
+    {
+        return snapshot_error_response(&workspace_id, err);
+    }
+
+    audit::record(
+        &state.repo,
+        AuditAction::SnapshotDeleted,
+        Some(&auth.user.id),
+        Some(&workspace_id),
+        serde_json::json!({ "snapshot_id": snapshot_id }),
+    );
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// POST /api/workspaces/:workspace_id/snapshots/:snapshot_id/restore -
+/// Restore a workspace to a server-side snapshot
+async fn restore_snapshot(
+    State(state): State<ApiState>,
+    RequireAnyAuth(auth): RequireAnyAuth,
+    axum::extract::Path((workspace_id, snapshot_id)): axum::extract::Path<(String, String)>,
+) -> impl IntoResponse {
+    if !token_allows(
+        &auth,
+        &workspace_id,
+        ApiTokenPermission::can_upload_snapshot,
+    ) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match member_role(&state, &workspace_id, &auth.user.id) {
+        Ok(role) if role.can_write() => {}
+        Ok(_) => return StatusCode::FORBIDDEN.into_response(),
+        Err(status) => return status.into_response(),
+    }
+
+    let result = state
+        .sync_v2
+        .store
+        .restore_snapshot(&workspace_id, &snapshot_id);
+    finish_restore(
+        &state,
+        &auth,
+        &workspace_id,
+        result,
+        serde_json::json!({ "snapshot_id": snapshot_id }),
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct RestoreRequest {
+    /// Point in time to restore to
+    at: DateTime<Utc>,
+}
+
+/// POST /api/workspaces/:workspace_id/restore - Restore a workspace to a
+/// point in time by replaying its update log
+async fn restore_workspace(
+    State(state): State<ApiState>,
+    RequireAnyAuth(auth): RequireAnyAuth,
+    axum::extract::Path(workspace_id): axum::extract::Path<String>,
+    Json(body): Json<RestoreRequest>,
+) -> impl IntoResponse {
+    if !token_allows(
+        &auth,
+        &workspace_id,
+        ApiTokenPermission::can_upload_snapshot,
+    ) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match member_role(&state, &workspace_id, &auth.user.id) {
+        Ok(role) if role.can_write() => {}
+        Ok(_) => return StatusCode::FORBIDDEN.into_response(),
+        Err(status) => return status.into_response(),
+    }
+
+    let result = state
+        .sync_v2
+        .store
+        .restore_to(&workspace_id, body.at.timestamp_millis());
+    finish_restore(
+        &state,
+        &auth,
+        &workspace_id,
+        result,
+        serde_json::json!({ "at": body.at.to_rfc3339() }),
+    )
+    .await
+}
+
+/// Update loaded rooms, record metrics and the audit event for a restore and
+/// build the response.
+async fn finish_restore(
+    state: &ApiState,
+    auth: &AuthUser,
+    workspace_id: &str,
+    result: Result<RestoreResult, SnapshotError>,
+    mut details: serde_json::Value,
+) -> Response {
+    let result = match result {
+        Ok(result) => result,
+        Err(err) => {
+            metrics().record_snapshot(SnapshotOp::Restore, false);
+            return snapshot_error_response(workspace_id, err);
+        }
+    };
+    metrics().record_snapshot(SnapshotOp::Restore, true);
+    state.sync_v2.restored(workspace_id, &result).await;
+
+    details["files_restored"] = result.files_restored.into();
+    details["files_deleted"] = result.files_deleted.into();
+    details["backup_snapshot_id"] = result.backup_snapshot_id.clone().into();
+    details["api_token"] = auth.api_token.as_ref().map(|t| t.id.clone()).into();
+    audit::record(
+        &state.repo,
+        AuditAction::WorkspaceRestored,
+        Some(&auth.user.id),
+        Some(workspace_id),
+        details,
+    );
 
     Json(result).into_response()
 }
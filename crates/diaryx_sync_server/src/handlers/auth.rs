@@ -1,6 +1,11 @@
-use crate::auth::{MagicLinkService, RequireAuth};
-use crate::db::AuthRepo;
+use crate::audit::{self, AuditAction};
+use crate::auth::{
+    DeviceCodeError, DeviceCodeService, MagicLinkService, PasskeyError, PasskeyService, RequireAuth,
+};
+use crate::db::{ApiTokenInfo, ApiTokenPermission, AuthRepo};
 use crate::email::EmailService;
+use crate::metrics::{AuthFailure, metrics};
+use crate::sync_v2::WorkspaceStore;
 use axum::{
     Router,
     extract::{Query, State},
@@ -9,7 +14,7 @@ use axum::{
     routing::{delete, get, post},
 };
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::json;
 use std::sync::Arc;
 use tracing::{error, info, warn};
 
@@ -17,10 +22,12 @@ use tracing::{error, info, warn};
 #[derive(Clone)]
 pub struct AuthState {
     pub magic_link_service: Arc<MagicLinkService>,
+    pub device_code_service: Arc<DeviceCodeService>,
+    pub passkey_service: Arc<PasskeyService>,
     pub email_service: Arc<EmailService>,
     pub repo: Arc<AuthRepo>,
-    /// Path to workspace database files (for cleanup on account deletion)
-    pub workspaces_dir: Option<PathBuf>,
+    /// Workspace storage (for cleanup on account deletion)
+    pub store: Option<Arc<WorkspaceStore>>,
 }
 
 /// Request body for magic link request
@@ -88,6 +95,154 @@ pub struct DeviceResponse {
     pub last_seen_at: String,
 }
 
+/// Request body for starting a device-code login
+#[derive(Debug, Default, Deserialize)]
+pub struct DeviceCodeRequest {
+    pub device_name: Option<String>,
+}
+
+/// Response for starting a device-code login
+#[derive(Debug, Serialize)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub expires_in: i64,
+    pub interval: u64,
+}
+
+/// Request body for polling a device-code login
+#[derive(Debug, Deserialize)]
+pub struct DeviceTokenRequest {
+    pub device_code: String,
+}
+
+/// Query params for looking up a device-code login
+#[derive(Debug, Deserialize)]
+pub struct DeviceLookupQuery {
+    pub user_code: String,
+}
+
+/// Pending device-code login, shown to the approver
+#[derive(Debug, Serialize)]
+pub struct DeviceLookupResponse {
+    pub user_code: String,
+    pub device_name: Option<String>,
+    pub expires_at: String,
+}
+
+/// Request body for approving or denying a device-code login
+#[derive(Debug, Deserialize)]
+pub struct DeviceApproveRequest {
+    pub user_code: String,
+    #[serde(default = "default_true")]
+    pub approve: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Response for starting a passkey ceremony
+#[derive(Debug, Serialize)]
+pub struct PasskeyChallengeResponse<T> {
+    pub challenge_id: String,
+    /// WebAuthn options for `navigator.credentials.create()` / `.get()`
+    pub options: T,
+}
+
+/// Request body for finishing passkey registration
+#[derive(Debug, Deserialize)]
+pub struct PasskeyRegisterFinishRequest {
+    pub challenge_id: String,
+    pub name: Option<String>,
+    pub credential: webauthn_rs::prelude::RegisterPublicKeyCredential,
+}
+
+/// Request body for starting a passkey login
+#[derive(Debug, Deserialize)]
+pub struct PasskeyLoginStartRequest {
+    pub email: String,
+}
+
+/// Request body for finishing a passkey login
+#[derive(Debug, Deserialize)]
+pub struct PasskeyLoginFinishRequest {
+    pub challenge_id: String,
+    pub credential: webauthn_rs::prelude::PublicKeyCredential,
+    pub device_name: Option<String>,
+}
+
+/// Registered passkey in responses
+#[derive(Debug, Serialize)]
+pub struct PasskeyResponse {
+    pub id: String,
+    pub name: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+impl From<crate::db::PasskeyInfo> for PasskeyResponse {
+    fn from(p: crate::db::PasskeyInfo) -> Self {
+        Self {
+            id: p.id,
+            name: p.name,
+            created_at: p.created_at.to_rfc3339(),
+            last_used_at: p.last_used_at.map(|t| t.to_rfc3339()),
+        }
+    }
+}
+
+/// Request body for creating a personal access token
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    /// Limit the token to one workspace (default: all of the user's workspaces)
+    pub workspace_id: Option<String>,
+    #[serde(default = "default_token_permission")]
+    pub permission: ApiTokenPermission,
+    /// Days until the token expires (default: never)
+    pub expires_in_days: Option<i64>,
+}
+
+fn default_token_permission() -> ApiTokenPermission {
+    ApiTokenPermission::Read
+}
+
+/// Personal access token in responses (never includes the secret)
+#[derive(Debug, Serialize)]
+pub struct ApiTokenResponse {
+    pub id: String,
+    pub name: String,
+    pub workspace_id: Option<String>,
+    pub permission: ApiTokenPermission,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+impl From<ApiTokenInfo> for ApiTokenResponse {
+    fn from(t: ApiTokenInfo) -> Self {
+        Self {
+            id: t.id,
+            name: t.name,
+            workspace_id: t.workspace_id,
+            permission: t.permission,
+            created_at: t.created_at.to_rfc3339(),
+            last_used_at: t.last_used_at.map(|d| d.to_rfc3339()),
+            expires_at: t.expires_at.map(|d| d.to_rfc3339()),
+        }
+    }
+}
+
+/// Response for creating a personal access token
+#[derive(Debug, Serialize)]
+pub struct CreateApiTokenResponse {
+    /// The bearer token; only returned once
+    pub token: String,
+    #[serde(flatten)]
+    pub info: ApiTokenResponse,
+}
+
 /// Create auth routes
 pub fn auth_routes(state: AuthState) -> Router {
     Router::new()
@@ -98,6 +253,21 @@ pub fn auth_routes(state: AuthState) -> Router {
         .route("/account", delete(delete_account))
         .route("/devices", get(list_devices))
         .route("/devices/{device_id}", axum::routing::delete(delete_device))
+        .route("/device", get(lookup_device_code))
+        .route("/device/code", post(request_device_code))
+        .route("/device/token", post(poll_device_token))
+        .route("/device/approve", post(approve_device_code))
+        .route("/tokens", get(list_api_tokens).post(create_api_token))
+        .route("/tokens/{token_id}", delete(revoke_api_token))
+        .route("/passkeys", get(list_passkeys))
+        .route("/passkeys/{passkey_id}", delete(delete_passkey))
+        .route("/passkeys/register/start", post(start_passkey_registration))
+        .route(
+            "/passkeys/register/finish",
+            post(finish_passkey_registration),
+        )
+        .route("/passkeys/login/start", post(start_passkey_login))
+        .route("/passkeys/login/finish", post(finish_passkey_login))
         .with_state(state)
 }
 
@@ -205,6 +375,7 @@ async fn verify_magic_link(
     match result {
         Ok(verify_result) => {
             info!("User {} logged in successfully", verify_result.email);
+            record_login(&state, &verify_result, "magic_link");
             (
                 StatusCode::OK,
                 Json(VerifyResponse {
@@ -218,13 +389,16 @@ async fn verify_magic_link(
             )
                 .into_response()
         }
-        Err(crate::auth::MagicLinkError::InvalidToken) => (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid or expired link. Please request a new one.".to_string(),
-            }),
-        )
-            .into_response(),
+        Err(crate::auth::MagicLinkError::InvalidToken) => {
+            record_login_failure(&state, "magic_link");
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Invalid or expired link. Please request a new one.".to_string(),
+                }),
+            )
+                .into_response()
+        }
         Err(e) => {
             error!("Failed to verify magic link: {}", e);
             (
@@ -283,6 +457,14 @@ async fn logout(
 ) -> impl IntoResponse {
     if let Err(e) = state.repo.delete_session(&auth.session.token) {
         error!("Failed to delete session: {}", e);
+    } else {
+        audit::record(
+            &state.repo,
+            AuditAction::Logout,
+            Some(&auth.user.id),
+            Some(&auth.session.device_id),
+            json!({}),
+        );
     }
 
     StatusCode::NO_CONTENT
@@ -336,6 +518,14 @@ async fn delete_device(
         return StatusCode::INTERNAL_SERVER_ERROR;
     }
 
+    audit::record(
+        &state.repo,
+        AuditAction::DeviceDeleted,
+        Some(&auth.user.id),
+        Some(&device_id),
+        json!({}),
+    );
+
     StatusCode::NO_CONTENT
 }
 
@@ -363,21 +553,452 @@ async fn delete_account(
         }
     };
 
-    // Delete workspace database files from disk
-    if let Some(workspaces_dir) = &state.workspaces_dir {
+    // Delete workspace CRDT data
+    if let Some(store) = &state.store {
         for workspace_id in workspace_ids {
-            let db_path = workspaces_dir.join(format!("{}.db", workspace_id));
-            if db_path.exists() {
-                if let Err(e) = std::fs::remove_file(&db_path) {
-                    warn!("Failed to delete workspace file {:?}: {}", db_path, e);
-                } else {
-                    info!("Deleted workspace file: {:?}", db_path);
-                }
+            match store.delete_files(&workspace_id) {
+                Ok(true) => info!("Deleted workspace storage: {}", workspace_id),
+                Ok(false) => {}
+                Err(e) => warn!("Failed to delete workspace {}: {}", workspace_id, e),
             }
         }
     }
 
+    audit::record(
+        &state.repo,
+        AuditAction::AccountDeleted,
+        Some(user_id),
+        None,
+        json!({ "email": auth.user.email }),
+    );
     info!("Successfully deleted account for user: {}", user_id);
 
     StatusCode::NO_CONTENT.into_response()
 }
+
+fn error_json(status: StatusCode, error: impl Into<String>) -> axum::response::Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.into(),
+        }),
+    )
+        .into_response()
+}
+
+/// Audit a successful login
+fn record_login(state: &AuthState, result: &crate::auth::VerifyResult, method: &str) {
+    audit::record(
+        &state.repo,
+        AuditAction::Login,
+        Some(&result.user_id),
+        Some(&result.device_id),
+        json!({ "method": method }),
+    );
+}
+
+/// Count and audit a rejected login attempt
+fn record_login_failure(state: &AuthState, method: &str) {
+    metrics().record_auth_failure(AuthFailure::Login);
+    audit::record(
+        &state.repo,
+        AuditAction::LoginFailed,
+        None,
+        None,
+        json!({ "method": method }),
+    );
+}
+
+fn login_response(result: crate::auth::VerifyResult) -> axum::response::Response {
+    (
+        StatusCode::OK,
+        Json(VerifyResponse {
+            success: true,
+            token: result.session_token,
+            user: UserResponse {
+                id: result.user_id,
+                email: result.email,
+            },
+        }),
+    )
+        .into_response()
+}
+
+/// POST /auth/device/code - Start a device-code login (no auth required)
+async fn request_device_code(
+    State(state): State<AuthState>,
+    body: Option<Json<DeviceCodeRequest>>,
+) -> impl IntoResponse {
+    let body = body.map(|Json(b)| b).unwrap_or_default();
+
+    match state.device_code_service.start(body.device_name.as_deref()) {
+        Ok(auth) => Json(DeviceCodeResponse {
+            device_code: auth.device_code,
+            user_code: auth.user_code,
+            expires_in: auth.expires_in,
+            interval: auth.interval,
+        })
+        .into_response(),
+        Err(e) => {
+            error!("Failed to create device code: {}", e);
+            error_json(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create device code",
+            )
+        }
+    }
+}
+
+/// POST /auth/device/token - Poll a device-code login
+///
+/// Errors use OAuth device-flow codes (`authorization_pending`, `access_denied`,
+/// `expired_token`) in the `error` field.
+async fn poll_device_token(
+    State(state): State<AuthState>,
+    Json(body): Json<DeviceTokenRequest>,
+) -> impl IntoResponse {
+    match state.device_code_service.poll(&body.device_code, None) {
+        Ok(result) => {
+            info!("User {} logged in with a device code", result.email);
+            record_login(&state, &result, "device_code");
+            login_response(result)
+        }
+        Err(DeviceCodeError::DatabaseError(e)) => {
+            error!("Failed to poll device code: {}", e);
+            error_json(StatusCode::INTERNAL_SERVER_ERROR, "server_error")
+        }
+        // Pending polls are the normal flow, not failures
+        Err(e @ DeviceCodeError::AuthorizationPending) => {
+            error_json(StatusCode::BAD_REQUEST, e.code())
+        }
+        Err(e) => {
+            record_login_failure(&state, "device_code");
+            error_json(StatusCode::BAD_REQUEST, e.code())
+        }
+    }
+}
+
+/// GET /auth/device?user_code=... - Show a pending device-code login before approving
+async fn lookup_device_code(
+    State(state): State<AuthState>,
+    RequireAuth(_auth): RequireAuth,
+    Query(query): Query<DeviceLookupQuery>,
+) -> impl IntoResponse {
+    match state.device_code_service.lookup(&query.user_code) {
+        Ok(Some(info)) => Json(DeviceLookupResponse {
+            user_code: info.user_code,
+            device_name: info.device_name,
+            expires_at: info.expires_at.to_rfc3339(),
+        })
+        .into_response(),
+        Ok(None) => error_json(StatusCode::NOT_FOUND, "Invalid or expired code"),
+        Err(e) => {
+            error!("Failed to look up device code: {}", e);
+            error_json(StatusCode::INTERNAL_SERVER_ERROR, "Failed to look up code")
+        }
+    }
+}
+
+/// POST /auth/device/approve - Approve or deny a device-code login
+async fn approve_device_code(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+    Json(body): Json<DeviceApproveRequest>,
+) -> impl IntoResponse {
+    match state
+        .device_code_service
+        .resolve(&body.user_code, &auth.user.id, body.approve)
+    {
+        Ok(()) => {
+            info!(
+                "User {} {} device code {}",
+                auth.user.id,
+                if body.approve { "approved" } else { "denied" },
+                body.user_code
+            );
+            audit::record(
+                &state.repo,
+                if body.approve {
+                    AuditAction::DeviceCodeApproved
+                } else {
+                    AuditAction::DeviceCodeDenied
+                },
+                Some(&auth.user.id),
+                Some(&body.user_code),
+                json!({}),
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(DeviceCodeError::DatabaseError(e)) => {
+            error!("Failed to resolve device code: {}", e);
+            error_json(StatusCode::INTERNAL_SERVER_ERROR, "Failed to approve code")
+        }
+        Err(_) => error_json(StatusCode::NOT_FOUND, "Invalid or expired code"),
+    }
+}
+
+fn passkey_error_response(e: PasskeyError) -> axum::response::Response {
+    match e {
+        PasskeyError::InvalidChallenge | PasskeyError::NoPasskeys => {
+            error_json(StatusCode::BAD_REQUEST, e.to_string())
+        }
+        PasskeyError::Verification(_) => {
+            warn!("{}", e);
+            error_json(StatusCode::UNAUTHORIZED, "Passkey verification failed")
+        }
+        PasskeyError::Config(_) | PasskeyError::DatabaseError(_) => {
+            error!("Passkey error: {}", e);
+            error_json(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Passkey operation failed",
+            )
+        }
+    }
+}
+
+/// GET /auth/passkeys - List the user's passkeys
+async fn list_passkeys(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+) -> impl IntoResponse {
+    let passkeys = state
+        .repo
+        .get_user_passkeys(&auth.user.id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(PasskeyResponse::from)
+        .collect::<Vec<_>>();
+
+    Json(passkeys)
+}
+
+/// DELETE /auth/passkeys/{passkey_id} - Remove a passkey
+async fn delete_passkey(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+    axum::extract::Path(passkey_id): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    match state.repo.delete_passkey(&auth.user.id, &passkey_id) {
+        Ok(true) => {
+            audit::record(
+                &state.repo,
+                AuditAction::PasskeyDeleted,
+                Some(&auth.user.id),
+                Some(&passkey_id),
+                json!({}),
+            );
+            StatusCode::NO_CONTENT
+        }
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to delete passkey: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// POST /auth/passkeys/register/start - Begin registering a passkey
+async fn start_passkey_registration(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+) -> impl IntoResponse {
+    match state.passkey_service.start_registration(&auth.user) {
+        Ok((challenge_id, options)) => Json(PasskeyChallengeResponse {
+            challenge_id,
+            options,
+        })
+        .into_response(),
+        Err(e) => passkey_error_response(e),
+    }
+}
+
+/// POST /auth/passkeys/register/finish - Verify and store a new passkey
+async fn finish_passkey_registration(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+    Json(body): Json<PasskeyRegisterFinishRequest>,
+) -> impl IntoResponse {
+    match state.passkey_service.finish_registration(
+        &body.challenge_id,
+        &auth.user.id,
+        body.name.as_deref(),
+        &body.credential,
+    ) {
+        Ok(passkey) => {
+            info!("User {} registered a passkey", auth.user.id);
+            audit::record(
+                &state.repo,
+                AuditAction::PasskeyRegistered,
+                Some(&auth.user.id),
+                Some(&passkey.id),
+                json!({ "name": passkey.name }),
+            );
+            Json(PasskeyResponse::from(passkey)).into_response()
+        }
+        Err(e) => passkey_error_response(e),
+    }
+}
+
+/// POST /auth/passkeys/login/start - Begin a passkey login (no auth required)
+async fn start_passkey_login(
+    State(state): State<AuthState>,
+    Json(body): Json<PasskeyLoginStartRequest>,
+) -> impl IntoResponse {
+    match state.passkey_service.start_login(&body.email) {
+        Ok((challenge_id, options)) => Json(PasskeyChallengeResponse {
+            challenge_id,
+            options,
+        })
+        .into_response(),
+        Err(e) => passkey_error_response(e),
+    }
+}
+
+/// POST /auth/passkeys/login/finish - Verify a passkey assertion and return a session token
+async fn finish_passkey_login(
+    State(state): State<AuthState>,
+    Json(body): Json<PasskeyLoginFinishRequest>,
+) -> impl IntoResponse {
+    match state.passkey_service.finish_login(
+        &body.challenge_id,
+        &body.credential,
+        body.device_name.as_deref(),
+        None,
+    ) {
+        Ok(result) => {
+            info!("User {} logged in with a passkey", result.email);
+            record_login(&state, &result, "passkey");
+            login_response(result)
+        }
+        Err(e) => {
+            if matches!(e, PasskeyError::Verification(_)) {
+                record_login_failure(&state, "passkey");
+            }
+            passkey_error_response(e)
+        }
+    }
+}
+
+/// GET /auth/tokens - List the user's personal access tokens
+async fn list_api_tokens(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+) -> impl IntoResponse {
+    let tokens = state
+        .repo
+        .get_user_api_tokens(&auth.user.id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(ApiTokenResponse::from)
+        .collect::<Vec<_>>();
+
+    Json(tokens)
+}
+
+/// POST /auth/tokens - Create a personal access token
+async fn create_api_token(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+    Json(body): Json<CreateApiTokenRequest>,
+) -> impl IntoResponse {
+    let name = body.name.trim();
+    if name.is_empty() {
+        return error_json(StatusCode::BAD_REQUEST, "Token name is required");
+    }
+
+    // A token can't grant more than the user's own role allows
+    if let Some(workspace_id) = &body.workspace_id {
+        match state.repo.get_workspace_role(workspace_id, &auth.user.id) {
+            Ok(Some(role)) if role.can_write() || body.permission == ApiTokenPermission::Read => {}
+            Ok(Some(_)) => {
+                return error_json(
+                    StatusCode::FORBIDDEN,
+                    "Viewers can only create read tokens for this workspace",
+                );
+            }
+            Ok(None) => return error_json(StatusCode::NOT_FOUND, "Workspace not found"),
+            Err(e) => {
+                error!("Failed to get workspace role: {}", e);
+                return error_json(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create token");
+            }
+        }
+    }
+
+    let expires_at = match body.expires_in_days {
+        Some(days) if days <= 0 => {
+            return error_json(StatusCode::BAD_REQUEST, "expires_in_days must be positive");
+        }
+        Some(days) => Some(chrono::Utc::now() + chrono::Duration::days(days)),
+        None => None,
+    };
+
+    let created = state.repo.create_api_token(
+        &auth.user.id,
+        name,
+        body.workspace_id.as_deref(),
+        body.permission,
+        expires_at,
+    );
+    let (id, token) = match created {
+        Ok(created) => created,
+        Err(e) => {
+            error!("Failed to create API token: {}", e);
+            return error_json(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create token");
+        }
+    };
+
+    let info = state
+        .repo
+        .get_user_api_tokens(&auth.user.id)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|t| t.id == id);
+
+    match info {
+        Some(info) => {
+            info!("User {} created API token {}", auth.user.id, id);
+            audit::record(
+                &state.repo,
+                AuditAction::ApiTokenCreated,
+                Some(&auth.user.id),
+                Some(&id),
+                json!({
+                    "name": info.name,
+                    "permission": info.permission.as_str(),
+                    "workspace_id": info.workspace_id,
+                }),
+            );
+            Json(CreateApiTokenResponse {
+                token,
+                info: info.into(),
+            })
+            .into_response()
+        }
+        None => error_json(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create token"),
+    }
+}
+
+/// DELETE /auth/tokens/{token_id} - Revoke a personal access token
+async fn revoke_api_token(
+    State(state): State<AuthState>,
+    RequireAuth(auth): RequireAuth,
+    axum::extract::Path(token_id): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    match state.repo.delete_api_token(&auth.user.id, &token_id) {
+        Ok(true) => {
+            audit::record(
+                &state.repo,
+                AuditAction::ApiTokenRevoked,
+                Some(&auth.user.id),
+                Some(&token_id),
+                json!({}),
+            );
+            StatusCode::NO_CONTENT
+        }
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to revoke API token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
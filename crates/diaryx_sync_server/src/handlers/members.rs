@@ -0,0 +1,341 @@
+use crate::auth::RequireAuth;
+use crate::db::{AuthRepo, WorkspaceRole};
+use crate::email::EmailService;
+use crate::sync_v2::SyncV2State;
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    routing::{delete, get, post},
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+/// How long a workspace invitation stays valid
+const INVITE_EXPIRY_DAYS: i64 = 7;
+
+/// Shared state for membership handlers
+#[derive(Clone)]
+pub struct MembersState {
+    pub repo: Arc<AuthRepo>,
+    pub email_service: Arc<EmailService>,
+    /// Base URL of the web app, used to build invitation links
+    pub app_base_url: String,
+    /// Sync server, to disconnect removed members
+    pub sync_v2: Arc<SyncV2State>,
+}
+
+/// Request to invite someone to a workspace
+#[derive(Debug, Deserialize)]
+pub struct InviteRequest {
+    pub email: String,
+    /// `editor` (default) or `viewer`
+    #[serde(default = "default_invite_role")]
+    pub role: WorkspaceRole,
+}
+
+fn default_invite_role() -> WorkspaceRole {
+    WorkspaceRole::Editor
+}
+
+/// Response for an invitation
+#[derive(Debug, Serialize)]
+pub struct InviteResponse {
+    pub success: bool,
+    pub email: String,
+    pub role: WorkspaceRole,
+    /// Only included in dev mode when email is not configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev_link: Option<String>,
+}
+
+/// Workspace member in responses
+#[derive(Debug, Serialize)]
+pub struct MemberResponse {
+    pub user_id: String,
+    pub email: String,
+    pub role: WorkspaceRole,
+}
+
+/// Pending invitation in responses
+#[derive(Debug, Serialize)]
+pub struct PendingInviteResponse {
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub expires_at: String,
+}
+
+/// Response for listing members
+#[derive(Debug, Serialize)]
+pub struct MembersResponse {
+    pub members: Vec<MemberResponse>,
+    /// Only included for the owner
+    pub invites: Vec<PendingInviteResponse>,
+}
+
+/// Response for accepting an invitation
+#[derive(Debug, Serialize)]
+pub struct AcceptInviteResponse {
+    pub workspace_id: String,
+    pub role: WorkspaceRole,
+}
+
+/// Create membership routes (nested under `/api`)
+pub fn member_routes(state: MembersState) -> Router {
+    Router::new()
+        .route(
+            "/workspaces/{workspace_id}/members",
+            get(list_members).post(invite_member),
+        )
+        .route(
+            "/workspaces/{workspace_id}/members/{member}",
+            delete(remove_member),
+        )
+        .route("/invites/{token}/accept", post(accept_invite))
+        .with_state(state)
+}
+
+fn error_response(status: StatusCode, message: &str) -> axum::response::Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// GET /api/workspaces/{workspace_id}/members - List members (any member)
+async fn list_members(
+    State(state): State<MembersState>,
+    RequireAuth(auth): RequireAuth,
+    Path(workspace_id): Path<String>,
+) -> impl IntoResponse {
+    let role = match state.repo.get_workspace_role(&workspace_id, &auth.user.id) {
+        Ok(Some(role)) => role,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Workspace not found"),
+        Err(e) => {
+            error!("Failed to get workspace role: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list members");
+        }
+    };
+
+    let members = state
+        .repo
+        .get_workspace_members(&workspace_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|m| MemberResponse {
+            user_id: m.user_id,
+            email: m.email,
+            role: m.role,
+        })
+        .collect();
+
+    let invites = if role == WorkspaceRole::Owner {
+        state
+            .repo
+            .get_workspace_invites(&workspace_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|i| PendingInviteResponse {
+                email: i.email,
+                role: i.role,
+                expires_at: i.expires_at.to_rfc3339(),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    Json(MembersResponse { members, invites }).into_response()
+}
+
+/// POST /api/workspaces/{workspace_id}/members - Invite by email (owner only)
+async fn invite_member(
+    State(state): State<MembersState>,
+    RequireAuth(auth): RequireAuth,
+    Path(workspace_id): Path<String>,
+    Json(req): Json<InviteRequest>,
+) -> impl IntoResponse {
+    let email = req.email.trim().to_lowercase();
+    if !email.contains('@') || email.len() < 5 {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid email address");
+    }
+    if req.role == WorkspaceRole::Owner {
+        return error_response(StatusCode::BAD_REQUEST, "Role must be editor or viewer");
+    }
+
+    let workspace = match state.repo.get_workspace(&workspace_id) {
+        Ok(Some(w)) if w.user_id == auth.user.id => w,
+        Ok(_) => {
+            return error_response(
+                StatusCode::FORBIDDEN,
+                "Only the workspace owner can invite members",
+            );
+        }
+        Err(e) => {
+            error!("Failed to get workspace: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to invite member");
+        }
+    };
+
+    if email == auth.user.email.to_lowercase() {
+        return error_response(StatusCode::BAD_REQUEST, "You already own this workspace");
+    }
+
+    let expires_at = Utc::now() + Duration::days(INVITE_EXPIRY_DAYS);
+    let token = match state.repo.create_workspace_invite(
+        &workspace_id,
+        &email,
+        req.role,
+        &auth.user.id,
+        expires_at,
+    ) {
+        Ok(token) => token,
+        Err(e) => {
+            error!("Failed to create workspace invite: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to invite member");
+        }
+    };
+
+    let invite_url = format!("{}/invite?token={}", state.app_base_url, token);
+
+    if !state.email_service.is_configured() {
+        // Dev mode: return the link directly
+        warn!(
+            "Email not configured, returning invite link directly (dev mode only!): {}",
+            invite_url
+        );
+        return Json(InviteResponse {
+            success: true,
+            email,
+            role: req.role,
+            dev_link: Some(invite_url),
+        })
+        .into_response();
+    }
+
+    if let Err(e) = state
+        .email_service
+        .send_workspace_invite(&email, &auth.user.email, &workspace.name, &invite_url)
+        .await
+    {
+        error!("Failed to send invite email: {}", e);
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send email");
+    }
+
+    info!("Invited {} to workspace {}", email, workspace_id);
+    Json(InviteResponse {
+        success: true,
+        email,
+        role: req.role,
+        dev_link: None,
+    })
+    .into_response()
+}
+
+/// DELETE /api/workspaces/{workspace_id}/members/{member} - Remove a member
+///
+/// `member` is a user ID or an email address (which also revokes pending
+/// invitations). Owners can remove anyone else; members can remove themselves.
+/// The removed member's open sync connections to the workspace are closed.
+async fn remove_member(
+    State(state): State<MembersState>,
+    RequireAuth(auth): RequireAuth,
+    Path((workspace_id, member)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let role = match state.repo.get_workspace_role(&workspace_id, &auth.user.id) {
+        Ok(Some(role)) => role,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Workspace not found"),
+        Err(e) => {
+            error!("Failed to get workspace role: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to remove member");
+        }
+    };
+
+    let (user_id, revoked) = if member.contains('@') {
+        let email = member.trim().to_lowercase();
+        let revoked = if role == WorkspaceRole::Owner {
+            state
+                .repo
+                .revoke_workspace_invites(&workspace_id, &email)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let user_id = state
+            .repo
+            .get_user_by_email(&email)
+            .ok()
+            .flatten()
+            .map(|u| u.id);
+        (user_id, revoked)
+    } else {
+        (Some(member), 0)
+    };
+
+    let removing_self = user_id.as_deref() == Some(auth.user.id.as_str());
+    if role == WorkspaceRole::Owner && removing_self {
+        return error_response(StatusCode::BAD_REQUEST, "The owner cannot be removed");
+    }
+    if role != WorkspaceRole::Owner && !removing_self {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Only the workspace owner can remove other members",
+        );
+    }
+
+    let removed = match user_id {
+        Some(user_id) => match state.repo.remove_workspace_member(&workspace_id, &user_id) {
+            Ok(removed) => {
+                if removed {
+                    state.sync_v2.disconnect_member(&workspace_id, &user_id);
+                }
+                removed
+            }
+            Err(e) => {
+                error!("Failed to remove workspace member: {}", e);
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to remove member",
+                );
+            }
+        },
+        None => false,
+    };
+
+    if removed || revoked > 0 {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        error_response(StatusCode::NOT_FOUND, "Member not found")
+    }
+}
+
+/// POST /api/invites/{token}/accept - Accept an invitation
+async fn accept_invite(
+    State(state): State<MembersState>,
+    RequireAuth(auth): RequireAuth,
+    Path(token): Path<String>,
+) -> impl IntoResponse {
+    match state.repo.accept_workspace_invite(&token, &auth.user.id) {
+        Ok(Some(invite)) => {
+            info!(
+                "User {} joined workspace {} as {}",
+                auth.user.id,
+                invite.workspace_id,
+                invite.role.as_str()
+            );
+            Json(AcceptInviteResponse {
+                workspace_id: invite.workspace_id,
+                role: invite.role,
+            })
+            .into_response()
+        }
+        Ok(None) => error_response(
+            StatusCode::NOT_FOUND,
+            "Invitation not found, expired, or sent to a different email",
+        ),
+        Err(e) => {
+            error!("Failed to accept invite: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to accept invite")
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::config::Config;
+use crate::metrics::metrics;
+use crate::sync_v2::SyncV2State;
+use axum::{
+    Router,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+    routing::get,
+};
+use std::sync::Arc;
+
+/// Shared state for the metrics endpoint
+#[derive(Clone)]
+pub struct MetricsState {
+    pub config: Arc<Config>,
+    pub sync_v2: Arc<SyncV2State>,
+}
+
+/// Create the `/metrics` route
+pub fn metrics_routes(state: MetricsState) -> Router {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(state)
+}
+
+/// GET /metrics - Prometheus metrics
+///
+/// Requires `Authorization: Bearer <METRICS_TOKEN>`; without `METRICS_TOKEN`
+/// the endpoint is disabled.
+async fn get_metrics(State(state): State<MetricsState>, headers: HeaderMap) -> impl IntoResponse {
+    let Some(expected) = &state.config.metrics_token else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !presented.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(&state.sync_v2.connection_stats()),
+    )
+        .into_response()
+}
+
+/// Compare secrets without exiting at the first differing byte, so response
+/// times don't reveal how much of a guessed token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
@@ -1,7 +1,15 @@
+pub mod admin;
 pub mod api;
 pub mod auth;
+pub mod members;
+pub mod metrics;
 pub mod sessions;
+pub mod webhooks;
 
+pub use admin::admin_routes;
 pub use api::api_routes;
 pub use auth::auth_routes;
+pub use members::member_routes;
+pub use metrics::metrics_routes;
 pub use sessions::session_routes;
+pub use webhooks::webhook_routes;
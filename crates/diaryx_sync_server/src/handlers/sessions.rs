@@ -1,3 +1,4 @@
+use crate::audit::{self, AuditAction};
 use crate::auth::RequireAuth;
 use crate::db::AuthRepo;
 use crate::sync_v2::SyncV2State;
@@ -8,6 +9,7 @@ use axum::{
     response::{IntoResponse, Json},
     routing::{delete, get, patch, post},
 };
+use diaryx_core::crdt::ShareScope;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -24,12 +26,21 @@ pub struct CreateSessionRequest {
     pub workspace_id: String,
     #[serde(default)]
     pub read_only: bool,
+    /// Limit the session to these paths (omit to share the whole workspace)
+    #[serde(default)]
+    pub scope: Option<ShareScope>,
 }
 
 /// Request to update a share session
+///
+/// Omitted fields are left unchanged. An empty `scope` shares the whole
+/// workspace again.
 #[derive(Debug, Deserialize)]
 pub struct UpdateSessionRequest {
-    pub read_only: bool,
+    #[serde(default)]
+    pub read_only: Option<bool>,
+    #[serde(default)]
+    pub scope: Option<ShareScope>,
 }
 
 /// Response for session creation
@@ -38,6 +49,7 @@ pub struct SessionResponse {
     pub code: String,
     pub workspace_id: String,
     pub read_only: bool,
+    pub scope: Option<ShareScope>,
 }
 
 /// Response for session info (includes peer count)
@@ -46,6 +58,7 @@ pub struct SessionInfoResponse {
     pub code: String,
     pub workspace_id: String,
     pub read_only: bool,
+    pub scope: Option<ShareScope>,
     pub peer_count: usize,
 }
 
@@ -108,6 +121,31 @@ async fn create_session(
         None, // No expiry for now
     ) {
         Ok(code) => {
+            let scope = req.scope.filter(|s| !s.is_empty());
+            if let Err(e) = state.repo.set_share_session_scope(&code, scope.as_ref()) {
+                tracing::error!("Failed to store share session scope: {}", e);
+                let _ = state.repo.delete_share_session(&code);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": "Failed to create session"
+                    })),
+                )
+                    .into_response();
+            }
+
+            audit::record(
+                &state.repo,
+                AuditAction::ShareSessionCreated,
+                Some(&auth.user.id),
+                Some(&code),
+                serde_json::json!({
+                    "workspace_id": workspace_id,
+                    "read_only": req.read_only,
+                    "scoped": scope.is_some(),
+                }),
+            );
+
             // Eagerly register session-to-workspace mapping
             state.sync_v2.register_session(&code, &workspace_id).await;
 
@@ -115,6 +153,7 @@ async fn create_session(
                 code,
                 workspace_id,
                 read_only: req.read_only,
+                scope,
             })
             .into_response()
         }
@@ -152,6 +191,7 @@ async fn get_session(
                 code: session.code,
                 workspace_id: session.workspace_id,
                 read_only: session.read_only,
+                scope: session.scope,
                 peer_count,
             })
             .into_response()
@@ -200,41 +240,71 @@ async fn update_session(
             }
 
             // Update read-only status in database
-            match state
-                .repo
-                .update_share_session_read_only(&code, req.read_only)
+            let read_only = req.read_only.unwrap_or(session.read_only);
+            if let Some(read_only) = req.read_only
+                && let Err(e) = state.repo.update_share_session_read_only(&code, read_only)
             {
-                Ok(true) => {
-                    // Broadcast read-only change to all connected clients
-                    state
-                        .sync_v2
-                        .broadcast_read_only_changed(&code, req.read_only)
-                        .await;
-
-                    Json(serde_json::json!({
-                        "code": code,
-                        "read_only": req.read_only,
-                    }))
-                    .into_response()
-                }
-                Ok(false) => (
-                    StatusCode::NOT_FOUND,
+                tracing::error!("Failed to update share session: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
                     Json(serde_json::json!({
-                        "error": "Session not found"
+                        "error": "Failed to update session"
                     })),
                 )
-                    .into_response(),
-                Err(e) => {
-                    tracing::error!("Failed to update share session: {}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(serde_json::json!({
-                            "error": "Failed to update session"
-                        })),
-                    )
-                        .into_response()
+                    .into_response();
+            }
+
+            // Update scope in database
+            let scope_changed = req.scope.is_some();
+            let scope = match req.scope {
+                Some(scope) => {
+                    let scope = (!scope.is_empty()).then_some(scope);
+                    if let Err(e) = state.repo.set_share_session_scope(&code, scope.as_ref()) {
+                        tracing::error!("Failed to update share session scope: {}", e);
+                        return (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            Json(serde_json::json!({
+                                "error": "Failed to update session"
+                            })),
+                        )
+                            .into_response();
+                    }
+                    // Broadcast scope change to all connected clients
+                    state
+                        .sync_v2
+                        .broadcast_scope_changed(&code, scope.as_ref())
+                        .await;
+                    scope
                 }
+                None => session.scope,
+            };
+
+            audit::record(
+                &state.repo,
+                AuditAction::ShareSessionUpdated,
+                Some(&auth.user.id),
+                Some(&code),
+                serde_json::json!({
+                    "read_only": read_only,
+                    "scope_changed": scope_changed,
+                    "scoped": scope.is_some(),
+                }),
+            );
+
+            if req.read_only.is_some() {
+                // Broadcast read-only change to all connected clients
+                state
+                    .sync_v2
+                    .broadcast_read_only_changed(&code, read_only)
+                    .await;
             }
+
+            Json(serde_json::json!({
+                "code": code,
+                "read_only": read_only,
+                "scope": scope,
+            }))
+            .into_response()
         }
         Ok(None) => (
             StatusCode::NOT_FOUND,
@@ -281,6 +351,14 @@ async fn delete_session(
             // Delete the session
             match state.repo.delete_share_session(&code) {
                 Ok(true) => {
+                    audit::record(
+                        &state.repo,
+                        AuditAction::ShareSessionDeleted,
+                        Some(&auth.user.id),
+                        Some(&code),
+                        serde_json::json!({ "workspace_id": session.workspace_id }),
+                    );
+
                     // Notify connected clients that session ended
                     state.sync_v2.end_session(&code).await;
 
@@ -0,0 +1,287 @@
+use crate::audit::{self, AuditAction};
+use crate::auth::{AuthUser, RequireAuth};
+use crate::db::{AuthRepo, WebhookDeliveryInfo, WebhookInfo, WorkspaceRole};
+use crate::webhooks::WebhookDispatcher;
+use axum::{
+    Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Deliveries returned by the delivery log endpoint unless `limit` is given
+const DEFAULT_DELIVERY_LIMIT: usize = 20;
+
+/// Shared state for webhook handlers
+#[derive(Clone)]
+pub struct WebhooksState {
+    pub repo: Arc<AuthRepo>,
+    pub dispatcher: Arc<WebhookDispatcher>,
+}
+
+/// Request to register a webhook
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    /// `http://` or `https://` URL deliveries are POSTed to
+    pub url: String,
+    pub description: Option<String>,
+}
+
+/// Webhook in responses (the secret is only returned on creation)
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub description: Option<String>,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl WebhookResponse {
+    fn new(webhook: WebhookInfo, with_secret: bool) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            description: webhook.description,
+            created_at: webhook.created_at.to_rfc3339(),
+            secret: with_secret.then_some(webhook.secret),
+        }
+    }
+}
+
+/// Query parameters for the delivery log
+#[derive(Debug, Deserialize)]
+pub struct DeliveriesQuery {
+    pub limit: Option<usize>,
+}
+
+/// Create webhook routes (nested under `/api`)
+pub fn webhook_routes(state: WebhooksState) -> Router {
+    Router::new()
+        .route(
+            "/workspaces/{workspace_id}/webhooks",
+            get(list_webhooks).post(create_webhook),
+        )
+        .route(
+            "/workspaces/{workspace_id}/webhooks/{webhook_id}",
+            delete(delete_webhook),
+        )
+        .route(
+            "/workspaces/{workspace_id}/webhooks/{webhook_id}/deliveries",
+            get(list_deliveries),
+        )
+        .route(
+            "/workspaces/{workspace_id}/webhooks/{webhook_id}/ping",
+            post(ping_webhook),
+        )
+        .with_state(state)
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Check that the user owns the workspace
+fn require_owner(
+    repo: &AuthRepo,
+    auth: &AuthUser,
+    workspace_id: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    match repo.get_workspace_role(workspace_id, &auth.user.id) {
+        Ok(Some(WorkspaceRole::Owner)) => Ok(()),
+        Ok(Some(_)) => Err((
+            StatusCode::FORBIDDEN,
+            "Only the workspace owner can manage webhooks",
+        )),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Workspace not found")),
+        Err(e) => {
+            error!("Failed to get workspace role: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to check access"))
+        }
+    }
+}
+
+/// Look up a webhook of the workspace
+fn find_webhook(
+    repo: &AuthRepo,
+    workspace_id: &str,
+    webhook_id: &str,
+) -> Result<WebhookInfo, (StatusCode, &'static str)> {
+    match repo.get_webhook(webhook_id) {
+        Ok(Some(webhook)) if webhook.workspace_id == workspace_id => Ok(webhook),
+        Ok(_) => Err((StatusCode::NOT_FOUND, "Webhook not found")),
+        Err(e) => {
+            error!("Failed to get webhook: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to get webhook"))
+        }
+    }
+}
+
+/// GET /api/workspaces/{workspace_id}/webhooks - List webhooks (owner only)
+async fn list_webhooks(
+    State(state): State<WebhooksState>,
+    RequireAuth(auth): RequireAuth,
+    Path(workspace_id): Path<String>,
+) -> Response {
+    if let Err((status, message)) = require_owner(&state.repo, &auth, &workspace_id) {
+        return error_response(status, message);
+    }
+
+    match state.repo.get_workspace_webhooks(&workspace_id) {
+        Ok(webhooks) => Json(
+            webhooks
+                .into_iter()
+                .map(|w| WebhookResponse::new(w, false))
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => {
+            error!("Failed to list webhooks: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /api/workspaces/{workspace_id}/webhooks - Register a webhook (owner only)
+///
+/// The response includes the signing secret, which is not shown again.
+async fn create_webhook(
+    State(state): State<WebhooksState>,
+    RequireAuth(auth): RequireAuth,
+    Path(workspace_id): Path<String>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Response {
+    if let Err((status, message)) = require_owner(&state.repo, &auth, &workspace_id) {
+        return error_response(status, message);
+    }
+
+    let url = req.url.trim();
+    if let Err(message) = state.dispatcher.check_url(url).await {
+        return error_response(StatusCode::BAD_REQUEST, &message);
+    }
+    let description = req
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty());
+
+    let webhook =
+        match state
+            .repo
+            .create_webhook(&workspace_id, url, description, Some(&auth.user.id))
+        {
+            Ok(webhook) => webhook,
+            Err(e) => {
+                error!("Failed to create webhook: {}", e);
+                return error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to create webhook",
+                );
+            }
+        };
+    state.dispatcher.invalidate(&workspace_id);
+
+    info!(
+        "Registered webhook {} on workspace {}",
+        webhook.id, workspace_id
+    );
+    audit::record(
+        &state.repo,
+        AuditAction::WebhookCreated,
+        Some(&auth.user.id),
+        Some(&workspace_id),
+        serde_json::json!({ "webhook_id": webhook.id, "url": webhook.url }),
+    );
+
+    (
+        StatusCode::CREATED,
+        Json(WebhookResponse::new(webhook, true)),
+    )
+        .into_response()
+}
+
+/// DELETE /api/workspaces/{workspace_id}/webhooks/{webhook_id} - Delete a webhook (owner only)
+async fn delete_webhook(
+    State(state): State<WebhooksState>,
+    RequireAuth(auth): RequireAuth,
+    Path((workspace_id, webhook_id)): Path<(String, String)>,
+) -> Response {
+    if let Err((status, message)) = require_owner(&state.repo, &auth, &workspace_id) {
+        return error_response(status, message);
+    }
+
+    match state.repo.delete_webhook(&workspace_id, &webhook_id) {
+        Ok(true) => {
+            state.dispatcher.invalidate(&workspace_id);
+            audit::record(
+                &state.repo,
+                AuditAction::WebhookDeleted,
+                Some(&auth.user.id),
+                Some(&workspace_id),
+                serde_json::json!({ "webhook_id": webhook_id }),
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "Webhook not found"),
+        Err(e) => {
+            error!("Failed to delete webhook: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /api/workspaces/{workspace_id}/webhooks/{webhook_id}/deliveries - Recent deliveries, newest first
+async fn list_deliveries(
+    State(state): State<WebhooksState>,
+    RequireAuth(auth): RequireAuth,
+    Path((workspace_id, webhook_id)): Path<(String, String)>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Response {
+    if let Err((status, message)) = require_owner(&state.repo, &auth, &workspace_id) {
+        return error_response(status, message);
+    }
+    if let Err((status, message)) = find_webhook(&state.repo, &workspace_id, &webhook_id) {
+        return error_response(status, message);
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_DELIVERY_LIMIT).clamp(1, 100);
+    match state.repo.get_webhook_deliveries(&webhook_id, limit) {
+        Ok(deliveries) => Json::<Vec<WebhookDeliveryInfo>>(deliveries).into_response(),
+        Err(e) => {
+            error!("Failed to list webhook deliveries: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /api/workspaces/{workspace_id}/webhooks/{webhook_id}/ping - Send a test delivery (owner only)
+async fn ping_webhook(
+    State(state): State<WebhooksState>,
+    RequireAuth(auth): RequireAuth,
+    Path((workspace_id, webhook_id)): Path<(String, String)>,
+) -> Response {
+    if let Err((status, message)) = require_owner(&state.repo, &auth, &workspace_id) {
+        return error_response(status, message);
+    }
+    let webhook = match find_webhook(&state.repo, &workspace_id, &webhook_id) {
+        Ok(webhook) => webhook,
+        Err((status, message)) => return error_response(status, message),
+    };
+
+    match state.dispatcher.ping(webhook) {
+        Ok(delivery_id) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "delivery_id": delivery_id })),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to queue webhook ping: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
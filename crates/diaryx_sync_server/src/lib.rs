@@ -16,6 +16,8 @@
 //! - `HOST`: Server host (default: 0.0.0.0)
 //! - `PORT`: Server port (default: 3030)
 //! - `DATABASE_PATH`: Path to SQLite database (default: ./diaryx_sync.db)
+//! - `DATABASE_URL`: PostgreSQL URL replacing SQLite (requires the `postgres` feature)
+//! - `DATABASE_MAX_CONNECTIONS`: PostgreSQL connection pool size (default: 16)
 //! - `APP_BASE_URL`: Base URL for magic link verification
 //! - `SMTP_HOST`: SMTP server host
 //! - `SMTP_PORT`: SMTP server port
@@ -26,12 +28,30 @@
 //! - `SESSION_EXPIRY_DAYS`: Session token expiration (default: 30)
 //! - `MAGIC_LINK_EXPIRY_MINUTES`: Magic link expiration (default: 15)
 //! - `CORS_ORIGINS`: Comma-separated list of allowed origins
+//! - `ADMIN_EMAILS`: Comma-separated emails allowed to use `/api/admin`
+//! - `METRICS_TOKEN`: Bearer token required to scrape `/metrics` (unset: disabled)
+//! - `SNAPSHOT_RETENTION`: Server-side snapshots kept per workspace (default: 20, 0 keeps all)
+//! - `RATE_LIMIT_AUTH_PER_MINUTE`, `RATE_LIMIT_API_PER_MINUTE`: Per-IP request limits (default: 30, 600)
+//! - `RATE_LIMIT_USER_PER_MINUTE`: Per-user request limit (default: 1200)
+//! - `RATE_LIMIT_SYNC_CONNECT_PER_MINUTE`: Per-IP WebSocket upgrades (default: 60)
+//! - `SYNC_MAX_MESSAGE_BYTES`: Largest accepted sync message (default: 8388608)
+//! - `SYNC_UPDATES_PER_SECOND`, `SYNC_UPDATE_BURST`: Per-connection update rate (default: 100, 2000)
+//! - `WEBHOOK_DEBOUNCE_SECS`, `WEBHOOK_MAX_DELAY_SECS`: Quiet period before a webhook fires, and the longest it waits (default: 5, 60)
+//! - `WEBHOOK_MAX_ATTEMPTS`, `WEBHOOK_RETRY_DELAY_SECS`: Delivery attempts and first retry delay, doubled each retry (default: 5, 10)
+//! - `WEBHOOK_TIMEOUT_SECS`: Webhook request timeout (default: 10)
+//! - `TRUST_PROXY_HEADERS`: Use `X-Forwarded-For` for client IPs (default: false)
+//! - `TRUSTED_PROXY_HOPS`: Reverse proxies appending to `X-Forwarded-For` (default: 1)
 
+pub mod admin;
+pub mod audit;
 pub mod auth;
 pub mod config;
 pub mod db;
 pub mod email;
 pub mod handlers;
+pub mod metrics;
+pub mod rate_limit;
 pub mod sync_v2;
+pub mod webhooks;
 
 pub use config::Config;
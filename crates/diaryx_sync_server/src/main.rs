@@ -2,17 +2,28 @@ use axum::{
     Router,
     extract::Extension,
     http::{Method, header},
+    middleware,
     routing::get,
 };
+use clap::{Parser, Subcommand};
+#[cfg(feature = "postgres")]
+use diaryx_sync_server::db::{PostgresAuthStore, connect_postgres};
 use diaryx_sync_server::{
-    auth::{AuthExtractor, MagicLinkService},
+    admin::{self, AdminCommand, run_admin_command},
+    auth::{AuthExtractor, DeviceCodeService, MagicLinkService, PasskeyService},
     config::Config,
     db::{AuthRepo, init_database},
     email::EmailService,
-    handlers::{api_routes, auth_routes, session_routes},
-    sync_v2::SyncV2Server,
+    handlers::{
+        admin_routes, api_routes, auth_routes, member_routes, metrics_routes, session_routes,
+        webhook_routes,
+    },
+    rate_limit::{LimitScope, RateLimits, rate_limit},
+    sync_v2::{ConnectionListener, StorageCache, SyncV2Server, WorkspaceStore, tag_connection},
+    webhooks::WebhookDispatcher,
 };
 use rusqlite::Connection;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::signal;
 use tower_http::{
@@ -22,13 +33,72 @@ use tower_http::{
 use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Diaryx sync server
+#[derive(Parser)]
+#[command(name = "diaryx_sync_server", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the sync server (default)
+    Serve,
+
+    /// Operator tasks against the server's database and workspace files
+    #[command(subcommand)]
+    Admin(AdminCommand),
+}
+
+/// Open the auth database and workspace storage: PostgreSQL when
+/// `DATABASE_URL` is set, otherwise SQLite at `DATABASE_PATH` with workspace
+/// files in a `workspaces` directory beside it.
+fn open_storage(config: &Config) -> Result<(AuthRepo, StorageCache), String> {
+    if let Some(url) = &config.database_url {
+        #[cfg(feature = "postgres")]
+        {
+            let pool = connect_postgres(url, config.database_max_connections)
+                .map_err(|e| format!("Failed to connect to PostgreSQL: {}", e))?;
+            let repo = AuthRepo::with_store(PostgresAuthStore::new(pool.clone()));
+            return Ok((repo, StorageCache::postgres(pool)));
+        }
+        #[cfg(not(feature = "postgres"))]
+        {
+            let _ = url;
+            return Err("DATABASE_URL requires a build with the `postgres` feature".to_string());
+        }
+    }
+
+    let conn = Connection::open(&config.database_path)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    init_database(&conn).map_err(|e| format!("Failed to initialize database: {}", e))?;
+
+    // Create data directory for workspace databases
+    let data_dir = config
+        .database_path
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new("."));
+    let workspaces_dir = data_dir.join("workspaces");
+    std::fs::create_dir_all(&workspaces_dir)
+        .map_err(|e| format!("Failed to create workspaces directory: {}", e))?;
+
+    Ok((AuthRepo::new(conn), StorageCache::new(workspaces_dir)))
+}
+
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
+    let cli = Cli::parse();
+
+    // Initialize tracing (admin commands print their own output, so keep logs quiet)
+    let default_filter = match cli.command {
+        Some(Command::Admin(_)) => "warn",
+        _ => "diaryx_sync_server=debug,tower_http=debug",
+    };
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "diaryx_sync_server=debug,tower_http=debug".into()),
+                .unwrap_or_else(|_| default_filter.into()),
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
@@ -42,57 +112,106 @@ async fn main() {
         }
     };
 
-    info!("Starting Diaryx Sync Server v{}", env!("CARGO_PKG_VERSION"));
-    info!("Database path: {:?}", config.database_path);
-    info!("CORS origins: {:?}", config.cors_origins);
-
-    // Initialize database
-    let conn = match Connection::open(&config.database_path) {
-        Ok(c) => c,
+    // Open the auth database and workspace storage
+    let (repo, storage_cache) = match open_storage(&config) {
+        Ok((repo, cache)) => (Arc::new(repo), Arc::new(cache)),
         Err(e) => {
-            error!("Failed to open database: {}", e);
+            error!("{}", e);
             std::process::exit(1);
         }
     };
 
-    if let Err(e) = init_database(&conn) {
-        error!("Failed to initialize database: {}", e);
-        std::process::exit(1);
+    if let Some(Command::Admin(command)) = cli.command {
+        let store = WorkspaceStore::new(storage_cache);
+        if let Err(e) = run_admin_command(command, &repo, &store) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    info!("Starting Diaryx Sync Server v{}", env!("CARGO_PKG_VERSION"));
+    if config.database_url.is_some() {
+        info!("Database: PostgreSQL (DATABASE_URL)");
+    } else {
+        info!("Database path: {:?}", config.database_path);
+    }
+    info!("CORS origins: {:?}", config.cors_origins);
+    if config.admin_emails.is_empty() {
+        info!("Admin endpoints disabled (set ADMIN_EMAILS to enable)");
     }
 
     // Create shared state
-    let repo = Arc::new(AuthRepo::new(conn));
     let magic_link_service = Arc::new(MagicLinkService::new(repo.clone(), config.clone()));
+    let device_code_service = Arc::new(DeviceCodeService::new(repo.clone(), config.clone()));
+    let passkey_service = match PasskeyService::new(repo.clone(), config.clone()) {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            error!("Failed to initialize passkeys: {}", e);
+            std::process::exit(1);
+        }
+    };
     let email_service = Arc::new(EmailService::new(config.clone()));
     let auth_extractor = AuthExtractor::new(repo.clone());
 
-    // Create data directory for workspace databases
-    let data_dir = config
-        .database_path
-        .parent()
-        .unwrap_or_else(|| std::path::Path::new("."));
-    let workspaces_dir = data_dir.join("workspaces");
-    if let Err(e) = std::fs::create_dir_all(&workspaces_dir) {
-        error!("Failed to create workspaces directory: {}", e);
-        std::process::exit(1);
-    }
+    // Webhook deliveries for changes persisted by the sync server
+    let webhooks = match WebhookDispatcher::new(repo.clone(), config.webhooks.clone()) {
+        Ok(d) => Arc::new(d),
+        Err(e) => {
+            error!("Failed to initialize webhook delivery: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     // Create sync v2 server (siphonophore-based)
-    let sync_v2_server = SyncV2Server::new(repo.clone(), workspaces_dir.clone());
+    let sync_v2_server = SyncV2Server::new(
+        repo.clone(),
+        storage_cache,
+        &config.rate_limits,
+        webhooks.clone(),
+    );
     let sync_v2_state = Arc::new(sync_v2_server.state());
-    let sync_v2_router = sync_v2_server.into_router_at("/sync2");
+
+    // Per-IP and per-user request limits
+    let rate_limits = Arc::new(RateLimits::new(repo.clone(), &config.rate_limits));
+    let limit = |scope| middleware::from_fn_with_state((rate_limits.clone(), scope), rate_limit);
+
+    // Sync connections are tagged with their socket, to close them when
+    // their access is revoked
+    let sync_v2_router = sync_v2_server
+        .into_router_at("/sync2")
+        .layer(limit(LimitScope::Sync))
+        .layer(middleware::from_fn_with_state(
+            sync_v2_state.connections.clone(),
+            tag_connection,
+        ));
 
     // Create handler states
+    let members_state = diaryx_sync_server::handlers::members::MembersState {
+        repo: repo.clone(),
+        email_service: email_service.clone(),
+        app_base_url: config.app_base_url.clone(),
+        sync_v2: sync_v2_state.clone(),
+    };
+
     let auth_state = diaryx_sync_server::handlers::auth::AuthState {
         magic_link_service,
+        device_code_service,
+        passkey_service,
         email_service,
         repo: repo.clone(),
-        workspaces_dir: Some(workspaces_dir.clone()),
+        store: Some(sync_v2_state.store.clone()),
     };
 
     let api_state = diaryx_sync_server::handlers::api::ApiState {
         repo: repo.clone(),
         sync_v2: sync_v2_state.clone(),
+        snapshot_retention: config.snapshot_retention,
+    };
+
+    let webhooks_state = diaryx_sync_server::handlers::webhooks::WebhooksState {
+        repo: repo.clone(),
+        dispatcher: webhooks,
     };
 
     let sessions_state = diaryx_sync_server::handlers::sessions::SessionsState {
@@ -100,6 +219,17 @@ async fn main() {
         sync_v2: sync_v2_state.clone(),
     };
 
+    let admin_state = diaryx_sync_server::handlers::admin::AdminState {
+        repo: repo.clone(),
+        config: config.clone(),
+        sync_v2: sync_v2_state.clone(),
+    };
+
+    let metrics_state = diaryx_sync_server::handlers::metrics::MetricsState {
+        config: config.clone(),
+        sync_v2: sync_v2_state.clone(),
+    };
+
     // Build CORS layer
     let cors = CorsLayer::new()
         .allow_methods([
@@ -118,11 +248,34 @@ async fn main() {
         .route("/", get(|| async { "Diaryx Sync Server" }))
         .route("/health", get(|| async { "OK" }))
         // Auth routes
-        .nest("/auth", auth_routes(auth_state))
+        .nest(
+            "/auth",
+            auth_routes(auth_state).layer(limit(LimitScope::Auth)),
+        )
         // API routes
-        .nest("/api", api_routes(api_state))
+        .nest("/api", api_routes(api_state).layer(limit(LimitScope::Api)))
+        // Workspace membership routes
+        .nest(
+            "/api",
+            member_routes(members_state).layer(limit(LimitScope::Api)),
+        )
+        // Workspace webhook routes
+        .nest(
+            "/api",
+            webhook_routes(webhooks_state).layer(limit(LimitScope::Api)),
+        )
+        // Operator routes (ADMIN_EMAILS only)
+        .nest(
+            "/api/admin",
+            admin_routes(admin_state).layer(limit(LimitScope::Api)),
+        )
+        // Prometheus metrics
+        .merge(metrics_routes(metrics_state))
         // Session routes (for live share)
-        .nest("/api/sessions", session_routes(sessions_state))
+        .nest(
+            "/api/sessions",
+            session_routes(sessions_state).layer(limit(LimitScope::Api)),
+        )
         // Sync v2 endpoint (siphonophore-based)
         .merge(sync_v2_router)
         // Add layers
@@ -133,7 +286,7 @@ async fn main() {
     // Create listener
     let addr = config.server_addr();
     let listener = match tokio::net::TcpListener::bind(&addr).await {
-        Ok(l) => l,
+        Ok(l) => ConnectionListener::new(l, sync_v2_state.connections.clone()),
         Err(e) => {
             error!("Failed to bind to {}: {}", addr, e);
             std::process::exit(1);
@@ -144,22 +297,31 @@ async fn main() {
 
     // Start cleanup task
     let cleanup_repo = repo.clone();
+    let cleanup_limits = rate_limits.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600));
         loop {
             interval.tick().await;
-            let _ = cleanup_repo.cleanup_expired_magic_tokens();
-            let _ = cleanup_repo.cleanup_expired_sessions();
-            let _ = cleanup_repo.cleanup_expired_share_sessions();
-            info!("Cleaned up expired tokens, sessions, and share sessions");
+            match admin::cleanup(&cleanup_repo) {
+                Ok(report) => info!(
+                    "Cleaned up {} expired tokens, sessions, device codes, API tokens, share sessions, and invites",
+                    report.total()
+                ),
+                Err(e) => error!("Cleanup failed: {}", e),
+            }
+            cleanup_limits.prune();
         }
     });
 
     // Run server with graceful shutdown
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    // Connection info gives the rate limiter each client's IP
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
 
     info!("Server shut down gracefully");
 }
@@ -0,0 +1,358 @@
+//! Prometheus metrics.
+//!
+//! Counters are recorded through the process-wide [`metrics()`] registry from
+//! auth middleware, sync hooks and snapshot handlers. Connection and room
+//! gauges are read from [`SyncStatsSnapshot`] when `/metrics` is scraped.
+//! [`Metrics::render`] produces the Prometheus text exposition format.
+
+use crate::sync_v2::SyncStatsSnapshot;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the handshake latency histogram buckets
+const HANDSHAKE_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The process-wide metrics registry
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Where an authentication failure happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AuthFailure {
+    /// Invalid or expired bearer token on an HTTP request
+    Token,
+    /// Rejected WebSocket sync connection
+    Sync,
+    /// Failed magic link, device code or passkey login
+    Login,
+}
+
+impl AuthFailure {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuthFailure::Token => "token",
+            AuthFailure::Sync => "sync",
+            AuthFailure::Login => "login",
+        }
+    }
+}
+
+/// Snapshot operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SnapshotOp {
+    Export,
+    Import,
+    Restore,
+}
+
+impl SnapshotOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotOp::Export => "export",
+            SnapshotOp::Import => "import",
+            SnapshotOp::Restore => "restore",
+        }
+    }
+}
+
+/// Counters and histograms for the sync server
+pub struct Metrics {
+    /// Bytes and count of persisted CRDT updates per workspace
+    update_bytes: Mutex<BTreeMap<String, (u64, u64)>>,
+    auth_failures: Mutex<BTreeMap<AuthFailure, u64>>,
+    /// (operation, success) -> count
+    snapshots: Mutex<BTreeMap<(SnapshotOp, bool), u64>>,
+    /// Scope (`auth`, `api`, `sync`, `sync_update`, `sync_size`) -> refused count
+    rate_limited: Mutex<BTreeMap<&'static str, u64>>,
+    /// Webhook delivery attempts that failed and succeeded
+    webhook_attempts: [AtomicU64; 2],
+    handshake_buckets: [AtomicU64; HANDSHAKE_BUCKETS.len()],
+    handshake_count: AtomicU64,
+    handshake_sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            update_bytes: Mutex::new(BTreeMap::new()),
+            auth_failures: Mutex::new(BTreeMap::new()),
+            snapshots: Mutex::new(BTreeMap::new()),
+            rate_limited: Mutex::new(BTreeMap::new()),
+            webhook_attempts: Default::default(),
+            handshake_buckets: Default::default(),
+            handshake_count: AtomicU64::new(0),
+            handshake_sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a persisted CRDT update
+    pub fn record_update(&self, workspace_id: &str, bytes: usize) {
+        let mut updates = self.update_bytes.lock().unwrap();
+        let entry = updates.entry(workspace_id.to_string()).or_default();
+        entry.0 += bytes as u64;
+        entry.1 += 1;
+    }
+
+    /// Record a failed authentication attempt
+    pub fn record_auth_failure(&self, kind: AuthFailure) {
+        *self.auth_failures.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    /// Record a snapshot export, import or restore
+    pub fn record_snapshot(&self, op: SnapshotOp, success: bool) {
+        *self
+            .snapshots
+            .lock()
+            .unwrap()
+            .entry((op, success))
+            .or_default() += 1;
+    }
+
+    /// Record a request or sync message refused by a rate or size limit
+    pub fn record_rate_limited(&self, scope: &'static str) {
+        *self.rate_limited.lock().unwrap().entry(scope).or_default() += 1;
+    }
+
+    /// Record a webhook delivery attempt
+    pub fn record_webhook_attempt(&self, success: bool) {
+        self.webhook_attempts[success as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how long a Files-Ready handshake took, from manifest to `files_ready`
+    pub fn observe_handshake(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        for (bucket, bound) in self.handshake_buckets.iter().zip(HANDSHAKE_BUCKETS) {
+            if secs <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.handshake_count.fetch_add(1, Ordering::Relaxed);
+        self.handshake_sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in the Prometheus text format
+    pub fn render(&self, stats: &SyncStatsSnapshot) -> String {
+        let mut out = String::new();
+
+        gauge(
+            &mut out,
+            "diaryx_active_connections",
+            "WebSocket clients connected to at least one document",
+            stats.active_connections as u64,
+        );
+        gauge(
+            &mut out,
+            "diaryx_active_rooms",
+            "Documents with at least one connected peer",
+            stats.active_rooms as u64,
+        );
+
+        let updates = self.update_bytes.lock().unwrap();
+        header(
+            &mut out,
+            "diaryx_update_bytes_total",
+            "counter",
+            "Bytes of CRDT updates persisted, by workspace",
+        );
+        for (workspace, (bytes, _)) in updates.iter() {
+            let _ = writeln!(
+                out,
+                "diaryx_update_bytes_total{{workspace=\"{}\"}} {}",
+                escape_label(workspace),
+                bytes
+            );
+        }
+        header(
+            &mut out,
+            "diaryx_updates_total",
+            "counter",
+            "CRDT updates persisted, by workspace",
+        );
+        for (workspace, (_, count)) in updates.iter() {
+            let _ = writeln!(
+                out,
+                "diaryx_updates_total{{workspace=\"{}\"}} {}",
+                escape_label(workspace),
+                count
+            );
+        }
+        drop(updates);
+
+        header(
+            &mut out,
+            "diaryx_handshake_duration_seconds",
+            "histogram",
+            "Time from file manifest to files_ready during the sync handshake",
+        );
+        for (bucket, bound) in self.handshake_buckets.iter().zip(HANDSHAKE_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "diaryx_handshake_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.handshake_count.load(Ordering::Relaxed);
+        let _ = writeln!(
+            out,
+            "diaryx_handshake_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(
+            out,
+            "diaryx_handshake_duration_seconds_sum {}",
+            self.handshake_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(out, "diaryx_handshake_duration_seconds_count {}", count);
+
+        header(
+            &mut out,
+            "diaryx_auth_failures_total",
+            "counter",
+            "Failed authentication attempts, by kind",
+        );
+        let failures = self.auth_failures.lock().unwrap();
+        for kind in [AuthFailure::Token, AuthFailure::Sync, AuthFailure::Login] {
+            let _ = writeln!(
+                out,
+                "diaryx_auth_failures_total{{kind=\"{}\"}} {}",
+                kind.as_str(),
+                failures.get(&kind).copied().unwrap_or(0)
+            );
+        }
+        drop(failures);
+
+        header(
+            &mut out,
+            "diaryx_snapshots_total",
+            "counter",
+            "Workspace snapshot exports, imports and restores, by result",
+        );
+        let snapshots = self.snapshots.lock().unwrap();
+        for op in [SnapshotOp::Export, SnapshotOp::Import, SnapshotOp::Restore] {
+            for success in [true, false] {
+                let _ = writeln!(
+                    out,
+                    "diaryx_snapshots_total{{op=\"{}\",result=\"{}\"}} {}",
+                    op.as_str(),
+                    if success { "ok" } else { "error" },
+                    snapshots.get(&(op, success)).copied().unwrap_or(0)
+                );
+            }
+        }
+        drop(snapshots);
+
+        header(
+            &mut out,
+            "diaryx_rate_limited_total",
+            "counter",
+            "Requests and sync messages refused by rate or size limits, by scope",
+        );
+        for (scope, count) in self.rate_limited.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "diaryx_rate_limited_total{{scope=\"{}\"}} {}",
+                scope, count
+            );
+        }
+
+        header(
+            &mut out,
+            "diaryx_webhook_attempts_total",
+            "counter",
+            "Webhook delivery attempts, by result",
+        );
+        for success in [true, false] {
+            let _ = writeln!(
+                out,
+                "diaryx_webhook_attempts_total{{result=\"{}\"}} {}",
+                if success { "ok" } else { "error" },
+                self.webhook_attempts[success as usize].load(Ordering::Relaxed)
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, "gauge", help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Escape a label value per the exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_stats() -> SyncStatsSnapshot {
+        SyncStatsSnapshot {
+            active_connections: 2,
+            active_rooms: 1,
+            rooms: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_counters() {
+        let metrics = Metrics::new();
+        metrics.record_update("ws1", 100);
+        metrics.record_update("ws1", 20);
+        metrics.record_auth_failure(AuthFailure::Sync);
+        metrics.record_snapshot(SnapshotOp::Import, false);
+        metrics.record_rate_limited("auth");
+        metrics.record_webhook_attempt(false);
+
+        let text = metrics.render(&empty_stats());
+        assert!(text.contains("diaryx_active_connections 2\n"));
+        assert!(text.contains("diaryx_active_rooms 1\n"));
+        assert!(text.contains("diaryx_update_bytes_total{workspace=\"ws1\"} 120\n"));
+        assert!(text.contains("diaryx_updates_total{workspace=\"ws1\"} 2\n"));
+        assert!(text.contains("diaryx_auth_failures_total{kind=\"sync\"} 1\n"));
+        assert!(text.contains("diaryx_auth_failures_total{kind=\"token\"} 0\n"));
+        assert!(text.contains("diaryx_snapshots_total{op=\"import\",result=\"error\"} 1\n"));
+        assert!(text.contains("diaryx_rate_limited_total{scope=\"auth\"} 1\n"));
+        assert!(text.contains("diaryx_webhook_attempts_total{result=\"error\"} 1\n"));
+        assert!(text.contains("diaryx_webhook_attempts_total{result=\"ok\"} 0\n"));
+        assert!(text.contains("# TYPE diaryx_update_bytes_total counter\n"));
+    }
+
+    #[test]
+    fn test_handshake_histogram_is_cumulative() {
+        let metrics = Metrics::new();
+        metrics.observe_handshake(Duration::from_millis(3));
+        metrics.observe_handshake(Duration::from_millis(200));
+        metrics.observe_handshake(Duration::from_secs(30));
+
+        let text = metrics.render(&empty_stats());
+        assert!(text.contains("diaryx_handshake_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("diaryx_handshake_duration_seconds_bucket{le=\"0.25\"} 2\n"));
+        assert!(text.contains("diaryx_handshake_duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(text.contains("diaryx_handshake_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("diaryx_handshake_duration_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -0,0 +1,420 @@
+//! Token-bucket rate limiting.
+//!
+//! [`RateLimiter`] keeps one bucket per key (client IP, user, or sync
+//! connection). [`rate_limit`] is an axum middleware applied to the `/auth`,
+//! `/api` and `/sync2` routers: it charges the caller's IP bucket for the
+//! route group and, when the request carries a valid token, the user's bucket.
+//! Limited requests get `429 Too Many Requests` with a `Retry-After` header.
+//!
+//! Limits inside the sync protocol (update rate and message size) are enforced
+//! by `DiaryxHook`, which also uses [`RateLimiter`].
+
+use crate::auth::validate_token;
+use crate::config::RateLimitConfig;
+use crate::db::AuthRepo;
+use crate::metrics::metrics;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Buckets kept before idle (full) ones are dropped on the next check
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// How long a token's user ID is cached for per-user limits
+const USER_CACHE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Keyed token buckets.
+///
+/// Each key starts with `capacity` tokens and regains `refill_per_sec` tokens
+/// per second up to `capacity`. A limiter with zero capacity allows everything.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Allow `burst` requests at once, refilling at `refill_per_sec`
+    pub fn new(burst: u32, refill_per_sec: f64) -> Self {
+        Self {
+            capacity: burst as f64,
+            refill_per_sec,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Allow `limit` requests per minute, all of which may arrive at once
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, limit as f64 / 60.0)
+    }
+
+    /// Whether this limiter allows everything
+    pub fn is_disabled(&self) -> bool {
+        self.capacity <= 0.0
+    }
+
+    /// Take a token for `key`, or return how long until one is available
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        if self.is_disabled() {
+            return Ok(());
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            self.prune_locked(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        self.refill(bucket, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.refill_per_sec > 0.0 {
+            let wait = (1.0 - bucket.tokens) / self.refill_per_sec;
+            Err(Duration::from_secs_f64(wait))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+
+    /// Drop buckets that have refilled completely; returns how many were removed
+    pub fn prune(&self) -> usize {
+        let mut buckets = self.buckets.lock().unwrap();
+        self.prune_locked(&mut buckets, Instant::now())
+    }
+
+    fn prune_locked(&self, buckets: &mut HashMap<String, Bucket>, now: Instant) -> usize {
+        let before = buckets.len();
+        buckets.retain(|_, bucket| {
+            self.refill(bucket, now);
+            bucket.tokens < self.capacity
+        });
+        before - buckets.len()
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.updated = now;
+    }
+}
+
+/// Route groups with their own per-IP limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitScope {
+    /// `/auth/*` (login, magic links, device codes, passkeys, tokens)
+    Auth,
+    /// `/api/*`
+    Api,
+    /// WebSocket upgrades on `/sync2`
+    Sync,
+}
+
+impl LimitScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LimitScope::Auth => "auth",
+            LimitScope::Api => "api",
+            LimitScope::Sync => "sync",
+        }
+    }
+}
+
+/// HTTP rate limiters shared by the [`rate_limit`] middleware
+pub struct RateLimits {
+    repo: Arc<AuthRepo>,
+    trust_proxy_headers: bool,
+    trusted_proxy_hops: usize,
+    auth_ip: RateLimiter,
+    api_ip: RateLimiter,
+    sync_ip: RateLimiter,
+    user: RateLimiter,
+    /// Token -> (user ID, when it was looked up)
+    user_cache: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl RateLimits {
+    pub fn new(repo: Arc<AuthRepo>, config: &RateLimitConfig) -> Self {
+        Self {
+            repo,
+            trust_proxy_headers: config.trust_proxy_headers,
+            trusted_proxy_hops: config.trusted_proxy_hops.max(1),
+            auth_ip: RateLimiter::per_minute(config.auth_per_minute),
+            api_ip: RateLimiter::per_minute(config.api_per_minute),
+            sync_ip: RateLimiter::per_minute(config.sync_connects_per_minute),
+            user: RateLimiter::per_minute(config.user_per_minute),
+            user_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn ip_limiter(&self, scope: LimitScope) -> &RateLimiter {
+        match scope {
+            LimitScope::Auth => &self.auth_ip,
+            LimitScope::Api => &self.api_ip,
+            LimitScope::Sync => &self.sync_ip,
+        }
+    }
+
+    /// Charge the IP and (if authenticated) user buckets for one request
+    pub fn check(
+        &self,
+        scope: LimitScope,
+        ip: Option<IpAddr>,
+        token: Option<&str>,
+    ) -> Result<(), Duration> {
+        if let Some(ip) = ip {
+            self.ip_limiter(scope).check(&ip.to_string())?;
+        }
+        if !self.user.is_disabled()
+            && let Some(user_id) = token.and_then(|t| self.user_id(t))
+        {
+            self.user.check(&user_id)?;
+        }
+        Ok(())
+    }
+
+    /// Resolve a token to its user, caching the lookup briefly.
+    ///
+    /// Invalid tokens have no user bucket; the handler rejects them anyway.
+    fn user_id(&self, token: &str) -> Option<String> {
+        let now = Instant::now();
+        if let Some((user_id, at)) = self.user_cache.lock().unwrap().get(token)
+            && now.duration_since(*at) < USER_CACHE_TTL
+        {
+            return Some(user_id.clone());
+        }
+
+        let user_id = validate_token(&self.repo, token)?.user.id;
+        let mut cache = self.user_cache.lock().unwrap();
+        if cache.len() >= PRUNE_THRESHOLD {
+            cache.retain(|_, (_, at)| now.duration_since(*at) < USER_CACHE_TTL);
+        }
+        cache.insert(token.to_string(), (user_id.clone(), now));
+        Some(user_id)
+    }
+
+    /// Drop idle buckets and expired user lookups
+    pub fn prune(&self) -> usize {
+        let now = Instant::now();
+        self.user_cache
+            .lock()
+            .unwrap()
+            .retain(|_, (_, at)| now.duration_since(*at) < USER_CACHE_TTL);
+        self.auth_ip.prune() + self.api_ip.prune() + self.sync_ip.prune() + self.user.prune()
+    }
+
+    /// Client IP, from `X-Forwarded-For` when behind trusted proxies.
+    ///
+    /// Clients can send their own `X-Forwarded-For`, which proxies append to,
+    /// so only the entry added by the outermost trusted proxy is used:
+    /// `trusted_proxy_hops` from the right.
+    fn client_ip(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+        if self.trust_proxy_headers {
+            let forwarded: Vec<&str> = headers
+                .get_all("X-Forwarded-For")
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .collect();
+            if let Some(ip) = forwarded
+                .len()
+                .checked_sub(self.trusted_proxy_hops)
+                .and_then(|i| forwarded[i].trim().parse().ok())
+            {
+                return Some(ip);
+            }
+        }
+        peer.map(|addr| addr.ip())
+    }
+}
+
+/// Bearer token from the `Authorization` header or `token` query parameter
+fn request_token(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| {
+            req.uri().query().and_then(|q| {
+                q.split('&')
+                    .find_map(|p| p.strip_prefix("token="))
+                    .filter(|t| !t.is_empty())
+            })
+        })
+}
+
+/// Middleware enforcing [`RateLimits`] for one route group.
+///
+/// Use with `axum::middleware::from_fn_with_state((limits, scope), rate_limit)`.
+pub async fn rate_limit(
+    State((limits, scope)): State<(Arc<RateLimits>, LimitScope)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0);
+    let ip = limits.client_ip(req.headers(), peer);
+
+    if let Err(retry_after) = limits.check(scope, ip, request_token(&req)) {
+        metrics().record_rate_limited(scope.as_str());
+        return too_many_requests(retry_after);
+    }
+    next.run(req).await
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(serde_json::json!({
+            "error": "Too many requests. Please slow down.",
+            "retry_after": secs,
+        })),
+    )
+        .into_response();
+    if let Ok(value) = HeaderValue::from_str(&secs.to_string()) {
+        response.headers_mut().insert(header::RETRY_AFTER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_limits() {
+        let limiter = RateLimiter::new(3, 1.0);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at("a", start).is_ok());
+        }
+        let wait = limiter.check_at("a", start).unwrap_err();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+
+        // Other keys have their own bucket
+        assert!(limiter.check_at("b", start).is_ok());
+
+        // One token back after a second
+        assert!(
+            limiter
+                .check_at("a", start + Duration::from_secs(1))
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check_at("a", start + Duration::from_secs(1))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_zero_capacity_disables() {
+        let limiter = RateLimiter::per_minute(0);
+        assert!(limiter.is_disabled());
+        for _ in 0..100 {
+            assert!(limiter.check("a").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_prune_drops_refilled_buckets() {
+        let limiter = RateLimiter::new(2, 100.0);
+        limiter.check("a").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(limiter.prune(), 1);
+    }
+
+    #[test]
+    fn test_forwarded_for_only_when_trusted() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::init_database(&conn).unwrap();
+        let repo = Arc::new(AuthRepo::new(conn));
+        let mut config = RateLimitConfig::default();
+
+        let mut headers = HeaderMap::new();
+        // The client claims 198.51.100.1; the proxy appends its real address
+        headers.insert(
+            "X-Forwarded-For",
+            "198.51.100.1, 203.0.113.7".parse().unwrap(),
+        );
+        let peer: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+
+        let limits = RateLimits::new(repo.clone(), &config);
+        assert_eq!(limits.client_ip(&headers, Some(peer)), Some(peer.ip()));
+
+        config.trust_proxy_headers = true;
+        let limits = RateLimits::new(repo.clone(), &config);
+        assert_eq!(
+            limits.client_ip(&headers, Some(peer)),
+            Some("203.0.113.7".parse().unwrap())
+        );
+
+        // Behind two proxies the outer one's entry is second from the right
+        headers.append("X-Forwarded-For", "10.0.0.2".parse().unwrap());
+        config.trusted_proxy_hops = 2;
+        let limits = RateLimits::new(repo.clone(), &config);
+        assert_eq!(
+            limits.client_ip(&headers, Some(peer)),
+            Some("203.0.113.7".parse().unwrap())
+        );
+
+        // Fewer entries than proxies: fall back to the peer address
+        config.trusted_proxy_hops = 4;
+        let limits = RateLimits::new(repo, &config);
+        assert_eq!(limits.client_ip(&headers, Some(peer)), Some(peer.ip()));
+    }
+
+    #[test]
+    fn test_user_limit_spans_ips() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::init_database(&conn).unwrap();
+        let repo = Arc::new(AuthRepo::new(conn));
+        let user_id = repo.get_or_create_user("test@example.com").unwrap();
+        let device_id = repo.create_device(&user_id, None, None).unwrap();
+        let expires = chrono::Utc::now() + chrono::Duration::days(1);
+        let token = repo.create_session(&user_id, &device_id, expires).unwrap();
+
+        let config = RateLimitConfig {
+            user_per_minute: 2,
+            ..RateLimitConfig::default()
+        };
+        let limits = RateLimits::new(repo, &config);
+        let ip = |n: u8| Some(IpAddr::from([192, 0, 2, n]));
+
+        assert!(limits.check(LimitScope::Api, ip(1), Some(&token)).is_ok());
+        assert!(limits.check(LimitScope::Api, ip(2), Some(&token)).is_ok());
+        assert!(limits.check(LimitScope::Api, ip(3), Some(&token)).is_err());
+        // Unauthenticated requests only count against their IP
+        assert!(limits.check(LimitScope::Api, ip(3), None).is_ok());
+        assert!(
+            limits
+                .check(LimitScope::Api, ip(3), Some("bogus-token"))
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,586 @@
+//! Full-fidelity workspace archives and history replay.
+//!
+//! Unlike the markdown snapshot zip, an archive carries everything needed to
+//! rebuild a workspace's CRDT documents, including their update history:
+//!
+//! - `manifest.json`: format version, source workspace, documents,
+//!   attachments, and a SHA-256 checksum for every other entry
+//! - `documents/<n>/state.bin`: the stored document state, if any
+//! - `documents/<n>/updates.json`: the update log with timestamps and device
+//!   attribution
+//! - `files/<path>`: each file rendered as markdown, so the archive is
+//!   readable without Diaryx
+//! - `attachments/<path>`: attachment bytes, when the producer has them
+//!
+//! The server only stores CRDT data, so archives it writes list attachments
+//! in the manifest without their bytes.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use diaryx_core::crdt::{CrdtStorage, CrdtUpdate, MemoryStorage, UpdateOrigin};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Seek, Write};
+use std::sync::Arc;
+use yrs::{Doc, ReadTxn, StateVector, Transact, Update, updates::decoder::Decode};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::store::{SnapshotError, WorkspaceStorage};
+
+/// `format` value identifying a workspace archive
+pub const ARCHIVE_FORMAT: &str = "diaryx-workspace-archive";
+
+/// Newest archive version this server reads and the one it writes
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// Contents of `manifest.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub version: u32,
+    /// Workspace the archive was taken from
+    pub workspace_id: String,
+    /// Unix milliseconds
+    pub created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub documents: Vec<ArchiveDocument>,
+    #[serde(default)]
+    pub attachments: Vec<ArchiveAttachment>,
+    /// Checksum of every entry except the manifest, by entry name
+    pub entries: BTreeMap<String, ArchiveEntry>,
+}
+
+/// A CRDT document in the archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveDocument {
+    /// Storage name, e.g. `workspace:<id>` or `body:<id>/<path>`
+    pub name: String,
+    /// Directory holding `state.bin` and `updates.json`
+    pub dir: String,
+    pub has_state: bool,
+    pub update_count: usize,
+}
+
+/// An attachment referenced by a file in the workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveAttachment {
+    pub path: String,
+    #[serde(default)]
+    pub hash: String,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub size: u64,
+    /// Whether the bytes are stored under `attachments/`
+    pub included: bool,
+}
+
+/// Checksum of one archive entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// One line of a document's update log in `updates.json`
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedUpdate {
+    /// Base64-encoded yrs update
+    data: String,
+    origin: String,
+    timestamp: i64,
+    #[serde(default)]
+    device_id: Option<String>,
+    #[serde(default)]
+    device_name: Option<String>,
+}
+
+/// Builds an archive, recording a checksum for each entry as it's written
+pub struct ArchiveWriter {
+    zip: ZipWriter<Cursor<Vec<u8>>>,
+    manifest: ArchiveManifest,
+}
+
+impl ArchiveWriter {
+    pub fn new(workspace_id: &str, label: Option<&str>) -> Self {
+        Self {
+            zip: ZipWriter::new(Cursor::new(Vec::new())),
+            manifest: ArchiveManifest {
+                format: ARCHIVE_FORMAT.to_string(),
+                version: ARCHIVE_VERSION,
+                workspace_id: workspace_id.to_string(),
+                created_at: chrono::Utc::now().timestamp_millis(),
+                label: label.map(String::from),
+                documents: Vec::new(),
+                attachments: Vec::new(),
+                entries: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Add every document in `storage`, with its stored state and update log
+    pub fn add_documents(&mut self, storage: &dyn WorkspaceStorage) -> Result<(), SnapshotError> {
+        for (index, name) in storage.list_all_docs()?.into_iter().enumerate() {
+            let dir = format!("documents/{}", index);
+            let state = storage.load_doc(&name)?;
+            if let Some(state) = &state {
+                self.add_entry(&format!("{}/state.bin", dir), state)?;
+            }
+
+            let updates: Vec<ArchivedUpdate> = storage
+                .get_all_updates(&name)?
+                .into_iter()
+                .map(|update| ArchivedUpdate {
+                    data: BASE64.encode(&update.data),
+                    origin: update.origin.to_string(),
+                    timestamp: update.timestamp,
+                    device_id: update.device_id,
+                    device_name: update.device_name,
+                })
+                .collect();
+            self.add_entry(
+                &format!("{}/updates.json", dir),
+                &serde_json::to_vec(&updates)?,
+            )?;
+
+            self.manifest.documents.push(ArchiveDocument {
+                name,
+                dir,
+                has_state: state.is_some(),
+                update_count: updates.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Add a file rendered as markdown under `files/`
+    pub fn add_file(&mut self, path: &str, content: &str) -> Result<(), SnapshotError> {
+        self.add_entry(&format!("files/{}", path), content.as_bytes())
+    }
+
+    /// List an attachment whose bytes aren't available to the writer
+    pub fn add_attachment_ref(&mut self, attachment: ArchiveAttachment) {
+        self.manifest.attachments.push(attachment);
+    }
+
+    /// Write the manifest and return the archive bytes
+    pub fn finish(mut self) -> Result<Vec<u8>, SnapshotError> {
+        let manifest = serde_json::to_vec_pretty(&self.manifest)?;
+        self.zip.start_file(MANIFEST_NAME, Self::options())?;
+        self.zip.write_all(&manifest)?;
+        Ok(self.zip.finish()?.into_inner())
+    }
+
+    fn add_entry(&mut self, name: &str, bytes: &[u8]) -> Result<(), SnapshotError> {
+        self.zip.start_file(name, Self::options())?;
+        self.zip.write_all(bytes)?;
+        self.manifest.entries.insert(
+            name.to_string(),
+            ArchiveEntry {
+                sha256: sha256_hex(bytes),
+                size: bytes.len() as u64,
+            },
+        );
+        Ok(())
+    }
+
+    fn options() -> FileOptions<'static, ()> {
+        FileOptions::<()>::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(0o644)
+    }
+}
+
+/// A document read back from an archive
+struct ArchivedDocument {
+    name: String,
+    state: Option<Vec<u8>>,
+    updates: Vec<CrdtUpdate>,
+}
+
+/// A verified archive, ready to import or restore from
+pub struct WorkspaceArchive {
+    pub manifest: ArchiveManifest,
+    documents: Vec<ArchivedDocument>,
+}
+
+impl WorkspaceArchive {
+    /// Parse an archive, checking its format, version and every checksum.
+    pub fn read(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut zip = ZipArchive::new(Cursor::new(bytes))?;
+        let manifest = read_manifest_from(&mut zip)?;
+
+        for (name, expected) in &manifest.entries {
+            let data = read_entry(&mut zip, name)?;
+            let actual = ArchiveEntry {
+                sha256: sha256_hex(&data),
+                size: data.len() as u64,
+            };
+            if &actual != expected {
+                return Err(SnapshotError::Checksum(name.clone()));
+            }
+        }
+
+        let mut documents = Vec::with_capacity(manifest.documents.len());
+        for doc in &manifest.documents {
+            let state_name = format!("{}/state.bin", doc.dir);
+            let state = if doc.has_state {
+                Some(Self::verified_entry(&mut zip, &manifest, &state_name)?)
+            } else {
+                None
+            };
+
+            let updates_name = format!("{}/updates.json", doc.dir);
+            let archived: Vec<ArchivedUpdate> =
+                serde_json::from_slice(&Self::verified_entry(&mut zip, &manifest, &updates_name)?)?;
+            let updates = archived
+                .into_iter()
+                .map(|update| {
+                    Ok(CrdtUpdate {
+                        update_id: 0,
+                        doc_name: doc.name.clone(),
+                        data: BASE64.decode(&update.data).map_err(|e| {
+                            SnapshotError::Parse(format!("Bad update in {}: {}", updates_name, e))
+                        })?,
+                        timestamp: update.timestamp,
+                        origin: update.origin.parse().unwrap_or(UpdateOrigin::Remote),
+                        device_id: update.device_id,
+                        device_name: update.device_name,
+                    })
+                })
+                .collect::<Result<_, SnapshotError>>()?;
+
+            documents.push(ArchivedDocument {
+                name: doc.name.clone(),
+                state,
+                updates,
+            });
+        }
+
+        Ok(Self {
+            manifest,
+            documents,
+        })
+    }
+
+    /// Write the archived documents and their history into an empty
+    /// workspace, renaming them from the source workspace to `workspace_id`.
+    ///
+    /// Returns how many updates were imported.
+    pub fn import_into(
+        &self,
+        storage: &dyn WorkspaceStorage,
+        workspace_id: &str,
+    ) -> Result<usize, SnapshotError> {
+        let mut imported = 0;
+        for doc in &self.documents {
+            let name = self.rename(&doc.name, workspace_id);
+            if let Some(state) = &doc.state {
+                storage.save_doc(&name, state)?;
+            }
+            let updates: Vec<CrdtUpdate> = doc
+                .updates
+                .iter()
+                .cloned()
+                .map(|update| CrdtUpdate {
+                    doc_name: name.clone(),
+                    ..update
+                })
+                .collect();
+            storage.import_updates(&updates)?;
+            imported += updates.len();
+        }
+        Ok(imported)
+    }
+
+    /// The archived documents as they'd be named in `workspace_id`, in
+    /// throwaway storage for reading the files and bodies to restore.
+    pub fn to_memory_storage(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Arc<MemoryStorage>, SnapshotError> {
+        let memory = Arc::new(MemoryStorage::new());
+        for doc in &self.documents {
+            let name = self.rename(&doc.name, workspace_id);
+            if let Some(state) = &doc.state {
+                memory.save_doc(&name, state)?;
+            }
+            for update in &doc.updates {
+                memory.append_update(&name, &update.data, update.origin)?;
+            }
+        }
+        Ok(memory)
+    }
+
+    /// Map a document name from the source workspace to `workspace_id`
+    fn rename(&self, name: &str, workspace_id: &str) -> String {
+        let source = &self.manifest.workspace_id;
+        if name == format!("workspace:{}", source) {
+            format!("workspace:{}", workspace_id)
+        } else if let Some(path) = name.strip_prefix(&format!("body:{}/", source)) {
+            format!("body:{}/{}", workspace_id, path)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Read an entry the manifest vouches for
+    fn verified_entry<R: Read + Seek>(
+        zip: &mut ZipArchive<R>,
+        manifest: &ArchiveManifest,
+        name: &str,
+    ) -> Result<Vec<u8>, SnapshotError> {
+        if !manifest.entries.contains_key(name) {
+            return Err(SnapshotError::Parse(format!(
+                "{} is missing from the manifest",
+                name
+            )));
+        }
+        read_entry(zip, name)
+    }
+}
+
+/// Read just the manifest of an archive, without verifying checksums.
+pub fn read_manifest(reader: impl Read + Seek) -> Result<ArchiveManifest, SnapshotError> {
+    read_manifest_from(&mut ZipArchive::new(reader)?)
+}
+
+fn read_manifest_from<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+) -> Result<ArchiveManifest, SnapshotError> {
+    let manifest: ArchiveManifest = serde_json::from_slice(&read_entry(zip, MANIFEST_NAME)?)?;
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(SnapshotError::Parse(format!(
+            "Not a workspace archive (format {:?})",
+            manifest.format
+        )));
+    }
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(SnapshotError::Parse(format!(
+            "Archive version {} is newer than this server supports ({})",
+            manifest.version, ARCHIVE_VERSION
+        )));
+    }
+    Ok(manifest)
+}
+
+fn read_entry<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, SnapshotError> {
+    let mut entry = zip
+        .by_name(name)
+        .map_err(|_| SnapshotError::Parse(format!("Archive is missing {}", name)))?;
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Rebuild every document of a workspace as it was at `at` (Unix
+/// milliseconds) by replaying its update log, into throwaway storage.
+///
+/// The stored document state is the latest state, so it can't be used as a
+/// starting point. Replay only works while the log is complete: once
+/// compaction has folded old updates into the stored state, the document's
+/// earlier history is gone and this returns
+/// [`SnapshotError::HistoryUnavailable`].
+pub fn state_at(
+    storage: &dyn WorkspaceStorage,
+    at: i64,
+) -> Result<Arc<MemoryStorage>, SnapshotError> {
+    let memory = Arc::new(MemoryStorage::new());
+    for name in storage.list_all_docs()? {
+        let updates = storage.get_all_updates(&name)?;
+        if let Some(state) = storage.load_doc(&name)?
+            && !log_covers_state(&updates, &state)
+        {
+            return Err(SnapshotError::HistoryUnavailable(name));
+        }
+
+        for update in updates.iter().filter(|u| u.timestamp <= at) {
+            memory.append_update(&name, &update.data, update.origin)?;
+        }
+    }
+    Ok(memory)
+}
+
+/// Whether replaying `updates` from scratch reaches everything in `state`
+fn log_covers_state(updates: &[CrdtUpdate], state: &[u8]) -> bool {
+    let replayed = Doc::new();
+    {
+        let mut txn = replayed.transact_mut();
+        for update in updates {
+            if let Ok(update) = Update::decode_v1(&update.data) {
+                let _ = txn.apply_update(update);
+            }
+        }
+    }
+    let replayed = replayed.transact().state_vector();
+    state_vector_of(state)
+        .iter()
+        .all(|(client, clock)| replayed.get(client) >= *clock)
+}
+
+fn state_vector_of(state: &[u8]) -> StateVector {
+    let doc = Doc::new();
+    {
+        let mut txn = doc.transact_mut();
+        if let Ok(update) = Update::decode_v1(state) {
+            let _ = txn.apply_update(update);
+        }
+    }
+    doc.transact().state_vector()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diaryx_core::crdt::SqliteStorage;
+    use yrs::{GetString, Text};
+
+    fn text_update(doc: &Doc, text: &str) -> Vec<u8> {
+        let content = doc.get_or_insert_text("content");
+        let before = doc.transact().state_vector();
+        let mut txn = doc.transact_mut();
+        let len = content.len(&txn);
+        content.insert(&mut txn, len, text);
+        txn.encode_diff_v1(&before)
+    }
+
+    fn text_of(storage: &dyn CrdtStorage, name: &str) -> String {
+        let doc = Doc::new();
+        let content = doc.get_or_insert_text("content");
+        {
+            let mut txn = doc.transact_mut();
+            for update in storage.get_all_updates(name).unwrap() {
+                txn.apply_update(Update::decode_v1(&update.data).unwrap())
+                    .unwrap();
+            }
+        }
+        content.get_string(&doc.transact())
+    }
+
+    #[test]
+    fn test_archive_round_trip_renames_documents() {
+        let source = SqliteStorage::in_memory().unwrap();
+        let doc = Doc::new();
+        source
+            .append_update(
+                "body:ws1/a.md",
+                &text_update(&doc, "Hello"),
+                UpdateOrigin::Remote,
+            )
+            .unwrap();
+        source
+            .append_update_with_device(
+                "body:ws1/a.md",
+                &text_update(&doc, " world"),
+                UpdateOrigin::Remote,
+                Some("dev-1"),
+                Some("Laptop"),
+            )
+            .unwrap();
+
+        let mut writer = ArchiveWriter::new("ws1", Some("nightly"));
+        writer.add_documents(&source).unwrap();
+        writer.add_file("a.md", "Hello world").unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let manifest = read_manifest(Cursor::new(&bytes)).unwrap();
+        assert_eq!(manifest.label.as_deref(), Some("nightly"));
+        assert_eq!(manifest.documents.len(), 1);
+        assert!(manifest.entries.contains_key("files/a.md"));
+
+        let archive = WorkspaceArchive::read(&bytes).unwrap();
+        let target = SqliteStorage::in_memory().unwrap();
+        assert_eq!(archive.import_into(&target, "ws2").unwrap(), 2);
+
+        let updates = target.get_all_updates("body:ws2/a.md").unwrap();
+        assert_eq!(updates[1].device_name.as_deref(), Some("Laptop"));
+        assert_eq!(text_of(&target, "body:ws2/a.md"), "Hello world");
+    }
+
+    #[test]
+    fn test_archive_rejects_bad_checksum() {
+        let mut writer = ArchiveWriter::new("ws1", None);
+        writer.add_file("a.md", "original").unwrap();
+        let manifest = writer.manifest.clone();
+        let bytes = writer.finish().unwrap();
+        assert!(WorkspaceArchive::read(&bytes).is_ok());
+
+        // Rebuild the archive with tampered file contents but the old manifest
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("files/a.md", ArchiveWriter::options())
+            .unwrap();
+        zip.write_all(b"tampered").unwrap();
+        zip.start_file(MANIFEST_NAME, ArchiveWriter::options())
+            .unwrap();
+        zip.write_all(&serde_json::to_vec(&manifest).unwrap())
+            .unwrap();
+        let tampered = zip.finish().unwrap().into_inner();
+
+        assert!(matches!(
+            WorkspaceArchive::read(&tampered),
+            Err(SnapshotError::Checksum(name)) if name == "files/a.md"
+        ));
+    }
+
+    #[test]
+    fn test_state_at_replays_log_and_detects_compaction() {
+        let storage = SqliteStorage::in_memory().unwrap();
+        let doc = Doc::new();
+        storage
+            .import_updates(&[
+                CrdtUpdate {
+                    update_id: 0,
+                    doc_name: "body:ws1/a.md".to_string(),
+                    data: text_update(&doc, "Hello"),
+                    timestamp: 1_000,
+                    origin: UpdateOrigin::Remote,
+                    device_id: None,
+                    device_name: None,
+                },
+                CrdtUpdate {
+                    update_id: 0,
+                    doc_name: "body:ws1/a.md".to_string(),
+                    data: text_update(&doc, " world"),
+                    timestamp: 2_000,
+                    origin: UpdateOrigin::Remote,
+                    device_id: None,
+                    device_name: None,
+                },
+            ])
+            .unwrap();
+        // The server also stores the latest state whenever a document is saved
+        storage
+            .save_doc(
+                "body:ws1/a.md",
+                &doc.transact()
+                    .encode_state_as_update_v1(&StateVector::default()),
+            )
+            .unwrap();
+
+        let at = state_at(&storage, 1_500).unwrap();
+        assert_eq!(text_of(at.as_ref(), "body:ws1/a.md"), "Hello");
+        let at = state_at(&storage, 2_000).unwrap();
+        assert_eq!(text_of(at.as_ref(), "body:ws1/a.md"), "Hello world");
+
+        storage.compact("body:ws1/a.md", 1).unwrap();
+        assert!(matches!(
+            state_at(&storage, 1_500),
+            Err(SnapshotError::HistoryUnavailable(name)) if name == "body:ws1/a.md"
+        ));
+    }
+}
@@ -0,0 +1,422 @@
+//! Sync connections the server can close.
+//!
+//! Siphonophore owns the WebSocket of each `/sync2` connection and can't
+//! close one, so access checked when a client joins a document would
+//! otherwise last as long as the socket. Instead:
+//!
+//! - the server accepts sockets through [`ConnectionListener`], which wraps
+//!   each one in a [`ClosableStream`] registered in [`SyncConnections`]
+//! - [`tag_connection`] passes the registered ID of the socket on to
+//!   `DiaryxHook` as the `conn` query parameter of the WebSocket upgrade
+//! - `DiaryxHook` records who each connection authenticated as
+//!
+//! Closing a connection ends its socket, so siphonophore drops it from every
+//! document. Used when a member is removed from a workspace and when a share
+//! session is narrowed or ended: the clients reconnect and are authenticated
+//! against their new access.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::Uri,
+    middleware::Next,
+    response::Response,
+};
+use futures::task::AtomicWaker;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+/// Query parameter carrying the connection ID to `DiaryxHook`.
+pub const CONNECTION_PARAM: &str = "conn";
+
+/// Who a connection authenticated as, for one or more documents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConnectionPrincipal {
+    /// A workspace owner or member.
+    Member {
+        workspace_id: String,
+        user_id: String,
+    },
+    /// A share-session guest.
+    Guest { session_code: String },
+}
+
+/// Lets a [`SyncConnections`] close a socket it didn't open.
+#[derive(Default)]
+struct CloseSignal {
+    closed: AtomicBool,
+    read_waker: AtomicWaker,
+    write_waker: AtomicWaker,
+}
+
+impl CloseSignal {
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.read_waker.wake();
+        self.write_waker.wake();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+}
+
+struct Connection {
+    addr: SocketAddr,
+    signal: Arc<CloseSignal>,
+    principals: HashSet<ConnectionPrincipal>,
+}
+
+#[derive(Default)]
+struct Registry {
+    connections: HashMap<u64, Connection>,
+    by_addr: HashMap<SocketAddr, u64>,
+}
+
+/// Open sockets, by ID, and who they authenticated as.
+#[derive(Default)]
+pub struct SyncConnections {
+    next_id: AtomicU64,
+    registry: Mutex<Registry>,
+}
+
+impl SyncConnections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new socket, returning its ID and close signal.
+    fn register(&self, addr: SocketAddr) -> (u64, Arc<CloseSignal>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let signal = Arc::new(CloseSignal::default());
+        let mut registry = self.registry.lock().unwrap();
+        registry.connections.insert(
+            id,
+            Connection {
+                addr,
+                signal: signal.clone(),
+                principals: HashSet::new(),
+            },
+        );
+        registry.by_addr.insert(addr, id);
+        (id, signal)
+    }
+
+    /// Forget a socket once it's dropped.
+    fn unregister(&self, id: u64) {
+        let mut registry = self.registry.lock().unwrap();
+        if let Some(connection) = registry.connections.remove(&id)
+            && registry.by_addr.get(&connection.addr) == Some(&id)
+        {
+            registry.by_addr.remove(&connection.addr);
+        }
+    }
+
+    /// ID of the open socket from `addr`.
+    pub fn id_of(&self, addr: SocketAddr) -> Option<u64> {
+        self.registry.lock().unwrap().by_addr.get(&addr).copied()
+    }
+
+    /// Record that a connection authenticated as `principal`.
+    pub fn authenticated(&self, id: u64, principal: ConnectionPrincipal) {
+        if let Some(connection) = self.registry.lock().unwrap().connections.get_mut(&id) {
+            connection.principals.insert(principal);
+        }
+    }
+
+    /// Close every connection that authenticated as `principal`, returning
+    /// how many were closed.
+    pub fn close(&self, principal: &ConnectionPrincipal) -> usize {
+        let registry = self.registry.lock().unwrap();
+        let mut closed = 0;
+        for connection in registry.connections.values() {
+            if connection.principals.contains(principal) {
+                connection.signal.close();
+                closed += 1;
+            }
+        }
+        if closed > 0 {
+            debug!("Closed {} sync connection(s) of {:?}", closed, principal);
+        }
+        closed
+    }
+
+    /// Close a member's connections to a workspace.
+    pub fn close_member(&self, workspace_id: &str, user_id: &str) -> usize {
+        self.close(&ConnectionPrincipal::Member {
+            workspace_id: workspace_id.to_string(),
+            user_id: user_id.to_string(),
+        })
+    }
+
+    /// Close the connections of a share session's guests.
+    pub fn close_session(&self, session_code: &str) -> usize {
+        self.close(&ConnectionPrincipal::Guest {
+            session_code: session_code.to_uppercase(),
+        })
+    }
+}
+
+/// A socket that [`SyncConnections`] can close: once closed, reads end and
+/// writes fail.
+pub struct ClosableStream<S> {
+    inner: S,
+    id: u64,
+    signal: Arc<CloseSignal>,
+    connections: Arc<SyncConnections>,
+}
+
+impl<S> ClosableStream<S> {
+    /// Wrap `inner`, registering it in `connections`.
+    pub fn new(inner: S, addr: SocketAddr, connections: Arc<SyncConnections>) -> Self {
+        let (id, signal) = connections.register(addr);
+        Self {
+            inner,
+            id,
+            signal,
+            connections,
+        }
+    }
+
+    /// ID of this socket in its `SyncConnections`.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl<S> Drop for ClosableStream<S> {
+    fn drop(&mut self) {
+        self.connections.unregister(self.id);
+    }
+}
+
+fn closed_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "connection closed by server",
+    )
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ClosableStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.signal.read_waker.register(cx.waker());
+        if self.signal.is_closed() {
+            // End of stream
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ClosableStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.signal.write_waker.register(cx.waker());
+        if self.signal.is_closed() {
+            return Poll::Ready(Err(closed_error()));
+        }
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.signal.write_waker.register(cx.waker());
+        if self.signal.is_closed() {
+            return Poll::Ready(Err(closed_error()));
+        }
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// TCP listener whose sockets are registered in [`SyncConnections`].
+///
+/// Use in place of `tokio::net::TcpListener` with `axum::serve`.
+pub struct ConnectionListener {
+    inner: TcpListener,
+    connections: Arc<SyncConnections>,
+}
+
+impl ConnectionListener {
+    pub fn new(inner: TcpListener, connections: Arc<SyncConnections>) -> Self {
+        Self { inner, connections }
+    }
+}
+
+impl axum::serve::Listener for ConnectionListener {
+    type Io = ClosableStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            match self.inner.accept().await {
+                Ok((stream, addr)) => {
+                    let stream = ClosableStream::new(stream, addr, self.connections.clone());
+                    return (stream, addr);
+                }
+                Err(e) => {
+                    // Like axum's own TCP listener: usually out of file
+                    // descriptors, so back off instead of spinning
+                    warn!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+/// Middleware on `/sync2` setting the `conn` query parameter to the ID of
+/// the request's socket.
+///
+/// Any `conn` the client sent is dropped, so a connection can't pass itself
+/// off as another one. Use with
+/// `axum::middleware::from_fn_with_state(connections, tag_connection)`.
+pub async fn tag_connection(
+    State(connections): State<Arc<SyncConnections>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let id = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .and_then(|info| connections.id_of(info.0));
+    if let Some(uri) = with_connection_param(req.uri(), id) {
+        *req.uri_mut() = uri;
+    }
+    next.run(req).await
+}
+
+/// `uri` with its `conn` parameters replaced by `id`.
+///
+/// Encoded parameter names are dropped too, since they might decode to
+/// `conn`; the sync protocol doesn't use any.
+fn with_connection_param(uri: &Uri, id: Option<u64>) -> Option<Uri> {
+    let mut params: Vec<String> = uri
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|param| {
+            let name = param.split('=').next().unwrap_or("");
+            !param.is_empty() && name != CONNECTION_PARAM && !name.contains('%')
+        })
+        .map(str::to_string)
+        .collect();
+    if let Some(id) = id {
+        params.push(format!("{}={}", CONNECTION_PARAM, id));
+    }
+
+    let path_and_query = if params.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), params.join("&"))
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[tokio::test]
+    async fn test_closing_a_principal_ends_its_sockets_only() {
+        let connections = Arc::new(SyncConnections::new());
+        let (member_side, mut member_peer) = tokio::io::duplex(64);
+        let (other_side, mut other_peer) = tokio::io::duplex(64);
+        let mut member = ClosableStream::new(member_side, addr(1), connections.clone());
+        let mut other = ClosableStream::new(other_side, addr(2), connections.clone());
+        assert_eq!(connections.id_of(addr(1)), Some(member.id()));
+
+        connections.authenticated(
+            member.id(),
+            ConnectionPrincipal::Member {
+                workspace_id: "ws".to_string(),
+                user_id: "alice".to_string(),
+            },
+        );
+        connections.authenticated(
+            other.id(),
+            ConnectionPrincipal::Member {
+                workspace_id: "ws".to_string(),
+                user_id: "bob".to_string(),
+            },
+        );
+
+        // A pending read is woken and ends
+        let read = tokio::spawn(async move {
+            let mut buf = [0u8; 8];
+            let n = member.read(&mut buf).await.unwrap();
+            (n, member)
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(connections.close_member("ws", "alice"), 1);
+        let (n, mut member) = read.await.unwrap();
+        assert_eq!(n, 0);
+        assert!(member.write_all(b"update").await.is_err());
+        member_peer.write_all(b"ignored").await.unwrap();
+
+        // Other members are untouched
+        other_peer.write_all(b"hi").await.unwrap();
+        let mut buf = [0u8; 2];
+        other.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hi");
+        assert_eq!(connections.close_member("other-ws", "bob"), 0);
+
+        drop(member);
+        assert_eq!(connections.id_of(addr(1)), None);
+        assert_eq!(connections.close_member("ws", "alice"), 0);
+    }
+
+    #[test]
+    fn test_session_connections_closed_by_code() {
+        let connections = Arc::new(SyncConnections::new());
+        let (side, _peer) = tokio::io::duplex(64);
+        let guest = ClosableStream::new(side, addr(3), connections.clone());
+        connections.authenticated(
+            guest.id(),
+            ConnectionPrincipal::Guest {
+                session_code: "ABCD".to_string(),
+            },
+        );
+        assert_eq!(connections.close_session("abcd"), 1);
+        assert!(guest.signal.is_closed());
+    }
+
+    #[test]
+    fn test_connection_param_replaces_client_value() {
+        let uri: Uri = "/sync2?token=t&conn=9&co%6En=8&client=c".parse().unwrap();
+        let uri = with_connection_param(&uri, Some(3)).unwrap();
+        assert_eq!(uri.to_string(), "/sync2?token=t&client=c&conn=3");
+
+        let uri: Uri = "/sync2?conn=9".parse().unwrap();
+        let uri = with_connection_param(&uri, None).unwrap();
+        assert_eq!(uri.to_string(), "/sync2");
+    }
+}
@@ -0,0 +1,194 @@
+//! Share-session guests connected to this instance.
+//!
+//! `DiaryxHook` checks guest updates, awareness and manifests against the
+//! session's scope. Rather than reading the session and loading the
+//! workspace for every message, each guest connection caches its scope and
+//! the scope resolved against the workspace tree here:
+//!
+//! - scope changes made through this instance replace the cached scope right
+//!   away (`SyncV2State::broadcast_scope_changed`)
+//! - persisted workspace updates drop the resolved scopes of that workspace
+//! - anything else (e.g. a scope changed on another instance sharing the
+//!   database) applies within [`SCOPE_CACHE_TTL`]
+
+use diaryx_core::crdt::{ResolvedScope, ShareScope};
+use siphonophore::ClientId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a guest connection trusts its cached scope.
+pub const SCOPE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Cached scope of one guest connection.
+struct GuestConnection {
+    session_code: String,
+    workspace_id: String,
+    /// The connection's `client` tag, to address messages to it
+    client_tag: Option<String>,
+    loaded_at: Instant,
+    /// Session scope (`None` = whole workspace)
+    scope: Option<ShareScope>,
+    /// `scope` resolved against the current workspace tree
+    resolved: Option<Arc<ResolvedScope>>,
+}
+
+/// Guest connections by client, with their cached scopes.
+#[derive(Default)]
+pub struct GuestConnections {
+    connections: Mutex<HashMap<ClientId, GuestConnection>>,
+}
+
+impl GuestConnections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scope of a guest connection, from the cache or else from `load`
+    /// (given the session code).
+    pub(crate) fn scope(
+        &self,
+        client_id: ClientId,
+        session_code: &str,
+        workspace_id: &str,
+        client_tag: Option<&str>,
+        load: impl FnOnce(&str) -> Option<ShareScope>,
+    ) -> Option<ShareScope> {
+        if let Some(connection) = self.connections.lock().unwrap().get(&client_id)
+            && connection.session_code == session_code
+            && connection.loaded_at.elapsed() < SCOPE_CACHE_TTL
+        {
+            return connection.scope.clone();
+        }
+
+        let scope = load(session_code);
+        self.connections.lock().unwrap().insert(
+            client_id,
+            GuestConnection {
+                session_code: session_code.to_string(),
+                workspace_id: workspace_id.to_string(),
+                client_tag: client_tag.map(str::to_string),
+                loaded_at: Instant::now(),
+                scope: scope.clone(),
+                resolved: None,
+            },
+        );
+        scope
+    }
+
+    /// Cached resolution of a connection's scope, if it has one.
+    pub(crate) fn resolved(&self, client_id: ClientId) -> Option<Arc<ResolvedScope>> {
+        self.connections
+            .lock()
+            .unwrap()
+            .get(&client_id)?
+            .resolved
+            .clone()
+    }
+
+    /// Cache the resolution of `scope`, unless the connection's scope changed
+    /// in the meantime.
+    pub(crate) fn set_resolved(
+        &self,
+        client_id: ClientId,
+        scope: &ShareScope,
+        resolved: Arc<ResolvedScope>,
+    ) {
+        if let Some(connection) = self.connections.lock().unwrap().get_mut(&client_id)
+            && connection.scope.as_ref() == Some(scope)
+        {
+            connection.resolved = Some(resolved);
+        }
+    }
+
+    /// Scoped connections among `clients`, with their workspace and scope.
+    pub(crate) fn scoped(&self, clients: &[ClientId]) -> Vec<(ClientId, String, ShareScope)> {
+        let connections = self.connections.lock().unwrap();
+        clients
+            .iter()
+            .filter_map(|client_id| {
+                let connection = connections.get(client_id)?;
+                let scope = connection.scope.clone()?;
+                Some((*client_id, connection.workspace_id.clone(), scope))
+            })
+            .collect()
+    }
+
+    /// Drop the resolved scopes of a workspace after its tree changed.
+    pub(crate) fn workspace_changed(&self, workspace_id: &str) {
+        for connection in self.connections.lock().unwrap().values_mut() {
+            if connection.workspace_id == workspace_id {
+                connection.resolved = None;
+            }
+        }
+    }
+
+    /// Replace the scope of a session's connections, returning the `client`
+    /// tags of those connections that have one.
+    pub fn scope_changed(&self, session_code: &str, scope: Option<&ShareScope>) -> Vec<String> {
+        let mut tags = Vec::new();
+        for connection in self.connections.lock().unwrap().values_mut() {
+            if connection.session_code != session_code {
+                continue;
+            }
+            connection.scope = scope.cloned();
+            connection.resolved = None;
+            connection.loaded_at = Instant::now();
+            tags.extend(connection.client_tag.clone());
+        }
+        tags
+    }
+
+    /// Forget a connection once it left every document.
+    pub(crate) fn remove(&self, client_id: ClientId) {
+        self.connections.lock().unwrap().remove(&client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diaryx_core::crdt::{ScopeEntry, SharePermission};
+
+    fn scope(path: &str) -> ShareScope {
+        ShareScope {
+            entries: vec![ScopeEntry {
+                path: path.to_string(),
+                permission: SharePermission::Write,
+                include_contents: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_scope_is_cached_until_changed() {
+        let guests = GuestConnections::new();
+        let mut loads = 0;
+        for _ in 0..3 {
+            let loaded = guests.scope(1, "ABC", "ws1", Some("tag-1"), |_| {
+                loads += 1;
+                Some(scope("a.md"))
+            });
+            assert_eq!(loaded, Some(scope("a.md")));
+        }
+        assert_eq!(loads, 1);
+        guests.scope(2, "OTHER", "ws1", Some("tag-2"), |_| None);
+
+        guests.set_resolved(1, &scope("a.md"), Arc::new(ResolvedScope::default()));
+        assert!(guests.resolved(1).is_some());
+        guests.workspace_changed("ws1");
+        assert!(guests.resolved(1).is_none());
+
+        // Only the session's own connections are updated and addressed
+        assert_eq!(
+            guests.scope_changed("ABC", Some(&scope("b.md"))),
+            vec!["tag-1".to_string()]
+        );
+        let loaded = guests.scope(1, "ABC", "ws1", None, |_| panic!("not cached"));
+        assert_eq!(loaded, Some(scope("b.md")));
+        assert_eq!(guests.scoped(&[1, 2, 3]).len(), 1);
+
+        guests.remove(1);
+        assert!(guests.scoped(&[1]).is_empty());
+    }
+}
@@ -13,6 +13,9 @@
 //! 5. Server sends `CrdtState` (JSON text message with base64 state)
 //! 6. Normal siphonophore y-sync begins
 //!
+//! Guests of a scoped share session stop at step 5: their `CrdtState` only
+//! holds the shared files, and y-sync for the workspace doc never starts.
+//!
 //! ## Message Format
 //!
 //! Control messages are JSON text messages:
@@ -23,9 +26,12 @@
 //! {"type": "crdt_state", "state": "<base64>"}
 //! {"type": "focus", "files": ["path/to/file.md"]}
 //! {"type": "unfocus", "files": ["path/to/file.md"]}
+//! {"type": "awareness", "doc_id": "workspace:<id>", "update": "<base64>"}
 //! {"type": "peer_joined", "guest_id": "...", "peer_count": 2}
 //! {"type": "peer_left", "guest_id": "...", "peer_count": 1}
+//! {"type": "scope_changed", "client": "<tag>"}
 //! {"type": "session_ended"}
+//! {"type": "sync_error", "code": "rate_limited", "message": "...", "client": "<tag>", "retry_after_ms": 1000}
 //! ```
 
 use axum::extract::ws::{Message, WebSocket};
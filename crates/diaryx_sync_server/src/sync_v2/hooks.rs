@@ -4,24 +4,40 @@
 //! - JWT authentication and session validation
 //! - SQLite-based document persistence
 //! - Change event handling
+//! - Share scope enforcement for guests
+//! - Presence (awareness) relay between collaborators
+//! - Per-connection update rate and message size limits
+//! - Reporting changed files to workspace webhooks
 
 use async_trait::async_trait;
-use diaryx_core::crdt::{CrdtStorage, UpdateOrigin};
-use siphonophore::Handle;
+use diaryx_core::crdt::{
+    Awareness, AwarenessUpdate, ResolvedScope, SharePermission, ShareScope, SyncErrorCode,
+    SyncServerError, UpdateOrigin, WorkspaceCrdt, decode_awareness_control,
+    encode_awareness_control, encode_sync_error_control,
+};
 use siphonophore::{
     BeforeCloseDirtyPayload, BeforeSyncAction, ControlMessageResponse, Hook, HookResult,
     OnAuthenticatePayload, OnBeforeSyncPayload, OnChangePayload, OnConnectPayload,
     OnControlMessagePayload, OnDisconnectPayload, OnLoadDocumentPayload, OnPeerJoinedPayload,
     OnPeerLeftPayload, OnSavePayload,
 };
-use std::collections::HashMap;
+use siphonophore::{ClientId, Handle};
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
 use crate::auth::validate_token;
-use crate::db::AuthRepo;
-
+use crate::config::RateLimitConfig;
+use crate::db::{AuthRepo, WorkspaceRole};
+use crate::metrics::{AuthFailure, metrics};
+use crate::rate_limit::RateLimiter;
+use crate::webhooks::{ChangeKind, FileChange, WebhookDispatcher, workspace_changes};
+
+use super::connections::{CONNECTION_PARAM, ConnectionPrincipal, SyncConnections};
+use super::guests::GuestConnections;
+use super::stats::SyncStats;
 use super::store::StorageCache;
 
 /// User information stored in the connection context after authentication.
@@ -33,6 +49,13 @@ pub struct AuthenticatedUser {
     pub device_id: Option<String>,
     pub is_guest: bool,
     pub read_only: bool,
+    /// Share session code for guests. The session's scope is cached per
+    /// connection in `GuestConnections` so owner changes apply to open
+    /// connections.
+    pub session_code: Option<String>,
+    /// Tag from the `client` query parameter, echoed in `sync_error` messages
+    /// so the client can tell which errors are its own.
+    pub client_tag: Option<String>,
 }
 
 /// Document type determined from doc_id prefix.
@@ -77,14 +100,30 @@ impl DocType {
             DocType::Body { workspace_id, path } => format!("body:{}/{}", workspace_id, path),
         }
     }
+
+    /// The same document in another workspace.
+    pub fn in_workspace(&self, workspace_id: &str) -> DocType {
+        match self {
+            DocType::Workspace(_) => DocType::Workspace(workspace_id.to_string()),
+            DocType::Body { path, .. } => DocType::Body {
+                workspace_id: workspace_id.to_string(),
+                path: path.clone(),
+            },
+        }
+    }
 }
 
+/// Workspace name clients synced as before they were given workspace IDs.
+/// Connections naming it are rewritten to the user's own default workspace.
+const LEGACY_DEFAULT_WORKSPACE: &str = "default";
+
 /// Diaryx hook implementation for siphonophore.
 ///
 /// This hook provides:
 /// - JWT authentication for authenticated users
 /// - Session code validation for guests
 /// - SQLite persistence for CRDT documents
+/// - Awareness relay so collaborators see each other's presence
 pub struct DiaryxHook {
     /// Auth repository for token validation.
     repo: Arc<AuthRepo>,
@@ -95,6 +134,77 @@ pub struct DiaryxHook {
     handle: Arc<OnceLock<Handle>>,
     /// Shared session-to-workspace mapping (also used by SyncV2State for peer counts).
     session_to_workspace: Arc<RwLock<HashMap<String, String>>>,
+    /// Presence states of each document's peers.
+    awareness: RwLock<AwarenessRelay>,
+    /// Live connection counts (also read by SyncV2State for admin/status endpoints).
+    stats: Arc<SyncStats>,
+    /// Cached share scopes of guest connections (also updated by SyncV2State
+    /// on scope changes).
+    guests: Arc<GuestConnections>,
+    /// When each client was sent its file manifest, for handshake latency metrics.
+    handshakes: RwLock<HashMap<(ClientId, String), Instant>>,
+    /// Largest update or control message accepted, in bytes (0 = unlimited).
+    max_message_bytes: usize,
+    /// Update and awareness rate per connection.
+    update_limiter: RateLimiter,
+    /// At most one `sync_error` per connection per second, so a flooding
+    /// client can't turn refused updates into an error storm.
+    error_throttle: RateLimiter,
+    /// Latest refused-update error of each connection, sent with the reply to
+    /// its next control message.
+    pending_errors: std::sync::Mutex<HashMap<ClientId, String>>,
+    /// Open sockets and who they authenticated as, so removed members and
+    /// narrowed share sessions can be disconnected.
+    connections: Arc<SyncConnections>,
+    /// Receives the files each persisted update changed, for webhooks.
+    webhooks: Arc<WebhookDispatcher>,
+}
+
+/// Awareness client ID used by the server's relay cache.
+///
+/// Outside the 32-bit range Y.js clients pick from, so it never shadows a peer.
+const RELAY_AWARENESS_CLIENT_ID: u64 = u64::MAX;
+
+/// Most presence states kept per document; updates adding more are dropped.
+const MAX_AWARENESS_STATES: usize = 256;
+
+/// Presence relayed between the peers of each document.
+#[derive(Default)]
+struct AwarenessRelay {
+    /// Latest awareness states per document, replayed to peers that join later.
+    docs: HashMap<String, Awareness>,
+    /// Clients (and the document) already sent the states of the peers that
+    /// were there before them.
+    synced: HashSet<(ClientId, String)>,
+}
+
+/// Strip the open file and cursor from presence states whose file isn't
+/// `visible`, or `None` if every file is.
+fn redact_presence(
+    update: &AwarenessUpdate,
+    visible: impl Fn(&str) -> bool,
+) -> Option<AwarenessUpdate> {
+    let mut redacted = false;
+    let entries = update
+        .entries
+        .iter()
+        .map(|entry| {
+            let mut entry = entry.clone();
+            if let Some(state) = &entry.state
+                && let Ok(serde_json::Value::Object(mut fields)) = serde_json::from_str(state)
+                && fields
+                    .get("file")
+                    .is_some_and(|file| !file.as_str().is_some_and(&visible))
+            {
+                fields.remove("file");
+                fields.remove("cursor");
+                entry.state = Some(serde_json::Value::Object(fields).to_string());
+                redacted = true;
+            }
+            entry
+        })
+        .collect();
+    redacted.then_some(AwarenessUpdate { entries })
 }
 
 impl DiaryxHook {
@@ -106,6 +216,11 @@ impl DiaryxHook {
         repo: Arc<AuthRepo>,
         storage_cache: Arc<StorageCache>,
         session_to_workspace: Arc<RwLock<HashMap<String, String>>>,
+        stats: Arc<SyncStats>,
+        guests: Arc<GuestConnections>,
+        connections: Arc<SyncConnections>,
+        limits: &RateLimitConfig,
+        webhooks: Arc<WebhookDispatcher>,
     ) -> (Self, Arc<OnceLock<Handle>>) {
         let handle = Arc::new(OnceLock::new());
         let hook = Self {
@@ -113,11 +228,149 @@ impl DiaryxHook {
             storage_cache,
             handle: handle.clone(),
             session_to_workspace,
+            awareness: RwLock::new(AwarenessRelay::default()),
+            stats,
+            guests,
+            handshakes: RwLock::new(HashMap::new()),
+            max_message_bytes: limits.sync_max_message_bytes,
+            update_limiter: RateLimiter::new(
+                limits.sync_update_burst,
+                limits.sync_updates_per_second as f64,
+            ),
+            error_throttle: RateLimiter::new(1, 1.0),
+            pending_errors: std::sync::Mutex::new(HashMap::new()),
+            connections,
+            webhooks,
         };
         (hook, handle)
     }
 
-    /// Authenticate from JWT token.
+    /// Check a client message against the size and rate limits.
+    fn check_limits(&self, client_id: ClientId, len: usize) -> Result<(), SyncServerError> {
+        if self.max_message_bytes > 0 && len > self.max_message_bytes {
+            metrics().record_rate_limited("sync_size");
+            return Err(SyncServerError {
+                code: SyncErrorCode::MessageTooLarge,
+                message: format!(
+                    "Message of {} bytes exceeds the {} byte limit",
+                    len, self.max_message_bytes
+                ),
+                doc_id: None,
+                client: None,
+                retry_after_ms: None,
+            });
+        }
+        if let Err(retry_after) = self.update_limiter.check(&client_id.to_string()) {
+            metrics().record_rate_limited("sync_update");
+            return Err(SyncServerError {
+                code: SyncErrorCode::RateLimited,
+                message: "Too many updates, slow down".to_string(),
+                doc_id: None,
+                client: None,
+                retry_after_ms: Some(retry_after.as_millis().max(1) as u64),
+            });
+        }
+        Ok(())
+    }
+
+    /// Tell a client its update was refused.
+    ///
+    /// Siphonophore can only send text to a single connection in reply to a
+    /// control message from it, so the error is held until the connection's
+    /// next control message rather than broadcast to the room. Clients poll
+    /// with `check_errors` so it is not held for long. Only the latest error
+    /// is kept.
+    fn send_sync_error(
+        &self,
+        doc_id: &str,
+        client_id: ClientId,
+        user: Option<&AuthenticatedUser>,
+        mut error: SyncServerError,
+    ) {
+        if self.error_throttle.check(&client_id.to_string()).is_err() {
+            return;
+        }
+        error.doc_id = Some(doc_id.to_string());
+        error.client = user.and_then(|u| u.client_tag.clone());
+        self.pending_errors
+            .lock()
+            .unwrap()
+            .insert(client_id, encode_sync_error_control(&error));
+    }
+
+    /// Add a connection's held `sync_error`, if any, to the replies to its
+    /// control message.
+    fn with_pending_error(
+        &self,
+        client_id: ClientId,
+        response: ControlMessageResponse,
+    ) -> ControlMessageResponse {
+        let add = |mut responses: Vec<String>| {
+            if let Some(error) = self.pending_errors.lock().unwrap().remove(&client_id) {
+                responses.insert(0, error);
+            }
+            responses
+        };
+        match response {
+            ControlMessageResponse::Handled { responses } => ControlMessageResponse::Handled {
+                responses: add(responses),
+            },
+            ControlMessageResponse::CompleteHandshake { responses } => {
+                ControlMessageResponse::CompleteHandshake {
+                    responses: add(responses),
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Record who the socket `conn` (set by `tag_connection`) authenticated
+    /// as, so it can be closed when that access is taken away.
+    fn record_connection(&self, conn: Option<impl AsRef<str>>, user: &AuthenticatedUser) {
+        let Some(id) = conn.and_then(|conn| conn.as_ref().parse::<u64>().ok()) else {
+            warn!(
+                "Sync connection of {} has no connection ID; it can't be closed on access changes",
+                user.user_id
+            );
+            return;
+        };
+        let principal = match &user.session_code {
+            Some(code) => ConnectionPrincipal::Guest {
+                session_code: code.clone(),
+            },
+            None => ConnectionPrincipal::Member {
+                workspace_id: user.workspace_id.clone(),
+                user_id: user.user_id.clone(),
+            },
+        };
+        self.connections.authenticated(id, principal);
+    }
+
+    /// The document a connection actually reads and writes.
+    ///
+    /// Doc IDs naming a workspace (the legacy `default`) are rewritten to the
+    /// workspace ID the connection authenticated for.
+    fn resolve_doc(doc_id: &str, user: Option<&AuthenticatedUser>) -> Option<DocType> {
+        let doc_type = DocType::parse(doc_id)?;
+        match user {
+            Some(user) if user.workspace_id != doc_type.workspace_id() => {
+                Some(doc_type.in_workspace(&user.workspace_id))
+            }
+            _ => Some(doc_type),
+        }
+    }
+
+    /// Whether a doc ID names a workspace by ID. Hooks without a connection
+    /// (loading and saving rooms) never touch storage for anything else, so a
+    /// room named after a workspace name can't read or write a shared store.
+    fn is_workspace_id(&self, doc_type: &DocType) -> bool {
+        matches!(
+            self.repo.get_workspace(doc_type.workspace_id()),
+            Ok(Some(_))
+        )
+    }
+
+    /// Authenticate from a session token or personal access token.
     fn authenticate_token(
         &self,
         token: &str,
@@ -127,23 +380,53 @@ impl DiaryxHook {
 
         let workspace_id = doc_type.workspace_id();
 
-        // Verify user has access to this workspace
+        // Verify user owns this workspace (by ID or name) or is a member of it
         let workspaces = self
             .repo
             .get_user_workspaces(&auth.user.id)
             .unwrap_or_default();
 
-        let has_access = workspaces
+        let owned = workspaces
             .iter()
-            .any(|w| w.id == workspace_id || w.name == workspace_id);
+            .find(|w| w.id == workspace_id || w.name == workspace_id);
 
-        // Allow access if user owns the workspace, or get/create default
-        let workspace_id = if !has_access {
-            self.repo
-                .get_or_create_workspace(&auth.user.id, "default")
-                .map_err(|e| format!("Failed to get/create workspace: {}", e))?
+        let role = if owned.is_some() {
+            Some(WorkspaceRole::Owner)
         } else {
-            workspace_id.to_string()
+            self.repo
+                .get_workspace_role(workspace_id, &auth.user.id)
+                .unwrap_or_default()
+        };
+
+        // Personal access tokens are limited to their workspace and permission,
+        // and never fall back to the default workspace
+        let mut token_read_only = false;
+        if let Some(token) = &auth.api_token {
+            let resolved_id = owned.map_or(workspace_id, |w| w.id.as_str());
+            if role.is_none() || !token.covers_workspace(resolved_id) {
+                return Err("API token does not grant access to this workspace".to_string());
+            }
+            if !token.permission.can_sync() {
+                return Err("API token does not allow sync".to_string());
+            }
+            token_read_only = !token.permission.can_write();
+        }
+
+        // Allow access if user owns or is a member of the workspace. Only the
+        // legacy literal name falls back to (and creates) their own default
+        // workspace; any other workspace needs a membership.
+        let (workspace_id, role) = match role {
+            Some(role) => (
+                owned.map_or(workspace_id, |w| w.id.as_str()).to_string(),
+                role,
+            ),
+            None if workspace_id == LEGACY_DEFAULT_WORKSPACE => (
+                self.repo
+                    .get_or_create_workspace(&auth.user.id, LEGACY_DEFAULT_WORKSPACE)
+                    .map_err(|e| format!("Failed to get/create workspace: {}", e))?,
+                WorkspaceRole::Owner,
+            ),
+            None => return Err("Not a member of this workspace".to_string()),
         };
 
         Ok(AuthenticatedUser {
@@ -151,7 +434,9 @@ impl DiaryxHook {
             workspace_id,
             device_id: Some(auth.session.device_id),
             is_guest: false,
-            read_only: false,
+            read_only: token_read_only || !role.can_write(),
+            session_code: None,
+            client_tag: None,
         })
     }
 
@@ -175,14 +460,437 @@ impl DiaryxHook {
             return Err("Document does not belong to session workspace".to_string());
         }
 
+        // Scoped sessions only expose the body docs inside the scope
+        let mut read_only = session.read_only;
+        if let (Some(scope), DocType::Body { path, .. }) = (&session.scope, doc_type) {
+            let workspace = self.load_workspace(&session.workspace_id)?;
+            match scope.resolve(&workspace).permission(path) {
+                Some(SharePermission::Write) => {}
+                Some(SharePermission::Read) => read_only = true,
+                None => return Err("Document is outside the session scope".to_string()),
+            }
+        }
+
         Ok(AuthenticatedUser {
             user_id: format!("guest:{}", guest_id),
             workspace_id: session.workspace_id,
             device_id: None,
             is_guest: true,
-            read_only: session.read_only,
+            read_only,
+            session_code: Some(session_code),
+            client_tag: None,
         })
     }
+
+    /// Current scope of a guest's share session (`None` = whole workspace).
+    fn guest_scope(&self, client_id: ClientId, user: &AuthenticatedUser) -> Option<ShareScope> {
+        if !user.is_guest {
+            return None;
+        }
+        let code = user.session_code.as_ref()?;
+        self.guests.scope(
+            client_id,
+            code,
+            &user.workspace_id,
+            user.client_tag.as_deref(),
+            |code| self.repo.get_share_session(code).ok().flatten()?.scope,
+        )
+    }
+
+    /// A guest's scope resolved against the workspace tree, or `None` if the
+    /// connection sees the whole workspace.
+    fn resolved_scope(
+        &self,
+        client_id: ClientId,
+        user: &AuthenticatedUser,
+    ) -> Result<Option<Arc<ResolvedScope>>, String> {
+        match self.guest_scope(client_id, user) {
+            Some(scope) => self
+                .resolve_cached(client_id, &user.workspace_id, &scope)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolve a guest connection's scope, reusing the cached resolution
+    /// until the scope or the workspace tree changes.
+    fn resolve_cached(
+        &self,
+        client_id: ClientId,
+        workspace_id: &str,
+        scope: &ShareScope,
+    ) -> Result<Arc<ResolvedScope>, String> {
+        if let Some(resolved) = self.guests.resolved(client_id) {
+            return Ok(resolved);
+        }
+        let workspace = self.load_workspace(workspace_id)?;
+        let resolved = Arc::new(scope.resolve(&workspace));
+        self.guests.set_resolved(client_id, scope, resolved.clone());
+        Ok(resolved)
+    }
+
+    /// Whether a connection may read a document of its workspace: always,
+    /// unless it's a scoped guest and the file is outside the scope.
+    fn can_read(&self, client_id: ClientId, user: &AuthenticatedUser, doc_type: &DocType) -> bool {
+        match doc_type {
+            DocType::Body { path, .. } => self.can_read_file(client_id, user, path),
+            DocType::Workspace(_) => true,
+        }
+    }
+
+    /// Whether a connection may see a file of its workspace.
+    fn can_read_file(&self, client_id: ClientId, user: &AuthenticatedUser, path: &str) -> bool {
+        match self.resolved_scope(client_id, user) {
+            Ok(Some(resolved)) => resolved.can_read(path),
+            Ok(None) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// Load the persisted workspace CRDT to resolve scopes against.
+    fn load_workspace(&self, workspace_id: &str) -> Result<WorkspaceCrdt, String> {
+        let storage = self.storage_cache.get_storage(workspace_id)?;
+        WorkspaceCrdt::load_with_name(storage, format!("workspace:{}", workspace_id))
+            .map_err(|e| format!("Failed to load workspace: {}", e))
+    }
+
+    /// Files an update to a document changes, for webhooks.
+    fn changed_files(&self, doc_type: &DocType, update: &[u8]) -> Option<Vec<FileChange>> {
+        match doc_type {
+            DocType::Body { path, .. } => {
+                Some(vec![FileChange::new(path.clone(), ChangeKind::Updated)])
+            }
+            DocType::Workspace(workspace_id) => {
+                let changes = self.load_workspace(workspace_id).and_then(|workspace| {
+                    workspace_changes(&workspace, update).map_err(|e| e.to_string())
+                });
+                match changes {
+                    Ok(changes) => Some(changes),
+                    Err(e) => {
+                        warn!(
+                            "Failed to track changes for webhooks on {}: {}",
+                            workspace_id, e
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check that a connection may make this change: read-only connections
+    /// (viewers, read tokens and read-scoped guest paths) may not write at
+    /// all, and guests only within their scope.
+    fn check_write(
+        &self,
+        client_id: ClientId,
+        user: &AuthenticatedUser,
+        doc_type: &DocType,
+        update: &[u8],
+    ) -> Result<(), String> {
+        if user.read_only {
+            return Err(format!(
+                "{} is read-only for this connection",
+                doc_type.storage_key()
+            ));
+        }
+        self.check_scope(client_id, user, doc_type, update)
+    }
+
+    /// Check that a guest update only touches files they may write.
+    fn check_scope(
+        &self,
+        client_id: ClientId,
+        user: &AuthenticatedUser,
+        doc_type: &DocType,
+        update: &[u8],
+    ) -> Result<(), String> {
+        let Some(scope) = self.guest_scope(client_id, user) else {
+            return Ok(());
+        };
+        match doc_type {
+            DocType::Body { path, .. } => {
+                let resolved = self.resolve_cached(client_id, &user.workspace_id, &scope)?;
+                if resolved.can_write(path) {
+                    Ok(())
+                } else {
+                    Err(format!("{} is outside the writable share scope", path))
+                }
+            }
+            DocType::Workspace(workspace_id) => scope
+                .check_workspace_update(&self.load_workspace(workspace_id)?, update)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Note that a client was sent its file manifest and the handshake started.
+    async fn start_handshake(&self, client_id: ClientId, doc_id: &str) {
+        self.handshakes
+            .write()
+            .await
+            .insert((client_id, doc_id.to_string()), Instant::now());
+    }
+
+    /// Record handshake latency the first time a client answers with `files_ready`.
+    async fn finish_handshake(&self, client_id: ClientId, doc_id: &str) {
+        let started = self
+            .handshakes
+            .write()
+            .await
+            .remove(&(client_id, doc_id.to_string()));
+        if let Some(started) = started {
+            metrics().observe_handshake(started.elapsed());
+        }
+    }
+
+    /// Filtered `crdt_state` message for a scoped guest, or `None` if the
+    /// sender sees the whole workspace.
+    fn scoped_crdt_state(&self, payload: &OnControlMessagePayload<'_>) -> Option<String> {
+        let user = payload.context.get::<AuthenticatedUser>()?;
+        let resolved = self.resolved_scope(payload.client_id, user).transpose()?;
+
+        let state = resolved
+            .and_then(|resolved| {
+                let workspace = self.load_workspace(&user.workspace_id)?;
+                resolved
+                    .filter_workspace(&workspace)
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap_or_else(|e| {
+                // Never fall back to the unfiltered state
+                error!("Failed to filter workspace for scoped guest: {}", e);
+                Vec::new()
+            });
+
+        info!(
+            "Sending scoped CRDT state ({} bytes) to {}",
+            state.len(),
+            user.user_id
+        );
+        let state_b64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &state);
+        Some(
+            serde_json::json!({
+                "type": "crdt_state",
+                "state": state_b64
+            })
+            .to_string(),
+        )
+    }
+
+    /// Relay an awareness update to the peers of its document, returning the
+    /// replies for the sender: everyone else's states, on its first update.
+    ///
+    /// Updates are only accepted from authenticated connections, for open
+    /// documents of their own workspace that they may read.
+    async fn relay_awareness(
+        &self,
+        client_id: ClientId,
+        own_doc: Option<&str>,
+        user: Option<&AuthenticatedUser>,
+        message: &str,
+    ) -> Vec<String> {
+        let Some((doc_id, update)) = decode_awareness_control(message) else {
+            warn!("Ignoring malformed awareness message");
+            return Vec::new();
+        };
+
+        let allowed = match (user, DocType::parse(&doc_id)) {
+            (Some(user), Some(target)) => {
+                own_doc
+                    .and_then(|own| Self::resolve_doc(own, Some(user)))
+                    .is_some()
+                    && target.workspace_id() == user.workspace_id
+                    && self.can_read(client_id, user, &target)
+            }
+            _ => false,
+        };
+        if !allowed || !self.stats.is_open(&doc_id) {
+            warn!("Rejecting awareness update for foreign document {}", doc_id);
+            return Vec::new();
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let snapshot = {
+            let mut relay = self.awareness.write().await;
+            let key = (client_id, doc_id.clone());
+            let synced = relay.synced.contains(&key);
+            let states = relay
+                .docs
+                .entry(doc_id.clone())
+                .or_insert_with(|| Awareness::with_client_id(RELAY_AWARENESS_CLIENT_ID));
+            states.remove_outdated(now);
+
+            let known: HashSet<u64> = states.peers().iter().map(|p| p.client_id).collect();
+            let added = update
+                .entries
+                .iter()
+                .filter(|e| e.state.is_some() && !known.contains(&e.client_id))
+                .count();
+            if known.len() + added > MAX_AWARENESS_STATES {
+                warn!("Dropping awareness update for full document {}", doc_id);
+                return Vec::new();
+            }
+
+            let snapshot = (!synced)
+                .then(|| states.full_update())
+                .filter(|update| !update.entries.is_empty());
+            states.apply_update(&update, now);
+            relay.synced.insert(key);
+            snapshot
+        };
+
+        // Scoped guests don't learn which files outside their scope others
+        // have open: the sender's snapshot is redacted for its own scope, and
+        // the broadcast for every scoped guest in the room.
+        let snapshot = snapshot.map(|states| {
+            let visible = |file: &str| user.is_some_and(|u| self.can_read_file(client_id, u, file));
+            let states = redact_presence(&states, visible).unwrap_or(states);
+            encode_awareness_control(&doc_id, &states)
+        });
+        let scoped: Vec<_> = self
+            .guests
+            .scoped(&self.stats.peers(&doc_id))
+            .into_iter()
+            .map(|(guest, workspace_id, scope)| {
+                self.resolve_cached(guest, &workspace_id, &scope).ok()
+            })
+            .collect();
+        let visible = |file: &str| {
+            scoped
+                .iter()
+                .all(|r| r.as_ref().is_some_and(|r| r.can_read(file)))
+        };
+        let message = match redact_presence(&update, visible) {
+            Some(redacted) => encode_awareness_control(&doc_id, &redacted),
+            None => message.to_string(),
+        };
+
+        // Senders ignore their own entries, so no need to exclude them
+        if let Some(handle) = self.handle.get() {
+            handle.broadcast_text(&doc_id, message, None).await;
+        }
+        snapshot.into_iter().collect()
+    }
+
+    /// Reply to a control message (FilesReady, focus, awareness, etc.).
+    async fn handle_control_message(
+        &self,
+        payload: OnControlMessagePayload<'_>,
+    ) -> ControlMessageResponse {
+        let message = payload.message;
+
+        if self.max_message_bytes > 0 && message.len() > self.max_message_bytes {
+            warn!("Refusing {} byte control message", message.len());
+            metrics().record_rate_limited("sync_size");
+            let error = SyncServerError {
+                code: SyncErrorCode::MessageTooLarge,
+                message: format!(
+                    "Message of {} bytes exceeds the {} byte limit",
+                    message.len(),
+                    self.max_message_bytes
+                ),
+                doc_id: payload.doc_id.map(str::to_string),
+                client: None,
+                retry_after_ms: None,
+            };
+            return ControlMessageResponse::Handled {
+                responses: vec![encode_sync_error_control(&error)],
+            };
+        }
+
+        // Try to parse as JSON
+        let json: serde_json::Value = match serde_json::from_str(message) {
+            Ok(v) => v,
+            Err(_) => return ControlMessageResponse::NotHandled,
+        };
+
+        let msg_type = json.get("type").and_then(|v| v.as_str());
+
+        match msg_type {
+            Some("files_ready") | Some("FilesReady") => {
+                debug!("Received FilesReady from client");
+                if let Some(doc_id) = payload.doc_id {
+                    self.finish_handshake(payload.client_id, doc_id).await;
+                }
+
+                // Scoped guests get a filtered snapshot instead of the full doc.
+                // The handshake stays open so y-sync never sends them the rest;
+                // they re-send files_ready to refresh (e.g. on scope_changed).
+                if let Some(response) = self.scoped_crdt_state(&payload) {
+                    return ControlMessageResponse::Handled {
+                        responses: vec![response],
+                    };
+                }
+
+                // Get workspace state to send as CrdtState
+                let user = payload.context.get::<AuthenticatedUser>();
+                if let Some(doc_id) = payload.doc_id
+                    && let Some(DocType::Workspace(workspace_id)) = Self::resolve_doc(doc_id, user)
+                    && let Ok(storage) = self.storage_cache.get_storage(&workspace_id)
+                    && let Ok(Some(state)) =
+                        storage.load_doc(&format!("workspace:{}", workspace_id))
+                {
+                    let state_b64 =
+                        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &state);
+                    let crdt_state = serde_json::json!({
+                        "type": "crdt_state",
+                        "state": state_b64
+                    });
+                    info!(
+                        "Completing handshake with CRDT state ({} bytes)",
+                        state.len()
+                    );
+                    return ControlMessageResponse::CompleteHandshake {
+                        responses: vec![crdt_state.to_string()],
+                    };
+                }
+
+                // No state to send, just complete handshake
+                ControlMessageResponse::CompleteHandshake { responses: vec![] }
+            }
+            Some("focus") => {
+                // Handle focus message (for now just log it)
+                if let Some(files) = json.get("files").and_then(|v| v.as_array()) {
+                    debug!("Client focusing on {} files", files.len());
+                }
+                ControlMessageResponse::Handled { responses: vec![] }
+            }
+            Some("unfocus") => {
+                // Handle unfocus message
+                if let Some(files) = json.get("files").and_then(|v| v.as_array()) {
+                    debug!("Client unfocusing {} files", files.len());
+                }
+                ControlMessageResponse::Handled { responses: vec![] }
+            }
+            Some("awareness") => {
+                // Awareness shares the update budget; handshake messages never do
+                if let Err(mut error) = self.check_limits(payload.client_id, 0) {
+                    if self
+                        .error_throttle
+                        .check(&payload.client_id.to_string())
+                        .is_err()
+                    {
+                        return ControlMessageResponse::Handled { responses: vec![] };
+                    }
+                    error.doc_id = payload.doc_id.map(str::to_string);
+                    return ControlMessageResponse::Handled {
+                        responses: vec![encode_sync_error_control(&error)],
+                    };
+                }
+
+                let user = payload.context.get::<AuthenticatedUser>();
+                let responses = self
+                    .relay_awareness(payload.client_id, payload.doc_id, user, message)
+                    .await;
+                ControlMessageResponse::Handled { responses }
+            }
+            Some("check_errors") => {
+                // Nothing to do: `on_control_message` adds any held error
+                ControlMessageResponse::Handled { responses: vec![] }
+            }
+            _ => ControlMessageResponse::NotHandled,
+        }
+    }
 }
 
 #[async_trait]
@@ -206,10 +914,14 @@ impl Hook for DiaryxHook {
             .ok_or_else(|| format!("Invalid document ID format: {}", doc_id))?;
 
         // Try JWT token first
+        let client_tag = request.query_params.get("client").cloned();
+
         if let Some(token) = &request.token {
             match self.authenticate_token(token, &doc_type) {
-                Ok(user) => {
+                Ok(mut user) => {
+                    user.client_tag = client_tag;
                     info!("Authenticated user {} for doc {}", user.user_id, doc_id);
+                    self.record_connection(request.query_params.get(CONNECTION_PARAM), &user);
                     payload.context.insert(user);
                     return Ok(());
                 }
@@ -228,7 +940,8 @@ impl Hook for DiaryxHook {
                 .unwrap_or_else(|| format!("guest-{}", uuid::Uuid::new_v4()));
 
             match self.authenticate_session(session_code, &guest_id, &doc_type) {
-                Ok(user) => {
+                Ok(mut user) => {
+                    user.client_tag = client_tag;
                     info!(
                         "Authenticated guest {} for session {} doc {}",
                         guest_id, session_code, doc_id
@@ -238,11 +951,13 @@ impl Hook for DiaryxHook {
                         .write()
                         .await
                         .insert(session_code.to_uppercase(), user.workspace_id.clone());
+                    self.record_connection(request.query_params.get(CONNECTION_PARAM), &user);
                     payload.context.insert(user);
                     return Ok(());
                 }
                 Err(e) => {
                     warn!("Session auth failed for {}: {}", session_code, e);
+                    metrics().record_auth_failure(AuthFailure::Sync);
                     return Err(e.into());
                 }
             }
@@ -250,6 +965,7 @@ impl Hook for DiaryxHook {
 
         // No valid auth method
         warn!("No valid authentication for document: {}", doc_id);
+        metrics().record_auth_failure(AuthFailure::Sync);
         Err("Authentication required".into())
     }
 
@@ -270,6 +986,13 @@ impl Hook for DiaryxHook {
             }
         };
 
+        // Rooms named after a workspace name start empty; connections get
+        // their own workspace's state from the handshake instead
+        if !self.is_workspace_id(&doc_type) {
+            debug!("Not loading {}: not a workspace ID", doc_id);
+            return Ok(None);
+        }
+
         // Get storage for this workspace
         let storage = match self.storage_cache.get_storage(doc_type.workspace_id()) {
             Ok(s) => s,
@@ -311,31 +1034,42 @@ impl Hook for DiaryxHook {
             payload.client_id
         );
 
+        // Get user info from context
+        let user = payload.context.get::<AuthenticatedUser>();
+
         // Parse document type
-        let doc_type = match DocType::parse(doc_id) {
+        let doc_type = match Self::resolve_doc(doc_id, user) {
             Some(dt) => dt,
             None => {
                 warn!("Invalid document ID on change: {}", doc_id);
                 return Ok(());
             }
         };
-
-        // Get user info from context
-        let user = payload.context.get::<AuthenticatedUser>();
         let (device_id, device_name) = match user {
             Some(u) => (u.device_id.as_deref(), None),
             None => (None, None),
         };
 
-        // Check read-only mode
-        if let Some(u) = user {
-            if u.read_only {
-                debug!(
-                    "Ignoring change from read-only user {} on {}",
-                    u.user_id, doc_id
-                );
-                return Ok(());
-            }
+        // Refuse oversized updates and connections sending too fast. The
+        // update isn't lost: the client re-sends it on the next handshake.
+        if let Err(e) = self.check_limits(payload.client_id, update.len()) {
+            warn!(
+                "Refusing update from client {:?} on {}: {}",
+                payload.client_id, doc_id, e.message
+            );
+            let message = e.message.clone();
+            self.send_sync_error(doc_id, payload.client_id, user, e);
+            return Err(message.into());
+        }
+
+        // Reject changes from read-only connections and outside a scoped
+        // guest's writable paths. Only an error keeps the update out of the
+        // room, so it isn't relayed or saved.
+        if let Some(u) = user
+            && let Err(e) = self.check_write(payload.client_id, u, &doc_type, update)
+        {
+            warn!("Rejecting change from {} on {}: {}", u.user_id, doc_id, e);
+            return Err(e.into());
         }
 
         // Get storage
@@ -347,6 +1081,14 @@ impl Hook for DiaryxHook {
             }
         };
 
+        // Work out which files changed while storage still holds the state
+        // before this update, but only if the workspace has webhooks
+        let webhook_changes = if self.webhooks.watches(doc_type.workspace_id()) {
+            self.changed_files(&doc_type, update)
+        } else {
+            None
+        };
+
         // Append update
         let storage_key = doc_type.storage_key();
         if let Err(e) = storage.append_update_with_device(
@@ -359,6 +1101,19 @@ impl Hook for DiaryxHook {
             error!("Failed to persist update for {}: {}", doc_id, e);
         } else {
             debug!("Persisted {} byte update for {}", update.len(), doc_id);
+            metrics().record_update(doc_type.workspace_id(), update.len());
+            if let DocType::Workspace(workspace_id) = &doc_type {
+                self.guests.workspace_changed(workspace_id);
+            }
+            if let Some(changes) = webhook_changes {
+                self.webhooks.notify(doc_type.workspace_id(), changes);
+            }
+        }
+
+        // A room named after the legacy workspace name is shared by everyone
+        // using it, so the update is kept out of it once it's persisted
+        if DocType::parse(doc_id).is_some_and(|d| d.workspace_id() != doc_type.workspace_id()) {
+            return Err("Legacy workspace documents are not relayed; sync by workspace ID".into());
         }
 
         Ok(())
@@ -370,6 +1125,10 @@ impl Hook for DiaryxHook {
             "Client {:?} disconnected from document: {}",
             payload.client_id, payload.doc_id
         );
+        self.handshakes
+            .write()
+            .await
+            .remove(&(payload.client_id, payload.doc_id.to_string()));
         Ok(())
     }
 
@@ -388,6 +1147,9 @@ impl Hook for DiaryxHook {
                 return Ok(());
             }
         };
+        if !self.is_workspace_id(&doc_type) {
+            return Ok(());
+        }
 
         // Get storage
         let storage = match self.storage_cache.get_storage(doc_type.workspace_id()) {
@@ -424,6 +1186,9 @@ impl Hook for DiaryxHook {
                 return Ok(());
             }
         };
+        if !self.is_workspace_id(&doc_type) {
+            return Ok(());
+        }
 
         // Get storage
         let storage = match self.storage_cache.get_storage(doc_type.workspace_id()) {
@@ -457,7 +1222,8 @@ impl Hook for DiaryxHook {
         let doc_id = payload.doc_id;
 
         // Parse document type
-        let doc_type = match DocType::parse(doc_id) {
+        let user = payload.context.get::<AuthenticatedUser>();
+        let doc_type = match Self::resolve_doc(doc_id, user) {
             Some(dt) => dt,
             None => {
                 return Ok(BeforeSyncAction::Abort {
@@ -473,9 +1239,11 @@ impl Hook for DiaryxHook {
 
         let mut messages = Vec::new();
 
+        let scope = user.and_then(|u| self.guest_scope(payload.client_id, u));
+
         // For session guests, send session_joined confirmation before anything else.
         // This is a unicast to the connecting client (on_before_sync runs per-client).
-        if let Some(user) = payload.context.get::<AuthenticatedUser>() {
+        if let Some(user) = user {
             if user.is_guest {
                 if let Some(session_code) = payload.request.query_params.get("session") {
                     let session_joined = serde_json::json!({
@@ -483,6 +1251,7 @@ impl Hook for DiaryxHook {
                         "joinCode": session_code.to_uppercase(),
                         "workspaceId": user.workspace_id,
                         "readOnly": user.read_only,
+                        "scope": scope,
                     });
                     messages.push(session_joined.to_string());
                     info!(
@@ -509,12 +1278,13 @@ impl Hook for DiaryxHook {
                     "client_is_new": false
                 });
                 messages.push(manifest.to_string());
+                self.start_handshake(payload.client_id, doc_id).await;
                 return Ok(BeforeSyncAction::SendMessages { messages });
             }
         };
 
         // Query active files
-        let files = match storage.query_active_files() {
+        let mut files = match storage.query_active_files() {
             Ok(f) => f,
             Err(e) => {
                 warn!("Failed to query files for manifest: {}", e);
@@ -527,10 +1297,22 @@ impl Hook for DiaryxHook {
                     "client_is_new": false
                 });
                 messages.push(manifest.to_string());
+                self.start_handshake(payload.client_id, doc_id).await;
                 return Ok(BeforeSyncAction::SendMessages { messages });
             }
         };
 
+        // Scoped guests only learn about the files they can read
+        if let Some(scope) = &scope {
+            match self.resolve_cached(payload.client_id, doc_type.workspace_id(), scope) {
+                Ok(resolved) => files.retain(|(path, _, _)| resolved.can_read(path)),
+                Err(e) => {
+                    warn!("Failed to resolve share scope for manifest: {}", e);
+                    files.clear();
+                }
+            }
+        }
+
         // If no files and no session messages, skip handshake
         if files.is_empty() && messages.is_empty() {
             debug!("No files in workspace, skipping Files-Ready handshake");
@@ -566,6 +1348,7 @@ impl Hook for DiaryxHook {
             messages.push(manifest.to_string());
         }
 
+        self.start_handshake(payload.client_id, doc_id).await;
         Ok(BeforeSyncAction::SendMessages { messages })
     }
 
@@ -574,65 +1357,9 @@ impl Hook for DiaryxHook {
         &self,
         payload: OnControlMessagePayload<'_>,
     ) -> ControlMessageResponse {
-        let message = payload.message;
-
-        // Try to parse as JSON
-        let json: serde_json::Value = match serde_json::from_str(message) {
-            Ok(v) => v,
-            Err(_) => return ControlMessageResponse::NotHandled,
-        };
-
-        let msg_type = json.get("type").and_then(|v| v.as_str());
-
-        match msg_type {
-            Some("files_ready") | Some("FilesReady") => {
-                debug!("Received FilesReady from client");
-
-                // Get workspace state to send as CrdtState
-                if let Some(doc_id) = payload.doc_id {
-                    if let Some(DocType::Workspace(workspace_id)) = DocType::parse(doc_id) {
-                        if let Ok(storage) = self.storage_cache.get_storage(&workspace_id) {
-                            let storage_key = format!("workspace:{}", workspace_id);
-                            if let Ok(Some(state)) = storage.load_doc(&storage_key) {
-                                let state_b64 = base64::Engine::encode(
-                                    &base64::engine::general_purpose::STANDARD,
-                                    &state,
-                                );
-                                let crdt_state = serde_json::json!({
-                                    "type": "crdt_state",
-                                    "state": state_b64
-                                });
-                                info!(
-                                    "Completing handshake with CRDT state ({} bytes)",
-                                    state.len()
-                                );
-                                return ControlMessageResponse::CompleteHandshake {
-                                    responses: vec![crdt_state.to_string()],
-                                };
-                            }
-                        }
-                    }
-                }
-
-                // No state to send, just complete handshake
-                ControlMessageResponse::CompleteHandshake { responses: vec![] }
-            }
-            Some("focus") => {
-                // Handle focus message (for now just log it)
-                if let Some(files) = json.get("files").and_then(|v| v.as_array()) {
-                    debug!("Client focusing on {} files", files.len());
-                }
-                ControlMessageResponse::Handled { responses: vec![] }
-            }
-            Some("unfocus") => {
-                // Handle unfocus message
-                if let Some(files) = json.get("files").and_then(|v| v.as_array()) {
-                    debug!("Client unfocusing {} files", files.len());
-                }
-                ControlMessageResponse::Handled { responses: vec![] }
-            }
-            _ => ControlMessageResponse::NotHandled,
-        }
+        let client_id = payload.client_id;
+        let response = self.handle_control_message(payload).await;
+        self.with_pending_error(client_id, response)
     }
 
     /// Called when a peer joins a document.
@@ -644,6 +1371,11 @@ impl Hook for DiaryxHook {
             "Peer {} joined document {} (total: {})",
             user_id, payload.doc_id, payload.peer_count
         );
+        self.stats.peer_joined(
+            payload.doc_id,
+            payload.client_id,
+            user.filter(|u| !u.is_guest).map(|u| u.user_id.as_str()),
+        );
 
         if let Some(handle) = self.handle.get() {
             let msg = serde_json::json!({
@@ -667,6 +1399,27 @@ impl Hook for DiaryxHook {
             "Peer {} left document {} (remaining: {})",
             user_id, payload.doc_id, payload.peer_count
         );
+        self.stats.peer_left(payload.doc_id, payload.client_id);
+        if !self.stats.is_connected(payload.client_id) {
+            self.guests.remove(payload.client_id);
+            self.pending_errors
+                .lock()
+                .unwrap()
+                .remove(&payload.client_id);
+        }
+
+        // Departed peers without a goodbye update time out on the clients;
+        // once nobody is left the cached states are just stale.
+        {
+            let mut relay = self.awareness.write().await;
+            relay
+                .synced
+                .remove(&(payload.client_id, payload.doc_id.to_string()));
+            if payload.peer_count == 0 {
+                relay.docs.remove(payload.doc_id);
+                relay.synced.retain(|(_, doc_id)| doc_id != payload.doc_id);
+            }
+        }
 
         if let Some(handle) = self.handle.get() {
             let msg = serde_json::json!({
@@ -685,6 +1438,419 @@ impl Hook for DiaryxHook {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
+
+    /// Hook over an in-memory database and a temporary storage directory
+    fn test_hook(dir: &Path) -> (DiaryxHook, Arc<AuthRepo>) {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::init_database(&conn).unwrap();
+        let repo = Arc::new(AuthRepo::new(conn));
+        let (hook, _) = DiaryxHook::new(
+            repo.clone(),
+            Arc::new(StorageCache::new(dir.to_path_buf())),
+            Arc::new(RwLock::new(HashMap::new())),
+            Arc::new(SyncStats::new()),
+            Arc::new(GuestConnections::new()),
+            Arc::new(SyncConnections::new()),
+            &RateLimitConfig::default(),
+            Arc::new(WebhookDispatcher::new(repo.clone(), Default::default()).unwrap()),
+        );
+        (hook, repo)
+    }
+
+    /// Create a user with a session, returning their ID and session token
+    fn login(repo: &AuthRepo, email: &str) -> (String, String) {
+        let user_id = repo.get_or_create_user(email).unwrap();
+        let device_id = repo.create_device(&user_id, None, None).unwrap();
+        let expires = chrono::Utc::now() + chrono::Duration::hours(1);
+        let token = repo.create_session(&user_id, &device_id, expires).unwrap();
+        (user_id, token)
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_token_requires_membership() {
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, owner_token) = login(&repo, "owner@example.com");
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let (member, member_token) = login(&repo, "member@example.com");
+        let (_, stranger_token) = login(&repo, "stranger@example.com");
+        let doc = DocType::Workspace(workspace_id.clone());
+        let body = DocType::Body {
+            workspace_id: workspace_id.clone(),
+            path: "a.md".to_string(),
+        };
+
+        let user = hook.authenticate_token(&owner_token, &doc).unwrap();
+        assert_eq!(user.workspace_id, workspace_id);
+
+        // Non-members don't get in, even with a valid session
+        assert_eq!(
+            hook.authenticate_token(&stranger_token, &doc).unwrap_err(),
+            "Not a member of this workspace"
+        );
+        assert!(hook.authenticate_token(&stranger_token, &body).is_err());
+
+        // Members get in until they're removed
+        repo.add_workspace_member(&workspace_id, &member, WorkspaceRole::Editor)
+            .unwrap();
+        assert!(
+            !hook
+                .authenticate_token(&member_token, &body)
+                .unwrap()
+                .read_only
+        );
+        repo.remove_workspace_member(&workspace_id, &member)
+            .unwrap();
+        assert_eq!(
+            hook.authenticate_token(&member_token, &body).unwrap_err(),
+            "Not a member of this workspace"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_viewer_updates_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, _) = login(&repo, "owner@example.com");
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let (viewer, viewer_token) = login(&repo, "viewer@example.com");
+        let (editor, editor_token) = login(&repo, "editor@example.com");
+        repo.add_workspace_member(&workspace_id, &viewer, WorkspaceRole::Viewer)
+            .unwrap();
+        repo.add_workspace_member(&workspace_id, &editor, WorkspaceRole::Editor)
+            .unwrap();
+        let body = DocType::Body {
+            workspace_id: workspace_id.clone(),
+            path: "a.md".to_string(),
+        };
+
+        for doc in [DocType::Workspace(workspace_id.clone()), body] {
+            let user = hook.authenticate_token(&viewer_token, &doc).unwrap();
+            assert!(hook.check_write(1, &user, &doc, &[]).is_err());
+            let user = hook.authenticate_token(&editor_token, &doc).unwrap();
+            assert!(hook.check_write(2, &user, &doc, &[]).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_guest_updates_to_read_scoped_paths_refused() {
+        use diaryx_core::crdt::{FileMetadata, ScopeEntry};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, _) = login(&repo, "owner@example.com");
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let storage = hook.storage_cache.get_storage(&workspace_id).unwrap();
+        let workspace =
+            WorkspaceCrdt::load_with_name(storage, format!("workspace:{}", workspace_id)).unwrap();
+        for path in ["read.md", "write.md"] {
+            workspace
+                .set_file(path, FileMetadata::new(Some(path.to_string())))
+                .unwrap();
+        }
+        let code = repo
+            .create_share_session(&workspace_id, &owner, false, None)
+            .unwrap();
+        let entry = |path: &str, permission| ScopeEntry {
+            path: path.to_string(),
+            permission,
+            include_contents: false,
+        };
+        let scope = ShareScope {
+            entries: vec![
+                entry("read.md", SharePermission::Read),
+                entry("write.md", SharePermission::Write),
+            ],
+        };
+        repo.set_share_session_scope(&code, Some(&scope)).unwrap();
+
+        let body = |path: &str| DocType::Body {
+            workspace_id: workspace_id.clone(),
+            path: path.to_string(),
+        };
+        let read = body("read.md");
+        let guest = hook.authenticate_session(&code, "g1", &read).unwrap();
+        assert!(guest.read_only);
+        assert!(hook.check_write(1, &guest, &read, &[]).is_err());
+
+        let write = body("write.md");
+        let guest = hook.authenticate_session(&code, "g1", &write).unwrap();
+        assert!(hook.check_write(2, &guest, &write, &[]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_read_token_updates_refused() {
+        use crate::db::ApiTokenPermission;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, _) = login(&repo, "owner@example.com");
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let doc = DocType::Body {
+            workspace_id: workspace_id.clone(),
+            path: "a.md".to_string(),
+        };
+
+        for (permission, writable) in [
+            (ApiTokenPermission::Read, false),
+            (ApiTokenPermission::Write, true),
+        ] {
+            let (_, token) = repo
+                .create_api_token(&owner, "sync", Some(&workspace_id), permission, None)
+                .unwrap();
+            let user = hook.authenticate_token(&token, &doc).unwrap();
+            assert_eq!(hook.check_write(1, &user, &doc, &[]).is_ok(), writable);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_errors_go_to_the_refused_connection_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, _) = test_hook(dir.path());
+        let error = SyncServerError {
+            code: SyncErrorCode::RateLimited,
+            message: "Too many updates, slow down".to_string(),
+            doc_id: None,
+            client: None,
+            retry_after_ms: Some(1000),
+        };
+        hook.send_sync_error("workspace:ws", 1, None, error);
+
+        let handled = |responses: Vec<String>| ControlMessageResponse::Handled { responses };
+        let ControlMessageResponse::Handled { responses } =
+            hook.with_pending_error(2, handled(vec![]))
+        else {
+            panic!("expected a handled reply");
+        };
+        assert!(responses.is_empty());
+
+        let ControlMessageResponse::Handled { responses } =
+            hook.with_pending_error(1, handled(vec!["reply".to_string()]))
+        else {
+            panic!("expected a handled reply");
+        };
+        assert_eq!(responses.len(), 2);
+        assert!(responses[0].contains(r#""type":"sync_error""#));
+        assert!(responses[0].contains("workspace:ws"));
+
+        // Sent once; an empty reply is what a `check_errors` poll gets
+        let ControlMessageResponse::Handled { responses } =
+            hook.with_pending_error(1, handled(vec![]))
+        else {
+            panic!("expected a handled reply");
+        };
+        assert!(responses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_connections_can_be_closed() {
+        use super::super::connections::ClosableStream;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, owner_token) = login(&repo, "owner@example.com");
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let doc = DocType::Workspace(workspace_id.clone());
+        let code = repo
+            .create_share_session(&workspace_id, &owner, false, None)
+            .unwrap();
+        let socket = |port: u16| {
+            let (side, _) = tokio::io::duplex(64);
+            let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+            ClosableStream::new(side, addr, hook.connections.clone())
+        };
+
+        let member_socket = socket(1);
+        let member = hook.authenticate_token(&owner_token, &doc).unwrap();
+        hook.record_connection(Some(member_socket.id().to_string()), &member);
+        let guest_socket = socket(2);
+        let guest = hook.authenticate_session(&code, "g1", &doc).unwrap();
+        hook.record_connection(Some(guest_socket.id().to_string()), &guest);
+        // Without a connection ID nothing is recorded
+        hook.record_connection(None::<String>, &member);
+
+        assert_eq!(hook.connections.close_member("other", &owner), 0);
+        assert_eq!(hook.connections.close_member(&workspace_id, &owner), 1);
+        assert_eq!(hook.connections.close_session(&code), 1);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_workspace_name_is_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, owner_token) = login(&repo, "owner@example.com");
+        let owner_workspace = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let (_, other_token) = login(&repo, "other@example.com");
+
+        // `default` means each user's own default workspace
+        let legacy = DocType::parse("workspace:default").unwrap();
+        let user = hook.authenticate_token(&owner_token, &legacy).unwrap();
+        assert_eq!(user.workspace_id, owner_workspace);
+        let other = hook.authenticate_token(&other_token, &legacy).unwrap();
+        assert_ne!(other.workspace_id, owner_workspace);
+        assert_ne!(other.workspace_id, "default");
+
+        assert_eq!(
+            DiaryxHook::resolve_doc("body:default/a.md", Some(&other)),
+            Some(DocType::Body {
+                workspace_id: other.workspace_id.clone(),
+                path: "a.md".to_string(),
+            })
+        );
+        // Rooms without a connection never use a shared `default` store
+        assert!(!hook.is_workspace_id(&legacy));
+        assert!(hook.is_workspace_id(&DocType::Workspace(owner_workspace)));
+    }
+
+    /// Awareness control message announcing `client_id` with a name
+    fn presence(doc_id: &str, client_ids: &[u64]) -> String {
+        let entries = client_ids
+            .iter()
+            .map(|&client_id| diaryx_core::crdt::AwarenessEntry {
+                client_id,
+                clock: 1,
+                state: Some(format!(r#"{{"name":"peer {}"}}"#, client_id)),
+            })
+            .collect();
+        encode_awareness_control(doc_id, &diaryx_core::crdt::AwarenessUpdate { entries })
+    }
+
+    #[tokio::test]
+    async fn test_awareness_relay_checks_access() {
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, owner_token) = login(&repo, "owner@example.com");
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let (_, stranger_token) = login(&repo, "stranger@example.com");
+        let doc_id = format!("workspace:{}", workspace_id);
+        let doc = DocType::Workspace(workspace_id.clone());
+        let owner_user = hook.authenticate_token(&owner_token, &doc).unwrap();
+        let stranger = hook
+            .authenticate_token(
+                &stranger_token,
+                &DocType::parse("workspace:default").unwrap(),
+            )
+            .unwrap();
+        let own = Some(doc_id.as_str());
+
+        // Nobody has the document open yet
+        assert!(
+            hook.relay_awareness(1, own, Some(&owner_user), &presence(&doc_id, &[11]))
+                .await
+                .is_empty()
+        );
+        assert!(hook.awareness.read().await.docs.is_empty());
+
+        hook.stats.peer_joined(&doc_id, 1, Some(&owner));
+        hook.stats.peer_joined(&doc_id, 2, Some(&owner));
+        let stranger_doc = format!("workspace:{}", stranger.workspace_id);
+        for (user, own) in [
+            (None, own),
+            (Some(&owner_user), None),
+            (Some(&stranger), Some(stranger_doc.as_str())),
+        ] {
+            hook.relay_awareness(3, own, user, &presence(&doc_id, &[13]))
+                .await;
+        }
+        assert!(hook.awareness.read().await.docs.is_empty());
+
+        // The first update of a newcomer is answered with the others' states
+        assert!(
+            hook.relay_awareness(1, own, Some(&owner_user), &presence(&doc_id, &[11]))
+                .await
+                .is_empty()
+        );
+        let replies = hook
+            .relay_awareness(2, own, Some(&owner_user), &presence(&doc_id, &[12]))
+            .await;
+        assert_eq!(replies.len(), 1);
+        let (_, snapshot) = decode_awareness_control(&replies[0]).unwrap();
+        assert_eq!(snapshot.entries.len(), 1);
+        assert_eq!(snapshot.entries[0].client_id, 11);
+        assert!(
+            hook.relay_awareness(2, own, Some(&owner_user), &presence(&doc_id, &[12]))
+                .await
+                .is_empty()
+        );
+
+        // States per document are bounded
+        let flood: Vec<u64> = (100..100 + MAX_AWARENESS_STATES as u64).collect();
+        hook.relay_awareness(1, own, Some(&owner_user), &presence(&doc_id, &flood))
+            .await;
+        assert_eq!(hook.awareness.read().await.docs[&doc_id].peers().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_awareness_hides_files_outside_guest_scope() {
+        use diaryx_core::crdt::{FileMetadata, ScopeEntry};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (hook, repo) = test_hook(dir.path());
+        let (owner, owner_token) = login(&repo, "owner@example.com");
+        let workspace_id = repo.get_or_create_workspace(&owner, "default").unwrap();
+        let storage = hook.storage_cache.get_storage(&workspace_id).unwrap();
+        let workspace =
+            WorkspaceCrdt::load_with_name(storage, format!("workspace:{}", workspace_id)).unwrap();
+        for path in ["shared.md", "private.md"] {
+            workspace
+                .set_file(path, FileMetadata::new(Some(path.to_string())))
+                .unwrap();
+        }
+        let code = repo
+            .create_share_session(&workspace_id, &owner, false, None)
+            .unwrap();
+        let scope = ShareScope {
+            entries: vec![ScopeEntry {
+                path: "shared.md".to_string(),
+                permission: SharePermission::Read,
+                include_contents: false,
+            }],
+        };
+        repo.set_share_session_scope(&code, Some(&scope)).unwrap();
+
+        let doc = DocType::Workspace(workspace_id.clone());
+        let doc_id = doc.storage_key();
+        let owner_user = hook.authenticate_token(&owner_token, &doc).unwrap();
+        let guest = hook.authenticate_session(&code, "g1", &doc).unwrap();
+        hook.stats.peer_joined(&doc_id, 1, Some(&owner));
+        hook.stats.peer_joined(&doc_id, 2, None);
+        let own = Some(doc_id.as_str());
+
+        let editing = |client_id: u64, file: &str| {
+            let state = format!(
+                r#"{{"name":"peer {}","file":"{}","cursor":{{"anchor":1,"head":1}}}}"#,
+                client_id, file
+            );
+            let entries = vec![diaryx_core::crdt::AwarenessEntry {
+                client_id,
+                clock: 1,
+                state: Some(state),
+            }];
+            encode_awareness_control(&doc_id, &AwarenessUpdate { entries })
+        };
+
+        // The guest's snapshot doesn't say which unshared file the owner has open
+        hook.relay_awareness(1, own, Some(&owner_user), &editing(11, "private.md"))
+            .await;
+        let replies = hook
+            .relay_awareness(2, own, Some(&guest), &editing(12, "shared.md"))
+            .await;
+        let (_, snapshot) = decode_awareness_control(&replies[0]).unwrap();
+        let state = snapshot.entries[0].state.as_deref().unwrap();
+        assert_eq!(state, r#"{"name":"peer 11"}"#);
+
+        // The scope and its resolution are cached for the connection
+        assert!(hook.guests.resolved(2).is_some());
+        hook.guests.scope_changed(&code, None);
+        assert!(hook.can_read(2, &guest, &DocType::parse("body:x/private.md").unwrap()));
+
+        let update = decode_awareness_control(&editing(11, "shared.md"))
+            .unwrap()
+            .1;
+        assert!(redact_presence(&update, |file| file == "shared.md").is_none());
+        assert!(redact_presence(&update, |_| false).is_some());
+    }
 
     #[test]
     fn test_doc_type_parse_workspace() {
@@ -31,17 +31,33 @@
 //! Not yet supported:
 //! - Focus tracking broadcast (focus/unfocus messages are received but not relayed)
 
+mod archive;
+mod connections;
+mod guests;
 mod handshake;
 mod hooks;
+#[cfg(feature = "postgres")]
+mod postgres_storage;
 mod server;
+mod stats;
 mod store;
 
+pub use archive::{
+    ARCHIVE_FORMAT, ARCHIVE_VERSION, ArchiveAttachment, ArchiveDocument, ArchiveEntry,
+    ArchiveManifest,
+};
+pub use connections::{ConnectionListener, ConnectionPrincipal, SyncConnections, tag_connection};
+pub use guests::GuestConnections;
 pub use handshake::{
     ClientControlMessage, ConnectionContext, HandshakeState, ManifestFileEntry,
     ServerControlMessage, handle_control_message, perform_handshake,
 };
 pub use hooks::{AuthenticatedUser, DiaryxHook, DocType};
+#[cfg(feature = "postgres")]
+pub use postgres_storage::PostgresCrdtStorage;
 pub use server::{SyncV2Server, SyncV2State};
+pub use stats::{RoomStats, SyncStats, SyncStatsSnapshot};
 pub use store::{
-    SnapshotError, SnapshotImportMode, SnapshotImportResult, StorageCache, WorkspaceStore,
+    ArchiveImportResult, FileIndexRow, RestoreResult, SnapshotError, SnapshotImportMode,
+    SnapshotImportResult, SnapshotInfo, StorageCache, WorkspaceStorage, WorkspaceStore,
 };
@@ -0,0 +1,718 @@
+//! PostgreSQL-backed CRDT storage (`postgres` feature).
+//!
+//! All workspaces share the `crdt_documents`, `crdt_updates`,
+//! `crdt_file_index` and `crdt_snapshots` tables, keyed by workspace ID, so any server instance
+//! connected to the database sees the same documents. Semantics match
+//! `SqliteStorage`: snapshots plus an append-only update log, with history
+//! reconstructed by replaying updates onto the snapshot.
+
+use diaryx_core::crdt::{CrdtStorage, CrdtUpdate, StorageResult, UpdateOrigin};
+use diaryx_core::error::DiaryxError;
+use postgres::{Client, GenericClient};
+use yrs::{Doc, ReadTxn, Transact, Update, updates::decoder::Decode, updates::encoder::Encode};
+
+use super::store::{FileIndexRow, SnapshotInfo, WorkspaceStorage};
+use crate::db::{PostgresPool, blocking};
+
+/// A document's snapshot (if any) and the updates to replay onto it
+type History = (Option<Vec<u8>>, Vec<Vec<u8>>);
+
+/// CRDT storage for one workspace in PostgreSQL
+pub struct PostgresCrdtStorage {
+    pool: PostgresPool,
+    workspace_id: String,
+}
+
+impl PostgresCrdtStorage {
+    /// Storage for `workspace_id` in a pool created with `connect_postgres`
+    pub fn new(pool: PostgresPool, workspace_id: &str) -> Self {
+        Self {
+            pool,
+            workspace_id: workspace_id.to_string(),
+        }
+    }
+
+    /// Run `f` with a pooled connection
+    fn run<T: Send>(
+        &self,
+        f: impl FnOnce(&mut Client, &str) -> Result<T, postgres::Error> + Send,
+    ) -> StorageResult<T> {
+        blocking(|| {
+            let mut conn = self.pool.get().map_err(storage_error)?;
+            f(&mut conn, &self.workspace_id).map_err(storage_error)
+        })
+    }
+
+    /// Whether this workspace has any documents or updates
+    pub fn exists(&self) -> StorageResult<bool> {
+        self.run(|db, ws| {
+            db.query_one(
+                "SELECT EXISTS (SELECT 1 FROM crdt_documents WHERE workspace_id = $1)
+                     OR EXISTS (SELECT 1 FROM crdt_updates WHERE workspace_id = $1)",
+                &[&ws],
+            )?
+            .try_get(0)
+        })
+    }
+
+    /// Bytes of snapshots and updates stored for this workspace
+    pub fn size_bytes(&self) -> StorageResult<u64> {
+        self.run(|db, ws| {
+            let size: i64 = db
+                .query_one(
+                    "SELECT
+                        COALESCE((SELECT SUM(octet_length(state) + octet_length(state_vector))
+                                  FROM crdt_documents WHERE workspace_id = $1), 0)
+                      + COALESCE((SELECT SUM(octet_length(data))
+                                  FROM crdt_updates WHERE workspace_id = $1), 0)",
+                    &[&ws],
+                )?
+                .try_get(0)?;
+            Ok(size.max(0) as u64)
+        })
+    }
+
+    /// Delete every document, update, file index entry and snapshot of this
+    /// workspace.
+    ///
+    /// Returns false if there was nothing to delete.
+    pub fn delete_all(&self) -> StorageResult<bool> {
+        self.run(|db, ws| {
+            let mut tx = db.transaction()?;
+            let mut deleted = 0;
+            for table in [
+                "crdt_updates",
+                "crdt_documents",
+                "crdt_file_index",
+                "crdt_snapshots",
+            ] {
+                deleted += tx.execute(
+                    &format!("DELETE FROM {} WHERE workspace_id = $1", table),
+                    &[&ws],
+                )?;
+            }
+            tx.commit()?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Store a server-side snapshot archive
+    pub fn save_snapshot(&self, info: &SnapshotInfo, archive: &[u8]) -> StorageResult<()> {
+        self.run(|db, ws| {
+            db.execute(
+                "INSERT INTO crdt_snapshots (workspace_id, id, label, created_at, data)
+                 VALUES ($1, $2, $3, $4, $5)",
+                &[&ws, &info.id, &info.label, &info.created_at, &archive],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Server-side snapshots of this workspace, without their archives
+    pub fn list_snapshots(&self) -> StorageResult<Vec<SnapshotInfo>> {
+        self.run(|db, ws| {
+            db.query(
+                "SELECT id, created_at, label, octet_length(data) FROM crdt_snapshots
+                 WHERE workspace_id = $1",
+                &[&ws],
+            )?
+            .iter()
+            .map(|row| {
+                let size: i32 = row.try_get(3)?;
+                Ok(SnapshotInfo {
+                    id: row.try_get(0)?,
+                    created_at: row.try_get(1)?,
+                    label: row.try_get(2)?,
+                    size: size.max(0) as u64,
+                })
+            })
+            .collect()
+        })
+    }
+
+    /// The archive of a server-side snapshot
+    pub fn load_snapshot(&self, id: &str) -> StorageResult<Option<Vec<u8>>> {
+        self.run(|db, ws| {
+            db.query_opt(
+                "SELECT data FROM crdt_snapshots WHERE workspace_id = $1 AND id = $2",
+                &[&ws, &id],
+            )?
+            .map(|row| row.try_get(0))
+            .transpose()
+        })
+    }
+
+    /// Delete a server-side snapshot. Returns false if it didn't exist.
+    pub fn delete_snapshot(&self, id: &str) -> StorageResult<bool> {
+        self.run(|db, ws| {
+            let deleted = db.execute(
+                "DELETE FROM crdt_snapshots WHERE workspace_id = $1 AND id = $2",
+                &[&ws, &id],
+            )?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Load the snapshot and updates (up to `up_to_id`, if given) of a document
+    fn load_history(
+        db: &mut impl GenericClient,
+        ws: &str,
+        name: &str,
+        up_to_id: Option<i64>,
+    ) -> Result<History, postgres::Error> {
+        let base = db
+            .query_opt(
+                "SELECT state FROM crdt_documents WHERE workspace_id = $1 AND name = $2",
+                &[&ws, &name],
+            )?
+            .map(|row| row.try_get(0))
+            .transpose()?;
+
+        let updates = db
+            .query(
+                "SELECT data FROM crdt_updates
+                 WHERE workspace_id = $1 AND doc_name = $2 AND ($3::BIGINT IS NULL OR id <= $3)
+                 ORDER BY id ASC",
+                &[&ws, &name, &up_to_id],
+            )?
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<_, _>>()?;
+
+        Ok((base, updates))
+    }
+}
+
+impl std::fmt::Debug for PostgresCrdtStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostgresCrdtStorage")
+            .field("workspace_id", &self.workspace_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CrdtStorage for PostgresCrdtStorage {
+    fn load_doc(&self, name: &str) -> StorageResult<Option<Vec<u8>>> {
+        self.run(|db, ws| {
+            db.query_opt(
+                "SELECT state FROM crdt_documents WHERE workspace_id = $1 AND name = $2",
+                &[&ws, &name],
+            )?
+            .map(|row| row.try_get(0))
+            .transpose()
+        })
+    }
+
+    fn save_doc(&self, name: &str, state: &[u8]) -> StorageResult<()> {
+        let state_vector = state_vector_of(state);
+        self.run(|db, ws| {
+            let now = chrono::Utc::now().timestamp_millis();
+            db.execute(
+                "INSERT INTO crdt_documents (workspace_id, name, state, state_vector, updated_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (workspace_id, name) DO UPDATE
+                 SET state = EXCLUDED.state, state_vector = EXCLUDED.state_vector,
+                     updated_at = EXCLUDED.updated_at",
+                &[&ws, &name, &state, &state_vector, &now],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn delete_doc(&self, name: &str) -> StorageResult<()> {
+        self.run(|db, ws| {
+            let mut tx = db.transaction()?;
+            tx.execute(
+                "DELETE FROM crdt_updates WHERE workspace_id = $1 AND doc_name = $2",
+                &[&ws, &name],
+            )?;
+            tx.execute(
+                "DELETE FROM crdt_documents WHERE workspace_id = $1 AND name = $2",
+                &[&ws, &name],
+            )?;
+            tx.commit()
+        })
+    }
+
+    fn list_docs(&self) -> StorageResult<Vec<String>> {
+        self.run(|db, ws| {
+            db.query(
+                "SELECT name FROM crdt_documents WHERE workspace_id = $1 ORDER BY name",
+                &[&ws],
+            )?
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect()
+        })
+    }
+
+    fn append_update_with_device(
+        &self,
+        name: &str,
+        update: &[u8],
+        origin: UpdateOrigin,
+        device_id: Option<&str>,
+        device_name: Option<&str>,
+    ) -> StorageResult<i64> {
+        self.run(|db, ws| {
+            let now = chrono::Utc::now().timestamp_millis();
+            db.query_one(
+                "INSERT INTO crdt_updates
+                     (workspace_id, doc_name, data, origin, timestamp, device_id, device_name)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+                &[
+                    &ws,
+                    &name,
+                    &update,
+                    &origin.to_string(),
+                    &now,
+                    &device_id,
+                    &device_name,
+                ],
+            )?
+            .try_get(0)
+        })
+    }
+
+    fn batch_append_updates(
+        &self,
+        updates: &[(&str, &[u8], UpdateOrigin)],
+    ) -> StorageResult<Vec<i64>> {
+        if updates.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.run(|db, ws| {
+            let now = chrono::Utc::now().timestamp_millis();
+            let mut tx = db.transaction()?;
+            let stmt = tx.prepare(
+                "INSERT INTO crdt_updates (workspace_id, doc_name, data, origin, timestamp)
+                 VALUES ($1, $2, $3, $4, $5) RETURNING id",
+            )?;
+
+            let mut ids = Vec::with_capacity(updates.len());
+            for (name, update, origin) in updates {
+                let row = tx.query_one(&stmt, &[&ws, name, update, &origin.to_string(), &now])?;
+                ids.push(row.try_get(0)?);
+            }
+
+            tx.commit()?;
+            Ok(ids)
+        })
+    }
+
+    fn get_updates_since(&self, name: &str, since_id: i64) -> StorageResult<Vec<CrdtUpdate>> {
+        self.run(|db, ws| {
+            db.query(
+                "SELECT id, data, origin, timestamp, device_id, device_name FROM crdt_updates
+                 WHERE workspace_id = $1 AND doc_name = $2 AND id > $3
+                 ORDER BY id ASC",
+                &[&ws, &name, &since_id],
+            )?
+            .iter()
+            .map(|row| {
+                let origin: String = row.try_get(2)?;
+                Ok(CrdtUpdate {
+                    update_id: row.try_get(0)?,
+                    doc_name: name.to_string(),
+                    data: row.try_get(1)?,
+                    timestamp: row.try_get(3)?,
+                    origin: origin.parse().unwrap_or(UpdateOrigin::Local),
+                    device_id: row.try_get(4)?,
+                    device_name: row.try_get(5)?,
+                })
+            })
+            .collect()
+        })
+    }
+
+    fn get_all_updates(&self, name: &str) -> StorageResult<Vec<CrdtUpdate>> {
+        self.get_updates_since(name, 0)
+    }
+
+    fn get_state_at(&self, name: &str, update_id: i64) -> StorageResult<Option<Vec<u8>>> {
+        let (base, updates) =
+            self.run(|db, ws| Self::load_history(db, ws, name, Some(update_id)))?;
+        if base.is_none() && updates.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(merge_state(base.as_deref(), &updates)))
+    }
+
+    fn compact(&self, name: &str, keep_updates: usize) -> StorageResult<()> {
+        self.run(|db, ws| {
+            let mut tx = db.transaction()?;
+
+            // Lock the snapshot row so concurrent compactions of the same
+            // document (e.g. from two instances) run one after the other
+            tx.execute(
+                "SELECT 1 FROM crdt_documents WHERE workspace_id = $1 AND name = $2 FOR UPDATE",
+                &[&ws, &name],
+            )?;
+
+            let update_count: i64 = tx
+                .query_one(
+                    "SELECT COUNT(*) FROM crdt_updates WHERE workspace_id = $1 AND doc_name = $2",
+                    &[&ws, &name],
+                )?
+                .try_get(0)?;
+            if update_count as usize <= keep_updates {
+                return Ok(());
+            }
+
+            // Keep the last `keep_updates` updates
+            let cutoff_id: i64 = tx
+                .query_opt(
+                    "SELECT id FROM crdt_updates WHERE workspace_id = $1 AND doc_name = $2
+                     ORDER BY id DESC LIMIT 1 OFFSET $3",
+                    &[&ws, &name, &(keep_updates.saturating_sub(1) as i64)],
+                )?
+                .map(|row| row.try_get(0))
+                .transpose()?
+                .unwrap_or(0);
+
+            // Fold every update seen so far into the snapshot, then drop the
+            // old ones; both happen in this transaction
+            let (base, updates) = Self::load_history(&mut tx, ws, name, None)?;
+            let full_state = merge_state(base.as_deref(), &updates);
+            let state_vector = state_vector_of(&full_state);
+            let now = chrono::Utc::now().timestamp_millis();
+
+            tx.execute(
+                "INSERT INTO crdt_documents (workspace_id, name, state, state_vector, updated_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (workspace_id, name) DO UPDATE
+                 SET state = EXCLUDED.state, state_vector = EXCLUDED.state_vector,
+                     updated_at = EXCLUDED.updated_at",
+                &[&ws, &name, &full_state, &state_vector, &now],
+            )?;
+            tx.execute(
+                "DELETE FROM crdt_updates WHERE workspace_id = $1 AND doc_name = $2 AND id < $3",
+                &[&ws, &name, &cutoff_id],
+            )?;
+
+            tx.commit()
+        })
+    }
+
+    fn get_latest_update_id(&self, name: &str) -> StorageResult<i64> {
+        self.run(|db, ws| {
+            db.query_one(
+                "SELECT COALESCE(MAX(id), 0) FROM crdt_updates
+                 WHERE workspace_id = $1 AND doc_name = $2",
+                &[&ws, &name],
+            )?
+            .try_get(0)
+        })
+    }
+
+    fn rename_doc(&self, old_name: &str, new_name: &str) -> StorageResult<()> {
+        self.run(|db, ws| {
+            let mut tx = db.transaction()?;
+            tx.execute(
+                "UPDATE crdt_documents SET name = $3 WHERE workspace_id = $1 AND name = $2",
+                &[&ws, &old_name, &new_name],
+            )?;
+            tx.execute(
+                "UPDATE crdt_updates SET doc_name = $3 WHERE workspace_id = $1 AND doc_name = $2",
+                &[&ws, &old_name, &new_name],
+            )?;
+            tx.commit()
+        })
+    }
+
+    fn clear_updates(&self, name: &str) -> StorageResult<()> {
+        self.run(|db, ws| {
+            db.execute(
+                "DELETE FROM crdt_updates WHERE workspace_id = $1 AND doc_name = $2",
+                &[&ws, &name],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+impl WorkspaceStorage for PostgresCrdtStorage {
+    fn update_file_index(
+        &self,
+        path: &str,
+        title: Option<&str>,
+        part_of: Option<&str>,
+        deleted: bool,
+        modified_at: i64,
+    ) -> StorageResult<()> {
+        self.run(|db, ws| {
+            db.execute(
+                "INSERT INTO crdt_file_index (workspace_id, path, title, part_of, deleted, modified_at)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT (workspace_id, path) DO UPDATE
+                 SET title = EXCLUDED.title, part_of = EXCLUDED.part_of,
+                     deleted = EXCLUDED.deleted, modified_at = EXCLUDED.modified_at",
+                &[&ws, &path, &title, &part_of, &deleted, &modified_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn query_active_files(&self) -> StorageResult<Vec<FileIndexRow>> {
+        self.run(|db, ws| {
+            db.query(
+                "SELECT path, title, part_of FROM crdt_file_index
+                 WHERE workspace_id = $1 AND NOT deleted ORDER BY path",
+                &[&ws],
+            )?
+            .iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)))
+            .collect()
+        })
+    }
+
+    fn clear_file_index(&self) -> StorageResult<()> {
+        self.run(|db, ws| {
+            db.execute(
+                "DELETE FROM crdt_file_index WHERE workspace_id = $1",
+                &[&ws],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn list_all_docs(&self) -> StorageResult<Vec<String>> {
+        self.run(|db, ws| {
+            db.query(
+                "SELECT name FROM crdt_documents WHERE workspace_id = $1
+                 UNION
+                 SELECT doc_name FROM crdt_updates WHERE workspace_id = $1
+                 ORDER BY 1",
+                &[&ws],
+            )?
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect()
+        })
+    }
+
+    fn import_updates(&self, updates: &[CrdtUpdate]) -> StorageResult<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        self.run(|db, ws| {
+            let mut tx = db.transaction()?;
+            let stmt = tx.prepare(
+                "INSERT INTO crdt_updates
+                     (workspace_id, doc_name, data, origin, timestamp, device_id, device_name)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            )?;
+            for update in updates {
+                tx.execute(
+                    &stmt,
+                    &[
+                        &ws,
+                        &update.doc_name,
+                        &update.data,
+                        &update.origin.to_string(),
+                        &update.timestamp,
+                        &update.device_id,
+                        &update.device_name,
+                    ],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+}
+
+fn storage_error(error: impl std::fmt::Display) -> DiaryxError {
+    DiaryxError::Crdt(format!("PostgreSQL storage error: {}", error))
+}
+
+/// Apply a snapshot and updates to a fresh doc and encode the result
+fn merge_state(base: Option<&[u8]>, updates: &[Vec<u8>]) -> Vec<u8> {
+    let doc = Doc::new();
+    {
+        let mut txn = doc.transact_mut();
+        for data in base.into_iter().chain(updates.iter().map(Vec::as_slice)) {
+            if let Ok(update) = Update::decode_v1(data) {
+                let _ = txn.apply_update(update);
+            }
+        }
+    }
+    let txn = doc.transact();
+    txn.encode_state_as_update_v1(&Default::default())
+}
+
+/// State vector of an encoded document state
+fn state_vector_of(state: &[u8]) -> Vec<u8> {
+    let doc = Doc::new();
+    {
+        let mut txn = doc.transact_mut();
+        if let Ok(update) = Update::decode_v1(state) {
+            let _ = txn.apply_update(update);
+        }
+    }
+    let txn = doc.transact();
+    txn.state_vector().encode_v1()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use yrs::{GetString, Text};
+
+    fn text_update(doc: &Doc, text: &str) -> Vec<u8> {
+        let content = doc.get_or_insert_text("content");
+        let before = doc.transact().state_vector();
+        let mut txn = doc.transact_mut();
+        let len = content.len(&txn);
+        content.insert(&mut txn, len, text);
+        txn.encode_diff_v1(&before)
+    }
+
+    fn text_of(state: &[u8]) -> String {
+        let doc = Doc::new();
+        let content = doc.get_or_insert_text("content");
+        doc.transact_mut()
+            .apply_update(Update::decode_v1(state).unwrap())
+            .unwrap();
+        content.get_string(&doc.transact())
+    }
+
+    #[test]
+    fn test_updates_history_and_compaction() {
+        let Some(pool) = test_pool() else { return };
+        let storage = PostgresCrdtStorage::new(pool.clone(), "ws1");
+        let other = PostgresCrdtStorage::new(pool, "ws2");
+        assert!(!storage.exists().unwrap());
+
+        let doc = Doc::new();
+        let first = storage
+            .append_update(
+                "body:ws1/a.md",
+                &text_update(&doc, "Hello"),
+                UpdateOrigin::Local,
+            )
+            .unwrap();
+        for word in [" big", " wide", " world"] {
+            storage
+                .append_update(
+                    "body:ws1/a.md",
+                    &text_update(&doc, word),
+                    UpdateOrigin::Remote,
+                )
+                .unwrap();
+        }
+        assert!(storage.exists().unwrap());
+        assert!(storage.size_bytes().unwrap() > 0);
+        assert_eq!(storage.get_all_updates("body:ws1/a.md").unwrap().len(), 4);
+        assert!(other.get_all_updates("body:ws1/a.md").unwrap().is_empty());
+
+        let at_first = storage
+            .get_state_at("body:ws1/a.md", first)
+            .unwrap()
+            .unwrap();
+        assert_eq!(text_of(&at_first), "Hello");
+
+        storage.compact("body:ws1/a.md", 1).unwrap();
+        assert_eq!(storage.get_all_updates("body:ws1/a.md").unwrap().len(), 1);
+        let latest = storage.get_latest_update_id("body:ws1/a.md").unwrap();
+        let state = storage
+            .get_state_at("body:ws1/a.md", latest)
+            .unwrap()
+            .unwrap();
+        assert_eq!(text_of(&state), "Hello big wide world");
+        assert_eq!(storage.list_docs().unwrap(), vec!["body:ws1/a.md"]);
+
+        storage
+            .rename_doc("body:ws1/a.md", "body:ws1/b.md")
+            .unwrap();
+        assert!(storage.load_doc("body:ws1/a.md").unwrap().is_none());
+        assert_eq!(
+            storage.get_latest_update_id("body:ws1/b.md").unwrap(),
+            latest
+        );
+
+        assert!(storage.delete_all().unwrap());
+        assert!(!storage.exists().unwrap());
+    }
+
+    #[test]
+    fn test_file_index() {
+        let Some(pool) = test_pool() else { return };
+        let storage = PostgresCrdtStorage::new(pool, "ws1");
+
+        storage
+            .update_file_index("b.md", Some("B"), Some("index.md"), false, 1)
+            .unwrap();
+        storage
+            .update_file_index("a.md", Some("A"), None, false, 1)
+            .unwrap();
+        storage
+            .update_file_index("gone.md", None, None, true, 1)
+            .unwrap();
+        storage
+            .update_file_index("a.md", Some("A2"), None, false, 2)
+            .unwrap();
+
+        let files = storage.query_active_files().unwrap();
+        assert_eq!(
+            files,
+            vec![
+                ("a.md".to_string(), Some("A2".to_string()), None),
+                (
+                    "b.md".to_string(),
+                    Some("B".to_string()),
+                    Some("index.md".to_string())
+                ),
+            ]
+        );
+
+        storage.clear_file_index().unwrap();
+        assert!(storage.query_active_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshots_and_imported_history() {
+        let Some(pool) = test_pool() else { return };
+        let storage = PostgresCrdtStorage::new(pool, "ws1");
+
+        let doc = Doc::new();
+        storage
+            .import_updates(&[CrdtUpdate {
+                update_id: 0,
+                doc_name: "body:ws1/a.md".to_string(),
+                data: text_update(&doc, "Hello"),
+                timestamp: 1_000,
+                origin: UpdateOrigin::Remote,
+                device_id: None,
+                device_name: Some("Laptop".to_string()),
+            }])
+            .unwrap();
+        assert!(storage.list_docs().unwrap().is_empty());
+        assert_eq!(storage.list_all_docs().unwrap(), vec!["body:ws1/a.md"]);
+        let updates = storage.get_all_updates("body:ws1/a.md").unwrap();
+        assert_eq!(updates[0].timestamp, 1_000);
+        assert_eq!(updates[0].device_name.as_deref(), Some("Laptop"));
+
+        let info = SnapshotInfo {
+            id: "snap-1".to_string(),
+            created_at: 5,
+            label: Some("nightly".to_string()),
+            size: 0,
+        };
+        storage.save_snapshot(&info, b"archive").unwrap();
+        let listed = storage.list_snapshots().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].label.as_deref(), Some("nightly"));
+        assert_eq!(listed[0].size, 7);
+        assert_eq!(
+            storage.load_snapshot("snap-1").unwrap().as_deref(),
+            Some(&b"archive"[..])
+        );
+
+        assert!(storage.delete_snapshot("snap-1").unwrap());
+        assert!(!storage.delete_snapshot("snap-1").unwrap());
+        assert!(storage.load_snapshot("snap-1").unwrap().is_none());
+    }
+}
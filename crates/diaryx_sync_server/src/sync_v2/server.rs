@@ -3,33 +3,55 @@
 //! This module wraps the siphonophore Server with Diaryx-specific configuration.
 
 use axum::Router;
+use diaryx_core::crdt::ShareScope;
 use siphonophore::{Handle, Server};
 use std::collections::HashMap;
-use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::info;
 
+use crate::config::RateLimitConfig;
 use crate::db::AuthRepo;
+use crate::webhooks::WebhookDispatcher;
 
+use super::connections::SyncConnections;
+use super::guests::GuestConnections;
 use super::hooks::DiaryxHook;
-use super::store::{StorageCache, WorkspaceStore};
+use super::stats::{SyncStats, SyncStatsSnapshot};
+use super::store::{RestoreResult, StorageCache, WorkspaceStore};
+
+/// How long a share session's guests keep their connections after being told
+/// the session ended or its access narrowed, so the message reaches them first.
+const SESSION_CLOSE_GRACE: Duration = Duration::from_secs(1);
 
 /// State for the sync v2 server, shared with HTTP handlers.
 ///
 /// Provides access to the siphonophore Handle for peer counts and broadcasts,
-/// a WorkspaceStore for snapshot operations, and session management.
+/// a WorkspaceStore for snapshot operations, connection statistics, and
+/// session management.
 #[derive(Clone)]
 pub struct SyncV2State {
     /// Handle to the siphonophore server for peer counts and broadcasts.
     pub handle: Handle,
     /// Workspace store for snapshot export/import and file queries.
     pub store: Arc<WorkspaceStore>,
+    /// Live connection counts recorded by the hook.
+    pub stats: Arc<SyncStats>,
+    /// Open sync sockets, for `ConnectionListener` and `tag_connection`.
+    pub connections: Arc<SyncConnections>,
+    /// Cached scopes of connected share-session guests.
+    guests: Arc<GuestConnections>,
     /// Session code -> workspace ID mapping for peer count lookups and broadcasts.
     session_to_workspace: Arc<RwLock<HashMap<String, String>>>,
 }
 
 impl SyncV2State {
+    /// Current connection and room counts.
+    pub fn connection_stats(&self) -> SyncStatsSnapshot {
+        self.stats.snapshot()
+    }
+
     /// Get peer count for a session by looking up the workspace and querying siphonophore.
     pub async fn get_session_peer_count(&self, session_code: &str) -> Option<usize> {
         let code = session_code.to_uppercase();
@@ -39,7 +61,37 @@ impl SyncV2State {
         if count == 0 { None } else { Some(count) }
     }
 
-    /// End a session: broadcast session_ended to all connected clients and remove mapping.
+    /// Disconnect a member removed from a workspace.
+    ///
+    /// Their clients reconnect and are refused, instead of keeping the
+    /// access they authenticated with.
+    pub fn disconnect_member(&self, workspace_id: &str, user_id: &str) {
+        let closed = self.connections.close_member(workspace_id, user_id);
+        if closed > 0 {
+            info!(
+                "Disconnected {} sync connection(s) of removed member {} from {}",
+                closed, user_id, workspace_id
+            );
+        }
+    }
+
+    /// Close a session's guest connections after [`SESSION_CLOSE_GRACE`].
+    ///
+    /// Access is checked when a connection joins a document, so guests whose
+    /// access is taken away are reconnected to be checked again.
+    fn disconnect_session(&self, code: String) {
+        let connections = self.connections.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(SESSION_CLOSE_GRACE).await;
+            let closed = connections.close_session(&code);
+            if closed > 0 {
+                info!("Disconnected {} guest connection(s) of {}", closed, code);
+            }
+        });
+    }
+
+    /// End a session: broadcast session_ended to all connected clients, remove
+    /// mapping and disconnect its guests.
     pub async fn end_session(&self, session_code: &str) {
         let code = session_code.to_uppercase();
         let workspace_id = {
@@ -53,6 +105,7 @@ impl SyncV2State {
             self.handle.broadcast_text(&doc_id, msg, None).await;
             info!("Ended session: {}", code);
         }
+        self.disconnect_session(code);
     }
 
     /// Register a session-to-workspace mapping.
@@ -72,7 +125,9 @@ impl SyncV2State {
             .cloned()
     }
 
-    /// Broadcast a read-only change to all clients connected to a session's workspace.
+    /// Broadcast a read-only change to all clients connected to a session's
+    /// workspace. Guests made read-only are disconnected, since their open
+    /// connections were authorized to write.
     pub async fn broadcast_read_only_changed(&self, session_code: &str, read_only: bool) {
         let code = session_code.to_uppercase();
         let workspace_id = {
@@ -89,6 +144,53 @@ impl SyncV2State {
             .to_string();
             self.handle.broadcast_text(&doc_id, msg, None).await;
         }
+        if read_only {
+            self.disconnect_session(code);
+        }
+    }
+
+    /// Bring loaded rooms up to date after a workspace restore.
+    ///
+    /// `WorkspaceStore` writes the restore to storage, which rooms that are
+    /// already loaded don't read again. Applying the same edits through the
+    /// room keeps its copy current (so it doesn't save the old state back) and
+    /// sends them to connected clients. Cached guest scopes are resolved again.
+    pub async fn restored(&self, workspace_id: &str, result: &RestoreResult) {
+        for (doc_id, update) in &result.updates {
+            self.handle.apply_update(doc_id, update.clone()).await;
+        }
+        self.guests.workspace_changed(workspace_id);
+    }
+
+    /// Apply a scope change to a session's open connections and notify them.
+    ///
+    /// Only the session's own connections are addressed, by their `client`
+    /// tag, and the scope itself isn't sent since other clients on the
+    /// workspace receive the message too. Guests given a scope are then
+    /// disconnected, dropping the documents they joined outside it; they
+    /// reconnect with a freshly filtered workspace state.
+    pub async fn broadcast_scope_changed(&self, session_code: &str, scope: Option<&ShareScope>) {
+        let code = session_code.to_uppercase();
+        let tags = self.guests.scope_changed(&code, scope);
+        let workspace_id = {
+            let map = self.session_to_workspace.read().await;
+            map.get(&code).cloned()
+        };
+
+        if let Some(workspace_id) = workspace_id {
+            let doc_id = format!("workspace:{}", workspace_id);
+            for tag in tags {
+                let msg = serde_json::json!({
+                    "type": "scope_changed",
+                    "client": tag,
+                })
+                .to_string();
+                self.handle.broadcast_text(&doc_id, msg, None).await;
+            }
+        }
+        if scope.is_some() {
+            self.disconnect_session(code);
+        }
     }
 }
 
@@ -97,16 +199,34 @@ pub struct SyncV2Server {
     server: Server,
     storage_cache: Arc<StorageCache>,
     session_to_workspace: Arc<RwLock<HashMap<String, String>>>,
+    stats: Arc<SyncStats>,
+    connections: Arc<SyncConnections>,
+    guests: Arc<GuestConnections>,
 }
 
 impl SyncV2Server {
     /// Create a new sync v2 server with Diaryx hooks.
-    pub fn new(repo: Arc<AuthRepo>, workspaces_dir: PathBuf) -> Self {
-        let storage_cache = Arc::new(StorageCache::new(workspaces_dir));
+    pub fn new(
+        repo: Arc<AuthRepo>,
+        storage_cache: Arc<StorageCache>,
+        limits: &RateLimitConfig,
+        webhooks: Arc<WebhookDispatcher>,
+    ) -> Self {
         let session_to_workspace = Arc::new(RwLock::new(HashMap::new()));
+        let stats = Arc::new(SyncStats::new());
+        let guests = Arc::new(GuestConnections::new());
+        let connections = Arc::new(SyncConnections::new());
 
-        let (hook, handle_cell) =
-            DiaryxHook::new(repo, storage_cache.clone(), session_to_workspace.clone());
+        let (hook, handle_cell) = DiaryxHook::new(
+            repo,
+            storage_cache.clone(),
+            session_to_workspace.clone(),
+            stats.clone(),
+            guests.clone(),
+            connections.clone(),
+            limits,
+            webhooks,
+        );
         let server = Server::with_hooks(vec![Box::new(hook)]);
         // Set the handle so the hook can broadcast messages to clients
         handle_cell.set(server.handle()).ok();
@@ -115,6 +235,9 @@ impl SyncV2Server {
             server,
             storage_cache,
             session_to_workspace,
+            stats,
+            connections,
+            guests,
         }
     }
 
@@ -124,6 +247,9 @@ impl SyncV2Server {
             handle: self.server.handle(),
             store: Arc::new(WorkspaceStore::new(self.storage_cache.clone())),
             session_to_workspace: self.session_to_workspace.clone(),
+            stats: self.stats.clone(),
+            connections: self.connections.clone(),
+            guests: self.guests.clone(),
         }
     }
 
@@ -132,3 +258,50 @@ impl SyncV2Server {
         self.server.into_router_at(path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_v2::connections::{ClosableStream, ConnectionPrincipal};
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_removed_member_is_disconnected() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::init_database(&conn).unwrap();
+        let repo = Arc::new(AuthRepo::new(conn));
+        let server = SyncV2Server::new(
+            repo.clone(),
+            Arc::new(StorageCache::new(dir.path().to_path_buf())),
+            &RateLimitConfig::default(),
+            Arc::new(WebhookDispatcher::new(repo, Default::default()).unwrap()),
+        );
+        let state = server.state();
+
+        let socket = |port: u16, user_id: &str| {
+            let (side, peer) = tokio::io::duplex(64);
+            let addr = SocketAddr::from(([127, 0, 0, 1], port));
+            let stream = ClosableStream::new(side, addr, state.connections.clone());
+            state.connections.authenticated(
+                stream.id(),
+                ConnectionPrincipal::Member {
+                    workspace_id: "ws".to_string(),
+                    user_id: user_id.to_string(),
+                },
+            );
+            (stream, peer)
+        };
+        let (mut removed, _removed_peer) = socket(1, "removed");
+        let (mut kept, mut kept_peer) = socket(2, "kept");
+
+        state.disconnect_member("ws", "removed");
+
+        let mut buf = [0u8; 2];
+        assert_eq!(removed.read(&mut buf).await.unwrap(), 0);
+        kept_peer.write_all(b"ok").await.unwrap();
+        kept.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ok");
+    }
+}
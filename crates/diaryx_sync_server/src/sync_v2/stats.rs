@@ -0,0 +1,168 @@
+//! Live connection statistics for the sync server.
+//!
+//! Siphonophore only exposes per-document peer counts, so `DiaryxHook` records
+//! peers joining and leaving here. Admin endpoints and `/api/status` read it.
+
+use serde::Serialize;
+use siphonophore::ClientId;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use super::hooks::DocType;
+
+/// Connected peers per document (room).
+#[derive(Default)]
+pub struct SyncStats {
+    rooms: RwLock<HashMap<String, HashMap<ClientId, Option<String>>>>,
+}
+
+/// Point-in-time view of connections.
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncStatsSnapshot {
+    /// Distinct WebSocket clients connected to at least one document
+    pub active_connections: usize,
+    /// Documents with at least one connected peer
+    pub active_rooms: usize,
+    pub rooms: Vec<RoomStats>,
+}
+
+/// Connections to a single document.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomStats {
+    pub doc_id: String,
+    pub workspace_id: Option<String>,
+    pub peers: usize,
+    /// Distinct authenticated user IDs (guests are counted in `peers` only)
+    pub users: Vec<String>,
+}
+
+impl SyncStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a peer joining a document.
+    pub fn peer_joined(&self, doc_id: &str, client_id: ClientId, user_id: Option<&str>) {
+        self.rooms
+            .write()
+            .unwrap()
+            .entry(doc_id.to_string())
+            .or_default()
+            .insert(client_id, user_id.map(String::from));
+    }
+
+    /// Record a peer leaving a document.
+    pub fn peer_left(&self, doc_id: &str, client_id: ClientId) {
+        let mut rooms = self.rooms.write().unwrap();
+        if let Some(peers) = rooms.get_mut(doc_id) {
+            peers.remove(&client_id);
+            if peers.is_empty() {
+                rooms.remove(doc_id);
+            }
+        }
+    }
+
+    /// Whether any peer is connected to a document.
+    pub fn is_open(&self, doc_id: &str) -> bool {
+        self.rooms.read().unwrap().contains_key(doc_id)
+    }
+
+    /// Clients connected to a document.
+    pub fn peers(&self, doc_id: &str) -> Vec<ClientId> {
+        self.rooms
+            .read()
+            .unwrap()
+            .get(doc_id)
+            .map(|peers| peers.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether a client is still connected to any document.
+    pub fn is_connected(&self, client_id: ClientId) -> bool {
+        self.rooms
+            .read()
+            .unwrap()
+            .values()
+            .any(|peers| peers.contains_key(&client_id))
+    }
+
+    /// Number of peers connected to any document of a workspace.
+    pub fn workspace_peer_count(&self, workspace_id: &str) -> usize {
+        let rooms = self.rooms.read().unwrap();
+        let clients: HashSet<&ClientId> = rooms
+            .iter()
+            .filter(|(doc_id, _)| {
+                DocType::parse(doc_id).is_some_and(|doc| doc.workspace_id() == workspace_id)
+            })
+            .flat_map(|(_, peers)| peers.keys())
+            .collect();
+        clients.len()
+    }
+
+    /// Take a snapshot of current connections, busiest rooms first.
+    pub fn snapshot(&self) -> SyncStatsSnapshot {
+        let rooms = self.rooms.read().unwrap();
+
+        let active_connections = rooms
+            .values()
+            .flat_map(|peers| peers.keys())
+            .collect::<HashSet<_>>()
+            .len();
+
+        let mut room_stats: Vec<RoomStats> = rooms
+            .iter()
+            .map(|(doc_id, peers)| {
+                let mut users: Vec<String> = peers
+                    .values()
+                    .flatten()
+                    .cloned()
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                users.sort();
+                RoomStats {
+                    doc_id: doc_id.clone(),
+                    workspace_id: DocType::parse(doc_id).map(|doc| doc.workspace_id().to_string()),
+                    peers: peers.len(),
+                    users,
+                }
+            })
+            .collect();
+        room_stats.sort_by(|a, b| b.peers.cmp(&a.peers).then_with(|| a.doc_id.cmp(&b.doc_id)));
+
+        SyncStatsSnapshot {
+            active_connections,
+            active_rooms: room_stats.len(),
+            rooms: room_stats,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_counts() {
+        let stats = SyncStats::new();
+        stats.peer_joined("workspace:ws1", 1, Some("alice"));
+        stats.peer_joined("body:ws1/notes.md", 1, Some("alice"));
+        stats.peer_joined("workspace:ws1", 2, None);
+        stats.peer_joined("workspace:ws2", 3, Some("bob"));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_connections, 3);
+        assert_eq!(snapshot.active_rooms, 3);
+        assert_eq!(snapshot.rooms[0].doc_id, "workspace:ws1");
+        assert_eq!(snapshot.rooms[0].peers, 2);
+        assert_eq!(snapshot.rooms[0].users, vec!["alice".to_string()]);
+        assert_eq!(stats.workspace_peer_count("ws1"), 2);
+
+        stats.peer_left("workspace:ws1", 2);
+        stats.peer_left("workspace:ws2", 3);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_connections, 1);
+        assert_eq!(snapshot.active_rooms, 2);
+        assert_eq!(stats.workspace_peer_count("ws2"), 0);
+    }
+}
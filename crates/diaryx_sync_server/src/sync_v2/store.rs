@@ -1,16 +1,26 @@
 //! Workspace storage and snapshot operations.
 //!
 //! This module provides:
-//! - `StorageCache`: shared cache of per-workspace `SqliteStorage` connections
-//! - `WorkspaceStore`: snapshot export/import and file queries for HTTP API handlers
-
-use diaryx_core::crdt::{BodyDocManager, FileMetadata, SqliteStorage, WorkspaceCrdt};
+//! - `WorkspaceStorage`: CRDT storage plus the file index, per workspace
+//! - `StorageCache`: shared cache of per-workspace storage (SQLite files or PostgreSQL)
+//! - `WorkspaceStore`: snapshot export/import, archives, server-side
+//!   snapshots, restores and file queries for HTTP API handlers
+
+use super::archive::{self, ArchiveAttachment, ArchiveWriter, WorkspaceArchive};
+#[cfg(feature = "postgres")]
+use super::postgres_storage::PostgresCrdtStorage;
+#[cfg(feature = "postgres")]
+use crate::db::PostgresPool;
+use diaryx_core::crdt::{
+    BodyDocManager, CrdtStorage, CrdtUpdate, FileMetadata, SqliteStorage, StorageResult,
+    WorkspaceCrdt,
+};
 use diaryx_core::metadata_writer::FrontmatterMetadata;
 use diaryx_core::{frontmatter, link_parser};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::{Cursor, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use tracing::{info, warn};
 use zip::write::FileOptions;
@@ -25,6 +35,14 @@ pub enum SnapshotError {
     Storage(String),
     Parse(String),
     ZipFormat(zip::result::ZipError),
+    /// An archive entry doesn't match the checksum in its manifest
+    Checksum(String),
+    /// The update log of this document no longer reaches back far enough
+    HistoryUnavailable(String),
+    /// No server-side snapshot with that ID
+    NotFound,
+    /// Archives can only be imported into a workspace with no documents
+    NotEmpty,
 }
 
 impl std::fmt::Display for SnapshotError {
@@ -35,6 +53,14 @@ impl std::fmt::Display for SnapshotError {
             SnapshotError::Storage(e) => write!(f, "Storage error: {}", e),
             SnapshotError::Parse(e) => write!(f, "Parse error: {}", e),
             SnapshotError::ZipFormat(e) => write!(f, "Zip format error: {}", e),
+            SnapshotError::Checksum(name) => write!(f, "Checksum mismatch for {}", name),
+            SnapshotError::HistoryUnavailable(name) => write!(
+                f,
+                "History of {} has been compacted; restore from a snapshot instead",
+                name
+            ),
+            SnapshotError::NotFound => write!(f, "Snapshot not found"),
+            SnapshotError::NotEmpty => write!(f, "Workspace already has documents"),
         }
     }
 }
@@ -74,27 +100,140 @@ pub struct SnapshotImportResult {
     pub files_imported: usize,
 }
 
+#[derive(Debug, Serialize)]
+pub struct ArchiveImportResult {
+    pub documents: usize,
+    pub updates: usize,
+}
+
+/// A server-side snapshot: a workspace archive kept by the server
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    /// Unix milliseconds
+    pub created_at: i64,
+    pub label: Option<String>,
+    /// Archive size in bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreResult {
+    /// Files re-created or changed back
+    pub files_restored: usize,
+    /// Files that didn't exist at the restore point
+    pub files_deleted: usize,
+    /// Snapshot taken just before restoring, to undo the restore
+    pub backup_snapshot_id: Option<String>,
+    /// Changes made, as (doc ID, Yjs update), for rooms that have the
+    /// documents loaded (see `SyncV2State::restored`)
+    #[serde(skip)]
+    pub updates: Vec<(String, Vec<u8>)>,
+}
+
 // ==================== StorageCache ====================
 
-/// Shared cache of per-workspace `SqliteStorage` connections.
+/// Row returned by [`WorkspaceStorage::query_active_files`]: (path, title, part_of)
+pub type FileIndexRow = (String, Option<String>, Option<String>);
+
+/// CRDT storage for one workspace, plus the file index that backs the
+/// Files-Ready manifest.
+///
+/// Implemented by `SqliteStorage` (one database file per workspace) and, with
+/// the `postgres` feature, by `PostgresCrdtStorage` (shared tables keyed by
+/// workspace ID).
+pub trait WorkspaceStorage: CrdtStorage {
+    /// Insert or replace a file's index entry.
+    fn update_file_index(
+        &self,
+        path: &str,
+        title: Option<&str>,
+        part_of: Option<&str>,
+        deleted: bool,
+        modified_at: i64,
+    ) -> StorageResult<()>;
+
+    /// Non-deleted files, ordered by path.
+    fn query_active_files(&self) -> StorageResult<Vec<FileIndexRow>>;
+
+    /// Remove every file index entry.
+    fn clear_file_index(&self) -> StorageResult<()>;
+
+    /// Names of documents with stored state or any updates.
+    fn list_all_docs(&self) -> StorageResult<Vec<String>>;
+
+    /// Append updates keeping their timestamps and device attribution.
+    fn import_updates(&self, updates: &[CrdtUpdate]) -> StorageResult<()>;
+}
+
+impl WorkspaceStorage for SqliteStorage {
+    fn update_file_index(
+        &self,
+        path: &str,
+        title: Option<&str>,
+        part_of: Option<&str>,
+        deleted: bool,
+        modified_at: i64,
+    ) -> StorageResult<()> {
+        SqliteStorage::update_file_index(self, path, title, part_of, deleted, modified_at)
+    }
+
+    fn query_active_files(&self) -> StorageResult<Vec<FileIndexRow>> {
+        SqliteStorage::query_active_files(self)
+    }
+
+    fn clear_file_index(&self) -> StorageResult<()> {
+        SqliteStorage::clear_file_index(self)
+    }
+
+    fn list_all_docs(&self) -> StorageResult<Vec<String>> {
+        SqliteStorage::list_all_docs(self)
+    }
+
+    fn import_updates(&self, updates: &[CrdtUpdate]) -> StorageResult<()> {
+        SqliteStorage::import_updates(self, updates)
+    }
+}
+
+/// Where workspace CRDT data lives
+enum StorageBackend {
+    /// One SQLite file per workspace in this directory
+    Sqlite(PathBuf),
+    /// Shared PostgreSQL tables
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresPool),
+}
+
+/// Shared cache of per-workspace storage handles.
 ///
 /// Used by both `DiaryxHook` (for sync persistence) and `WorkspaceStore`
 /// (for HTTP API snapshot operations) to avoid duplicate connections.
 pub struct StorageCache {
-    workspaces_dir: PathBuf,
-    cache: RwLock<HashMap<String, Arc<SqliteStorage>>>,
+    backend: StorageBackend,
+    cache: RwLock<HashMap<String, Arc<dyn WorkspaceStorage>>>,
 }
 
 impl StorageCache {
+    /// Store each workspace in `<workspaces_dir>/<workspace_id>.db`.
     pub fn new(workspaces_dir: PathBuf) -> Self {
+        Self::with_backend(StorageBackend::Sqlite(workspaces_dir))
+    }
+
+    /// Store every workspace in PostgreSQL.
+    #[cfg(feature = "postgres")]
+    pub fn postgres(pool: PostgresPool) -> Self {
+        Self::with_backend(StorageBackend::Postgres(pool))
+    }
+
+    fn with_backend(backend: StorageBackend) -> Self {
         Self {
-            workspaces_dir,
+            backend,
             cache: RwLock::new(HashMap::new()),
         }
     }
 
     /// Get or create storage for a workspace.
-    pub fn get_storage(&self, workspace_id: &str) -> Result<Arc<SqliteStorage>, String> {
+    pub fn get_storage(&self, workspace_id: &str) -> Result<Arc<dyn WorkspaceStorage>, String> {
         // Check cache first
         {
             let cache = self.cache.read().unwrap();
@@ -104,10 +243,17 @@ impl StorageCache {
         }
 
         // Create new storage
-        let db_path = self.workspaces_dir.join(format!("{}.db", workspace_id));
-        let storage = SqliteStorage::open(&db_path)
-            .map_err(|e| format!("Failed to open storage for {}: {}", workspace_id, e))?;
-        let storage = Arc::new(storage);
+        let storage: Arc<dyn WorkspaceStorage> = match &self.backend {
+            StorageBackend::Sqlite(dir) => {
+                let storage = SqliteStorage::open(sqlite_path(dir, workspace_id))
+                    .map_err(|e| format!("Failed to open storage for {}: {}", workspace_id, e))?;
+                Arc::new(storage)
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => {
+                Arc::new(PostgresCrdtStorage::new(pool.clone(), workspace_id))
+            }
+        };
 
         // Cache it
         {
@@ -117,6 +263,193 @@ impl StorageCache {
 
         Ok(storage)
     }
+
+    /// Whether a workspace has any stored CRDT data.
+    pub fn exists(&self, workspace_id: &str) -> bool {
+        match &self.backend {
+            StorageBackend::Sqlite(dir) => sqlite_path(dir, workspace_id).exists(),
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => PostgresCrdtStorage::new(pool.clone(), workspace_id)
+                .exists()
+                .unwrap_or(false),
+        }
+    }
+
+    /// Bytes used by a workspace: file sizes (including WAL files) for
+    /// SQLite, stored state and update bytes for PostgreSQL.
+    pub fn size(&self, workspace_id: &str) -> u64 {
+        match &self.backend {
+            StorageBackend::Sqlite(dir) => {
+                let db_path = sqlite_path(dir, workspace_id);
+                ["", "-wal", "-shm"]
+                    .iter()
+                    .filter_map(|suffix| {
+                        let mut path = db_path.clone().into_os_string();
+                        path.push(suffix);
+                        std::fs::metadata(path).ok()
+                    })
+                    .map(|meta| meta.len())
+                    .sum()
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => PostgresCrdtStorage::new(pool.clone(), workspace_id)
+                .size_bytes()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Delete all of a workspace's CRDT data. Returns false if there was none.
+    pub fn delete(&self, workspace_id: &str) -> Result<bool, String> {
+        // Drop the cached connection before removing its files
+        self.cache.write().unwrap().remove(workspace_id);
+
+        match &self.backend {
+            StorageBackend::Sqlite(dir) => {
+                let db_path = sqlite_path(dir, workspace_id);
+                let existed = db_path.exists();
+                for suffix in ["", "-wal", "-shm"] {
+                    let mut path = db_path.clone().into_os_string();
+                    path.push(suffix);
+                    match std::fs::remove_file(&path) {
+                        Ok(()) => {}
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(format!("Failed to delete {:?}: {}", path, e)),
+                    }
+                }
+                let snapshots = snapshot_dir(dir, workspace_id);
+                match std::fs::remove_dir_all(&snapshots) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(format!("Failed to delete {:?}: {}", snapshots, e)),
+                }
+                Ok(existed)
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => PostgresCrdtStorage::new(pool.clone(), workspace_id)
+                .delete_all()
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Keep an archive as a server-side snapshot of a workspace.
+    pub fn save_snapshot(
+        &self,
+        workspace_id: &str,
+        info: &SnapshotInfo,
+        archive: &[u8],
+    ) -> Result<(), String> {
+        match &self.backend {
+            StorageBackend::Sqlite(dir) => {
+                let dir = snapshot_dir(dir, workspace_id);
+                std::fs::create_dir_all(&dir)
+                    .map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+                // Write under a temporary name so a partial file is never listed
+                let partial = dir.join(format!("{}.partial", info.id));
+                let path = dir.join(format!("{}.zip", info.id));
+                std::fs::write(&partial, archive)
+                    .and_then(|()| std::fs::rename(&partial, &path))
+                    .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => PostgresCrdtStorage::new(pool.clone(), workspace_id)
+                .save_snapshot(info, archive)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Server-side snapshots of a workspace, newest first.
+    pub fn list_snapshots(&self, workspace_id: &str) -> Result<Vec<SnapshotInfo>, String> {
+        let mut snapshots = match &self.backend {
+            StorageBackend::Sqlite(dir) => {
+                let dir = snapshot_dir(dir, workspace_id);
+                let entries = match std::fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+                    Err(e) => return Err(format!("Failed to read {:?}: {}", dir, e)),
+                };
+
+                let mut snapshots = Vec::new();
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.extension().is_none_or(|ext| ext != "zip") {
+                        continue;
+                    }
+                    let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+                        continue;
+                    };
+                    let manifest = std::fs::File::open(&path)
+                        .map_err(SnapshotError::from)
+                        .and_then(archive::read_manifest);
+                    match manifest {
+                        Ok(manifest) => snapshots.push(SnapshotInfo {
+                            id: id.to_string(),
+                            created_at: manifest.created_at,
+                            label: manifest.label,
+                            size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                        }),
+                        Err(e) => warn!("Skipping unreadable snapshot {:?}: {}", path, e),
+                    }
+                }
+                snapshots
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => PostgresCrdtStorage::new(pool.clone(), workspace_id)
+                .list_snapshots()
+                .map_err(|e| e.to_string())?,
+        };
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        Ok(snapshots)
+    }
+
+    /// The archive of a server-side snapshot, if it exists.
+    pub fn load_snapshot(
+        &self,
+        workspace_id: &str,
+        snapshot_id: &str,
+    ) -> Result<Option<Vec<u8>>, String> {
+        match &self.backend {
+            StorageBackend::Sqlite(dir) => {
+                let path = snapshot_dir(dir, workspace_id).join(format!("{}.zip", snapshot_id));
+                match std::fs::read(&path) {
+                    Ok(bytes) => Ok(Some(bytes)),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(format!("Failed to read {:?}: {}", path, e)),
+                }
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => PostgresCrdtStorage::new(pool.clone(), workspace_id)
+                .load_snapshot(snapshot_id)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Delete a server-side snapshot. Returns false if it didn't exist.
+    pub fn delete_snapshot(&self, workspace_id: &str, snapshot_id: &str) -> Result<bool, String> {
+        match &self.backend {
+            StorageBackend::Sqlite(dir) => {
+                let path = snapshot_dir(dir, workspace_id).join(format!("{}.zip", snapshot_id));
+                match std::fs::remove_file(&path) {
+                    Ok(()) => Ok(true),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+                    Err(e) => Err(format!("Failed to delete {:?}: {}", path, e)),
+                }
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => PostgresCrdtStorage::new(pool.clone(), workspace_id)
+                .delete_snapshot(snapshot_id)
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Path of a workspace's SQLite database file.
+fn sqlite_path(workspaces_dir: &Path, workspace_id: &str) -> PathBuf {
+    workspaces_dir.join(format!("{}.db", workspace_id))
+}
+
+/// Directory holding a workspace's server-side snapshots with the SQLite backend.
+fn snapshot_dir(workspaces_dir: &Path, workspace_id: &str) -> PathBuf {
+    workspaces_dir.join("snapshots").join(workspace_id)
 }
 
 // ==================== WorkspaceStore ====================
@@ -124,7 +457,7 @@ impl StorageCache {
 /// Provides snapshot export/import and file queries for HTTP API handlers.
 ///
 /// This is a stateless wrapper around `StorageCache` that operates directly
-/// on `SqliteStorage`, `WorkspaceCrdt`, and `BodyDocManager` without needing
+/// on `WorkspaceStorage`, `WorkspaceCrdt`, and `BodyDocManager` without needing
 /// broadcast channels or connection tracking.
 pub struct WorkspaceStore {
     storage_cache: Arc<StorageCache>,
@@ -151,83 +484,222 @@ impl WorkspaceStore {
         workspace.file_count()
     }
 
+    /// Bytes of storage used by a workspace (see [`StorageCache::size`]).
+    pub fn storage_size(&self, workspace_id: &str) -> u64 {
+        self.storage_cache.size(workspace_id)
+    }
+
+    /// Compact every document in a workspace, keeping the newest
+    /// `keep_updates` updates of each. Returns how many documents were compacted.
+    ///
+    /// Workspaces that have never synced have no stored data and are skipped.
+    pub fn compact(&self, workspace_id: &str, keep_updates: usize) -> Result<usize, String> {
+        if !self.storage_cache.exists(workspace_id) {
+            return Ok(0);
+        }
+
+        let storage = self.storage_cache.get_storage(workspace_id)?;
+        let docs = storage.list_docs().map_err(|e| e.to_string())?;
+        for doc in &docs {
+            storage
+                .compact(doc, keep_updates)
+                .map_err(|e| format!("Failed to compact {}: {}", doc, e))?;
+        }
+        Ok(docs.len())
+    }
+
+    /// Delete a workspace's CRDT data. Returns false if there was none.
+    pub fn delete_files(&self, workspace_id: &str) -> Result<bool, String> {
+        self.storage_cache.delete(workspace_id)
+    }
+
     /// Export a workspace snapshot as a zip archive (markdown only).
     pub fn export_snapshot_zip(&self, workspace_id: &str) -> Result<Vec<u8>, SnapshotError> {
-        let storage = self
-            .storage_cache
-            .get_storage(workspace_id)
-            .map_err(SnapshotError::Storage)?;
+        let storage = self.storage(workspace_id)?;
 
         let workspace_doc_name = format!("workspace:{}", workspace_id);
         let workspace = WorkspaceCrdt::load_with_name(storage.clone(), workspace_doc_name)
             .map_err(|e| SnapshotError::Storage(e.to_string()))?;
         let body_docs = BodyDocManager::new(storage);
 
-        let files = workspace.list_files();
-
-        let mut id_to_path: HashMap<String, String> = HashMap::new();
-        for (key, _meta) in &files {
-            if key.contains('/') || key.ends_with(".md") {
-                id_to_path.insert(key.clone(), key.clone());
-            } else if let Some(path) = workspace.get_path(key) {
-                id_to_path.insert(key.clone(), path.to_string_lossy().to_string());
-            }
-        }
-
         let cursor = Cursor::new(Vec::new());
         let mut zip = ZipWriter::new(cursor);
         let options = FileOptions::<()>::default()
             .compression_method(CompressionMethod::Deflated)
             .unix_permissions(0o644);
 
-        for (key, meta) in files {
-            if meta.deleted {
-                continue;
-            }
+        for (path, content) in Self::render_files(workspace_id, &workspace, &body_docs)? {
+            zip.start_file(path, options)?;
+            zip.write_all(content.as_bytes())?;
+        }
 
-            let path = match Self::resolve_snapshot_path(&key, &id_to_path) {
-                Some(path) => path,
-                None => {
-                    warn!("Snapshot export: skipping unresolved path for {}", key);
-                    continue;
-                }
-            };
+        let cursor = zip.finish()?;
+        Ok(cursor.into_inner())
+    }
 
-            let mut export_meta = meta.clone();
-            export_meta.part_of = export_meta
-                .part_of
-                .and_then(|value| Self::resolve_snapshot_path(&value, &id_to_path));
+    /// Export a full-fidelity archive of a workspace: every CRDT document
+    /// with its update history, the files rendered as markdown, and a
+    /// checksummed manifest (see the `archive` module).
+    pub fn export_archive(
+        &self,
+        workspace_id: &str,
+        label: Option<&str>,
+    ) -> Result<Vec<u8>, SnapshotError> {
+        let storage = self.storage(workspace_id)?;
+        let workspace =
+            WorkspaceCrdt::load_with_name(storage.clone(), format!("workspace:{}", workspace_id))?;
+        let body_docs = BodyDocManager::new(storage.clone());
 
-            if let Some(contents) = export_meta.contents.take() {
-                let resolved: Vec<String> = contents
-                    .into_iter()
-                    .filter_map(|value| Self::resolve_snapshot_path(&value, &id_to_path))
-                    .collect();
-                export_meta.contents = Some(resolved);
+        let mut writer = ArchiveWriter::new(workspace_id, label);
+        writer.add_documents(storage.as_ref())?;
+        for (path, content) in Self::render_files(workspace_id, &workspace, &body_docs)? {
+            writer.add_file(&path, &content)?;
+        }
+        for (_, meta) in workspace.list_active_files() {
+            for attachment in meta.attachments.into_iter().filter(|a| !a.deleted) {
+                writer.add_attachment_ref(ArchiveAttachment {
+                    path: attachment.path,
+                    hash: attachment.hash,
+                    mime_type: attachment.mime_type,
+                    size: attachment.size,
+                    included: false,
+                });
             }
+        }
+        writer.finish()
+    }
 
-            let metadata_json = serde_json::to_value(&export_meta)?;
-            let fm = FrontmatterMetadata::from_json_with_file_path(&metadata_json, Some(&path));
-            let yaml = fm.to_yaml();
+    /// Import an archive, history included, into a workspace that has no
+    /// documents yet (e.g. when moving a workspace between servers).
+    pub fn import_archive(
+        &self,
+        workspace_id: &str,
+        bytes: &[u8],
+    ) -> Result<ArchiveImportResult, SnapshotError> {
+        let archive = WorkspaceArchive::read(bytes)?;
+        let storage = self.storage(workspace_id)?;
+        if !storage.list_all_docs()?.is_empty() {
+            return Err(SnapshotError::NotEmpty);
+        }
 
-            let body_key = format!("body:{}/{}", workspace_id, path);
-            let mut body = body_docs.get_or_create(&body_key).get_body();
-            if body.is_empty() && key != path {
-                let alt_key = format!("body:{}/{}", workspace_id, key);
-                body = body_docs.get_or_create(&alt_key).get_body();
-            }
-            let content = if yaml.is_empty() {
-                body
-            } else {
-                format!("---\n{}\n---\n\n{}", yaml, body)
-            };
+        let updates = archive.import_into(storage.as_ref(), workspace_id)?;
+        let workspace =
+            WorkspaceCrdt::load_with_name(storage.clone(), format!("workspace:{}", workspace_id))?;
+        Self::rebuild_file_index(storage.as_ref(), &workspace)?;
 
-            zip.start_file(path.replace('\\', "/"), options)?;
-            zip.write_all(content.as_bytes())?;
+        info!(
+            "Imported archive of {} into workspace {} ({} updates)",
+            archive.manifest.workspace_id, workspace_id, updates
+        );
+        Ok(ArchiveImportResult {
+            documents: archive.manifest.documents.len(),
+            updates,
+        })
+    }
+
+    /// Store an archive of the workspace as a server-side snapshot.
+    pub fn create_snapshot(
+        &self,
+        workspace_id: &str,
+        label: Option<&str>,
+    ) -> Result<SnapshotInfo, SnapshotError> {
+        let bytes = self.export_archive(workspace_id, label)?;
+        let manifest = archive::read_manifest(Cursor::new(&bytes))?;
+        let info = SnapshotInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: manifest.created_at,
+            label: manifest.label,
+            size: bytes.len() as u64,
+        };
+        self.storage_cache
+            .save_snapshot(workspace_id, &info, &bytes)
+            .map_err(SnapshotError::Storage)?;
+        Ok(info)
+    }
+
+    /// Server-side snapshots of a workspace, newest first.
+    pub fn list_snapshots(&self, workspace_id: &str) -> Result<Vec<SnapshotInfo>, SnapshotError> {
+        self.storage_cache
+            .list_snapshots(workspace_id)
+            .map_err(SnapshotError::Storage)
+    }
+
+    /// The archive bytes of a server-side snapshot.
+    pub fn snapshot_archive(
+        &self,
+        workspace_id: &str,
+        snapshot_id: &str,
+    ) -> Result<Vec<u8>, SnapshotError> {
+        Self::check_snapshot_id(snapshot_id)?;
+        self.storage_cache
+            .load_snapshot(workspace_id, snapshot_id)
+            .map_err(SnapshotError::Storage)?
+            .ok_or(SnapshotError::NotFound)
+    }
+
+    /// Delete a server-side snapshot.
+    pub fn delete_snapshot(
+        &self,
+        workspace_id: &str,
+        snapshot_id: &str,
+    ) -> Result<(), SnapshotError> {
+        Self::check_snapshot_id(snapshot_id)?;
+        match self
+            .storage_cache
+            .delete_snapshot(workspace_id, snapshot_id)
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SnapshotError::NotFound),
+            Err(e) => Err(SnapshotError::Storage(e)),
         }
+    }
 
-        let cursor = zip.finish()?;
-        Ok(cursor.into_inner())
+    /// Delete all but the newest `keep` snapshots (0 keeps everything).
+    /// Returns how many were deleted.
+    pub fn prune_snapshots(&self, workspace_id: &str, keep: usize) -> Result<usize, SnapshotError> {
+        if keep == 0 {
+            return Ok(0);
+        }
+        let old = self.list_snapshots(workspace_id)?.into_iter().skip(keep);
+        let mut deleted = 0;
+        for snapshot in old {
+            self.delete_snapshot(workspace_id, &snapshot.id)?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+
+    /// Restore a workspace to a server-side snapshot.
+    ///
+    /// A snapshot of the current state is taken first so the restore can be
+    /// undone.
+    pub fn restore_snapshot(
+        &self,
+        workspace_id: &str,
+        snapshot_id: &str,
+    ) -> Result<RestoreResult, SnapshotError> {
+        let archive = WorkspaceArchive::read(&self.snapshot_archive(workspace_id, snapshot_id)?)?;
+        let target = archive.to_memory_storage(workspace_id)?;
+        self.restore_from(
+            workspace_id,
+            target,
+            &format!("Before restoring snapshot {}", snapshot_id),
+        )
+    }
+
+    /// Restore a workspace to how it was at `at` (Unix milliseconds) by
+    /// replaying its update log, e.g. to undo an accidental mass deletion.
+    ///
+    /// A snapshot of the current state is taken first so the restore can be
+    /// undone. Fails with [`SnapshotError::HistoryUnavailable`] if the log has
+    /// been compacted.
+    pub fn restore_to(&self, workspace_id: &str, at: i64) -> Result<RestoreResult, SnapshotError> {
+        let storage = self.storage(workspace_id)?;
+        let target = archive::state_at(storage.as_ref(), at)?;
+        let at = chrono::DateTime::from_timestamp_millis(at)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| at.to_string());
+        self.restore_from(workspace_id, target, &format!("Before restoring to {}", at))
     }
 
     /// Import a workspace snapshot zip into the CRDT store.
@@ -237,10 +709,7 @@ impl WorkspaceStore {
         bytes: &[u8],
         mode: SnapshotImportMode,
     ) -> Result<SnapshotImportResult, SnapshotError> {
-        let storage = self
-            .storage_cache
-            .get_storage(workspace_id)
-            .map_err(SnapshotError::Storage)?;
+        let storage = self.storage(workspace_id)?;
 
         let workspace_doc_name = format!("workspace:{}", workspace_id);
         let workspace = WorkspaceCrdt::load_with_name(storage.clone(), workspace_doc_name)
@@ -309,6 +778,190 @@ impl WorkspaceStore {
 
     // ==================== Private Helpers ====================
 
+    fn storage(&self, workspace_id: &str) -> Result<Arc<dyn WorkspaceStorage>, SnapshotError> {
+        self.storage_cache
+            .get_storage(workspace_id)
+            .map_err(SnapshotError::Storage)
+    }
+
+    /// Snapshot IDs are UUIDs; anything else can't name a stored snapshot.
+    fn check_snapshot_id(snapshot_id: &str) -> Result<(), SnapshotError> {
+        uuid::Uuid::parse_str(snapshot_id)
+            .map(|_| ())
+            .map_err(|_| SnapshotError::NotFound)
+    }
+
+    /// Each non-deleted file as `(path, markdown)`, with `part_of` and
+    /// `contents` resolved from doc IDs to paths.
+    fn render_files(
+        workspace_id: &str,
+        workspace: &WorkspaceCrdt,
+        body_docs: &BodyDocManager,
+    ) -> Result<Vec<(String, String)>, SnapshotError> {
+        let files = workspace.list_files();
+        let id_to_path = Self::snapshot_paths(workspace, &files);
+
+        let mut rendered = Vec::new();
+        for (key, meta) in files {
+            if meta.deleted {
+                continue;
+            }
+
+            let path = match Self::resolve_snapshot_path(&key, &id_to_path) {
+                Some(path) => path,
+                None => {
+                    warn!("Snapshot export: skipping unresolved path for {}", key);
+                    continue;
+                }
+            };
+
+            let mut export_meta = meta.clone();
+            export_meta.part_of = export_meta
+                .part_of
+                .and_then(|value| Self::resolve_snapshot_path(&value, &id_to_path));
+
+            if let Some(contents) = export_meta.contents.take() {
+                let resolved: Vec<String> = contents
+                    .into_iter()
+                    .filter_map(|value| Self::resolve_snapshot_path(&value, &id_to_path))
+                    .collect();
+                export_meta.contents = Some(resolved);
+            }
+
+            let metadata_json = serde_json::to_value(&export_meta)?;
+            let fm = FrontmatterMetadata::from_json_with_file_path(&metadata_json, Some(&path));
+            let yaml = fm.to_yaml();
+
+            let body_key = format!("body:{}/{}", workspace_id, path);
+            let mut body = body_docs.get_or_create(&body_key).get_body();
+            if body.is_empty() && key != path {
+                let alt_key = format!("body:{}/{}", workspace_id, key);
+                body = body_docs.get_or_create(&alt_key).get_body();
+            }
+            let content = if yaml.is_empty() {
+                body
+            } else {
+                format!("---\n{}\n---\n\n{}", yaml, body)
+            };
+
+            rendered.push((path.replace('\\', "/"), content));
+        }
+        Ok(rendered)
+    }
+
+    /// Map doc-ID keys to their paths
+    fn snapshot_paths(
+        workspace: &WorkspaceCrdt,
+        files: &[(String, FileMetadata)],
+    ) -> HashMap<String, String> {
+        let mut id_to_path = HashMap::new();
+        for (key, _meta) in files {
+            if key.contains('/') || key.ends_with(".md") {
+                id_to_path.insert(key.clone(), key.clone());
+            } else if let Some(path) = workspace.get_path(key) {
+                id_to_path.insert(key.clone(), path.to_string_lossy().to_string());
+            }
+        }
+        id_to_path
+    }
+
+    /// Back up the current state, then make the live workspace match
+    /// `target`.
+    ///
+    /// Changes are written as ordinary CRDT edits on top of the current
+    /// documents rather than by swapping their state, so clients that still
+    /// hold the newer state converge on the restored one instead of merging
+    /// it back in. The edits are also returned in [`RestoreResult::updates`]
+    /// since loaded rooms don't read storage again.
+    fn restore_from(
+        &self,
+        workspace_id: &str,
+        target: Arc<dyn CrdtStorage>,
+        backup_label: &str,
+    ) -> Result<RestoreResult, SnapshotError> {
+        let backup = self.create_snapshot(workspace_id, Some(backup_label))?;
+
+        let storage = self.storage(workspace_id)?;
+        let doc_name = format!("workspace:{}", workspace_id);
+        let live = WorkspaceCrdt::load_with_name(storage.clone(), doc_name.clone())?;
+        let live_before = live.encode_state_vector();
+        let live_bodies = BodyDocManager::new(storage.clone());
+        let restored = WorkspaceCrdt::load_with_name(target.clone(), doc_name.clone())?;
+        let restored_bodies = BodyDocManager::new(target);
+
+        let wanted: HashMap<String, FileMetadata> =
+            restored.list_active_files().into_iter().collect();
+        let mut result = RestoreResult {
+            backup_snapshot_id: Some(backup.id),
+            ..Default::default()
+        };
+
+        for (key, _) in live.list_active_files() {
+            if !wanted.contains_key(&key) {
+                live.delete_file(&key)?;
+                result.files_deleted += 1;
+            }
+        }
+
+        let id_to_path = Self::snapshot_paths(&restored, &restored.list_files());
+        for (key, meta) in &wanted {
+            let mut changed = live
+                .get_file(key)
+                .is_none_or(|current| !current.is_content_equal(meta));
+            if changed {
+                live.set_file(key, meta.clone())?;
+            }
+
+            let path = Self::resolve_snapshot_path(key, &id_to_path).unwrap_or_else(|| key.clone());
+            let body_key = format!("body:{}/{}", workspace_id, path);
+            let body = restored_bodies.get_or_create(&body_key).get_body();
+            let live_body = live_bodies.get_or_create(&body_key);
+            if live_body.get_body() != body {
+                let before = live_body.encode_state_vector();
+                live_body.set_body(&body)?;
+                result
+                    .updates
+                    .push((body_key, live_body.encode_diff(&before)?));
+                changed = true;
+            }
+
+            if changed {
+                result.files_restored += 1;
+            }
+        }
+
+        if result.files_restored + result.files_deleted > 0 {
+            result
+                .updates
+                .insert(0, (doc_name, live.encode_diff(&live_before)?));
+        }
+        Self::rebuild_file_index(storage.as_ref(), &live)?;
+
+        info!(
+            "Restored workspace {}: {} files restored, {} deleted",
+            workspace_id, result.files_restored, result.files_deleted
+        );
+        Ok(result)
+    }
+
+    /// Replace the file index with the files in `workspace`
+    fn rebuild_file_index(
+        storage: &dyn WorkspaceStorage,
+        workspace: &WorkspaceCrdt,
+    ) -> Result<(), SnapshotError> {
+        storage.clear_file_index()?;
+        for (key, meta) in workspace.list_files() {
+            storage.update_file_index(
+                &key,
+                meta.title.as_deref(),
+                meta.part_of.as_deref(),
+                meta.deleted,
+                meta.modified_at,
+            )?;
+        }
+        Ok(())
+    }
+
     fn resolve_snapshot_path(value: &str, id_to_path: &HashMap<String, String>) -> Option<String> {
         if value.contains('/') || value.ends_with(".md") {
             Some(value.to_string())
@@ -426,3 +1079,116 @@ impl WorkspaceStore {
         Ok((metadata, body))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diaryx_core::crdt::{MemoryStorage, UpdateOrigin};
+
+    fn write_file(store: &WorkspaceStore, workspace_id: &str, path: &str, body: &str) {
+        let storage = store.storage(workspace_id).unwrap();
+        let workspace =
+            WorkspaceCrdt::load_with_name(storage.clone(), format!("workspace:{}", workspace_id))
+                .unwrap();
+        workspace
+            .set_file(path, FileMetadata::new(Some(path.to_string())))
+            .unwrap();
+        BodyDocManager::new(storage)
+            .get_or_create(&format!("body:{}/{}", workspace_id, path))
+            .set_body(body)
+            .unwrap();
+    }
+
+    fn active_files(store: &WorkspaceStore, workspace_id: &str) -> Vec<String> {
+        let storage = store.storage(workspace_id).unwrap();
+        let workspace =
+            WorkspaceCrdt::load_with_name(storage, format!("workspace:{}", workspace_id)).unwrap();
+        let mut files: Vec<String> = workspace
+            .list_active_files()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_restore_to_undoes_mass_deletion() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = WorkspaceStore::new(Arc::new(StorageCache::new(dir.path().to_path_buf())));
+        write_file(&store, "ws", "a.md", "alpha");
+        write_file(&store, "ws", "b.md", "beta");
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let before_delete = chrono::Utc::now().timestamp_millis();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        write_file(&store, "ws", "a.md", "alpha, edited");
+        let storage = store.storage("ws").unwrap();
+        let workspace = WorkspaceCrdt::load_with_name(storage, "workspace:ws".into()).unwrap();
+        workspace.delete_file("a.md").unwrap();
+        workspace.delete_file("b.md").unwrap();
+        drop(workspace);
+        write_file(&store, "ws", "c.md", "gamma");
+        assert_eq!(active_files(&store, "ws"), vec!["c.md"]);
+
+        // A room that loaded the workspace before the restore
+        let storage = store.storage("ws").unwrap();
+        let live = WorkspaceCrdt::load_with_name(storage, "workspace:ws".into()).unwrap();
+        let room = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+        room.apply_update(&live.encode_diff(&[0]).unwrap(), UpdateOrigin::Remote)
+            .unwrap();
+        drop(live);
+
+        let result = store.restore_to("ws", before_delete).unwrap();
+        assert_eq!(result.files_restored, 2);
+        assert_eq!(result.files_deleted, 1);
+        assert_eq!(active_files(&store, "ws"), vec!["a.md", "b.md"]);
+
+        // The returned updates bring loaded rooms up to date
+        let doc_ids: Vec<&str> = result.updates.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(doc_ids[0], "workspace:ws");
+        assert!(doc_ids.contains(&"body:ws/a.md"));
+        room.apply_update(&result.updates[0].1, UpdateOrigin::Remote)
+            .unwrap();
+        let mut files: Vec<String> = room
+            .list_active_files()
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["a.md", "b.md"]);
+
+        // The backup snapshot undoes the restore
+        let backup = result.backup_snapshot_id.unwrap();
+        let undo = store.restore_snapshot("ws", &backup).unwrap();
+        assert_eq!(undo.files_deleted, 2);
+        assert_eq!(active_files(&store, "ws"), vec!["c.md"]);
+        assert_eq!(store.list_snapshots("ws").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_snapshots_are_pruned_and_ids_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = WorkspaceStore::new(Arc::new(StorageCache::new(dir.path().to_path_buf())));
+        write_file(&store, "ws", "a.md", "alpha");
+
+        for label in ["one", "two", "three"] {
+            store.create_snapshot("ws", Some(label)).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        assert_eq!(store.prune_snapshots("ws", 2).unwrap(), 1);
+        let labels: Vec<_> = store
+            .list_snapshots("ws")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.label)
+            .collect();
+        assert_eq!(labels, vec![Some("three".into()), Some("two".into())]);
+
+        assert!(matches!(
+            store.snapshot_archive("ws", "../../etc/passwd"),
+            Err(SnapshotError::NotFound)
+        ));
+    }
+}
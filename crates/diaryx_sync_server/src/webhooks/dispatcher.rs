@@ -0,0 +1,533 @@
+use axum::http::header::CONTENT_TYPE;
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{debug, error, warn};
+
+use crate::config::WebhookConfig;
+use crate::db::{AuthRepo, DbError, WebhookDeliveryStatus, WebhookInfo};
+use crate::metrics::metrics;
+
+use super::{
+    ChangeKind, DELIVERY_HEADER, EVENT_HEADER, EVENT_PING, EVENT_WORKSPACE_CHANGED, FileChange,
+    SIGNATURE_HEADER, TIMESTAMP_HEADER, WebhookPayload, check_url, sign, target::PublicResolver,
+};
+
+/// Deliveries kept in each webhook's log
+const DELIVERY_LOG_SIZE: usize = 100;
+
+/// How long the dispatcher trusts its record of which workspaces have
+/// webhooks. Registrations through this instance apply immediately; those
+/// made on another instance sharing the database apply within this window.
+const WATCH_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Files reported changed in one workspace
+struct Notice {
+    workspace_id: String,
+    changes: Vec<FileChange>,
+}
+
+/// Changes waiting for their workspace to go quiet
+struct Pending {
+    first: Instant,
+    last: Instant,
+    files: BTreeMap<String, ChangeKind>,
+}
+
+impl Pending {
+    fn due_at(&self, config: &WebhookConfig) -> Instant {
+        (self.last + config.debounce).min(self.first + config.max_delay)
+    }
+
+    fn add(&mut self, change: FileChange) {
+        let merged = match self.files.get(&change.path) {
+            Some(earlier) => earlier.then(change.change),
+            None => Some(change.change),
+        };
+        match merged {
+            Some(kind) => self.files.insert(change.path, kind),
+            None => self.files.remove(&change.path),
+        };
+    }
+}
+
+/// Collects file changes reported by the sync hook and delivers them to each
+/// workspace's webhooks.
+///
+/// Changes are batched per workspace until it has been quiet for
+/// [`WebhookConfig::debounce`] (or for at most [`WebhookConfig::max_delay`]
+/// while edits keep coming in). Failed deliveries are retried in the
+/// background with exponential backoff.
+pub struct WebhookDispatcher {
+    sender: Arc<Sender>,
+    notices: mpsc::UnboundedSender<Notice>,
+    /// Workspace ID -> (when checked, whether it has webhooks)
+    watched: Mutex<HashMap<String, (Instant, bool)>>,
+}
+
+impl WebhookDispatcher {
+    /// Start the dispatcher's debounce task. Must be called inside a Tokio
+    /// runtime; the task delivers what's still pending and stops when the
+    /// dispatcher is dropped.
+    ///
+    /// Fails if the HTTP client can't be built, rather than delivering
+    /// through a client without the redirect and private-address checks.
+    pub fn new(repo: Arc<AuthRepo>, config: WebhookConfig) -> Result<Self, reqwest::Error> {
+        let mut client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .user_agent(concat!("diaryx-sync-server/", env!("CARGO_PKG_VERSION")))
+            .redirect(reqwest::redirect::Policy::none());
+        if !config.allow_private_urls {
+            // Resolve again on every connection, so a host can't switch to a
+            // private address after it was registered
+            client = client.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = client.build()?;
+        let sender = Arc::new(Sender {
+            repo,
+            config,
+            client,
+        });
+
+        let (notices, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(debounce(sender.clone(), receiver));
+
+        Ok(Self {
+            sender,
+            notices,
+            watched: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether a workspace has webhooks, so the sync hook only works out
+    /// which files an update changed when someone is listening.
+    pub fn watches(&self, workspace_id: &str) -> bool {
+        if let Some((checked, watched)) = self.watched.lock().unwrap().get(workspace_id)
+            && checked.elapsed() < WATCH_CACHE_TTL
+        {
+            return *watched;
+        }
+
+        let watched = match self.sender.repo.get_workspace_webhooks(workspace_id) {
+            Ok(webhooks) => !webhooks.is_empty(),
+            Err(e) => {
+                error!("Failed to load webhooks for {}: {}", workspace_id, e);
+                false
+            }
+        };
+        self.watched
+            .lock()
+            .unwrap()
+            .insert(workspace_id.to_string(), (Instant::now(), watched));
+        watched
+    }
+
+    /// Forget whether a workspace has webhooks, after one was registered or
+    /// deleted.
+    pub fn invalidate(&self, workspace_id: &str) {
+        self.watched.lock().unwrap().remove(workspace_id);
+    }
+
+    /// Queue changed files for delivery once the workspace goes quiet.
+    pub fn notify(&self, workspace_id: &str, changes: Vec<FileChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        let notice = Notice {
+            workspace_id: workspace_id.to_string(),
+            changes,
+        };
+        if self.notices.send(notice).is_err() {
+            warn!(
+                "Webhook dispatcher stopped; dropping changes for {}",
+                workspace_id
+            );
+        }
+    }
+
+    /// Check a URL before registering it as a webhook; see [`check_url`].
+    pub async fn check_url(&self, url: &str) -> Result<(), String> {
+        check_url(url, self.sender.config.allow_private_urls).await
+    }
+
+    /// Send a `ping` to a webhook right away, returning the delivery ID.
+    pub fn ping(&self, webhook: WebhookInfo) -> Result<String, DbError> {
+        self.sender.enqueue(webhook, EVENT_PING, Vec::new())
+    }
+}
+
+/// Merge notices per workspace and hand each batch to the sender once due.
+async fn debounce(sender: Arc<Sender>, mut notices: mpsc::UnboundedReceiver<Notice>) {
+    let mut pending: HashMap<String, Pending> = HashMap::new();
+
+    loop {
+        let next_due = pending.values().map(|p| p.due_at(&sender.config)).min();
+        let notice = match next_due {
+            Some(due) => tokio::select! {
+                notice = notices.recv() => notice,
+                _ = tokio::time::sleep_until(due) => {
+                    let now = Instant::now();
+                    let ready: Vec<String> = pending
+                        .iter()
+                        .filter(|(_, p)| p.due_at(&sender.config) <= now)
+                        .map(|(workspace_id, _)| workspace_id.clone())
+                        .collect();
+                    for workspace_id in ready {
+                        if let Some(batch) = pending.remove(&workspace_id) {
+                            sender.workspace_changed(&workspace_id, batch.files);
+                        }
+                    }
+                    continue;
+                }
+            },
+            None => notices.recv().await,
+        };
+
+        let Some(notice) = notice else { break };
+        let now = Instant::now();
+        let batch = pending
+            .entry(notice.workspace_id)
+            .or_insert_with(|| Pending {
+                first: now,
+                last: now,
+                files: BTreeMap::new(),
+            });
+        batch.last = now;
+        for change in notice.changes {
+            batch.add(change);
+        }
+    }
+
+    for (workspace_id, batch) in pending {
+        sender.workspace_changed(&workspace_id, batch.files);
+    }
+}
+
+/// Signs and sends deliveries; shared by the debounce task and retries
+struct Sender {
+    repo: Arc<AuthRepo>,
+    config: WebhookConfig,
+    client: reqwest::Client,
+}
+
+impl Sender {
+    /// Deliver a batch of changes to every webhook of the workspace.
+    fn workspace_changed(
+        self: &Arc<Self>,
+        workspace_id: &str,
+        files: BTreeMap<String, ChangeKind>,
+    ) {
+        if files.is_empty() {
+            return;
+        }
+        let webhooks = match self.repo.get_workspace_webhooks(workspace_id) {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                error!("Failed to load webhooks for {}: {}", workspace_id, e);
+                return;
+            }
+        };
+
+        let files: Vec<FileChange> = files
+            .into_iter()
+            .map(|(path, change)| FileChange { path, change })
+            .collect();
+        for webhook in webhooks {
+            let webhook_id = webhook.id.clone();
+            if let Err(e) = self.enqueue(webhook, EVENT_WORKSPACE_CHANGED, files.clone()) {
+                error!("Failed to queue delivery for webhook {}: {}", webhook_id, e);
+            }
+        }
+    }
+
+    /// Log a delivery and start sending it in the background.
+    fn enqueue(
+        self: &Arc<Self>,
+        webhook: WebhookInfo,
+        event: &'static str,
+        files: Vec<FileChange>,
+    ) -> Result<String, DbError> {
+        let payload = WebhookPayload {
+            event: event.to_string(),
+            webhook_id: webhook.id.clone(),
+            workspace_id: webhook.workspace_id.clone(),
+            timestamp: Utc::now(),
+            files,
+        };
+        let payload = serde_json::to_value(&payload).unwrap_or_default();
+        let delivery_id = self
+            .repo
+            .create_webhook_delivery(&webhook.id, event, &payload)?;
+        if let Err(e) = self
+            .repo
+            .trim_webhook_deliveries(&webhook.id, DELIVERY_LOG_SIZE)
+        {
+            warn!(
+                "Failed to trim delivery log of webhook {}: {}",
+                webhook.id, e
+            );
+        }
+
+        let body = payload.to_string().into_bytes();
+        tokio::spawn(
+            self.clone()
+                .deliver(webhook, delivery_id.clone(), event, body),
+        );
+        Ok(delivery_id)
+    }
+
+    /// Send a delivery, retrying with exponential backoff until it succeeds
+    /// or runs out of attempts, and record each outcome in the log.
+    async fn deliver(
+        self: Arc<Self>,
+        webhook: WebhookInfo,
+        delivery_id: String,
+        event: &'static str,
+        body: Vec<u8>,
+    ) {
+        let max_attempts = self.config.max_attempts.max(1);
+        let mut delay = self.config.retry_delay;
+
+        for attempt in 1..=max_attempts {
+            let result = self.attempt(&webhook, &delivery_id, event, &body).await;
+            metrics().record_webhook_attempt(result.is_ok());
+
+            let last = attempt == max_attempts;
+            let (status, response_status, error) = match &result {
+                Ok(code) => (WebhookDeliveryStatus::Delivered, Some(*code), None),
+                Err((code, e)) if last => (WebhookDeliveryStatus::Failed, *code, Some(e.as_str())),
+                Err((code, e)) => (WebhookDeliveryStatus::Pending, *code, Some(e.as_str())),
+            };
+            if let Err(e) = self.repo.update_webhook_delivery(
+                &delivery_id,
+                status,
+                attempt,
+                response_status,
+                error,
+            ) {
+                warn!("Failed to record webhook delivery {}: {}", delivery_id, e);
+            }
+
+            match result {
+                Ok(_) => {
+                    debug!("Delivered {} to webhook {}", event, webhook.id);
+                    return;
+                }
+                Err((_, e)) if last => {
+                    warn!(
+                        "Giving up on {} delivery {} to webhook {} after {} attempts: {}",
+                        event, delivery_id, webhook.id, attempt, e
+                    );
+                    return;
+                }
+                Err((_, e)) => debug!(
+                    "Webhook {} delivery attempt {} failed ({}); retrying in {:?}",
+                    webhook.id, attempt, e, delay
+                ),
+            }
+
+            tokio::time::sleep(delay).await;
+            delay = delay.saturating_mul(2);
+
+            // Stop retrying once the webhook has been deleted
+            if !matches!(self.repo.get_webhook(&webhook.id), Ok(Some(_))) {
+                return;
+            }
+        }
+    }
+
+    /// POST a signed payload once. On failure, returns the response status
+    /// (if the receiver answered) and why it failed.
+    async fn attempt(
+        &self,
+        webhook: &WebhookInfo,
+        delivery_id: &str,
+        event: &str,
+        body: &[u8],
+    ) -> Result<u16, (Option<u16>, String)> {
+        // IP literals don't go through the resolver
+        check_url(&webhook.url, self.config.allow_private_urls)
+            .await
+            .map_err(|e| (None, e))?;
+
+        let timestamp = Utc::now().timestamp();
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event)
+            .header(DELIVERY_HEADER, delivery_id)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, body))
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(status.as_u16())
+        } else {
+            Err((Some(status.as_u16()), format!("HTTP {}", status)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{WebhookDeliveryInfo, init_database};
+    use crate::webhooks::TestReceiver;
+    use rusqlite::Connection;
+
+    fn test_config() -> WebhookConfig {
+        WebhookConfig {
+            debounce: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(20),
+            timeout: Duration::from_secs(5),
+            allow_private_urls: true,
+        }
+    }
+
+    fn setup() -> (Arc<AuthRepo>, String) {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        let repo = Arc::new(AuthRepo::new(conn));
+        let user_id = repo.get_or_create_user("owner@example.com").unwrap();
+        let workspace_id = repo.get_or_create_workspace(&user_id, "default").unwrap();
+        (repo, workspace_id)
+    }
+
+    /// Wait for a webhook's latest delivery to stop being pending
+    async fn settled(repo: &AuthRepo, webhook_id: &str) -> Vec<WebhookDeliveryInfo> {
+        for _ in 0..100 {
+            let log = repo.get_webhook_deliveries(webhook_id, 10).unwrap();
+            if log
+                .first()
+                .is_some_and(|d| d.status != WebhookDeliveryStatus::Pending)
+            {
+                return log;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("delivery still pending");
+    }
+
+    #[tokio::test]
+    async fn test_debounced_signed_delivery() {
+        let (repo, workspace_id) = setup();
+        let receiver = TestReceiver::start().await.unwrap();
+        let webhook = repo
+            .create_webhook(&workspace_id, &receiver.url(), None, None)
+            .unwrap();
+        let dispatcher = WebhookDispatcher::new(repo.clone(), test_config()).unwrap();
+        assert!(dispatcher.watches(&workspace_id));
+        assert!(!dispatcher.watches("other"));
+
+        dispatcher.notify(
+            &workspace_id,
+            vec![FileChange::new("a.md", ChangeKind::Created)],
+        );
+        dispatcher.notify(
+            &workspace_id,
+            vec![
+                FileChange::new("a.md", ChangeKind::Updated),
+                FileChange::new("b.md", ChangeKind::Updated),
+                FileChange::new("c.md", ChangeKind::Created),
+            ],
+        );
+        dispatcher.notify(
+            &workspace_id,
+            vec![FileChange::new("c.md", ChangeKind::Deleted)],
+        );
+
+        let received = receiver.wait_for(1, Duration::from_secs(5)).await;
+        assert_eq!(received.len(), 1);
+        let delivery = &received[0];
+        assert_eq!(delivery.event, EVENT_WORKSPACE_CHANGED);
+        assert!(delivery.verify(&webhook.secret));
+        assert!(!delivery.verify("whsec_wrong"));
+
+        let payload = delivery.payload().unwrap();
+        assert_eq!(payload.workspace_id, workspace_id);
+        assert_eq!(payload.webhook_id, webhook.id);
+        assert_eq!(
+            payload.files,
+            vec![
+                FileChange::new("a.md", ChangeKind::Created),
+                FileChange::new("b.md", ChangeKind::Updated),
+            ]
+        );
+
+        // Nothing else arrives: the three notices were one batch
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(receiver.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried_and_logged() {
+        let (repo, workspace_id) = setup();
+        let receiver = TestReceiver::start().await.unwrap();
+        let webhook = repo
+            .create_webhook(&workspace_id, &receiver.url(), None, None)
+            .unwrap();
+        let dispatcher = WebhookDispatcher::new(repo.clone(), test_config()).unwrap();
+
+        receiver.fail_next(2);
+        let delivery_id = dispatcher.ping(webhook.clone()).unwrap();
+        let received = receiver.wait_for(1, Duration::from_secs(5)).await;
+        assert_eq!(received[0].event, EVENT_PING);
+        assert_eq!(received[0].delivery_id, delivery_id);
+        assert_eq!(receiver.requests(), 3);
+
+        let log = settled(&repo, &webhook.id).await;
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(log[0].attempts, 3);
+        assert_eq!(log[0].response_status, Some(200));
+
+        // Every attempt fails: the delivery is marked failed
+        receiver.fail_next(3);
+        let failed_id = dispatcher.ping(webhook.clone()).unwrap();
+        let log = settled(&repo, &webhook.id).await;
+        assert_eq!(log[0].id, failed_id);
+        assert_eq!(log[0].status, WebhookDeliveryStatus::Failed);
+        assert_eq!(log[0].attempts, 3);
+        assert_eq!(log[0].response_status, Some(500));
+        assert_eq!(
+            log[0].error.as_deref(),
+            Some("HTTP 500 Internal Server Error")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_private_urls_are_refused() {
+        let (repo, workspace_id) = setup();
+        let receiver = TestReceiver::start().await.unwrap();
+        let dispatcher = WebhookDispatcher::new(
+            repo.clone(),
+            WebhookConfig {
+                allow_private_urls: false,
+                ..test_config()
+            },
+        )
+        .unwrap();
+        assert!(dispatcher.check_url(&receiver.url()).await.is_err());
+
+        // Registered before the check (or resolving differently since): every
+        // delivery attempt is refused without a request
+        let webhook = repo
+            .create_webhook(&workspace_id, &receiver.url(), None, None)
+            .unwrap();
+        dispatcher.ping(webhook.clone()).unwrap();
+        let log = settled(&repo, &webhook.id).await;
+        assert_eq!(log[0].status, WebhookDeliveryStatus::Failed);
+        assert_eq!(log[0].response_status, None);
+        assert_eq!(receiver.requests(), 0);
+    }
+}
@@ -0,0 +1,255 @@
+//! Outbound webhooks called when a synced workspace changes.
+//!
+//! `DiaryxHook` reports the files each persisted sync update touches: the path
+//! of a body document, or the files a workspace update creates, changes or
+//! deletes (from `WorkspaceCrdt::apply_update_tracking_changes`). The
+//! [`WebhookDispatcher`] collects these per workspace and, once the workspace
+//! has been quiet for the debounce period, POSTs one signed
+//! `workspace.changed` payload to each registered webhook, retrying failures
+//! with exponential backoff and logging every delivery.
+//!
+//! Payloads are signed with the webhook's secret; see [`sign`] for the scheme
+//! and [`verify`] for checking it on the receiving end. Webhook URLs must
+//! resolve to public addresses (see [`check_url`]) unless
+//! `WEBHOOK_ALLOW_PRIVATE_URLS` is set. [`TestReceiver`] is a local receiver
+//! for integration tests.
+
+mod dispatcher;
+mod receiver;
+mod target;
+
+pub use dispatcher::WebhookDispatcher;
+pub use receiver::{ReceivedWebhook, TestReceiver};
+pub use target::check_url;
+
+use chrono::{DateTime, Utc};
+use diaryx_core::crdt::{FileMetadata, MemoryStorage, StorageResult, UpdateOrigin, WorkspaceCrdt};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Event sent after a workspace's files changed
+pub const EVENT_WORKSPACE_CHANGED: &str = "workspace.changed";
+/// Event sent by `POST .../webhooks/{id}/ping` to test a receiver
+pub const EVENT_PING: &str = "ping";
+
+/// Header carrying the event name
+pub const EVENT_HEADER: &str = "x-diaryx-event";
+/// Header carrying the delivery ID (stable across retries)
+pub const DELIVERY_HEADER: &str = "x-diaryx-delivery";
+/// Header carrying the Unix time the payload was signed at
+pub const TIMESTAMP_HEADER: &str = "x-diaryx-timestamp";
+/// Header carrying the payload signature
+pub const SIGNATURE_HEADER: &str = "x-diaryx-signature";
+
+/// How a file changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl ChangeKind {
+    /// Combine with a later change to the same file, or `None` if the two
+    /// cancel out (created and deleted again before the webhook fired).
+    pub fn then(self, later: ChangeKind) -> Option<ChangeKind> {
+        use ChangeKind::*;
+        match (self, later) {
+            (Created, Deleted) => None,
+            (_, Deleted) => Some(Deleted),
+            (Deleted, Created) => Some(Updated),
+            (Deleted, Updated) => Some(Deleted),
+            (Created, _) | (_, Created) => Some(Created),
+            (Updated, Updated) => Some(Updated),
+        }
+    }
+}
+
+/// A changed file in a `workspace.changed` payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    /// Workspace-relative path; a rename shows up as a deletion of the old
+    /// path and a creation of the new one
+    pub path: String,
+    pub change: ChangeKind,
+}
+
+impl FileChange {
+    pub fn new(path: impl Into<String>, change: ChangeKind) -> Self {
+        Self {
+            path: path.into(),
+            change,
+        }
+    }
+}
+
+/// JSON body POSTed to a webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub event: String,
+    pub webhook_id: String,
+    pub workspace_id: String,
+    pub timestamp: DateTime<Utc>,
+    /// Changed files, sorted by path (`workspace.changed` only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileChange>,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn signer(secret: &str, timestamp: i64, body: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Signature for a payload: `sha256=` followed by the hex HMAC-SHA256 of
+/// `"{timestamp}.{body}"` keyed with the webhook secret.
+///
+/// The timestamp is sent in [`TIMESTAMP_HEADER`]; including it in the signed
+/// data lets receivers reject replayed deliveries.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(signer(secret, timestamp, body).finalize().into_bytes())
+    )
+}
+
+/// Check a [`SIGNATURE_HEADER`] value in constant time.
+pub fn verify(secret: &str, timestamp: i64, body: &[u8], signature: &str) -> bool {
+    let Some(digest) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex_digest| hex::decode(hex_digest).ok())
+    else {
+        return false;
+    };
+    signer(secret, timestamp, body)
+        .verify_slice(&digest)
+        .is_ok()
+}
+
+/// Files that applying `update` to `workspace` creates, changes or deletes.
+///
+/// The update is applied to a scratch copy, so `workspace` is unchanged.
+pub fn workspace_changes(
+    workspace: &WorkspaceCrdt,
+    update: &[u8],
+) -> StorageResult<Vec<FileChange>> {
+    let before: HashMap<String, FileMetadata> = workspace.list_files().into_iter().collect();
+
+    let scratch = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+    scratch.apply_update(&workspace.encode_state_as_update(), UpdateOrigin::Sync)?;
+    let (_, changed_keys, _) =
+        scratch.apply_update_tracking_changes(update, UpdateOrigin::Remote)?;
+    let after: HashMap<String, FileMetadata> = scratch.list_files().into_iter().collect();
+
+    let mut changes = Vec::new();
+    for key in changed_keys {
+        let was_active = before.get(&key).is_some_and(|m| !m.deleted);
+        let is_active = after.get(&key).is_some_and(|m| !m.deleted);
+        match (was_active, is_active) {
+            (false, true) => changes.push(FileChange::new(
+                key_path(&scratch, &key),
+                ChangeKind::Created,
+            )),
+            (true, false) => changes.push(FileChange::new(
+                key_path(workspace, &key),
+                ChangeKind::Deleted,
+            )),
+            (true, true) => {
+                let old_path = key_path(workspace, &key);
+                let new_path = key_path(&scratch, &key);
+                if old_path == new_path {
+                    changes.push(FileChange::new(new_path, ChangeKind::Updated));
+                } else {
+                    changes.push(FileChange::new(old_path, ChangeKind::Deleted));
+                    changes.push(FileChange::new(new_path, ChangeKind::Created));
+                }
+            }
+            (false, false) => {}
+        }
+    }
+    Ok(changes)
+}
+
+/// Path of a file-map key, which is either the path itself or a doc ID
+fn key_path(workspace: &WorkspaceCrdt, key: &str) -> String {
+    if key.contains('/') || key.ends_with(".md") {
+        return key.to_string();
+    }
+    workspace
+        .get_path(key)
+        .map(|path| path.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let body = br#"{"event":"ping"}"#;
+        let signature = sign("whsec_test", 1_700_000_000, body);
+        assert!(signature.starts_with("sha256="));
+        assert!(verify("whsec_test", 1_700_000_000, body, &signature));
+        assert!(!verify("whsec_other", 1_700_000_000, body, &signature));
+        assert!(!verify("whsec_test", 1_700_000_001, body, &signature));
+        assert!(!verify("whsec_test", 1_700_000_000, b"{}", &signature));
+        assert!(!verify("whsec_test", 1_700_000_000, body, "sha256=zz"));
+    }
+
+    #[test]
+    fn test_change_kinds_combine() {
+        use ChangeKind::*;
+        assert_eq!(Created.then(Updated), Some(Created));
+        assert_eq!(Updated.then(Created), Some(Created));
+        assert_eq!(Created.then(Deleted), None);
+        assert_eq!(Updated.then(Deleted), Some(Deleted));
+        assert_eq!(Deleted.then(Created), Some(Updated));
+        assert_eq!(Deleted.then(Updated), Some(Deleted));
+        assert_eq!(Updated.then(Updated), Some(Updated));
+    }
+
+    #[test]
+    fn test_workspace_changes() {
+        let workspace = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+        workspace
+            .set_file("notes/a.md", FileMetadata::new(Some("A".into())))
+            .unwrap();
+        workspace
+            .set_file("notes/b.md", FileMetadata::new(Some("B".into())))
+            .unwrap();
+
+        // A peer deletes one file, edits another and creates a third
+        let peer = WorkspaceCrdt::new(Arc::new(MemoryStorage::new()));
+        peer.apply_update(&workspace.encode_state_as_update(), UpdateOrigin::Sync)
+            .unwrap();
+        peer.delete_file("notes/a.md").unwrap();
+        peer.set_file("notes/b.md", FileMetadata::new(Some("B2".into())))
+            .unwrap();
+        peer.set_file("notes/c.md", FileMetadata::new(Some("C".into())))
+            .unwrap();
+        let update = peer.encode_diff(&workspace.encode_state_vector()).unwrap();
+
+        let mut changes = workspace_changes(&workspace, &update).unwrap();
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            changes,
+            vec![
+                FileChange::new("notes/a.md", ChangeKind::Deleted),
+                FileChange::new("notes/b.md", ChangeKind::Updated),
+                FileChange::new("notes/c.md", ChangeKind::Created),
+            ]
+        );
+        // The workspace itself is untouched
+        assert_eq!(workspace.list_active_files().len(), 2);
+    }
+}
@@ -0,0 +1,161 @@
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, oneshot};
+
+use super::{
+    DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER, WebhookPayload, verify,
+};
+
+/// A delivery accepted by a [`TestReceiver`]
+#[derive(Debug, Clone)]
+pub struct ReceivedWebhook {
+    pub event: String,
+    pub delivery_id: String,
+    pub timestamp: i64,
+    pub signature: String,
+    pub body: Vec<u8>,
+}
+
+impl ReceivedWebhook {
+    /// The parsed JSON body
+    pub fn payload(&self) -> Option<WebhookPayload> {
+        serde_json::from_slice(&self.body).ok()
+    }
+
+    /// Whether the delivery was signed with `secret`
+    pub fn verify(&self, secret: &str) -> bool {
+        verify(secret, self.timestamp, &self.body, &self.signature)
+    }
+}
+
+/// Webhook receiver on an ephemeral localhost port, for integration tests.
+///
+/// Records every delivery and answers `200`, unless told to fail with
+/// [`fail_next`](Self::fail_next) to exercise retries. Stops when dropped.
+pub struct TestReceiver {
+    addr: SocketAddr,
+    state: Arc<ReceiverState>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Default)]
+struct ReceiverState {
+    received: Mutex<Vec<ReceivedWebhook>>,
+    requests: AtomicUsize,
+    fail_next: AtomicUsize,
+    arrived: Notify,
+}
+
+impl TestReceiver {
+    /// Start listening on `127.0.0.1` (must be called inside a Tokio runtime).
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(ReceiverState::default());
+        let app = Router::new()
+            .route("/", post(receive))
+            .with_state(state.clone());
+
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// URL to register as the webhook
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// Answer the next `count` requests with `500 Internal Server Error`.
+    pub fn fail_next(&self, count: usize) {
+        self.state.fail_next.store(count, Ordering::SeqCst);
+    }
+
+    /// Requests received so far, failed ones included
+    pub fn requests(&self) -> usize {
+        self.state.requests.load(Ordering::SeqCst)
+    }
+
+    /// Deliveries accepted so far, oldest first
+    pub fn received(&self) -> Vec<ReceivedWebhook> {
+        self.state.received.lock().unwrap().clone()
+    }
+
+    /// Wait until at least `count` deliveries were accepted or `timeout`
+    /// passes, then return them.
+    pub async fn wait_for(&self, count: usize, timeout: Duration) -> Vec<ReceivedWebhook> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let arrived = self.state.arrived.notified();
+            tokio::pin!(arrived);
+            arrived.as_mut().enable();
+
+            let received = self.received();
+            if received.len() >= count || tokio::time::timeout_at(deadline, arrived).await.is_err()
+            {
+                return self.received();
+            }
+        }
+    }
+}
+
+impl Drop for TestReceiver {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn receive(
+    State(state): State<Arc<ReceiverState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    state.requests.fetch_add(1, Ordering::SeqCst);
+    if state
+        .fail_next
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let delivery = ReceivedWebhook {
+        event: header(EVENT_HEADER),
+        delivery_id: header(DELIVERY_HEADER),
+        timestamp: header(TIMESTAMP_HEADER).parse().unwrap_or_default(),
+        signature: header(SIGNATURE_HEADER),
+        body: body.to_vec(),
+    };
+    state.received.lock().unwrap().push(delivery);
+    state.arrived.notify_waiters();
+    StatusCode::OK
+}
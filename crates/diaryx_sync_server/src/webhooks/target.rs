@@ -0,0 +1,151 @@
+//! Keeps webhooks from reaching the server's own network.
+//!
+//! A webhook URL must resolve only to public addresses, both when it is
+//! registered ([`check_url`]) and when each delivery connects
+//! ([`PublicResolver`]), so a host name that later resolves to a private
+//! address (DNS rebinding) is refused too.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// Check that a webhook URL is `http(s)` and that its host resolves only to
+/// public addresses (any address with `allow_private`).
+pub async fn check_url(url: &str, allow_private: bool) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|_| "URL must be http:// or https://")?;
+    let (true, Some(host)) = (
+        matches!(parsed.scheme(), "http" | "https"),
+        parsed.host_str(),
+    ) else {
+        return Err("URL must be http:// or https://".to_string());
+    };
+    if allow_private {
+        return Ok(());
+    }
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| format!("Cannot resolve {}", host))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("Cannot resolve {}", host));
+    }
+    if addrs.iter().any(|addr| !is_public(addr.ip())) {
+        return Err("URL must not point at a private or local address".to_string());
+    }
+    Ok(())
+}
+
+/// Whether an address is reachable on the public internet, i.e. not
+/// loopback, private, link-local, shared, reserved or otherwise special
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_v4(v4);
+            }
+            let segments = ip.segments();
+            // NAT64 (64:ff9b::/96) embeds an IPv4 address
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let o = ip.octets();
+                return is_public_v4(Ipv4Addr::new(o[12], o[13], o[14], o[15]));
+            }
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                // IPv4-compatible (::/96), documentation and discard ranges
+                || segments[..6] == [0; 6]
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+                || segments[..4] == [0x100, 0, 0, 0])
+        }
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Shared address space (100.64.0.0/10)
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments (192.0.0.0/24)
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking (198.18.0.0/15)
+        || (a == 198 && (b == 18 || b == 19))
+        // Reserved (240.0.0.0/4)
+        || a >= 240)
+}
+
+/// DNS resolver for webhook deliveries that drops non-public addresses, so
+/// the connection goes to an address that passed the check
+pub(crate) struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        for private in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!is_public(private.parse().unwrap()), "{}", private);
+        }
+        for public in ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public(public.parse().unwrap()), "{}", public);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_url() {
+        assert!(check_url("https://93.184.216.34/hook", false).await.is_ok());
+        assert!(
+            check_url("http://127.0.0.1:8080/hook", false)
+                .await
+                .is_err()
+        );
+        assert!(check_url("http://[::1]/hook", false).await.is_err());
+        assert!(check_url("http://localhost/hook", false).await.is_err());
+        assert!(check_url("http://169.254.169.254/", false).await.is_err());
+        assert!(check_url("ftp://example.com/", false).await.is_err());
+        assert!(check_url("http://127.0.0.1:8080/hook", true).await.is_ok());
+    }
+}
@@ -59,6 +59,11 @@
 //! metaWs.onopen = () => client.markMetadataConnected();
 //! metaWs.onclose = () => client.markMetadataDisconnected();
 //! metaWs.onmessage = async (e) => {
+//!   if (typeof e.data === 'string') {
+//!     const error = client.parseSyncError(e.data);
+//!     if (error) showSyncError(error);
+//!     return;
+//!   }
 //!   const response = await client.injectMetadataMessage(new Uint8Array(e.data));
 //!   if (response) metaWs.send(response);
 //! };
@@ -70,17 +75,39 @@
 //!   let msg;
 //!   while ((msg = client.pollMetadataOutgoing())) metaWs.send(msg);
 //!   while ((msg = client.pollBodyOutgoing())) bodyWs.send(msg);
+//!   while ((msg = client.pollMetadataOutgoingText())) metaWs.send(msg);
+//!   while ((msg = client.pollBodyOutgoingText())) bodyWs.send(msg);
 //! }, 50);
 //!
 //! // Start sync (sends initial SyncStep1 messages)
 //! await client.start();
+//!
+//! // Share presence and track collaborators
+//! client.setPresence({ name: "Ada", file: "notes.md", cursor: { anchor: 4, head: 4 } });
+//! bodyWs.onmessage = async (e) => {
+//!   if (typeof e.data === 'string') {
+//!     const error = client.parseSyncError(e.data); // rate limit or size limit
+//!     if (error) return showSyncError(error);
+//!     if (client.injectControlMessage(e.data)) renderPeers(client.getPeers());
+//!     return;
+//!   }
+//!   // ...binary handling as above
+//! };
+//! setInterval(() => client.renewPresence(), 15000);
+//!
+//! // Collect errors the server holds for refused updates (on both sockets)
+//! setInterval(() => client.checkErrors(), 5000);
 //! ```
 
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
 
-use diaryx_core::crdt::{RustSyncManager, SyncClientConfig, SyncConfig, SyncTransport};
+use diaryx_core::crdt::{
+    Awareness, PeerPresence, RustSyncManager, SyncClientConfig, SyncConfig, SyncTransport,
+    decode_awareness_control, decode_sync_error_control, encode_awareness_control,
+    encode_error_check_control, format_workspace_doc_id,
+};
 use diaryx_core::fs::{CrdtFs, EventEmittingFs};
 use js_sys::Promise;
 use wasm_bindgen::prelude::*;
@@ -100,6 +127,7 @@ use crate::callback_transport::CallbackTransport;
 /// - Injecting incoming messages
 /// - Polling for outgoing messages
 /// - Starting and stopping sync
+/// - Sharing presence and tracking collaborators
 #[wasm_bindgen]
 pub struct WasmSyncClient {
     /// Transport for metadata (workspace) sync.
@@ -116,6 +144,9 @@ pub struct WasmSyncClient {
 
     /// Reference to the sync manager for creating sync messages.
     sync_manager: Arc<RustSyncManager<EventEmittingFs<CrdtFs<StorageBackend>>>>,
+
+    /// Local presence and known remote peers.
+    awareness: Rc<RefCell<Awareness>>,
 }
 
 impl WasmSyncClient {
@@ -135,8 +166,21 @@ impl WasmSyncClient {
             config,
             started: RefCell::new(false),
             sync_manager,
+            awareness: Rc::new(RefCell::new(Awareness::new())),
         }
     }
+
+    /// Queue an awareness update as a text message on the body connection.
+    fn queue_awareness(
+        transport: &RefCell<CallbackTransport>,
+        workspace_id: &str,
+        update: &diaryx_core::crdt::AwarenessUpdate,
+    ) {
+        let doc_id = format_workspace_doc_id(workspace_id);
+        transport
+            .borrow()
+            .queue_outgoing_text(encode_awareness_control(&doc_id, update));
+    }
 }
 
 #[wasm_bindgen]
@@ -158,7 +202,8 @@ impl WasmSyncClient {
             config.with_auth(token.clone())
         } else {
             config
-        };
+        }
+        .with_client_tag(self.config.client_tag.clone());
         Some(config.build_url())
     }
 
@@ -175,7 +220,8 @@ impl WasmSyncClient {
             config.with_auth(token.clone())
         } else {
             config
-        };
+        }
+        .with_client_tag(self.config.client_tag.clone());
         Some(config.build_url())
     }
 
@@ -355,6 +401,8 @@ impl WasmSyncClient {
         let metadata_transport = Rc::clone(&self.metadata_transport);
         let body_transport = Rc::clone(&self.body_transport);
         let started = self.started.clone();
+        let awareness = Rc::clone(&self.awareness);
+        let workspace_id = self.config.workspace_id.clone();
 
         future_to_promise(async move {
             // Prevent double-start
@@ -408,6 +456,12 @@ impl WasmSyncClient {
                 empty_count
             );
 
+            // Re-announce presence set before (re)connecting
+            let renewal = awareness.borrow_mut().renew_local_state();
+            if let Some(update) = renewal {
+                Self::queue_awareness(&body_transport, &workspace_id, &update);
+            }
+
             log::info!("[WasmSyncClient] Sync session started");
             Ok(JsValue::UNDEFINED)
         })
@@ -422,6 +476,7 @@ impl WasmSyncClient {
 
         self.metadata_transport.borrow().clear_outgoing();
         self.body_transport.borrow().clear_outgoing();
+        self.awareness.borrow_mut().clear_peers();
         *self.started.borrow_mut() = false;
 
         log::info!("[WasmSyncClient] Sync session stopped");
@@ -549,6 +604,99 @@ impl WasmSyncClient {
         }
     }
 
+    // =========================================================================
+    // Presence API - Y.js awareness for live collaboration
+    // =========================================================================
+
+    /// Share this client's presence with collaborators.
+    ///
+    /// Accepts `{ name?, color?, file?, cursor?: { anchor, head } }`, or
+    /// null to appear offline. The update is queued as a body text message.
+    #[wasm_bindgen(js_name = "setPresence")]
+    pub fn set_presence(&self, presence: JsValue) -> std::result::Result<(), JsValue> {
+        let presence: Option<PeerPresence> = if presence.is_null() || presence.is_undefined() {
+            None
+        } else {
+            Some(serde_wasm_bindgen::from_value(presence)?)
+        };
+
+        let update = self.awareness.borrow_mut().set_local_state(presence);
+        Self::queue_awareness(&self.body_transport, &self.config.workspace_id, &update);
+        log::debug!("[WasmSyncClient] Queued presence update");
+        Ok(())
+    }
+
+    /// Renew the local presence and drop peers that stopped renewing theirs.
+    ///
+    /// Call this every 15 seconds. Returns true if peers timed out, in which
+    /// case `getPeers()` has changed.
+    #[wasm_bindgen(js_name = "renewPresence")]
+    pub fn renew_presence(&self) -> bool {
+        let mut awareness = self.awareness.borrow_mut();
+        let removed = awareness.remove_outdated(chrono::Utc::now().timestamp_millis());
+        if let Some(update) = awareness.renew_local_state() {
+            Self::queue_awareness(&self.body_transport, &self.config.workspace_id, &update);
+        }
+        removed
+    }
+
+    /// Inject an incoming body text message.
+    ///
+    /// Call this when the body WebSocket receives a text frame. Returns true
+    /// if it was an awareness update that changed `getPeers()`.
+    #[wasm_bindgen(js_name = "injectControlMessage")]
+    pub fn inject_control_message(&self, text: &str) -> bool {
+        let Some((_, update)) = decode_awareness_control(text) else {
+            return false;
+        };
+        self.awareness
+            .borrow_mut()
+            .apply_update(&update, chrono::Utc::now().timestamp_millis())
+    }
+
+    /// Parse a `sync_error` control message addressed to this client.
+    ///
+    /// Returns `{ code, message, doc_id, retry_after_ms }` when the server
+    /// refused one of this client's messages (`code` is `"rate_limited"` or
+    /// `"message_too_large"`), or null for any other text message.
+    #[wasm_bindgen(js_name = "parseSyncError")]
+    pub fn parse_sync_error(&self, text: &str) -> std::result::Result<JsValue, JsValue> {
+        match decode_sync_error_control(text) {
+            Some(error) if error.is_for(&self.config.client_tag) => {
+                serde_wasm_bindgen::to_value(&error).map_err(|e| {
+                    JsValue::from_str(&format!("Failed to serialize sync error: {}", e))
+                })
+            }
+            _ => Ok(JsValue::NULL),
+        }
+    }
+
+    /// Ask the server for errors it is holding for either connection.
+    ///
+    /// Call this every 5 seconds. The check is queued as a text message on
+    /// each connected transport; held errors come back as `sync_error`
+    /// messages for `parseSyncError()`.
+    #[wasm_bindgen(js_name = "checkErrors")]
+    pub fn check_errors(&self) {
+        let text = encode_error_check_control(&format_workspace_doc_id(&self.config.workspace_id));
+        for transport in [&self.metadata_transport, &self.body_transport] {
+            let transport = transport.borrow();
+            if transport.is_connected() {
+                transport.queue_outgoing_text(text.clone());
+            }
+        }
+    }
+
+    /// Get the collaborators that are currently online.
+    ///
+    /// Returns an array of `{ clientId, presence }` objects.
+    #[wasm_bindgen(js_name = "getPeers")]
+    pub fn get_peers(&self) -> std::result::Result<JsValue, JsValue> {
+        let peers = self.awareness.borrow().peers();
+        serde_wasm_bindgen::to_value(&peers)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize peers: {}", e)))
+    }
+
     // =========================================================================
     // Focus API - Focus-based sync subscription
     // =========================================================================
@@ -619,6 +767,14 @@ impl WasmSyncClient {
         self.body_transport.borrow().poll_outgoing_text()
     }
 
+    /// Poll for an outgoing metadata text message (for error checks).
+    ///
+    /// Returns a string if there's a text message to send, null otherwise.
+    #[wasm_bindgen(js_name = "pollMetadataOutgoingText")]
+    pub fn poll_metadata_outgoing_text(&self) -> Option<String> {
+        self.metadata_transport.borrow().poll_outgoing_text()
+    }
+
     /// Check if there are pending body outgoing text messages.
     #[wasm_bindgen(js_name = "hasBodyOutgoingText")]
     pub fn has_body_outgoing_text(&self) -> bool {